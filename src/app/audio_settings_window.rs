@@ -0,0 +1,118 @@
+//! Audio settings window.
+//!
+//! Chooses the sample rate and buffer size of the output stream. The
+//! options the device supports are queried when the window opens, since
+//! asking the audio host is too slow to do every frame. A new choice is
+//! reported back to the app, which reconfigures the engine.
+
+use eframe::egui::{self, RichText};
+
+use crate::engine::{AudioEngine, StreamSettings, COMMON_BUFFER_SIZES};
+use super::theme;
+
+/// State of the audio settings window.
+#[derive(Default)]
+pub struct AudioSettingsWindow {
+    /// Whether the window is shown.
+    pub open: bool,
+    /// Sample rates the output device supports.
+    sample_rates: Vec<u32>,
+    /// Buffer sizes the output device supports.
+    buffer_sizes: Vec<u32>,
+}
+
+impl AudioSettingsWindow {
+    /// Create a closed window.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Query the options the output device supports.
+    pub fn refresh(&mut self, engine: &AudioEngine) {
+        self.sample_rates = engine.supported_sample_rates();
+        self.buffer_sizes = match engine.buffer_size_range() {
+            Some((min, max)) => COMMON_BUFFER_SIZES
+                .iter()
+                .copied()
+                .filter(|size| (min..=max).contains(size))
+                .collect(),
+            None => COMMON_BUFFER_SIZES.to_vec(),
+        };
+    }
+
+    /// Draw the window (if open) and return new settings if one was chosen.
+    pub fn show(&mut self, ctx: &egui::Context, engine: &AudioEngine) -> Option<StreamSettings> {
+        if !self.open {
+            return None;
+        }
+
+        let current = engine.settings();
+        let sample_rate = engine.sample_rate();
+        let mut settings = current;
+
+        let mut open = true;
+        egui::Window::new("Audio Settings")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(280.0)
+            .show(ctx, |ui| {
+                ui.label(RichText::new(engine.current_device_name()).strong());
+                ui.add_space(4.0);
+
+                egui::Grid::new("audio_settings_grid")
+                    .num_columns(2)
+                    .spacing([12.0, 6.0])
+                    .show(ui, |ui| {
+                        ui.label("Sample rate");
+                        let selected = match current.sample_rate {
+                            Some(rate) => format!("{} Hz", rate),
+                            None => format!("Default ({} Hz)", sample_rate),
+                        };
+                        egui::ComboBox::from_id_salt("sample_rate_selector")
+                            .selected_text(selected)
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut settings.sample_rate, None, "Device default");
+                                for &rate in &self.sample_rates {
+                                    ui.selectable_value(&mut settings.sample_rate, Some(rate), format!("{} Hz", rate));
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.label("Buffer size");
+                        let selected = match current.buffer_size {
+                            Some(frames) => buffer_label(frames, sample_rate),
+                            None => "Device default".to_string(),
+                        };
+                        egui::ComboBox::from_id_salt("buffer_size_selector")
+                            .selected_text(selected)
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut settings.buffer_size, None, "Device default");
+                                for &frames in &self.buffer_sizes {
+                                    ui.selectable_value(
+                                        &mut settings.buffer_size,
+                                        Some(frames),
+                                        buffer_label(frames, sample_rate),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+                    });
+
+                ui.add_space(4.0);
+                ui.label(
+                    RichText::new("Smaller buffers lower the latency but need more CPU headroom. Changing a setting stops the recording and briefly interrupts the audio.")
+                        .color(theme::text::SECONDARY)
+                        .small(),
+                );
+            });
+        self.open = open;
+
+        (settings != current).then_some(settings)
+    }
+}
+
+/// A buffer size with the latency it adds, e.g. "256 (5.3 ms)".
+fn buffer_label(frames: u32, sample_rate: u32) -> String {
+    format!("{} ({:.1} ms)", frames, frames as f32 * 1000.0 / sample_rate.max(1) as f32)
+}
@@ -0,0 +1,301 @@
+//! Patch library browser panel.
+//!
+//! A side panel listing the patches in the library directory, with search,
+//! tag filters, favorites and graph thumbnails. Clicking a patch previews it;
+//! what to load is reported back to the app as [`LibraryActions`].
+
+use std::path::{Path, PathBuf};
+
+use eframe::egui::{self, RichText, Sense};
+
+use crate::persistence::{paths, LibraryEntry, PatchLibrary};
+use crate::widgets::patch_thumbnail;
+use super::theme;
+
+/// Size of the graph thumbnail shown for each patch.
+const THUMBNAIL_SIZE: egui::Vec2 = egui::vec2(64.0, 40.0);
+
+/// Number of tags offered as quick filters.
+const MAX_TAG_FILTERS: usize = 12;
+
+/// Actions requested from the library browser, applied by the app.
+#[derive(Default)]
+pub struct LibraryActions {
+    /// Preview this patch, keeping the current patch to return to.
+    pub preview: Option<PathBuf>,
+    /// Open this patch, ending any preview.
+    pub open: Option<PathBuf>,
+    /// Keep the previewed patch as the current patch.
+    pub keep_preview: bool,
+    /// Return to the patch that was open before previewing.
+    pub end_preview: bool,
+}
+
+/// What the app is currently previewing, for display in the browser.
+pub struct PreviewStatus<'a> {
+    /// Previewed patch file.
+    pub path: &'a Path,
+    /// Name of the patch that will be restored.
+    pub previous_name: &'a str,
+}
+
+/// State of the library browser panel.
+#[derive(Default)]
+pub struct LibraryBrowser {
+    /// Whether the panel is shown.
+    pub open: bool,
+    /// The library, opened and indexed the first time it is needed.
+    library: Option<PatchLibrary>,
+    /// Search query.
+    query: String,
+    /// Only list favorites.
+    favorites_only: bool,
+    /// Error from the last library operation.
+    error: Option<String>,
+}
+
+impl LibraryBrowser {
+    /// Create a closed browser. The library is indexed when first shown.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The library, opening and indexing it on first use.
+    fn library(&mut self) -> &mut PatchLibrary {
+        self.library
+            .get_or_insert_with(|| PatchLibrary::open(paths::library_settings_file(), paths::library_dir()))
+    }
+
+    /// The library patch after `current`, for stepping through patches from a controller.
+    ///
+    /// Waits for indexing if it is still running.
+    pub fn next_patch(&mut self, current: Option<&Path>) -> Option<PathBuf> {
+        let library = self.library();
+        library.finish_scan();
+        library.next_after(current).map(|entry| entry.path.clone())
+    }
+
+    /// Re-index the library if a patch was saved inside it.
+    pub fn notify_saved(&mut self, path: &Path) {
+        if let Some(library) = self.library.as_mut() {
+            if library.contains(path) {
+                library.rescan();
+            }
+        }
+    }
+
+    /// Draw the panel (if open) and return the requested actions.
+    pub fn show(&mut self, ctx: &egui::Context, preview: Option<PreviewStatus<'_>>) -> LibraryActions {
+        let mut actions = LibraryActions::default();
+        if !self.open {
+            return actions;
+        }
+
+        // Pick up the index once the background scan is done
+        let library = self.library();
+        library.update();
+        if library.is_scanning() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+
+        egui::SidePanel::left("library_browser")
+            .resizable(true)
+            .default_width(300.0)
+            .frame(egui::Frame::none()
+                .fill(theme::background::PANEL)
+                .inner_margin(egui::Margin::same(8.0)))
+            .show(ctx, |ui| {
+                self.draw_header(ui);
+                if let Some(preview) = preview.as_ref() {
+                    draw_preview_bar(ui, preview, &mut actions);
+                }
+                self.draw_filters(ui);
+                ui.separator();
+                self.draw_entries(ui, preview.as_ref().map(|p| p.path), &mut actions);
+            });
+
+        actions
+    }
+
+    fn draw_header(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(RichText::new("Patch Library").color(theme::text::PRIMARY).strong());
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.small_button("🔄").on_hover_text("Rescan library").clicked() {
+                    self.library().rescan();
+                }
+                if ui.small_button("📁").on_hover_text("Choose library folder").clicked() {
+                    let start = self.library().root().to_path_buf();
+                    if let Some(dir) = rfd::FileDialog::new().set_directory(start).pick_folder() {
+                        self.error = self.library().set_root(dir).err().map(|e| e.to_string());
+                    }
+                }
+            });
+        });
+
+        let root = self.library().root().display().to_string();
+        ui.label(RichText::new(root).color(theme::text::DISABLED).small());
+
+        if let Some(ref error) = self.error {
+            ui.label(RichText::new(format!("⚠ {}", error)).color(theme::accent::ERROR).small());
+        }
+        ui.add_space(4.0);
+    }
+
+    fn draw_filters(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::TextEdit::singleline(&mut self.query)
+            .hint_text("Search name, tags, modules… (tag:bass)")
+            .desired_width(f32::INFINITY));
+        ui.checkbox(&mut self.favorites_only, "★ Favorites only");
+
+        // Most used tags as one-click filters
+        let mut tags = self.library().tags();
+        tags.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        if tags.is_empty() {
+            return;
+        }
+
+        ui.horizontal_wrapped(|ui| {
+            for (tag, count) in tags.iter().take(MAX_TAG_FILTERS) {
+                let term = format!("tag:{}", tag);
+                let active = self.query.split_whitespace().any(|t| t.eq_ignore_ascii_case(&term));
+                let label = RichText::new(format!("{} ({})", tag, count)).small();
+                if ui.selectable_label(active, label).clicked() {
+                    self.query = if active {
+                        self.query
+                            .split_whitespace()
+                            .filter(|t| !t.eq_ignore_ascii_case(&term))
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    } else {
+                        format!("{} {}", self.query.trim(), term).trim().to_string()
+                    };
+                }
+            }
+        });
+    }
+
+    fn draw_entries(&mut self, ui: &mut egui::Ui, previewing: Option<&Path>, actions: &mut LibraryActions) {
+        let query = self.query.clone();
+        let favorites_only = self.favorites_only;
+        let library = self.library();
+        let results = library.search(&query, favorites_only);
+        let mut toggle_favorite: Option<PathBuf> = None;
+
+        egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+            if results.is_empty() {
+                let message = if library.is_scanning() {
+                    "Indexing patches…"
+                } else if library.entries().is_empty() {
+                    "No patches yet. Save patches into the library folder to see them here."
+                } else {
+                    "No matching patches"
+                };
+                ui.label(RichText::new(message).color(theme::text::DISABLED).italics());
+            }
+
+            for entry in &results {
+                let favorite = library.is_favorite(&entry.path);
+                let selected = previewing == Some(entry.path.as_path());
+                let row = draw_entry(ui, entry, favorite, selected, &mut toggle_favorite);
+
+                if row.double_clicked() {
+                    actions.open = Some(entry.path.clone());
+                } else if row.clicked() && !selected {
+                    actions.preview = Some(entry.path.clone());
+                }
+            }
+
+            ui.add_space(8.0);
+            let mut summary = format!("{} of {} patches", results.len(), library.entries().len());
+            if !library.skipped().is_empty() {
+                summary.push_str(&format!(", {} unreadable", library.skipped().len()));
+            }
+            if library.is_scanning() {
+                summary.push_str(", indexing…");
+            }
+            ui.label(RichText::new(summary).color(theme::text::DISABLED).small());
+        });
+
+        if let Some(path) = toggle_favorite {
+            self.error = self.library().toggle_favorite(&path).err().map(|e| e.to_string());
+        }
+    }
+}
+
+/// Draw the "previewing" banner with Keep and Back buttons.
+fn draw_preview_bar(ui: &mut egui::Ui, preview: &PreviewStatus<'_>, actions: &mut LibraryActions) {
+    egui::Frame::none()
+        .fill(theme::background::WIDGET)
+        .rounding(4.0)
+        .inner_margin(egui::Margin::same(6.0))
+        .show(ui, |ui| {
+            let name = preview.path.file_stem().and_then(|s| s.to_str()).unwrap_or("patch");
+            ui.label(RichText::new(format!("Previewing {}", name)).color(theme::accent::WARNING));
+            ui.horizontal(|ui| {
+                if ui.button("✔ Keep").on_hover_text("Keep this patch open").clicked() {
+                    actions.keep_preview = true;
+                }
+                if ui.button(format!("↩ Back to {}", preview.previous_name)).clicked() {
+                    actions.end_preview = true;
+                }
+            });
+        });
+    ui.add_space(4.0);
+}
+
+/// Draw one library entry and return its click response.
+fn draw_entry(
+    ui: &mut egui::Ui,
+    entry: &LibraryEntry,
+    favorite: bool,
+    selected: bool,
+    toggle_favorite: &mut Option<PathBuf>,
+) -> egui::Response {
+    let response = ui.horizontal(|ui| {
+        let star = if favorite {
+            RichText::new("★").color(theme::accent::WARNING)
+        } else {
+            RichText::new("☆").color(theme::text::DISABLED)
+        };
+        if ui.add(egui::Button::new(star).frame(false)).on_hover_text("Favorite").clicked() {
+            *toggle_favorite = Some(entry.path.clone());
+        }
+
+        // Everything right of the star is one clickable area
+        let body = ui.horizontal(|ui| {
+            patch_thumbnail(ui, entry.metadata.thumbnail.as_ref(), THUMBNAIL_SIZE);
+
+            ui.vertical(|ui| {
+                let name_color = if selected { theme::text::ACCENT } else { theme::text::PRIMARY };
+                ui.label(RichText::new(&entry.name).color(name_color).strong());
+
+                let mut details = format!("{} module{}", entry.node_count, if entry.node_count == 1 { "" } else { "s" });
+                if !entry.metadata.author.is_empty() {
+                    details = format!("{} • {}", entry.metadata.author, details);
+                }
+                if let Some(tempo) = entry.metadata.tempo {
+                    details.push_str(&format!(" • {:.0} BPM", tempo));
+                }
+                ui.label(RichText::new(details).color(theme::text::SECONDARY).small());
+
+                if !entry.metadata.tags.is_empty() {
+                    ui.label(RichText::new(entry.metadata.tags_string()).color(theme::text::ACCENT).small());
+                }
+            });
+        }).response;
+
+        ui.interact(body.rect, ui.id().with(&entry.path), Sense::click())
+    }).inner;
+
+    if selected || response.hovered() {
+        ui.painter().rect_stroke(response.rect.expand(2.0), 3.0, (1.0, theme::text::DISABLED));
+    }
+
+    let hover = if entry.metadata.description.is_empty() {
+        entry.path.display().to_string()
+    } else {
+        format!("{}\n\n{}", entry.metadata.description, entry.path.display())
+    };
+    response.on_hover_text(format!("{}\nClick to preview, double-click to open", hover))
+}
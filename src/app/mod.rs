@@ -2,6 +2,12 @@
 //!
 //! Contains the main egui application, theme definitions, and UI state management.
 
+pub mod audio_settings_window;
+pub mod library_browser;
+pub mod randomizer_window;
+pub mod routing_window;
+pub mod scenes_window;
+pub mod setlist_window;
 pub mod synth_app;
 pub mod theme;
 
@@ -0,0 +1,143 @@
+//! Randomizer window.
+//!
+//! Randomizes or mutates the parameters of the whole patch or of the
+//! selected nodes, with a seed so results can be reproduced. What to do is
+//! reported back to the app as [`RandomizeActions`].
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use eframe::egui::{self, RichText};
+
+use crate::persistence::Variation;
+use super::theme;
+
+/// Actions requested from the randomizer window, applied by the app.
+#[derive(Default)]
+pub struct RandomizeActions {
+    /// Vary parameters this way.
+    pub vary: Option<Variation>,
+    /// Undo the last variation.
+    pub undo: bool,
+    /// Unlock every parameter.
+    pub clear_locks: bool,
+}
+
+/// State of the randomizer window.
+pub struct RandomizerWindow {
+    /// Whether the window is shown.
+    pub open: bool,
+    /// Only vary the selected nodes.
+    pub selected_only: bool,
+    /// How far Mutate moves each value, as a fraction of its range.
+    pub amount: f32,
+    /// Seed for the next variation; advanced after each one.
+    pub seed: u64,
+    /// Also store each result as a new scene.
+    pub store_as_scene: bool,
+}
+
+impl Default for RandomizerWindow {
+    fn default() -> Self {
+        Self {
+            open: false,
+            selected_only: false,
+            amount: 0.1,
+            seed: 1,
+            store_as_scene: false,
+        }
+    }
+}
+
+impl RandomizerWindow {
+    /// Create a closed window.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draw the window (if open) and return the requested actions.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        has_selection: bool,
+        undo_steps: usize,
+        lock_count: usize,
+    ) -> RandomizeActions {
+        let mut actions = RandomizeActions::default();
+        if !self.open {
+            return actions;
+        }
+
+        // Fall back to the whole patch when the selection goes away
+        if !has_selection {
+            self.selected_only = false;
+        }
+
+        let mut open = true;
+        egui::Window::new("Randomize")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut self.selected_only, false, "Whole patch");
+                    ui.add_enabled_ui(has_selection, |ui| {
+                        ui.radio_value(&mut self.selected_only, true, "Selected nodes")
+                            .on_disabled_hover_text("Select nodes to vary them alone");
+                    });
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Seed");
+                    ui.add(egui::DragValue::new(&mut self.seed));
+                    if ui.small_button("🎲").on_hover_text("Pick a new seed").clicked() {
+                        self.seed = time_seed();
+                    }
+                });
+                ui.add_space(4.0);
+
+                ui.horizontal(|ui| {
+                    if ui.button("🎲 Randomize").on_hover_text("Pick every value anywhere in its range").clicked() {
+                        actions.vary = Some(Variation::Randomize);
+                    }
+                    if ui.button("Mutate").on_hover_text("Nudge every value by up to the amount").clicked() {
+                        actions.vary = Some(Variation::Mutate { amount: self.amount });
+                    }
+                    let mut percent = self.amount * 100.0;
+                    if ui.add(egui::Slider::new(&mut percent, 1.0..=100.0).suffix("%")).changed() {
+                        self.amount = percent / 100.0;
+                    }
+                });
+
+                ui.checkbox(&mut self.store_as_scene, "Store each result as a new scene");
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(undo_steps > 0, egui::Button::new("↶ Undo"))
+                        .on_hover_text(format!("{} step{} to undo", undo_steps, if undo_steps == 1 { "" } else { "s" }))
+                        .clicked()
+                    {
+                        actions.undo = true;
+                    }
+
+                    let locks = format!("{} locked", lock_count);
+                    ui.label(RichText::new(locks).color(theme::text::SECONDARY))
+                        .on_hover_text("Right-click a knob to lock it");
+                    if ui.add_enabled(lock_count > 0, egui::Button::new("Unlock all")).clicked() {
+                        actions.clear_locks = true;
+                    }
+                });
+            });
+
+        self.open = open;
+        actions
+    }
+}
+
+/// A seed taken from the clock.
+fn time_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64 % 100_000)
+        .unwrap_or(1)
+}
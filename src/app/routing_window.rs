@@ -0,0 +1,87 @@
+//! Output routing window.
+//!
+//! A matrix of checkboxes sending the stereo master and the Multi Output
+//! channels to the channels of the audio device. Changes are reported back
+//! to the app, which passes them on to the audio engine.
+
+use eframe::egui::{self, RichText};
+
+use crate::engine::{OutputRouting, MAX_OUTPUT_CHANNELS, ROUTING_SOURCES};
+use super::theme;
+
+/// State of the output routing window.
+#[derive(Default)]
+pub struct RoutingWindow {
+    /// Whether the window is shown.
+    pub open: bool,
+    /// The routing currently in effect.
+    pub routing: OutputRouting,
+}
+
+impl RoutingWindow {
+    /// Create a closed window with the default routing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draw the window (if open) and return the new routing if it changed.
+    ///
+    /// `device_channels` is the channel count of the output device; only
+    /// that many columns are shown.
+    pub fn show(&mut self, ctx: &egui::Context, device_channels: usize) -> Option<OutputRouting> {
+        if !self.open {
+            return None;
+        }
+
+        let channels = device_channels.clamp(1, MAX_OUTPUT_CHANNELS);
+        let mut routing = self.routing;
+
+        let mut open = true;
+        egui::Window::new("Output Routing")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("output_routing_grid")
+                    .spacing([6.0, 4.0])
+                    .show(ui, |ui| {
+                        ui.label("");
+                        for channel in 0..channels {
+                            ui.label(RichText::new(format!("{}", channel + 1)).color(theme::text::SECONDARY));
+                        }
+                        ui.end_row();
+
+                        for source in 0..ROUTING_SOURCES {
+                            ui.label(OutputRouting::source_name(source));
+                            for channel in 0..channels {
+                                let mut routed = routing.is_routed(source, channel);
+                                if ui.checkbox(&mut routed, "").changed() {
+                                    routing.set(source, channel, routed);
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                ui.add_space(4.0);
+                ui.label(
+                    RichText::new(format!("Columns are the {} channels of the output device", channels))
+                        .color(theme::text::SECONDARY)
+                        .small(),
+                );
+
+                ui.separator();
+                if ui.button("Reset").on_hover_text("Master on 1-2, Multi N on channel N").clicked() {
+                    routing = OutputRouting::default();
+                }
+            });
+        self.open = open;
+
+        if routing != self.routing {
+            self.routing = routing;
+            Some(routing)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Scenes window.
+//!
+//! Lists the patch's parameter scenes with buttons to capture, recall,
+//! update and delete them, and a crossfader that morphs between two scenes.
+//! What to do is reported back to the app as [`SceneActions`].
+
+use eframe::egui::{self, RichText};
+
+use crate::persistence::Scene;
+use crate::widgets::{horizontal_fader, FaderConfig, ParamFormat};
+use super::theme;
+
+/// Actions requested from the scenes window, applied by the app.
+#[derive(Default)]
+pub struct SceneActions {
+    /// Capture a new scene; `true` captures only the selected nodes.
+    pub capture: Option<bool>,
+    /// Recall this scene.
+    pub recall: Option<usize>,
+    /// Overwrite this scene with the current values of its nodes.
+    pub update: Option<usize>,
+    /// Delete this scene.
+    pub delete: Option<usize>,
+    /// Apply the morph at the current crossfader position.
+    pub morph: bool,
+    /// A scene was renamed.
+    pub renamed: bool,
+}
+
+/// State of the scenes window.
+pub struct ScenesWindow {
+    /// Whether the window is shown.
+    pub open: bool,
+    /// Scene at the left end of the crossfader.
+    pub morph_a: usize,
+    /// Scene at the right end of the crossfader.
+    pub morph_b: usize,
+    /// Crossfader position, 0.0 (scene A) to 1.0 (scene B).
+    pub morph_position: f32,
+    /// Whether a Scene Morph module currently drives the crossfader.
+    pub morph_module_active: bool,
+}
+
+impl Default for ScenesWindow {
+    fn default() -> Self {
+        Self {
+            open: false,
+            morph_a: 0,
+            morph_b: 1,
+            morph_position: 0.0,
+            morph_module_active: false,
+        }
+    }
+}
+
+impl ScenesWindow {
+    /// Create a closed window.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The two scenes to morph between, if both exist and differ.
+    pub fn morph_pair(&self, scene_count: usize) -> Option<(usize, usize)> {
+        (self.morph_a < scene_count && self.morph_b < scene_count && self.morph_a != self.morph_b)
+            .then_some((self.morph_a, self.morph_b))
+    }
+
+    /// Keep the crossfader ends valid after a scene is deleted.
+    pub fn scene_removed(&mut self, index: usize) {
+        for end in [&mut self.morph_a, &mut self.morph_b] {
+            if *end > index {
+                *end -= 1;
+            }
+        }
+    }
+
+    /// Draw the window (if open) and return the requested actions.
+    pub fn show(&mut self, ctx: &egui::Context, scenes: &mut [Scene], has_selection: bool) -> SceneActions {
+        let mut actions = SceneActions::default();
+        if !self.open {
+            return actions;
+        }
+
+        let mut open = true;
+        egui::Window::new("Scenes")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(340.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("📷 Capture").on_hover_text("Store all parameter values as a new scene").clicked() {
+                        actions.capture = Some(false);
+                    }
+                    if ui.add_enabled(has_selection, egui::Button::new("📷 Capture Selected"))
+                        .on_hover_text("Store only the selected nodes' parameter values")
+                        .on_disabled_hover_text("Select nodes to capture them alone")
+                        .clicked()
+                    {
+                        actions.capture = Some(true);
+                    }
+                });
+                ui.add_space(4.0);
+
+                if scenes.is_empty() {
+                    ui.label(RichText::new("No scenes yet").color(theme::text::DISABLED).italics());
+                }
+                draw_scene_list(ui, scenes, &mut actions);
+
+                ui.separator();
+                self.draw_morph(ui, scenes, &mut actions);
+
+                ui.add_space(4.0);
+                ui.label(RichText::new("Without a setlist, MIDI program change N recalls scene N (counting from 0)")
+                    .color(theme::text::DISABLED)
+                    .small());
+            });
+
+        self.open = open;
+        actions
+    }
+
+    fn draw_morph(&mut self, ui: &mut egui::Ui, scenes: &[Scene], actions: &mut SceneActions) {
+        ui.label(RichText::new("Morph").color(theme::text::PRIMARY).strong());
+        if scenes.len() < 2 {
+            ui.label(RichText::new("Capture two scenes to morph between them")
+                .color(theme::text::DISABLED)
+                .small());
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            actions.morph |= scene_combo(ui, "morph_a", &mut self.morph_a, scenes);
+            ui.label("→");
+            actions.morph |= scene_combo(ui, "morph_b", &mut self.morph_b, scenes);
+        });
+
+        let config = FaderConfig {
+            range: 0.0..=1.0,
+            default: 0.0,
+            format: ParamFormat::Percent,
+            show_markers: false,
+            ..Default::default()
+        }
+        .with_size(16.0, 300.0);
+
+        let before = self.morph_position;
+        ui.add_enabled_ui(!self.morph_module_active, |ui| {
+            horizontal_fader(ui, &mut self.morph_position, &config)
+                .on_disabled_hover_text("Driven by a Scene Morph module");
+        });
+        actions.morph |= self.morph_position != before;
+    }
+}
+
+/// Draw one row per scene with its name and action buttons.
+fn draw_scene_list(ui: &mut egui::Ui, scenes: &mut [Scene], actions: &mut SceneActions) {
+    egui::Grid::new("scene_list")
+        .num_columns(3)
+        .spacing([6.0, 4.0])
+        .show(ui, |ui| {
+            for (index, scene) in scenes.iter_mut().enumerate() {
+                ui.label(RichText::new(index.to_string()).color(theme::text::SECONDARY).monospace());
+                actions.renamed |= ui.add(egui::TextEdit::singleline(&mut scene.name).desired_width(140.0))
+                    .on_hover_text(format!(
+                        "{} parameter{} on {} node{}",
+                        scene.values.len(),
+                        if scene.values.len() == 1 { "" } else { "s" },
+                        scene.node_count(),
+                        if scene.node_count() == 1 { "" } else { "s" }
+                    ))
+                    .changed();
+
+                ui.horizontal(|ui| {
+                    if ui.button("▶").on_hover_text("Recall").clicked() {
+                        actions.recall = Some(index);
+                    }
+                    if ui.button("⟳").on_hover_text("Update with current values").clicked() {
+                        actions.update = Some(index);
+                    }
+                    if ui.button("🗑").on_hover_text("Delete").clicked() {
+                        actions.delete = Some(index);
+                    }
+                });
+                ui.end_row();
+            }
+        });
+}
+
+/// A combo box choosing a scene by index. Returns true if the choice changed.
+fn scene_combo(ui: &mut egui::Ui, id: &str, selected: &mut usize, scenes: &[Scene]) -> bool {
+    let before = *selected;
+    let text = scenes.get(*selected).map_or("-", |scene| scene.name.as_str());
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(text)
+        .width(120.0)
+        .show_ui(ui, |ui| {
+            for (index, scene) in scenes.iter().enumerate() {
+                ui.selectable_value(selected, index, &scene.name);
+            }
+        });
+    *selected != before
+}
@@ -0,0 +1,185 @@
+//! Setlist window.
+//!
+//! Shows the open setlist with the bank and program number of each entry,
+//! and buttons to add the current patch or scene, reorder, remove and jump
+//! to entries, and to open and save setlist files. What to do is reported
+//! back to the app as [`SetlistActions`].
+
+use std::path::PathBuf;
+
+use eframe::egui::{self, RichText};
+
+use crate::persistence::{program_label, Setlist};
+use super::theme;
+
+/// Actions requested from the setlist window, applied by the app.
+#[derive(Default)]
+pub struct SetlistActions {
+    /// Start an empty setlist.
+    pub new: bool,
+    /// Choose a setlist file to open.
+    pub open: bool,
+    /// Save the setlist; `true` always asks for a file name.
+    pub save: Option<bool>,
+    /// Append the current patch; `true` also recalls the last recalled scene.
+    pub add: Option<bool>,
+    /// Load this entry.
+    pub go: Option<usize>,
+}
+
+/// State of the setlist window, and the setlist itself.
+#[derive(Default)]
+pub struct SetlistWindow {
+    /// Whether the window is shown.
+    pub open: bool,
+    /// The setlist program changes select from.
+    pub setlist: Setlist,
+    /// File the setlist was opened from or saved to.
+    pub path: Option<PathBuf>,
+    /// Entry loaded last.
+    pub current: Option<usize>,
+    /// Whether the setlist changed since it was opened or saved.
+    pub modified: bool,
+}
+
+impl SetlistWindow {
+    /// Create a closed window with an empty setlist.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the setlist, e.g. after opening a file.
+    pub fn set_setlist(&mut self, setlist: Setlist, path: Option<PathBuf>) {
+        self.setlist = setlist;
+        self.path = path;
+        self.current = None;
+        self.modified = false;
+    }
+
+    /// Draw the window (if open) and return the requested actions.
+    ///
+    /// `can_add` and `can_add_scene` tell whether the current patch has a
+    /// file, and whether a scene of it was recalled.
+    pub fn show(&mut self, ctx: &egui::Context, can_add: bool, can_add_scene: bool) -> SetlistActions {
+        let mut actions = SetlistActions::default();
+        if !self.open {
+            return actions;
+        }
+
+        let mut open = true;
+        egui::Window::new("Setlist")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    actions.new = ui.button("New").clicked();
+                    actions.open = ui.button("Open…").clicked();
+                    if ui.button("Save").clicked() {
+                        actions.save = Some(false);
+                    }
+                    if ui.button("Save As…").clicked() {
+                        actions.save = Some(true);
+                    }
+                    if self.modified {
+                        ui.label(RichText::new("●").color(theme::accent::WARNING))
+                            .on_hover_text("Unsaved changes");
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Name");
+                    self.modified |= ui.add(egui::TextEdit::singleline(&mut self.setlist.name).desired_width(220.0))
+                        .changed();
+                });
+                ui.add_space(4.0);
+
+                if self.setlist.entries.is_empty() {
+                    ui.label(RichText::new("No entries yet").color(theme::text::DISABLED).italics());
+                }
+                self.draw_entries(ui, &mut actions);
+
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(can_add, egui::Button::new("➕ Current Patch"))
+                        .on_disabled_hover_text("Save the patch to add it")
+                        .clicked()
+                    {
+                        actions.add = Some(false);
+                    }
+                    if ui.add_enabled(can_add && can_add_scene, egui::Button::new("➕ Current Scene"))
+                        .on_hover_text("Add the patch, recalling the last recalled scene")
+                        .on_disabled_hover_text("Recall a scene of a saved patch to add it")
+                        .clicked()
+                    {
+                        actions.add = Some(true);
+                    }
+                });
+
+                ui.add_space(4.0);
+                let hint = if self.setlist.entries.is_empty() {
+                    "Without a setlist, MIDI program change N recalls scene N"
+                } else {
+                    "MIDI program changes load entries; Bank Select (CC 0/32) picks the bank"
+                };
+                ui.label(RichText::new(hint).color(theme::text::DISABLED).small());
+            });
+
+        self.open = open;
+        actions
+    }
+
+    /// Draw one row per entry with its program number, name and buttons.
+    fn draw_entries(&mut self, ui: &mut egui::Ui, actions: &mut SetlistActions) {
+        let mut move_entry = None;
+        let mut remove = None;
+        let count = self.setlist.entries.len();
+
+        egui::Grid::new("setlist_entries")
+            .num_columns(3)
+            .spacing([6.0, 4.0])
+            .show(ui, |ui| {
+                for (index, entry) in self.setlist.entries.iter().enumerate() {
+                    let color = if self.current == Some(index) { theme::accent::PRIMARY } else { theme::text::SECONDARY };
+                    ui.label(RichText::new(program_label(index)).color(color).monospace())
+                        .on_hover_text("Bank:program");
+                    ui.label(entry.label()).on_hover_text(entry.path.display().to_string());
+
+                    ui.horizontal(|ui| {
+                        if ui.button("▶").on_hover_text("Load").clicked() {
+                            actions.go = Some(index);
+                        }
+                        if ui.add_enabled(index > 0, egui::Button::new("⏶")).on_hover_text("Move up").clicked() {
+                            move_entry = Some((index, true));
+                        }
+                        if ui.add_enabled(index + 1 < count, egui::Button::new("⏷")).on_hover_text("Move down").clicked() {
+                            move_entry = Some((index, false));
+                        }
+                        if ui.button("🗑").on_hover_text("Remove").clicked() {
+                            remove = Some(index);
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+
+        if let Some((index, up)) = move_entry {
+            let target = self.setlist.move_entry(index, up);
+            if self.current == Some(index) {
+                self.current = Some(target);
+            } else if self.current == Some(target) {
+                self.current = Some(index);
+            }
+            self.modified = true;
+        }
+        if let Some(index) = remove {
+            self.setlist.entries.remove(index);
+            self.current = match self.current {
+                Some(current) if current == index => None,
+                Some(current) if current > index => Some(current - 1),
+                current => current,
+            };
+            self.modified = true;
+        }
+    }
+}
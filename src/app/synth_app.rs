@@ -4,28 +4,48 @@
 //! the synthesizer's UI state, audio engine, and graph state.
 
 use std::collections::HashMap;
-use std::path::PathBuf;
-use std::time::Instant;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
 use eframe::egui::{self, RichText, Layout, Align};
 use egui_node_graph2::{GraphEditorState, NodeResponse, NodeTemplateTrait, InputParamKind};
 
+use crate::dsp::{ModuleRegistry, Tuning};
 use crate::engine::{
-    AudioEngine, AudioError, AudioProcessor, DeviceInfo, EngineChannels, EngineCommand, UiHandle,
-    MidiDeviceInfo, MidiEngine, MidiEvent, TimestampedMidiEvent,
+    create_module_registry, AudioEngine, AudioError, AudioProcessor, DeviceInfo, EngineChannels,
+    EngineCommand, UiHandle, MAX_INPUT_CHANNELS, MidiConnectionChange, MidiDeviceInfo, MidiEngine, MidiEvent,
+    KeyboardMapping, MidiClockFollower, MidiFile, MidiInputInfo, MidiOutputEngine, MidiOutputInfo, MidiRecorder,
+    MpeVoiceAllocator, MpeZone, MpeZoneKind, OutputRouting, ScalaError, Scale, SmfError,
+    RecordSource, RecorderEvent, StreamSettings, TimestampedMidiEvent, WavRecorder, DEFAULT_VIRTUAL_OUTPUT_NAME,
+    DEFAULT_VIRTUAL_PORT_NAME,
 };
 use rtrb::Consumer;
 use crate::graph::{
-    validate_connection, AllNodeTemplates, AnyParameterId, SynthDataType, SynthGraphState,
-    SynthNodeData, SynthNodeTemplate, SynthValueType,
+    validate_connection, AllNodeTemplates, AnyParameterId, LoadedMidiFile, PresetMenuEntry, SynthDataType,
+    SynthGraphState, SynthNodeData, SynthNodeTemplate, SynthValueType,
 };
 use crate::modules::keyboard::{key_to_note, relative_to_midi};
 use crate::modules::midi_note::MidiNote;
+use crate::modules::mpe_voice::MpeVoice;
 use crate::persistence::{
-    ConnectionData, MidiMapping, NodeData, ParameterValue, Patch, PatchError,
-    load_from_file, save_to_file,
+    dsl, format_timestamp, lint, paths, unix_now, AppConfig, AutosaveSession, ButtonMode, ConnectionData,
+    ControlMessage, ControllerState, DirtyTracker, FeedbackThrottle, Lint, MappingLearner, MappingOptions, MidiAction, MidiMapping,
+    ModulePreset, NodeData, ParameterLock, ParameterValue, Patch, PatchDiff, PatchError, PatchMetadata,
+    PatchPreloader, PatchThumbnail, PresetStore, ProgramSelect, RecoveryData, Scene, SceneValue, Setlist,
+    SetlistEntry, TuningData, Variation, load_from_file, program_label, save_to_file, AUTOSAVE_INTERVAL, DSL_EXTENSION,
+    PATCH_VERSION,
 };
-use crate::widgets::{cpu_meter, CpuMeterConfig};
+use crate::persistence::randomize::vary;
+use crate::persistence::scene::morph;
+use crate::persistence::WindowState;
+use crate::widgets::{cpu_meter, vu_meter, CpuMeterConfig, VuMeterConfig};
+use super::audio_settings_window::AudioSettingsWindow;
+use super::library_browser::{LibraryBrowser, PreviewStatus};
+use super::randomizer_window::RandomizerWindow;
+use super::routing_window::RoutingWindow;
+use super::scenes_window::ScenesWindow;
+use super::setlist_window::SetlistWindow;
 use super::theme;
 
 /// Type alias for our graph editor state
@@ -34,7 +54,8 @@ type SynthGraphEditorState = GraphEditorState<SynthNodeData, SynthDataType, Synt
 /// Target parameter for MIDI Learn mode.
 ///
 /// When the user activates MIDI Learn on a knob, this stores the target
-/// parameter information until a CC event is received.
+/// parameter information until a controller is moved. Learning an app
+/// action leaves the parameter fields unused.
 #[derive(Debug, Clone)]
 pub struct MidiLearnTarget {
     /// Engine node ID of the target parameter.
@@ -47,6 +68,199 @@ pub struct MidiLearnTarget {
     pub min_value: f32,
     /// Maximum value of the parameter range.
     pub max_value: f32,
+    /// What a note or button does to the parameter.
+    pub button: ButtonMode,
+    /// App action to map instead of the parameter.
+    pub action: Option<MidiAction>,
+}
+
+impl MidiLearnTarget {
+    /// Learn a button for an app action.
+    pub fn action(action: MidiAction) -> Self {
+        Self {
+            node_id: 0,
+            param_index: 0,
+            param_name: action.label().to_string(),
+            min_value: 0.0,
+            max_value: 1.0,
+            button: ButtonMode::Momentary,
+            action: Some(action),
+        }
+    }
+}
+
+/// Channel and input port filter of a MIDI Note module, as (channel, port).
+///
+/// Both use 0 for "any", like the module's Channel parameter. The port is
+/// looked up from the module's input device each frame, since port numbers
+/// move as inputs are added and removed (see `SynthApp::midi_input_port`).
+type MidiNoteFilter = (u8, u8);
+
+/// Zone and input port filter of an MPE Voice module (port 0 = any).
+/// Modules with the same filter share one set of voices.
+type MpeFilter = (MpeZone, u8);
+
+/// Port filter of a module whose input device isn't connected. Inputs are
+/// numbered from 1 in connection order, so no event arrives on it in practice.
+const DISCONNECTED_PORT: u8 = u8::MAX;
+
+/// Whether an event on `channel` (0-15) from input `port` passes a filter.
+fn filter_accepts(filter: MidiNoteFilter, channel: u8, port: u8) -> bool {
+    (filter.0 == 0 || filter.0 == channel + 1) && (filter.1 == 0 || filter.1 == port)
+}
+
+/// Note and gate state shared by the MIDI Note modules with the same filter.
+#[derive(Debug, Clone, Copy)]
+struct MidiVoice {
+    /// When the gate was last triggered (for minimum gate duration).
+    gate_on: Option<Instant>,
+    /// Whether the gate is being held high (for minimum duration).
+    gate_held_high: bool,
+    /// The last note triggered (to maintain pitch during gate hold).
+    note: u8,
+    /// The last velocity triggered.
+    velocity: u8,
+    /// Current aftertouch value (channel pressure).
+    aftertouch: u8,
+}
+
+impl Default for MidiVoice {
+    fn default() -> Self {
+        Self {
+            gate_on: None,
+            gate_held_high: false,
+            note: 60,
+            velocity: 100,
+            aftertouch: 0,
+        }
+    }
+}
+
+/// A patch file being watched for hot-reload.
+#[derive(Debug, Clone)]
+struct WatchedFile {
+    /// Path of the watched patch file.
+    path: PathBuf,
+    /// Modification time of the last version applied (None = not yet applied).
+    modified: Option<SystemTime>,
+    /// When the file was last polled.
+    last_check: Instant,
+}
+
+/// A node's settings waiting for a name before being saved as a preset.
+struct PresetSaveDialog {
+    /// Graph node whose settings are saved.
+    node_id: egui_node_graph2::NodeId,
+    /// Preset name being typed.
+    name: String,
+}
+
+/// State of the toolbar recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecordState {
+    /// Not recording.
+    Idle,
+    /// Recording starts when playback starts.
+    Armed,
+    /// Recording.
+    Recording,
+}
+
+/// Time stamp for recording file names, e.g. "2024-03-09_14-05".
+fn file_timestamp() -> String {
+    format_timestamp(unix_now()).replace(' ', "_").replace(':', "-")
+}
+
+/// Format a recording length as minutes, seconds and tenths, e.g. "1:05.3".
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs_f64();
+    format!("{}:{:04.1}", (secs / 60.0) as u64, secs % 60.0)
+}
+
+/// A library patch loaded for preview, with the patch it temporarily replaced.
+struct PatchPreview {
+    /// The previewed patch file.
+    path: PathBuf,
+    /// Name of the patch that was open before previewing.
+    previous_name: String,
+    /// The patch that was open before previewing, set aside untouched.
+    previous: OpenPatch,
+}
+
+/// The editor state of an open patch, moved aside while another patch is
+/// previewed or preloaded. Its engine modules are set aside along with it.
+struct OpenPatch {
+    graph_state: SynthGraphEditorState,
+    user_state: SynthGraphState,
+    cached_params: HashMap<(u64, usize), f32>,
+    path: Option<PathBuf>,
+    dirty: DirtyTracker,
+    watched_file: Option<WatchedFile>,
+    midi_mappings: Vec<MidiMapping>,
+    controller_states: HashMap<(u64, usize), ControllerState>,
+    scenes: Vec<Scene>,
+    recalled_scene: Option<usize>,
+    variation_undo: Vec<Vec<SceneValue>>,
+    patch_node_ids: HashMap<u64, egui_node_graph2::NodeId>,
+    patch_metadata: PatchMetadata,
+    patch_info_tags: String,
+    tuning: Option<TuningData>,
+}
+
+/// A setlist patch built ahead of time, in the editor state set aside here
+/// and the engine's scratch graph.
+struct PreloadedPatch {
+    /// The patch file.
+    path: PathBuf,
+    /// Name of the patch.
+    name: String,
+    /// The built patch, ready to switch to.
+    patch: OpenPatch,
+}
+
+/// Module ID of the Scene Morph module, whose output drives the scene crossfader.
+const SCENE_MORPH_MODULE: &str = "util.scene_morph";
+
+/// Reselect the output device and stream format of the last session.
+///
+/// Failures are reported and leave the device defaults in place.
+fn restore_audio_output(engine: &mut AudioEngine, config: &AppConfig) {
+    if let Some(name) = &config.audio_output {
+        let device = engine.enumerate_devices().into_iter().find(|d| &d.name == name);
+        if let Some(device) = device {
+            if let Err(e) = engine.select_device(device.index) {
+                eprintln!("Could not reopen audio output {}: {}", name, e);
+            }
+        }
+    }
+
+    let settings = StreamSettings {
+        sample_rate: config.sample_rate,
+        buffer_size: config.buffer_size,
+    };
+    if settings != engine.settings() {
+        if let Err(e) = engine.set_stream_settings(settings) {
+            eprintln!("Could not restore audio settings: {}", e);
+        }
+    }
+}
+
+/// Whether a path names a patch script rather than a JSON patch.
+fn is_patch_script(path: &std::path::Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some(DSL_EXTENSION)
+}
+
+/// For every registered module, which of its parameters are discrete.
+fn discrete_parameters(registry: &ModuleRegistry) -> HashMap<&'static str, Vec<bool>> {
+    registry
+        .list_modules()
+        .iter()
+        .filter_map(|info| {
+            let module = registry.create(info.id)?;
+            let flags = module.parameters().iter().map(|p| p.display.is_discrete()).collect();
+            Some((info.id, flags))
+        })
+        .collect()
 }
 
 /// Main application state for the Modular Synth
@@ -78,6 +292,18 @@ pub struct SynthApp {
     /// Index of currently selected device
     selected_device_index: usize,
 
+    /// Cached list of audio input devices
+    input_devices: Vec<DeviceInfo>,
+
+    /// Index of the selected audio input device (None = no input)
+    selected_input_device: Option<usize>,
+
+    /// Metered audio input peak levels, one per channel
+    input_levels: [f32; MAX_INPUT_CHANNELS],
+
+    /// Number of channels of the metered audio input
+    input_level_channels: usize,
+
     /// Cached parameter values for change detection.
     /// Key is (node_id as u64, param_index), value is the last sent value.
     cached_params: HashMap<(u64, usize), f32>,
@@ -113,31 +339,37 @@ pub struct SynthApp {
     /// Cached list of MIDI input devices.
     midi_devices: Vec<MidiDeviceInfo>,
 
-    /// Index of currently selected MIDI device (None = no device).
-    selected_midi_device: Option<usize>,
+    /// Chosen MIDI inputs in port order, with their connection state.
+    midi_inputs: Vec<MidiInputInfo>,
+
+    /// When the MIDI inputs were last checked for unplugged devices.
+    last_midi_check: Instant,
 
     /// MIDI error message to display.
     midi_error_message: Option<String>,
 
-    // --- MIDI Note module state ---
-    /// Currently held MIDI notes for MIDI Note modules.
-    /// Stores (note_number, velocity, channel) in order of press for voice priority.
-    midi_held_notes: Vec<(u8, u8, u8)>,
+    /// MIDI output engine for sending MIDI to external gear.
+    midi_output: Option<MidiOutputEngine>,
+
+    /// Cached list of MIDI output devices.
+    midi_output_devices: Vec<MidiDeviceInfo>,
 
-    /// Timestamp when the MIDI gate was last triggered (for minimum gate duration).
-    midi_last_gate_on: Option<Instant>,
+    /// Chosen MIDI outputs, with their connection state.
+    midi_outputs: Vec<MidiOutputInfo>,
 
-    /// Whether the MIDI gate is currently being held high (for minimum duration).
-    midi_gate_held_high: bool,
+    // --- MIDI Note module state ---
+    /// Currently held MIDI notes for MIDI Note modules.
+    /// Stores (note_number, velocity, channel, port) in order of press for voice priority.
+    midi_held_notes: Vec<(u8, u8, u8, u8)>,
 
-    /// The last MIDI note that was triggered (to maintain pitch during gate hold).
-    midi_last_triggered_note: u8,
+    /// Note and gate state of the MIDI Note modules, per channel and port filter.
+    midi_voices: HashMap<MidiNoteFilter, MidiVoice>,
 
-    /// The last MIDI velocity that was triggered.
-    midi_last_velocity: u8,
+    /// Tempo and transport of an external MIDI clock.
+    midi_clock: MidiClockFollower,
 
-    /// Current aftertouch value (channel pressure).
-    midi_aftertouch: u8,
+    /// Voices of the MPE zones that MPE Voice modules listen to.
+    mpe_voices: HashMap<MpeFilter, MpeVoiceAllocator>,
 
     // --- MIDI CC Mapping state ---
     /// Active MIDI CC to parameter mappings.
@@ -145,51 +377,224 @@ pub struct SynthApp {
 
     /// Target for MIDI Learn mode (None = not learning).
     midi_learn_target: Option<MidiLearnTarget>,
+
+    /// Works out what the controller moved during MIDI Learn sends.
+    midi_learner: MappingLearner,
+
+    /// 14-bit halves and takeover state of each mapping, by node and parameter.
+    controller_states: HashMap<(u64, usize), ControllerState>,
+
+    /// Rate limit of the mapped values sent back to the controller.
+    feedback_throttle: FeedbackThrottle,
+
+    // --- Clipboard state ---
+    /// Number of times the current clipboard contents have been pasted.
+    /// Each paste is offset a little further so copies don't stack exactly.
+    paste_count: u32,
+
+    // --- Autosave state ---
+    /// Background autosave session (None if the recovery directory is unavailable).
+    autosave: Option<AutosaveSession>,
+
+    /// Tracks unsaved changes to the current patch.
+    dirty: DirtyTracker,
+
+    /// When the patch was last handed to the autosaver.
+    last_autosave: Instant,
+
+    /// Patch recovered from an unclean shutdown, awaiting the user's decision.
+    recovered_patch: Option<RecoveryData>,
+
+    // --- Hot-reload state ---
+    /// Graph nodes created from the current patch file, keyed by patch node ID.
+    /// Lets hot-reload match the nodes in the file against the running graph.
+    patch_node_ids: HashMap<u64, egui_node_graph2::NodeId>,
+
+    /// Patch file watched for changes (None = watch mode off).
+    watched_file: Option<WatchedFile>,
+
+    /// Module definitions, used to compile and export patch scripts.
+    module_registry: ModuleRegistry,
+
+    // --- Patch library state ---
+    /// Author, tags and other metadata of the current patch.
+    patch_metadata: PatchMetadata,
+
+    /// Tuning of the note modules in the current patch (None = 12-TET).
+    tuning: Option<TuningData>,
+
+    /// Whether the Patch Info window is open.
+    show_patch_info: bool,
+
+    /// Text being edited in the Patch Info tags field.
+    patch_info_tags: String,
+
+    /// Library browser panel.
+    library_browser: LibraryBrowser,
+
+    /// Library patch being previewed (None = not previewing).
+    preview: Option<PatchPreview>,
+
+    // --- Scene state ---
+    /// Parameter scenes of the current patch, keyed by engine node ID.
+    scenes: Vec<Scene>,
+
+    /// Scenes window, including the morph crossfader.
+    scenes_window: ScenesWindow,
+
+    /// Index of the scene recalled last, for stepping to the next one.
+    recalled_scene: Option<usize>,
+
+    // --- Setlist state ---
+    /// Setlist window, holding the setlist program changes select from.
+    setlist_window: SetlistWindow,
+
+    /// Bank Select per MIDI channel, for numbering program changes.
+    program_select: ProgramSelect,
+
+    /// Reads the next setlist entry's patch in the background.
+    patch_preloader: PatchPreloader,
+
+    /// The next setlist entry's patch, built once read.
+    preloaded_patch: Option<PreloadedPatch>,
+
+    /// Setlist entry to load once the output has faded out, and when.
+    pending_switch: Option<(usize, Instant)>,
+
+    /// Which parameters of each module switch rather than interpolate when morphing.
+    discrete_params: HashMap<&'static str, Vec<bool>>,
+
+    // --- Randomizer state ---
+    /// Randomizer window.
+    randomizer: RandomizerWindow,
+
+    /// Parameter values from before each randomization, most recent last.
+    variation_undo: Vec<Vec<SceneValue>>,
+
+    /// Output routing window.
+    routing_window: RoutingWindow,
+
+    /// Sample rate and buffer size window.
+    audio_settings: AudioSettingsWindow,
+
+    /// Devices, interface scale and window state remembered between sessions.
+    config: AppConfig,
+
+    // --- Preset state ---
+    /// Factory and user module presets.
+    presets: PresetStore,
+
+    /// Save Preset dialog (None = closed).
+    preset_save: Option<PresetSaveDialog>,
+
+    // --- Lint state ---
+    /// Mistakes found in the current patch, errors first.
+    lints: Vec<Lint>,
+
+    /// When the patch was last checked for mistakes.
+    last_lint: Instant,
+
+    // --- Recording state ---
+    /// Recording writer (None if audio or the recordings directory is unavailable).
+    recorder: Option<WavRecorder>,
+
+    /// Whether the toolbar recording is idle, armed or running.
+    record_state: RecordState,
+
+    /// What the toolbar recording taps.
+    record_source: RecordSource,
+
+    /// Records incoming MIDI to a MIDI file.
+    midi_recorder: MidiRecorder,
 }
 
 impl SynthApp {
     /// Create a new SynthApp instance
     ///
     /// If `enable_test_tone` is true, audio will start with a test tone immediately.
-    pub fn new(enable_test_tone: bool) -> Self {
+    /// The devices and settings of `config` are reselected where still available.
+    pub fn new(enable_test_tone: bool, config: AppConfig) -> Self {
         let mut audio_engine = AudioEngine::new();
+        if let Ok(ref mut engine) = audio_engine {
+            restore_audio_output(engine, &config);
+        }
 
         let audio_error_message = match &audio_engine {
             Ok(_) => None,
             Err(e) => Some(e.to_string()),
         };
 
-        // Get initial device list and find the default device index
+        // Get initial device list and find the index of the open device
         let (audio_devices, selected_device_index) = match &audio_engine {
             Ok(engine) => {
                 let devices = engine.enumerate_devices();
+                let current = engine.current_device_name();
                 let default_idx = devices.iter()
-                    .position(|d| d.is_default)
+                    .find(|d| d.name == current)
+                    .or_else(|| devices.iter().find(|d| d.is_default))
+                    .map(|d| d.index)
                     .unwrap_or(0);
                 (devices, default_idx)
             }
             Err(_) => (Vec::new(), 0),
         };
+        let input_devices = match &audio_engine {
+            Ok(engine) => engine.enumerate_input_devices(),
+            Err(_) => Vec::new(),
+        };
 
         // Create engine channels for communication with audio thread
         let channels = EngineChannels::with_defaults();
         let (ui_handle, engine_handle) = channels.split();
 
+        // MIDI generated by the patch is sent by the MIDI output engine's thread
+        let (midi_output, midi_output_producer) = match MidiOutputEngine::new() {
+            Ok((engine, producer)) => (Some(engine), Some(producer)),
+            Err(e) => {
+                eprintln!("MIDI output unavailable: {}", e);
+                (None, None)
+            }
+        };
+        let midi_output_devices = midi_output
+            .as_ref()
+            .map(|engine| engine.enumerate_devices())
+            .unwrap_or_default();
+
         // Create and start the audio processor if engine is available
-        let ui_handle = if let Ok(ref mut engine) = audio_engine {
+        let (ui_handle, recorder) = if let Ok(ref mut engine) = audio_engine {
             let sample_rate = engine.sample_rate() as f32;
-            let block_size = 256; // Standard block size
-            let processor = AudioProcessor::new(sample_rate, block_size, engine_handle);
+            let block_size = engine.block_size();
+            let mut processor = AudioProcessor::new(sample_rate, block_size, engine_handle);
+
+            // Audio Input modules read the input device through the processor
+            if let Some(input) = engine.take_input_reader() {
+                processor.set_input(input);
+            }
+            if let Some(producer) = midi_output_producer {
+                processor.set_midi_output(producer);
+            }
+
+            // Recordings are written by a background thread fed from the audio callback
+            let recorder = match WavRecorder::start(paths::recordings_dir(), &file_timestamp(), engine.sample_rate()) {
+                Ok((recorder, tap)) => {
+                    processor.set_recorder(tap);
+                    Some(recorder)
+                }
+                Err(e) => {
+                    eprintln!("Recording disabled: {}", e);
+                    None
+                }
+            };
 
             if let Err(e) = engine.start_with_processor(processor) {
                 eprintln!("Failed to start audio processor: {}", e);
             }
 
-            Some(ui_handle)
+            (Some(ui_handle), recorder)
         } else {
             // Drop the engine_handle since we can't use it
             drop(engine_handle);
-            None
+            (None, None)
         };
 
         // Initialize MIDI engine
@@ -205,7 +610,19 @@ impl SynthApp {
                 }
             };
 
-        let app = Self {
+        // Start autosave; this also detects whether the last session crashed
+        let (autosave, recovered_patch) = match AutosaveSession::start(paths::recovery_dir()) {
+            Ok((session, recovered)) => (Some(session), recovered),
+            Err(e) => {
+                eprintln!("Autosave disabled: {}", e);
+                (None, None)
+            }
+        };
+
+        let module_registry = create_module_registry();
+        let discrete_params = discrete_parameters(&module_registry);
+
+        let mut app = Self {
             audio_engine,
             ui_handle,
             audio_error_message,
@@ -215,6 +632,10 @@ impl SynthApp {
             user_state: SynthGraphState::default(),
             audio_devices,
             selected_device_index,
+            input_devices,
+            selected_input_device: None,
+            input_levels: [0.0; MAX_INPUT_CHANNELS],
+            input_level_channels: 0,
             cached_params: HashMap::new(),
             current_patch_path: None,
             status_message: None,
@@ -226,47 +647,184 @@ impl SynthApp {
             midi_engine,
             midi_event_consumer,
             midi_devices,
-            selected_midi_device: None,
+            midi_inputs: Vec::new(),
+            last_midi_check: Instant::now(),
             midi_error_message,
+            midi_output,
+            midi_output_devices,
+            midi_outputs: Vec::new(),
             // MIDI Note module state
             midi_held_notes: Vec::new(),
-            midi_last_gate_on: None,
-            midi_gate_held_high: false,
-            midi_last_triggered_note: 60,
-            midi_last_velocity: 100,
-            midi_aftertouch: 0,
+            midi_voices: HashMap::new(),
+            midi_clock: MidiClockFollower::new(),
+            mpe_voices: HashMap::new(),
             // MIDI CC Mapping state
             midi_mappings: Vec::new(),
             midi_learn_target: None,
+            midi_learner: MappingLearner::new(),
+            controller_states: HashMap::new(),
+            feedback_throttle: FeedbackThrottle::default(),
+            // Clipboard state
+            paste_count: 0,
+            // Autosave state
+            autosave,
+            dirty: DirtyTracker::new(),
+            last_autosave: Instant::now(),
+            recovered_patch,
+            // Hot-reload state
+            patch_node_ids: HashMap::new(),
+            watched_file: None,
+            module_registry,
+            // Patch library state
+            patch_metadata: PatchMetadata::default(),
+            tuning: None,
+            show_patch_info: false,
+            patch_info_tags: String::new(),
+            library_browser: LibraryBrowser::new(),
+            preview: None,
+            // Scene state
+            scenes: Vec::new(),
+            scenes_window: ScenesWindow::new(),
+            recalled_scene: None,
+            discrete_params,
+
+            // Setlist state
+            setlist_window: SetlistWindow::new(),
+            program_select: ProgramSelect::new(),
+            patch_preloader: PatchPreloader::new(),
+            preloaded_patch: None,
+            pending_switch: None,
+
+            // Randomizer state
+            randomizer: RandomizerWindow::new(),
+            variation_undo: Vec::new(),
+            routing_window: RoutingWindow::new(),
+            audio_settings: AudioSettingsWindow::new(),
+            config,
+
+            // Preset state
+            presets: PresetStore::open(paths::presets_dir()),
+            preset_save: None,
+
+            // Lint state
+            lints: Vec::new(),
+            last_lint: Instant::now(),
+
+            // Recording state
+            recorder,
+            record_state: RecordState::Idle,
+            record_source: RecordSource::Master,
+            midi_recorder: MidiRecorder::new(),
         };
 
         // Note: enable_test_tone is ignored - test tone was removed in favor of AudioProcessor
         let _ = enable_test_tone;
 
+        app.refresh_preset_menus();
+        app.restore_devices();
+        app.restore_setlist();
         app
     }
 
-    /// Refresh the list of available audio devices
+    /// Reopen the audio input, MIDI inputs and output routing of the last session.
+    fn restore_devices(&mut self) {
+        if let Some(name) = self.config.audio_input.clone() {
+            let device = self.input_devices.iter().find(|d| d.name == name).map(|d| d.index);
+            if let Some(index) = device {
+                self.select_input_device(Some(index));
+            }
+        }
+
+        // Devices that are not plugged in are connected when they appear
+        if let Some(ref mut engine) = self.midi_engine {
+            for name in &self.config.midi_inputs {
+                engine.add_input(name);
+            }
+            self.midi_inputs = engine.inputs();
+        }
+        if let Some(name) = self.config.virtual_midi_input.clone() {
+            self.open_virtual_midi_port(&name);
+        }
+        if let Some(ref mut engine) = self.midi_output {
+            for name in &self.config.midi_outputs {
+                engine.add_output(name);
+            }
+            if let Some(name) = &self.config.virtual_midi_output {
+                if let Err(e) = engine.open_virtual_port(name) {
+                    self.midi_error_message = Some(e.to_string());
+                }
+            }
+            if let Some(name) = &self.config.midi_feedback_output {
+                engine.set_feedback_output(Some(name));
+            }
+            self.midi_outputs = engine.outputs();
+        }
+
+        if let Some(routing) = self.config.output_routing.as_deref().map(OutputRouting::from_masks) {
+            self.routing_window.routing = routing;
+            self.send_command(EngineCommand::SetOutputRouting(routing));
+        }
+    }
+
+    /// Write the configuration file.
+    fn save_config(&self) {
+        if let Err(e) = self.config.save(&paths::config_file()) {
+            eprintln!("Could not save settings: {}", e);
+        }
+    }
+
+    /// Remember the interface scale and main window geometry.
+    fn track_window_state(&mut self, ctx: &egui::Context) {
+        self.config.ui_scale = ctx.zoom_factor();
+
+        let viewport = ctx.input(|i| i.viewport().clone());
+        let maximized = viewport.maximized.unwrap_or(false);
+        let previous = self.config.window;
+        let (size, position) = match (maximized, previous) {
+            // Keep the size to restore to when un-maximizing
+            (true, Some(window)) => ((window.width, window.height), window.position),
+            _ => match viewport.inner_rect {
+                Some(rect) => (
+                    (rect.width(), rect.height()),
+                    viewport.outer_rect.map(|outer| (outer.min.x, outer.min.y)),
+                ),
+                None => return,
+            },
+        };
+
+        self.config.window = Some(WindowState {
+            width: size.0,
+            height: size.1,
+            position,
+            maximized,
+        });
+    }
+
+    /// Refresh the lists of available audio output and input devices
     fn refresh_devices(&mut self) {
         if let Ok(ref engine) = self.audio_engine {
             self.audio_devices = engine.enumerate_devices();
+            self.input_devices = engine.enumerate_input_devices();
         }
     }
 
-    /// Refresh the list of available MIDI devices
+    /// Refresh the lists of available MIDI input and output devices
     fn refresh_midi_devices(&mut self) {
         if let Some(ref mut engine) = self.midi_engine {
             self.midi_devices = engine.enumerate_devices();
         }
+        if let Some(ref engine) = self.midi_output {
+            self.midi_output_devices = engine.enumerate_devices();
+        }
     }
 
-    /// Connect to a MIDI device by index
+    /// Connect to a MIDI device by index, adding it to the MIDI inputs
     fn connect_midi_device(&mut self, index: usize) {
         if let Some(ref mut engine) = self.midi_engine {
             match engine.connect(index) {
-                Ok(()) => {
-                    self.selected_midi_device = Some(index);
+                Ok(_) => {
                     self.midi_error_message = None;
+                    self.update_midi_inputs();
                 }
                 Err(e) => {
                     self.midi_error_message = Some(e.to_string());
@@ -275,75 +833,294 @@ impl SynthApp {
         }
     }
 
-    /// Disconnect from the current MIDI device
-    fn disconnect_midi_device(&mut self) {
+    /// Disconnect a MIDI device and remove it from the MIDI inputs
+    fn disconnect_midi_device(&mut self, name: &str) {
+        if let Some(ref mut engine) = self.midi_engine {
+            engine.disconnect(name);
+            self.update_midi_inputs();
+        }
+    }
+
+    /// Disconnect all MIDI devices, including the virtual port
+    fn disconnect_all_midi_devices(&mut self) {
+        if let Some(ref mut engine) = self.midi_engine {
+            engine.disconnect_all();
+            self.config.virtual_midi_input = None;
+            self.update_midi_inputs();
+        }
+    }
+
+    /// Repaint the UI as MIDI input arrives, so program changes and mapped
+    /// controls are handled at once even while nothing else is animating.
+    pub fn wake_on_midi(&mut self, ctx: egui::Context) {
+        if let Some(engine) = &mut self.midi_engine {
+            engine.set_waker(move || ctx.request_repaint());
+        }
+    }
+
+    /// Publish a virtual MIDI input port that other applications can send to.
+    ///
+    /// The port is not remembered in the settings; the toolbar toggle does that.
+    pub fn open_virtual_midi_port(&mut self, name: &str) {
         if let Some(ref mut engine) = self.midi_engine {
-            engine.disconnect();
-            self.selected_midi_device = None;
+            match engine.open_virtual_port(name) {
+                Ok(port) => {
+                    self.midi_inputs = engine.inputs();
+                    self.midi_error_message = None;
+                    self.status_message = Some(format!("Virtual MIDI port \"{}\" open as input {}", name, port));
+                }
+                Err(e) => {
+                    self.midi_error_message = Some(e.to_string());
+                }
+            }
+        }
+    }
+
+    /// Open or close the virtual MIDI input port, remembering the choice.
+    fn toggle_virtual_midi_port(&mut self) {
+        let open_port = self.midi_engine.as_ref().and_then(|e| e.virtual_port()).map(str::to_string);
+        match open_port {
+            Some(_) => {
+                if let Some(ref mut engine) = self.midi_engine {
+                    engine.close_virtual_port();
+                }
+                self.config.virtual_midi_input = None;
+            }
+            None => {
+                let name = self.config.virtual_midi_input
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_VIRTUAL_PORT_NAME.to_string());
+                self.open_virtual_midi_port(&name);
+                self.config.virtual_midi_input = self.midi_engine
+                    .as_ref()
+                    .and_then(|e| e.virtual_port())
+                    .map(str::to_string);
+            }
+        }
+        self.update_midi_inputs();
+    }
+
+    /// Connect to a MIDI output device by index, adding it to the MIDI outputs
+    fn connect_midi_output(&mut self, index: usize) {
+        if let Some(ref mut engine) = self.midi_output {
+            match engine.connect(index) {
+                Ok(()) => {
+                    self.midi_error_message = None;
+                    self.update_midi_outputs();
+                }
+                Err(e) => {
+                    self.midi_error_message = Some(e.to_string());
+                }
+            }
+        }
+    }
+
+    /// Disconnect a MIDI output device and remove it from the MIDI outputs
+    fn disconnect_midi_output(&mut self, name: &str) {
+        if let Some(ref mut engine) = self.midi_output {
+            engine.disconnect(name);
+            self.update_midi_outputs();
+        }
+    }
+
+    /// Open or close the virtual MIDI output port, remembering the choice.
+    fn toggle_virtual_midi_output(&mut self) {
+        let Some(ref mut engine) = self.midi_output else {
+            return;
+        };
+        if engine.virtual_port().is_some() {
+            engine.close_virtual_port();
+        } else {
+            let name = self.config.virtual_midi_output
+                .clone()
+                .unwrap_or_else(|| DEFAULT_VIRTUAL_OUTPUT_NAME.to_string());
+            match engine.open_virtual_port(&name) {
+                Ok(()) => {
+                    self.midi_error_message = None;
+                    self.status_message = Some(format!("Virtual MIDI output \"{}\" open", name));
+                }
+                Err(e) => {
+                    self.midi_error_message = Some(e.to_string());
+                }
+            }
+        }
+        self.update_midi_outputs();
+    }
+
+    /// Re-read the MIDI outputs after one was added or removed, and remember them.
+    fn update_midi_outputs(&mut self) {
+        if let Some(ref engine) = self.midi_output {
+            self.midi_outputs = engine.outputs();
+            self.config.midi_outputs = self.midi_outputs
+                .iter()
+                .filter(|output| !output.is_virtual)
+                .map(|output| output.name.clone())
+                .collect();
+            self.config.virtual_midi_output = engine.virtual_port();
+            self.save_config();
+        }
+    }
+
+    /// Re-read the MIDI inputs after one was added or removed, and remember them.
+    fn update_midi_inputs(&mut self) {
+        if let Some(ref engine) = self.midi_engine {
+            self.midi_inputs = engine.inputs();
+            self.config.midi_inputs = self.midi_inputs
+                .iter()
+                .filter(|input| !input.is_virtual)
+                .map(|input| input.name.clone())
+                .collect();
+            self.save_config();
+        }
+
+        // Port numbers may have moved, so held notes can no longer be matched to their input
+        if !self.midi_held_notes.is_empty() {
+            self.midi_held_notes.clear();
+            self.sync_midi_note_modules();
+        }
+    }
+
+    /// Offer the chosen MIDI inputs in the input menus of the MIDI modules.
+    fn sync_midi_input_names(&mut self) {
+        let names = &mut self.user_state.midi_input_names;
+        if !names.iter().eq(self.midi_inputs.iter().map(|input| &input.name)) {
+            *names = self.midi_inputs.iter().map(|input| input.name.clone()).collect();
+        }
+    }
+
+    /// How often to check for MIDI devices being unplugged or plugged back in.
+    const MIDI_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Follow MIDI devices being unplugged and plugged back in.
+    fn check_midi_connections(&mut self) {
+        if self.last_midi_check.elapsed() < Self::MIDI_CHECK_INTERVAL {
+            return;
+        }
+        self.last_midi_check = Instant::now();
+
+        if let Some(ref mut engine) = self.midi_output {
+            let changes = engine.check_connections();
+            if !changes.is_empty() {
+                self.midi_outputs = engine.outputs();
+                self.midi_output_devices = engine.enumerate_devices();
+            }
+            for change in changes {
+                self.status_message = Some(match change {
+                    MidiConnectionChange::Lost(name) => format!("MIDI output unplugged: {}", name),
+                    MidiConnectionChange::Restored(name) => {
+                        // A controller plugged back in shows nothing yet
+                        self.feedback_throttle.resend_all();
+                        format!("MIDI output reconnected: {}", name)
+                    }
+                });
+            }
+        }
+
+        let Some(ref mut engine) = self.midi_engine else {
+            return;
+        };
+        let changes = engine.check_connections();
+        if changes.is_empty() {
+            return;
+        }
+        let previous_inputs = std::mem::replace(&mut self.midi_inputs, engine.inputs());
+        self.midi_devices = engine.enumerate_devices();
+
+        for change in changes {
+            match change {
+                MidiConnectionChange::Lost(name) => {
+                    // Release the notes that were held on the unplugged device
+                    if let Some(input) = previous_inputs.iter().find(|input| input.name == name) {
+                        self.midi_held_notes.retain(|&(_, _, _, port)| port != input.port);
+                        self.sync_midi_note_modules();
+                    }
+                    self.status_message = Some(format!("MIDI device unplugged: {}", name));
+                }
+                MidiConnectionChange::Restored(name) => {
+                    self.status_message = Some(format!("MIDI device reconnected: {}", name));
+                }
+            }
         }
     }
 
     /// Process pending MIDI events.
     /// - Stores events in user state for display by MIDI Monitor modules.
     /// - Routes note events to MIDI Note modules.
-    /// - Handles CC events for MIDI Learn and mapped parameters.
+    /// - Handles CC, NRPN, RPN and note events for MIDI Learn, mapped parameters and actions.
+    /// - Follows external MIDI clock and transport for Clock modules.
+    /// - Assigns MPE notes and their expression to MPE Voice modules.
     fn process_midi_events(&mut self) {
+        let mpe_nodes = self.update_mpe_allocators();
+        let now = Instant::now();
+        let mut mpe_changed = false;
         let mut notes_changed = false;
         let mut cc_updates: Vec<(u64, usize, f32)> = Vec::new();
-        let mut learned_mapping: Option<MidiMapping> = None;
+        let mut control_messages: Vec<(ControlMessage, u8, u8)> = Vec::new();
+        let mut program_recall: Option<usize> = None;
 
         if let Some(ref mut consumer) = self.midi_event_consumer {
             while let Ok(timestamped) = consumer.pop() {
                 let event = timestamped.event;
+                let port = timestamped.port;
 
-                // Store the event for MIDI Monitor display
-                self.user_state.push_midi_event(event);
+                // Follow the tempo and transport of external sequencers
+                self.midi_clock.handle(&event, timestamped.timestamp_us, port);
+                self.midi_recorder.record(&timestamped, now);
+
+                for allocator in self.mpe_voices.values_mut() {
+                    mpe_changed |= allocator.handle(&event, port, now);
+                }
+
+                // Store the event for MIDI Monitor display. Clock ticks arrive
+                // dozens of times a second and would push everything else out.
+                if !matches!(event, MidiEvent::Clock) {
+                    self.user_state.push_midi_event(event, port);
+                }
 
                 // Process MIDI events for MIDI Note modules
                 match event {
                     MidiEvent::NoteOn { channel, note, velocity } => {
+                        // Notes also press mapped buttons; they still play as well
+                        control_messages.push((ControlMessage::Note { note, velocity }, channel, port));
+
                         // Add note if not already in list
-                        if !self.midi_held_notes.iter().any(|(n, _, _)| *n == note) {
-                            self.midi_held_notes.push((note, velocity, channel));
+                        if !self.midi_held_notes.iter().any(|&(n, _, c, p)| (n, c, p) == (note, channel, port)) {
+                            self.midi_held_notes.push((note, velocity, channel, port));
                             notes_changed = true;
                         }
                     }
-                    MidiEvent::NoteOff { note, .. } => {
+                    MidiEvent::NoteOff { channel, note, .. } => {
+                        control_messages.push((ControlMessage::Note { note, velocity: 0 }, channel, port));
+
                         // Remove note from list
-                        if let Some(pos) = self.midi_held_notes.iter().position(|(n, _, _)| *n == note) {
+                        if let Some(pos) = self.midi_held_notes
+                            .iter()
+                            .position(|&(n, _, c, p)| (n, c, p) == (note, channel, port))
+                        {
                             self.midi_held_notes.remove(pos);
                             notes_changed = true;
                         }
                     }
-                    MidiEvent::ChannelPressure { pressure, .. } => {
-                        // Update aftertouch
-                        if self.midi_aftertouch != pressure {
-                            self.midi_aftertouch = pressure;
-                            notes_changed = true;
+                    MidiEvent::ChannelPressure { channel, pressure } => {
+                        // Update aftertouch of the voices listening to this channel and port
+                        for (filter, voice) in &mut self.midi_voices {
+                            if filter_accepts(*filter, channel, port) && voice.aftertouch != pressure {
+                                voice.aftertouch = pressure;
+                                notes_changed = true;
+                            }
                         }
                     }
                     MidiEvent::ControlChange { channel, controller, value } => {
-                        // Check if we're in MIDI Learn mode
-                        if let Some(ref target) = self.midi_learn_target {
-                            // Create new mapping from the received CC
-                            learned_mapping = Some(MidiMapping::new(
-                                controller,
-                                0, // Omni channel for learned mappings
-                                target.node_id,
-                                target.param_index,
-                                target.param_name.clone(),
-                                target.min_value,
-                                target.max_value,
-                            ));
-                        } else {
-                            // Apply CC to all matching mappings
-                            for mapping in &self.midi_mappings {
-                                if mapping.matches(controller, channel) {
-                                    let param_value = mapping.cc_to_value(value);
-                                    cc_updates.push((mapping.node_id, mapping.param_index, param_value));
-                                }
-                            }
-                        }
+                        self.program_select.control_change(channel, controller, value);
+                        control_messages.push((ControlMessage::Cc { controller, value }, channel, port));
+                    }
+                    MidiEvent::ParameterNumber { channel, registered, number, value } => {
+                        let message = ControlMessage::ParameterNumber { registered, number, value };
+                        control_messages.push((message, channel, port));
+                    }
+                    MidiEvent::ProgramChange { channel, program } => {
+                        // Numbered across banks, for the setlist
+                        program_recall = Some(self.program_select.program_change(channel, program));
                     }
                     _ => {
                         // Other events (pitch bend, etc.) are not handled yet
@@ -352,37 +1129,50 @@ impl SynthApp {
             }
         }
 
-        // Handle MIDI Learn completion
-        if let Some(mapping) = learned_mapping {
-            // Remove any existing mapping for the same parameter (from user_state too)
-            self.midi_mappings.retain(|m| {
-                !(m.node_id == mapping.node_id && m.param_index == mapping.param_index)
-            });
-            self.user_state.remove_midi_mapping(mapping.node_id, mapping.param_index);
-
-            // Also remove any existing mapping for the same CC
-            self.midi_mappings.retain(|m| {
-                !(m.cc_number == mapping.cc_number && (m.channel == 0 || m.channel == mapping.channel))
-            });
-
-            // Add the new mapping
-            self.user_state.set_midi_mapping(
-                mapping.node_id,
-                mapping.param_index,
-                mapping.cc_number,
-                mapping.channel,
-            );
-            self.midi_mappings.push(mapping);
-
-            // Exit learn mode
-            self.midi_learn_target = None;
-            self.user_state.midi_learn_active = false;
-            self.user_state.midi_learn_target = None;
-            self.status_message = Some("MIDI CC mapped successfully".to_string());
+        // Controller messages go to MIDI Learn, the mapped parameters or app actions
+        let mut midi_actions = Vec::new();
+        for (message, channel, port) in control_messages {
+            midi_actions.extend(self.handle_control_message(message, channel, port, now, &mut cc_updates));
         }
 
-        // Apply CC updates to parameters
-        for (node_id, param_index, value) in cc_updates {
+        // Handle MIDI Learn completion
+        if let Some(learned) = self.midi_learner.finish(now) {
+            if let Some(ref target) = self.midi_learn_target {
+                // With several inputs connected it only listens to the device the control is on
+                let learned_device = if self.midi_inputs.len() > 1 {
+                    self.midi_inputs
+                        .iter()
+                        .find(|input| input.port == learned.port)
+                        .map(|input| input.name.clone())
+                } else {
+                    None
+                };
+                let mapping = match target.action {
+                    Some(action) => MidiMapping::for_action(action, learned.control, learned.cc_number),
+                    None => {
+                        let mapping = MidiMapping::new(
+                            learned.cc_number,
+                            0, // Omni channel for learned mappings
+                            target.node_id,
+                            target.param_index,
+                            target.param_name.clone(),
+                            target.min_value,
+                            target.max_value,
+                        )
+                        .with_control(learned.control)
+                        .with_encoder(learned.encoder);
+                        if learned.button { mapping.with_button(target.button) } else { mapping }
+                    }
+                };
+                self.add_learned_mapping(mapping.with_device(learned_device));
+            }
+        }
+
+        // Apply CC updates to parameters
+        if !cc_updates.is_empty() {
+            self.dirty.mark_dirty();
+        }
+        for (node_id, param_index, value) in cc_updates {
             self.send_command(EngineCommand::SetParameter {
                 node_id,
                 param_index,
@@ -392,39 +1182,174 @@ impl SynthApp {
             self.cached_params.insert((node_id, param_index), value);
 
             // Also update the graph UI to reflect the change
-            self.update_graph_param_from_cc(node_id, param_index, value);
+            self.update_graph_param(node_id, param_index, value);
+        }
+
+        if let Some(number) = program_recall {
+            self.recall_program(number);
         }
 
         // Update MIDI Note modules if note state changed
         if notes_changed {
             self.sync_midi_note_modules();
         }
+
+        if let Some(transport) = self.midi_clock.take_update() {
+            self.send_command(EngineCommand::SetMidiTransport(transport));
+        }
+
+        // Release MPE gates whose minimum duration has passed
+        for allocator in self.mpe_voices.values_mut() {
+            mpe_changed |= allocator.update_gates(now);
+        }
+        if mpe_changed {
+            self.sync_mpe_voice_modules(&mpe_nodes);
+        }
+
+        // Actions last, as the next patch replaces everything above
+        for action in midi_actions {
+            self.run_midi_action(action);
+        }
+    }
+
+    /// Run an app action triggered by a mapped button.
+    fn run_midi_action(&mut self, action: MidiAction) {
+        match action {
+            MidiAction::PlayStop => self.toggle_playing(),
+            MidiAction::NextScene => self.recall_next_scene(),
+            MidiAction::Panic => self.midi_panic(),
+            MidiAction::NextPatch => {
+                match self.library_browser.next_patch(self.current_patch_path.as_deref()) {
+                    Some(path) => self.open_library_patch(path),
+                    None => self.status_message = Some("No patches in the library".to_string()),
+                }
+            }
+        }
+    }
+
+    /// Release every held MIDI note, in the patch and on the MIDI outputs.
+    fn midi_panic(&mut self) {
+        self.midi_held_notes.clear();
+        self.sync_midi_note_modules();
+
+        for (filter, allocator) in &mut self.mpe_voices {
+            *allocator = MpeVoiceAllocator::new(filter.0, filter.1, allocator.voice_count());
+        }
+        let mpe_nodes = self.mpe_voice_nodes();
+        self.sync_mpe_voice_modules(&mpe_nodes);
+
+        self.send_command(EngineCommand::AllNotesOff);
+        self.status_message = Some("All notes off".to_string());
+    }
+
+    /// The MPE Voice modules of the patch, as (engine node ID, filter, voice index).
+    fn mpe_voice_nodes(&self) -> Vec<(u64, MpeFilter, usize)> {
+        let param = |engine_node_id, param_index, default: f32| {
+            self.cached_params
+                .get(&(engine_node_id, param_index))
+                .copied()
+                .unwrap_or(default)
+                .round() as u8
+        };
+        self.graph_state.graph.nodes.iter()
+            .filter(|(_, node)| node.user_data.module_id == "input.mpe_voice")
+            .filter_map(|(node_id, _)| self.user_state.get_engine_node_id(node_id))
+            .map(|id| {
+                let kind = if param(id, MpeVoice::PARAM_ZONE, 0.0) == 1 {
+                    MpeZoneKind::Upper
+                } else {
+                    MpeZoneKind::Lower
+                };
+                let zone = MpeZone::new(
+                    kind,
+                    param(id, MpeVoice::PARAM_CHANNELS, MpeZone::MAX_MEMBER_CHANNELS as f32),
+                    param(id, MpeVoice::PARAM_BEND_RANGE, MpeZone::DEFAULT_PITCH_BEND_RANGE as f32),
+                );
+                let filter = (zone, self.midi_input_port(id));
+                (id, filter, param(id, MpeVoice::PARAM_VOICE, 0.0) as usize)
+            })
+            .collect()
+    }
+
+    /// Keep one voice allocator per MPE filter in use, with as many voices
+    /// as the highest Voice any of its modules outputs.
+    ///
+    /// Returns the MPE Voice modules, as `mpe_voice_nodes`.
+    fn update_mpe_allocators(&mut self) -> Vec<(u64, MpeFilter, usize)> {
+        let nodes = self.mpe_voice_nodes();
+        let mut voice_counts: HashMap<MpeFilter, usize> = HashMap::new();
+        for &(_, filter, voice) in &nodes {
+            let count = voice_counts.entry(filter).or_default();
+            *count = (*count).max(voice + 1);
+        }
+
+        self.mpe_voices.retain(|filter, _| voice_counts.contains_key(filter));
+        for (filter, count) in voice_counts {
+            let allocator = self.mpe_voices
+                .entry(filter)
+                .or_insert_with(|| MpeVoiceAllocator::new(filter.0, filter.1, count));
+            if allocator.voice_count() != count {
+                allocator.set_voice_count(count);
+            }
+        }
+        nodes
+    }
+
+    /// Send the state of their voice to the MPE Voice modules.
+    fn sync_mpe_voice_modules(&mut self, nodes: &[(u64, MpeFilter, usize)]) {
+        for &(engine_node_id, filter, voice) in nodes {
+            let Some(state) = self.mpe_voices.get(&filter).and_then(|allocator| allocator.voice(voice)) else {
+                continue;
+            };
+            let values = [
+                (MpeVoice::PARAM_PITCH, state.pitch),
+                (MpeVoice::PARAM_GATE, if state.gate { 1.0 } else { 0.0 }),
+                (MpeVoice::PARAM_VELOCITY, state.velocity),
+                (MpeVoice::PARAM_PRESSURE, state.pressure),
+                (MpeVoice::PARAM_SLIDE, state.slide),
+            ];
+            for (param_index, value) in values {
+                // Only send what changed; expression arrives many times a second
+                if self.cached_params.get(&(engine_node_id, param_index)) != Some(&value) {
+                    self.send_command(EngineCommand::SetParameter { node_id: engine_node_id, param_index, value });
+                    // Also update the cached param so sync_parameters doesn't overwrite
+                    self.cached_params.insert((engine_node_id, param_index), value);
+                }
+            }
+        }
     }
 
-    /// Update a graph parameter value from a CC change.
-    fn update_graph_param_from_cc(&mut self, engine_node_id: u64, param_index: usize, value: f32) {
+    /// Update a graph parameter value addressed by engine node ID.
+    fn update_graph_param(&mut self, engine_node_id: u64, param_index: usize, value: f32) {
         // Find the graph node ID for this engine node
         let graph_node_id = self.user_state.node_id_map.iter()
             .find(|(_, &engine_id)| engine_id == engine_node_id)
             .map(|(graph_id, _)| *graph_id);
 
         if let Some(graph_node_id) = graph_node_id {
-            if let Some(node) = self.graph_state.graph.nodes.get_mut(graph_node_id) {
-                // Find the parameter by index
-                let mut current_param_index = 0;
-                for (_name, input_id) in &node.inputs {
-                    if let Some(input) = self.graph_state.graph.inputs.get_mut(*input_id) {
-                        match input.kind {
-                            InputParamKind::ConstantOnly | InputParamKind::ConnectionOrConstant => {
-                                if current_param_index == param_index {
-                                    input.value.set_actual_value(value);
-                                    return;
-                                }
-                                current_param_index += 1;
-                            }
-                            InputParamKind::ConnectionOnly => {
-                                // Skip connection-only inputs
+            self.set_graph_param(graph_node_id, param_index, value);
+        }
+    }
+
+    /// Set a graph node's parameter by index (counting only constant inputs).
+    ///
+    /// The new value reaches the engine through `sync_parameters`.
+    fn set_graph_param(&mut self, graph_node_id: egui_node_graph2::NodeId, param_index: usize, value: f32) {
+        if let Some(node) = self.graph_state.graph.nodes.get_mut(graph_node_id) {
+            // Find the parameter by index
+            let mut current_param_index = 0;
+            for (_name, input_id) in &node.inputs {
+                if let Some(input) = self.graph_state.graph.inputs.get_mut(*input_id) {
+                    match input.kind {
+                        InputParamKind::ConstantOnly | InputParamKind::ConnectionOrConstant => {
+                            if current_param_index == param_index {
+                                input.value.set_actual_value(value);
+                                return;
                             }
+                            current_param_index += 1;
+                        }
+                        InputParamKind::ConnectionOnly => {
+                            // Skip connection-only inputs
                         }
                     }
                 }
@@ -432,15 +1357,177 @@ impl SynthApp {
         }
     }
 
+    /// Pass a controller message to MIDI Learn or to the mappings listening to it.
+    ///
+    /// `channel` is the 0-based channel of the message and `port` the input
+    /// it arrived on; mappings match the input by its device name. New
+    /// parameter values are added to `updates`; the app actions the message
+    /// presses are returned.
+    fn handle_control_message(
+        &mut self,
+        message: ControlMessage,
+        channel: u8,
+        port: u8,
+        now: Instant,
+        updates: &mut Vec<(u64, usize, f32)>,
+    ) -> Vec<MidiAction> {
+        let mut actions = Vec::new();
+        if self.midi_learn_target.is_some() {
+            self.midi_learner.handle(message, port, now);
+            return actions;
+        }
+
+        let device = self.midi_inputs.iter().find(|input| input.port == port).map(|input| input.name.as_str());
+        for mapping in &self.midi_mappings {
+            if let Some(action) = mapping.action {
+                if mapping.pressed(message, channel, device) == Some(true) {
+                    actions.push(action);
+                }
+                continue;
+            }
+
+            let key = (mapping.node_id, mapping.param_index);
+            let current = self.cached_params.get(&key).copied().unwrap_or(mapping.min_value);
+            let state = self.controller_states.entry(key).or_default();
+            if let Some(value) = mapping.handle(message, channel, device, current, state) {
+                // Later messages in the same batch (encoder steps) continue from here
+                self.cached_params.insert(key, value);
+                updates.push((mapping.node_id, mapping.param_index, value));
+
+                // An absolute control already shows the value it sent; encoders and buttons don't
+                if mapping.feedback && mapping.button_mode().is_none() && !mapping.encoder.is_relative() {
+                    self.feedback_throttle.received(key, mapping.feedback_messages(value));
+                }
+            }
+        }
+        actions
+    }
+
+    /// Send the values of mapped parameters that changed back to the controller.
+    ///
+    /// Parameters can change from the knobs, MIDI, scenes or a patch load;
+    /// each is compared with what the controller was last sent.
+    fn send_controller_feedback(&mut self) {
+        let Some(engine) = self.midi_output.as_mut() else {
+            return;
+        };
+        if engine.feedback_output().is_none() {
+            return;
+        }
+
+        for mapping in self.midi_mappings.iter().filter(|mapping| mapping.feedback && mapping.action.is_none()) {
+            let key = (mapping.node_id, mapping.param_index);
+            if let Some(&value) = self.cached_params.get(&key) {
+                self.feedback_throttle.update(key, mapping.feedback_messages(value));
+            }
+        }
+
+        let events = self.feedback_throttle.take_due(Instant::now());
+        if !events.is_empty() {
+            engine.send_feedback(&events);
+        }
+    }
+
+    /// Choose the MIDI output controller feedback goes to (None = no feedback).
+    fn set_feedback_output(&mut self, name: Option<String>) {
+        let Some(ref mut engine) = self.midi_output else {
+            return;
+        };
+        let connected = engine.set_feedback_output(name.as_deref());
+        if let (Some(name), false) = (&name, connected) {
+            self.status_message = Some(format!("Feedback output not connected: {}", name));
+        }
+        self.config.midi_feedback_output = name;
+        self.save_config();
+        self.feedback_throttle.resend_all();
+    }
+
+    /// Add a mapping found by MIDI Learn and leave learn mode.
+    ///
+    /// Replaces any mapping of the same parameter and any mapping of the
+    /// same control that would also hear it.
+    fn add_learned_mapping(&mut self, mapping: MidiMapping) {
+        if !mapping.is_valid() {
+            self.status_message = Some(format!("MIDI {} can't be mapped", mapping.control_label()));
+            return;
+        }
+
+        // Remove any existing mapping for the same parameter, and any for the
+        // same control that would also hear it (from user_state too)
+        let replaced = |m: &MidiMapping| {
+            let same_target = match mapping.action {
+                Some(action) => m.action == Some(action),
+                None => m.targets(mapping.node_id, mapping.param_index),
+            };
+            same_target
+                || (m.shares_control(&mapping)
+                    && (m.channel == 0 || m.channel == mapping.channel)
+                    && (m.device.is_none() || mapping.device.is_none() || m.device == mapping.device))
+        };
+        for existing in self.midi_mappings.iter().filter(|m| m.action.is_none() && replaced(m)) {
+            self.user_state.remove_midi_mapping(existing.node_id, existing.param_index);
+        }
+        self.midi_mappings.retain(|m| !replaced(m));
+
+        // Add the new mapping
+        if mapping.action.is_none() {
+            self.user_state.set_midi_mapping(&mapping);
+        }
+        let label = mapping.control_label();
+        let encoder = mapping.encoder;
+        let action = mapping.action;
+        self.midi_mappings.push(mapping);
+        self.controller_states.clear();
+        self.dirty.mark_dirty();
+
+        // Exit learn mode
+        self.midi_learn_target = None;
+        self.user_state.midi_learn_active = false;
+        self.user_state.midi_learn_target = None;
+        self.status_message = Some(match action {
+            Some(action) => format!("MIDI {} mapped to {}", label, action.label()),
+            None if encoder.is_relative() => {
+                format!("MIDI {} mapped as a relative encoder ({})", label, encoder.label())
+            }
+            None => format!("MIDI {} mapped", label),
+        });
+    }
+
+    /// Change how a parameter's MIDI mapping responds to its controller.
+    fn set_mapping_options(&mut self, node_id: u64, param_index: usize, options: MappingOptions) {
+        let Some(mapping) = self.midi_mappings.iter_mut().find(|m| m.targets(node_id, param_index)) else {
+            return;
+        };
+        mapping.set_options(options);
+        self.user_state.set_midi_mapping(mapping);
+        self.controller_states.remove(&(node_id, param_index));
+        self.dirty.mark_dirty();
+    }
+
+    /// Start or stop audio processing, starting an armed recording.
+    fn toggle_playing(&mut self) {
+        self.is_playing = !self.is_playing;
+        self.user_state.is_playing = self.is_playing;
+        self.send_command(EngineCommand::SetPlaying(self.is_playing));
+        if self.is_playing && self.record_state == RecordState::Armed {
+            self.start_recording();
+        }
+    }
+
     /// Start MIDI Learn mode for a parameter.
     pub fn start_midi_learn(&mut self, target: MidiLearnTarget) {
+        self.status_message = Some(match target.action {
+            Some(action) => format!("Press a MIDI button or pad for {}...", action.label()),
+            None => "Move a MIDI control to map it (turn encoders both ways)...".to_string(),
+        });
         self.midi_learn_target = Some(target);
-        self.status_message = Some("Move a MIDI CC to map it...".to_string());
+        self.midi_learner.reset();
     }
 
     /// Cancel MIDI Learn mode.
     pub fn cancel_midi_learn(&mut self) {
         self.midi_learn_target = None;
+        self.midi_learner.reset();
         self.user_state.midi_learn_active = false;
         self.user_state.midi_learn_target = None;
         self.status_message = Some("MIDI Learn cancelled".to_string());
@@ -454,20 +1541,20 @@ impl SynthApp {
     /// Get the MIDI mapping for a specific parameter, if any.
     pub fn get_mapping_for_param(&self, node_id: u64, param_index: usize) -> Option<&MidiMapping> {
         self.midi_mappings.iter()
-            .find(|m| m.node_id == node_id && m.param_index == param_index)
+            .find(|m| m.targets(node_id, param_index))
     }
 
     /// Remove MIDI mapping for a specific parameter.
     pub fn clear_mapping_for_param(&mut self, node_id: u64, param_index: usize) {
-        self.midi_mappings.retain(|m| {
-            !(m.node_id == node_id && m.param_index == param_index)
-        });
+        self.midi_mappings.retain(|m| !m.targets(node_id, param_index));
+        self.controller_states.remove(&(node_id, param_index));
         self.status_message = Some("MIDI mapping cleared".to_string());
     }
 
     /// Clear all MIDI mappings.
     pub fn clear_all_midi_mappings(&mut self) {
         self.midi_mappings.clear();
+        self.controller_states.clear();
         self.status_message = Some("All MIDI mappings cleared".to_string());
     }
 
@@ -481,59 +1568,32 @@ impl SynthApp {
     const MIN_MIDI_GATE_DURATION_MS: u64 = 30;
 
     /// Sync the current MIDI note state to all MIDI Note modules in the graph.
+    ///
+    /// Each module only sees the notes that pass its Channel and Port filter;
+    /// modules with the same filter share one voice.
     fn sync_midi_note_modules(&mut self) {
-        // Determine the active note based on voice priority (for now, always use "Last" priority)
-        let (active_note, active_velocity, should_gate_on) = if let Some(&(note, velocity, _channel)) = self.midi_held_notes.last() {
-            (note, velocity, true)
-        } else {
-            (self.midi_last_triggered_note, self.midi_last_velocity, false)
-        };
-
-        // Determine actual gate state considering minimum duration
-        let gate_value = if should_gate_on {
-            // Note is pressed - gate should be on
-            if !self.midi_gate_held_high {
-                // New note trigger (gate was off)
-                self.midi_last_gate_on = Some(Instant::now());
-                self.midi_gate_held_high = true;
-            }
-            // Always update to the most recent note ("Last" priority)
-            self.midi_last_triggered_note = active_note;
-            self.midi_last_velocity = active_velocity;
-            1.0
-        } else if self.midi_gate_held_high {
-            // Note released but check minimum duration
-            if let Some(gate_time) = self.midi_last_gate_on {
-                let elapsed_ms = gate_time.elapsed().as_millis() as u64;
-                if elapsed_ms < Self::MIN_MIDI_GATE_DURATION_MS {
-                    // Keep gate high until minimum duration
-                    1.0
-                } else {
-                    // Minimum duration passed, can release
-                    self.midi_gate_held_high = false;
-                    self.midi_last_gate_on = None;
-                    0.0
-                }
-            } else {
-                self.midi_gate_held_high = false;
-                0.0
-            }
-        } else {
-            0.0
-        };
-
-        // Collect MIDI Note module engine IDs first to avoid borrow issues
-        let midi_note_nodes: Vec<u64> = self.graph_state.graph.nodes.iter()
+        // Collect MIDI Note module engine IDs and filters first to avoid borrow issues
+        let midi_note_nodes: Vec<(u64, MidiNoteFilter)> = self.graph_state.graph.nodes.iter()
             .filter(|(_, node)| node.user_data.module_id == "input.midi_note")
             .filter_map(|(node_id, _)| self.user_state.get_engine_node_id(node_id))
+            .map(|engine_node_id| (engine_node_id, self.midi_note_filter(engine_node_id)))
             .collect();
 
-        // Use the triggered note when gate is high, otherwise active_note
-        let note_to_send = if self.midi_gate_held_high { self.midi_last_triggered_note } else { active_note };
-        let velocity_to_send = if self.midi_gate_held_high { self.midi_last_velocity } else { active_velocity };
+        // Work out each voice once, however many modules share it
+        let mut voice_values: HashMap<MidiNoteFilter, (u8, f32, u8, u8)> = HashMap::new();
+        for &(_, filter) in &midi_note_nodes {
+            if !voice_values.contains_key(&filter) {
+                let values = self.update_midi_voice(filter);
+                voice_values.insert(filter, values);
+            }
+        }
+        // Forget voices no module listens to any more
+        self.midi_voices.retain(|filter, _| voice_values.contains_key(filter));
 
         // Update all MIDI Note modules
-        for engine_node_id in midi_note_nodes {
+        for (engine_node_id, filter) in midi_note_nodes {
+            let (note_to_send, gate_value, velocity_to_send, aftertouch) = voice_values[&filter];
+
             // Update Note parameter (param index 0)
             self.send_command(EngineCommand::SetParameter {
                 node_id: engine_node_id,
@@ -559,48 +1619,207 @@ impl SynthApp {
             self.send_command(EngineCommand::SetParameter {
                 node_id: engine_node_id,
                 param_index: MidiNote::PARAM_AFTERTOUCH,
-                value: self.midi_aftertouch as f32,
+                value: aftertouch as f32,
             });
 
             // Also update the cached params so sync_parameters doesn't overwrite
             self.cached_params.insert((engine_node_id, MidiNote::PARAM_NOTE), note_to_send as f32);
             self.cached_params.insert((engine_node_id, MidiNote::PARAM_GATE), gate_value);
             self.cached_params.insert((engine_node_id, MidiNote::PARAM_VELOCITY), velocity_to_send as f32);
-            self.cached_params.insert((engine_node_id, MidiNote::PARAM_AFTERTOUCH), self.midi_aftertouch as f32);
+            self.cached_params.insert((engine_node_id, MidiNote::PARAM_AFTERTOUCH), aftertouch as f32);
         }
 
         // Update active notes for piano display
         let active_notes: Vec<u8> = self.midi_held_notes.iter()
-            .map(|(note, _, _)| *note)
+            .map(|(note, _, _, _)| *note)
             .collect();
         self.user_state.set_midi_active_notes(active_notes);
     }
 
-    /// Check if MIDI gate needs to be released after minimum duration.
-    fn update_midi_gate_timing(&mut self) {
-        if self.midi_gate_held_high && self.midi_held_notes.is_empty() {
-            // Note was released, check if we should release the gate now
-            if let Some(gate_time) = self.midi_last_gate_on {
-                if gate_time.elapsed().as_millis() as u64 >= Self::MIN_MIDI_GATE_DURATION_MS {
-                    // Time to release
-                    self.sync_midi_note_modules();
+    /// The Channel and input filter of a MIDI Note module.
+    fn midi_note_filter(&self, engine_node_id: u64) -> MidiNoteFilter {
+        let channel = self.cached_params
+            .get(&(engine_node_id, MidiNote::PARAM_CHANNEL))
+            .map_or(0, |value| value.round() as u8);
+        (channel, self.midi_input_port(engine_node_id))
+    }
+
+    /// Choose the MIDI input device a MIDI Note or MPE Voice module listens
+    /// to (None = any input).
+    fn set_node_midi_input(&mut self, engine_node_id: u64, device: Option<String>) {
+        match device {
+            Some(device) => {
+                self.user_state.node_midi_inputs.insert(engine_node_id, device);
+            }
+            None => {
+                self.user_state.node_midi_inputs.remove(&engine_node_id);
+            }
+        }
+        // Show the notes held on the new input
+        self.sync_midi_note_modules();
+    }
+
+    /// The input port a MIDI Note or MPE Voice module listens to now (0 = any).
+    ///
+    /// Modules choose their input by device name; this finds the port number
+    /// the device is connected on, or `DISCONNECTED_PORT` if it isn't.
+    fn midi_input_port(&self, engine_node_id: u64) -> u8 {
+        match self.user_state.node_midi_inputs.get(&engine_node_id) {
+            None => 0,
+            Some(name) => self.midi_inputs
+                .iter()
+                .find(|input| &input.name == name)
+                .map_or(DISCONNECTED_PORT, |input| input.port),
+        }
+    }
+
+    /// Advance the voice of a filter to the held notes.
+    ///
+    /// Returns the (note, gate, velocity, aftertouch) to send to its modules.
+    fn update_midi_voice(&mut self, filter: MidiNoteFilter) -> (u8, f32, u8, u8) {
+        // Determine the active note based on voice priority (for now, always use "Last" priority)
+        let held = self.midi_held_notes.iter()
+            .rev()
+            .find(|&&(_, _, channel, port)| filter_accepts(filter, channel, port))
+            .map(|&(note, velocity, _, _)| (note, velocity));
+        let voice = self.midi_voices.entry(filter).or_default();
+        let (active_note, active_velocity, should_gate_on) = match held {
+            Some((note, velocity)) => (note, velocity, true),
+            None => (voice.note, voice.velocity, false),
+        };
+
+        // Determine actual gate state considering minimum duration
+        let gate_value = if should_gate_on {
+            // Note is pressed - gate should be on
+            if !voice.gate_held_high {
+                // New note trigger (gate was off)
+                voice.gate_on = Some(Instant::now());
+                voice.gate_held_high = true;
+            }
+            // Always update to the most recent note ("Last" priority)
+            voice.note = active_note;
+            voice.velocity = active_velocity;
+            1.0
+        } else if voice.gate_held_high {
+            // Note released but check minimum duration
+            if let Some(gate_time) = voice.gate_on {
+                let elapsed_ms = gate_time.elapsed().as_millis() as u64;
+                if elapsed_ms < Self::MIN_MIDI_GATE_DURATION_MS {
+                    // Keep gate high until minimum duration
+                    1.0
+                } else {
+                    // Minimum duration passed, can release
+                    voice.gate_held_high = false;
+                    voice.gate_on = None;
+                    0.0
                 }
+            } else {
+                voice.gate_held_high = false;
+                0.0
             }
+        } else {
+            0.0
+        };
+
+        // Use the triggered note when gate is high, otherwise active_note
+        let note_to_send = if voice.gate_held_high { voice.note } else { active_note };
+        let velocity_to_send = if voice.gate_held_high { voice.velocity } else { active_velocity };
+        (note_to_send, gate_value, velocity_to_send, voice.aftertouch)
+    }
+
+    /// Check if MIDI gate needs to be released after minimum duration.
+    fn update_midi_gate_timing(&mut self) {
+        // A voice whose notes were all released, waiting for its minimum gate time
+        let release_due = self.midi_voices.iter().any(|(&filter, voice)| {
+            voice.gate_held_high
+                && !self.midi_held_notes
+                    .iter()
+                    .any(|&(_, _, channel, port)| filter_accepts(filter, channel, port))
+                && voice.gate_on.is_none_or(|gate_time| {
+                    gate_time.elapsed().as_millis() as u64 >= Self::MIN_MIDI_GATE_DURATION_MS
+                })
+        });
+        if release_due {
+            // Time to release
+            self.sync_midi_note_modules();
         }
     }
 
     /// Select an audio output device by index
     fn select_device(&mut self, index: usize) {
+        if self.record_state == RecordState::Recording {
+            self.stop_recording();
+        }
+
         if let Ok(ref mut engine) = self.audio_engine {
             match engine.select_device(index) {
                 Ok(()) => {
                     self.selected_device_index = index;
                     self.audio_error_message = None;
+
+                    // The device may not support the chosen sample rate
+                    self.config.audio_output = Some(engine.current_device_name());
+                    self.config.sample_rate = engine.settings().sample_rate;
+                    if let Some(recorder) = &mut self.recorder {
+                        recorder.set_sample_rate(engine.sample_rate());
+                    }
+                    self.audio_settings.refresh(engine);
+                    self.save_config();
+                }
+                Err(e) => {
+                    self.audio_error_message = Some(e.to_string());
+                }
+            }
+        }
+    }
+
+    /// Change the sample rate and buffer size, keeping the patch playing.
+    fn apply_stream_settings(&mut self, settings: StreamSettings) {
+        if self.record_state == RecordState::Recording {
+            self.stop_recording();
+        }
+
+        let Ok(ref mut engine) = self.audio_engine else {
+            return;
+        };
+        match engine.set_stream_settings(settings) {
+            Ok(()) => {
+                self.audio_error_message = None;
+                self.config.sample_rate = settings.sample_rate;
+                self.config.buffer_size = settings.buffer_size;
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.set_sample_rate(engine.sample_rate());
+                }
+                self.status_message = Some(format!(
+                    "Audio running at {} Hz, {} frame buffer",
+                    engine.sample_rate(),
+                    engine.block_size()
+                ));
+                self.save_config();
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Audio settings not applied: {}", e));
+            }
+        }
+    }
+
+    /// Select an audio input device by index, or `None` to close the input
+    fn select_input_device(&mut self, index: Option<usize>) {
+        if let Ok(ref mut engine) = self.audio_engine {
+            match engine.select_input_device(index) {
+                Ok(()) => {
+                    self.selected_input_device = index;
+                    self.audio_error_message = None;
+                    self.config.audio_input = engine.input_device_name();
+                    self.save_config();
                 }
                 Err(e) => {
+                    self.selected_input_device = None;
                     self.audio_error_message = Some(e.to_string());
+                    self.status_message = Some(format!("Audio input unavailable: {}", e));
                 }
             }
+            self.input_levels = [0.0; MAX_INPUT_CHANNELS];
         }
     }
 
@@ -647,6 +1866,11 @@ impl SynthApp {
                 actions.toggle_playing = true;
             }
 
+            if self.recorder.is_some() {
+                ui.add_space(8.0);
+                self.draw_record_controls(ui, &mut actions);
+            }
+
             ui.add_space(20.0);
             ui.separator();
             ui.add_space(20.0);
@@ -671,6 +1895,85 @@ impl SynthApp {
                 actions.save_as_patch = true;
             }
 
+            // Hot-reload toggle: re-apply the patch file whenever it changes on disk
+            let watching = self.watched_file.is_some();
+            let watch_text = if watching {
+                RichText::new("👁 Watching").color(theme::accent::SUCCESS)
+            } else {
+                RichText::new("👁 Watch")
+            };
+            if ui.add_enabled(
+                self.current_patch_path.is_some(),
+                egui::SelectableLabel::new(watching, watch_text),
+            )
+                .on_hover_text("Reload the patch file when it changes on disk")
+                .on_disabled_hover_text("Save or open a patch file to watch it")
+                .clicked()
+            {
+                actions.toggle_watch = true;
+            }
+
+            if ui.button("ℹ Info").on_hover_text("Edit author, description and tags").clicked() {
+                actions.toggle_patch_info = true;
+            }
+
+            if ui.add(egui::SelectableLabel::new(self.library_browser.open, "📚 Library"))
+                .on_hover_text("Browse and search saved patches")
+                .clicked()
+            {
+                actions.toggle_library = true;
+            }
+
+            if ui.add(egui::SelectableLabel::new(self.scenes_window.open, "🎬 Scenes"))
+                .on_hover_text("Capture, recall and morph parameter scenes")
+                .clicked()
+            {
+                actions.toggle_scenes = true;
+            }
+
+            if ui.add(egui::SelectableLabel::new(self.setlist_window.open, "📋 Setlist"))
+                .on_hover_text("Patches and scenes for MIDI program changes to switch between")
+                .clicked()
+            {
+                actions.toggle_setlist = true;
+            }
+
+            if ui.add(egui::SelectableLabel::new(self.randomizer.open, "🎲 Randomize"))
+                .on_hover_text("Randomize or mutate parameter values")
+                .clicked()
+            {
+                actions.toggle_randomizer = true;
+            }
+
+            ui.menu_button("🎼 Tuning", |ui| {
+                let current = self.tuning.as_ref().map_or_else(|| "12-TET".to_string(), TuningData::label);
+                ui.label(RichText::new(current).color(theme::text::SECONDARY));
+                ui.separator();
+                if ui.button("Load Scale…").on_hover_text("Tune the note modules to a Scala scale (.scl)").clicked() {
+                    actions.load_scale = true;
+                    ui.close_menu();
+                }
+                if ui.add_enabled(self.tuning.is_some(), egui::Button::new("Load Keyboard Mapping…"))
+                    .on_hover_text("Choose which keys play which scale degrees (.kbm)")
+                    .on_disabled_hover_text("Load a scale first")
+                    .clicked()
+                {
+                    actions.load_mapping = true;
+                    ui.close_menu();
+                }
+                if ui.add_enabled(self.tuning.is_some(), egui::Button::new("Reset to 12-TET")).clicked() {
+                    actions.reset_tuning = true;
+                    ui.close_menu();
+                }
+                ui.label(
+                    RichText::new("Right-click a note module's icon to tune it on its own")
+                        .small()
+                        .color(theme::text::DISABLED),
+                );
+            })
+            .response
+            .on_hover_text("Tuning of the MIDI Note, Keyboard, MIDI File Player and Step Sequencer modules");
+
             ui.add_space(20.0);
             ui.separator();
             ui.add_space(20.0);
@@ -722,6 +2025,26 @@ impl SynthApp {
                             }
                         });
 
+                    if ui.add(egui::SelectableLabel::new(self.routing_window.open, "🔀 Routing"))
+                        .on_hover_text("Route the master and Multi Output channels to device channels")
+                        .clicked()
+                    {
+                        actions.toggle_routing = true;
+                    }
+
+                    if ui.add(egui::SelectableLabel::new(self.audio_settings.open, "⚙ Audio"))
+                        .on_hover_text("Sample rate and buffer size")
+                        .clicked()
+                    {
+                        actions.toggle_audio_settings = true;
+                    }
+
+                    ui.add_space(20.0);
+                    ui.separator();
+                    ui.add_space(20.0);
+
+                    self.draw_input_selector(ui, engine, &mut actions);
+
                     ui.add_space(20.0);
                     ui.separator();
                     ui.add_space(20.0);
@@ -730,58 +2053,167 @@ impl SynthApp {
                     ui.label(RichText::new("MIDI In").color(theme::text::SECONDARY));
                     ui.add_space(8.0);
 
-                    // Get current MIDI device name for display
-                    let current_midi = self.selected_midi_device
-                        .and_then(|idx| self.midi_devices.get(idx))
-                        .map(|d| d.name.as_str())
-                        .unwrap_or("None");
+                    // Current MIDI inputs for display
+                    let current_midi = match self.midi_inputs.as_slice() {
+                        [] => "None".to_string(),
+                        [input] => input.name.clone(),
+                        inputs => format!("{} inputs", inputs.len()),
+                    };
 
                     // Truncate long device names
                     let midi_display_name = if current_midi.len() > 25 {
                         format!("{}...", &current_midi[..22])
                     } else {
-                        current_midi.to_string()
+                        current_midi
                     };
 
                     // MIDI connection indicator
-                    let midi_connected = self.selected_midi_device.is_some()
-                        && self.midi_engine.as_ref().map(|e| e.is_connected()).unwrap_or(false);
+                    let midi_connected = self.midi_engine.as_ref().map(|e| e.is_connected()).unwrap_or(false);
                     let midi_indicator = if midi_connected { "● " } else { "○ " };
 
+                    // Stays open while ticking devices, so several can be chosen
                     egui::ComboBox::from_id_salt("midi_device_selector")
                         .selected_text(format!("{}{}", midi_indicator, midi_display_name))
                         .width(180.0)
+                        .close_behavior(egui::PopupCloseBehavior::CloseOnClickOutside)
                         .show_ui(ui, |ui| {
-                            // Option to disconnect / select none
+                            // Option to disconnect everything
                             if ui.selectable_label(
-                                self.selected_midi_device.is_none(),
-                                "None (Disconnect)"
+                                self.midi_inputs.is_empty(),
+                                "None (Disconnect all)"
                             ).clicked() {
                                 actions.disconnect_midi = true;
                             }
 
                             ui.separator();
 
-                            // List available MIDI devices
+                            // List available MIDI devices, numbered by input port once connected
                             if self.midi_devices.is_empty() {
                                 ui.label(RichText::new("No MIDI devices found")
                                     .color(theme::text::DISABLED)
                                     .italics());
                             } else {
                                 for device in &self.midi_devices {
-                                    let is_selected = self.selected_midi_device == Some(device.index);
-                                    if ui.selectable_label(is_selected, &device.name).clicked() {
-                                        actions.connect_midi_device = Some(device.index);
+                                    let input = self.midi_inputs.iter().find(|input| input.name == device.name);
+                                    let mut checked = input.is_some();
+                                    let label = match input {
+                                        Some(input) => format!("{}: {}", input.port, device.name),
+                                        None => device.name.clone(),
+                                    };
+                                    if ui.checkbox(&mut checked, label).changed() {
+                                        if checked {
+                                            actions.connect_midi_device = Some(device.index);
+                                        } else {
+                                            actions.disconnect_midi_device = Some(device.name.clone());
+                                        }
                                     }
                                 }
                             }
 
+                            // Inputs whose device is unplugged keep their port until removed
+                            let missing = self.midi_inputs
+                                .iter()
+                                .filter(|input| !input.is_virtual)
+                                .filter(|input| !self.midi_devices.iter().any(|d| d.name == input.name));
+                            for input in missing {
+                                let mut checked = true;
+                                let label = RichText::new(format!("{}: {} (unplugged)", input.port, input.name))
+                                    .color(theme::text::DISABLED);
+                                if ui.checkbox(&mut checked, label)
+                                    .on_hover_text("Reconnects when the device is plugged back in")
+                                    .changed()
+                                {
+                                    actions.disconnect_midi_device = Some(input.name.clone());
+                                }
+                            }
+
+                            // A port other applications can send MIDI to
+                            ui.separator();
+                            let virtual_input = self.midi_inputs.iter().find(|input| input.is_virtual);
+                            let mut virtual_open = virtual_input.is_some();
+                            let label = match virtual_input {
+                                Some(input) => format!("{}: {} (virtual)", input.port, input.name),
+                                None => "Virtual port".to_string(),
+                            };
+                            if ui.checkbox(&mut virtual_open, label)
+                                .on_hover_text("Publish a MIDI input that DAWs, sequencers and scripts can send to")
+                                .changed()
+                            {
+                                actions.toggle_virtual_midi = true;
+                            }
+
                             ui.separator();
                             if ui.button("🔄 Refresh").clicked() {
                                 actions.refresh_midi_devices = true;
                             }
                         });
 
+                    // Record the incoming MIDI to a MIDI file
+                    let midi_recording = self.midi_recorder.is_recording();
+                    let label = if midi_recording {
+                        format!("⏹ {} events", self.midi_recorder.event_count())
+                    } else {
+                        "⏺ MIDI".to_string()
+                    };
+                    if ui.add(egui::SelectableLabel::new(midi_recording, RichText::new(label).color(theme::accent::ERROR)))
+                        .on_hover_text(if midi_recording {
+                            "Stop recording MIDI and save the file".to_string()
+                        } else {
+                            format!("Record the MIDI inputs to a MIDI file\nSaved to {}", paths::recordings_dir().display())
+                        })
+                        .clicked()
+                    {
+                        actions.toggle_midi_recording = true;
+                    }
+
+                    // Buttons and pads that run app actions
+                    ui.menu_button("🎛 Actions", |ui| {
+                        ui.label(RichText::new("Map a button or pad to").small().color(theme::text::SECONDARY));
+                        egui::Grid::new("midi_actions_grid").num_columns(3).show(ui, |ui| {
+                            for action in MidiAction::ALL {
+                                let mapping = self.midi_mappings.iter().find(|m| m.action == Some(action));
+                                let learning = self.midi_learn_target
+                                    .as_ref()
+                                    .is_some_and(|target| target.action == Some(action));
+                                ui.label(action.label());
+                                let control = match mapping {
+                                    _ if learning => "Press a button…".to_string(),
+                                    Some(mapping) => mapping.control_label(),
+                                    None => "—".to_string(),
+                                };
+                                ui.label(RichText::new(control).color(theme::text::SECONDARY));
+                                ui.horizontal(|ui| {
+                                    if learning {
+                                        if ui.small_button("Cancel").clicked() {
+                                            actions.cancel_midi_learn = true;
+                                        }
+                                    } else if ui.small_button("Learn").clicked() {
+                                        actions.learn_midi_action = Some(action);
+                                    }
+                                    if mapping.is_some() && ui.small_button("Clear").clicked() {
+                                        actions.clear_midi_action = Some(action);
+                                    }
+                                });
+                                ui.end_row();
+                            }
+                        });
+                    });
+
+                    ui.add_space(12.0);
+                    self.draw_midi_output_selector(ui, &mut actions);
+
+                    // Tempo and transport of an external MIDI clock, once one is received
+                    if let Some(tempo) = self.midi_clock.tempo_bpm() {
+                        ui.add_space(8.0);
+                        let (symbol, color) = if self.midi_clock.is_playing() {
+                            ("▶", theme::accent::SUCCESS)
+                        } else {
+                            ("■", theme::text::SECONDARY)
+                        };
+                        ui.label(RichText::new(format!("{} MIDI Clock {:.1} BPM", symbol, tempo)).color(color).small())
+                            .on_hover_text("Tempo of the incoming MIDI clock, followed by Clock modules set to the MIDI source");
+                    }
+
                     // Status indicator (right-to-left layout: items appear from right to left)
                     ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                         // Running status (rightmost)
@@ -818,6 +2250,351 @@ impl SynthApp {
         actions
     }
 
+    /// Draw the MIDI output selector: devices and the virtual port that the
+    /// MIDI output modules send to.
+    fn draw_midi_output_selector(&self, ui: &mut egui::Ui, actions: &mut ToolbarActions) {
+        ui.label(RichText::new("MIDI Out").color(theme::text::SECONDARY));
+        ui.add_space(8.0);
+
+        let current = match self.midi_outputs.as_slice() {
+            [] => "None".to_string(),
+            [output] => output.name.clone(),
+            outputs => format!("{} outputs", outputs.len()),
+        };
+
+        // Truncate long device names
+        let display_name = if current.len() > 25 {
+            format!("{}...", &current[..22])
+        } else {
+            current
+        };
+
+        let connected = self.midi_output.as_ref().is_some_and(|e| e.is_connected());
+        let indicator = if connected { "● " } else { "○ " };
+
+        // Stays open while ticking devices, so several can be chosen
+        egui::ComboBox::from_id_salt("midi_output_selector")
+            .selected_text(format!("{}{}", indicator, display_name))
+            .width(180.0)
+            .close_behavior(egui::PopupCloseBehavior::CloseOnClickOutside)
+            .show_ui(ui, |ui| {
+                if self.midi_output.is_none() {
+                    ui.label(RichText::new("MIDI output unavailable")
+                        .color(theme::text::DISABLED)
+                        .italics());
+                    return;
+                }
+
+                // Every output receives all MIDI sent by the patch
+                if self.midi_output_devices.is_empty() {
+                    ui.label(RichText::new("No MIDI outputs found")
+                        .color(theme::text::DISABLED)
+                        .italics());
+                }
+                for device in &self.midi_output_devices {
+                    let mut checked = self.midi_outputs.iter().any(|output| output.name == device.name);
+                    if ui.checkbox(&mut checked, device.name.as_str()).changed() {
+                        if checked {
+                            actions.connect_midi_output = Some(device.index);
+                        } else {
+                            actions.disconnect_midi_output = Some(device.name.clone());
+                        }
+                    }
+                }
+
+                // Outputs whose device is unplugged stay chosen until removed
+                let missing = self.midi_outputs
+                    .iter()
+                    .filter(|output| !output.is_virtual)
+                    .filter(|output| !self.midi_output_devices.iter().any(|d| d.name == output.name));
+                for output in missing {
+                    let mut checked = true;
+                    let label = RichText::new(format!("{} (unplugged)", output.name))
+                        .color(theme::text::DISABLED);
+                    if ui.checkbox(&mut checked, label)
+                        .on_hover_text("Reconnects when the device is plugged back in")
+                        .changed()
+                    {
+                        actions.disconnect_midi_output = Some(output.name.clone());
+                    }
+                }
+
+                // A port other applications can receive MIDI from
+                ui.separator();
+                let virtual_output = self.midi_outputs.iter().find(|output| output.is_virtual);
+                let mut virtual_open = virtual_output.is_some();
+                let label = match virtual_output {
+                    Some(output) => format!("{} (virtual)", output.name),
+                    None => "Virtual port".to_string(),
+                };
+                if ui.checkbox(&mut virtual_open, label)
+                    .on_hover_text("Publish a MIDI output that DAWs and other synths can receive from")
+                    .changed()
+                {
+                    actions.toggle_virtual_midi_output = true;
+                }
+
+                // Mapped values go back to the controller on an output of its own
+                ui.separator();
+                ui.label(RichText::new("Controller feedback").color(theme::text::SECONDARY).small());
+                let feedback = self.midi_output.as_ref().and_then(|engine| engine.feedback_output());
+                let current = feedback.as_ref().map(|output| output.name.as_str());
+                if ui.radio(current.is_none(), "None").clicked() {
+                    actions.select_feedback_output = Some(None);
+                }
+                for device in &self.midi_output_devices {
+                    if ui.radio(current == Some(device.name.as_str()), device.name.as_str()).clicked() {
+                        actions.select_feedback_output = Some(Some(device.name.clone()));
+                    }
+                }
+                if let Some(output) = feedback.as_ref().filter(|output| !output.connected) {
+                    ui.label(RichText::new(format!("{} (unplugged)", output.name)).color(theme::text::DISABLED));
+                }
+                if ui.add_enabled(feedback.is_some(), egui::Button::new("⟳ Resend Values"))
+                    .on_hover_text("Send every mapped value to the controller again")
+                    .clicked()
+                {
+                    actions.resend_feedback = true;
+                }
+
+                ui.separator();
+                if ui.button("🔄 Refresh").clicked() {
+                    actions.refresh_midi_devices = true;
+                }
+            });
+    }
+
+    /// Draw the audio input device selector and its level meter.
+    fn draw_input_selector(&self, ui: &mut egui::Ui, engine: &AudioEngine, actions: &mut ToolbarActions) {
+        ui.label(RichText::new("Input").color(theme::text::SECONDARY));
+        ui.add_space(8.0);
+
+        let current_input = self.selected_input_device
+            .and_then(|idx| self.input_devices.iter().find(|d| d.index == idx))
+            .map(|d| d.name.as_str())
+            .unwrap_or("None");
+
+        // Truncate long device names
+        let display_name = if current_input.len() > 25 {
+            format!("{}...", &current_input[..22])
+        } else {
+            current_input.to_string()
+        };
+
+        egui::ComboBox::from_id_salt("input_device_selector")
+            .selected_text(display_name)
+            .width(160.0)
+            .show_ui(ui, |ui| {
+                if ui.selectable_label(self.selected_input_device.is_none(), "None").clicked() {
+                    actions.select_input_device = Some(None);
+                }
+
+                ui.separator();
+
+                if self.input_devices.is_empty() {
+                    ui.label(RichText::new("No input devices found")
+                        .color(theme::text::DISABLED)
+                        .italics());
+                }
+                for device in &self.input_devices {
+                    let label = if device.is_default {
+                        format!("{} (Default)", device.name)
+                    } else {
+                        device.name.clone()
+                    };
+
+                    if ui.selectable_label(
+                        self.selected_input_device == Some(device.index),
+                        label
+                    ).clicked() {
+                        actions.select_input_device = Some(Some(device.index));
+                    }
+                }
+
+                ui.separator();
+                if ui.button("🔄 Refresh").clicked() {
+                    actions.refresh_devices = true;
+                }
+            });
+
+        if self.selected_input_device.is_some() {
+            ui.add_space(4.0);
+            let channels = self.input_level_channels.clamp(1, MAX_INPUT_CHANNELS);
+            vu_meter(ui, &self.input_levels[..channels], &VuMeterConfig::compact());
+
+            let (overruns, underruns) = engine.input_xruns();
+            if overruns > 0 || underruns > 0 {
+                ui.label(RichText::new("⚠").color(theme::accent::WARNING))
+                    .on_hover_text(format!(
+                        "Input and output clocks drift apart: {} samples dropped, {} frames of silence inserted",
+                        overruns, underruns
+                    ));
+            }
+        }
+    }
+
+    /// Draw the arm/record/stop controls, the recording source and the elapsed time.
+    fn draw_record_controls(&self, ui: &mut egui::Ui, actions: &mut ToolbarActions) {
+        let Some(recorder) = &self.recorder else {
+            return;
+        };
+
+        if self.record_state == RecordState::Recording {
+            if ui.button(RichText::new("⏹ Stop Rec").color(theme::accent::ERROR))
+                .on_hover_text("Stop recording and save the file")
+                .clicked()
+            {
+                actions.stop_recording = true;
+            }
+            ui.label(RichText::new(format!("● {}", format_elapsed(recorder.elapsed())))
+                .color(theme::accent::ERROR)
+                .monospace());
+
+            let dropped = recorder.dropped_frames();
+            if dropped > 0 {
+                ui.label(RichText::new(format!("⚠ {} dropped", dropped)).color(theme::accent::WARNING))
+                    .on_hover_text("Samples were lost because the disk could not keep up");
+            }
+            return;
+        }
+
+        let armed = self.record_state == RecordState::Armed;
+        if ui.add(egui::SelectableLabel::new(armed, RichText::new("⏺ Arm").color(theme::accent::ERROR)))
+            .on_hover_text("Start recording when playback starts")
+            .clicked()
+        {
+            actions.toggle_arm = true;
+        }
+        if ui.button(RichText::new("⏺ Rec").color(theme::accent::ERROR))
+            .on_hover_text(format!("Start recording now\nSaved to {}", recorder.dir().display()))
+            .clicked()
+        {
+            actions.start_recording = true;
+        }
+
+        let sources = self.record_sources();
+        let selected = sources
+            .iter()
+            .find(|(source, _)| *source == self.record_source)
+            .map(|(_, label)| label.as_str())
+            .unwrap_or("Master");
+        egui::ComboBox::from_id_salt("record_source")
+            .selected_text(selected)
+            .width(140.0)
+            .show_ui(ui, |ui| {
+                for (source, label) in &sources {
+                    if ui.selectable_label(*source == self.record_source, label.as_str()).clicked() {
+                        actions.record_source = Some(*source);
+                    }
+                }
+            })
+            .response
+            .on_hover_text("What to record: the master output or any module output");
+    }
+
+    /// Everything the toolbar recording can tap: the master output, then every
+    /// module output in the order the modules were added.
+    fn record_sources(&self) -> Vec<(RecordSource, String)> {
+        let mut outputs: Vec<(RecordSource, String)> = Vec::new();
+        for (graph_node_id, node) in self.graph_state.graph.nodes.iter() {
+            let Some(&engine_id) = self.user_state.node_id_map.get(&graph_node_id) else {
+                continue;
+            };
+            for (output_index, (name, _)) in node.outputs.iter().enumerate() {
+                let source = RecordSource::Output { node_id: engine_id, output_index };
+                outputs.push((source, format!("{} › {}", node.label, name)));
+            }
+        }
+        outputs.sort_by_key(|(source, _)| match source {
+            RecordSource::Output { node_id, output_index } => (*node_id, *output_index),
+            RecordSource::Master => (0, 0),
+        });
+
+        let mut sources = vec![(RecordSource::Master, "Master".to_string())];
+        sources.extend(outputs);
+        sources
+    }
+
+    /// Start the toolbar recording, starting playback if needed.
+    fn start_recording(&mut self) {
+        let name = format!("{}-{}", self.current_patch_name(), file_timestamp());
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        let take = recorder.begin_take(&name);
+
+        // A tapped module that was removed falls back to the master output
+        if !self.record_sources().iter().any(|(source, _)| *source == self.record_source) {
+            self.record_source = RecordSource::Master;
+        }
+        self.send_command(EngineCommand::StartRecording { source: self.record_source, take });
+        self.record_state = RecordState::Recording;
+
+        if !self.is_playing {
+            self.is_playing = true;
+            self.user_state.is_playing = true;
+            self.send_command(EngineCommand::SetPlaying(true));
+        }
+    }
+
+    /// Stop the toolbar recording; the writer reports when the file is saved.
+    fn stop_recording(&mut self) {
+        self.send_command(EngineCommand::StopRecording);
+        self.record_state = RecordState::Idle;
+    }
+
+    /// Report recordings saved (or failed) by the writer thread.
+    fn poll_recorder(&mut self) {
+        let Some(recorder) = &self.recorder else {
+            return;
+        };
+
+        for event in recorder.poll_events() {
+            match event {
+                RecorderEvent::Saved { path, frames } => {
+                    let length = Duration::from_secs_f64(frames as f64 / f64::from(recorder.sample_rate().max(1)));
+                    self.status_message = Some(format!(
+                        "Saved recording ({}): {}",
+                        format_elapsed(length),
+                        path.display()
+                    ));
+                }
+                RecorderEvent::Failed(e) => {
+                    eprintln!("Recording failed: {}", e);
+                    self.status_message = Some(format!("Recording failed: {}", e));
+                }
+            }
+        }
+    }
+
+    /// Start recording the MIDI inputs, or stop and save the MIDI file.
+    fn toggle_midi_recording(&mut self) {
+        let now = Instant::now();
+        if !self.midi_recorder.is_recording() {
+            self.midi_recorder.start(now);
+            self.status_message = Some("Recording MIDI...".to_string());
+            return;
+        }
+
+        let midi_engine = self.midi_engine.as_ref();
+        let Some(file) = self.midi_recorder.stop(now, |port| {
+            midi_engine.and_then(|engine| engine.port_name(port)).map(str::to_string)
+        }) else {
+            self.status_message = Some("No MIDI recorded".to_string());
+            return;
+        };
+
+        let dir = paths::recordings_dir();
+        let path = dir.join(format!("{}-{}.mid", self.current_patch_name(), file_timestamp()));
+        let result = std::fs::create_dir_all(&dir)
+            .map_err(SmfError::from)
+            .and_then(|()| file.save(&path));
+        match result {
+            Ok(()) => self.status_message = Some(format!("Saved MIDI recording: {}", path.display())),
+            Err(e) => self.status_message = Some(format!("Saving MIDI recording failed: {}", e)),
+        }
+    }
+
     /// Send a command to the audio engine.
     fn send_command(&mut self, cmd: EngineCommand) {
         if let Some(ref mut handle) = self.ui_handle {
@@ -856,8 +2633,17 @@ impl SynthApp {
                         // Update CPU load for display
                         self.cpu_load = load;
                     }
+                    crate::engine::EngineEvent::InputLevel { peaks, channels } => {
+                        // Let the input meter fall back gradually
+                        for (level, peak) in self.input_levels.iter_mut().zip(peaks) {
+                            *level = peak.max(*level * 0.7);
+                        }
+                        self.input_level_channels = channels;
+                    }
                     // Other events are not currently handled by the app
-                    // (OutputLevel, Started, Stopped, Error)
+                    // (OutputLevel, Started, Stopped, Error); released
+                    // tunings and note sequences are freed here, off the
+                    // audio thread
                     _ => {}
                 }
             }
@@ -904,6 +2690,8 @@ impl SynthApp {
                 for response in graph_response.node_responses {
                     match response {
                         NodeResponse::CreatedNode(node_id) => {
+                            self.dirty.mark_dirty();
+
                             // Allocate engine node ID for the new node
                             let engine_node_id = self.user_state.allocate_engine_node_id(node_id);
 
@@ -933,8 +2721,13 @@ impl SynthApp {
                             }
                         }
                         NodeResponse::DeleteNodeFull { node_id, .. } => {
+                            self.dirty.mark_dirty();
+
                             // Get engine node ID before removing from mapping
                             if let Some(engine_node_id) = self.user_state.remove_node(node_id) {
+                                self.user_state.midi_files.remove(&engine_node_id);
+                                self.user_state.node_tunings.remove(&engine_node_id);
+                                self.user_state.node_midi_inputs.remove(&engine_node_id);
                                 commands_to_send.push(EngineCommand::RemoveModule {
                                     node_id: engine_node_id,
                                 });
@@ -948,6 +2741,8 @@ impl SynthApp {
                                 // Show error message
                                 self.user_state.set_validation_error(error_msg);
                             } else {
+                                self.dirty.mark_dirty();
+
                                 // Always send a disconnect command first to clear any existing connection
                                 // The graph library auto-disconnects old connections visually when a new
                                 // connection is made to an input, but doesn't emit a DisconnectEvent.
@@ -971,6 +2766,8 @@ impl SynthApp {
                             }
                         }
                         NodeResponse::DisconnectEvent { output, input } => {
+                            self.dirty.mark_dirty();
+
                             // Send disconnect command to engine
                             if let Some(cmd) = self.build_disconnect_command(input) {
                                 commands_to_send.push(cmd);
@@ -1005,6 +2802,8 @@ impl SynthApp {
                             param_name,
                             value,
                         }) => {
+                            self.dirty.mark_dirty();
+
                             // Handle parameter changes from bottom_ui knobs
                             // Find the input param by name and update its value
                             if let Some(node) = self.graph_state.graph.nodes.get_mut(response_node_id) {
@@ -1022,6 +2821,7 @@ impl SynthApp {
                             param_name,
                             min_value,
                             max_value,
+                            button,
                         }) => {
                             // Start MIDI Learn mode for this parameter
                             self.start_midi_learn(MidiLearnTarget {
@@ -1030,6 +2830,8 @@ impl SynthApp {
                                 param_name,
                                 min_value,
                                 max_value,
+                                button,
+                                action: None,
                             });
                             // Update the user state to show visual feedback
                             self.user_state.midi_learn_active = true;
@@ -1041,9 +2843,68 @@ impl SynthApp {
                         }) => {
                             // Clear MIDI mapping for this parameter
                             self.clear_mapping_for_param(engine_node_id, param_index);
+                            self.dirty.mark_dirty();
                             // Update the user state
                             self.user_state.remove_midi_mapping(engine_node_id, param_index);
                         }
+                        NodeResponse::User(crate::graph::SynthResponse::MidiMappingOptions {
+                            engine_node_id,
+                            param_index,
+                            options,
+                        }) => {
+                            self.set_mapping_options(engine_node_id, param_index, options);
+                        }
+                        NodeResponse::User(crate::graph::SynthResponse::ToggleParamLock {
+                            engine_node_id,
+                            param_index,
+                        }) => {
+                            let lock = (engine_node_id, param_index);
+                            if !self.user_state.locked_params.remove(&lock) {
+                                self.user_state.locked_params.insert(lock);
+                            }
+                            self.dirty.mark_dirty();
+                        }
+                        NodeResponse::User(crate::graph::SynthResponse::LoadPreset { node_id, name }) => {
+                            self.load_preset(node_id, &name);
+                        }
+                        NodeResponse::User(crate::graph::SynthResponse::SavePreset(node_id)) => {
+                            let name = self.graph_state.graph.nodes
+                                .get(node_id)
+                                .map(|node| format!("{} Preset", node.label))
+                                .unwrap_or_default();
+                            self.preset_save = Some(PresetSaveDialog { node_id, name });
+                        }
+                        NodeResponse::User(crate::graph::SynthResponse::DeletePreset { node_id, name }) => {
+                            self.delete_preset(node_id, &name);
+                        }
+                        NodeResponse::User(crate::graph::SynthResponse::LoadMidiFile(node_id)) => {
+                            self.show_midi_file_dialog(node_id);
+                        }
+                        NodeResponse::User(crate::graph::SynthResponse::LoadNodeScale(node_id)) => {
+                            if let Some(engine_node_id) = self.user_state.get_engine_node_id(node_id) {
+                                self.show_tuning_dialog(Some(engine_node_id), false);
+                            }
+                        }
+                        NodeResponse::User(crate::graph::SynthResponse::LoadNodeMapping(node_id)) => {
+                            if let Some(engine_node_id) = self.user_state.get_engine_node_id(node_id) {
+                                self.show_tuning_dialog(Some(engine_node_id), true);
+                            }
+                        }
+                        NodeResponse::User(crate::graph::SynthResponse::ClearNodeTuning(node_id)) => {
+                            if let Some(engine_node_id) = self.user_state.get_engine_node_id(node_id) {
+                                self.reset_tuning(Some(engine_node_id));
+                                self.dirty.mark_dirty();
+                            }
+                        }
+                        NodeResponse::User(crate::graph::SynthResponse::SetMidiInput { node_id, device }) => {
+                            if let Some(engine_node_id) = self.user_state.get_engine_node_id(node_id) {
+                                self.set_node_midi_input(engine_node_id, device);
+                                self.dirty.mark_dirty();
+                            }
+                        }
+                        NodeResponse::MoveNode { .. } => {
+                            self.dirty.mark_dirty();
+                        }
                         _ => {
                             // Other responses not yet handled
                         }
@@ -1196,6 +3057,7 @@ impl SynthApp {
                 // Set the node position and add to node_order
                 self.graph_state.node_positions.insert(node_id, graph_pos);
                 self.graph_state.node_order.push(node_id);
+                self.dirty.mark_dirty();
 
                 // Allocate engine node ID and send command
                 let engine_node_id = self.user_state.allocate_engine_node_id(node_id);
@@ -1489,6 +3351,7 @@ impl SynthApp {
             // Check if this is a keyboard or MIDI note module - we handle Note/Gate params separately
             let is_keyboard = node.user_data.module_id == "input.keyboard";
             let is_midi_note = node.user_data.module_id == "input.midi_note";
+            let is_mpe_voice = node.user_data.module_id == "input.mpe_voice";
 
             // Track which param index we're at (only count ConstantOnly params)
             let mut param_index = 0;
@@ -1515,6 +3378,12 @@ impl SynthApp {
                             continue;
                         }
 
+                        // Skip the voice state params (Pitch to Slide) of MPE Voice modules
+                        if is_mpe_voice && param_index <= MpeVoice::PARAM_SLIDE {
+                            param_index += 1;
+                            continue;
+                        }
+
                         // Get the actual value (not normalized) for the audio engine
                         // This ensures frequency values are in Hz, time values in seconds, etc.
                         let actual_value = input.value.actual_value();
@@ -1531,7 +3400,12 @@ impl SynthApp {
                                 } else {
                                     0.0001 // Absolute tolerance for small values
                                 };
-                                diff > threshold
+                                let changed = diff > threshold;
+                                // Initial syncs below are not edits; only real changes dirty the patch
+                                if changed {
+                                    self.dirty.mark_dirty();
+                                }
+                                changed
                             }
                             None => true, // New parameter, needs initial sync
                         };
@@ -1561,6 +3435,49 @@ impl SynthApp {
         }
     }
 
+    /// Convert a node position in the current view to zoom-independent patch coordinates.
+    ///
+    /// The library's update_node_positions_after_zoom modifies positions when zooming,
+    /// so we need to reverse that transformation to get zoom-independent positions.
+    /// On load, we reset to zoom=1.0 and pan=0, so positions saved this way will match.
+    fn view_to_canonical(&self, pos: egui::Pos2) -> (f32, f32) {
+        let zoom = self.graph_state.pan_zoom.zoom;
+        let pan = self.graph_state.pan_zoom.pan;
+        let clip_rect = self.graph_state.pan_zoom.clip_rect;
+
+        // If zoom is ~1.0 or clip_rect is invalid, use position as-is
+        if (zoom - 1.0).abs() < 0.001 || clip_rect.is_negative() {
+            (pos.x, pos.y)
+        } else {
+            // Reverse the zoom transformation to get canonical position
+            // This inverts what update_node_positions_after_zoom does
+            let half_size = clip_rect.size() / 2.0;
+            let local_pos = pos.to_vec2() - half_size + pan;
+            let unscaled = local_pos / zoom;
+            // For loading with pan=0, canonical position is:
+            let canonical = (unscaled + half_size).to_pos2();
+            (canonical.x, canonical.y)
+        }
+    }
+
+    /// Convert a zoom-independent patch position to the current view.
+    ///
+    /// Inverse of [`Self::view_to_canonical`], used when inserting nodes into
+    /// a graph that may be panned or zoomed (e.g. pasting).
+    fn canonical_to_view(&self, position: (f32, f32)) -> egui::Pos2 {
+        let zoom = self.graph_state.pan_zoom.zoom;
+        let pan = self.graph_state.pan_zoom.pan;
+        let clip_rect = self.graph_state.pan_zoom.clip_rect;
+
+        if (zoom - 1.0).abs() < 0.001 || clip_rect.is_negative() {
+            egui::pos2(position.0, position.1)
+        } else {
+            let half_size = clip_rect.size() / 2.0;
+            let unscaled = egui::vec2(position.0, position.1) - half_size;
+            (unscaled * zoom + half_size - pan).to_pos2()
+        }
+    }
+
     /// Create a Patch from the current graph state.
     fn create_patch(&self, name: &str) -> Patch {
         let mut patch = Patch::new(name);
@@ -1573,30 +3490,9 @@ impl SynthApp {
             };
 
             // Get node position, normalized to zoom=1.0 coordinates for persistence.
-            // The library's update_node_positions_after_zoom modifies positions when zooming,
-            // so we need to reverse that transformation to get zoom-independent positions.
-            // On load, we reset to zoom=1.0 and pan=0, so positions saved this way will match.
             let position = self.graph_state.node_positions
                 .get(node_id)
-                .map(|pos| {
-                    let zoom = self.graph_state.pan_zoom.zoom;
-                    let pan = self.graph_state.pan_zoom.pan;
-                    let clip_rect = self.graph_state.pan_zoom.clip_rect;
-
-                    // If zoom is ~1.0 or clip_rect is invalid, use position as-is
-                    if (zoom - 1.0).abs() < 0.001 || clip_rect.is_negative() {
-                        (pos.x, pos.y)
-                    } else {
-                        // Reverse the zoom transformation to get canonical position
-                        // This inverts what update_node_positions_after_zoom does
-                        let half_size = clip_rect.size() / 2.0;
-                        let local_pos = pos.to_vec2() - half_size + pan;
-                        let unscaled = local_pos / zoom;
-                        // For loading with pan=0, canonical position is:
-                        let canonical = (unscaled + half_size).to_pos2();
-                        (canonical.x, canonical.y)
-                    }
-                })
+                .map(|pos| self.view_to_canonical(*pos))
                 .unwrap_or((0.0, 0.0));
 
             let mut node_data = NodeData::new(
@@ -1629,6 +3525,12 @@ impl SynthApp {
                 }
             }
 
+            node_data.file = self.user_state.midi_files
+                .get(&engine_node_id)
+                .map(|file| file.path.clone());
+            node_data.tuning = self.user_state.node_tunings.get(&engine_node_id).cloned();
+            node_data.midi_input = self.user_state.node_midi_inputs.get(&engine_node_id).cloned();
+
             patch.nodes.push(node_data);
         }
 
@@ -1666,12 +3568,38 @@ impl SynthApp {
             }
         }
 
-        // Copy MIDI mappings to the patch
+        // Copy MIDI mappings, scenes and locks to the patch
         patch.midi_mappings = self.midi_mappings.clone();
+        patch.scenes = self.scenes.clone();
+        patch.locks = self.user_state.locked_params
+            .iter()
+            .map(|&(node_id, param_index)| ParameterLock::new(node_id, param_index))
+            .collect();
+        patch.locks.sort_by_key(|lock| (lock.node_id, lock.param_index));
+
+        patch.metadata = self.patch_metadata.clone();
+        patch.tuning = self.tuning.clone();
+
+        patch
+    }
 
+    /// Create a patch for writing to disk, stamping its save time and thumbnail.
+    fn create_patch_for_save(&mut self, name: &str) -> Patch {
+        self.patch_metadata.touch(unix_now());
+        let mut patch = self.create_patch(name);
+        patch.metadata.thumbnail = Some(PatchThumbnail::from_patch(&patch));
         patch
     }
 
+    /// Name of the current patch, taken from its file name.
+    fn current_patch_name(&self) -> &str {
+        self.current_patch_path
+            .as_ref()
+            .and_then(|p| p.file_stem())
+            .and_then(|s| s.to_str())
+            .unwrap_or("Untitled")
+    }
+
     /// Load a patch, replacing the current graph.
     fn load_patch(&mut self, patch: &Patch) -> Result<(), PatchError> {
         // Stop playback during load
@@ -1684,22 +3612,71 @@ impl SynthApp {
 
         // Clear the current graph
         self.clear_graph();
+        self.build_patch(patch)?;
+
+        // Bring the controller's faders and LEDs to the new patch
+        self.feedback_throttle.resend_all();
+
+        // Restore playback state
+        if was_playing {
+            self.is_playing = true;
+            self.user_state.is_playing = true;
+            self.send_command(EngineCommand::SetPlaying(true));
+        }
 
+        Ok(())
+    }
+
+    /// Build a patch in the empty editor, with its mappings, scenes and tuning.
+    fn build_patch(&mut self, patch: &Patch) -> Result<(), PatchError> {
         // Reset pan/zoom to default (zoom=1.0, pan=0) before loading positions.
         // This is critical because the library's update_node_positions_after_zoom
         // mutates node_positions based on the current zoom level. Loading positions
         // at a different zoom than they were saved at would cause layout drift.
         self.graph_state.pan_zoom = egui_node_graph2::PanZoom::default();
 
+        let id_map = self.instantiate_patch(patch, egui::Vec2::ZERO)?;
+
+        // Load MIDI mappings, retargeted from patch node IDs to the new engine node IDs
+        self.restore_midi_mappings(&patch.midi_mappings, &id_map);
+        self.restore_scenes(&patch.scenes, &id_map);
+        self.restore_locks(&patch.locks, &id_map);
+        self.restore_tuning(None, patch.tuning.as_ref());
+
+        // Remember which graph node came from which patch node, for hot-reload
+        self.patch_node_ids = id_map;
+
+        self.patch_metadata = patch.metadata.clone();
+        self.patch_info_tags = self.patch_metadata.tags_string();
+
+        Ok(())
+    }
+
+    /// Create the nodes and connections of a patch in the current graph.
+    ///
+    /// Nodes get fresh engine IDs and are placed at their saved positions plus
+    /// `offset`. Existing nodes are left untouched, so this is used both for
+    /// loading a whole patch and for pasting a fragment. Returns the mapping
+    /// from patch node IDs to the newly created graph nodes.
+    fn instantiate_patch(
+        &mut self,
+        patch: &Patch,
+        offset: egui::Vec2,
+    ) -> Result<HashMap<u64, egui_node_graph2::NodeId>, PatchError> {
         // Map from patch node IDs to graph node IDs
         let mut id_map: HashMap<u64, egui_node_graph2::NodeId> = HashMap::new();
 
-        // Create nodes
-        for node_data in &patch.nodes {
-            // Find the template for this module ID
-            let template = self.find_template_for_module(&node_data.module_id)
-                .ok_or_else(|| PatchError::UnknownModule(node_data.module_id.clone()))?;
+        // Resolve all templates up front so an unknown module doesn't leave a partial graph
+        let templates = patch.nodes
+            .iter()
+            .map(|node_data| {
+                self.find_template_for_module(&node_data.module_id)
+                    .ok_or_else(|| PatchError::UnknownModule(node_data.module_id.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
+        // Create nodes
+        for (node_data, template) in patch.nodes.iter().zip(templates) {
             // Create the node
             let graph_node_id = self.graph_state.graph.add_node(
                 template.node_graph_label(&mut self.user_state),
@@ -1708,12 +3685,14 @@ impl SynthApp {
             );
 
             // Set node position
-            let pos = egui::pos2(node_data.position.0, node_data.position.1);
+            let pos = self.canonical_to_view((
+                node_data.position.0 + offset.x,
+                node_data.position.1 + offset.y,
+            ));
             self.graph_state.node_positions.insert(graph_node_id, pos);
             self.graph_state.node_order.push(graph_node_id);
 
-            // Allocate engine node ID (use the patch ID to maintain consistency)
-            // Note: We use our own ID allocation to keep engine and graph in sync
+            // Allocate a fresh engine node ID; patch IDs are only used to resolve connections
             let engine_node_id = self.user_state.allocate_engine_node_id(graph_node_id);
 
             // Send command to create the module in the audio engine
@@ -1768,6 +3747,19 @@ impl SynthApp {
                     }
                 }
             }
+
+            // Reload the node's MIDI file; the patch still loads if it is missing
+            if let Some(path) = &node_data.file {
+                if let Err(e) = self.load_midi_file(engine_node_id, path) {
+                    eprintln!("Could not load MIDI file {}: {}", path.display(), e);
+                }
+            }
+            if node_data.tuning.is_some() {
+                self.restore_tuning(Some(engine_node_id), node_data.tuning.as_ref());
+            }
+            if let Some(device) = &node_data.midi_input {
+                self.user_state.node_midi_inputs.insert(engine_node_id, device.clone());
+            }
         }
 
         // Restore connections
@@ -1777,177 +3769,1661 @@ impl SynthApp {
             let to_graph_id = id_map.get(&conn.to_node);
 
             if let (Some(&from_graph_id), Some(&to_graph_id)) = (from_graph_id, to_graph_id) {
-                // Find output port by name
-                let output_id = self.graph_state.graph.nodes.get(from_graph_id)
-                    .and_then(|node| {
-                        node.outputs.iter()
-                            .find(|(name, _)| *name == conn.from_port)
-                            .map(|(_, id)| *id)
-                    });
+                if let Some((output_id, input_id)) = self.resolve_connection_ports(from_graph_id, to_graph_id, conn) {
+                    self.connect_ports(output_id, input_id);
+                }
+            }
+        }
 
-                // Find input port by name
-                let input_id = self.graph_state.graph.nodes.get(to_graph_id)
-                    .and_then(|node| {
-                        node.inputs.iter()
-                            .find(|(name, _)| *name == conn.to_port)
-                            .map(|(_, id)| *id)
-                    });
+        Ok(id_map)
+    }
+
+    /// Find the graph ports a saved connection refers to, by port name.
+    fn resolve_connection_ports(
+        &self,
+        from_graph_id: egui_node_graph2::NodeId,
+        to_graph_id: egui_node_graph2::NodeId,
+        conn: &ConnectionData,
+    ) -> Option<(egui_node_graph2::OutputId, egui_node_graph2::InputId)> {
+        // Find output port by name
+        let output_id = self.graph_state.graph.nodes.get(from_graph_id)
+            .and_then(|node| {
+                node.outputs.iter()
+                    .find(|(name, _)| *name == conn.from_port)
+                    .map(|(_, id)| *id)
+            })?;
+
+        // Find input port by name
+        let input_id = self.graph_state.graph.nodes.get(to_graph_id)
+            .and_then(|node| {
+                node.inputs.iter()
+                    .find(|(name, _)| *name == conn.to_port)
+                    .map(|(_, id)| *id)
+            })?;
+
+        Some((output_id, input_id))
+    }
+
+    /// Connect two ports in the graph and the engine, with monitoring set up.
+    fn connect_ports(&mut self, output_id: egui_node_graph2::OutputId, input_id: egui_node_graph2::InputId) {
+        // Add connection to graph (pos=0 adds at beginning, order doesn't matter for audio)
+        self.graph_state.graph.add_connection(output_id, input_id, 0);
+
+        // Send connection command to engine
+        if let Some(cmd) = self.build_connect_command(output_id, input_id) {
+            self.send_command(cmd);
+        }
+
+        // Set up input monitoring if this is an exposed parameter
+        if let Some(monitor_cmd) = self.build_monitor_input_command(input_id) {
+            self.send_command(monitor_cmd);
+        }
+
+        // Set up output monitoring for cable animation
+        if let Some(monitor_cmd) = self.build_monitor_output_command(output_id) {
+            self.send_command(monitor_cmd);
+        }
+    }
+
+    /// Disconnect two ports in the graph and the engine.
+    fn disconnect_ports(&mut self, output_id: egui_node_graph2::OutputId, input_id: egui_node_graph2::InputId) {
+        // Build commands while the connection still exists in the graph
+        let disconnect_cmd = self.build_disconnect_command(input_id);
+        let unmonitor_cmd = self.build_unmonitor_input_command(input_id);
+
+        self.graph_state.graph.remove_connection(input_id, output_id);
 
-                if let (Some(output_id), Some(input_id)) = (output_id, input_id) {
-                    // Add connection to graph (pos=0 adds at beginning, order doesn't matter for audio)
-                    self.graph_state.graph.add_connection(output_id, input_id, 0);
+        if let Some(cmd) = disconnect_cmd {
+            self.send_command(cmd);
+        }
+        if let Some(cmd) = unmonitor_cmd {
+            self.send_command(cmd);
+        }
+    }
+
+    /// Remove a node from the graph and the engine.
+    ///
+    /// Used when nodes are removed programmatically rather than through the editor.
+    fn remove_graph_node(&mut self, graph_node_id: egui_node_graph2::NodeId) {
+        self.graph_state.graph.remove_node(graph_node_id);
+        self.graph_state.node_positions.remove(graph_node_id);
+        self.graph_state.node_order.retain(|id| *id != graph_node_id);
+        self.graph_state.selected_nodes.retain(|id| *id != graph_node_id);
+
+        if let Some(engine_node_id) = self.user_state.remove_node(graph_node_id) {
+            // The engine drops the module's connections along with it
+            self.send_command(EngineCommand::RemoveModule {
+                node_id: engine_node_id,
+            });
+            self.cached_params.retain(|(node_id, _), _| *node_id != engine_node_id);
+            self.midi_mappings.retain(|m| m.action.is_some() || m.node_id != engine_node_id);
+            for scene in &mut self.scenes {
+                scene.values.retain(|v| v.node_id != engine_node_id);
+            }
+            self.user_state.locked_params.retain(|(node_id, _)| *node_id != engine_node_id);
+        }
+    }
+
+    /// Replace the active MIDI mappings with ones from a patch.
+    ///
+    /// Mapping targets are translated from patch node IDs to engine node IDs
+    /// through `id_map`; mappings to unknown nodes are dropped. Action
+    /// mappings have no target and are kept as they are.
+    fn restore_midi_mappings(
+        &mut self,
+        mappings: &[MidiMapping],
+        id_map: &HashMap<u64, egui_node_graph2::NodeId>,
+    ) {
+        for mapping in self.midi_mappings.iter().filter(|m| m.action.is_none()) {
+            self.user_state.remove_midi_mapping(mapping.node_id, mapping.param_index);
+        }
+
+        self.midi_mappings = mappings
+            .iter()
+            .filter_map(|mapping| {
+                if mapping.action.is_some() {
+                    return Some(mapping.clone());
+                }
+                let graph_node_id = id_map.get(&mapping.node_id)?;
+                let engine_node_id = self.user_state.get_engine_node_id(*graph_node_id)?;
+                Some(MidiMapping { node_id: engine_node_id, ..mapping.clone() })
+            })
+            .collect();
+
+        self.controller_states.clear();
+
+        // Sync mappings to user state for UI display
+        for mapping in self.midi_mappings.iter().filter(|m| m.action.is_none()) {
+            self.user_state.set_midi_mapping(mapping);
+        }
+    }
+
+    /// Replace the scenes with ones from a patch.
+    ///
+    /// Like MIDI mappings, scene values are retargeted from patch node IDs to
+    /// engine node IDs through `id_map`; values for unknown nodes are dropped.
+    fn restore_scenes(&mut self, scenes: &[Scene], id_map: &HashMap<u64, egui_node_graph2::NodeId>) {
+        let user_state = &self.user_state;
+        self.scenes = scenes
+            .iter()
+            .map(|scene| {
+                scene.retarget(|id| id_map.get(&id).and_then(|graph_id| user_state.get_engine_node_id(*graph_id)))
+            })
+            .collect();
+    }
+
+    /// Replace the parameter locks with ones from a patch, retargeted through `id_map`.
+    fn restore_locks(&mut self, locks: &[ParameterLock], id_map: &HashMap<u64, egui_node_graph2::NodeId>) {
+        let locked = locks
+            .iter()
+            .filter_map(|lock| {
+                let graph_node_id = id_map.get(&lock.node_id)?;
+                let engine_node_id = self.user_state.get_engine_node_id(*graph_node_id)?;
+                Some((engine_node_id, lock.param_index))
+            })
+            .collect();
+        self.user_state.locked_params = locked;
+    }
+
+    /// Offset applied to duplicated nodes, and per paste of the clipboard.
+    const PASTE_OFFSET: egui::Vec2 = egui::vec2(40.0, 40.0);
+
+    /// Build a patch fragment from the currently selected nodes.
+    ///
+    /// Returns None if nothing is selected.
+    fn selection_fragment(&self) -> Option<Patch> {
+        let selected: Vec<u64> = self.graph_state.selected_nodes
+            .iter()
+            .filter_map(|node_id| self.user_state.get_engine_node_id(*node_id))
+            .collect();
+
+        if selected.is_empty() {
+            return None;
+        }
+
+        Some(self.create_patch("Clipboard").extract(&selected))
+    }
+
+    /// Copy the selected nodes to the system clipboard as a JSON patch fragment.
+    fn copy_selection(&mut self, ctx: &egui::Context) {
+        let Some(fragment) = self.selection_fragment() else {
+            return;
+        };
+
+        match serde_json::to_string_pretty(&fragment) {
+            Ok(json) => {
+                ctx.copy_text(json);
+                self.paste_count = 0;
+                let count = fragment.nodes.len();
+                self.status_message = Some(format!(
+                    "Copied {} node{}",
+                    count,
+                    if count == 1 { "" } else { "s" }
+                ));
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Copy failed: {}", e));
+            }
+        }
+    }
+
+    /// Paste a JSON patch fragment from the clipboard into the graph.
+    fn paste_fragment(&mut self, text: &str) {
+        let fragment: Patch = match serde_json::from_str(text) {
+            Ok(fragment) => fragment,
+            Err(_) => {
+                self.status_message = Some("Clipboard does not contain a patch".to_string());
+                return;
+            }
+        };
+
+        if !fragment.is_compatible() {
+            let error = PatchError::IncompatibleVersion {
+                found: fragment.version,
+                expected: PATCH_VERSION,
+            };
+            self.status_message = Some(format!("Paste failed: {}", error));
+            return;
+        }
+
+        self.paste_count += 1;
+        let offset = Self::PASTE_OFFSET * self.paste_count as f32;
+        self.insert_fragment(&fragment, offset);
+    }
+
+    /// Duplicate the selected nodes, keeping their internal connections.
+    fn duplicate_selection(&mut self) {
+        if let Some(fragment) = self.selection_fragment() {
+            self.insert_fragment(&fragment, Self::PASTE_OFFSET);
+        }
+    }
+
+    /// Insert a patch fragment into the graph and select the new nodes.
+    fn insert_fragment(&mut self, fragment: &Patch, offset: egui::Vec2) {
+        match self.instantiate_patch(fragment, offset) {
+            Ok(id_map) => {
+                self.graph_state.selected_nodes = id_map.values().copied().collect();
+                self.dirty.mark_dirty();
+                let count = id_map.len();
+                self.status_message = Some(format!(
+                    "Pasted {} node{}",
+                    count,
+                    if count == 1 { "" } else { "s" }
+                ));
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Paste failed: {}", e));
+            }
+        }
+    }
+
+    /// Clear the entire graph.
+    fn clear_graph(&mut self) {
+        // Send clear command to audio engine
+        self.send_command(EngineCommand::ClearGraph);
+
+        // Clear graph state
+        self.graph_state.graph = egui_node_graph2::Graph::default();
+        self.graph_state.node_positions.clear();
+        self.graph_state.node_order.clear();
+
+        // Clear user state (also clears MIDI mapping UI state)
+        self.user_state.clear();
+
+        // Clear cached parameters
+        self.cached_params.clear();
+
+        // Clear MIDI mappings
+        self.midi_mappings.clear();
+        self.midi_learn_target = None;
+        self.midi_learner.reset();
+        self.controller_states.clear();
+
+        self.scenes.clear();
+        self.recalled_scene = None;
+        self.variation_undo.clear();
+
+        self.patch_node_ids.clear();
+    }
+
+    /// Start a new patch - clears the graph and resets the current file path.
+    fn new_patch(&mut self) {
+        self.clear_graph();
+        self.current_patch_path = None;
+        self.watched_file = None;
+        self.forget_preview();
+        self.patch_metadata = PatchMetadata::default();
+        self.patch_info_tags.clear();
+        self.reset_tuning(None);
+        self.dirty.mark_saved();
+        self.status_message = Some("New patch created".to_string());
+    }
+
+    /// Find the template for a given module ID.
+    fn find_template_for_module(&self, module_id: &str) -> Option<SynthNodeTemplate> {
+        use egui_node_graph2::NodeTemplateIter;
+        AllNodeTemplates.all_kinds()
+            .into_iter()
+            .find(|t| t.module_id() == module_id)
+    }
+
+    /// Show a save file dialog and save the current patch.
+    fn show_save_dialog(&mut self) {
+        let default_name = self.current_patch_path
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .and_then(|n| n.to_str())
+            .unwrap_or("patch.json");
+
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("Synth Patch", &["json"])
+            .add_filter("Patch Script", &[DSL_EXTENSION])
+            .set_file_name(default_name)
+            .save_file()
+        {
+            // Derive patch name from filename
+            let name = path.file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("Untitled");
+
+            let patch = self.create_patch_for_save(name);
+            match self.write_patch_file(&patch, &path) {
+                Ok(file_ids) => {
+                    self.adopt_file_ids(&file_ids);
+                    if self.current_patch_path.as_ref() != Some(&path) {
+                        self.watched_file = None;
+                    }
+                    self.current_patch_path = Some(path.clone());
+                    self.forget_preview();
+                    self.mark_saved();
+                    self.library_browser.notify_saved(&path);
+                    self.status_message = Some(format!("Saved: {}", path.display()));
+                }
+                Err(e) => {
+                    self.status_message = Some(format!("Save failed: {}", e));
+                }
+            }
+        }
+    }
+
+    /// Show a load file dialog and load the selected patch.
+    fn show_load_dialog(&mut self) {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("Synth Patch", &["json", DSL_EXTENSION])
+            .add_filter("Patch Script", &[DSL_EXTENSION])
+            .pick_file()
+        {
+            match self.read_patch_file(&path) {
+                Ok(patch) => {
+                    match self.load_patch(&patch) {
+                        Ok(()) => {
+                            self.current_patch_path = Some(path.clone());
+                            self.watched_file = None;
+                            self.forget_preview();
+                            self.mark_saved();
+                            self.status_message = Some(format!("Loaded: {}", patch.name));
+                        }
+                        Err(e) => {
+                            self.status_message = Some(format!("Load failed: {}", e));
+                        }
+                    }
+                }
+                Err(e) => {
+                    self.status_message = Some(format!("Load failed: {}", e));
+                }
+            }
+        }
+    }
+
+    /// Read a patch file, compiling it if it is a patch script.
+    fn read_patch_file(&self, path: &std::path::Path) -> Result<Patch, PatchError> {
+        if !is_patch_script(path) {
+            return load_from_file(path);
+        }
+
+        let source = std::fs::read_to_string(path)?;
+        let mut patch = dsl::compile(&source, &self.module_registry)?;
+        if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+            patch.name = name.to_string();
+        }
+        Ok(patch)
+    }
+
+    /// Write a patch file, as a patch script if the extension asks for one.
+    ///
+    /// Scripts don't store node positions or MIDI mappings. Returns the ID
+    /// each node has in the file, keyed by its ID in `patch`.
+    fn write_patch_file(&self, patch: &Patch, path: &std::path::Path) -> Result<HashMap<u64, u64>, PatchError> {
+        if !is_patch_script(path) {
+            save_to_file(patch, path)?;
+            return Ok(patch.nodes.iter().map(|node| (node.id, node.id)).collect());
+        }
+
+        let source = dsl::export(patch, &self.module_registry)?;
+        std::fs::write(path, source)?;
+        Ok(dsl::script_node_ids(patch))
+    }
+
+    /// Quick save to the current path, or show save dialog if no path.
+    fn quick_save(&mut self) {
+        if let Some(path) = self.current_patch_path.clone() {
+            let name = path.file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("Untitled");
+
+            let patch = self.create_patch_for_save(name);
+            match self.write_patch_file(&patch, &path) {
+                Ok(file_ids) => {
+                    self.adopt_file_ids(&file_ids);
+                    self.forget_preview();
+                    self.mark_saved();
+                    self.library_browser.notify_saved(&path);
+                    self.status_message = Some(format!("Saved: {}", path.display()));
+                }
+                Err(e) => {
+                    self.status_message = Some(format!("Save failed: {}", e));
+                }
+            }
+        } else {
+            self.show_save_dialog();
+        }
+    }
+
+    /// Record that the patch on disk matches the graph, dropping any stale autosave.
+    fn mark_saved(&mut self) {
+        self.dirty.mark_saved();
+        if let Some(ref autosave) = self.autosave {
+            autosave.clear();
+        }
+    }
+
+    /// Hand the current patch to the autosave thread if it has unsaved changes
+    /// and the autosave interval has elapsed.
+    fn autosave_if_needed(&mut self) {
+        // A preview must not overwrite the autosave of the patch it replaced
+        if self.autosave.is_none()
+            || self.preview.is_some()
+            || !self.dirty.needs_autosave()
+            || self.last_autosave.elapsed() < AUTOSAVE_INTERVAL
+        {
+            return;
+        }
+
+        let data = RecoveryData {
+            source_path: self.current_patch_path.clone(),
+            patch: self.create_patch(self.current_patch_name()),
+        };
+
+        if let Some(ref autosave) = self.autosave {
+            autosave.submit(data);
+        }
+        self.dirty.mark_autosaved();
+        self.last_autosave = Instant::now();
+    }
+
+    /// Offer to restore a patch recovered from an unclean shutdown.
+    fn draw_recovery_dialog(&mut self, ctx: &egui::Context) {
+        let Some(ref recovered) = self.recovered_patch else {
+            return;
+        };
+
+        let mut restore = false;
+        let mut discard = false;
+
+        egui::Window::new("Recover Patch")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label("The previous session did not shut down cleanly.");
+                ui.label(format!(
+                    "Restore the autosaved patch \"{}\" ({} nodes)?",
+                    recovered.patch.name,
+                    recovered.patch.nodes.len()
+                ));
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Restore").clicked() {
+                        restore = true;
+                    }
+                    if ui.button("Discard").clicked() {
+                        discard = true;
+                    }
+                });
+            });
+
+        if restore {
+            if let Some(recovered) = self.recovered_patch.take() {
+                match self.load_patch(&recovered.patch) {
+                    Ok(()) => {
+                        self.current_patch_path = recovered.source_path;
+                        self.forget_preview();
+                        // The recovered state was never saved by the user
+                        self.dirty.mark_dirty();
+                        self.status_message = Some(format!("Recovered: {}", recovered.patch.name));
+                    }
+                    Err(e) => {
+                        self.status_message = Some(format!("Recovery failed: {}", e));
+                    }
+                }
+            }
+        } else if discard {
+            self.recovered_patch = None;
+            if let Some(ref autosave) = self.autosave {
+                autosave.clear();
+            }
+        }
+    }
+
+    /// Load a library patch for preview, keeping the current patch to return to.
+    ///
+    /// Previewing another patch while one is already previewed keeps the
+    /// original patch, so "Back" always returns to where browsing started.
+    fn preview_patch(&mut self, path: PathBuf) {
+        let patch = match self.read_patch_file(&path) {
+            Ok(patch) => patch,
+            Err(e) => {
+                self.status_message = Some(format!("Preview failed: {}", e));
+                return;
+            }
+        };
+
+        if self.preview.is_none() {
+            // The engine builds the preview in a scratch graph, leaving the
+            // current modules as they are
+            self.drop_preloaded_patch();
+            self.send_command(EngineCommand::BeginPreview);
+            let previous_name = self.current_patch_name().to_string();
+            let previous = self.take_open_patch();
+            self.preview = Some(PatchPreview { path: path.clone(), previous_name, previous });
+        }
+
+        match self.load_patch(&patch) {
+            Ok(()) => {
+                if let Some(ref mut preview) = self.preview {
+                    preview.path = path.clone();
+                }
+                self.current_patch_path = Some(path);
+                self.dirty = DirtyTracker::new();
+                self.status_message = Some(format!("Previewing: {}", patch.name));
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Preview failed: {}", e));
+                self.end_preview();
+            }
+        }
+    }
+
+    /// Open a library patch as the current patch.
+    fn open_library_patch(&mut self, path: PathBuf) {
+        if self.preview.as_ref().map(|p| &p.path) != Some(&path) {
+            self.preview_patch(path);
+        }
+        self.keep_preview();
+    }
+
+    /// Make the previewed patch the current patch.
+    fn keep_preview(&mut self) {
+        if self.preview.is_some() {
+            self.forget_preview();
+            self.mark_saved();
+            self.status_message = Some(format!("Opened: {}", self.current_patch_name()));
+        }
+    }
+
+    /// Stop previewing, keeping whatever patch is loaded now and dropping
+    /// the one set aside.
+    fn forget_preview(&mut self) {
+        if self.preview.take().is_some() {
+            self.send_command(EngineCommand::EndPreview { keep: true });
+        }
+    }
+
+    /// Leave preview and restore the patch that was open before.
+    fn end_preview(&mut self) {
+        let Some(preview) = self.preview.take() else {
+            return;
+        };
+
+        self.send_command(EngineCommand::EndPreview { keep: false });
+        self.restore_open_patch(preview.previous);
+        self.feedback_throttle.resend_all();
+        self.status_message = Some(format!("Back to: {}", self.current_patch_name()));
+    }
+
+    /// Move the open patch aside, leaving an empty editor in its place.
+    fn take_open_patch(&mut self) -> OpenPatch {
+        self.midi_learn_target = None;
+        self.midi_learner.reset();
+        let user_state = self.user_state.for_new_patch();
+
+        OpenPatch {
+            graph_state: std::mem::replace(&mut self.graph_state, GraphEditorState::new(1.0)),
+            user_state: std::mem::replace(&mut self.user_state, user_state),
+            cached_params: std::mem::take(&mut self.cached_params),
+            path: self.current_patch_path.take(),
+            dirty: std::mem::replace(&mut self.dirty, DirtyTracker::new()),
+            watched_file: self.watched_file.take(),
+            midi_mappings: std::mem::take(&mut self.midi_mappings),
+            controller_states: std::mem::take(&mut self.controller_states),
+            scenes: std::mem::take(&mut self.scenes),
+            recalled_scene: self.recalled_scene.take(),
+            variation_undo: std::mem::take(&mut self.variation_undo),
+            patch_node_ids: std::mem::take(&mut self.patch_node_ids),
+            patch_metadata: std::mem::take(&mut self.patch_metadata),
+            patch_info_tags: std::mem::take(&mut self.patch_info_tags),
+            tuning: self.tuning.take(),
+        }
+    }
+
+    /// Bring back a patch moved aside by `take_open_patch`, replacing the open one.
+    fn restore_open_patch(&mut self, patch: OpenPatch) {
+        self.midi_learn_target = None;
+        self.midi_learner.reset();
+
+        // Presets saved and playback changed while the other patch was open still apply
+        let mut user_state = patch.user_state;
+        user_state.presets = std::mem::take(&mut self.user_state.presets);
+        user_state.is_playing = self.is_playing;
+
+        self.graph_state = patch.graph_state;
+        self.user_state = user_state;
+        self.cached_params = patch.cached_params;
+        self.current_patch_path = patch.path;
+        self.dirty = patch.dirty;
+        self.watched_file = patch.watched_file;
+        self.midi_mappings = patch.midi_mappings;
+        self.controller_states = patch.controller_states;
+        self.scenes = patch.scenes;
+        self.recalled_scene = patch.recalled_scene;
+        self.variation_undo = patch.variation_undo;
+        self.patch_node_ids = patch.patch_node_ids;
+        self.patch_metadata = patch.patch_metadata;
+        self.patch_info_tags = patch.patch_info_tags;
+        self.tuning = patch.tuning;
+    }
+
+    /// Length of the output fade around a setlist patch change.
+    const SETLIST_FADE: Duration = Duration::from_millis(30);
+
+    /// Handle a MIDI program change, numbered across banks.
+    ///
+    /// With a setlist it loads the matching entry; without one, program N
+    /// recalls scene N of the current patch. MIDI input wakes the UI (see
+    /// `wake_on_midi`), so this runs as soon as the program change arrives
+    /// rather than at the next repaint.
+    fn recall_program(&mut self, number: usize) {
+        if self.setlist_window.setlist.entries.is_empty() {
+            if number < self.scenes.len() {
+                self.recall_scene(number);
+            }
+        } else {
+            self.go_to_setlist_entry(number);
+        }
+    }
+
+    /// Switch to a setlist entry.
+    ///
+    /// An entry recalling a scene of the patch already loaded only recalls
+    /// the scene. Otherwise, while playing, the output fades out first and
+    /// the patch is loaded once it is silent (see `finish_setlist_switch`).
+    fn go_to_setlist_entry(&mut self, index: usize) {
+        let Some(entry) = self.setlist_window.setlist.entries.get(index).cloned() else {
+            self.status_message = Some(format!("No setlist entry at program {}", program_label(index)));
+            return;
+        };
+        self.setlist_window.current = Some(index);
+
+        let loaded = self.pending_switch.is_none() && self.current_patch_path.as_ref() == Some(&entry.path);
+        if let Some(scene) = entry.scene.filter(|_| loaded) {
+            self.recall_scene(scene);
+            self.preload_next_setlist_entry();
+            return;
+        }
+
+        if !self.is_playing {
+            self.load_setlist_entry(index, &entry);
+            self.preload_next_setlist_entry();
+            return;
+        }
+
+        // A switch already fading out just changes its destination
+        let due = match self.pending_switch {
+            Some((_, due)) => due,
+            None => {
+                self.send_command(EngineCommand::FadeOutput {
+                    gain: 0.0,
+                    seconds: Self::SETLIST_FADE.as_secs_f32(),
+                });
+                // Leave time for the faded blocks to reach the device
+                Instant::now() + Self::SETLIST_FADE * 2
+            }
+        };
+        self.pending_switch = Some((index, due));
+    }
+
+    /// Load the pending setlist entry once the output has faded out, and fade back in.
+    fn finish_setlist_switch(&mut self) {
+        let Some((index, due)) = self.pending_switch else {
+            return;
+        };
+        if Instant::now() < due {
+            return;
+        }
+
+        self.pending_switch = None;
+        if let Some(entry) = self.setlist_window.setlist.entries.get(index).cloned() {
+            self.load_setlist_entry(index, &entry);
+        }
+        self.send_command(EngineCommand::FadeOutput {
+            gain: 1.0,
+            seconds: Self::SETLIST_FADE.as_secs_f32(),
+        });
+        self.preload_next_setlist_entry();
+    }
+
+    /// Load a setlist entry's patch and recall its scene.
+    ///
+    /// A patch built ahead by `build_preloaded_patch` is switched to by
+    /// swapping engine graphs; otherwise it is read (or taken from the
+    /// preloader) and loaded here.
+    fn load_setlist_entry(&mut self, index: usize, entry: &SetlistEntry) {
+        let loaded = match self.preloaded_patch.take() {
+            Some(preloaded) if preloaded.path == entry.path => Ok(self.switch_to_preloaded_patch(preloaded)),
+            other => {
+                if other.is_some() {
+                    self.send_command(EngineCommand::ClearPreload);
+                }
+                let patch = match self.patch_preloader.take(&entry.path) {
+                    Some(patch) => patch,
+                    None => self.read_patch_file(&entry.path),
+                };
+                patch.and_then(|patch| self.load_patch(&patch).map(|()| patch.name))
+            }
+        };
+
+        match loaded {
+            Ok(name) => {
+                self.current_patch_path = Some(entry.path.clone());
+                self.watched_file = None;
+                self.forget_preview();
+                self.mark_saved();
+                if let Some(scene) = entry.scene {
+                    self.recall_scene(scene);
+                }
+                self.status_message = Some(format!("{} {}", program_label(index), name));
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Setlist {} failed: {}", program_label(index), e));
+            }
+        }
+    }
+
+    /// Start reading the patch of the entry after the current one.
+    ///
+    /// Once read, `build_preloaded_patch` builds it. Patch scripts are
+    /// compiled when loaded rather than preloaded.
+    fn preload_next_setlist_entry(&mut self) {
+        let setlist = &self.setlist_window.setlist;
+        let next = setlist
+            .next_index(self.setlist_window.current)
+            .and_then(|index| setlist.entries.get(index))
+            .map(|entry| entry.path.clone())
+            .filter(|path| !is_patch_script(path) && self.current_patch_path.as_ref() != Some(path));
+
+        // A patch already built for the entry is kept
+        if self.preloaded_patch.as_ref().map(|preloaded| &preloaded.path) != next.as_ref()
+            && self.preloaded_patch.take().is_some()
+        {
+            self.send_command(EngineCommand::ClearPreload);
+        }
+        match next {
+            Some(path) if self.preloaded_patch.is_none() => self.patch_preloader.preload(&path, load_from_file),
+            Some(_) => {}
+            None => self.patch_preloader.clear(),
+        }
+    }
+
+    /// Build the preloaded setlist patch once it has been read: its editor
+    /// state is set aside and its modules are created in the engine's
+    /// scratch graph while the current patch keeps playing.
+    ///
+    /// Waits while previewing or learning a MIDI mapping, which use the
+    /// scratch graph and the editor. A file that fails to load is left to
+    /// `load_setlist_entry` to report.
+    fn build_preloaded_patch(&mut self) {
+        if self.preview.is_some() || self.midi_learn_target.is_some() {
+            return;
+        }
+        let Some(path) = self.patch_preloader.finished().map(Path::to_path_buf) else {
+            return;
+        };
+        let Some(Ok(patch)) = self.patch_preloader.take(&path) else {
+            return;
+        };
+
+        self.send_command(EngineCommand::BeginPreload);
+        let current = self.take_open_patch();
+        let built = self.build_patch(&patch);
+        let preloaded = self.take_open_patch();
+        self.restore_open_patch(current);
+        self.send_command(EngineCommand::EndPreload);
+
+        match built {
+            Ok(()) => {
+                self.preloaded_patch = Some(PreloadedPatch { path, name: patch.name, patch: preloaded });
+            }
+            Err(e) => {
+                eprintln!("Could not preload {}: {}", path.display(), e);
+                self.send_command(EngineCommand::ClearPreload);
+            }
+        }
+    }
+
+    /// Switch to the preloaded patch: the engine swaps in the graph built
+    /// for it and drops the current one. Returns the patch's name.
+    fn switch_to_preloaded_patch(&mut self, preloaded: PreloadedPatch) -> String {
+        self.send_command(EngineCommand::BeginPreview);
+        self.send_command(EngineCommand::EndPreview { keep: true });
+        self.restore_open_patch(preloaded.patch);
+        self.feedback_throttle.resend_all();
+        preloaded.name
+    }
+
+    /// Drop the preloaded patch and any file being read for it.
+    fn drop_preloaded_patch(&mut self) {
+        self.patch_preloader.clear();
+        if self.preloaded_patch.take().is_some() {
+            self.send_command(EngineCommand::ClearPreload);
+        }
+    }
+
+    /// Reopen the setlist of the last session.
+    fn restore_setlist(&mut self) {
+        if let Some(path) = self.config.setlist.clone() {
+            self.open_setlist(path);
+        }
+    }
+
+    /// Open a setlist file and remember it for the next session.
+    fn open_setlist(&mut self, path: PathBuf) {
+        match Setlist::load(&path) {
+            Ok(setlist) => {
+                self.status_message = Some(format!(
+                    "Setlist: {} ({} entries)",
+                    path.display(),
+                    setlist.entries.len()
+                ));
+                self.setlist_window.set_setlist(setlist, Some(path.clone()));
+                self.config.setlist = Some(path);
+                self.save_config();
+                self.drop_preloaded_patch();
+                self.preload_next_setlist_entry();
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Opening setlist failed: {}", e));
+            }
+        }
+    }
+
+    /// Show an open file dialog for setlists.
+    fn show_open_setlist_dialog(&mut self) {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("Setlist", &["json"])
+            .set_directory(paths::setlists_dir())
+            .pick_file()
+        {
+            self.open_setlist(path);
+        }
+    }
+
+    /// Save the setlist to its file, asking for one if it has none or `save_as` is set.
+    fn save_setlist(&mut self, save_as: bool) {
+        let path = match self.setlist_window.path.clone() {
+            Some(path) if !save_as => path,
+            _ => {
+                let name = match self.setlist_window.setlist.name.as_str() {
+                    "" => "setlist".to_string(),
+                    name => name.to_string(),
+                };
+                let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Setlist", &["json"])
+                    .set_directory(paths::setlists_dir())
+                    .set_file_name(format!("{}.json", name))
+                    .save_file()
+                else {
+                    return;
+                };
+                path
+            }
+        };
+
+        match self.setlist_window.setlist.save(&path) {
+            Ok(()) => {
+                self.setlist_window.path = Some(path.clone());
+                self.setlist_window.modified = false;
+                self.config.setlist = Some(path.clone());
+                self.save_config();
+                self.status_message = Some(format!("Saved setlist: {}", path.display()));
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Saving setlist failed: {}", e));
+            }
+        }
+    }
+
+    /// Start an empty setlist; program changes recall scenes again.
+    fn new_setlist(&mut self) {
+        self.setlist_window.set_setlist(Setlist::default(), None);
+        self.config.setlist = None;
+        self.save_config();
+        self.drop_preloaded_patch();
+    }
+
+    /// Append the current patch to the setlist, with the last recalled scene if asked.
+    fn add_setlist_entry(&mut self, with_scene: bool) {
+        let Some(path) = self.current_patch_path.clone() else {
+            return;
+        };
+        let scene = if with_scene { self.recalled_scene } else { None };
+        let entry = SetlistEntry::new(path, scene);
+        let number = program_label(self.setlist_window.setlist.entries.len());
+        self.status_message = Some(format!("Added {} as program {}", entry.label(), number));
+        self.setlist_window.setlist.entries.push(entry);
+        self.setlist_window.modified = true;
+        self.preload_next_setlist_entry();
+    }
+
+    /// Window for editing the current patch's metadata.
+    fn draw_patch_info(&mut self, ctx: &egui::Context) {
+        if !self.show_patch_info {
+            return;
+        }
+
+        let mut open = true;
+        let mut changed = false;
+        let metadata = &mut self.patch_metadata;
+        let tags = &mut self.patch_info_tags;
+
+        egui::Window::new("Patch Info")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                egui::Grid::new("patch_info_grid")
+                    .num_columns(2)
+                    .spacing([8.0, 6.0])
+                    .show(ui, |ui| {
+                        ui.label("Author");
+                        changed |= ui.text_edit_singleline(&mut metadata.author).changed();
+                        ui.end_row();
+
+                        ui.label("Tags");
+                        if ui.add(egui::TextEdit::singleline(tags).hint_text("bass, acid, mono")).changed() {
+                            metadata.set_tags(tags);
+                            changed = true;
+                        }
+                        ui.end_row();
+
+                        ui.label("Tempo");
+                        ui.horizontal(|ui| {
+                            let mut has_tempo = metadata.tempo.is_some();
+                            if ui.checkbox(&mut has_tempo, "").changed() {
+                                metadata.tempo = has_tempo.then_some(120.0);
+                                changed = true;
+                            }
+                            if let Some(ref mut tempo) = metadata.tempo {
+                                changed |= ui.add(egui::DragValue::new(tempo)
+                                    .range(20.0..=300.0)
+                                    .suffix(" BPM"))
+                                    .changed();
+                            }
+                        });
+                        ui.end_row();
+
+                        for (label, time) in [("Created", metadata.created), ("Modified", metadata.modified)] {
+                            ui.label(label);
+                            let text = time.map_or("Not saved yet".to_string(), |t| format!("{} UTC", format_timestamp(t)));
+                            ui.label(RichText::new(text).color(theme::text::SECONDARY));
+                            ui.end_row();
+                        }
+                    });
+
+                ui.add_space(4.0);
+                ui.label("Description");
+                changed |= ui.add(egui::TextEdit::multiline(&mut metadata.description)
+                    .desired_rows(4)
+                    .desired_width(f32::INFINITY))
+                    .changed();
+            });
+
+        self.show_patch_info = open;
+        if changed {
+            self.dirty.mark_dirty();
+        }
+    }
+
+    /// Smallest Scene Morph module movement that re-applies the morph.
+    const MORPH_THRESHOLD: f32 = 0.002;
+
+    /// Engine IDs of the nodes a new scene captures.
+    ///
+    /// Scene Morph modules are left out, so morphing never moves its own source.
+    fn scene_node_ids(&self, selected_only: bool) -> Vec<u64> {
+        self.graph_state.graph.nodes
+            .iter()
+            .filter(|(node_id, _)| !selected_only || self.graph_state.selected_nodes.contains(node_id))
+            .filter(|(_, node)| node.user_data.module_id != SCENE_MORPH_MODULE)
+            .filter_map(|(node_id, _)| self.user_state.get_engine_node_id(node_id))
+            .collect()
+    }
+
+    /// Store the current parameter values as a new scene.
+    fn capture_scene(&mut self, selected_only: bool) {
+        let node_ids = self.scene_node_ids(selected_only);
+        let name = format!("Scene {}", self.scenes.len() + 1);
+        let scene = Scene::capture(name, &self.create_patch("Scene"), &node_ids);
+
+        self.status_message = Some(format!("Captured {} ({} parameters)", scene.name, scene.values.len()));
+        self.scenes.push(scene);
+        self.dirty.mark_dirty();
+    }
+
+    /// Overwrite a scene with the current values of the nodes it covers.
+    fn update_scene(&mut self, index: usize) {
+        let Some(scene) = self.scenes.get(index) else {
+            return;
+        };
+
+        let node_ids: Vec<u64> = scene.values.iter().map(|v| v.node_id).collect();
+        let updated = Scene::capture(scene.name.clone(), &self.create_patch("Scene"), &node_ids);
+        self.status_message = Some(format!("Updated {}", updated.name));
+        self.scenes[index] = updated;
+        self.dirty.mark_dirty();
+    }
+
+    /// Delete a scene.
+    fn delete_scene(&mut self, index: usize) {
+        if index < self.scenes.len() {
+            self.scenes.remove(index);
+            self.scenes_window.scene_removed(index);
+            self.dirty.mark_dirty();
+        }
+    }
+
+    /// Set every parameter stored in a scene.
+    fn recall_scene(&mut self, index: usize) {
+        let Some(scene) = self.scenes.get(index) else {
+            return;
+        };
+
+        let values = scene.values.clone();
+        self.status_message = Some(format!("Recalled {}", scene.name));
+        self.recalled_scene = Some(index);
+        self.apply_scene_values(&values);
+    }
+
+    /// Recall the scene after the last one recalled, wrapping around.
+    fn recall_next_scene(&mut self) {
+        if self.scenes.is_empty() {
+            self.status_message = Some("No scenes to recall".to_string());
+            return;
+        }
+        let next = self.recalled_scene.map_or(0, |index| (index + 1) % self.scenes.len());
+        self.recall_scene(next);
+    }
+
+    /// Set the morph between the two crossfader scenes at the crossfader position.
+    fn apply_morph(&mut self) {
+        let Some((a, b)) = self.scenes_window.morph_pair(self.scenes.len()) else {
+            return;
+        };
+
+        let discrete = self.discrete_params_by_engine_id();
+        let values = morph(
+            &self.scenes[a],
+            &self.scenes[b],
+            self.scenes_window.morph_position,
+            |node_id, param_index| {
+                discrete
+                    .get(&node_id)
+                    .and_then(|flags| flags.get(param_index).copied())
+                    .unwrap_or(false)
+            },
+        );
+        self.apply_scene_values(&values);
+    }
+
+    /// Write scene values into the graph; `sync_parameters` sends them to the engine.
+    fn apply_scene_values(&mut self, values: &[SceneValue]) {
+        for value in values {
+            self.update_graph_param(value.node_id, value.param_index, value.value.as_f32());
+        }
+    }
+
+    /// Which parameters take discrete steps (choices or toggles), per engine node.
+    ///
+    /// Built in one pass over the graph, so morphing doesn't search the
+    /// node map for every parameter.
+    fn discrete_params_by_engine_id(&self) -> HashMap<u64, &[bool]> {
+        self.user_state.node_id_map
+            .iter()
+            .filter_map(|(graph_node_id, &engine_id)| {
+                let node = self.graph_state.graph.nodes.get(*graph_node_id)?;
+                let flags = self.discrete_params.get(node.user_data.module_id)?;
+                Some((engine_id, flags.as_slice()))
+            })
+            .collect()
+    }
+
+    /// Let the first Scene Morph module in the graph drive the crossfader.
+    ///
+    /// The morph is only re-applied when the module's output moves, so
+    /// parameters can still be tweaked by hand while it sits still.
+    fn follow_scene_morph_module(&mut self) {
+        let position = self.graph_state.graph.nodes
+            .iter()
+            .filter(|(_, node)| node.user_data.module_id == SCENE_MORPH_MODULE)
+            .find_map(|(node_id, _)| {
+                let engine_node_id = self.user_state.get_engine_node_id(node_id)?;
+                self.user_state.get_output_value(engine_node_id, 0)
+            });
+
+        self.scenes_window.morph_module_active = position.is_some();
+        if let Some(position) = position {
+            if (position - self.scenes_window.morph_position).abs() > Self::MORPH_THRESHOLD {
+                self.scenes_window.morph_position = position;
+                self.apply_morph();
+            }
+        }
+    }
+
+    /// Most randomizations that can be undone.
+    const MAX_VARIATION_UNDO: usize = 32;
+
+    /// Randomize or mutate the unlocked parameters of the patch or the selection.
+    ///
+    /// The previous values are kept so the change can be undone in one step,
+    /// and the seed advances so the next variation differs.
+    fn apply_variation(&mut self, variation: Variation) {
+        let node_ids = self.scene_node_ids(self.randomizer.selected_only);
+        let patch = self.create_patch("Variation");
+        let locks: Vec<ParameterLock> = self.user_state.locked_params
+            .iter()
+            .map(|&(node_id, param_index)| ParameterLock::new(node_id, param_index))
+            .collect();
+
+        let seed = self.randomizer.seed;
+        let values = vary(&patch, &node_ids, &self.module_registry, variation, &locks, seed);
+        self.randomizer.seed = seed.wrapping_add(1);
+        if values.is_empty() {
+            self.status_message = Some("Nothing to vary".to_string());
+            return;
+        }
+
+        let previous: Vec<SceneValue> = values
+            .iter()
+            .filter_map(|v| {
+                let node = patch.nodes.iter().find(|node| node.id == v.node_id)?;
+                let value = node.parameters.get(v.param_index)?.clone();
+                Some(SceneValue { value, ..v.clone() })
+            })
+            .collect();
+        if self.variation_undo.len() == Self::MAX_VARIATION_UNDO {
+            self.variation_undo.remove(0);
+        }
+        self.variation_undo.push(previous);
+
+        self.apply_scene_values(&values);
+
+        let verb = match variation {
+            Variation::Randomize => "Randomized",
+            Variation::Mutate { .. } => "Mutated",
+        };
+        if self.randomizer.store_as_scene {
+            let name = format!("{} {}", verb, seed);
+            let scene = Scene::capture(name, &self.create_patch("Scene"), &node_ids);
+            self.scenes.push(scene);
+        }
+        self.status_message = Some(format!("{} {} parameters (seed {})", verb, values.len(), seed));
+    }
+
+    /// Restore the parameter values from before the last randomization.
+    fn undo_variation(&mut self) {
+        if let Some(previous) = self.variation_undo.pop() {
+            self.status_message = Some(format!("Restored {} parameters", previous.len()));
+            self.apply_scene_values(&previous);
+        }
+    }
+
+    /// A graph node's module ID and current parameter values.
+    fn node_settings(&self, graph_node_id: egui_node_graph2::NodeId) -> Option<(&'static str, Vec<ParameterValue>)> {
+        let module_id = self.graph_state.graph.nodes.get(graph_node_id)?.user_data.module_id;
+        let engine_node_id = self.user_state.get_engine_node_id(graph_node_id)?;
+        let patch = self.create_patch("Preset");
+        let node = patch.nodes.into_iter().find(|node| node.id == engine_node_id)?;
+        Some((module_id, node.parameters))
+    }
+
+    /// Set a node's parameters from a preset.
+    fn load_preset(&mut self, graph_node_id: egui_node_graph2::NodeId, name: &str) {
+        let Some((module_id, current)) = self.node_settings(graph_node_id) else {
+            return;
+        };
+        let (Some(preset), Some(module)) = (self.presets.find(module_id, name), self.module_registry.create(module_id)) else {
+            return;
+        };
+
+        let values = preset.apply(&current, module.parameters());
+        for (param_index, value) in values {
+            self.set_graph_param(graph_node_id, param_index, value.as_f32());
+        }
+        self.status_message = Some(format!("Loaded preset: {}", name));
+    }
+
+    /// Save a node's settings as a user preset.
+    fn save_preset(&mut self, graph_node_id: egui_node_graph2::NodeId, name: &str) {
+        let Some((module_id, current)) = self.node_settings(graph_node_id) else {
+            return;
+        };
+        let Some(module) = self.module_registry.create(module_id) else {
+            return;
+        };
+
+        let preset = ModulePreset::capture(name, module_id, &current, module.parameters());
+        match self.presets.save(preset) {
+            Ok(()) => self.status_message = Some(format!("Saved preset: {}", name)),
+            Err(e) => self.status_message = Some(format!("Save preset failed: {}", e)),
+        }
+        self.refresh_preset_menus();
+    }
+
+    /// Delete a user preset of a node's module type.
+    fn delete_preset(&mut self, graph_node_id: egui_node_graph2::NodeId, name: &str) {
+        let Some(node) = self.graph_state.graph.nodes.get(graph_node_id) else {
+            return;
+        };
+
+        match self.presets.delete(node.user_data.module_id, name) {
+            Ok(()) => self.status_message = Some(format!("Deleted preset: {}", name)),
+            Err(e) => self.status_message = Some(format!("Delete preset failed: {}", e)),
+        }
+        self.refresh_preset_menus();
+    }
+
+    /// Show a file dialog and load the selected MIDI file into a MIDI File Player node.
+    fn show_midi_file_dialog(&mut self, graph_node_id: egui_node_graph2::NodeId) {
+        let Some(engine_node_id) = self.user_state.get_engine_node_id(graph_node_id) else {
+            return;
+        };
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("MIDI File", &["mid", "midi"])
+            .pick_file()
+        else {
+            return;
+        };
+
+        match self.load_midi_file(engine_node_id, &path) {
+            Ok(()) => {
+                self.dirty.mark_dirty();
+                self.status_message = Some(format!("Loaded MIDI file: {}", path.display()));
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Load MIDI file failed: {}", e));
+            }
+        }
+    }
+
+    /// Load a MIDI file and hand its notes to a MIDI File Player node.
+    fn load_midi_file(&mut self, engine_node_id: u64, path: &Path) -> Result<(), SmfError> {
+        let file = MidiFile::load(path)?;
+        let sequence = file.note_sequence();
+        self.user_state.midi_files.insert(engine_node_id, LoadedMidiFile {
+            path: path.to_path_buf(),
+            tracks: sequence.tracks,
+            length_seconds: sequence.length_seconds,
+        });
+        self.send_command(EngineCommand::SetNoteSequence {
+            node_id: engine_node_id,
+            sequence: Some(Arc::new(sequence)),
+        });
+        Ok(())
+    }
+
+    /// The tuning of a note module of its own, or the patch tuning (`node` None).
+    fn tuning_of(&self, node: Option<u64>) -> Option<&TuningData> {
+        match node {
+            Some(engine_node_id) => self.user_state.node_tunings.get(&engine_node_id),
+            None => self.tuning.as_ref(),
+        }
+    }
+
+    /// Set the patch tuning (`node` None) or a note module's own tuning, and
+    /// send it to the engine.
+    fn set_tuning(&mut self, node: Option<u64>, data: TuningData) -> Result<(), ScalaError> {
+        let scale = Scale::parse(&data.scale)?;
+        let mapping = match &data.mapping {
+            Some(text) => KeyboardMapping::parse(text)?,
+            None => KeyboardMapping::default(),
+        };
+        let tuning = Arc::new(mapping.tuning(&scale));
+
+        match node {
+            Some(engine_node_id) => {
+                self.user_state.node_tunings.insert(engine_node_id, data);
+            }
+            None => self.tuning = Some(data),
+        }
+        self.send_command(match node {
+            Some(node_id) => EngineCommand::SetModuleTuning { node_id, tuning: Some(tuning) },
+            None => EngineCommand::SetTuning { tuning },
+        });
+        Ok(())
+    }
+
+    /// Return the patch to 12-TET (`node` None), or a note module to the
+    /// patch tuning.
+    fn reset_tuning(&mut self, node: Option<u64>) {
+        match node {
+            Some(engine_node_id) => {
+                self.user_state.node_tunings.remove(&engine_node_id);
+            }
+            None => self.tuning = None,
+        }
+        self.send_command(match node {
+            Some(node_id) => EngineCommand::SetModuleTuning { node_id, tuning: None },
+            None => EngineCommand::SetTuning { tuning: Arc::new(Tuning::equal()) },
+        });
+    }
+
+    /// Apply a tuning saved in a patch; one that can't be read is reset.
+    fn restore_tuning(&mut self, node: Option<u64>, data: Option<&TuningData>) {
+        if let Some(data) = data {
+            match self.set_tuning(node, data.clone()) {
+                Ok(()) => return,
+                Err(e) => eprintln!("Could not apply tuning {}: {}", data.label(), e),
+            }
+        }
+        self.reset_tuning(node);
+    }
+
+    /// Show a file dialog and load a Scala scale, or with `mapping` a keyboard
+    /// mapping, into the patch tuning (`node` None) or a module's own tuning.
+    ///
+    /// A new scale keeps the keyboard mapping loaded before it; a mapping
+    /// needs a scale to apply to.
+    fn show_tuning_dialog(&mut self, node: Option<u64>, mapping: bool) {
+        let current = self.tuning_of(node).cloned();
+        if mapping && current.is_none() {
+            self.status_message = Some("Load a scale before a keyboard mapping".to_string());
+            return;
+        }
+        let (filter, extension) = if mapping { ("Scala Keyboard Mapping", "kbm") } else { ("Scala Scale", "scl") };
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter(filter, &[extension])
+            .pick_file()
+        else {
+            return;
+        };
+
+        let result = std::fs::read(&path).map_err(ScalaError::from).and_then(|bytes| {
+            let text = String::from_utf8_lossy(&bytes).into_owned();
+            let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+            let data = match current {
+                Some(current) if mapping => TuningData { mapping: Some(text), mapping_name: Some(name), ..current },
+                Some(current) => TuningData { name, scale: text, ..current },
+                None => TuningData::new(name, text),
+            };
+            self.set_tuning(node, data)
+        });
+
+        match result {
+            Ok(()) => {
+                self.dirty.mark_dirty();
+                let label = self.tuning_of(node).map(TuningData::label).unwrap_or_default();
+                self.status_message = Some(format!("Tuning: {}", label));
+            }
+            Err(e) => {
+                let kind = if mapping { "keyboard mapping" } else { "scale" };
+                self.status_message = Some(format!("Load {} failed: {}", kind, e));
+            }
+        }
+    }
+
+    /// Rebuild the preset menus shown on the nodes.
+    fn refresh_preset_menus(&mut self) {
+        self.user_state.presets = self.module_registry
+            .list_modules()
+            .iter()
+            .map(|info| {
+                let entries = self.presets
+                    .presets(info.id)
+                    .into_iter()
+                    .map(|preset| PresetMenuEntry { name: preset.name.clone(), factory: preset.factory })
+                    .collect();
+                (info.id.to_string(), entries)
+            })
+            .collect();
+    }
 
-                    // Send connection command to engine
-                    if let Some(cmd) = self.build_connect_command(output_id, input_id) {
-                        self.send_command(cmd);
-                    }
+    /// Draw the Save Preset dialog while it is open.
+    fn draw_preset_save_dialog(&mut self, ctx: &egui::Context) {
+        let Some(ref mut dialog) = self.preset_save else {
+            return;
+        };
 
-                    // Set up input monitoring if this is an exposed parameter
-                    if let Some(monitor_cmd) = self.build_monitor_input_command(input_id) {
-                        self.send_command(monitor_cmd);
-                    }
+        let mut open = true;
+        let mut save = false;
+        egui::Window::new("Save Preset")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                let response = ui.text_edit_singleline(&mut dialog.name);
+                response.request_focus();
+                let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+                ui.add_space(4.0);
+                let valid = !dialog.name.trim().is_empty();
+                let clicked = ui.add_enabled(valid, egui::Button::new("Save")).clicked();
+                save = valid && (clicked || entered);
+            });
 
-                    // Set up output monitoring for cable animation
-                    if let Some(monitor_cmd) = self.build_monitor_output_command(output_id) {
-                        self.send_command(monitor_cmd);
-                    }
-                }
+        if save {
+            if let Some(dialog) = self.preset_save.take() {
+                self.save_preset(dialog.node_id, dialog.name.trim());
             }
+        } else if !open {
+            self.preset_save = None;
         }
+    }
 
-        // Load MIDI mappings
-        self.midi_mappings = patch.midi_mappings.clone();
-        // Sync mappings to user state for UI display
-        for mapping in &self.midi_mappings {
-            self.user_state.set_midi_mapping(
-                mapping.node_id,
-                mapping.param_index,
-                mapping.cc_number,
-                mapping.channel,
-            );
+    /// How often the patch is checked for mistakes.
+    const LINT_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Select the node a lint is about, so it can be found in the graph.
+    fn select_engine_node(&mut self, engine_node_id: u64) {
+        let graph_node_id = self.user_state.node_id_map
+            .iter()
+            .find(|(_, &engine_id)| engine_id == engine_node_id)
+            .map(|(graph_node_id, _)| *graph_node_id);
+        if let Some(graph_node_id) = graph_node_id {
+            self.graph_state.selected_nodes = vec![graph_node_id];
         }
+    }
 
-        // Restore playback state
-        if was_playing {
-            self.is_playing = true;
-            self.user_state.is_playing = true;
-            self.send_command(EngineCommand::SetPlaying(true));
+    /// How often the watched patch file is checked for changes.
+    const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Start or stop watching the current patch file.
+    fn toggle_watch(&mut self) {
+        if self.watched_file.take().is_some() {
+            self.status_message = Some("Stopped watching patch file".to_string());
+            return;
         }
 
-        Ok(())
+        if let Some(path) = self.current_patch_path.clone() {
+            self.status_message = Some(format!("Watching: {}", path.display()));
+            // No known modification time, so the first poll syncs the graph to the file
+            self.watched_file = Some(WatchedFile {
+                path,
+                modified: None,
+                last_check: Instant::now() - Self::WATCH_POLL_INTERVAL,
+            });
+        }
     }
 
-    /// Clear the entire graph.
-    fn clear_graph(&mut self) {
-        // Send clear command to audio engine
-        self.send_command(EngineCommand::ClearGraph);
+    /// Check the watched file and hot-reload it if it changed.
+    fn poll_watched_file(&mut self) {
+        let Some(ref mut watched) = self.watched_file else {
+            return;
+        };
 
-        // Clear graph state
-        self.graph_state.graph = egui_node_graph2::Graph::default();
-        self.graph_state.node_positions.clear();
-        self.graph_state.node_order.clear();
+        if watched.last_check.elapsed() < Self::WATCH_POLL_INTERVAL {
+            return;
+        }
+        watched.last_check = Instant::now();
 
-        // Clear user state (also clears MIDI mapping UI state)
-        self.user_state.clear();
+        let modified = std::fs::metadata(&watched.path)
+            .and_then(|meta| meta.modified())
+            .ok();
+        if modified.is_none() || modified == watched.modified {
+            return;
+        }
+        watched.modified = modified;
+
+        let path = watched.path.clone();
+        match self.read_patch_file(&path).and_then(|patch| self.hot_reload(&patch)) {
+            Ok(0) => {}
+            Ok(changes) => {
+                self.status_message = Some(format!(
+                    "Reloaded: {} ({} change{})",
+                    path.display(),
+                    changes,
+                    if changes == 1 { "" } else { "s" }
+                ));
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Reload failed: {}", e));
+            }
+        }
+    }
 
-        // Clear cached parameters
-        self.cached_params.clear();
+    /// Bring the running graph in line with `patch`, touching only what differs.
+    ///
+    /// Unchanged modules are left alone so they keep their state and audio
+    /// keeps running. Returns the number of changes applied.
+    fn hot_reload(&mut self, patch: &Patch) -> Result<usize, PatchError> {
+        let (current, mut graph_ids) = self.current_patch_with_file_ids();
+        let diff = PatchDiff::between(&current, patch);
+        if diff.is_empty() {
+            return Ok(0);
+        }
 
-        // Clear MIDI mappings
-        self.midi_mappings.clear();
-        self.midi_learn_target = None;
-    }
+        // Check modules exist before touching anything
+        if let Some(unknown) = diff.added_nodes
+            .iter()
+            .find(|node| self.find_template_for_module(&node.module_id).is_none())
+        {
+            return Err(PatchError::UnknownModule(unknown.module_id.clone()));
+        }
 
-    /// Start a new patch - clears the graph and resets the current file path.
-    fn new_patch(&mut self) {
-        self.clear_graph();
-        self.current_patch_path = None;
-        self.status_message = Some("New patch created".to_string());
-    }
+        for conn in &diff.removed_connections {
+            let (Some(&from), Some(&to)) = (graph_ids.get(&conn.from_node), graph_ids.get(&conn.to_node)) else {
+                continue;
+            };
+            if let Some((output_id, input_id)) = self.resolve_connection_ports(from, to, conn) {
+                self.disconnect_ports(output_id, input_id);
+            }
+        }
 
-    /// Find the template for a given module ID.
-    fn find_template_for_module(&self, module_id: &str) -> Option<SynthNodeTemplate> {
-        use egui_node_graph2::NodeTemplateIter;
-        AllNodeTemplates.all_kinds()
-            .into_iter()
-            .find(|t| t.module_id() == module_id)
-    }
+        for patch_id in &diff.removed_nodes {
+            if let Some(graph_node_id) = graph_ids.remove(patch_id) {
+                self.remove_graph_node(graph_node_id);
+            }
+            self.patch_node_ids.remove(patch_id);
+        }
 
-    /// Show a save file dialog and save the current patch.
-    fn show_save_dialog(&mut self) {
-        let default_name = self.current_patch_path
-            .as_ref()
-            .and_then(|p| p.file_name())
-            .and_then(|n| n.to_str())
-            .unwrap_or("patch.json");
+        let added = Patch {
+            nodes: diff.added_nodes.clone(),
+            ..Patch::new(patch.name.clone())
+        };
+        let added_ids = self.instantiate_patch(&added, egui::Vec2::ZERO)?;
+        graph_ids.extend(added_ids.iter().map(|(id, graph_id)| (*id, *graph_id)));
+        self.patch_node_ids.extend(added_ids);
 
-        if let Some(path) = rfd::FileDialog::new()
-            .add_filter("Synth Patch", &["json"])
-            .set_file_name(default_name)
-            .save_file()
-        {
-            // Derive patch name from filename
-            let name = path.file_stem()
-                .and_then(|s| s.to_str())
-                .unwrap_or("Untitled");
+        for change in &diff.changed_parameters {
+            if let Some(&graph_node_id) = graph_ids.get(&change.node_id) {
+                self.set_graph_param(graph_node_id, change.param_index, change.value.as_f32());
+            }
+        }
 
-            let patch = self.create_patch(name);
-            match save_to_file(&patch, &path) {
-                Ok(()) => {
-                    self.current_patch_path = Some(path.clone());
-                    self.status_message = Some(format!("Saved: {}", path.display()));
-                }
-                Err(e) => {
-                    self.status_message = Some(format!("Save failed: {}", e));
-                }
+        for (patch_id, position) in &diff.moved_nodes {
+            if let Some(&graph_node_id) = graph_ids.get(patch_id) {
+                let pos = self.canonical_to_view(*position);
+                self.graph_state.node_positions.insert(graph_node_id, pos);
             }
         }
-    }
 
-    /// Show a load file dialog and load the selected patch.
-    fn show_load_dialog(&mut self) {
-        if let Some(path) = rfd::FileDialog::new()
-            .add_filter("Synth Patch", &["json"])
-            .pick_file()
-        {
-            match load_from_file(&path) {
-                Ok(patch) => {
-                    match self.load_patch(&patch) {
-                        Ok(()) => {
-                            self.current_patch_path = Some(path.clone());
-                            self.status_message = Some(format!("Loaded: {}", patch.name));
-                        }
-                        Err(e) => {
-                            self.status_message = Some(format!("Load failed: {}", e));
-                        }
-                    }
-                }
-                Err(e) => {
-                    self.status_message = Some(format!("Load failed: {}", e));
-                }
+        for conn in &diff.added_connections {
+            let (Some(&from), Some(&to)) = (graph_ids.get(&conn.from_node), graph_ids.get(&conn.to_node)) else {
+                continue;
+            };
+            if let Some((output_id, input_id)) = self.resolve_connection_ports(from, to, conn) {
+                self.connect_ports(output_id, input_id);
+            }
+        }
+
+        if let Some(ref mappings) = diff.midi_mappings {
+            self.restore_midi_mappings(mappings, &graph_ids);
+        }
+        if let Some(ref scenes) = diff.scenes {
+            self.restore_scenes(scenes, &graph_ids);
+        }
+        if let Some(ref locks) = diff.locks {
+            self.restore_locks(locks, &graph_ids);
+        }
+        if let Some(ref tuning) = diff.tuning {
+            self.restore_tuning(None, tuning.as_ref());
+        }
+        for (patch_id, tuning) in &diff.retuned_nodes {
+            let engine_node_id = graph_ids
+                .get(patch_id)
+                .and_then(|&graph_node_id| self.user_state.get_engine_node_id(graph_node_id));
+            if let Some(engine_node_id) = engine_node_id {
+                self.restore_tuning(Some(engine_node_id), tuning.as_ref());
+            }
+        }
+        for (patch_id, device) in &diff.rerouted_nodes {
+            let engine_node_id = graph_ids
+                .get(patch_id)
+                .and_then(|&graph_node_id| self.user_state.get_engine_node_id(graph_node_id));
+            if let Some(engine_node_id) = engine_node_id {
+                self.set_node_midi_input(engine_node_id, device.clone());
             }
         }
+
+        // The graph now matches the file on disk
+        self.mark_saved();
+
+        Ok(diff.removed_nodes.len()
+            + diff.added_nodes.len()
+            + diff.changed_parameters.len()
+            + diff.moved_nodes.len()
+            + diff.retuned_nodes.len()
+            + diff.rerouted_nodes.len()
+            + diff.removed_connections.len()
+            + diff.added_connections.len()
+            + usize::from(diff.midi_mappings.is_some())
+            + usize::from(diff.scenes.is_some())
+            + usize::from(diff.locks.is_some())
+            + usize::from(diff.tuning.is_some()))
     }
 
-    /// Quick save to the current path, or show save dialog if no path.
-    fn quick_save(&mut self) {
-        if let Some(path) = self.current_patch_path.clone() {
-            let name = path.file_stem()
-                .and_then(|s| s.to_str())
-                .unwrap_or("Untitled");
+    /// Snapshot the graph as a patch whose node IDs match the current patch file.
+    ///
+    /// Nodes that came from the file keep their patch IDs. Nodes added in the
+    /// editor get IDs counting down from `u64::MAX`, so they don't collide with
+    /// the file and a diff against it removes them. Also returns the graph node
+    /// for every ID in the snapshot.
+    fn current_patch_with_file_ids(&self) -> (Patch, HashMap<u64, egui_node_graph2::NodeId>) {
+        let mut patch = self.create_patch("Current");
+
+        let graph_to_patch: HashMap<egui_node_graph2::NodeId, u64> = self.patch_node_ids
+            .iter()
+            .map(|(patch_id, graph_id)| (*graph_id, *patch_id))
+            .collect();
 
-            let patch = self.create_patch(name);
-            match save_to_file(&patch, &path) {
-                Ok(()) => {
-                    self.status_message = Some(format!("Saved: {}", path.display()));
-                }
-                Err(e) => {
-                    self.status_message = Some(format!("Save failed: {}", e));
-                }
-            }
-        } else {
-            self.show_save_dialog();
+        let mut next_unmapped_id = u64::MAX;
+        let mut engine_to_patch: HashMap<u64, u64> = HashMap::new();
+        let mut graph_ids: HashMap<u64, egui_node_graph2::NodeId> = HashMap::new();
+
+        for (graph_id, engine_id) in &self.user_state.node_id_map {
+            let patch_id = graph_to_patch.get(graph_id).copied().unwrap_or_else(|| {
+                let id = next_unmapped_id;
+                next_unmapped_id -= 1;
+                id
+            });
+            engine_to_patch.insert(*engine_id, patch_id);
+            graph_ids.insert(patch_id, *graph_id);
         }
+        patch.renumber(&engine_to_patch);
+
+        (patch, graph_ids)
+    }
+
+    /// Match the graph to the IDs its nodes were given in a file just written.
+    ///
+    /// `file_ids` maps the engine node IDs of the saved patch to file IDs.
+    /// Without this, a watched file would still be matched against the IDs of
+    /// the file loaded before, and hot-reload would recreate every module.
+    fn adopt_file_ids(&mut self, file_ids: &HashMap<u64, u64>) {
+        self.patch_node_ids = self.user_state.node_id_map
+            .iter()
+            .filter_map(|(graph_id, engine_id)| Some((*file_ids.get(engine_id)?, *graph_id)))
+            .collect();
     }
 
     /// Validate a connection and return an error message if invalid.
@@ -1999,6 +5475,8 @@ impl SynthApp {
 
     /// Draw the bottom status bar
     fn draw_status_bar(&mut self, ui: &mut egui::Ui) {
+        let mut lint_node: Option<u64> = None;
+
         ui.horizontal(|ui| {
             ui.add_space(8.0);
 
@@ -2042,22 +5520,58 @@ impl SynthApp {
             }
 
             ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-                // Show current patch name if any
-                if let Some(ref path) = self.current_patch_path {
-                    if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
-                        ui.label(RichText::new(name)
-                            .color(theme::text::SECONDARY)
-                            .small());
-                        ui.label(RichText::new("|")
-                            .color(theme::text::DISABLED)
-                            .small());
-                    }
+                // Patch mistakes, listed in a menu; clicking one selects its node
+                if !self.lints.is_empty() {
+                    let has_errors = lint::has_errors(&self.lints);
+                    let color = if has_errors { theme::accent::ERROR } else { theme::accent::WARNING };
+                    let title = format!(
+                        "⚠ {} issue{}",
+                        self.lints.len(),
+                        if self.lints.len() == 1 { "" } else { "s" }
+                    );
+                    ui.menu_button(RichText::new(title).color(color).small(), |ui| {
+                        for finding in &self.lints {
+                            let color = match finding.severity {
+                                lint::Severity::Error => theme::accent::ERROR,
+                                lint::Severity::Warning => theme::accent::WARNING,
+                            };
+                            let text = RichText::new(&finding.message).color(color).small();
+                            if ui.add(egui::Button::new(text).frame(false)).clicked() {
+                                lint_node = finding.node_id;
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                    ui.label(RichText::new("|")
+                        .color(theme::text::DISABLED)
+                        .small());
+                }
+
+                // Show current patch name if any, marked with * when there are unsaved changes
+                let is_dirty = self.dirty.is_dirty();
+                let name = self.current_patch_path
+                    .as_ref()
+                    .and_then(|path| path.file_stem())
+                    .and_then(|s| s.to_str())
+                    .or(if is_dirty { Some("Untitled") } else { None });
+                if let Some(name) = name {
+                    let label = if is_dirty { format!("{}*", name) } else { name.to_string() };
+                    ui.label(RichText::new(label)
+                        .color(theme::text::SECONDARY)
+                        .small());
+                    ui.label(RichText::new("|")
+                        .color(theme::text::DISABLED)
+                        .small());
                 }
                 ui.label(RichText::new("Modular Synth v0.1")
                     .color(theme::text::DISABLED)
                     .small());
             });
         });
+
+        if let Some(engine_node_id) = lint_node {
+            self.select_engine_node(engine_node_id);
+        }
     }
 
     /// Check if there are any Keyboard modules in the graph.
@@ -2222,23 +5736,64 @@ impl SynthApp {
 struct ToolbarActions {
     toggle_playing: bool,
     select_device: Option<usize>,
+    /// Input device to select (`Some(None)` closes the input).
+    select_input_device: Option<Option<usize>>,
     refresh_devices: bool,
     save_patch: bool,
     save_as_patch: bool,
     load_patch: bool,
     new_patch: bool,
+    toggle_watch: bool,
+    toggle_patch_info: bool,
+    toggle_library: bool,
+    toggle_scenes: bool,
+    toggle_setlist: bool,
+    toggle_randomizer: bool,
+    load_scale: bool,
+    load_mapping: bool,
+    reset_tuning: bool,
+    toggle_routing: bool,
+    toggle_audio_settings: bool,
+    // Recording actions
+    toggle_arm: bool,
+    start_recording: bool,
+    stop_recording: bool,
+    record_source: Option<RecordSource>,
     // MIDI actions
     connect_midi_device: Option<usize>,
+    disconnect_midi_device: Option<String>,
     disconnect_midi: bool,
+    toggle_virtual_midi: bool,
+    toggle_midi_recording: bool,
+    learn_midi_action: Option<MidiAction>,
+    clear_midi_action: Option<MidiAction>,
+    cancel_midi_learn: bool,
     refresh_midi_devices: bool,
+    connect_midi_output: Option<usize>,
+    disconnect_midi_output: Option<String>,
+    toggle_virtual_midi_output: bool,
+    /// Controller feedback output to choose (`Some(None)` turns feedback off).
+    select_feedback_output: Option<Option<String>>,
+    resend_feedback: bool,
+}
+
+impl Drop for SynthApp {
+    fn drop(&mut self) {
+        // Window state and interface scale are only saved on exit
+        self.save_config();
+    }
 }
 
 impl eframe::App for SynthApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Apply theme on first frame
+        // Apply theme and the saved interface scale on first frame
         if !self.theme_applied {
             theme::apply_theme(ctx);
+            ctx.set_zoom_factor(self.config.ui_scale);
             self.theme_applied = true;
+        } else {
+            // The scale set above only takes effect on the next frame
+            self.track_window_state(ctx);
         }
 
         // Process events from the audio engine
@@ -2250,13 +5805,24 @@ impl eframe::App for SynthApp {
 
         // Request continuous repaints when playing (for LED indicators and other visualizations)
         // Also repaint continuously when there are keyboard or MIDI Note modules to catch all events
-        if self.is_playing || self.has_keyboard_modules() || self.has_midi_note_modules() {
+        // and while an audio input is selected to keep its meter moving
+        if self.is_playing
+            || self.has_keyboard_modules()
+            || self.has_midi_note_modules()
+            || self.selected_input_device.is_some()
+        {
             ctx.request_repaint();
         }
 
         // Handle keyboard shortcuts
         let mut keyboard_save = false;
         let mut keyboard_load = false;
+        let mut keyboard_copy = false;
+        let mut keyboard_duplicate = false;
+        let mut clipboard_paste: Option<String> = None;
+
+        // Clipboard shortcuts must not steal copy/paste from focused text fields
+        let editing_text = ctx.wants_keyboard_input();
 
         ctx.input(|i| {
             // Ctrl+S: Save
@@ -2267,6 +5833,20 @@ impl eframe::App for SynthApp {
             if i.modifiers.ctrl && i.key_pressed(egui::Key::O) {
                 keyboard_load = true;
             }
+            if !editing_text {
+                // Ctrl+C / Ctrl+V arrive as clipboard events rather than key presses
+                for event in &i.events {
+                    match event {
+                        egui::Event::Copy => keyboard_copy = true,
+                        egui::Event::Paste(text) => clipboard_paste = Some(text.clone()),
+                        _ => {}
+                    }
+                }
+                // Ctrl+D: Duplicate selection
+                if i.modifiers.ctrl && i.key_pressed(egui::Key::D) {
+                    keyboard_duplicate = true;
+                }
+            }
         });
 
         // Handle musical keyboard input (QWERTY to notes)
@@ -2295,24 +5875,84 @@ impl eframe::App for SynthApp {
                 self.draw_status_bar(ui);
             });
 
+        // Library browser (left side panel, drawn before the central editor)
+        let preview_status = self.preview.as_ref().map(|preview| PreviewStatus {
+            path: &preview.path,
+            previous_name: &preview.previous_name,
+        });
+        let library_actions = self.library_browser.show(ctx, preview_status);
+
         // Main content area - the node graph editor
         self.draw_main_area(ctx);
 
+        // Crash recovery prompt (only shown after an unclean shutdown)
+        self.draw_recovery_dialog(ctx);
+
+        self.draw_patch_info(ctx);
+        self.draw_preset_save_dialog(ctx);
+
+        let has_selection = !self.graph_state.selected_nodes.is_empty();
+        let scene_actions = self.scenes_window.show(ctx, &mut self.scenes, has_selection);
+        let setlist_actions = self.setlist_window.show(
+            ctx,
+            self.current_patch_path.is_some(),
+            self.recalled_scene.is_some(),
+        );
+        self.follow_scene_morph_module();
+        let randomize_actions = self.randomizer.show(
+            ctx,
+            has_selection,
+            self.variation_undo.len(),
+            self.user_state.locked_params.len(),
+        );
+        let device_channels = self.audio_engine.as_ref().map(|e| e.channels() as usize).unwrap_or(2);
+        if let Some(routing) = self.routing_window.show(ctx, device_channels) {
+            self.send_command(EngineCommand::SetOutputRouting(routing));
+            self.config.output_routing = Some(routing.masks().to_vec());
+            self.save_config();
+        }
+        let stream_settings = match &self.audio_engine {
+            Ok(engine) => self.audio_settings.show(ctx, engine),
+            Err(_) => None,
+        };
+        if let Some(settings) = stream_settings {
+            self.apply_stream_settings(settings);
+        }
+
         // Sync parameter values to the audio engine
         self.sync_parameters();
 
         // Handle deferred actions (to avoid borrow checker issues)
         if toolbar_actions.toggle_playing {
-            self.is_playing = !self.is_playing;
-            self.user_state.is_playing = self.is_playing;
-            self.send_command(EngineCommand::SetPlaying(self.is_playing));
+            self.toggle_playing();
+        }
+
+        // Handle recording actions
+        if let Some(source) = toolbar_actions.record_source {
+            self.record_source = source;
+        }
+        if toolbar_actions.toggle_arm {
+            self.record_state = match self.record_state {
+                RecordState::Armed => RecordState::Idle,
+                _ => RecordState::Armed,
+            };
+        }
+        if toolbar_actions.start_recording {
+            self.start_recording();
         }
+        if toolbar_actions.stop_recording {
+            self.stop_recording();
+        }
+        self.poll_recorder();
         if toolbar_actions.refresh_devices {
             self.refresh_devices();
         }
         if let Some(device_index) = toolbar_actions.select_device {
             self.select_device(device_index);
         }
+        if let Some(input_index) = toolbar_actions.select_input_device {
+            self.select_input_device(input_index);
+        }
 
         // Handle save/load actions (from toolbar buttons or keyboard shortcuts)
         if toolbar_actions.save_patch || keyboard_save {
@@ -2327,6 +5967,115 @@ impl eframe::App for SynthApp {
         if toolbar_actions.new_patch {
             self.new_patch();
         }
+        if toolbar_actions.toggle_watch {
+            self.toggle_watch();
+        }
+        if toolbar_actions.toggle_patch_info {
+            self.show_patch_info = !self.show_patch_info;
+        }
+        if toolbar_actions.toggle_library {
+            self.library_browser.open = !self.library_browser.open;
+        }
+        if toolbar_actions.toggle_scenes {
+            self.scenes_window.open = !self.scenes_window.open;
+        }
+        if toolbar_actions.toggle_setlist {
+            self.setlist_window.open = !self.setlist_window.open;
+        }
+        if toolbar_actions.toggle_randomizer {
+            self.randomizer.open = !self.randomizer.open;
+        }
+        if toolbar_actions.load_scale || toolbar_actions.load_mapping {
+            self.show_tuning_dialog(None, toolbar_actions.load_mapping);
+        }
+        if toolbar_actions.reset_tuning {
+            self.reset_tuning(None);
+            self.dirty.mark_dirty();
+            self.status_message = Some("Tuning: 12-TET".to_string());
+        }
+        if toolbar_actions.toggle_routing {
+            self.routing_window.open = !self.routing_window.open;
+        }
+        if toolbar_actions.toggle_audio_settings {
+            self.audio_settings.open = !self.audio_settings.open;
+            if let (true, Ok(engine)) = (self.audio_settings.open, &self.audio_engine) {
+                self.audio_settings.refresh(engine);
+            }
+        }
+
+        // Handle scene actions
+        if let Some(selected_only) = scene_actions.capture {
+            self.capture_scene(selected_only);
+        }
+        if let Some(index) = scene_actions.recall {
+            self.recall_scene(index);
+        }
+        if let Some(index) = scene_actions.update {
+            self.update_scene(index);
+        }
+        if let Some(index) = scene_actions.delete {
+            self.delete_scene(index);
+        }
+        if scene_actions.renamed {
+            self.dirty.mark_dirty();
+        }
+        if scene_actions.morph {
+            self.apply_morph();
+        }
+
+        // Handle setlist actions
+        if setlist_actions.new {
+            self.new_setlist();
+        }
+        if setlist_actions.open {
+            self.show_open_setlist_dialog();
+        }
+        if let Some(save_as) = setlist_actions.save {
+            self.save_setlist(save_as);
+        }
+        if let Some(with_scene) = setlist_actions.add {
+            self.add_setlist_entry(with_scene);
+        }
+        if let Some(index) = setlist_actions.go {
+            self.go_to_setlist_entry(index);
+        }
+
+        // Handle randomizer actions
+        if let Some(variation) = randomize_actions.vary {
+            self.apply_variation(variation);
+        }
+        if randomize_actions.undo {
+            self.undo_variation();
+        }
+        if randomize_actions.clear_locks {
+            self.user_state.locked_params.clear();
+            self.dirty.mark_dirty();
+        }
+
+        // Handle library actions
+        if let Some(path) = library_actions.preview {
+            self.preview_patch(path);
+        }
+        if let Some(path) = library_actions.open {
+            self.open_library_patch(path);
+        }
+        if library_actions.keep_preview {
+            self.keep_preview();
+        }
+        if library_actions.end_preview {
+            self.end_preview();
+        }
+
+        // Handle clipboard actions
+        if keyboard_copy {
+            self.copy_selection(ctx);
+        }
+        if let Some(text) = clipboard_paste {
+            self.paste_fragment(&text);
+        }
+        if keyboard_duplicate {
+            self.duplicate_selection();
+        }
 
         // Handle MIDI actions
         if toolbar_actions.refresh_midi_devices {
@@ -2335,13 +6084,81 @@ impl eframe::App for SynthApp {
         if let Some(device_index) = toolbar_actions.connect_midi_device {
             self.connect_midi_device(device_index);
         }
+        if let Some(name) = toolbar_actions.disconnect_midi_device {
+            self.disconnect_midi_device(&name);
+        }
         if toolbar_actions.disconnect_midi {
-            self.disconnect_midi_device();
+            self.disconnect_all_midi_devices();
+        }
+        if toolbar_actions.toggle_virtual_midi {
+            self.toggle_virtual_midi_port();
+        }
+        if toolbar_actions.toggle_midi_recording {
+            self.toggle_midi_recording();
+        }
+        if let Some(action) = toolbar_actions.learn_midi_action {
+            self.start_midi_learn(MidiLearnTarget::action(action));
+        }
+        if toolbar_actions.cancel_midi_learn {
+            self.cancel_midi_learn();
+        }
+        if let Some(action) = toolbar_actions.clear_midi_action {
+            self.midi_mappings.retain(|m| m.action != Some(action));
+            self.dirty.mark_dirty();
+        }
+        if let Some(device_index) = toolbar_actions.connect_midi_output {
+            self.connect_midi_output(device_index);
+        }
+        if let Some(name) = toolbar_actions.disconnect_midi_output {
+            self.disconnect_midi_output(&name);
+        }
+        if toolbar_actions.toggle_virtual_midi_output {
+            self.toggle_virtual_midi_output();
+        }
+        if let Some(name) = toolbar_actions.select_feedback_output {
+            self.set_feedback_output(name);
+        }
+        if toolbar_actions.resend_feedback {
+            self.feedback_throttle.resend_all();
         }
 
-        // Process pending MIDI events
+        // Reconnect unplugged MIDI devices and process pending MIDI events
+        self.check_midi_connections();
+        self.sync_midi_input_names();
         self.process_midi_events();
 
+        // Keep motorized faders and LED rings in step with the parameters
+        self.send_controller_feedback();
+
+        // Load the next setlist patch once the output has faded out, and
+        // build the one after as soon as it has been read
+        self.finish_setlist_switch();
+        if self.pending_switch.is_some() {
+            ctx.request_repaint_after(Self::SETLIST_FADE);
+        }
+        self.build_preloaded_patch();
+        if self.patch_preloader.is_reading() {
+            ctx.request_repaint_after(Self::SETLIST_FADE);
+        }
+
+        // Hot-reload the watched patch file if it changed on disk
+        if self.watched_file.is_some() {
+            self.poll_watched_file();
+            ctx.request_repaint_after(Self::WATCH_POLL_INTERVAL);
+        }
+
+        // Re-check the patch for mistakes shown in the status bar
+        if self.last_lint.elapsed() >= Self::LINT_INTERVAL {
+            self.lints = lint::lint(&self.create_patch("Lint"), &self.module_registry);
+            self.last_lint = Instant::now();
+        }
+
+        // Periodic background autosave; make sure we wake up for it even when idle
+        self.autosave_if_needed();
+        if self.dirty.needs_autosave() {
+            ctx.request_repaint_after(AUTOSAVE_INTERVAL);
+        }
+
         // Clear status message after showing it for one frame
         // This gives user time to read it but doesn't persist forever
         if had_status_message {
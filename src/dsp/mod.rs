@@ -1,15 +1,18 @@
 //! DSP module
 //!
 //! Core DSP traits and types.
-//! Defines the DspModule trait, ports, parameters, and signal types.
+//! Defines the DspModule trait, ports, parameters, signal types, note sequences
+//! and tunings.
 
 pub mod context;
 pub mod module_trait;
 pub mod parameter;
 pub mod port;
 pub mod registry;
+pub mod sequence;
 pub mod signal;
 pub mod smoothed_value;
+pub mod tuning;
 
 // Re-export commonly used types
 pub use context::{ProcessContext, TransportState};
@@ -17,5 +20,7 @@ pub use module_trait::{DspModule, ModuleCategory, ModuleError, ModuleInfo};
 pub use parameter::{ParameterDefinition, ParameterDisplay};
 pub use port::{PortDefinition, PortDirection};
 pub use registry::{ModuleFactory, ModuleRegistry};
+pub use sequence::{NoteSequence, SequenceNote};
 pub use signal::{MidiEvent, MidiMessage, SignalBuffer, SignalType};
 pub use smoothed_value::SmoothedValue;
+pub use tuning::{Tuning, MIDDLE_C_HZ};
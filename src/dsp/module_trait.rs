@@ -6,10 +6,11 @@
 use super::context::ProcessContext;
 use super::parameter::ParameterDefinition;
 use super::port::PortDefinition;
-use super::SignalBuffer;
+use super::{MidiEvent, NoteSequence, SignalBuffer, Tuning};
 use egui::Color32;
 use egui_node_graph2::CategoryTrait;
 use std::fmt;
+use std::sync::Arc;
 
 /// Category of a DSP module, used for organization and UI coloring.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -259,6 +260,14 @@ pub trait DspModule: Send + 'static {
         None
     }
 
+    /// Returns the channels of multichannel output modules.
+    ///
+    /// Used by the audio engine to route Multi Output modules to device
+    /// channels. Returns one buffer per channel, or `None` for other modules.
+    fn get_multichannel_output(&self) -> Option<&[Vec<f32>]> {
+        None
+    }
+
     /// Returns peak levels for metering (for output modules).
     ///
     /// Returns (left_peak, right_peak) in the range 0.0 to 1.0+.
@@ -277,6 +286,48 @@ pub trait DspModule: Send + 'static {
     fn take_scope_data(&mut self) -> Option<(Vec<f32>, Vec<f32>, bool)> {
         None
     }
+
+    /// Returns the audio to record for recorder modules.
+    ///
+    /// Returns `Some((left, right, recording))` with the last processed block
+    /// and whether a take is in progress. Returns `None` for other modules.
+    fn get_recording(&self) -> Option<(&[f32], &[f32], bool)> {
+        None
+    }
+
+    /// Receives the audio input device's signal for the next block.
+    ///
+    /// Called before `process` with one slice per input channel. Only audio
+    /// input modules need to implement this; the default ignores the input.
+    fn set_audio_input(&mut self, _channels: &[&[f32]]) {}
+
+    /// Returns whether the module reads the audio input device.
+    ///
+    /// The engine only calls `set_audio_input` on modules that return
+    /// `true`. The default returns `false`.
+    fn uses_audio_input(&self) -> bool {
+        false
+    }
+
+    /// Moves the MIDI messages generated by the last `process` call into `messages`.
+    ///
+    /// Called after every block. Modules that send MIDI to external gear
+    /// append their events, with sample offsets within the block. The default
+    /// sends nothing.
+    fn take_midi_output(&mut self, _messages: &mut Vec<MidiEvent>) {}
+
+    /// Receives the notes of a MIDI file loaded into the module.
+    ///
+    /// `None` unloads the file. Only MIDI file players need to implement
+    /// this; the default ignores the notes.
+    fn set_note_sequence(&mut self, _sequence: Option<Arc<NoteSequence>>) {}
+
+    /// Receives the tuning to convert note numbers to pitch with.
+    ///
+    /// Called when the module is added and whenever its tuning changes. Only
+    /// modules that turn notes into V/Oct need to implement this; the
+    /// default ignores the tuning.
+    fn set_tuning(&mut self, _tuning: Arc<Tuning>) {}
 }
 
 #[cfg(test)]
@@ -62,6 +62,11 @@ impl ParameterDisplay {
     pub fn is_logarithmic(&self) -> bool {
         matches!(self, Self::Logarithmic { .. })
     }
+
+    /// Returns true if the parameter takes distinct steps (choices or toggles).
+    pub fn is_discrete(&self) -> bool {
+        matches!(self, Self::Discrete { .. } | Self::Toggle { .. })
+    }
 }
 
 /// Definition of a parameter on a DSP module.
@@ -82,6 +87,8 @@ pub struct ParameterDefinition {
     pub default: f32,
     /// How to display and interpret the parameter value.
     pub display: ParameterDisplay,
+    /// Whether patch variations may change the parameter.
+    pub randomize: bool,
 }
 
 impl ParameterDefinition {
@@ -101,6 +108,7 @@ impl ParameterDefinition {
             max,
             default,
             display,
+            randomize: true,
         }
     }
 
@@ -113,6 +121,7 @@ impl ParameterDefinition {
             max: 1.0,
             default,
             display: ParameterDisplay::linear("%"),
+            randomize: true,
         }
     }
 
@@ -131,6 +140,7 @@ impl ParameterDefinition {
             max,
             default,
             display: ParameterDisplay::logarithmic("Hz"),
+            randomize: true,
         }
     }
 
@@ -143,6 +153,7 @@ impl ParameterDefinition {
             max: 1.0,
             default: if default { 1.0 } else { 0.0 },
             display: ParameterDisplay::on_off(),
+            randomize: true,
         }
     }
 
@@ -160,9 +171,16 @@ impl ParameterDefinition {
             max: (labels.len().saturating_sub(1)) as f32,
             default: default_index as f32,
             display: ParameterDisplay::discrete(labels),
+            randomize: true,
         }
     }
 
+    /// Excludes the parameter from patch variations (see `persistence::randomize`).
+    pub fn not_randomized(mut self) -> Self {
+        self.randomize = false;
+        self
+    }
+
     /// Clamps a value to this parameter's valid range.
     pub fn clamp(&self, value: f32) -> f32 {
         value.clamp(self.min, self.max)
@@ -199,6 +217,7 @@ mod tests {
         let display = ParameterDisplay::logarithmic("dB");
         assert_eq!(display.unit(), Some("dB"));
         assert!(display.is_logarithmic());
+        assert!(!display.is_discrete());
     }
 
     #[test]
@@ -206,12 +225,14 @@ mod tests {
         let labels: &[&str] = &["Sine", "Square", "Saw"];
         let display = ParameterDisplay::discrete(labels);
         assert_eq!(display.unit(), None);
+        assert!(display.is_discrete());
     }
 
     #[test]
     fn test_parameter_display_toggle() {
         let display = ParameterDisplay::on_off();
         assert_eq!(display.unit(), None);
+        assert!(display.is_discrete());
     }
 
     #[test]
@@ -260,6 +281,12 @@ mod tests {
         assert_eq!(param_on.default, 1.0);
     }
 
+    #[test]
+    fn test_not_randomized() {
+        assert!(ParameterDefinition::normalized("mix", "Mix", 0.5).randomize);
+        assert!(!ParameterDefinition::normalized("mix", "Mix", 0.5).not_randomized().randomize);
+    }
+
     #[test]
     fn test_choice_parameter() {
         let param = ParameterDefinition::choice(
@@ -0,0 +1,41 @@
+//! Note sequences for playback.
+//!
+//! A `NoteSequence` is the notes of a MIDI file on one timeline, prepared on
+//! the UI thread and handed to playback modules on the audio thread.
+
+/// A Note On or Note Off of a sequence.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SequenceNote {
+    /// Time from the start in seconds, following the file's tempo map.
+    pub seconds: f64,
+    /// Time from the start in quarter notes.
+    pub beats: f64,
+    /// Index of the track the note came from.
+    pub track: u16,
+    /// The MIDI channel (0-15).
+    pub channel: u8,
+    /// Note number (0-127).
+    pub note: u8,
+    /// Note On velocity (1-127), or 0 for a Note Off.
+    pub velocity: u8,
+}
+
+impl SequenceNote {
+    /// Whether this is a Note On.
+    pub fn is_note_on(&self) -> bool {
+        self.velocity > 0
+    }
+}
+
+/// The notes of all tracks of a MIDI file, in time order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NoteSequence {
+    /// Notes in time order.
+    pub notes: Vec<SequenceNote>,
+    /// Length in seconds.
+    pub length_seconds: f64,
+    /// Length in quarter notes.
+    pub length_beats: f64,
+    /// Number of tracks in the file.
+    pub tracks: usize,
+}
@@ -156,6 +156,14 @@ pub enum MidiMessage {
     Aftertouch { pressure: u8 },
     /// Program Change: (program number 0-127)
     ProgramChange { program: u8 },
+    /// Timing Clock (24 per quarter note). Has no channel.
+    Clock,
+    /// Start the sequence from the beginning. Has no channel.
+    Start,
+    /// Continue the sequence where it stopped. Has no channel.
+    Continue,
+    /// Stop the sequence. Has no channel.
+    Stop,
 }
 
 impl MidiMessage {
@@ -215,6 +223,40 @@ impl MidiEvent {
             MidiMessage::NoteOff { note, velocity },
         )
     }
+
+    /// Creates a Control Change event.
+    pub fn control_change(sample_offset: u32, channel: u8, controller: u8, value: u8) -> Self {
+        Self::new(
+            sample_offset,
+            channel,
+            MidiMessage::ControlChange { controller, value },
+        )
+    }
+
+    /// Encodes the event as raw MIDI bytes.
+    ///
+    /// Returns the bytes and how many of them are used. Data bytes are
+    /// masked to 7 bits and the channel to 4 bits.
+    pub fn to_bytes(&self) -> ([u8; 3], usize) {
+        let channel = self.channel & 0x0F;
+        match self.message {
+            MidiMessage::NoteOn { note, velocity } => ([0x90 | channel, note & 0x7F, velocity & 0x7F], 3),
+            MidiMessage::NoteOff { note, velocity } => ([0x80 | channel, note & 0x7F, velocity & 0x7F], 3),
+            MidiMessage::ControlChange { controller, value } => {
+                ([0xB0 | channel, controller & 0x7F, value & 0x7F], 3)
+            }
+            MidiMessage::PitchBend { value } => {
+                let bend = (value.clamp(-8192, 8191) + 8192) as u16;
+                ([0xE0 | channel, (bend & 0x7F) as u8, (bend >> 7) as u8], 3)
+            }
+            MidiMessage::Aftertouch { pressure } => ([0xD0 | channel, pressure & 0x7F, 0], 2),
+            MidiMessage::ProgramChange { program } => ([0xC0 | channel, program & 0x7F, 0], 2),
+            MidiMessage::Clock => ([0xF8, 0, 0], 1),
+            MidiMessage::Start => ([0xFA, 0, 0], 1),
+            MidiMessage::Continue => ([0xFB, 0, 0], 1),
+            MidiMessage::Stop => ([0xFC, 0, 0], 1),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -342,4 +384,22 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_midi_event_to_bytes() {
+        assert_eq!(MidiEvent::note_on(0, 2, 60, 100).to_bytes(), ([0x92, 60, 100], 3));
+        assert_eq!(MidiEvent::note_off(0, 15, 61, 0).to_bytes(), ([0x8F, 61, 0], 3));
+        assert_eq!(MidiEvent::control_change(0, 0, 74, 200).to_bytes(), ([0xB0, 74, 72], 3));
+        assert_eq!(
+            MidiEvent::new(0, 0, MidiMessage::PitchBend { value: 0 }).to_bytes(),
+            ([0xE0, 0x00, 0x40], 3)
+        );
+        assert_eq!(
+            MidiEvent::new(0, 1, MidiMessage::ProgramChange { program: 5 }).to_bytes(),
+            ([0xC1, 5, 0], 2)
+        );
+        // System real-time messages ignore the channel
+        assert_eq!(MidiEvent::new(0, 3, MidiMessage::Clock).to_bytes(), ([0xF8, 0, 0], 1));
+        assert_eq!(MidiEvent::new(0, 0, MidiMessage::Stop).to_bytes(), ([0xFC, 0, 0], 1));
+    }
 }
@@ -0,0 +1,118 @@
+//! Note tunings.
+//!
+//! A `Tuning` maps MIDI note numbers to V/Oct pitch. The default is 12-tone
+//! equal temperament with middle C (MIDI 60) at 0 V. Other tunings are built
+//! on the UI thread, usually from Scala files, and shared with the note
+//! modules on the audio thread.
+
+/// Frequency of 0 V: middle C in 12-TET with A4 at 440 Hz.
+pub const MIDDLE_C_HZ: f64 = 261.625_565_300_598_6;
+
+/// Number of MIDI notes.
+const NOTES: usize = 128;
+
+/// V/Oct pitch of every MIDI note.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tuning {
+    /// Pitch of each note in volts, relative to `MIDDLE_C_HZ`.
+    pitches: [f32; NOTES],
+}
+
+impl Tuning {
+    /// 12-tone equal temperament, middle C at 0 V.
+    pub fn equal() -> Self {
+        Self { pitches: std::array::from_fn(|note| (note as f32 - 60.0) / 12.0) }
+    }
+
+    /// Build a tuning from the pitch of each note in volts.
+    ///
+    /// Notes without a pitch play the nearest tuned note below them (or above,
+    /// for notes below the first tuned one). If no note is tuned, the result
+    /// is 12-TET.
+    pub fn from_pitches(pitches: &[Option<f32>; NOTES]) -> Self {
+        let Some(first) = pitches.iter().flatten().next().copied() else {
+            return Self::equal();
+        };
+
+        let mut last = first;
+        Self {
+            pitches: std::array::from_fn(|note| {
+                if let Some(pitch) = pitches[note] {
+                    last = pitch;
+                }
+                last
+            }),
+        }
+    }
+
+    /// V/Oct pitch of a note number.
+    ///
+    /// Fractional notes are interpolated between their neighbours. Notes
+    /// beyond the MIDI range continue in 12-TET steps from the nearest end.
+    #[inline]
+    pub fn voct(&self, note: f32) -> f32 {
+        let last = (NOTES - 1) as f32;
+        if note <= 0.0 {
+            return self.pitches[0] + note / 12.0;
+        }
+        if note >= last {
+            return self.pitches[NOTES - 1] + (note - last) / 12.0;
+        }
+
+        let index = note as usize;
+        let fraction = note - index as f32;
+        let low = self.pitches[index];
+        low + (self.pitches[index + 1] - low) * fraction
+    }
+
+    /// Pitch of every note in volts.
+    pub fn pitches(&self) -> &[f32; NOTES] {
+        &self.pitches
+    }
+
+    /// Frequency of a note in Hz.
+    pub fn frequency(&self, note: u8) -> f64 {
+        MIDDLE_C_HZ * 2.0_f64.powf(self.voct(note as f32) as f64)
+    }
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self::equal()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equal_tuning() {
+        let tuning = Tuning::equal();
+        assert!((tuning.voct(60.0) - 0.0).abs() < f32::EPSILON);
+        assert!((tuning.voct(72.0) - 1.0).abs() < 1e-6);
+        assert!((tuning.voct(69.0) - 0.75).abs() < 1e-6);
+        assert!((tuning.voct(60.5) - 0.5 / 12.0).abs() < 1e-6);
+        // Beyond the MIDI range
+        assert!((tuning.voct(-12.0) - -6.0).abs() < 1e-5);
+        assert!((tuning.voct(139.0) - 6.583_333).abs() < 1e-5);
+        assert!((tuning.frequency(69) - 440.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_from_pitches() {
+        let mut pitches = [None; NOTES];
+        pitches[60] = Some(0.0);
+        pitches[62] = Some(0.25);
+        let tuning = Tuning::from_pitches(&pitches);
+
+        // Unmapped notes take the nearest tuned note below, or above
+        assert_eq!(tuning.voct(0.0), 0.0);
+        assert_eq!(tuning.voct(61.0), 0.0);
+        assert_eq!(tuning.voct(62.0), 0.25);
+        assert_eq!(tuning.voct(127.0), 0.25);
+        assert!((tuning.voct(61.5) - 0.125).abs() < 1e-6);
+
+        assert_eq!(Tuning::from_pitches(&[None; NOTES]), Tuning::equal());
+    }
+}
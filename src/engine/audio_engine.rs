@@ -1,20 +1,33 @@
 //! Audio Engine
 //!
-//! Manages the cpal audio stream and interfaces with system audio hardware.
-//! The audio callback runs in a separate thread and must be real-time safe.
+//! Manages the cpal audio streams and interfaces with system audio hardware.
+//! The audio callbacks run in separate threads and must be real-time safe.
+//!
+//! An optional input stream captures an audio input device at the output
+//! sample rate and hands its samples to the audio processor through an
+//! `InputReader` (see `input_stream`).
+//!
+//! The sample rate and buffer size can be chosen with `StreamSettings`.
+//! Changing them (or the device) rebuilds the streams and re-prepares the
+//! processor's modules, so the patch keeps running.
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Device, Host, SampleRate, Stream, StreamConfig};
+use cpal::{Device, Host, SampleRate, Stream, StreamConfig, SupportedBufferSize};
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 
 use super::audio_processor::AudioProcessor;
+use super::input_stream::{
+    input_stream, InputCapture, InputReader, InputStats, DEFAULT_INPUT_BUFFER_SAMPLES,
+};
 
 /// Errors that can occur during audio engine operation.
 #[derive(Debug, Clone)]
 pub enum AudioError {
     /// No audio output device was found.
     NoOutputDevice,
+    /// The selected audio input device was not found.
+    NoInputDevice,
     /// Failed to get device configuration.
     ConfigurationFailed(String),
     /// Failed to create the audio stream.
@@ -27,6 +40,7 @@ impl std::fmt::Display for AudioError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             AudioError::NoOutputDevice => write!(f, "No audio output device found"),
+            AudioError::NoInputDevice => write!(f, "Audio input device not found"),
             AudioError::ConfigurationFailed(msg) => {
                 write!(f, "Failed to get device configuration: {}", msg)
             }
@@ -42,12 +56,31 @@ impl std::fmt::Display for AudioError {
 
 impl std::error::Error for AudioError {}
 
-/// Information about an audio output device.
+/// Sample rates offered in the audio settings, where the device supports them.
+pub const COMMON_SAMPLE_RATES: [u32; 6] = [44100, 48000, 88200, 96000, 176400, 192000];
+
+/// Buffer sizes (in frames) offered in the audio settings, where the device
+/// supports them.
+pub const COMMON_BUFFER_SIZES: [u32; 6] = [64, 128, 256, 512, 1024, 2048];
+
+/// Block size the processor is prepared for when the device picks the buffer size.
+pub const DEFAULT_BLOCK_SIZE: usize = 256;
+
+/// Requested stream format. `None` leaves the choice to the device.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamSettings {
+    /// Sample rate in Hz.
+    pub sample_rate: Option<u32>,
+    /// Buffer size in frames.
+    pub buffer_size: Option<u32>,
+}
+
+/// Information about an audio output or input device.
 #[derive(Debug, Clone)]
 pub struct DeviceInfo {
     /// Human-readable device name.
     pub name: String,
-    /// Whether this is the default output device.
+    /// Whether this is the default device of its kind.
     pub is_default: bool,
     /// Index in the device list (for selection).
     pub index: usize,
@@ -77,8 +110,24 @@ pub struct AudioEngine {
     host: Host,
     device: Device,
     config: StreamConfig,
+    /// Requested stream format the config was built from.
+    settings: StreamSettings,
     stream: Option<Stream>,
     state: Arc<AudioState>,
+    /// Processor driven by the output stream, kept to rebuild the stream.
+    processor: Option<Arc<Mutex<AudioProcessor>>>,
+    /// Selected input device (None = no input).
+    input_device: Option<Device>,
+    /// Channel count of the input stream.
+    input_channels: u16,
+    /// Running input stream.
+    input_stream: Option<Stream>,
+    /// Callback side of the input ring buffer, shared by successive input streams.
+    input_capture: Arc<Mutex<InputCapture>>,
+    /// Processor side of the input ring buffer, until taken by `take_input_reader`.
+    input_reader: Option<InputReader>,
+    /// Dropout counters of the input ring buffer.
+    input_stats: InputStats,
 }
 
 impl AudioEngine {
@@ -90,25 +139,27 @@ impl AudioEngine {
             .default_output_device()
             .ok_or(AudioError::NoOutputDevice)?;
 
-        let supported_config = device
-            .default_output_config()
-            .map_err(|e| AudioError::ConfigurationFailed(e.to_string()))?;
-
-        let sample_rate = supported_config.sample_rate().0;
-        let config = StreamConfig {
-            channels: supported_config.channels(),
-            sample_rate: SampleRate(sample_rate),
-            buffer_size: cpal::BufferSize::Default,
-        };
+        let settings = StreamSettings::default();
+        let config = output_config(&device, settings)?;
 
         let state = Arc::new(AudioState::new());
+        let (input_capture, input_reader) = input_stream(DEFAULT_INPUT_BUFFER_SAMPLES);
+        let input_stats = input_capture.stats();
 
         Ok(Self {
             host,
             device,
             config,
+            settings,
             stream: None,
             state,
+            processor: None,
+            input_device: None,
+            input_channels: 0,
+            input_stream: None,
+            input_capture: Arc::new(Mutex::new(input_capture)),
+            input_reader: Some(input_reader),
+            input_stats,
         })
     }
 
@@ -143,15 +194,9 @@ impl AudioEngine {
 
     /// Select a different output device by index.
     ///
-    /// This will stop the current stream if running. Call `start()` to begin
-    /// playback on the new device.
+    /// A running stream moves to the new device. If the device doesn't
+    /// support the chosen sample rate, its default rate is used instead.
     pub fn select_device(&mut self, index: usize) -> Result<(), AudioError> {
-        // Stop current stream if running
-        let was_running = self.is_running();
-        if was_running {
-            self.stop()?;
-        }
-
         // Find the device by index
         let device = self
             .host
@@ -160,29 +205,240 @@ impl AudioEngine {
             .nth(index)
             .ok_or(AudioError::NoOutputDevice)?;
 
-        // Get configuration for new device
-        let supported_config = device
-            .default_output_config()
-            .map_err(|e| AudioError::ConfigurationFailed(e.to_string()))?;
-
-        let sample_rate = supported_config.sample_rate().0;
-        let config = StreamConfig {
-            channels: supported_config.channels(),
-            sample_rate: SampleRate(sample_rate),
-            buffer_size: cpal::BufferSize::Default,
+        let config = match output_config(&device, self.settings) {
+            Ok(config) => config,
+            Err(_) => {
+                self.settings.sample_rate = None;
+                output_config(&device, self.settings)?
+            }
         };
 
         self.device = device;
+        self.reconfigure(config)
+    }
+
+    /// Get the requested stream format.
+    pub fn settings(&self) -> StreamSettings {
+        self.settings
+    }
+
+    /// Change the sample rate and buffer size.
+    ///
+    /// The streams are rebuilt and the processor's modules re-prepared, so
+    /// the patch keeps playing. If the new format doesn't work, the previous
+    /// one is restored and the error returned.
+    pub fn set_stream_settings(&mut self, settings: StreamSettings) -> Result<(), AudioError> {
+        let config = output_config(&self.device, settings)?;
+        let previous = (self.config.clone(), self.settings);
+
+        self.settings = settings;
+        if let Err(e) = self.reconfigure(config) {
+            self.settings = previous.1;
+            let _ = self.reconfigure(previous.0);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Sample rates from `COMMON_SAMPLE_RATES` the output device supports.
+    pub fn supported_sample_rates(&self) -> Vec<u32> {
+        let channels = self.config.channels;
+        let ranges: Vec<(u32, u32)> = self
+            .device
+            .supported_output_configs()
+            .map(|configs| {
+                configs
+                    .filter(|range| range.channels() == channels)
+                    .map(|range| (range.min_sample_rate().0, range.max_sample_rate().0))
+                    .collect()
+            })
+            .unwrap_or_default();
+        rates_in_ranges(&ranges)
+    }
+
+    /// Smallest and largest buffer size of the output device, if it reports them.
+    pub fn buffer_size_range(&self) -> Option<(u32, u32)> {
+        match self.device.default_output_config().ok()?.buffer_size() {
+            SupportedBufferSize::Range { min, max } => Some((*min, *max)),
+            SupportedBufferSize::Unknown => None,
+        }
+    }
+
+    /// Block size the processor is prepared for.
+    pub fn block_size(&self) -> usize {
+        match self.config.buffer_size {
+            cpal::BufferSize::Fixed(frames) => frames as usize,
+            cpal::BufferSize::Default => DEFAULT_BLOCK_SIZE,
+        }
+    }
+
+    /// Rebuild the streams with a new output configuration.
+    fn reconfigure(&mut self, config: StreamConfig) -> Result<(), AudioError> {
+        let was_running = self.is_running();
+        self.stop()?;
         self.config = config;
 
-        // Restart if it was running before
+        // The stream is gone, so the processor is free to re-prepare
+        if let Some(processor) = &self.processor {
+            if let Ok(mut processor) = processor.lock() {
+                processor.prepare(self.config.sample_rate.0 as f32, self.block_size());
+            }
+        }
+
         if was_running {
-            self.start()?;
+            if self.processor.is_some() {
+                self.start_processor_stream()?;
+            } else {
+                self.start()?;
+            }
+        }
+
+        // The input has to follow the output sample rate
+        if self.input_device.is_some() {
+            self.start_input()?;
+        }
+        Ok(())
+    }
+
+    /// Get information about all available input devices.
+    pub fn enumerate_input_devices(&self) -> Vec<DeviceInfo> {
+        let default_name = self
+            .host
+            .default_input_device()
+            .and_then(|d| d.name().ok());
+
+        self.host
+            .input_devices()
+            .map(|devices| {
+                devices
+                    .enumerate()
+                    .filter_map(|(index, device)| {
+                        device.name().ok().map(|name| DeviceInfo {
+                            is_default: Some(&name) == default_name.as_ref(),
+                            name,
+                            index,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get the name of the selected input device (None = no input).
+    pub fn input_device_name(&self) -> Option<String> {
+        self.input_device
+            .as_ref()
+            .map(|d| d.name().unwrap_or_else(|_| "Unknown".to_string()))
+    }
+
+    /// Get the number of channels of the input stream (0 = no input).
+    pub fn input_channels(&self) -> u16 {
+        self.input_channels
+    }
+
+    /// Select an input device by index, or `None` to close the input.
+    ///
+    /// The input stream starts right away and runs at the output sample rate.
+    pub fn select_input_device(&mut self, index: Option<usize>) -> Result<(), AudioError> {
+        self.stop_input();
+        self.input_device = None;
+
+        let Some(index) = index else {
+            return Ok(());
+        };
+
+        let device = self
+            .host
+            .input_devices()
+            .map_err(|e| AudioError::ConfigurationFailed(e.to_string()))?
+            .nth(index)
+            .ok_or(AudioError::NoInputDevice)?;
+
+        self.input_device = Some(device);
+        let result = self.start_input();
+        if result.is_err() {
+            self.input_device = None;
+        }
+        result
+    }
+
+    /// Take the processor side of the input stream.
+    ///
+    /// Pass it to `AudioProcessor::set_input`. Returns `None` after the first call.
+    pub fn take_input_reader(&mut self) -> Option<InputReader> {
+        self.input_reader.take()
+    }
+
+    /// Samples lost because the processor didn't keep up with the input, and
+    /// frames filled with silence because the input didn't keep up with the output.
+    pub fn input_xruns(&self) -> (u64, u64) {
+        (self.input_stats.overruns(), self.input_stats.underruns())
+    }
+
+    /// Build and start the input stream on the selected input device.
+    fn start_input(&mut self) -> Result<(), AudioError> {
+        self.stop_input();
+        let Some(device) = &self.input_device else {
+            return Ok(());
+        };
+
+        let sample_rate = self.config.sample_rate;
+        let supported = device
+            .supported_input_configs()
+            .map_err(|e| AudioError::ConfigurationFailed(e.to_string()))?
+            .find(|range| range.min_sample_rate() <= sample_rate && range.max_sample_rate() >= sample_rate)
+            .ok_or_else(|| {
+                AudioError::ConfigurationFailed(format!(
+                    "input device doesn't support {}Hz",
+                    sample_rate.0
+                ))
+            })?;
+
+        let config = StreamConfig {
+            channels: supported.channels(),
+            sample_rate,
+            buffer_size: cpal::BufferSize::Default,
+        };
+
+        if let Ok(capture) = self.input_capture.lock() {
+            capture.set_channels(usize::from(config.channels));
         }
 
+        let capture = Arc::clone(&self.input_capture);
+        let stream = device
+            .build_input_stream(
+                &config,
+                move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                    // Uncontested except while the UI switches devices
+                    if let Ok(mut capture) = capture.try_lock() {
+                        capture.push(data);
+                    }
+                },
+                move |err| {
+                    eprintln!("Audio input stream error: {}", err);
+                },
+                None,
+            )
+            .map_err(|e| AudioError::StreamCreationFailed(e.to_string()))?;
+
+        stream
+            .play()
+            .map_err(|e| AudioError::StreamPlaybackFailed(e.to_string()))?;
+
+        self.input_channels = config.channels;
+        self.input_stream = Some(stream);
         Ok(())
     }
 
+    /// Stop the input stream; the graph then receives silence.
+    fn stop_input(&mut self) {
+        self.input_stream = None;
+        self.input_channels = 0;
+        if let Ok(capture) = self.input_capture.lock() {
+            capture.set_channels(0);
+        }
+    }
+
     /// Get the current stream configuration.
     pub fn config(&self) -> &StreamConfig {
         &self.config
@@ -300,9 +556,10 @@ impl AudioEngine {
 
     /// Start the audio stream with an AudioProcessor for graph-based synthesis.
     ///
-    /// The AudioProcessor is moved into the audio callback where it processes
+    /// The AudioProcessor is shared with the audio callback where it processes
     /// the audio graph and produces output. The processor is wrapped in a Mutex
-    /// to allow safe access from the audio callback.
+    /// to allow safe access from the audio callback, and kept by the engine so
+    /// the stream can be rebuilt when the device or settings change.
     ///
     /// Note: This method is preferred over `start()` for actual synthesis.
     /// The test tone (`start()`) is only for basic audio testing.
@@ -311,14 +568,20 @@ impl AudioEngine {
             return Ok(());
         }
 
-        let channels = self.config.channels as usize;
-        let sample_rate = self.config.sample_rate.0 as f32;
-
         // Wrap processor in Mutex for the callback
         // Note: In practice, the Mutex is uncontested since only the audio
-        // callback accesses it, so there's no actual blocking.
-        let processor = Arc::new(Mutex::new(processor));
-        let processor_clone = Arc::clone(&processor);
+        // callback accesses it, except while the stream is rebuilt.
+        self.processor = Some(Arc::new(Mutex::new(processor)));
+        self.start_processor_stream()
+    }
+
+    /// Build and start the output stream driving the processor.
+    fn start_processor_stream(&mut self) -> Result<(), AudioError> {
+        let Some(processor) = &self.processor else {
+            return Ok(());
+        };
+        let channels = self.config.channels as usize;
+        let processor_clone = Arc::clone(processor);
 
         // DEBUG: Count callback invocations and lock failures
         use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
@@ -363,10 +626,87 @@ impl AudioEngine {
     }
 }
 
+/// Build the output stream configuration for `device` with the requested settings.
+///
+/// The channel count is the device default. A requested sample rate must be
+/// supported; a requested buffer size is clamped to the device's range.
+fn output_config(device: &Device, settings: StreamSettings) -> Result<StreamConfig, AudioError> {
+    let default = device
+        .default_output_config()
+        .map_err(|e| AudioError::ConfigurationFailed(e.to_string()))?;
+    let channels = default.channels();
+
+    let sample_rate = match settings.sample_rate {
+        Some(rate) => {
+            let supported = device
+                .supported_output_configs()
+                .map_err(|e| AudioError::ConfigurationFailed(e.to_string()))?
+                .any(|range| {
+                    range.channels() == channels
+                        && range.min_sample_rate().0 <= rate
+                        && range.max_sample_rate().0 >= rate
+                });
+            if !supported {
+                return Err(AudioError::ConfigurationFailed(format!(
+                    "output device doesn't support {}Hz",
+                    rate
+                )));
+            }
+            rate
+        }
+        None => default.sample_rate().0,
+    };
+
+    let buffer_size = match settings.buffer_size {
+        Some(frames) => cpal::BufferSize::Fixed(fixed_buffer_size(frames, default.buffer_size())),
+        None => cpal::BufferSize::Default,
+    };
+
+    Ok(StreamConfig {
+        channels,
+        sample_rate: SampleRate(sample_rate),
+        buffer_size,
+    })
+}
+
+/// The entries of `COMMON_SAMPLE_RATES` inside any of the `(min, max)` ranges.
+fn rates_in_ranges(ranges: &[(u32, u32)]) -> Vec<u32> {
+    COMMON_SAMPLE_RATES
+        .iter()
+        .copied()
+        .filter(|rate| ranges.iter().any(|(min, max)| min <= rate && rate <= max))
+        .collect()
+}
+
+/// Clamp a requested buffer size to what the device supports.
+fn fixed_buffer_size(frames: u32, supported: &SupportedBufferSize) -> u32 {
+    match supported {
+        SupportedBufferSize::Range { min, max } if min <= max => frames.clamp(*min, *max),
+        _ => frames,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_rates_in_ranges() {
+        assert_eq!(rates_in_ranges(&[(44100, 48000)]), vec![44100, 48000]);
+        assert_eq!(rates_in_ranges(&[(8000, 192000)]), COMMON_SAMPLE_RATES.to_vec());
+        assert_eq!(rates_in_ranges(&[(96000, 96000), (44100, 44100)]), vec![44100, 96000]);
+        assert!(rates_in_ranges(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_fixed_buffer_size() {
+        let range = SupportedBufferSize::Range { min: 128, max: 1024 };
+        assert_eq!(fixed_buffer_size(256, &range), 256);
+        assert_eq!(fixed_buffer_size(64, &range), 128);
+        assert_eq!(fixed_buffer_size(4096, &range), 1024);
+        assert_eq!(fixed_buffer_size(64, &SupportedBufferSize::Unknown), 64);
+    }
+
     #[test]
     fn test_audio_error_display() {
         let err = AudioError::NoOutputDevice;
@@ -376,6 +716,12 @@ mod tests {
         assert!(err.to_string().contains("test error"));
     }
 
+    #[test]
+    fn test_input_error_display() {
+        let err = AudioError::NoInputDevice;
+        assert_eq!(err.to_string(), "Audio input device not found");
+    }
+
     #[test]
     fn test_device_info() {
         let info = DeviceInfo {
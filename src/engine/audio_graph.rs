@@ -5,8 +5,9 @@
 //! graph manipulation commands from the UI thread in a real-time safe manner.
 
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
-use crate::dsp::{DspModule, ModuleRegistry, ProcessContext, SignalBuffer, SignalType};
+use crate::dsp::{DspModule, ModuleRegistry, NoteSequence, ProcessContext, SignalBuffer, SignalType, Tuning};
 use crate::engine::buffer_pool::BufferPool;
 use crate::engine::commands::{EngineCommand, NodeId, PortIndex};
 
@@ -41,6 +42,10 @@ struct ModuleData {
     module: Box<dyn DspModule>,
     /// Current parameter values (denormalized, ready to pass to process()).
     parameters: Vec<f32>,
+    /// The module's own tuning (None = the global tuning).
+    tuning: Option<Arc<Tuning>>,
+    /// The notes given to the module (MIDI file players only).
+    sequence: Option<Arc<NoteSequence>>,
 }
 
 impl ModuleData {
@@ -55,10 +60,21 @@ impl ModuleData {
         // Prepare the module
         module.prepare(sample_rate, block_size);
 
-        Self { module, parameters }
+        Self { module, parameters, tuning: None, sequence: None }
     }
 }
 
+/// Tunings the graph can hold for the UI thread to free between drains.
+///
+/// Any further tunings are freed on the audio thread.
+const RELEASED_TUNINGS_CAPACITY: usize = 16;
+
+/// Note sequences the graph can hold for the UI thread to free between
+/// drains.
+///
+/// Any further sequences are freed on the audio thread.
+const RELEASED_SEQUENCES_CAPACITY: usize = 16;
+
 /// The audio graph that manages modules and their connections.
 ///
 /// The graph maintains:
@@ -99,6 +115,12 @@ pub struct AudioGraph {
     /// Pending scope buffer data to send to UI.
     /// Populated during process(), consumed by the caller.
     pending_scope_buffers: Vec<(NodeId, Vec<f32>, Vec<f32>, bool)>,
+    /// Tuning of modules without a tuning of their own.
+    tuning: Arc<Tuning>,
+    /// Tunings replaced since the last drain, to be freed on the UI thread.
+    released_tunings: Vec<Arc<Tuning>>,
+    /// Note sequences replaced since the last drain, to be freed on the UI thread.
+    released_sequences: Vec<Arc<NoteSequence>>,
 }
 
 impl AudioGraph {
@@ -118,6 +140,9 @@ impl AudioGraph {
             monitored_outputs: HashSet::new(),
             sampled_output_values: Vec::new(),
             pending_scope_buffers: Vec::new(),
+            tuning: Arc::new(Tuning::equal()),
+            released_tunings: Vec::with_capacity(RELEASED_TUNINGS_CAPACITY),
+            released_sequences: Vec::with_capacity(RELEASED_SEQUENCES_CAPACITY),
         }
     }
 
@@ -137,6 +162,9 @@ impl AudioGraph {
             monitored_outputs: HashSet::new(),
             sampled_output_values: Vec::new(),
             pending_scope_buffers: Vec::new(),
+            tuning: Arc::new(Tuning::equal()),
+            released_tunings: Vec::with_capacity(RELEASED_TUNINGS_CAPACITY),
+            released_sequences: Vec::with_capacity(RELEASED_SEQUENCES_CAPACITY),
         }
     }
 
@@ -166,6 +194,26 @@ impl AudioGraph {
         }
     }
 
+    /// Updates the sample rate and block size, re-preparing all modules.
+    ///
+    /// Called when the audio stream is reconfigured; the modules and their
+    /// connections are kept. Allocates, so it must not be called from the
+    /// audio callback.
+    pub fn prepare(&mut self, sample_rate: f32, block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.block_size = block_size;
+        self.buffers.resize_all(block_size);
+
+        for data in self.modules.values_mut() {
+            data.module.prepare(sample_rate, block_size);
+        }
+    }
+
+    /// Returns the sample rate modules are prepared for.
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
     /// Returns a reference to the processing order.
     pub fn processing_order(&self) -> &[NodeId] {
         &self.processing_order
@@ -242,7 +290,8 @@ impl AudioGraph {
         }
 
         // Create module data with default parameters
-        let data = ModuleData::new(module, self.sample_rate, self.block_size);
+        let mut data = ModuleData::new(module, self.sample_rate, self.block_size);
+        data.module.set_tuning(Arc::clone(&self.tuning));
         self.modules.insert(node_id, data);
 
         self.needs_sort = true;
@@ -252,8 +301,14 @@ impl AudioGraph {
     ///
     /// Also removes all connections to/from this module.
     pub fn remove_module(&mut self, node_id: NodeId) -> bool {
-        if self.modules.remove(&node_id).is_none() {
+        let Some(mut data) = self.modules.remove(&node_id) else {
             return false;
+        };
+        if let Some(tuning) = data.tuning.take() {
+            release(&mut self.released_tunings, tuning);
+        }
+        if let Some(sequence) = data.sequence.take() {
+            release(&mut self.released_sequences, sequence);
         }
 
         // Remove all connections involving this node
@@ -365,6 +420,14 @@ impl AudioGraph {
 
     /// Clears the entire graph.
     pub fn clear(&mut self) {
+        for data in self.modules.values_mut() {
+            if let Some(tuning) = data.tuning.take() {
+                release(&mut self.released_tunings, tuning);
+            }
+            if let Some(sequence) = data.sequence.take() {
+                release(&mut self.released_sequences, sequence);
+            }
+        }
         self.modules.clear();
         self.connections.clear();
         self.processing_order.clear();
@@ -376,6 +439,58 @@ impl AudioGraph {
         self.sampled_output_values.clear();
     }
 
+    /// Sets the global tuning, used by every module without its own tuning.
+    ///
+    /// The previous tuning is kept for `drain_released_tunings`.
+    pub fn set_tuning(&mut self, tuning: Arc<Tuning>) {
+        let previous = std::mem::replace(&mut self.tuning, tuning);
+        for data in self.modules.values_mut().filter(|data| data.tuning.is_none()) {
+            data.module.set_tuning(Arc::clone(&self.tuning));
+        }
+        release(&mut self.released_tunings, previous);
+    }
+
+    /// Gives a module its own tuning, or returns it to the global tuning.
+    ///
+    /// A replaced tuning of the module's own is kept for
+    /// `drain_released_tunings`.
+    pub fn set_module_tuning(&mut self, node_id: NodeId, tuning: Option<Arc<Tuning>>) -> bool {
+        let Some(data) = self.modules.get_mut(&node_id) else {
+            return false;
+        };
+        data.module.set_tuning(tuning.clone().unwrap_or_else(|| Arc::clone(&self.tuning)));
+        if let Some(previous) = std::mem::replace(&mut data.tuning, tuning) {
+            release(&mut self.released_tunings, previous);
+        }
+        true
+    }
+
+    /// Takes the tunings replaced since the last call, so the caller can
+    /// free them off the audio thread.
+    pub fn drain_released_tunings(&mut self) -> std::vec::Drain<'_, Arc<Tuning>> {
+        self.released_tunings.drain(..)
+    }
+
+    /// Gives a MIDI file player the notes to play (None = unload the file).
+    ///
+    /// The previous notes are kept for `drain_released_sequences`.
+    pub fn set_note_sequence(&mut self, node_id: NodeId, sequence: Option<Arc<NoteSequence>>) -> bool {
+        let Some(data) = self.modules.get_mut(&node_id) else {
+            return false;
+        };
+        data.module.set_note_sequence(sequence.clone());
+        if let Some(previous) = std::mem::replace(&mut data.sequence, sequence) {
+            release(&mut self.released_sequences, previous);
+        }
+        true
+    }
+
+    /// Takes the note sequences replaced since the last call, so the caller
+    /// can free them off the audio thread.
+    pub fn drain_released_sequences(&mut self) -> std::vec::Drain<'_, Arc<NoteSequence>> {
+        self.released_sequences.drain(..)
+    }
+
     /// Start monitoring an input port for UI feedback.
     pub fn monitor_input(&mut self, node_id: NodeId, input_index: PortIndex) {
         self.monitored_inputs.insert((node_id, input_index));
@@ -408,6 +523,11 @@ impl AudioGraph {
         std::mem::take(&mut self.sampled_output_values)
     }
 
+    /// Returns the samples of a node output from the last processed block.
+    pub fn output_samples(&self, node_id: NodeId, output_index: PortIndex) -> Option<&[f32]> {
+        self.buffers.get(node_id, output_index).map(|buf| buf.samples.as_slice())
+    }
+
     /// Drain pending scope buffer data for sending to UI.
     /// Call this after process() to get oscilloscope waveform captures.
     pub fn drain_scope_buffers(&mut self) -> Vec<(NodeId, Vec<f32>, Vec<f32>, bool)> {
@@ -517,7 +637,24 @@ impl AudioGraph {
                 param_index,
                 value,
             } => self.set_parameter(node_id, param_index, value),
-            EngineCommand::SetPlaying(_) => {
+            EngineCommand::SetNoteSequence { node_id, sequence } => self.set_note_sequence(node_id, sequence),
+            EngineCommand::SetTuning { tuning } => {
+                self.set_tuning(tuning);
+                true
+            }
+            EngineCommand::SetModuleTuning { node_id, tuning } => self.set_module_tuning(node_id, tuning),
+            EngineCommand::SetPlaying(_)
+            | EngineCommand::StartRecording { .. }
+            | EngineCommand::StopRecording
+            | EngineCommand::SetOutputRouting(_)
+            | EngineCommand::SetMidiTransport(_)
+            | EngineCommand::AllNotesOff
+            | EngineCommand::FadeOutput { .. }
+            | EngineCommand::BeginPreview
+            | EngineCommand::EndPreview { .. }
+            | EngineCommand::BeginPreload
+            | EngineCommand::EndPreload
+            | EngineCommand::ClearPreload => {
                 // Handled at a higher level
                 true
             }
@@ -787,6 +924,14 @@ impl AudioGraph {
     }
 }
 
+/// Keep a replaced tuning or note sequence for the UI thread to free, if
+/// there is room without allocating.
+fn release<T>(released: &mut Vec<Arc<T>>, shared: Arc<T>) {
+    if released.len() < released.capacity() {
+        released.push(shared);
+    }
+}
+
 impl Default for AudioGraph {
     fn default() -> Self {
         Self::new(44100.0, 256)
@@ -1234,6 +1379,98 @@ mod tests {
         assert!(!graph.set_parameter(999, 0, 0.5)); // Invalid node
     }
 
+    #[test]
+    fn test_set_tuning() {
+        use std::sync::Mutex;
+
+        // Records the pitch its tuning gives note 61
+        struct TuningProbe {
+            pitch: Arc<Mutex<f32>>,
+        }
+
+        impl DspModule for TuningProbe {
+            fn info(&self) -> &ModuleInfo {
+                static INFO: ModuleInfo = ModuleInfo {
+                    id: "test.tuning",
+                    name: "Test Tuning",
+                    category: ModuleCategory::Utility,
+                    description: "Test",
+                };
+                &INFO
+            }
+            fn ports(&self) -> &[PortDefinition] {
+                &[]
+            }
+            fn parameters(&self) -> &[ParameterDefinition] {
+                &[]
+            }
+            fn prepare(&mut self, _: f32, _: usize) {}
+            fn process(&mut self, _: &[&SignalBuffer], _: &mut [SignalBuffer], _: &[f32], _: &ProcessContext) {}
+            fn reset(&mut self) {}
+            fn set_tuning(&mut self, tuning: Arc<Tuning>) {
+                *self.pitch.lock().unwrap() = tuning.voct(61.0);
+            }
+        }
+
+        let mut graph = AudioGraph::new(44100.0, 256);
+        let first = Arc::new(Mutex::new(f32::NAN));
+        let second = Arc::new(Mutex::new(f32::NAN));
+        graph.add_module_instance(1, Box::new(TuningProbe { pitch: Arc::clone(&first) }));
+        graph.add_module_instance(2, Box::new(TuningProbe { pitch: Arc::clone(&second) }));
+        let equal = 1.0 / 12.0;
+        assert!((*first.lock().unwrap() - equal).abs() < 1e-6);
+
+        // A tuning where every note is a quarter tone
+        let mut pitches = [None; 128];
+        for (note, pitch) in pitches.iter_mut().enumerate() {
+            *pitch = Some((note as f32 - 60.0) / 24.0);
+        }
+        let quarter = Arc::new(Tuning::from_pitches(&pitches));
+
+        // Module 2 gets its own tuning, then the global tuning changes
+        assert!(graph.handle_command(EngineCommand::SetModuleTuning { node_id: 2, tuning: Some(Arc::clone(&quarter)) }));
+        assert!((*second.lock().unwrap() - 1.0 / 24.0).abs() < 1e-6);
+        assert!(graph.handle_command(EngineCommand::SetTuning { tuning: Arc::clone(&quarter) }));
+        assert!((*first.lock().unwrap() - 1.0 / 24.0).abs() < 1e-6);
+        assert!(graph.handle_command(EngineCommand::SetTuning { tuning: Arc::new(Tuning::equal()) }));
+        assert!((*first.lock().unwrap() - equal).abs() < 1e-6);
+        assert!((*second.lock().unwrap() - 1.0 / 24.0).abs() < 1e-6);
+
+        // Module 2 returns to the global tuning
+        assert!(graph.handle_command(EngineCommand::SetModuleTuning { node_id: 2, tuning: None }));
+        assert!((*second.lock().unwrap() - equal).abs() < 1e-6);
+        assert!(!graph.handle_command(EngineCommand::SetModuleTuning { node_id: 9, tuning: None }));
+
+        // Every replaced tuning is handed back: the first global 12-TET, the
+        // quarter tones as global and as module 2's own tuning
+        let released: Vec<Arc<Tuning>> = graph.drain_released_tunings().collect();
+        assert_eq!(released.len(), 3);
+        drop(released);
+        assert_eq!(Arc::strong_count(&quarter), 1);
+    }
+
+    #[test]
+    fn test_note_sequences_are_released() {
+        let mut registry = ModuleRegistry::new();
+        registry.register::<TestOscillator>();
+        let mut graph = AudioGraph::with_registry(44100.0, 256, registry);
+        graph.handle_command(EngineCommand::AddModule { node_id: 1, module_id: "test.osc" });
+
+        let first = Arc::new(NoteSequence::default());
+        let second = Arc::new(NoteSequence::default());
+        assert!(graph.handle_command(EngineCommand::SetNoteSequence { node_id: 1, sequence: Some(Arc::clone(&first)) }));
+        assert!(graph.handle_command(EngineCommand::SetNoteSequence { node_id: 1, sequence: Some(Arc::clone(&second)) }));
+        assert!(!graph.handle_command(EngineCommand::SetNoteSequence { node_id: 9, sequence: None }));
+        // Removing the player hands back the notes it was playing
+        assert!(graph.handle_command(EngineCommand::RemoveModule { node_id: 1 }));
+
+        let released: Vec<Arc<NoteSequence>> = graph.drain_released_sequences().collect();
+        assert_eq!(released.len(), 2);
+        drop(released);
+        assert_eq!(Arc::strong_count(&first), 1);
+        assert_eq!(Arc::strong_count(&second), 1);
+    }
+
     #[test]
     fn test_handle_command_add_remove() {
         let mut registry = ModuleRegistry::new();
@@ -3,14 +3,21 @@
 //! Handles audio processing in the audio callback, integrating the AudioGraph
 //! with command handling from the UI thread.
 
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use crate::dsp::{ModuleRegistry, ProcessContext};
-use crate::modules::{AdsrEnvelope, Attenuverter, AudioOutput, Chorus, Clock, Compressor, Distortion, KeyboardInput, Lfo, MidiMonitor, MidiNote, Mixer, Oscilloscope, ParametricEq, Reverb, SampleHold, SineOscillator, StepSequencer, StereoDelay, SvfFilter, Vca};
+use rtrb::Producer;
+
+use crate::dsp::{MidiEvent, ModuleRegistry, ProcessContext};
+use crate::modules::{AdsrEnvelope, Attenuverter, AudioInput, AudioOutput, Chorus, Clock, Compressor, CvToCc, CvToMidi, Distortion, KeyboardInput, Lfo, MidiFilePlayer, MidiMonitor, MidiNote, Mixer, MpeVoice, MultiOutput, Oscilloscope, ParametricEq, Quantizer, Recorder, Reverb, SampleHold, SceneMorph, SineOscillator, StepSequencer, StereoDelay, SvfFilter, Vca};
 
 use super::audio_graph::AudioGraph;
 use super::channels::EngineHandle;
 use super::commands::{EngineCommand, EngineEvent};
+use super::input_stream::{InputReader, MAX_INPUT_CHANNELS};
+use super::midi_clock::MidiTransport;
+use super::midi_output::TimedMidiEvent;
+use super::recorder::{RecordSource, RecorderTap};
+use super::routing::{OutputRouting, ROUTING_SOURCES, SOURCE_MASTER_LEFT, SOURCE_MASTER_RIGHT, SOURCE_MULTI};
 
 /// Creates a module registry with all built-in modules.
 pub fn create_module_registry() -> ModuleRegistry {
@@ -26,7 +33,10 @@ pub fn create_module_registry() -> ModuleRegistry {
     registry.register::<KeyboardInput>();
     registry.register::<MidiMonitor>();
     registry.register::<MidiNote>();
+    registry.register::<MpeVoice>();
+    registry.register::<MidiFilePlayer>();
     registry.register::<SampleHold>();
+    registry.register::<Quantizer>();
     registry.register::<Oscilloscope>();
     registry.register::<StepSequencer>();
     registry.register::<StereoDelay>();
@@ -36,6 +46,12 @@ pub fn create_module_registry() -> ModuleRegistry {
     registry.register::<Chorus>();
     registry.register::<Compressor>();
     registry.register::<Mixer>();
+    registry.register::<SceneMorph>();
+    registry.register::<Recorder>();
+    registry.register::<AudioInput>();
+    registry.register::<MultiOutput>();
+    registry.register::<CvToMidi>();
+    registry.register::<CvToCc>();
     registry
 }
 
@@ -44,14 +60,26 @@ pub fn create_module_registry() -> ModuleRegistry {
 /// This struct is moved into the audio callback closure and handles
 /// all audio processing, including:
 /// - Receiving and processing commands from the UI thread
+/// - Passing the audio input device's signal to Audio Input modules
 /// - Running the audio graph to generate samples
-/// - Extracting output from the AudioOutput module
+/// - Summing the output modules and routing them to the device channels
+/// - Streaming recorded audio to the recorder's writer thread
+/// - Passing the MIDI generated by modules to the MIDI output thread
+/// - Keeping the transport of an external MIDI clock for Clock modules
+/// - Fading the device output in and out for click-free patch changes
 pub struct AudioProcessor {
     /// The audio processing graph.
     graph: AudioGraph,
+    /// The graph not being processed: the patch set aside while previewing,
+    /// a preloaded patch, or an empty graph ready to build the next preview in.
+    parked: AudioGraph,
+    /// Whether `graph` is a preview and `parked` holds the patch to return to.
+    previewing: bool,
+    /// Whether graph commands go to `parked`, to preload a patch.
+    preloading: bool,
     /// Handle for receiving commands from the UI thread.
     engine_handle: EngineHandle,
-    /// Processing context (sample rate, block size).
+    /// Processing context (sample rate, block size, external MIDI transport).
     context: ProcessContext,
     /// Whether audio processing is active.
     is_playing: bool,
@@ -59,6 +87,28 @@ pub struct AudioProcessor {
     frame_counter: u32,
     /// Running average of CPU load (0.0-100.0).
     cpu_load_avg: f32,
+    /// Audio-thread side of the recorder (None = recording unavailable).
+    recorder: Option<RecorderTap>,
+    /// Audio input stream (None = audio input unavailable).
+    input: Option<InputReader>,
+    /// Which device channels the output sources reach.
+    routing: OutputRouting,
+    /// Output sources of the current block: the summed master, then the
+    /// summed Multi Output channels (see `routing`).
+    sources: Vec<Vec<f32>>,
+    /// Whether the patch has an Audio Output module.
+    has_master: bool,
+    /// Queue to the MIDI output thread (None = MIDI output unavailable).
+    midi_output: Option<Producer<TimedMidiEvent>>,
+    /// MIDI messages taken from a module, preallocated to avoid allocating
+    /// on the audio thread.
+    midi_messages: Vec<MidiEvent>,
+    /// Current gain of the device output.
+    output_gain: f32,
+    /// Gain the output is ramping to.
+    output_gain_target: f32,
+    /// Gain change per frame while ramping.
+    output_gain_step: f32,
 }
 
 impl AudioProcessor {
@@ -69,20 +119,66 @@ impl AudioProcessor {
     /// * `block_size` - The maximum number of samples per processing block
     /// * `engine_handle` - Handle for receiving commands from the UI
     pub fn new(sample_rate: f32, block_size: usize, engine_handle: EngineHandle) -> Self {
-        let registry = create_module_registry();
-        let graph = AudioGraph::with_registry(sample_rate, block_size, registry);
+        let graph = AudioGraph::with_registry(sample_rate, block_size, create_module_registry());
+        let parked = AudioGraph::with_registry(sample_rate, block_size, create_module_registry());
         let context = ProcessContext::new(sample_rate, block_size);
 
         Self {
             graph,
+            parked,
+            previewing: false,
+            preloading: false,
             engine_handle,
             context,
             is_playing: false,
             frame_counter: 0,
             cpu_load_avg: 0.0,
+            recorder: None,
+            input: None,
+            routing: OutputRouting::default(),
+            sources: vec![vec![0.0; block_size]; ROUTING_SOURCES],
+            has_master: false,
+            midi_output: None,
+            midi_messages: Vec::with_capacity(Self::MIDI_MESSAGES_CAPACITY),
+            output_gain: 1.0,
+            output_gain_target: 1.0,
+            output_gain_step: 0.0,
+        }
+    }
+
+    /// Attach the recorder that `StartRecording` and Recorder modules stream to.
+    pub fn set_recorder(&mut self, recorder: RecorderTap) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Attach the audio input stream that Audio Input modules read from.
+    pub fn set_input(&mut self, input: InputReader) {
+        self.input = Some(input);
+    }
+
+    /// Attach the queue that the MIDI generated by modules is sent through.
+    pub fn set_midi_output(&mut self, midi_output: Producer<TimedMidiEvent>) {
+        self.midi_output = Some(midi_output);
+    }
+
+    /// Reconfigure for a new sample rate and block size, keeping the patch.
+    ///
+    /// Every module is re-prepared. Call while the audio stream is stopped,
+    /// as this allocates.
+    pub fn prepare(&mut self, sample_rate: f32, block_size: usize) {
+        self.context = ProcessContext::with_transport(sample_rate, block_size, self.context.transport);
+        self.graph.prepare(sample_rate, block_size);
+        self.parked.prepare(sample_rate, block_size);
+        for source in &mut self.sources {
+            source.resize(block_size, 0.0);
         }
     }
 
+    /// Sample rate the processor is prepared for.
+    pub fn sample_rate(&self) -> f32 {
+        self.context.sample_rate
+    }
+
     /// How often to send CPU load events (in audio callbacks).
     /// At 44100Hz with 256 sample blocks, this is about 172 callbacks/sec.
     /// Sending every 8 callbacks gives ~21Hz update rate.
@@ -91,6 +187,15 @@ impl AudioProcessor {
     /// Smoothing factor for CPU load averaging (0-1, higher = more responsive).
     const CPU_SMOOTHING: f32 = 0.3;
 
+    /// Room for the MIDI messages one module generates in a block.
+    const MIDI_MESSAGES_CAPACITY: usize = 256;
+
+    /// Tempo assumed for a song position received before any clock.
+    const DEFAULT_TEMPO_BPM: f32 = 120.0;
+
+    /// Controller number of the All Notes Off channel mode message.
+    const ALL_NOTES_OFF: u8 = 123;
+
     /// Processes a block of audio.
     ///
     /// This is called from the cpal audio callback. It:
@@ -110,6 +215,22 @@ impl AudioProcessor {
             *sample = 0.0;
         }
 
+        // Calculate number of frames in this callback
+        let num_frames = output.len() / channels;
+
+        // Keep reading the input while stopped, so it can be metered and
+        // doesn't build up latency
+        if let Some(input) = &mut self.input {
+            input.read(num_frames);
+        }
+
+        self.frame_counter += 1;
+        let report = self.frame_counter >= Self::CPU_REPORT_INTERVAL;
+        if report {
+            self.frame_counter = 0;
+            self.send_input_levels();
+        }
+
         if !self.is_playing {
             // Reset CPU load when not playing
             self.cpu_load_avg = 0.0;
@@ -119,16 +240,16 @@ impl AudioProcessor {
         // Start timing for CPU measurement
         let start_time = Instant::now();
 
-        // Calculate number of frames in this callback
-        let num_frames = output.len() / channels;
-
         // Update context and graph block size if different
         if num_frames != self.context.block_size {
-            self.context = ProcessContext::new(self.context.sample_rate, num_frames);
+            self.context = ProcessContext::with_transport(self.context.sample_rate, num_frames, self.context.transport);
             // Resize audio graph buffers to match new block size
             self.graph.set_block_size(num_frames);
         }
 
+        // Hand the input signal to Audio Input modules
+        self.distribute_input();
+
         // Process the audio graph
         self.graph.process(&self.context);
 
@@ -141,8 +262,20 @@ impl AudioProcessor {
         // Send oscilloscope buffer data to UI for waveform display
         self.send_scope_buffers();
 
-        // Extract output from AudioOutput modules and write to output buffer
+        // Sum the output modules and route them to the device channels
         self.extract_output(output, channels, num_frames);
+        self.apply_output_gain(output, channels);
+
+        // Stream recorded audio to the writer thread
+        self.capture_recordings();
+
+        // Pass MIDI generated by modules to the MIDI output thread
+        self.send_midi_output(start_time, num_frames);
+
+        // Move the external transport on to the next block
+        if self.context.transport.playing {
+            self.context.transport.sample_position += num_frames as u64;
+        }
 
         // Calculate CPU load
         let elapsed = start_time.elapsed();
@@ -154,13 +287,41 @@ impl AudioProcessor {
             + (1.0 - Self::CPU_SMOOTHING) * self.cpu_load_avg;
 
         // Send CPU load event at regular intervals (to avoid flooding UI)
-        self.frame_counter += 1;
-        if self.frame_counter >= Self::CPU_REPORT_INTERVAL {
-            self.frame_counter = 0;
+        if report {
             self.engine_handle.send_event_lossy(EngineEvent::CpuLoad(self.cpu_load_avg));
         }
     }
 
+    /// Passes the last block read from the audio input to every module.
+    fn distribute_input(&mut self) {
+        let Some(input) = &self.input else {
+            return;
+        };
+        let channels: [&[f32]; MAX_INPUT_CHANNELS] = std::array::from_fn(|i| input.channel(i));
+        let channels = &channels[..input.channels()];
+
+        // Modules added since the last block aren't in the order yet
+        self.graph.update_processing_order();
+        for i in 0..self.graph.processing_order().len() {
+            let node_id = self.graph.processing_order()[i];
+            if let Some(module) = self.graph.get_module_mut(node_id) {
+                if module.uses_audio_input() {
+                    module.set_audio_input(channels);
+                }
+            }
+        }
+    }
+
+    /// Sends the audio input peak levels to the UI thread.
+    fn send_input_levels(&mut self) {
+        if let Some(input) = &mut self.input {
+            let channels = input.channels();
+            let peaks = input.take_peaks();
+            self.engine_handle
+                .send_event_lossy(EngineEvent::InputLevel { peaks, channels });
+        }
+    }
+
     /// Sends monitored input values to the UI thread.
     fn send_input_values(&mut self) {
         for (node_id, input_index, value) in self.graph.drain_sampled_input_values() {
@@ -207,6 +368,10 @@ impl AudioProcessor {
         for cmd in commands {
             match cmd {
                 EngineCommand::SetPlaying(playing) => {
+                    // Don't leave notes hanging on external gear
+                    if self.is_playing && !playing {
+                        self.send_all_notes_off();
+                    }
                     self.is_playing = playing;
                     let event = if playing {
                         EngineEvent::Started
@@ -215,53 +380,254 @@ impl AudioProcessor {
                     };
                     self.engine_handle.send_event_lossy(event);
                 }
+                EngineCommand::StartRecording { source, take } => {
+                    if let Some(recorder) = &mut self.recorder {
+                        recorder.start(source, take);
+                    }
+                }
+                EngineCommand::StopRecording => {
+                    if let Some(recorder) = &mut self.recorder {
+                        recorder.stop();
+                    }
+                }
+                EngineCommand::SetOutputRouting(routing) => {
+                    self.routing = routing;
+                }
+                EngineCommand::SetMidiTransport(transport) => {
+                    self.set_midi_transport(transport);
+                }
+                EngineCommand::AllNotesOff => {
+                    self.send_all_notes_off();
+                }
+                EngineCommand::FadeOutput { gain, seconds } => {
+                    self.fade_output(gain, seconds);
+                }
+                EngineCommand::BeginPreview => {
+                    self.begin_preview();
+                }
+                EngineCommand::EndPreview { keep } => {
+                    self.end_preview(keep);
+                }
+                EngineCommand::BeginPreload => {
+                    self.begin_preload();
+                }
+                EngineCommand::EndPreload => {
+                    self.preloading = false;
+                }
+                EngineCommand::ClearPreload => {
+                    self.clear_preload();
+                }
+                other if self.preloading => {
+                    self.parked.handle_command(other);
+                }
                 other => {
                     // Delegate graph-related commands to the audio graph
                     self.graph.handle_command(other);
                 }
             }
         }
+
+        // Tunings and note sequences are freed on the UI thread; if the queue
+        // is full, they are freed here
+        for graph in [&mut self.graph, &mut self.parked] {
+            for tuning in graph.drain_released_tunings() {
+                self.engine_handle.send_event_lossy(EngineEvent::ReleaseTuning(tuning));
+            }
+            for sequence in graph.drain_released_sequences() {
+                self.engine_handle.send_event_lossy(EngineEvent::ReleaseNoteSequence(sequence));
+            }
+        }
     }
 
-    /// Extracts audio from AudioOutput modules and writes to the output buffer.
+    /// Sets the current graph aside and switches to the empty parked graph.
+    fn begin_preview(&mut self) {
+        if self.previewing {
+            return;
+        }
+        std::mem::swap(&mut self.graph, &mut self.parked);
+        self.graph.set_block_size(self.context.block_size);
+        self.previewing = true;
+        self.preloading = false;
+    }
+
+    /// Keeps the preview or returns to the graph set aside, and clears the other.
+    fn end_preview(&mut self, keep: bool) {
+        if !self.previewing {
+            return;
+        }
+        if !keep {
+            std::mem::swap(&mut self.graph, &mut self.parked);
+            self.graph.set_block_size(self.context.block_size);
+        }
+        self.parked.clear();
+        self.previewing = false;
+    }
+
+    /// Clears the parked graph and starts building a patch in it.
+    fn begin_preload(&mut self) {
+        if self.previewing {
+            return;
+        }
+        self.parked.clear();
+        self.preloading = true;
+    }
+
+    /// Drops the patch preloaded in the parked graph.
+    fn clear_preload(&mut self) {
+        if self.previewing {
+            return;
+        }
+        self.parked.clear();
+        self.preloading = false;
+    }
+
+    /// Sums the Audio Output and Multi Output modules and routes them to the output buffer.
     fn extract_output(&mut self, output: &mut [f32], channels: usize, num_frames: usize) {
-        // Find the AudioOutput module(s) and extract their output
-        // For now, we support a single output module
-
-        for node_id in self.graph.processing_order().to_vec() {
-            if let Some(module) = self.graph.get_module(node_id) {
-                // Check if this module provides audio output
-                if let Some((left, right)) = module.get_audio_output() {
-                    // Write to output (interleaved stereo)
-                    for (i, frame) in output.chunks_mut(channels).enumerate() {
-                        if i < num_frames {
-                            let l = left.get(i).copied().unwrap_or(0.0);
-                            let r = right.get(i).copied().unwrap_or(0.0);
-
-                            if channels >= 1 {
-                                frame[0] = l;
-                            }
-                            if channels >= 2 {
-                                frame[1] = r;
-                            }
-                            // For more than 2 channels, duplicate to additional channels
-                            for ch in frame.iter_mut().skip(2) {
-                                *ch = (l + r) * 0.5;
-                            }
-                        }
-                    }
+        for source in &mut self.sources {
+            source.clear();
+            source.resize(num_frames, 0.0);
+        }
+        self.has_master = false;
+
+        let mut peak_left: f32 = 0.0;
+        let mut peak_right: f32 = 0.0;
+        for &node_id in self.graph.processing_order() {
+            let Some(module) = self.graph.get_module(node_id) else {
+                continue;
+            };
+
+            // Every Audio Output module adds to the stereo master
+            if let Some((left, right)) = module.get_audio_output() {
+                self.has_master = true;
+                add_into(&mut self.sources[SOURCE_MASTER_LEFT], left);
+                add_into(&mut self.sources[SOURCE_MASTER_RIGHT], right);
+                if let Some((l, r)) = module.get_peak_levels() {
+                    peak_left = peak_left.max(l);
+                    peak_right = peak_right.max(r);
+                }
+            }
 
-                    // Send output levels to UI for metering
-                    if let Some((peak_l, peak_r)) = module.get_peak_levels() {
-                        self.engine_handle.send_event_lossy(EngineEvent::OutputLevel {
-                            left: peak_l,
-                            right: peak_r,
-                        });
-                    }
+            // Multi Output channels are summed per channel
+            if let Some(multi) = module.get_multichannel_output() {
+                for (source, samples) in self.sources[SOURCE_MULTI..].iter_mut().zip(multi) {
+                    add_into(source, samples);
+                }
+            }
+        }
+
+        self.routing.render(&self.sources, output, channels);
+
+        // Send output levels to UI for metering
+        if self.has_master {
+            self.engine_handle.send_event_lossy(EngineEvent::OutputLevel {
+                left: peak_left,
+                right: peak_right,
+            });
+        }
+    }
+
+    /// Starts ramping the device output to `gain` over `seconds`.
+    fn fade_output(&mut self, gain: f32, seconds: f32) {
+        let gain = gain.max(0.0);
+        let frames = seconds * self.context.sample_rate;
+        self.output_gain_target = gain;
+        if frames < 1.0 {
+            self.output_gain = gain;
+            self.output_gain_step = 0.0;
+        } else {
+            self.output_gain_step = (gain - self.output_gain).abs() / frames;
+        }
+    }
+
+    /// Scales the output buffer by the output gain, moving it along its ramp.
+    fn apply_output_gain(&mut self, output: &mut [f32], channels: usize) {
+        if self.output_gain == 1.0 && self.output_gain_target == 1.0 {
+            return;
+        }
 
-                    break; // Only process first output module
+        for frame in output.chunks_mut(channels) {
+            if self.output_gain < self.output_gain_target {
+                self.output_gain = (self.output_gain + self.output_gain_step).min(self.output_gain_target);
+            } else if self.output_gain > self.output_gain_target {
+                self.output_gain = (self.output_gain - self.output_gain_step).max(self.output_gain_target);
+            }
+            for sample in frame {
+                *sample *= self.output_gain;
+            }
+        }
+    }
+
+    /// Passes the recorded source and the input of Recorder modules to the recorder.
+    fn capture_recordings(&mut self) {
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        let graph = &self.graph;
+
+        match recorder.source() {
+            Some(RecordSource::Master) if self.has_master => {
+                recorder.write_main(&[
+                    &self.sources[SOURCE_MASTER_LEFT],
+                    &self.sources[SOURCE_MASTER_RIGHT],
+                ]);
+            }
+            Some(RecordSource::Output { node_id, output_index }) => {
+                if let Some(samples) = graph.output_samples(node_id, output_index) {
+                    recorder.write_main(&[samples]);
                 }
             }
+            _ => {}
+        }
+
+        recorder.write_nodes(graph.processing_order().iter().filter_map(|&node_id| {
+            let (left, right, recording) = graph.get_module(node_id)?.get_recording()?;
+            Some((node_id, left, right, recording))
+        }));
+    }
+
+    /// Passes the MIDI messages generated by modules to the MIDI output thread.
+    ///
+    /// Each message is due one block after `block_time` plus its sample
+    /// offset, so the sender can keep the spacing of events within the
+    /// block. Messages that don't fit in the queue are dropped.
+    fn send_midi_output(&mut self, block_time: Instant, num_frames: usize) {
+        let Some(midi_output) = &mut self.midi_output else {
+            return;
+        };
+        let sample_rate = self.context.sample_rate;
+        let block_time = block_time + Duration::from_secs_f64(num_frames as f64 / f64::from(sample_rate));
+
+        for i in 0..self.graph.processing_order().len() {
+            let node_id = self.graph.processing_order()[i];
+            if let Some(module) = self.graph.get_module_mut(node_id) {
+                module.take_midi_output(&mut self.midi_messages);
+            }
+            for event in self.midi_messages.drain(..) {
+                let _ = midi_output.push(TimedMidiEvent::at(event, block_time, sample_rate));
+            }
+        }
+    }
+
+    /// Follow the transport of an external MIDI clock.
+    fn set_midi_transport(&mut self, transport: MidiTransport) {
+        let state = &mut self.context.transport;
+        state.playing = transport.playing;
+        state.tempo_bpm = transport.tempo_bpm;
+        if let Some(beats) = transport.locate_beats {
+            let tempo = transport.tempo_bpm.unwrap_or(Self::DEFAULT_TEMPO_BPM);
+            let samples_per_beat = self.context.sample_rate as f64 * 60.0 / tempo as f64;
+            state.sample_position = (beats * samples_per_beat).round() as u64;
+        }
+    }
+
+    /// Sends All Notes Off on every MIDI channel.
+    fn send_all_notes_off(&mut self) {
+        if let Some(midi_output) = &mut self.midi_output {
+            let now = Instant::now();
+            for channel in 0..16 {
+                let event = MidiEvent::control_change(0, channel, Self::ALL_NOTES_OFF, 0);
+                let _ = midi_output.push(TimedMidiEvent { event, due: now });
+            }
         }
     }
 
@@ -271,6 +637,13 @@ impl AudioProcessor {
     }
 }
 
+/// Adds `samples` onto `target`, sample by sample.
+fn add_into(target: &mut [f32], samples: &[f32]) {
+    for (out, sample) in target.iter_mut().zip(samples) {
+        *out += sample;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -290,7 +663,10 @@ mod tests {
         assert!(registry.contains("input.keyboard"));
         assert!(registry.contains("util.midi_monitor"));
         assert!(registry.contains("input.midi_note"));
+        assert!(registry.contains("input.mpe_voice"));
+        assert!(registry.contains("input.midi_file"));
         assert!(registry.contains("util.sample_hold"));
+        assert!(registry.contains("util.quantizer"));
         assert!(registry.contains("util.oscilloscope"));
         assert!(registry.contains("seq.step"));
         assert!(registry.contains("fx.delay"));
@@ -300,7 +676,13 @@ mod tests {
         assert!(registry.contains("fx.chorus"));
         assert!(registry.contains("fx.compressor"));
         assert!(registry.contains("util.mixer"));
-        assert_eq!(registry.len(), 21);
+        assert!(registry.contains("util.scene_morph"));
+        assert!(registry.contains("output.recorder"));
+        assert!(registry.contains("input.audio"));
+        assert!(registry.contains("output.multi"));
+        assert!(registry.contains("output.cv_to_midi"));
+        assert!(registry.contains("output.cv_to_cc"));
+        assert_eq!(registry.len(), 30);
     }
 
     #[test]
@@ -346,4 +728,278 @@ mod tests {
         let event = ui.recv_event();
         assert!(matches!(event, Some(EngineEvent::Started)));
     }
+
+    #[test]
+    fn test_audio_processor_passes_input_to_modules() {
+        let channels = EngineChannels::with_defaults();
+        let (mut ui, engine) = channels.split();
+        let (mut capture, reader) = crate::engine::input_stream::input_stream(4096);
+
+        let mut processor = AudioProcessor::new(44100.0, 256, engine);
+        processor.set_input(reader);
+        capture.set_channels(2);
+
+        // The first block switches the reader to the new device
+        let mut output = vec![0.0; 512];
+        processor.process(&mut output, 2);
+        capture.push(&[0.5, -0.5].repeat(256));
+
+        ui.send_command(EngineCommand::AddModule { node_id: 1, module_id: "input.audio" }).unwrap();
+        ui.send_command(EngineCommand::MonitorOutput { node_id: 1, output_index: 1 }).unwrap();
+        ui.send_command(EngineCommand::SetPlaying(true)).unwrap();
+        processor.process(&mut output, 2);
+
+        let mut seen = None;
+        while let Some(event) = ui.recv_event() {
+            if let EngineEvent::OutputValue { node_id: 1, output_index: 1, value } = event {
+                seen = Some(value);
+            }
+        }
+        let value = seen.expect("no output value from the Audio Input module");
+        assert!((value + 0.5).abs() < 1e-3);
+    }
+
+    /// A playing processor whose audio input delivers a constant 0.5 on channel 1.
+    fn processor_with_input(ui: &mut crate::engine::UiHandle, engine: crate::engine::EngineHandle) -> (AudioProcessor, crate::engine::InputCapture) {
+        let (capture, reader) = crate::engine::input_stream::input_stream(65536);
+        let mut processor = AudioProcessor::new(44100.0, 256, engine);
+        processor.set_input(reader);
+        capture.set_channels(1);
+        processor.process(&mut [0.0; 512], 2);
+
+        ui.send_command(EngineCommand::AddModule { node_id: 1, module_id: "input.audio" }).unwrap();
+        ui.send_command(EngineCommand::SetPlaying(true)).unwrap();
+        (processor, capture)
+    }
+
+    #[test]
+    fn test_audio_processor_sums_output_modules() {
+        let channels = EngineChannels::with_defaults();
+        let (mut ui, engine) = channels.split();
+        let (mut processor, mut capture) = processor_with_input(&mut ui, engine);
+
+        for node_id in [2, 3] {
+            ui.send_command(EngineCommand::AddModule { node_id, module_id: "output.audio" }).unwrap();
+            // Audio Input In 1 -> Audio Output Mono
+            ui.send_command(EngineCommand::Connect { from_node: 1, from_port: 0, to_node: node_id, to_port: 2 }).unwrap();
+        }
+
+        capture.push(&[0.5; 256]);
+        let mut output = vec![0.0; 512];
+        processor.process(&mut output, 2);
+
+        // Each output applies volume 0.8 and the soft limiter
+        let expected = 2.0 * (0.5_f32 * 0.8).tanh();
+        assert!((output[200] - expected).abs() < 1e-3, "got {}", output[200]);
+        assert!((output[201] - expected).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_audio_processor_routes_multi_output() {
+        let channels = EngineChannels::with_defaults();
+        let (mut ui, engine) = channels.split();
+        let (mut processor, mut capture) = processor_with_input(&mut ui, engine);
+
+        // Audio Input In 1 -> Multi Output Ch 2 -> device channel 4
+        ui.send_command(EngineCommand::AddModule { node_id: 2, module_id: "output.multi" }).unwrap();
+        ui.send_command(EngineCommand::Connect { from_node: 1, from_port: 0, to_node: 2, to_port: 1 }).unwrap();
+        let mut routing = OutputRouting::empty();
+        routing.set(SOURCE_MULTI + 1, 3, true);
+        ui.send_command(EngineCommand::SetOutputRouting(routing)).unwrap();
+
+        capture.push(&[0.5; 256]);
+        let mut output = vec![0.0; 1024];
+        processor.process(&mut output, 4);
+
+        let frame = &output[400..404];
+        assert_eq!(&frame[..3], &[0.0, 0.0, 0.0]);
+        assert!((frame[3] - 0.5).abs() < 1e-3, "got {}", frame[3]);
+    }
+
+    #[test]
+    fn test_audio_processor_prepare_keeps_patch() {
+        let channels = EngineChannels::with_defaults();
+        let (mut ui, engine) = channels.split();
+        let (mut processor, mut capture) = processor_with_input(&mut ui, engine);
+        ui.send_command(EngineCommand::AddModule { node_id: 2, module_id: "output.audio" }).unwrap();
+        ui.send_command(EngineCommand::Connect { from_node: 1, from_port: 0, to_node: 2, to_port: 2 }).unwrap();
+        processor.process(&mut [0.0; 512], 2);
+
+        processor.prepare(96000.0, 128);
+        assert_eq!(processor.sample_rate(), 96000.0);
+        assert_eq!(processor.graph.module_count(), 2);
+        assert_eq!(processor.graph.connection_count(), 1);
+
+        capture.push(&[0.5; 128]);
+        let mut output = vec![0.0; 256];
+        processor.process(&mut output, 2);
+        assert!(output[200] > 0.1, "got {}", output[200]);
+    }
+
+    #[test]
+    fn test_audio_processor_preview_keeps_patch() {
+        let channels = EngineChannels::with_defaults();
+        let (mut ui, engine) = channels.split();
+        let (mut processor, mut capture) = processor_with_input(&mut ui, engine);
+        ui.send_command(EngineCommand::AddModule { node_id: 2, module_id: "output.audio" }).unwrap();
+        ui.send_command(EngineCommand::Connect { from_node: 1, from_port: 0, to_node: 2, to_port: 2 }).unwrap();
+        processor.process(&mut [0.0; 512], 2);
+
+        // The preview is built in an empty graph; the patch is set aside
+        ui.send_command(EngineCommand::BeginPreview).unwrap();
+        ui.send_command(EngineCommand::ClearGraph).unwrap();
+        ui.send_command(EngineCommand::AddModule { node_id: 7, module_id: "osc.sine" }).unwrap();
+        processor.process(&mut [0.0; 512], 2);
+        assert_eq!(processor.graph.module_count(), 1);
+        assert_eq!(processor.parked.module_count(), 2);
+
+        // Going back restores the same modules, and the preview is dropped
+        ui.send_command(EngineCommand::EndPreview { keep: false }).unwrap();
+        capture.push(&[0.5; 256]);
+        let mut output = vec![0.0; 512];
+        processor.process(&mut output, 2);
+        assert_eq!(processor.graph.connection_count(), 1);
+        assert!(processor.graph.contains_module(1));
+        assert_eq!(processor.parked.module_count(), 0);
+        assert!(output[400] > 0.1, "got {}", output[400]);
+
+        // Keeping a preview drops the patch set aside
+        ui.send_command(EngineCommand::BeginPreview).unwrap();
+        ui.send_command(EngineCommand::AddModule { node_id: 7, module_id: "osc.sine" }).unwrap();
+        ui.send_command(EngineCommand::EndPreview { keep: true }).unwrap();
+        processor.process(&mut [0.0; 512], 2);
+        assert!(processor.graph.contains_module(7));
+        assert_eq!(processor.parked.module_count(), 0);
+    }
+
+    #[test]
+    fn test_audio_processor_preload_switches_graphs() {
+        let channels = EngineChannels::with_defaults();
+        let (mut ui, engine) = channels.split();
+        let (mut processor, _capture) = processor_with_input(&mut ui, engine);
+        processor.process(&mut [0.0; 512], 2);
+
+        // The next patch is built aside while the current one keeps playing
+        ui.send_command(EngineCommand::BeginPreload).unwrap();
+        ui.send_command(EngineCommand::AddModule { node_id: 7, module_id: "osc.sine" }).unwrap();
+        ui.send_command(EngineCommand::EndPreload).unwrap();
+        ui.send_command(EngineCommand::SetParameter { node_id: 1, param_index: 0, value: 0.5 }).unwrap();
+        processor.process(&mut [0.0; 512], 2);
+        assert!(processor.graph.contains_module(1));
+        assert!(!processor.graph.contains_module(7));
+        assert!(processor.parked.contains_module(7));
+
+        // Switching to it only swaps the graphs
+        ui.send_command(EngineCommand::BeginPreview).unwrap();
+        ui.send_command(EngineCommand::EndPreview { keep: true }).unwrap();
+        processor.process(&mut [0.0; 512], 2);
+        assert!(processor.graph.contains_module(7));
+        assert_eq!(processor.parked.module_count(), 0);
+
+        // A preload that isn't needed is dropped
+        ui.send_command(EngineCommand::BeginPreload).unwrap();
+        ui.send_command(EngineCommand::AddModule { node_id: 8, module_id: "osc.sine" }).unwrap();
+        ui.send_command(EngineCommand::EndPreload).unwrap();
+        ui.send_command(EngineCommand::ClearPreload).unwrap();
+        processor.process(&mut [0.0; 512], 2);
+        assert_eq!(processor.parked.module_count(), 0);
+        assert!(processor.graph.contains_module(7));
+    }
+
+    #[test]
+    fn test_audio_processor_sends_midi_output() {
+        let channels = EngineChannels::with_defaults();
+        let (mut ui, engine) = channels.split();
+        let mut processor = AudioProcessor::new(44100.0, 256, engine);
+        let (producer, mut consumer) = rtrb::RingBuffer::new(64);
+        processor.set_midi_output(producer);
+
+        // CV to CC with no input sends CC 74 = 0 on channel 3
+        ui.send_command(EngineCommand::AddModule { node_id: 1, module_id: "output.cv_to_cc" }).unwrap();
+        ui.send_command(EngineCommand::SetParameter { node_id: 1, param_index: 0, value: 74.0 }).unwrap();
+        ui.send_command(EngineCommand::SetParameter { node_id: 1, param_index: 1, value: 2.0 }).unwrap();
+        ui.send_command(EngineCommand::SetPlaying(true)).unwrap();
+        let before = Instant::now();
+        processor.process(&mut [0.0; 512], 2);
+        let timed: TimedMidiEvent = consumer.pop().unwrap();
+        assert_eq!(timed.event, MidiEvent::control_change(255, 2, 74, 0));
+        // Due one block plus its offset after the block was processed
+        assert!(timed.due >= before + Duration::from_secs_f64(511.0 / 44100.0));
+        assert!(consumer.pop().is_err());
+
+        // Stopping releases every note on external gear
+        ui.send_command(EngineCommand::SetPlaying(false)).unwrap();
+        processor.process(&mut [0.0; 512], 2);
+        let notes_off: Vec<MidiEvent> = std::iter::from_fn(|| consumer.pop().ok()).map(|timed| timed.event).collect();
+        assert_eq!(notes_off.len(), 16);
+        assert_eq!(notes_off[15], MidiEvent::control_change(0, 15, 123, 0));
+
+        // A panic sends them again, playing or not
+        ui.send_command(EngineCommand::AllNotesOff).unwrap();
+        processor.process(&mut [0.0; 512], 2);
+        assert_eq!(std::iter::from_fn(|| consumer.pop().ok()).count(), 16);
+    }
+
+    #[test]
+    fn test_audio_processor_fades_output() {
+        let channels = EngineChannels::with_defaults();
+        let (mut ui, engine) = channels.split();
+        let (mut processor, mut capture) = processor_with_input(&mut ui, engine);
+        ui.send_command(EngineCommand::AddModule { node_id: 2, module_id: "output.audio" }).unwrap();
+        ui.send_command(EngineCommand::Connect { from_node: 1, from_port: 0, to_node: 2, to_port: 2 }).unwrap();
+        let mut output = vec![0.0; 512];
+        for _ in 0..4 {
+            capture.push(&[0.5; 256]);
+            processor.process(&mut output, 2);
+        }
+        let level = output[510];
+        assert!(level > 0.1, "got {}", level);
+
+        // Fade out over exactly one block
+        ui.send_command(EngineCommand::FadeOutput { gain: 0.0, seconds: 256.0 / 44100.0 }).unwrap();
+        capture.push(&[0.5; 256]);
+        let mut output = vec![0.0; 512];
+        processor.process(&mut output, 2);
+        assert!((output[0] - level).abs() < 0.01, "got {}", output[0]);
+        assert!((output[256] - level / 2.0).abs() < 0.01, "got {}", output[256]);
+        assert!(output[510].abs() < 1e-3, "got {}", output[510]);
+
+        // Silent until faded back in
+        capture.push(&[0.5; 256]);
+        let mut output = vec![0.0; 512];
+        processor.process(&mut output, 2);
+        assert!(output.iter().all(|&s| s == 0.0));
+
+        ui.send_command(EngineCommand::FadeOutput { gain: 1.0, seconds: 0.0 }).unwrap();
+        capture.push(&[0.5; 256]);
+        let mut output = vec![0.0; 512];
+        processor.process(&mut output, 2);
+        assert!((output[510] - level).abs() < 1e-3, "got {}", output[510]);
+    }
+
+    #[test]
+    fn test_audio_processor_follows_midi_transport() {
+        let channels = EngineChannels::with_defaults();
+        let (mut ui, engine) = channels.split();
+        let mut processor = AudioProcessor::new(48000.0, 256, engine);
+        ui.send_command(EngineCommand::SetPlaying(true)).unwrap();
+
+        // Continue from beat 2 at 120 BPM: one second in
+        let transport = MidiTransport { playing: true, tempo_bpm: Some(120.0), locate_beats: Some(2.0) };
+        ui.send_command(EngineCommand::SetMidiTransport(transport)).unwrap();
+        processor.process(&mut [0.0; 512], 2);
+        assert!(processor.context.transport.playing);
+        assert_eq!(processor.context.transport.tempo_bpm, Some(120.0));
+        assert_eq!(processor.context.transport.sample_position, 48000 + 256);
+
+        // A new block size keeps the transport
+        processor.process(&mut [0.0; 256], 2);
+        assert_eq!(processor.context.transport.sample_position, 48000 + 384);
+
+        // Stopped: the position holds
+        let transport = MidiTransport { playing: false, tempo_bpm: Some(120.0), locate_beats: None };
+        ui.send_command(EngineCommand::SetMidiTransport(transport)).unwrap();
+        processor.process(&mut [0.0; 512], 2);
+        assert_eq!(processor.context.transport.sample_position, 48000 + 384);
+    }
 }
@@ -3,6 +3,14 @@
 //! Defines the messages that flow between the UI thread and the audio engine thread.
 //! All types here must be Send + 'static for safe cross-thread communication.
 
+use std::sync::Arc;
+
+use super::input_stream::MAX_INPUT_CHANNELS;
+use super::midi_clock::MidiTransport;
+use super::recorder::RecordSource;
+use super::routing::OutputRouting;
+use crate::dsp::{NoteSequence, Tuning};
+
 /// Unique identifier for a node in the audio graph.
 /// Maps to the node ID from egui_node_graph2.
 pub type NodeId = u64;
@@ -66,6 +74,33 @@ pub enum EngineCommand {
     /// Clear the entire audio graph.
     ClearGraph,
 
+    /// Set the current graph aside, modules and all, and switch to the
+    /// scratch graph that the following commands build a preview in. The
+    /// scratch graph is empty unless a patch was preloaded into it.
+    /// Does nothing while already previewing.
+    BeginPreview,
+
+    /// Leave the scratch graph started by `BeginPreview`.
+    EndPreview {
+        /// Keep the preview as the current graph (true), or return to the
+        /// graph that was set aside (false). The other graph is cleared.
+        keep: bool,
+    },
+
+    /// Clear the scratch graph and send the following graph commands to it,
+    /// building the next patch there while the current graph keeps playing.
+    /// Switching to it is then `BeginPreview` followed by
+    /// `EndPreview { keep: true }`. Does nothing while previewing.
+    BeginPreload,
+
+    /// Send graph commands to the current graph again, leaving the patch
+    /// built since `BeginPreload` in the scratch graph.
+    EndPreload,
+
+    /// Drop a preloaded patch, clearing the scratch graph. Does nothing
+    /// while previewing.
+    ClearPreload,
+
     /// Start monitoring an input port for UI feedback.
     /// The engine will send InputValue events with the signal values.
     MonitorInput {
@@ -99,6 +134,59 @@ pub enum EngineCommand {
         /// The output port index to stop monitoring.
         output_index: PortIndex,
     },
+
+    /// Start streaming a source to the recorder.
+    /// Ends the current recording take, if any.
+    StartRecording {
+        /// What to record.
+        source: RecordSource,
+        /// Take number from `WavRecorder::begin_take`.
+        take: u32,
+    },
+
+    /// End the current recording take.
+    StopRecording,
+
+    /// Change which device channels the master and Multi Output channels reach.
+    SetOutputRouting(OutputRouting),
+
+    /// Update the transport of the external MIDI clock that Clock modules
+    /// set to the MIDI source follow.
+    SetMidiTransport(MidiTransport),
+
+    /// Send All Notes Off on every channel of the MIDI outputs.
+    AllNotesOff,
+
+    /// Ramp the gain of the device output, e.g. to switch patches without a click.
+    FadeOutput {
+        /// Gain to reach (0.0 = silent, 1.0 = normal level).
+        gain: f32,
+        /// Length of the ramp in seconds (0.0 = jump).
+        seconds: f32,
+    },
+
+    /// Hand the notes of a MIDI file to a MIDI File Player module.
+    SetNoteSequence {
+        /// Target node.
+        node_id: NodeId,
+        /// The notes to play (None = unload the file).
+        sequence: Option<Arc<NoteSequence>>,
+    },
+
+    /// Set the tuning note modules convert note numbers with, unless they
+    /// have their own (see `SetModuleTuning`).
+    SetTuning {
+        /// The tuning, built on the UI thread.
+        tuning: Arc<Tuning>,
+    },
+
+    /// Give a module its own tuning, overriding the global one.
+    SetModuleTuning {
+        /// Target node.
+        node_id: NodeId,
+        /// The tuning (None = return to the global tuning).
+        tuning: Option<Arc<Tuning>>,
+    },
 }
 
 /// Events sent from the audio engine to the UI thread.
@@ -113,6 +201,14 @@ pub enum EngineEvent {
         right: f32,
     },
 
+    /// Current audio input levels for metering display.
+    InputLevel {
+        /// Peak level per input channel since the last report (0.0-1.0+).
+        peaks: [f32; MAX_INPUT_CHANNELS],
+        /// Number of channels the input device has (at most `MAX_INPUT_CHANNELS`).
+        channels: usize,
+    },
+
     /// Current CPU load of the audio processing.
     CpuLoad(f32),
 
@@ -159,6 +255,14 @@ pub enum EngineEvent {
         /// Whether this capture was triggered (true) or free-running (false).
         triggered: bool,
     },
+
+    /// A tuning the engine no longer uses, handed back so that it is freed
+    /// on the UI thread rather than the audio thread.
+    ReleaseTuning(Arc<Tuning>),
+
+    /// A note sequence the engine no longer uses, handed back so that it is
+    /// freed on the UI thread rather than the audio thread.
+    ReleaseNoteSequence(Arc<NoteSequence>),
 }
 
 #[cfg(test)]
@@ -201,6 +305,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_input_level_event() {
+        let mut peaks = [0.0; MAX_INPUT_CHANNELS];
+        peaks[1] = 0.5;
+        let event = EngineEvent::InputLevel { peaks, channels: 2 };
+        if let EngineEvent::InputLevel { peaks, channels } = event.clone() {
+            assert_eq!(channels, 2);
+            assert!((peaks[1] - 0.5).abs() < f32::EPSILON);
+        } else {
+            panic!("Clone failed");
+        }
+    }
+
     #[test]
     fn test_scope_buffer_event() {
         let event = EngineEvent::ScopeBuffer {
@@ -260,6 +377,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_start_recording_command() {
+        let cmd = EngineCommand::StartRecording {
+            source: RecordSource::Output { node_id: 3, output_index: 1 },
+            take: 2,
+        };
+        if let EngineCommand::StartRecording { source, take } = cmd.clone() {
+            assert_eq!(source.channels(), 1);
+            assert_eq!(take, 2);
+        } else {
+            panic!("Clone failed");
+        }
+    }
+
+    #[test]
+    fn test_set_note_sequence_command() {
+        let sequence = Arc::new(NoteSequence { length_beats: 4.0, ..NoteSequence::default() });
+        let cmd = EngineCommand::SetNoteSequence { node_id: 7, sequence: Some(sequence) };
+        if let EngineCommand::SetNoteSequence { node_id, sequence } = cmd.clone() {
+            assert_eq!(node_id, 7);
+            assert_eq!(sequence.unwrap().length_beats, 4.0);
+        } else {
+            panic!("Clone failed");
+        }
+    }
+
     #[test]
     fn test_command_is_send() {
         fn assert_send<T: Send>() {}
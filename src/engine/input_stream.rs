@@ -0,0 +1,300 @@
+//! Audio Input Stream
+//!
+//! Carries samples from the input device callback to the audio processor.
+//! Input and output run as separate cpal streams, each on its own callback,
+//! so the input is passed through a lock-free ring buffer and read back one
+//! output block at a time.
+//!
+//! The two device clocks drift apart slowly. When the input runs ahead the
+//! oldest samples are skipped to keep latency bounded; when it falls behind
+//! the missing samples are filled with silence.
+
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use rtrb::{Consumer, Producer, RingBuffer};
+
+/// Maximum number of input channels passed to the audio graph.
+pub const MAX_INPUT_CHANNELS: usize = 8;
+
+/// Ring buffer size in samples (shared by all channels).
+pub const DEFAULT_INPUT_BUFFER_SAMPLES: usize = 65536;
+
+/// Input buffered beyond this many frames (after a block is read) is skipped.
+const MAX_LATENCY_FRAMES: usize = 2048;
+
+/// State shared by both sides of the input stream.
+struct InputShared {
+    /// Interleaved channel count of the current input device (0 = no input).
+    channels: AtomicUsize,
+    /// Bumped whenever the input device changes, so stale samples are discarded.
+    generation: AtomicU32,
+    /// Last generation the reader has switched to. Samples are only queued
+    /// once the reader has caught up, so none are read with the wrong layout.
+    reader_generation: AtomicU32,
+    /// Samples lost because the ring buffer was full.
+    overruns: AtomicU64,
+    /// Frames filled with silence because the input fell behind.
+    underruns: AtomicU64,
+}
+
+/// Input-callback side of the input stream.
+pub struct InputCapture {
+    producer: Producer<f32>,
+    shared: Arc<InputShared>,
+}
+
+impl InputCapture {
+    /// Announce a new input device with `channels` interleaved channels.
+    ///
+    /// Samples already in the ring buffer belong to the previous device and
+    /// are discarded by the reader. The dropout counters start over.
+    pub fn set_channels(&self, channels: usize) {
+        self.shared.overruns.store(0, Ordering::Relaxed);
+        self.shared.underruns.store(0, Ordering::Relaxed);
+        self.shared.channels.store(channels, Ordering::Release);
+        self.shared.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Queue interleaved samples from the input callback.
+    ///
+    /// Samples that don't fit are dropped and counted as overruns. Right after
+    /// a device change, samples are dropped until the reader has switched over.
+    pub fn push(&mut self, samples: &[f32]) {
+        let generation = self.shared.generation.load(Ordering::Acquire);
+        if self.shared.reader_generation.load(Ordering::Acquire) != generation {
+            return;
+        }
+
+        let writable = self.producer.slots().min(samples.len());
+        if let Ok(chunk) = self.producer.write_chunk_uninit(writable) {
+            chunk.fill_from_iter(samples[..writable].iter().copied());
+        }
+        let dropped = samples.len() - writable;
+        if dropped > 0 {
+            self.shared.overruns.fetch_add(dropped as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// A handle for reading the dropout counters from another thread.
+    pub fn stats(&self) -> InputStats {
+        InputStats {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+/// Dropout counters of an input stream, readable from any thread.
+#[derive(Clone)]
+pub struct InputStats {
+    shared: Arc<InputShared>,
+}
+
+impl InputStats {
+    /// Number of samples dropped because the ring buffer was full.
+    pub fn overruns(&self) -> u64 {
+        self.shared.overruns.load(Ordering::Relaxed)
+    }
+
+    /// Number of frames filled with silence because the input fell behind.
+    pub fn underruns(&self) -> u64 {
+        self.shared.underruns.load(Ordering::Relaxed)
+    }
+}
+
+/// Audio-processor side of the input stream.
+///
+/// Holds the last block read, split into one buffer per channel.
+pub struct InputReader {
+    consumer: Consumer<f32>,
+    shared: Arc<InputShared>,
+    /// Generation of the input device the buffered samples came from.
+    generation: u32,
+    /// Channel count of the current device.
+    channels: usize,
+    /// Whether the current device has delivered a full block yet. Until it
+    /// has, missing samples are expected and not counted as underruns.
+    primed: bool,
+    /// Deinterleaved samples of the last block, one buffer per channel.
+    buffers: [Vec<f32>; MAX_INPUT_CHANNELS],
+    /// Peak level per channel since the last `take_peaks`.
+    peaks: [f32; MAX_INPUT_CHANNELS],
+}
+
+impl InputReader {
+    /// Number of input channels available to the graph (at most `MAX_INPUT_CHANNELS`).
+    pub fn channels(&self) -> usize {
+        self.channels.min(MAX_INPUT_CHANNELS)
+    }
+
+    /// Read the next `frames` frames from the input.
+    ///
+    /// Call once per output block, before the graph is processed. Channels the
+    /// device doesn't have are silent.
+    pub fn read(&mut self, frames: usize) {
+        let generation = self.shared.generation.load(Ordering::Acquire);
+        if generation != self.generation {
+            self.generation = generation;
+            self.channels = self.shared.channels.load(Ordering::Acquire);
+            self.primed = false;
+            self.skip(self.consumer.slots());
+            self.shared.reader_generation.store(generation, Ordering::Release);
+        }
+
+        for buffer in &mut self.buffers {
+            buffer.clear();
+            buffer.resize(frames, 0.0);
+        }
+        if self.channels == 0 {
+            return;
+        }
+
+        let available_frames = self.consumer.slots() / self.channels;
+        self.primed |= frames > 0 && available_frames >= frames;
+        let read_frames = available_frames.min(frames);
+        if let Ok(chunk) = self.consumer.read_chunk(read_frames * self.channels) {
+            let (first, second) = chunk.as_slices();
+            for (i, sample) in first.iter().chain(second).enumerate() {
+                let channel = i % self.channels;
+                if channel < MAX_INPUT_CHANNELS {
+                    self.buffers[channel][i / self.channels] = *sample;
+                }
+            }
+            chunk.commit_all();
+        }
+        if read_frames < frames && self.primed {
+            self.shared
+                .underruns
+                .fetch_add((frames - read_frames) as u64, Ordering::Relaxed);
+        }
+
+        // Keep latency bounded when the input clock runs faster than the output
+        let buffered_frames = self.consumer.slots() / self.channels;
+        if buffered_frames > MAX_LATENCY_FRAMES {
+            self.skip((buffered_frames - MAX_LATENCY_FRAMES) * self.channels);
+        }
+
+        for (peak, buffer) in self.peaks.iter_mut().zip(&self.buffers) {
+            *peak = buffer.iter().fold(*peak, |acc, sample| acc.max(sample.abs()));
+        }
+    }
+
+    /// Samples of one channel from the last block (empty beyond `MAX_INPUT_CHANNELS`).
+    pub fn channel(&self, index: usize) -> &[f32] {
+        self.buffers.get(index).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Peak level per channel since the last call, then reset them.
+    pub fn take_peaks(&mut self) -> [f32; MAX_INPUT_CHANNELS] {
+        std::mem::replace(&mut self.peaks, [0.0; MAX_INPUT_CHANNELS])
+    }
+
+    /// Discard `samples` buffered samples.
+    fn skip(&mut self, samples: usize) {
+        if let Ok(chunk) = self.consumer.read_chunk(samples) {
+            chunk.commit_all();
+        }
+    }
+}
+
+/// Create both sides of an input stream with room for `capacity` samples.
+pub fn input_stream(capacity: usize) -> (InputCapture, InputReader) {
+    let (producer, consumer) = RingBuffer::new(capacity);
+    let shared = Arc::new(InputShared {
+        channels: AtomicUsize::new(0),
+        generation: AtomicU32::new(0),
+        reader_generation: AtomicU32::new(0),
+        overruns: AtomicU64::new(0),
+        underruns: AtomicU64::new(0),
+    });
+
+    let capture = InputCapture {
+        producer,
+        shared: Arc::clone(&shared),
+    };
+    let reader = InputReader {
+        consumer,
+        shared,
+        generation: 0,
+        channels: 0,
+        primed: false,
+        buffers: std::array::from_fn(|_| Vec::new()),
+        peaks: [0.0; MAX_INPUT_CHANNELS],
+    };
+    (capture, reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_deinterleaves_channels() {
+        let (mut capture, mut reader) = input_stream(1024);
+        capture.set_channels(2);
+        reader.read(0);
+        capture.push(&[0.1, -0.2, 0.3, -0.4, 0.5, -0.6]);
+
+        reader.read(3);
+        assert_eq!(reader.channels(), 2);
+        assert_eq!(reader.channel(0), &[0.1, 0.3, 0.5]);
+        assert_eq!(reader.channel(1), &[-0.2, -0.4, -0.6]);
+        assert_eq!(reader.channel(2), &[0.0, 0.0, 0.0]);
+        assert!(reader.channel(MAX_INPUT_CHANNELS).is_empty());
+
+        let peaks = reader.take_peaks();
+        assert!((peaks[0] - 0.5).abs() < f32::EPSILON);
+        assert!((peaks[1] - 0.6).abs() < f32::EPSILON);
+        assert_eq!(reader.take_peaks()[0], 0.0);
+    }
+
+    #[test]
+    fn test_input_underrun_fills_silence() {
+        let (mut capture, mut reader) = input_stream(1024);
+        capture.set_channels(1);
+        reader.read(0);
+
+        // Missing samples before the first full block are expected
+        capture.push(&[1.0, 1.0]);
+        reader.read(4);
+        assert_eq!(reader.channel(0), &[1.0, 1.0, 0.0, 0.0]);
+        assert_eq!(capture.stats().underruns(), 0);
+
+        capture.push(&[1.0; 6]);
+        reader.read(4);
+        reader.read(4);
+        assert_eq!(reader.channel(0), &[1.0, 1.0, 0.0, 0.0]);
+        assert_eq!(capture.stats().underruns(), 2);
+    }
+
+    #[test]
+    fn test_input_overrun_and_latency_limit() {
+        let (mut capture, mut reader) = input_stream(4096);
+        capture.set_channels(1);
+        reader.read(0);
+        capture.push(&vec![0.5; 5000]);
+        assert_eq!(capture.stats().overruns(), 5000 - 4096);
+
+        reader.read(64);
+        assert_eq!(reader.consumer.slots(), MAX_LATENCY_FRAMES);
+    }
+
+    #[test]
+    fn test_input_device_change_discards_stale_samples() {
+        let (mut capture, mut reader) = input_stream(1024);
+        capture.set_channels(2);
+        reader.read(0);
+        capture.push(&[1.0; 8]);
+
+        // Samples of the old device and those before the reader switched are dropped
+        capture.set_channels(1);
+        capture.push(&[0.5; 4]);
+        reader.read(4);
+        assert_eq!(reader.channels(), 1);
+        assert_eq!(reader.channel(0), &[0.0; 4]);
+
+        capture.push(&[0.25; 4]);
+        reader.read(4);
+        assert_eq!(reader.channel(0), &[0.25; 4]);
+    }
+}
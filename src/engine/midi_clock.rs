@@ -0,0 +1,295 @@
+//! MIDI Clock Follower
+//!
+//! Follows the clock and transport of an external sequencer, drum machine or
+//! DAW. Incoming clock ticks are turned into a smoothed tempo estimate, and
+//! Start, Continue, Stop and Song Position Pointer messages into a transport
+//! state that Clock modules set to the MIDI source can follow.
+//!
+//! Runs on the UI thread, which receives the MIDI input; the result is sent
+//! to the audio engine as a `MidiTransport`.
+
+use super::midi_engine::MidiEvent;
+
+/// MIDI clock ticks per quarter note.
+pub const MIDI_CLOCK_PPQN: u32 = 24;
+
+/// Transport of an external MIDI clock, as sent to the audio engine.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MidiTransport {
+    /// Whether the external sequencer is playing.
+    pub playing: bool,
+    /// Tempo estimated from the incoming clock (None = no clock received yet).
+    pub tempo_bpm: Option<f32>,
+    /// Position to jump to, in beats from the start of the song
+    /// (None = carry on from the current position).
+    pub locate_beats: Option<f64>,
+}
+
+/// Turns incoming MIDI clock and transport messages into a `MidiTransport`.
+///
+/// The tempo is an exponential average of the tick intervals. An interval
+/// far from the average restarts the estimate, so a tempo jump is followed
+/// within a couple of ticks rather than glided to. Once ticks arrive from an
+/// input port, ticks from other ports are ignored until that clock goes quiet.
+#[derive(Debug, Clone, Default)]
+pub struct MidiClockFollower {
+    /// Whether a Start or Continue was received without a Stop since.
+    playing: bool,
+    /// Input port the clock is taken from (None = no clock yet).
+    source_port: Option<u8>,
+    /// Timestamp of the last tick in microseconds.
+    last_tick_us: Option<u64>,
+    /// Smoothed interval between ticks in microseconds.
+    tick_interval_us: Option<f64>,
+    /// Song position received while stopped, applied by the next Continue.
+    song_position: Option<u16>,
+    /// Position the engine should jump to with the next update.
+    locate_beats: Option<f64>,
+    /// Transport last returned by `take_update`.
+    reported: Option<MidiTransport>,
+}
+
+impl MidiClockFollower {
+    /// Weight of each new tick interval in the tempo average.
+    const SMOOTHING: f64 = 0.1;
+
+    /// An interval differing from the average by more than this ratio
+    /// restarts the estimate.
+    const JUMP_RATIO: f64 = 0.25;
+
+    /// Gap after which the clock is considered stopped (a tick at 10 BPM).
+    const MAX_TICK_GAP_US: u64 = 250_000;
+
+    /// Smallest tempo change worth sending to the engine.
+    const TEMPO_RESOLUTION: f32 = 0.01;
+
+    /// Creates a follower that hasn't received any clock yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the external sequencer is playing.
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Tempo estimated from the incoming clock, if any clock was received.
+    pub fn tempo_bpm(&self) -> Option<f32> {
+        self.tick_interval_us
+            .map(|interval| (60_000_000.0 / (interval * MIDI_CLOCK_PPQN as f64)) as f32)
+    }
+
+    /// Handle a MIDI message received on `port` at `timestamp_us`.
+    /// Messages other than clock and transport are ignored.
+    pub fn handle(&mut self, event: &MidiEvent, timestamp_us: u64, port: u8) {
+        match event {
+            MidiEvent::Clock => self.tick(timestamp_us, port),
+            MidiEvent::Start => {
+                self.playing = true;
+                self.song_position = None;
+                self.locate_beats = Some(0.0);
+            }
+            MidiEvent::Continue => {
+                self.playing = true;
+                if let Some(position) = self.song_position.take() {
+                    self.locate_beats = Some(position as f64 / 4.0);
+                }
+            }
+            MidiEvent::Stop => {
+                self.playing = false;
+            }
+            // Only meaningful while stopped; it takes effect on Continue
+            MidiEvent::SongPosition { position } if !self.playing => {
+                self.song_position = Some(*position);
+            }
+            _ => {}
+        }
+    }
+
+    /// Feed one clock tick into the tempo estimate.
+    fn tick(&mut self, timestamp_us: u64, port: u8) {
+        // A clock that went quiet gives way to any other
+        let quiet = self
+            .last_tick_us
+            .is_none_or(|last| timestamp_us.saturating_sub(last) > Self::MAX_TICK_GAP_US);
+        if quiet {
+            self.source_port = Some(port);
+            self.last_tick_us = Some(timestamp_us);
+            return;
+        }
+        if self.source_port != Some(port) {
+            return;
+        }
+
+        let Some(last) = self.last_tick_us.replace(timestamp_us) else {
+            return;
+        };
+        let interval = timestamp_us.saturating_sub(last) as f64;
+        if interval <= 0.0 {
+            return;
+        }
+
+        self.tick_interval_us = Some(match self.tick_interval_us {
+            Some(average) if (interval - average).abs() <= average * Self::JUMP_RATIO => {
+                average + (interval - average) * Self::SMOOTHING
+            }
+            _ => interval,
+        });
+    }
+
+    /// The transport to send to the engine, if it changed since the last call.
+    pub fn take_update(&mut self) -> Option<MidiTransport> {
+        let transport = MidiTransport {
+            playing: self.playing,
+            tempo_bpm: self.tempo_bpm(),
+            locate_beats: self.locate_beats.take(),
+        };
+
+        let changed = match self.reported {
+            None => true,
+            Some(reported) => {
+                transport.playing != reported.playing
+                    || transport.locate_beats.is_some()
+                    || match (transport.tempo_bpm, reported.tempo_bpm) {
+                        (Some(tempo), Some(previous)) => (tempo - previous).abs() >= Self::TEMPO_RESOLUTION,
+                        (tempo, previous) => tempo.is_some() != previous.is_some(),
+                    }
+            }
+        };
+
+        if changed {
+            self.reported = Some(transport);
+            Some(transport)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tick interval in microseconds at the given tempo.
+    fn interval_us(bpm: f64) -> u64 {
+        (60_000_000.0 / (bpm * MIDI_CLOCK_PPQN as f64)) as u64
+    }
+
+    /// Send `count` ticks from `port` starting at `start_us`, returning the
+    /// time of the next tick.
+    fn send_ticks(follower: &mut MidiClockFollower, start_us: u64, bpm: f64, count: usize, port: u8) -> u64 {
+        let mut time = start_us;
+        for _ in 0..count {
+            follower.handle(&MidiEvent::Clock, time, port);
+            time += interval_us(bpm);
+        }
+        time
+    }
+
+    #[test]
+    fn test_tempo_estimate() {
+        let mut follower = MidiClockFollower::new();
+        assert_eq!(follower.tempo_bpm(), None);
+
+        send_ticks(&mut follower, 1_000, 120.0, 48, 1);
+        let tempo = follower.tempo_bpm().unwrap();
+        assert!((tempo - 120.0).abs() < 0.1, "got {}", tempo);
+    }
+
+    #[test]
+    fn test_tempo_smooths_jitter() {
+        let mut follower = MidiClockFollower::new();
+        let interval = interval_us(120.0);
+        let mut time = 0;
+        for i in 0..96 {
+            // +-1 ms of jitter on every other tick
+            let jitter = if i % 2 == 0 { 1_000 } else { 0 };
+            follower.handle(&MidiEvent::Clock, time + jitter, 1);
+            time += interval;
+        }
+        let tempo = follower.tempo_bpm().unwrap();
+        assert!((tempo - 120.0).abs() < 3.0, "got {}", tempo);
+    }
+
+    #[test]
+    fn test_tempo_follows_jump() {
+        let mut follower = MidiClockFollower::new();
+        let time = send_ticks(&mut follower, 0, 120.0, 48, 1);
+        send_ticks(&mut follower, time, 90.0, 3, 1);
+        let tempo = follower.tempo_bpm().unwrap();
+        assert!((tempo - 90.0).abs() < 0.5, "got {}", tempo);
+    }
+
+    #[test]
+    fn test_ignores_second_clock_source() {
+        let mut follower = MidiClockFollower::new();
+        let mut time = 0;
+        for _ in 0..48 {
+            follower.handle(&MidiEvent::Clock, time, 1);
+            follower.handle(&MidiEvent::Clock, time + 3_000, 2);
+            time += interval_us(100.0);
+        }
+        let tempo = follower.tempo_bpm().unwrap();
+        assert!((tempo - 100.0).abs() < 0.1, "got {}", tempo);
+
+        // Port 2 takes over once port 1 goes quiet
+        let time = time + 1_000_000;
+        send_ticks(&mut follower, time, 140.0, 24, 2);
+        let tempo = follower.tempo_bpm().unwrap();
+        assert!((tempo - 140.0).abs() < 0.1, "got {}", tempo);
+    }
+
+    #[test]
+    fn test_transport() {
+        let mut follower = MidiClockFollower::new();
+        let initial = follower.take_update().unwrap();
+        assert!(!initial.playing);
+        assert_eq!(follower.take_update(), None);
+
+        // Start plays from the beginning
+        follower.handle(&MidiEvent::Start, 0, 1);
+        let update = follower.take_update().unwrap();
+        assert!(update.playing);
+        assert_eq!(update.locate_beats, Some(0.0));
+
+        follower.handle(&MidiEvent::Stop, 0, 1);
+        assert_eq!(
+            follower.take_update(),
+            Some(MidiTransport { playing: false, tempo_bpm: None, locate_beats: None })
+        );
+
+        // Continue without a song position carries on where it stopped
+        follower.handle(&MidiEvent::Continue, 0, 1);
+        assert_eq!(follower.take_update().unwrap().locate_beats, None);
+    }
+
+    #[test]
+    fn test_song_position() {
+        let mut follower = MidiClockFollower::new();
+        // 16 sixteenths = 4 beats, applied when playback continues
+        follower.handle(&MidiEvent::SongPosition { position: 16 }, 0, 1);
+        follower.handle(&MidiEvent::Continue, 0, 1);
+        let update = follower.take_update().unwrap();
+        assert!(update.playing);
+        assert_eq!(update.locate_beats, Some(4.0));
+
+        // Start always goes back to the beginning
+        follower.handle(&MidiEvent::Stop, 0, 1);
+        follower.handle(&MidiEvent::SongPosition { position: 32 }, 0, 1);
+        follower.handle(&MidiEvent::Start, 0, 1);
+        assert_eq!(follower.take_update().unwrap().locate_beats, Some(0.0));
+    }
+
+    #[test]
+    fn test_update_only_on_tempo_change() {
+        let mut follower = MidiClockFollower::new();
+        follower.take_update();
+
+        let time = send_ticks(&mut follower, 0, 120.0, 24, 1);
+        assert!(follower.take_update().unwrap().tempo_bpm.is_some());
+
+        // A steady clock doesn't generate updates
+        send_ticks(&mut follower, time, 120.0, 24, 1);
+        assert_eq!(follower.take_update(), None);
+    }
+}
@@ -1,10 +1,14 @@
 //! MIDI Engine
 //!
 //! Handles MIDI input from hardware controllers and virtual MIDI ports.
+//! Several devices can be connected at once; their events share one queue
+//! and are tagged with the port number of the input they came from. On
+//! Linux and macOS the engine can also publish a virtual input port that
+//! other applications send MIDI to.
 //! Uses midir for cross-platform MIDI access and rtrb for lock-free
 //! communication with the audio thread.
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
@@ -12,9 +16,14 @@ use std::time::Duration;
 use midir::{MidiInput, MidiInputConnection, MidiInputPort};
 use rtrb::{Consumer, Producer, RingBuffer};
 
+use super::parameter_number::ParameterNumberParser;
+
 /// Default buffer size for MIDI events.
 pub const DEFAULT_MIDI_BUFFER_SIZE: usize = 512;
 
+/// Default name of the virtual MIDI input port.
+pub const DEFAULT_VIRTUAL_PORT_NAME: &str = "Modular Synth In";
+
 /// Information about a MIDI input device.
 #[derive(Debug, Clone)]
 pub struct MidiDeviceInfo {
@@ -25,7 +34,7 @@ pub struct MidiDeviceInfo {
 }
 
 /// MIDI event types received from hardware.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MidiEvent {
     /// Note On event.
     NoteOn {
@@ -84,6 +93,31 @@ pub enum MidiEvent {
         /// Program number (0-127).
         program: u8,
     },
+    /// Timing Clock, sent 24 times per quarter note.
+    Clock,
+    /// Start the sequence from the beginning.
+    Start,
+    /// Continue the sequence from the current song position.
+    Continue,
+    /// Stop the sequence.
+    Stop,
+    /// Song Position Pointer.
+    SongPosition {
+        /// Position in sixteenth notes (6 clocks) from the start (0-16383).
+        position: u16,
+    },
+    /// A Registered (RPN) or Non-Registered (NRPN) Parameter Number value,
+    /// assembled from its Control Changes by a `ParameterNumberParser`.
+    ParameterNumber {
+        /// MIDI channel (0-15).
+        channel: u8,
+        /// Whether this is an RPN rather than an NRPN.
+        registered: bool,
+        /// Parameter number (0-16383).
+        number: u16,
+        /// 14-bit value (0-16383).
+        value: u16,
+    },
 }
 
 impl MidiEvent {
@@ -98,6 +132,25 @@ impl MidiEvent {
         let channel = status & 0x0F;
         let msg_type = status & 0xF0;
 
+        // System messages have no channel
+        match status {
+            0xF8 => return Some(MidiEvent::Clock),
+            0xFA => return Some(MidiEvent::Start),
+            0xFB => return Some(MidiEvent::Continue),
+            0xFC => return Some(MidiEvent::Stop),
+            0xF2 => {
+                // Song Position Pointer: 14-bit position, LSB first
+                return if data.len() >= 3 {
+                    let position = ((data[2] as u16 & 0x7F) << 7) | (data[1] as u16 & 0x7F);
+                    Some(MidiEvent::SongPosition { position })
+                } else {
+                    None
+                };
+            }
+            0xF0..=0xFF => return None,
+            _ => {}
+        }
+
         match msg_type {
             0x90 => {
                 // Note On (velocity 0 = Note Off)
@@ -195,7 +248,47 @@ impl MidiEvent {
         }
     }
 
+    /// Encode the event as raw MIDI bytes.
+    ///
+    /// Returns the bytes and how many of them are used. Data bytes are
+    /// masked to 7 bits and the channel to 4 bits. Parameter Number values
+    /// travel as their Control Changes and have no bytes of their own.
+    pub fn to_bytes(&self) -> ([u8; 3], usize) {
+        match *self {
+            MidiEvent::NoteOn { channel, note, velocity } => {
+                ([0x90 | (channel & 0x0F), note & 0x7F, velocity & 0x7F], 3)
+            }
+            MidiEvent::NoteOff { channel, note, velocity } => {
+                ([0x80 | (channel & 0x0F), note & 0x7F, velocity & 0x7F], 3)
+            }
+            MidiEvent::ControlChange { channel, controller, value } => {
+                ([0xB0 | (channel & 0x0F), controller & 0x7F, value & 0x7F], 3)
+            }
+            MidiEvent::PitchBend { channel, value } => {
+                let bend = (value.clamp(-8192, 8191) + 8192) as u16;
+                ([0xE0 | (channel & 0x0F), (bend & 0x7F) as u8, (bend >> 7) as u8], 3)
+            }
+            MidiEvent::ChannelPressure { channel, pressure } => ([0xD0 | (channel & 0x0F), pressure & 0x7F, 0], 2),
+            MidiEvent::PolyPressure { channel, note, pressure } => {
+                ([0xA0 | (channel & 0x0F), note & 0x7F, pressure & 0x7F], 3)
+            }
+            MidiEvent::ProgramChange { channel, program } => ([0xC0 | (channel & 0x0F), program & 0x7F, 0], 2),
+            MidiEvent::Clock => ([0xF8, 0, 0], 1),
+            MidiEvent::Start => ([0xFA, 0, 0], 1),
+            MidiEvent::Continue => ([0xFB, 0, 0], 1),
+            MidiEvent::Stop => ([0xFC, 0, 0], 1),
+            MidiEvent::SongPosition { position } => {
+                let position = position & 0x3FFF;
+                ([0xF2, (position & 0x7F) as u8, (position >> 7) as u8], 3)
+            }
+            MidiEvent::ParameterNumber { .. } => ([0, 0, 0], 0),
+        }
+    }
+
     /// Get the MIDI channel for this event.
+    ///
+    /// System messages (clock, transport and song position) have no
+    /// channel and report channel 0.
     pub fn channel(&self) -> u8 {
         match self {
             MidiEvent::NoteOn { channel, .. } => *channel,
@@ -205,6 +298,12 @@ impl MidiEvent {
             MidiEvent::ChannelPressure { channel, .. } => *channel,
             MidiEvent::PolyPressure { channel, .. } => *channel,
             MidiEvent::ProgramChange { channel, .. } => *channel,
+            MidiEvent::ParameterNumber { channel, .. } => *channel,
+            MidiEvent::Clock
+            | MidiEvent::Start
+            | MidiEvent::Continue
+            | MidiEvent::Stop
+            | MidiEvent::SongPosition { .. } => 0,
         }
     }
 }
@@ -216,6 +315,32 @@ pub struct TimestampedMidiEvent {
     pub event: MidiEvent,
     /// Timestamp in microseconds since connection started.
     pub timestamp_us: u64,
+    /// Input port the event arrived on (1 = the first MIDI input).
+    pub port: u8,
+}
+
+/// A MIDI input chosen by the user, with its port number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MidiInputInfo {
+    /// Device name.
+    pub name: String,
+    /// Port number events from this input are tagged with (1-based).
+    /// It changes as other inputs are removed, so anything saved refers to
+    /// the input by name.
+    pub port: u8,
+    /// Whether the device is currently connected; false while it is unplugged.
+    pub connected: bool,
+    /// Whether this is the virtual port published by the synth.
+    pub is_virtual: bool,
+}
+
+/// A change in the connection state of a MIDI input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MidiConnectionChange {
+    /// The device was unplugged.
+    Lost(String),
+    /// The device was plugged back in and reconnected.
+    Restored(String),
 }
 
 /// Error type for MIDI operations.
@@ -229,6 +354,8 @@ pub enum MidiError {
     DeviceNotFound,
     /// No MIDI devices available.
     NoDevices,
+    /// Virtual ports are not supported on this platform.
+    VirtualPortUnsupported,
 }
 
 impl std::fmt::Display for MidiError {
@@ -238,6 +365,9 @@ impl std::fmt::Display for MidiError {
             MidiError::ConnectionError(s) => write!(f, "MIDI connection error: {}", s),
             MidiError::DeviceNotFound => write!(f, "MIDI device not found"),
             MidiError::NoDevices => write!(f, "No MIDI devices available"),
+            MidiError::VirtualPortUnsupported => {
+                write!(f, "Virtual MIDI ports are not supported on this platform")
+            }
         }
     }
 }
@@ -252,16 +382,39 @@ struct MidiState {
     port_names: Vec<String>,
 }
 
+/// A MIDI input chosen by the user.
+///
+/// The input stays in the list while its device is unplugged, so it can be
+/// reconnected under the same port number when the device comes back.
+struct MidiInputSlot {
+    /// Device name.
+    name: String,
+    /// Port number, shared with the connection callback so it follows
+    /// renumbering when an earlier input is removed.
+    port: Arc<AtomicU8>,
+    /// Active connection (None while the device is unplugged).
+    connection: Option<MidiInputConnection<()>>,
+    /// Whether this is the virtual port, which is never unplugged.
+    is_virtual: bool,
+}
+
+/// Wakes the UI when MIDI input arrives (see `MidiEngine::set_waker`).
+type MidiWaker = Box<dyn Fn() + Send>;
+
 /// MIDI engine for receiving MIDI input.
+///
+/// Any number of devices can be connected at once. All of them feed the same
+/// event queue, and each event is tagged with the port number of the input
+/// it arrived on.
 pub struct MidiEngine {
     /// Cached device list.
     devices: Vec<MidiDeviceInfo>,
-    /// Currently selected device index (None = no device).
-    selected_device: Option<usize>,
-    /// Active MIDI connection.
-    connection: Option<MidiInputConnection<()>>,
-    /// Producer for sending events to consumers.
-    event_producer: Option<Producer<TimestampedMidiEvent>>,
+    /// Chosen inputs, in port order.
+    inputs: Vec<MidiInputSlot>,
+    /// Producer shared by the callbacks of all connections.
+    event_producer: Arc<Mutex<Producer<TimestampedMidiEvent>>>,
+    /// Called by the callbacks after queueing events (see `set_waker`).
+    waker: Arc<Mutex<Option<MidiWaker>>>,
     /// Shared state for device enumeration.
     state: Arc<Mutex<MidiState>>,
     /// Flag to signal device scan thread to stop.
@@ -332,9 +485,9 @@ impl MidiEngine {
 
         let engine = Self {
             devices,
-            selected_device: None,
-            connection: None,
-            event_producer: Some(producer),
+            inputs: Vec::new(),
+            event_producer: Arc::new(Mutex::new(producer)),
+            waker: Arc::new(Mutex::new(None)),
             state,
             scan_running,
             scan_thread: Some(scan_thread),
@@ -343,6 +496,16 @@ impl MidiEngine {
         Ok((engine, consumer))
     }
 
+    /// Call `waker` whenever events are queued, so a UI that only polls the
+    /// queue when it redraws can handle them straight away.
+    ///
+    /// MIDI clock ticks don't wake it; they arrive dozens of times a second.
+    pub fn set_waker(&mut self, waker: impl Fn() + Send + 'static) {
+        if let Ok(mut slot) = self.waker.lock() {
+            *slot = Some(Box::new(waker));
+        }
+    }
+
     /// Enumerate available MIDI input devices.
     /// This returns a fresh list reflecting any hot-plugged devices.
     pub fn enumerate_devices(&mut self) -> Vec<MidiDeviceInfo> {
@@ -365,95 +528,272 @@ impl MidiEngine {
         &self.devices
     }
 
-    /// Get the currently selected device index.
-    pub fn selected_device(&self) -> Option<usize> {
-        self.selected_device
+    /// The chosen inputs, in port order.
+    pub fn inputs(&self) -> Vec<MidiInputInfo> {
+        self.inputs
+            .iter()
+            .map(|slot| MidiInputInfo {
+                name: slot.name.clone(),
+                port: slot.port.load(Ordering::Relaxed),
+                connected: slot.connection.is_some(),
+                is_virtual: slot.is_virtual,
+            })
+            .collect()
     }
 
-    /// Connect to a MIDI device by index.
-    pub fn connect(&mut self, device_index: usize) -> Result<(), MidiError> {
-        // Disconnect existing connection
-        self.disconnect();
+    /// Name of the input events tagged with `port` came from.
+    pub fn port_name(&self, port: u8) -> Option<&str> {
+        self.inputs
+            .get((port as usize).checked_sub(1)?)
+            .map(|slot| slot.name.as_str())
+    }
 
-        // Get the port from our state
-        let port = {
+    /// Connect to a MIDI device by index, adding it to the inputs.
+    ///
+    /// Returns the port number its events are tagged with. Connecting a
+    /// device that is already an input keeps its port.
+    pub fn connect(&mut self, device_index: usize) -> Result<u8, MidiError> {
+        let name = {
             let state = self.state.lock().map_err(|_| {
                 MidiError::ConnectionError("Failed to lock state".to_string())
             })?;
+            state.port_names.get(device_index).cloned().ok_or(MidiError::DeviceNotFound)?
+        };
+
+        let slot = match self.inputs.iter().position(|slot| slot.name == name && !slot.is_virtual) {
+            Some(slot) => slot,
+            None => {
+                let port = port_number(self.inputs.len());
+                let slot = self.open(&name, Arc::new(AtomicU8::new(port)))?;
+                self.inputs.push(slot);
+                return Ok(port);
+            }
+        };
+
+        if self.inputs[slot].connection.is_none() {
+            let port = Arc::clone(&self.inputs[slot].port);
+            self.inputs[slot] = self.open(&name, port)?;
+        }
+        Ok(self.inputs[slot].port.load(Ordering::Relaxed))
+    }
+
+    /// Add an input by device name, whether or not the device is present.
+    ///
+    /// A missing device is connected as soon as it appears (see
+    /// `check_connections`). Returns whether it is connected now.
+    pub fn add_input(&mut self, name: &str) -> bool {
+        if let Some(slot) = self.inputs.iter().find(|slot| slot.name == name && !slot.is_virtual) {
+            return slot.connection.is_some();
+        }
+
+        let port = Arc::new(AtomicU8::new(port_number(self.inputs.len())));
+        let slot = self.open(name, Arc::clone(&port)).unwrap_or(MidiInputSlot {
+            name: name.to_string(),
+            port,
+            connection: None,
+            is_virtual: false,
+        });
+        let connected = slot.connection.is_some();
+        self.inputs.push(slot);
+        connected
+    }
+
+    /// Disconnect a device and remove it from the inputs.
+    ///
+    /// The inputs after it move down one port number.
+    pub fn disconnect(&mut self, name: &str) {
+        if let Some(index) = self.inputs.iter().position(|slot| slot.name == name && !slot.is_virtual) {
+            self.remove_input(index);
+            eprintln!("MIDI disconnected: {}", name);
+        }
+    }
+
+    /// Publish a virtual MIDI input port that other applications can send to.
+    ///
+    /// The port is added to the inputs like a device and gets a port number,
+    /// which is returned. There is at most one virtual port; opening another
+    /// one replaces it. Only supported on Linux and macOS.
+    pub fn open_virtual_port(&mut self, name: &str) -> Result<u8, MidiError> {
+        if let Some(slot) = self.inputs.iter().find(|slot| slot.is_virtual && slot.name == name) {
+            return Ok(slot.port.load(Ordering::Relaxed));
+        }
+        self.close_virtual_port();
+
+        let port = port_number(self.inputs.len());
+        let slot = self.create_virtual(name, Arc::new(AtomicU8::new(port)))?;
+        self.inputs.push(slot);
+        eprintln!("MIDI virtual port {} opened: {}", port, name);
+        Ok(port)
+    }
+
+    /// Remove the virtual MIDI input port, if it is open.
+    pub fn close_virtual_port(&mut self) {
+        if let Some(index) = self.inputs.iter().position(|slot| slot.is_virtual) {
+            self.remove_input(index);
+        }
+    }
+
+    /// Name of the virtual MIDI input port, if it is open.
+    pub fn virtual_port(&self) -> Option<&str> {
+        self.inputs.iter().find(|slot| slot.is_virtual).map(|slot| slot.name.as_str())
+    }
+
+    /// Disconnect all devices.
+    pub fn disconnect_all(&mut self) {
+        for slot in self.inputs.drain(..) {
+            if let Some(connection) = slot.connection {
+                connection.close();
+            }
+        }
+    }
+
+    /// Check if at least one device is connected.
+    pub fn is_connected(&self) -> bool {
+        self.inputs.iter().any(|slot| slot.connection.is_some())
+    }
 
-            if device_index >= state.ports.len() {
-                return Err(MidiError::DeviceNotFound);
+    /// Follow devices being unplugged and plugged back in.
+    ///
+    /// Drops the connections of inputs whose device disappeared from the last
+    /// scan and reconnects inputs whose device is back. Call this regularly;
+    /// it only looks at the result of the background scan, so it is cheap.
+    pub fn check_connections(&mut self) -> Vec<MidiConnectionChange> {
+        let available = match self.state.lock() {
+            Ok(state) => state.port_names.clone(),
+            Err(_) => return Vec::new(),
+        };
+
+        let status: Vec<(&str, bool)> = self.inputs
+            .iter()
+            .map(|slot| (slot.name.as_str(), slot.connection.is_some()))
+            .collect();
+        // The virtual port is not in the device list, but never goes away
+        let changes: Vec<_> = connection_changes(&status, &available)
+            .into_iter()
+            .filter(|(index, _)| !self.inputs[*index].is_virtual)
+            .collect();
+
+        let mut reported = Vec::new();
+        for (index, change) in changes {
+            match change {
+                MidiConnectionChange::Lost(name) => {
+                    if let Some(connection) = self.inputs[index].connection.take() {
+                        connection.close();
+                    }
+                    eprintln!("MIDI device lost: {}", name);
+                    reported.push(MidiConnectionChange::Lost(name));
+                }
+                MidiConnectionChange::Restored(name) => {
+                    let port = Arc::clone(&self.inputs[index].port);
+                    if let Ok(slot) = self.open(&name, port) {
+                        self.inputs[index] = slot;
+                        reported.push(MidiConnectionChange::Restored(name));
+                    }
+                }
             }
+        }
+        reported
+    }
 
-            state.ports[device_index].clone()
+    /// Open a connection to the device called `name`, tagging its events
+    /// with the port number in `port`.
+    fn open(&self, name: &str, port: Arc<AtomicU8>) -> Result<MidiInputSlot, MidiError> {
+        // Get the port from our state
+        let device_port = {
+            let state = self.state.lock().map_err(|_| {
+                MidiError::ConnectionError("Failed to lock state".to_string())
+            })?;
+
+            let index = state.port_names
+                .iter()
+                .position(|port_name| port_name == name)
+                .ok_or(MidiError::DeviceNotFound)?;
+            state.ports[index].clone()
         };
 
         // Create a new MIDI input for this connection
         let midi_in = MidiInput::new("Modular Synth Input")
             .map_err(|e| MidiError::InitError(e.to_string()))?;
 
-        // Take the producer for use in the callback
-        let producer = self.event_producer.take().ok_or_else(|| {
-            MidiError::ConnectionError("Event producer already in use".to_string())
-        })?;
-
-        // Wrap producer in Arc<Mutex> for the callback
-        let producer = Arc::new(Mutex::new(producer));
-
         // Connect with callback
         let connection = midi_in
-            .connect(
-                &port,
-                "Modular Synth Input",
-                {
-                    let producer = Arc::clone(&producer);
-                    move |timestamp_us, data, _| {
-                        if let Some(event) = MidiEvent::from_bytes(data) {
-                            let timestamped = TimestampedMidiEvent {
-                                event,
-                                timestamp_us,
-                            };
-                            if let Ok(mut prod) = producer.lock() {
-                                // Use lossy push - drop events if buffer is full
-                                let _ = prod.push(timestamped);
-                            }
-                            // Log MIDI events to console for debugging
-                            eprintln!("MIDI: {:?}", event);
-                        }
-                    }
-                },
-                (),
-            )
+            .connect(&device_port, "Modular Synth Input", self.event_callback(&port), ())
             .map_err(|e| MidiError::ConnectionError(e.to_string()))?;
 
-        // Store connection and put producer back (wrapped in Arc)
-        self.connection = Some(connection);
-        // We can't get the producer back out of the callback, so we leave it as None
-        // This is fine because we only support one connection at a time
-        self.selected_device = Some(device_index);
+        eprintln!("MIDI connected to port {}: {}", port.load(Ordering::Relaxed), name);
 
-        eprintln!(
-            "MIDI connected to device {}: {}",
-            device_index,
-            self.devices.get(device_index).map(|d| d.name.as_str()).unwrap_or("Unknown")
-        );
+        Ok(MidiInputSlot {
+            name: name.to_string(),
+            port,
+            connection: Some(connection),
+            is_virtual: false,
+        })
+    }
+
+    /// Create a virtual input port called `name`.
+    #[cfg(unix)]
+    fn create_virtual(&self, name: &str, port: Arc<AtomicU8>) -> Result<MidiInputSlot, MidiError> {
+        use midir::os::unix::VirtualInput;
 
-        Ok(())
+        let midi_in = MidiInput::new("Modular Synth")
+            .map_err(|e| MidiError::InitError(e.to_string()))?;
+        let connection = midi_in
+            .create_virtual(name, self.event_callback(&port), ())
+            .map_err(|e| MidiError::ConnectionError(e.to_string()))?;
+
+        Ok(MidiInputSlot {
+            name: name.to_string(),
+            port,
+            connection: Some(connection),
+            is_virtual: true,
+        })
     }
 
-    /// Disconnect from the current MIDI device.
-    pub fn disconnect(&mut self) {
-        if let Some(connection) = self.connection.take() {
-            // Close the connection - this drops it
-            connection.close();
-            self.selected_device = None;
-            eprintln!("MIDI disconnected");
+    /// Virtual ports need ALSA or CoreMIDI.
+    #[cfg(not(unix))]
+    fn create_virtual(&self, _name: &str, _port: Arc<AtomicU8>) -> Result<MidiInputSlot, MidiError> {
+        Err(MidiError::VirtualPortUnsupported)
+    }
+
+    /// The callback of a connection, queueing its events tagged with the
+    /// port number in `port`.
+    fn event_callback(&self, port: &Arc<AtomicU8>) -> impl FnMut(u64, &[u8], &mut ()) + Send + 'static {
+        let producer = Arc::clone(&self.event_producer);
+        let waker = Arc::clone(&self.waker);
+        let port = Arc::clone(port);
+        let mut parameter_numbers = ParameterNumberParser::new();
+        move |timestamp_us, data, _| {
+            if let Some(event) = MidiEvent::from_bytes(data) {
+                let port = port.load(Ordering::Relaxed);
+                // NRPN and RPN values follow the Control Changes they are made of
+                let assembled = parameter_numbers.handle(&event);
+                if let Ok(mut prod) = producer.lock() {
+                    // Use lossy push - drop events if buffer is full
+                    for event in std::iter::once(event).chain(assembled) {
+                        let _ = prod.push(TimestampedMidiEvent { event, timestamp_us, port });
+                    }
+                }
+                // Log MIDI events to console for debugging and wake the UI,
+                // except for the clock, which arrives dozens of times a second
+                if !matches!(event, MidiEvent::Clock) {
+                    eprintln!("MIDI: {:?}", event);
+                    if let Some(wake) = waker.lock().ok().as_deref().and_then(Option::as_ref) {
+                        wake();
+                    }
+                }
+            }
         }
     }
 
-    /// Check if currently connected to a device.
-    pub fn is_connected(&self) -> bool {
-        self.connection.is_some()
+    /// Remove the input at `index`, moving the inputs after it down one port number.
+    fn remove_input(&mut self, index: usize) {
+        let slot = self.inputs.remove(index);
+        if let Some(connection) = slot.connection {
+            connection.close();
+        }
+        for (i, slot) in self.inputs.iter().enumerate() {
+            slot.port.store(port_number(i), Ordering::Relaxed);
+        }
     }
 }
 
@@ -462,8 +802,8 @@ impl Drop for MidiEngine {
         // Stop the scan thread
         self.scan_running.store(false, Ordering::Relaxed);
 
-        // Disconnect if connected
-        self.disconnect();
+        // Disconnect all devices
+        self.disconnect_all();
 
         // Wait for scan thread to finish
         if let Some(thread) = self.scan_thread.take() {
@@ -472,6 +812,31 @@ impl Drop for MidiEngine {
     }
 }
 
+/// Port number of the input at `index` in the input list.
+fn port_number(index: usize) -> u8 {
+    (index + 1).min(u8::MAX as usize) as u8
+}
+
+/// Work out which inputs were unplugged or plugged back in.
+///
+/// `inputs` holds the name and connection state of each input, and
+/// `available` the names of the devices found by the last scan. Returns the
+/// index of each input whose state should change, with the change.
+pub(super) fn connection_changes(inputs: &[(&str, bool)], available: &[String]) -> Vec<(usize, MidiConnectionChange)> {
+    inputs
+        .iter()
+        .enumerate()
+        .filter_map(|(index, &(name, connected))| {
+            let present = available.iter().any(|device| device == name);
+            match (connected, present) {
+                (true, false) => Some((index, MidiConnectionChange::Lost(name.to_string()))),
+                (false, true) => Some((index, MidiConnectionChange::Restored(name.to_string()))),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -596,6 +961,51 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_midi_event_from_bytes_realtime() {
+        assert!(matches!(MidiEvent::from_bytes(&[0xF8]), Some(MidiEvent::Clock)));
+        assert!(matches!(MidiEvent::from_bytes(&[0xFA]), Some(MidiEvent::Start)));
+        assert!(matches!(MidiEvent::from_bytes(&[0xFB]), Some(MidiEvent::Continue)));
+        assert!(matches!(MidiEvent::from_bytes(&[0xFC]), Some(MidiEvent::Stop)));
+        assert_eq!(MidiEvent::from_bytes(&[0xF8]).unwrap().channel(), 0);
+
+        // Active sensing and system exclusive are ignored
+        assert!(MidiEvent::from_bytes(&[0xFE]).is_none());
+        assert!(MidiEvent::from_bytes(&[0xF0, 0x7E, 0xF7]).is_none());
+    }
+
+    #[test]
+    fn test_midi_event_from_bytes_song_position() {
+        // LSB 0x10, MSB 0x02: 2 * 128 + 16 = 272 sixteenths
+        let event = MidiEvent::from_bytes(&[0xF2, 0x10, 0x02]);
+        if let Some(MidiEvent::SongPosition { position }) = event {
+            assert_eq!(position, 272);
+        } else {
+            panic!("Expected SongPosition event");
+        }
+        assert!(MidiEvent::from_bytes(&[0xF2, 0x10]).is_none());
+    }
+
+    #[test]
+    fn test_midi_event_to_bytes_round_trip() {
+        let events = [
+            MidiEvent::NoteOn { channel: 3, note: 60, velocity: 100 },
+            MidiEvent::NoteOff { channel: 3, note: 60, velocity: 64 },
+            MidiEvent::ControlChange { channel: 15, controller: 74, value: 12 },
+            MidiEvent::PitchBend { channel: 0, value: -8192 },
+            MidiEvent::PitchBend { channel: 0, value: 8191 },
+            MidiEvent::ChannelPressure { channel: 9, pressure: 90 },
+            MidiEvent::PolyPressure { channel: 1, note: 64, pressure: 30 },
+            MidiEvent::ProgramChange { channel: 2, program: 5 },
+            MidiEvent::Clock,
+            MidiEvent::SongPosition { position: 1000 },
+        ];
+        for event in events {
+            let (bytes, len) = event.to_bytes();
+            assert_eq!(MidiEvent::from_bytes(&bytes[..len]), Some(event));
+        }
+    }
+
     #[test]
     fn test_midi_event_is_send() {
         fn assert_send<T: Send>() {}
@@ -609,4 +1019,25 @@ mod tests {
         assert_copy::<MidiEvent>();
         assert_copy::<TimestampedMidiEvent>();
     }
+
+    #[test]
+    fn test_port_numbers() {
+        assert_eq!(port_number(0), 1);
+        assert_eq!(port_number(3), 4);
+        assert_eq!(port_number(1000), u8::MAX);
+    }
+
+    #[test]
+    fn test_connection_changes() {
+        let available = vec!["Keys".to_string(), "Knobs".to_string()];
+        let inputs = [("Keys", true), ("Pads", true), ("Knobs", false), ("Drums", false)];
+        assert_eq!(
+            connection_changes(&inputs, &available),
+            vec![
+                (1, MidiConnectionChange::Lost("Pads".to_string())),
+                (2, MidiConnectionChange::Restored("Knobs".to_string())),
+            ]
+        );
+        assert!(connection_changes(&[("Keys", true)], &available).is_empty());
+    }
 }
@@ -0,0 +1,741 @@
+//! MIDI Output
+//!
+//! Sends the MIDI generated by the patch to external gear. Modules such as
+//! CV/Gate to MIDI, CV to CC and the Clock's MIDI clock queue their messages
+//! during processing; the audio processor pushes them into a lock-free queue
+//! and a sender thread passes them on to every connected output. On Linux
+//! and macOS the engine can also publish a virtual output port that other
+//! applications receive from.
+//!
+//! Each message carries the time it is due: the time its block was
+//! processed, plus one block of latency, plus its sample offset within the
+//! block. The sender thread holds messages until they are due, so MIDI
+//! clock and notes keep the spacing they had in the audio instead of
+//! arriving in bursts once per buffer.
+//!
+//! Controller feedback (the values of mapped parameters, sent back to the
+//! controller's motorized faders and LED rings) goes to a separate output
+//! of its own, so controllers don't receive the patch's MIDI.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use midir::{MidiOutput, MidiOutputConnection};
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use crate::dsp::MidiEvent;
+
+use super::midi_engine::{connection_changes, MidiConnectionChange, MidiDeviceInfo, MidiError};
+
+/// Default buffer size for outgoing MIDI messages.
+pub const DEFAULT_MIDI_OUTPUT_BUFFER_SIZE: usize = 1024;
+
+/// Default name of the virtual MIDI output port.
+pub const DEFAULT_VIRTUAL_OUTPUT_NAME: &str = "Modular Synth Out";
+
+/// How often the sender thread checks for new messages.
+const SEND_INTERVAL: Duration = Duration::from_millis(1);
+
+/// How often the sender thread rescans the output devices.
+const SCAN_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A message on its way to the sender thread, with the time it is due.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedMidiEvent {
+    /// The message.
+    pub event: MidiEvent,
+    /// When to send it.
+    pub due: Instant,
+}
+
+impl TimedMidiEvent {
+    /// A message due `sample_offset` samples after `block_time`.
+    pub fn at(event: MidiEvent, block_time: Instant, sample_rate: f32) -> Self {
+        let offset = Duration::from_secs_f64(f64::from(event.sample_offset) / f64::from(sample_rate.max(1.0)));
+        Self { event, due: block_time + offset }
+    }
+}
+
+/// Messages waiting for their time, in the order they are due.
+///
+/// Messages due at the same time keep the order they arrived in.
+#[derive(Default)]
+struct SendQueue {
+    pending: VecDeque<TimedMidiEvent>,
+}
+
+impl SendQueue {
+    fn insert(&mut self, timed: TimedMidiEvent) {
+        let index = self.pending.partition_point(|queued| queued.due <= timed.due);
+        self.pending.insert(index, timed);
+    }
+
+    /// The next message due at `now`, if any.
+    fn pop_due(&mut self, now: Instant) -> Option<MidiEvent> {
+        if self.pending.front()?.due <= now {
+            self.pending.pop_front().map(|timed| timed.event)
+        } else {
+            None
+        }
+    }
+
+    /// When the next message is due.
+    fn next_due(&self) -> Option<Instant> {
+        self.pending.front().map(|timed| timed.due)
+    }
+}
+
+/// A MIDI output chosen by the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MidiOutputInfo {
+    /// Device name.
+    pub name: String,
+    /// Whether the device is currently connected; false while it is unplugged.
+    pub connected: bool,
+    /// Whether this is the virtual port published by the synth.
+    pub is_virtual: bool,
+}
+
+/// A connection to an output, as far as the bookkeeping of `OutputSlots`
+/// is concerned. Implemented by midir's connections, and by a stand-in in
+/// the tests.
+trait OutputConnection {
+    /// Send one message, ignoring errors.
+    fn send_message(&mut self, bytes: &[u8]);
+    /// Close the connection.
+    fn close_connection(self);
+}
+
+impl OutputConnection for MidiOutputConnection {
+    fn send_message(&mut self, bytes: &[u8]) {
+        let _ = self.send(bytes);
+    }
+
+    fn close_connection(self) {
+        self.close();
+    }
+}
+
+/// A MIDI output chosen by the user.
+///
+/// The output stays in the list while its device is unplugged, so it can be
+/// reconnected when the device comes back.
+struct MidiOutputSlot<C> {
+    /// Device name.
+    name: String,
+    /// Active connection (None while the device is unplugged).
+    connection: Option<C>,
+    /// Whether this is the virtual port, which is never unplugged.
+    is_virtual: bool,
+}
+
+/// The chosen outputs and their connections.
+///
+/// Keeps track of which outputs are chosen and connected; opening device
+/// connections is left to the `open` functions passed in.
+struct OutputSlots<C> {
+    slots: Vec<MidiOutputSlot<C>>,
+}
+
+impl<C: OutputConnection> OutputSlots<C> {
+    fn new() -> Self {
+        Self { slots: Vec::new() }
+    }
+
+    /// The device output called `name`, if chosen.
+    fn device(&mut self, name: &str) -> Option<&mut MidiOutputSlot<C>> {
+        self.slots.iter_mut().find(|slot| slot.name == name && !slot.is_virtual)
+    }
+
+    /// Connect the device called `name`, adding it to the outputs.
+    fn connect(&mut self, name: &str, open: impl FnOnce(&str) -> Result<C, MidiError>) -> Result<(), MidiError> {
+        match self.device(name) {
+            Some(slot) if slot.connection.is_some() => {}
+            Some(slot) => slot.connection = Some(open(name)?),
+            None => self.slots.push(MidiOutputSlot {
+                connection: Some(open(name)?),
+                name: name.to_string(),
+                is_virtual: false,
+            }),
+        }
+        Ok(())
+    }
+
+    /// Add the device called `name`, connecting it if `open` succeeds.
+    /// Returns whether it is connected.
+    fn add(&mut self, name: &str, open: impl FnOnce(&str) -> Result<C, MidiError>) -> bool {
+        if let Some(slot) = self.device(name) {
+            return slot.connection.is_some();
+        }
+
+        let connection = open(name).ok();
+        let connected = connection.is_some();
+        self.slots.push(MidiOutputSlot {
+            name: name.to_string(),
+            connection,
+            is_virtual: false,
+        });
+        connected
+    }
+
+    /// Remove the device output called `name`. Returns whether it was chosen.
+    fn remove(&mut self, name: &str) -> bool {
+        let Some(index) = self.slots.iter().position(|slot| slot.name == name && !slot.is_virtual) else {
+            return false;
+        };
+        if let Some(connection) = self.slots.remove(index).connection {
+            connection.close_connection();
+        }
+        true
+    }
+
+    /// Remove every output, including the virtual port.
+    fn clear(&mut self) {
+        for slot in self.slots.drain(..) {
+            if let Some(connection) = slot.connection {
+                connection.close_connection();
+            }
+        }
+    }
+
+    /// Add the virtual port, replacing any other.
+    fn set_virtual(&mut self, name: &str, connection: C) {
+        self.remove_virtual();
+        self.slots.push(MidiOutputSlot {
+            name: name.to_string(),
+            connection: Some(connection),
+            is_virtual: true,
+        });
+    }
+
+    /// Remove the virtual port, if there is one.
+    fn remove_virtual(&mut self) {
+        if let Some(index) = self.slots.iter().position(|slot| slot.is_virtual) {
+            if let Some(connection) = self.slots.remove(index).connection {
+                connection.close_connection();
+            }
+        }
+    }
+
+    /// Name of the virtual port, if there is one.
+    fn virtual_name(&self) -> Option<&str> {
+        self.slots.iter().find(|slot| slot.is_virtual).map(|slot| slot.name.as_str())
+    }
+
+    fn infos(&self) -> Vec<MidiOutputInfo> {
+        self.slots
+            .iter()
+            .map(|slot| MidiOutputInfo {
+                name: slot.name.clone(),
+                connected: slot.connection.is_some(),
+                is_virtual: slot.is_virtual,
+            })
+            .collect()
+    }
+
+    fn is_connected(&self) -> bool {
+        self.slots.iter().any(|slot| slot.connection.is_some())
+    }
+
+    /// Send a message to every connected output.
+    fn send(&mut self, bytes: &[u8]) {
+        for connection in self.slots.iter_mut().filter_map(|slot| slot.connection.as_mut()) {
+            connection.send_message(bytes);
+        }
+    }
+
+    /// Drop the connections of outputs whose device is not `available`, and
+    /// reconnect the ones whose device is back. Returns the changes made.
+    fn check_connections(
+        &mut self,
+        available: &[String],
+        mut open: impl FnMut(&str) -> Result<C, MidiError>,
+    ) -> Vec<MidiConnectionChange> {
+        let status: Vec<(&str, bool)> = self.slots
+            .iter()
+            .map(|slot| (slot.name.as_str(), slot.connection.is_some()))
+            .collect();
+        // The virtual port is not in the device list, but never goes away
+        let changes: Vec<_> = connection_changes(&status, available)
+            .into_iter()
+            .filter(|(index, _)| !self.slots[*index].is_virtual)
+            .collect();
+
+        let mut reported = Vec::new();
+        for (index, change) in changes {
+            let slot = &mut self.slots[index];
+            match change {
+                MidiConnectionChange::Lost(name) => {
+                    if let Some(connection) = slot.connection.take() {
+                        connection.close_connection();
+                    }
+                    reported.push(MidiConnectionChange::Lost(name));
+                }
+                MidiConnectionChange::Restored(name) => {
+                    if let Ok(connection) = open(&name) {
+                        slot.connection = Some(connection);
+                        reported.push(MidiConnectionChange::Restored(name));
+                    }
+                }
+            }
+        }
+        reported
+    }
+}
+
+/// MIDI engine for sending MIDI to external gear.
+///
+/// Every message is sent to all connected outputs; modules pick the MIDI
+/// channel.
+pub struct MidiOutputEngine {
+    /// Chosen outputs, shared with the sender thread.
+    outputs: Arc<Mutex<OutputSlots<MidiOutputConnection>>>,
+    /// Names of the output devices found by the last scan.
+    port_names: Arc<Mutex<Vec<String>>>,
+    /// Flag to signal the sender thread to stop.
+    running: Arc<AtomicBool>,
+    /// Handle for the sender thread.
+    sender_thread: Option<thread::JoinHandle<()>>,
+    /// Output for controller feedback, sent from the UI thread; holds at
+    /// most one output.
+    feedback: OutputSlots<MidiOutputConnection>,
+}
+
+impl MidiOutputEngine {
+    /// Create a new MIDI output engine.
+    ///
+    /// Returns the engine and a producer for the audio processor to push
+    /// outgoing messages to (see `AudioProcessor::set_midi_output`).
+    pub fn new() -> Result<(Self, Producer<TimedMidiEvent>), MidiError> {
+        let (producer, consumer) = RingBuffer::new(DEFAULT_MIDI_OUTPUT_BUFFER_SIZE);
+
+        let port_names = Arc::new(Mutex::new(scan_ports()?));
+        let outputs = Arc::new(Mutex::new(OutputSlots::new()));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let sender_thread = {
+            let outputs = Arc::clone(&outputs);
+            let port_names = Arc::clone(&port_names);
+            let running = Arc::clone(&running);
+            thread::spawn(move || send_loop(consumer, outputs, port_names, running))
+        };
+
+        let engine = Self {
+            outputs,
+            port_names,
+            running,
+            sender_thread: Some(sender_thread),
+            feedback: OutputSlots::new(),
+        };
+        Ok((engine, producer))
+    }
+
+    /// Enumerate available MIDI output devices, as of the last scan.
+    pub fn enumerate_devices(&self) -> Vec<MidiDeviceInfo> {
+        self.port_names
+            .lock()
+            .map(|names| {
+                names
+                    .iter()
+                    .enumerate()
+                    .map(|(index, name)| MidiDeviceInfo {
+                        name: name.clone(),
+                        index,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The chosen outputs.
+    pub fn outputs(&self) -> Vec<MidiOutputInfo> {
+        self.outputs.lock().map(|outputs| outputs.infos()).unwrap_or_default()
+    }
+
+    /// Connect to a MIDI output device by index, adding it to the outputs.
+    pub fn connect(&mut self, device_index: usize) -> Result<(), MidiError> {
+        let name = self
+            .port_names
+            .lock()
+            .map_err(|_| MidiError::ConnectionError("Failed to lock state".to_string()))?
+            .get(device_index)
+            .cloned()
+            .ok_or(MidiError::DeviceNotFound)?;
+
+        self.lock_outputs()?.connect(&name, open)
+    }
+
+    /// Add an output by device name, whether or not the device is present.
+    ///
+    /// A missing device is connected as soon as it appears (see
+    /// `check_connections`). Returns whether it is connected now.
+    pub fn add_output(&mut self, name: &str) -> bool {
+        self.lock_outputs().is_ok_and(|mut outputs| outputs.add(name, open))
+    }
+
+    /// Disconnect a device and remove it from the outputs.
+    pub fn disconnect(&mut self, name: &str) {
+        if let Ok(mut outputs) = self.lock_outputs() {
+            if outputs.remove(name) {
+                eprintln!("MIDI output disconnected: {}", name);
+            }
+        }
+    }
+
+    /// Disconnect all devices, including the virtual port.
+    pub fn disconnect_all(&mut self) {
+        if let Ok(mut outputs) = self.lock_outputs() {
+            outputs.clear();
+        }
+    }
+
+    /// Check if at least one output is connected.
+    pub fn is_connected(&self) -> bool {
+        self.outputs.lock().is_ok_and(|outputs| outputs.is_connected())
+    }
+
+    /// Publish a virtual MIDI output port that other applications can receive from.
+    ///
+    /// There is at most one virtual port; opening another one replaces it.
+    /// Only supported on Linux and macOS.
+    pub fn open_virtual_port(&mut self, name: &str) -> Result<(), MidiError> {
+        if self.virtual_port().as_deref() == Some(name) {
+            return Ok(());
+        }
+        self.close_virtual_port();
+
+        let connection = create_virtual(name)?;
+        self.lock_outputs()?.set_virtual(name, connection);
+        eprintln!("MIDI virtual output opened: {}", name);
+        Ok(())
+    }
+
+    /// Remove the virtual MIDI output port, if it is open.
+    pub fn close_virtual_port(&mut self) {
+        if let Ok(mut outputs) = self.lock_outputs() {
+            outputs.remove_virtual();
+        }
+    }
+
+    /// Name of the virtual MIDI output port, if it is open.
+    pub fn virtual_port(&self) -> Option<String> {
+        self.outputs.lock().ok()?.virtual_name().map(str::to_string)
+    }
+
+    /// Choose the output controller feedback is sent to (None = no feedback).
+    ///
+    /// A missing device is connected as soon as it appears (see
+    /// `check_connections`). Returns whether it is connected now.
+    pub fn set_feedback_output(&mut self, name: Option<&str>) -> bool {
+        let current = self.feedback.slots.first().map(|slot| slot.name.as_str());
+        if current.is_some() && current == name {
+            return self.feedback.is_connected();
+        }
+
+        self.feedback.clear();
+        name.is_some_and(|name| self.feedback.add(name, open))
+    }
+
+    /// The output controller feedback is sent to, if one is chosen.
+    pub fn feedback_output(&self) -> Option<MidiOutputInfo> {
+        self.feedback.infos().into_iter().next()
+    }
+
+    /// Send controller feedback to the feedback output.
+    ///
+    /// Does nothing while no feedback output is connected.
+    pub fn send_feedback(&mut self, events: &[MidiEvent]) {
+        for event in events {
+            let (bytes, len) = event.to_bytes();
+            self.feedback.send(&bytes[..len]);
+        }
+    }
+
+    /// Follow devices being unplugged and plugged back in.
+    ///
+    /// Drops the connections of outputs whose device disappeared from the
+    /// last scan and reconnects outputs whose device is back, including the
+    /// feedback output. Call this regularly; it only looks at the result of
+    /// the background scan.
+    pub fn check_connections(&mut self) -> Vec<MidiConnectionChange> {
+        let available = match self.port_names.lock() {
+            Ok(names) => names.clone(),
+            Err(_) => return Vec::new(),
+        };
+
+        let mut reported = Vec::new();
+        for change in self.feedback.check_connections(&available, open) {
+            if let MidiConnectionChange::Lost(name) = &change {
+                eprintln!("MIDI feedback output lost: {}", name);
+            }
+            reported.push(change);
+        }
+        if let Ok(mut outputs) = self.lock_outputs() {
+            for change in outputs.check_connections(&available, open) {
+                if let MidiConnectionChange::Lost(name) = &change {
+                    eprintln!("MIDI output lost: {}", name);
+                }
+                reported.push(change);
+            }
+        }
+        reported
+    }
+
+    /// Lock the outputs shared with the sender thread.
+    fn lock_outputs(&self) -> Result<std::sync::MutexGuard<'_, OutputSlots<MidiOutputConnection>>, MidiError> {
+        self.outputs
+            .lock()
+            .map_err(|_| MidiError::ConnectionError("Failed to lock outputs".to_string()))
+    }
+}
+
+impl Drop for MidiOutputEngine {
+    fn drop(&mut self) {
+        // Stop the sender thread before closing the connections it uses
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.sender_thread.take() {
+            let _ = thread.join();
+        }
+        self.disconnect_all();
+        self.feedback.clear();
+    }
+}
+
+/// Names of the MIDI output devices currently present.
+fn scan_ports() -> Result<Vec<String>, MidiError> {
+    let midi_out = MidiOutput::new("Modular Synth Scanner")
+        .map_err(|e| MidiError::InitError(e.to_string()))?;
+    Ok(midi_out
+        .ports()
+        .iter()
+        .map(|p| midi_out.port_name(p).unwrap_or_else(|_| "Unknown".to_string()))
+        .collect())
+}
+
+/// Open a connection to the output device called `name`.
+fn open(name: &str) -> Result<MidiOutputConnection, MidiError> {
+    let midi_out = MidiOutput::new("Modular Synth Output")
+        .map_err(|e| MidiError::InitError(e.to_string()))?;
+    let port = midi_out
+        .ports()
+        .into_iter()
+        .find(|p| midi_out.port_name(p).is_ok_and(|port_name| port_name == name))
+        .ok_or(MidiError::DeviceNotFound)?;
+    let connection = midi_out
+        .connect(&port, "Modular Synth Output")
+        .map_err(|e| MidiError::ConnectionError(e.to_string()))?;
+
+    eprintln!("MIDI output connected: {}", name);
+    Ok(connection)
+}
+
+/// Create a virtual output port called `name`.
+#[cfg(unix)]
+fn create_virtual(name: &str) -> Result<MidiOutputConnection, MidiError> {
+    use midir::os::unix::VirtualOutput;
+
+    let midi_out = MidiOutput::new("Modular Synth")
+        .map_err(|e| MidiError::InitError(e.to_string()))?;
+    midi_out
+        .create_virtual(name)
+        .map_err(|e| MidiError::ConnectionError(e.to_string()))
+}
+
+/// Virtual ports need ALSA or CoreMIDI.
+#[cfg(not(unix))]
+fn create_virtual(_name: &str) -> Result<MidiOutputConnection, MidiError> {
+    Err(MidiError::VirtualPortUnsupported)
+}
+
+/// Body of the sender thread: passes queued messages on to the outputs when
+/// they are due and periodically rescans the output devices (hot-plug
+/// detection).
+fn send_loop(
+    mut consumer: Consumer<TimedMidiEvent>,
+    outputs: Arc<Mutex<OutputSlots<MidiOutputConnection>>>,
+    port_names: Arc<Mutex<Vec<String>>>,
+    running: Arc<AtomicBool>,
+) {
+    let mut queue = SendQueue::default();
+    let mut last_scan = Instant::now();
+    while running.load(Ordering::Relaxed) {
+        while let Ok(timed) = consumer.pop() {
+            queue.insert(timed);
+        }
+
+        let now = Instant::now();
+        if queue.next_due().is_some_and(|due| due <= now) {
+            if let Ok(mut outputs) = outputs.lock() {
+                while let Some(event) = queue.pop_due(now) {
+                    let (bytes, len) = event.to_bytes();
+                    outputs.send(&bytes[..len]);
+                }
+            }
+        }
+
+        if last_scan.elapsed() >= SCAN_INTERVAL {
+            last_scan = Instant::now();
+            if let Ok(names) = scan_ports() {
+                if let Ok(mut port_names) = port_names.lock() {
+                    *port_names = names;
+                }
+            }
+        }
+
+        // Wake up in time for the next message
+        let wait = queue
+            .next_due()
+            .map_or(SEND_INTERVAL, |due| due.saturating_duration_since(Instant::now()).min(SEND_INTERVAL));
+        thread::sleep(wait);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timed_event_offset() {
+        let block_time = Instant::now();
+        let timed = TimedMidiEvent::at(MidiEvent::control_change(4410, 0, 1, 0), block_time, 44100.0);
+        assert_eq!(timed.due - block_time, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_send_queue_orders_by_due_time() {
+        let now = Instant::now();
+        let event = |offset: u32| MidiEvent::control_change(offset, 0, 1, 0);
+        let mut queue = SendQueue::default();
+        queue.insert(TimedMidiEvent { event: event(2), due: now + Duration::from_millis(2) });
+        queue.insert(TimedMidiEvent { event: event(0), due: now });
+        queue.insert(TimedMidiEvent { event: event(1), due: now + Duration::from_millis(2) });
+
+        assert_eq!(queue.pop_due(now), Some(event(0)));
+        // Not due yet
+        assert_eq!(queue.pop_due(now), None);
+        assert_eq!(queue.next_due(), Some(now + Duration::from_millis(2)));
+
+        // Same due time: arrival order
+        let later = now + Duration::from_millis(5);
+        assert_eq!(queue.pop_due(later), Some(event(2)));
+        assert_eq!(queue.pop_due(later), Some(event(1)));
+        assert_eq!(queue.next_due(), None);
+    }
+
+    /// Records what is sent instead of talking to a device.
+    #[derive(Default)]
+    struct FakeConnection {
+        sent: Vec<Vec<u8>>,
+    }
+
+    impl OutputConnection for FakeConnection {
+        fn send_message(&mut self, bytes: &[u8]) {
+            self.sent.push(bytes.to_vec());
+        }
+
+        fn close_connection(self) {}
+    }
+
+    fn open_fake(_name: &str) -> Result<FakeConnection, MidiError> {
+        Ok(FakeConnection::default())
+    }
+
+    fn open_missing(_name: &str) -> Result<FakeConnection, MidiError> {
+        Err(MidiError::DeviceNotFound)
+    }
+
+    fn connected(slots: &OutputSlots<FakeConnection>) -> Vec<(String, bool)> {
+        slots.infos().into_iter().map(|info| (info.name, info.connected)).collect()
+    }
+
+    #[test]
+    fn test_connect_and_add() {
+        let mut slots = OutputSlots::new();
+        assert!(slots.connect("Synth", open_missing).is_err());
+        assert!(slots.infos().is_empty());
+
+        slots.connect("Synth", open_fake).unwrap();
+        // Connecting again keeps the single slot
+        slots.connect("Synth", open_fake).unwrap();
+        // A missing device is still added, to be connected when it appears
+        assert!(!slots.add("Drum Machine", open_missing));
+        assert!(slots.add("Synth", open_missing));
+
+        assert_eq!(
+            connected(&slots),
+            vec![("Synth".to_string(), true), ("Drum Machine".to_string(), false)]
+        );
+        assert!(slots.is_connected());
+
+        assert!(slots.remove("Synth"));
+        assert!(!slots.remove("Synth"));
+        assert!(!slots.is_connected());
+    }
+
+    #[test]
+    fn test_virtual_port() {
+        let mut slots = OutputSlots::new();
+        slots.add("Synth", open_fake);
+        slots.set_virtual("Modular Out", FakeConnection::default());
+        slots.set_virtual("Modular Synth", FakeConnection::default());
+        assert_eq!(slots.virtual_name(), Some("Modular Synth"));
+        assert_eq!(slots.infos().len(), 2);
+
+        // Device removal doesn't touch the virtual port, even by name
+        assert!(!slots.remove("Modular Synth"));
+        // Nor does the device going missing from the scan
+        assert!(slots.check_connections(&["Synth".to_string()], open_fake).is_empty());
+        assert!(slots.infos()[1].connected);
+
+        slots.remove_virtual();
+        assert_eq!(slots.virtual_name(), None);
+        assert_eq!(connected(&slots), vec![("Synth".to_string(), true)]);
+    }
+
+    #[test]
+    fn test_check_connections() {
+        let mut slots = OutputSlots::new();
+        slots.add("Synth", open_fake);
+        slots.add("Drum Machine", open_missing);
+
+        let changes = slots.check_connections(&["Drum Machine".to_string()], open_fake);
+        assert_eq!(
+            changes,
+            vec![
+                MidiConnectionChange::Lost("Synth".to_string()),
+                MidiConnectionChange::Restored("Drum Machine".to_string()),
+            ]
+        );
+        assert_eq!(
+            connected(&slots),
+            vec![("Synth".to_string(), false), ("Drum Machine".to_string(), true)]
+        );
+
+        // A device that is back but fails to open is not reported
+        let available = ["Synth".to_string(), "Drum Machine".to_string()];
+        assert!(slots.check_connections(&available, open_missing).is_empty());
+        assert_eq!(
+            slots.check_connections(&available, open_fake),
+            vec![MidiConnectionChange::Restored("Synth".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_send_reaches_connected_outputs() {
+        let mut slots = OutputSlots::new();
+        slots.add("Synth", open_fake);
+        slots.add("Drum Machine", open_missing);
+        slots.set_virtual("Modular Synth", FakeConnection::default());
+
+        slots.send(&[0xF8]);
+        let sent: Vec<_> = slots
+            .slots
+            .iter()
+            .map(|slot| slot.connection.as_ref().map(|connection| connection.sent.clone()))
+            .collect();
+        assert_eq!(sent, vec![Some(vec![vec![0xF8]]), None, Some(vec![vec![0xF8]])]);
+    }
+}
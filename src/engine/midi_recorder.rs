@@ -0,0 +1,213 @@
+//! MIDI Recorder
+//!
+//! Captures the MIDI received from the inputs, with its timing, and turns it
+//! into a Standard MIDI File. Runs on the UI thread, which receives the MIDI
+//! input.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use super::midi_engine::{MidiEvent, TimestampedMidiEvent};
+use super::smf::{MidiFile, MidiTrack, TrackEvent, DEFAULT_TEMPO_US};
+
+/// Records incoming MIDI events for saving as a MIDI file.
+///
+/// Event timestamps come from the MIDI driver and each input counts from
+/// its own start, so the first event of every input is aligned to the time
+/// it was received; the events after it keep the driver's timing.
+///
+/// The file is written at 120 BPM. A recording from one input becomes a
+/// format 0 file; with several inputs, each gets its own track in a
+/// format 1 file.
+#[derive(Debug, Clone, Default)]
+pub struct MidiRecorder {
+    /// When recording started (None = not recording).
+    started: Option<Instant>,
+    /// Offset from each input's timestamps to microseconds since the start.
+    port_offsets: HashMap<u8, i64>,
+    /// Recorded events: microseconds since the start, input port, event.
+    events: Vec<(u64, u8, MidiEvent)>,
+}
+
+impl MidiRecorder {
+    /// Resolution of recorded files.
+    pub const TICKS_PER_QUARTER: u16 = 960;
+
+    /// Creates a recorder that isn't recording.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a new recording, dropping anything recorded before.
+    pub fn start(&mut self, now: Instant) {
+        self.started = Some(now);
+        self.port_offsets.clear();
+        self.events.clear();
+    }
+
+    /// Whether a recording is in progress.
+    pub fn is_recording(&self) -> bool {
+        self.started.is_some()
+    }
+
+    /// Number of events recorded so far.
+    pub fn event_count(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Record an event received at `now`.
+    ///
+    /// Clock, transport and song position messages aren't recorded, nor
+    /// Parameter Number values, whose Control Changes are.
+    pub fn record(&mut self, event: &TimestampedMidiEvent, now: Instant) {
+        let Some(started) = self.started else {
+            return;
+        };
+        if matches!(
+            event.event,
+            MidiEvent::Clock
+                | MidiEvent::Start
+                | MidiEvent::Continue
+                | MidiEvent::Stop
+                | MidiEvent::SongPosition { .. }
+                | MidiEvent::ParameterNumber { .. }
+        ) {
+            return;
+        }
+
+        let received_us = now.saturating_duration_since(started).as_micros() as i64;
+        let offset = *self
+            .port_offsets
+            .entry(event.port)
+            .or_insert(received_us - event.timestamp_us as i64);
+        let time_us = (event.timestamp_us as i64 + offset).max(0) as u64;
+        self.events.push((time_us, event.port, event.event));
+    }
+
+    /// Stop recording and return the recorded MIDI file.
+    ///
+    /// Tracks are named with `port_name`, falling back to the input number.
+    /// Returns None if not recording or nothing was recorded.
+    pub fn stop(&mut self, now: Instant, port_name: impl Fn(u8) -> Option<String>) -> Option<MidiFile> {
+        let started = self.started.take()?;
+        if self.events.is_empty() {
+            return None;
+        }
+
+        let mut ports: Vec<u8> = self.port_offsets.keys().copied().collect();
+        ports.sort_unstable();
+        let end_tick = Self::tick_at(now.saturating_duration_since(started).as_micros() as u64);
+
+        let track_for = |port: u8| {
+            let mut track = MidiTrack::named(port_name(port).unwrap_or_else(|| format!("In {}", port)));
+            track.events = self
+                .events
+                .iter()
+                .filter(|(_, event_port, _)| *event_port == port)
+                .map(|&(time_us, _, event)| TrackEvent::midi(Self::tick_at(time_us), event))
+                .collect();
+            track.events.sort_by_key(|event| event.tick);
+            track.end_tick = end_tick;
+            track
+        };
+
+        let file = if let [port] = ports[..] {
+            let mut track = track_for(port);
+            track.events.insert(0, TrackEvent::tempo(0, DEFAULT_TEMPO_US));
+            let mut file = MidiFile::new(0, Self::TICKS_PER_QUARTER);
+            file.tracks.push(track);
+            file
+        } else {
+            let mut tempo = MidiTrack::named("Tempo");
+            tempo.events.push(TrackEvent::tempo(0, DEFAULT_TEMPO_US));
+            let mut file = MidiFile::new(1, Self::TICKS_PER_QUARTER);
+            file.tracks.push(tempo);
+            file.tracks.extend(ports.iter().map(|&port| track_for(port)));
+            file
+        };
+
+        self.port_offsets.clear();
+        self.events.clear();
+        Some(file)
+    }
+
+    /// Tick of a time in microseconds since the start, at 120 BPM.
+    fn tick_at(time_us: u64) -> u64 {
+        let ticks = time_us as f64 * Self::TICKS_PER_QUARTER as f64 / DEFAULT_TEMPO_US as f64;
+        ticks.round() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::smf::TrackEventKind;
+    use std::time::Duration;
+
+    fn event(event: MidiEvent, timestamp_us: u64, port: u8) -> TimestampedMidiEvent {
+        TimestampedMidiEvent { event, timestamp_us, port }
+    }
+
+    fn note_on(note: u8) -> MidiEvent {
+        MidiEvent::NoteOn { channel: 0, note, velocity: 100 }
+    }
+
+    #[test]
+    fn test_records_single_input_as_format_0() {
+        let start = Instant::now();
+        let mut recorder = MidiRecorder::new();
+        assert!(!recorder.is_recording());
+        recorder.start(start);
+
+        // The input's clock started long before the recording
+        recorder.record(&event(note_on(60), 5_000_000, 1), start + Duration::from_millis(100));
+        // Arrives late, but the driver timestamp says 500 ms after the first
+        recorder.record(&event(note_on(62), 5_500_000, 1), start + Duration::from_millis(620));
+        recorder.record(&event(MidiEvent::Clock, 5_600_000, 1), start + Duration::from_millis(700));
+        assert_eq!(recorder.event_count(), 2);
+
+        let file = recorder.stop(start + Duration::from_secs(1), |_| Some("Keys".to_string())).unwrap();
+        assert!(!recorder.is_recording());
+        assert_eq!(file.format, 0);
+        assert_eq!(file.tracks.len(), 1);
+
+        // 960 ticks per quarter note at 120 BPM: 1920 ticks per second
+        let track = &file.tracks[0];
+        assert_eq!(track.name.as_deref(), Some("Keys"));
+        assert_eq!(track.events[0].kind, TrackEventKind::Tempo(DEFAULT_TEMPO_US));
+        assert_eq!(track.events[1], TrackEvent::midi(192, note_on(60)));
+        assert_eq!(track.events[2], TrackEvent::midi(1152, note_on(62)));
+        assert_eq!(track.end_tick, 1920);
+    }
+
+    #[test]
+    fn test_records_inputs_to_separate_tracks() {
+        let start = Instant::now();
+        let mut recorder = MidiRecorder::new();
+        recorder.start(start);
+        recorder.record(&event(note_on(36), 0, 2), start);
+        recorder.record(&event(note_on(72), 1_000, 1), start + Duration::from_millis(250));
+
+        let file = recorder.stop(start + Duration::from_millis(500), |_| None).unwrap();
+        assert_eq!(file.format, 1);
+        let names: Vec<_> = file.tracks.iter().map(|track| track.name.clone().unwrap()).collect();
+        assert_eq!(names, ["Tempo", "In 1", "In 2"]);
+        assert_eq!(file.tracks[1].events, vec![TrackEvent::midi(480, note_on(72))]);
+        assert_eq!(file.tracks[2].events, vec![TrackEvent::midi(0, note_on(36))]);
+    }
+
+    #[test]
+    fn test_nothing_recorded() {
+        let start = Instant::now();
+        let mut recorder = MidiRecorder::new();
+        assert!(recorder.stop(start, |_| None).is_none());
+
+        recorder.start(start);
+        recorder.record(&event(MidiEvent::Start, 0, 1), start);
+        assert!(recorder.stop(start, |_| None).is_none());
+
+        // Events outside a recording are ignored
+        recorder.record(&event(note_on(60), 0, 1), start);
+        assert_eq!(recorder.event_count(), 0);
+    }
+}
@@ -1,7 +1,9 @@
 //! Engine module
 //!
 //! Audio engine and processing graph.
-//! Handles cpal integration, audio graph processing, buffer management, and MIDI input.
+//! Handles cpal integration, audio input, audio graph processing, output routing,
+//! buffer management, MIDI input and output, NRPN/RPN parsing, MIDI clock sync,
+//! MPE, MIDI files, Scala tunings, and recording to WAV and MIDI files.
 
 pub mod audio_engine;
 pub mod audio_graph;
@@ -9,9 +11,22 @@ pub mod audio_processor;
 pub mod buffer_pool;
 pub mod channels;
 pub mod commands;
+pub mod input_stream;
+pub mod midi_clock;
 pub mod midi_engine;
+pub mod midi_output;
+pub mod midi_recorder;
+pub mod mpe;
+pub mod parameter_number;
+pub mod recorder;
+pub mod routing;
+pub mod scala;
+pub mod smf;
+pub mod wav;
 
-pub use audio_engine::{AudioEngine, AudioError, DeviceInfo};
+pub use audio_engine::{
+    AudioEngine, AudioError, DeviceInfo, StreamSettings, COMMON_BUFFER_SIZES, COMMON_SAMPLE_RATES,
+};
 pub use audio_graph::{AudioGraph, Connection};
 pub use audio_processor::{AudioProcessor, create_module_registry};
 pub use buffer_pool::{BufferPool, BufferSlot};
@@ -19,4 +34,18 @@ pub use channels::{
     EngineChannels, EngineHandle, UiHandle, DEFAULT_COMMAND_BUFFER_SIZE, DEFAULT_EVENT_BUFFER_SIZE,
 };
 pub use commands::{EngineCommand, EngineEvent, NodeId, PortIndex};
-pub use midi_engine::{MidiDeviceInfo, MidiEngine, MidiError, MidiEvent, TimestampedMidiEvent};
+pub use input_stream::{input_stream, InputCapture, InputReader, InputStats, MAX_INPUT_CHANNELS};
+pub use midi_clock::{MidiClockFollower, MidiTransport, MIDI_CLOCK_PPQN};
+pub use midi_engine::{
+    MidiConnectionChange, MidiDeviceInfo, MidiEngine, MidiError, MidiEvent, MidiInputInfo, TimestampedMidiEvent,
+    DEFAULT_VIRTUAL_PORT_NAME,
+};
+pub use midi_output::{MidiOutputEngine, MidiOutputInfo, DEFAULT_VIRTUAL_OUTPUT_NAME};
+pub use midi_recorder::MidiRecorder;
+pub use mpe::{MpeVoiceAllocator, MpeVoiceState, MpeZone, MpeZoneKind};
+pub use parameter_number::ParameterNumberParser;
+pub use recorder::{RecordSource, RecorderEvent, RecorderTap, WavRecorder};
+pub use routing::{OutputRouting, MAX_OUTPUT_CHANNELS, MULTI_OUTPUT_CHANNELS, ROUTING_SOURCES};
+pub use scala::{KeyboardMapping, ScalaError, Scale};
+pub use smf::{MidiFile, MidiTrack, SmfError, TempoMap, TrackEvent, TrackEventKind};
+pub use wav::WavWriter;
@@ -0,0 +1,483 @@
+//! MPE (MIDI Polyphonic Expression)
+//!
+//! MPE controllers play each note on its own member channel, so pitch bend,
+//! channel pressure and CC74 (slide) apply to that note alone. A zone is a
+//! master channel (1 for the lower zone, 16 for the upper) with a range of
+//! member channels next to it; pitch bend on the master channel bends every
+//! note of the zone.
+//!
+//! `MpeVoiceAllocator` keeps the expression of each member channel and
+//! assigns the notes to a fixed number of voices, which MPE Voice modules
+//! output. Like the MIDI Note voices it runs on the UI thread, which
+//! receives the MIDI input.
+
+use std::time::{Duration, Instant};
+
+use super::midi_engine::MidiEvent;
+
+/// Which end of the 16 MIDI channels a zone occupies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MpeZoneKind {
+    /// Master channel 1, member channels from 2 upwards.
+    #[default]
+    Lower,
+    /// Master channel 16, member channels from 15 downwards.
+    Upper,
+}
+
+/// An MPE zone: where its channels are and how far its notes bend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MpeZone {
+    /// Lower or upper zone.
+    pub kind: MpeZoneKind,
+    /// Number of member channels (1-15).
+    pub member_channels: u8,
+    /// Pitch bend range of the member channels in semitones.
+    pub pitch_bend_range: u8,
+}
+
+impl MpeZone {
+    /// Member channel pitch bend range the MPE specification defaults to.
+    pub const DEFAULT_PITCH_BEND_RANGE: u8 = 48;
+
+    /// Pitch bend range of the master channel in semitones.
+    pub const MASTER_PITCH_BEND_RANGE: f32 = 2.0;
+
+    /// Most member channels a zone can have.
+    pub const MAX_MEMBER_CHANNELS: u8 = 15;
+
+    /// A zone of the given kind with `member_channels` (clamped to 1-15).
+    pub fn new(kind: MpeZoneKind, member_channels: u8, pitch_bend_range: u8) -> Self {
+        Self {
+            kind,
+            member_channels: member_channels.clamp(1, Self::MAX_MEMBER_CHANNELS),
+            pitch_bend_range,
+        }
+    }
+
+    /// The master channel (0-15).
+    pub fn master_channel(&self) -> u8 {
+        match self.kind {
+            MpeZoneKind::Lower => 0,
+            MpeZoneKind::Upper => 15,
+        }
+    }
+
+    /// Whether `channel` (0-15) is one of the zone's member channels.
+    pub fn is_member(&self, channel: u8) -> bool {
+        match self.kind {
+            MpeZoneKind::Lower => (1..=self.member_channels).contains(&channel),
+            MpeZoneKind::Upper => (15 - self.member_channels..15).contains(&channel),
+        }
+    }
+}
+
+impl Default for MpeZone {
+    /// The lower zone with all 15 member channels and a 48 semitone bend range.
+    fn default() -> Self {
+        Self::new(MpeZoneKind::Lower, Self::MAX_MEMBER_CHANNELS, Self::DEFAULT_PITCH_BEND_RANGE)
+    }
+}
+
+/// The signals of one voice, as output by an MPE Voice module.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MpeVoiceState {
+    /// V/Oct pitch including per-note and zone pitch bend (0.0 = C4).
+    pub pitch: f32,
+    /// Whether the voice is playing a note.
+    pub gate: bool,
+    /// Note On velocity (0.0-1.0).
+    pub velocity: f32,
+    /// Per-note pressure (0.0-1.0).
+    pub pressure: f32,
+    /// Per-note slide, CC74 (0.0-1.0).
+    pub slide: f32,
+}
+
+/// Expression received on one channel.
+#[derive(Debug, Clone, Copy)]
+struct ChannelExpression {
+    /// Pitch bend in semitones.
+    bend: f32,
+    /// Pressure (0.0-1.0).
+    pressure: f32,
+    /// Slide (0.0-1.0).
+    slide: f32,
+}
+
+impl Default for ChannelExpression {
+    fn default() -> Self {
+        Self {
+            bend: 0.0,
+            pressure: 0.0,
+            slide: MpeVoiceAllocator::DEFAULT_SLIDE,
+        }
+    }
+}
+
+/// A voice and the note assigned to it.
+#[derive(Debug, Clone, Copy)]
+struct VoiceSlot {
+    /// Note number and member channel of the last note played.
+    note: u8,
+    channel: u8,
+    /// Note On velocity (0.0-1.0).
+    velocity: f32,
+    /// Whether the key is held.
+    held: bool,
+    /// Whether the gate is high: while the key is held, and for at least
+    /// the minimum gate time.
+    gate: bool,
+    /// When the note started.
+    gate_on: Instant,
+    /// Order of the note, for picking the oldest voice.
+    age: u64,
+}
+
+/// Assigns the notes of an MPE zone to voices and tracks their expression.
+#[derive(Debug, Clone)]
+pub struct MpeVoiceAllocator {
+    /// The zone listened to.
+    zone: MpeZone,
+    /// MIDI input listened to (0 = any).
+    port: u8,
+    /// Expression of each channel; member channels apply to their note,
+    /// the master channel's pitch bend to every note.
+    channels: [ChannelExpression; 16],
+    /// One slot per voice (None = never played).
+    slots: Vec<Option<VoiceSlot>>,
+    /// Counter for `VoiceSlot::age`.
+    next_age: u64,
+}
+
+impl MpeVoiceAllocator {
+    /// Shortest gate, so that notes shorter than a UI frame still trigger.
+    pub const MIN_GATE_DURATION: Duration = Duration::from_millis(30);
+
+    /// Slide before any CC74 is received: the centre, as MPE recommends.
+    const DEFAULT_SLIDE: f32 = 0.5;
+
+    /// Controller number of the slide (timbre) dimension.
+    const SLIDE_CC: u8 = 74;
+
+    /// Creates an allocator with `voices` voices (at least 1).
+    pub fn new(zone: MpeZone, port: u8, voices: usize) -> Self {
+        Self {
+            zone,
+            port,
+            channels: [ChannelExpression::default(); 16],
+            slots: vec![None; voices.max(1)],
+            next_age: 0,
+        }
+    }
+
+    /// Number of voices notes are assigned to.
+    pub fn voice_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Change the number of voices (at least 1). Notes on removed voices are dropped.
+    pub fn set_voice_count(&mut self, voices: usize) {
+        self.slots.resize(voices.max(1), None);
+    }
+
+    /// Handle a MIDI message received on `port` at `now`.
+    ///
+    /// Returns whether any voice changed.
+    pub fn handle(&mut self, event: &MidiEvent, port: u8, now: Instant) -> bool {
+        if self.port != 0 && self.port != port {
+            return false;
+        }
+        let channel = event.channel();
+        let is_master = channel == self.zone.master_channel();
+        if !is_master && !self.zone.is_member(channel) {
+            return false;
+        }
+
+        match *event {
+            MidiEvent::NoteOn { note, velocity, .. } if !is_master => {
+                self.note_on(note, channel, velocity as f32 / 127.0, now);
+                true
+            }
+            MidiEvent::NoteOff { note, .. } if !is_master => self.note_off(note, channel, now),
+            MidiEvent::PitchBend { value, .. } => {
+                let range = if is_master {
+                    MpeZone::MASTER_PITCH_BEND_RANGE
+                } else {
+                    self.zone.pitch_bend_range as f32
+                };
+                self.channels[channel as usize].bend = value as f32 / 8192.0 * range;
+                self.channel_sounding(channel, is_master)
+            }
+            MidiEvent::ChannelPressure { pressure, .. } if !is_master => {
+                self.channels[channel as usize].pressure = pressure as f32 / 127.0;
+                self.channel_sounding(channel, false)
+            }
+            MidiEvent::PolyPressure { pressure, .. } if !is_master => {
+                self.channels[channel as usize].pressure = pressure as f32 / 127.0;
+                self.channel_sounding(channel, false)
+            }
+            MidiEvent::ControlChange { controller, value, .. } if controller == Self::SLIDE_CC && !is_master => {
+                self.channels[channel as usize].slide = value as f32 / 127.0;
+                self.channel_sounding(channel, false)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether a change on `channel` affects a voice that is playing.
+    fn channel_sounding(&self, channel: u8, is_master: bool) -> bool {
+        self.slots
+            .iter()
+            .flatten()
+            .any(|slot| slot.gate && (is_master || slot.channel == channel))
+    }
+
+    /// Assign a note to the voice that has been free longest, or take the
+    /// voice of the oldest note if all are playing.
+    fn note_on(&mut self, note: u8, channel: u8, velocity: f32, now: Instant) {
+        let age_of = |slot: &Option<VoiceSlot>| slot.map_or(0, |slot| slot.age);
+        let index = (0..self.slots.len())
+            .filter(|&i| self.slots[i].is_none_or(|slot| !slot.gate))
+            .min_by_key(|&i| age_of(&self.slots[i]))
+            .or_else(|| (0..self.slots.len()).min_by_key(|&i| age_of(&self.slots[i])))
+            .unwrap_or(0);
+
+        self.next_age += 1;
+        self.slots[index] = Some(VoiceSlot {
+            note,
+            channel,
+            velocity,
+            held: true,
+            gate: true,
+            gate_on: now,
+            age: self.next_age,
+        });
+    }
+
+    /// Release the voice playing `note` on `channel`, if any.
+    fn note_off(&mut self, note: u8, channel: u8, now: Instant) -> bool {
+        let Some(slot) = self
+            .slots
+            .iter_mut()
+            .flatten()
+            .find(|slot| slot.held && slot.note == note && slot.channel == channel)
+        else {
+            return false;
+        };
+        slot.held = false;
+        if now.duration_since(slot.gate_on) >= Self::MIN_GATE_DURATION {
+            slot.gate = false;
+        }
+        // The pressure of a released key is gone; bend and slide carry over
+        self.channels[channel as usize].pressure = 0.0;
+        true
+    }
+
+    /// Close the gates of released notes whose minimum gate time has passed.
+    ///
+    /// Returns whether any voice changed.
+    pub fn update_gates(&mut self, now: Instant) -> bool {
+        let mut changed = false;
+        for slot in self.slots.iter_mut().flatten() {
+            if slot.gate && !slot.held && now.duration_since(slot.gate_on) >= Self::MIN_GATE_DURATION {
+                slot.gate = false;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// The signals of voice `index`, or None if it doesn't exist.
+    ///
+    /// A released voice keeps its pitch and expression so the release of
+    /// its envelope sounds at the right pitch.
+    pub fn voice(&self, index: usize) -> Option<MpeVoiceState> {
+        let slot = self.slots.get(index)?;
+        let Some(slot) = slot else {
+            return Some(MpeVoiceState {
+                pitch: 0.0,
+                gate: false,
+                velocity: 0.0,
+                pressure: 0.0,
+                slide: Self::DEFAULT_SLIDE,
+            });
+        };
+
+        let expression = &self.channels[slot.channel as usize];
+        let master_bend = self.channels[self.zone.master_channel() as usize].bend;
+        let semitones = slot.note as f32 - 60.0 + expression.bend + master_bend;
+        Some(MpeVoiceState {
+            pitch: semitones / 12.0,
+            gate: slot.gate,
+            velocity: slot.velocity,
+            pressure: expression.pressure,
+            slide: expression.slide,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note_on(channel: u8, note: u8) -> MidiEvent {
+        MidiEvent::NoteOn { channel, note, velocity: 127 }
+    }
+
+    fn note_off(channel: u8, note: u8) -> MidiEvent {
+        MidiEvent::NoteOff { channel, note, velocity: 0 }
+    }
+
+    #[test]
+    fn test_zone_channels() {
+        let lower = MpeZone::new(MpeZoneKind::Lower, 7, 48);
+        assert_eq!(lower.master_channel(), 0);
+        assert!(!lower.is_member(0));
+        assert!(lower.is_member(1));
+        assert!(lower.is_member(7));
+        assert!(!lower.is_member(8));
+
+        let upper = MpeZone::new(MpeZoneKind::Upper, 3, 48);
+        assert_eq!(upper.master_channel(), 15);
+        assert!(upper.is_member(12));
+        assert!(upper.is_member(14));
+        assert!(!upper.is_member(11));
+        assert!(!upper.is_member(15));
+
+        // Out of range member counts are clamped
+        assert_eq!(MpeZone::new(MpeZoneKind::Lower, 0, 48).member_channels, 1);
+        assert_eq!(MpeZone::new(MpeZoneKind::Lower, 20, 48).member_channels, 15);
+    }
+
+    #[test]
+    fn test_notes_get_separate_voices() {
+        let now = Instant::now();
+        let mut mpe = MpeVoiceAllocator::new(MpeZone::default(), 0, 4);
+        assert!(mpe.handle(&note_on(1, 60), 1, now));
+        assert!(mpe.handle(&note_on(2, 64), 1, now));
+
+        let first = mpe.voice(0).unwrap();
+        let second = mpe.voice(1).unwrap();
+        assert!(first.gate && second.gate);
+        assert_eq!(first.pitch, 0.0);
+        assert!((second.pitch - 4.0 / 12.0).abs() < 1e-6);
+        assert!(!mpe.voice(2).unwrap().gate);
+        assert!(mpe.voice(4).is_none());
+    }
+
+    #[test]
+    fn test_per_note_expression() {
+        let now = Instant::now();
+        let mut mpe = MpeVoiceAllocator::new(MpeZone::default(), 0, 2);
+        mpe.handle(&note_on(1, 60), 1, now);
+        mpe.handle(&note_on(2, 67), 1, now);
+
+        // Bend the first note up a whole tone: 48 semitones over 8192
+        assert!(mpe.handle(&MidiEvent::PitchBend { channel: 1, value: 8192 / 24 }, 1, now));
+        mpe.handle(&MidiEvent::ChannelPressure { channel: 2, pressure: 127 }, 1, now);
+        mpe.handle(&MidiEvent::ControlChange { channel: 2, controller: 74, value: 0 }, 1, now);
+
+        let first = mpe.voice(0).unwrap();
+        assert!((first.pitch - 2.0 / 12.0).abs() < 1e-3, "got {}", first.pitch);
+        assert_eq!(first.pressure, 0.0);
+        assert_eq!(first.slide, 0.5);
+
+        let second = mpe.voice(1).unwrap();
+        assert!((second.pitch - 7.0 / 12.0).abs() < 1e-6);
+        assert_eq!(second.pressure, 1.0);
+        assert_eq!(second.slide, 0.0);
+    }
+
+    #[test]
+    fn test_master_bend_moves_every_note() {
+        let now = Instant::now();
+        let mut mpe = MpeVoiceAllocator::new(MpeZone::default(), 0, 2);
+        mpe.handle(&note_on(1, 60), 1, now);
+        mpe.handle(&note_on(2, 72), 1, now);
+
+        // Full master bend: +2 semitones
+        assert!(mpe.handle(&MidiEvent::PitchBend { channel: 0, value: 8191 }, 1, now));
+        assert!((mpe.voice(0).unwrap().pitch - 2.0 / 12.0).abs() < 1e-3);
+        assert!((mpe.voice(1).unwrap().pitch - 14.0 / 12.0).abs() < 1e-3);
+
+        // Notes on the master channel aren't played
+        assert!(!mpe.handle(&note_on(0, 50), 1, now));
+    }
+
+    #[test]
+    fn test_ignores_other_channels_and_ports() {
+        let now = Instant::now();
+        let zone = MpeZone::new(MpeZoneKind::Upper, 4, 48);
+        let mut mpe = MpeVoiceAllocator::new(zone, 2, 2);
+        assert!(!mpe.handle(&note_on(1, 60), 2, now));
+        assert!(!mpe.handle(&note_on(14, 60), 1, now));
+        assert!(mpe.handle(&note_on(14, 60), 2, now));
+    }
+
+    #[test]
+    fn test_expression_before_note_on() {
+        // Controllers send the initial expression just before the note
+        let now = Instant::now();
+        let mut mpe = MpeVoiceAllocator::new(MpeZone::default(), 0, 1);
+        assert!(!mpe.handle(&MidiEvent::ControlChange { channel: 3, controller: 74, value: 127 }, 1, now));
+        mpe.handle(&note_on(3, 60), 1, now);
+        assert_eq!(mpe.voice(0).unwrap().slide, 1.0);
+    }
+
+    #[test]
+    fn test_minimum_gate_time() {
+        let now = Instant::now();
+        let mut mpe = MpeVoiceAllocator::new(MpeZone::default(), 0, 1);
+        mpe.handle(&note_on(1, 60), 1, now);
+        assert!(mpe.handle(&note_off(1, 60), 1, now));
+
+        // A very short note keeps its gate for the minimum time
+        assert!(mpe.voice(0).unwrap().gate);
+        assert!(!mpe.update_gates(now + Duration::from_millis(10)));
+        assert!(mpe.update_gates(now + Duration::from_millis(40)));
+        let voice = mpe.voice(0).unwrap();
+        assert!(!voice.gate);
+        // The released voice keeps its pitch
+        assert_eq!(voice.pitch, 0.0);
+
+        // A longer note closes its gate on release
+        let later = now + Duration::from_millis(100);
+        mpe.handle(&note_on(1, 62), 1, later);
+        mpe.handle(&note_off(1, 62), 1, later + Duration::from_millis(50));
+        assert!(!mpe.voice(0).unwrap().gate);
+    }
+
+    #[test]
+    fn test_voice_allocation() {
+        let now = Instant::now();
+        let mut mpe = MpeVoiceAllocator::new(MpeZone::default(), 0, 2);
+        let later = now + Duration::from_millis(100);
+        mpe.handle(&note_on(1, 60), 1, now);
+        mpe.handle(&note_on(2, 62), 1, now);
+
+        // Released voice 1 is reused; voice 2 keeps playing
+        mpe.handle(&note_off(1, 60), 1, later);
+        mpe.handle(&note_on(3, 64), 1, later);
+        assert!((mpe.voice(0).unwrap().pitch - 4.0 / 12.0).abs() < 1e-6);
+        assert!((mpe.voice(1).unwrap().pitch - 2.0 / 12.0).abs() < 1e-6);
+
+        // All voices busy: the oldest note (62 on voice 2) is taken over
+        mpe.handle(&note_on(4, 65), 1, later);
+        assert!((mpe.voice(1).unwrap().pitch - 5.0 / 12.0).abs() < 1e-6);
+        assert!(mpe.voice(0).unwrap().gate);
+
+        // The Note Off of the stolen note doesn't release its new owner
+        assert!(!mpe.handle(&note_off(2, 62), 1, later));
+        assert!(mpe.voice(1).unwrap().gate);
+    }
+
+    #[test]
+    fn test_set_voice_count() {
+        let mut mpe = MpeVoiceAllocator::new(MpeZone::default(), 0, 0);
+        assert_eq!(mpe.voice_count(), 1);
+        mpe.set_voice_count(4);
+        assert_eq!(mpe.voice_count(), 4);
+        assert!(mpe.voice(3).is_some());
+    }
+}
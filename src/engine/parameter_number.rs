@@ -0,0 +1,175 @@
+//! NRPN and RPN parsing
+//!
+//! Registered and Non-Registered Parameter Numbers reach 16384 parameters
+//! with 14-bit values, but travel as a series of Control Changes: CC 99/98
+//! (NRPN) or 101/100 (RPN) select the parameter, then Data Entry CC 6/38
+//! or Data Increment/Decrement CC 96/97 set its value. The parser follows
+//! these per channel and reports each complete value.
+
+use super::midi_engine::MidiEvent;
+
+/// NRPN number MSB.
+const NRPN_MSB: u8 = 99;
+/// NRPN number LSB.
+const NRPN_LSB: u8 = 98;
+/// RPN number MSB.
+const RPN_MSB: u8 = 101;
+/// RPN number LSB.
+const RPN_LSB: u8 = 100;
+/// Data Entry MSB.
+const DATA_ENTRY_MSB: u8 = 6;
+/// Data Entry LSB.
+const DATA_ENTRY_LSB: u8 = 38;
+/// Data Increment.
+const DATA_INCREMENT: u8 = 96;
+/// Data Decrement.
+const DATA_DECREMENT: u8 = 97;
+/// RPN 127/127 deselects the parameter.
+const NULL_PARAMETER: u16 = 0x3FFF;
+/// Data Increment/Decrement move the value by one step of the MSB.
+const DATA_STEP: u16 = 128;
+
+/// Parameter selection and value of one channel.
+#[derive(Debug, Clone, Copy, Default)]
+struct ChannelState {
+    /// Number MSB and LSB of the NRPN being selected.
+    nrpn: (u8, u8),
+    /// Number MSB and LSB of the RPN being selected.
+    rpn: (u8, u8),
+    /// The selected parameter: registered, number (None = none selected).
+    selected: Option<(bool, u16)>,
+    /// Value of the selected parameter.
+    value: u16,
+}
+
+/// Assembles NRPN and RPN values from the Control Changes of one input.
+///
+/// Values are reported on every Data Entry MSB (with the LSB cleared, as
+/// the MIDI specification asks), Data Entry LSB and Data
+/// Increment/Decrement. The Control Changes themselves are left for the
+/// caller to pass on.
+#[derive(Debug, Clone, Default)]
+pub struct ParameterNumberParser {
+    channels: [ChannelState; 16],
+}
+
+impl ParameterNumberParser {
+    /// Creates a parser with no parameter selected.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Follow an event, returning the Parameter Number value it completes.
+    pub fn handle(&mut self, event: &MidiEvent) -> Option<MidiEvent> {
+        let MidiEvent::ControlChange { channel, controller, value } = *event else {
+            return None;
+        };
+        let state = &mut self.channels[(channel & 0x0F) as usize];
+
+        match controller {
+            NRPN_MSB | NRPN_LSB => {
+                if controller == NRPN_MSB {
+                    state.nrpn.0 = value;
+                } else {
+                    state.nrpn.1 = value;
+                }
+                state.selected = Some((false, combine(state.nrpn.0, state.nrpn.1)));
+                state.value = 0;
+                None
+            }
+            RPN_MSB | RPN_LSB => {
+                if controller == RPN_MSB {
+                    state.rpn.0 = value;
+                } else {
+                    state.rpn.1 = value;
+                }
+                let number = combine(state.rpn.0, state.rpn.1);
+                state.selected = (number != NULL_PARAMETER).then_some((true, number));
+                state.value = 0;
+                None
+            }
+            DATA_ENTRY_MSB => {
+                state.value = combine(value, 0);
+                state.report(channel)
+            }
+            DATA_ENTRY_LSB => {
+                state.value = (state.value & !0x7F) | u16::from(value & 0x7F);
+                state.report(channel)
+            }
+            DATA_INCREMENT => {
+                state.value = (state.value + DATA_STEP).min(0x3FFF);
+                state.report(channel)
+            }
+            DATA_DECREMENT => {
+                state.value = state.value.saturating_sub(DATA_STEP);
+                state.report(channel)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl ChannelState {
+    /// The value of the selected parameter as an event.
+    fn report(&self, channel: u8) -> Option<MidiEvent> {
+        let (registered, number) = self.selected?;
+        Some(MidiEvent::ParameterNumber { channel, registered, number, value: self.value })
+    }
+}
+
+/// Combine a 7-bit MSB and LSB into a 14-bit number.
+fn combine(msb: u8, lsb: u8) -> u16 {
+    (u16::from(msb & 0x7F) << 7) | u16::from(lsb & 0x7F)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cc(channel: u8, controller: u8, value: u8) -> MidiEvent {
+        MidiEvent::ControlChange { channel, controller, value }
+    }
+
+    #[test]
+    fn test_nrpn_value() {
+        let mut parser = ParameterNumberParser::new();
+        assert_eq!(parser.handle(&cc(2, NRPN_MSB, 3)), None);
+        assert_eq!(parser.handle(&cc(2, NRPN_LSB, 5)), None);
+
+        // The MSB reports with the LSB cleared, then the LSB completes the value
+        assert_eq!(
+            parser.handle(&cc(2, DATA_ENTRY_MSB, 64)),
+            Some(MidiEvent::ParameterNumber { channel: 2, registered: false, number: 389, value: 8192 })
+        );
+        assert_eq!(
+            parser.handle(&cc(2, DATA_ENTRY_LSB, 10)),
+            Some(MidiEvent::ParameterNumber { channel: 2, registered: false, number: 389, value: 8202 })
+        );
+
+        // Other channels and controllers are left alone
+        assert_eq!(parser.handle(&cc(3, DATA_ENTRY_MSB, 1)), None);
+        assert_eq!(parser.handle(&cc(2, 74, 1)), None);
+        assert_eq!(parser.handle(&MidiEvent::NoteOn { channel: 2, note: 60, velocity: 1 }), None);
+    }
+
+    #[test]
+    fn test_rpn_increment_and_null() {
+        let mut parser = ParameterNumberParser::new();
+        parser.handle(&cc(0, RPN_MSB, 0));
+        parser.handle(&cc(0, RPN_LSB, 0));
+        parser.handle(&cc(0, DATA_ENTRY_MSB, 2));
+        assert_eq!(
+            parser.handle(&cc(0, DATA_INCREMENT, 0)),
+            Some(MidiEvent::ParameterNumber { channel: 0, registered: true, number: 0, value: 384 })
+        );
+        assert_eq!(
+            parser.handle(&cc(0, DATA_DECREMENT, 0)),
+            Some(MidiEvent::ParameterNumber { channel: 0, registered: true, number: 0, value: 256 })
+        );
+
+        // RPN 127/127 deselects, so Data Entry means nothing
+        parser.handle(&cc(0, RPN_MSB, 127));
+        parser.handle(&cc(0, RPN_LSB, 127));
+        assert_eq!(parser.handle(&cc(0, DATA_ENTRY_MSB, 10)), None);
+    }
+}
@@ -0,0 +1,747 @@
+//! Recording
+//!
+//! Streams audio from the audio thread to WAV files. The audio thread copies
+//! samples into fixed-size blocks on a lock-free ring buffer; a writer thread
+//! drains the ring and does all file I/O, so the callback never waits on disk.
+//!
+//! There are two kinds of recordings:
+//! - The main recording, started and stopped from the toolbar, which taps
+//!   the master output or a single node output.
+//! - Takes of Recorder modules, started and stopped by their Record input.
+//!
+//! When the writer falls behind and the ring fills up, blocks are dropped
+//! and counted so the UI can warn that the recording has gaps.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use super::commands::{NodeId, PortIndex};
+use super::wav::WavWriter;
+
+/// Frames per stereo block on the ring buffer.
+pub const RECORD_BLOCK_FRAMES: usize = 128;
+
+/// Samples per block (room for a full stereo block).
+const RECORD_BLOCK_SAMPLES: usize = RECORD_BLOCK_FRAMES * 2;
+
+/// Default ring buffer size in blocks (about 5 seconds of stereo at 48kHz).
+pub const DEFAULT_RECORD_BUFFER_BLOCKS: usize = 2048;
+
+/// How long the writer thread sleeps when the ring buffer is empty.
+const WRITER_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Recorder modules the audio thread can track without allocating.
+///
+/// Takes of further Recorder modules are not recorded.
+const MAX_RECORDER_NODES: usize = 64;
+
+/// What the main recording taps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordSource {
+    /// The stereo signal sent to the audio device.
+    Master,
+    /// One output of a node, recorded in mono.
+    Output {
+        /// The node to record.
+        node_id: NodeId,
+        /// Index of the output among the node's outputs.
+        output_index: PortIndex,
+    },
+}
+
+impl RecordSource {
+    /// Number of channels this source records.
+    pub fn channels(&self) -> u16 {
+        match self {
+            RecordSource::Master => 2,
+            RecordSource::Output { .. } => 1,
+        }
+    }
+}
+
+/// Which recording a block belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum StreamId {
+    /// The main recording.
+    Main,
+    /// Takes of a Recorder module.
+    Node(NodeId),
+}
+
+/// A block of interleaved samples on its way to the writer thread.
+struct RecordBlock {
+    stream: StreamId,
+    /// Take number; a new take starts a new file.
+    take: u32,
+    channels: u16,
+    /// Number of frames used in `samples`.
+    frames: usize,
+    /// Whether this block ends the take.
+    last: bool,
+    samples: [f32; RECORD_BLOCK_SAMPLES],
+}
+
+/// Counters shared between the audio thread and the UI.
+#[derive(Default)]
+struct RecorderStats {
+    /// Frames captured by the current main recording.
+    frames: AtomicU64,
+    /// Frames lost because the ring buffer was full.
+    dropped: AtomicU64,
+}
+
+/// State of a Recorder module seen by the audio thread.
+struct NodeTake {
+    /// Number of the current (or last) take.
+    take: u32,
+    recording: bool,
+    /// Whether the module was present in the last processed block.
+    seen: bool,
+    /// Whether the end of the last take did not fit on the ring yet.
+    end_pending: bool,
+}
+
+/// Audio-thread side of the recorder, owned by the `AudioProcessor`.
+///
+/// REAL-TIME SAFE: pushing blocks never blocks or allocates. The map of
+/// Recorder modules is sized when the tap is created.
+pub struct RecorderTap {
+    producer: Producer<RecordBlock>,
+    stats: Arc<RecorderStats>,
+    /// Source and take number of the running main recording.
+    main: Option<(RecordSource, u32)>,
+    /// Take number and channels of a main take whose end did not fit on the ring.
+    main_end_pending: Option<(u32, u16)>,
+    nodes: HashMap<NodeId, NodeTake>,
+}
+
+impl RecorderTap {
+    fn new(producer: Producer<RecordBlock>, stats: Arc<RecorderStats>) -> Self {
+        Self {
+            producer,
+            stats,
+            main: None,
+            main_end_pending: None,
+            nodes: HashMap::with_capacity(MAX_RECORDER_NODES),
+        }
+    }
+
+    /// Start a main recording take, ending any take in progress.
+    pub fn start(&mut self, source: RecordSource, take: u32) {
+        self.stop();
+        // The writer closes the previous file when the new take arrives
+        self.main_end_pending = None;
+        self.main = Some((source, take));
+        self.stats.frames.store(0, Ordering::Relaxed);
+    }
+
+    /// End the main recording take.
+    pub fn stop(&mut self) {
+        if let Some((source, take)) = self.main.take() {
+            if !push_end(&mut self.producer, StreamId::Main, take, source.channels()) {
+                self.main_end_pending = Some((take, source.channels()));
+            }
+        }
+    }
+
+    /// The source of the running main recording.
+    pub fn source(&self) -> Option<RecordSource> {
+        self.main.map(|(source, _)| source)
+    }
+
+    /// Append one block of the main recording, one slice per channel.
+    pub fn write_main(&mut self, channels: &[&[f32]]) {
+        if let Some((_, take)) = self.main {
+            let frames = channels.first().map_or(0, |c| c.len());
+            self.stats.frames.fetch_add(frames as u64, Ordering::Relaxed);
+            push_samples(&mut self.producer, &self.stats, StreamId::Main, take, channels);
+        }
+    }
+
+    /// Record one block of every Recorder module.
+    ///
+    /// Takes `(node_id, left, right, recording)` for each module present in
+    /// the graph. Takes of modules that stopped recording or were removed
+    /// are ended. Called every block, so ends that did not fit on the ring
+    /// before are retried here.
+    pub fn write_nodes<'a>(&mut self, recordings: impl Iterator<Item = (NodeId, &'a [f32], &'a [f32], bool)>) {
+        if let Some((take, channels)) = self.main_end_pending {
+            if push_end(&mut self.producer, StreamId::Main, take, channels) {
+                self.main_end_pending = None;
+            }
+        }
+        for (&node_id, node) in &mut self.nodes {
+            if node.end_pending {
+                node.end_pending = !push_end(&mut self.producer, StreamId::Node(node_id), node.take, 2);
+            }
+            node.seen = false;
+        }
+
+        for (node_id, left, right, recording) in recordings {
+            // Inserting past the capacity reserved up front would allocate
+            if !self.nodes.contains_key(&node_id) && self.nodes.len() >= self.nodes.capacity() {
+                continue;
+            }
+            let node = self.nodes.entry(node_id).or_insert(NodeTake {
+                take: 0,
+                recording: false,
+                seen: false,
+                end_pending: false,
+            });
+            node.seen = true;
+
+            let stream = StreamId::Node(node_id);
+            match (node.recording, recording) {
+                (false, false) => {}
+                (true, false) => node.end_pending = !push_end(&mut self.producer, stream, node.take, 2),
+                (was_recording, true) => {
+                    if !was_recording {
+                        // The writer closes the previous file when the new take arrives
+                        node.take += 1;
+                        node.end_pending = false;
+                    }
+                    push_samples(&mut self.producer, &self.stats, stream, node.take, &[left, right]);
+                }
+            }
+            node.recording = recording;
+        }
+
+        let producer = &mut self.producer;
+        self.nodes.retain(|&node_id, node| {
+            if !node.seen && node.recording {
+                node.recording = false;
+                node.end_pending = !push_end(producer, StreamId::Node(node_id), node.take, 2);
+            }
+            node.seen || node.end_pending
+        });
+    }
+}
+
+/// Push samples to the writer, one slice per channel, split into blocks.
+fn push_samples(
+    producer: &mut Producer<RecordBlock>,
+    stats: &RecorderStats,
+    stream: StreamId,
+    take: u32,
+    channels: &[&[f32]],
+) {
+    let channel_count = channels.len().clamp(1, 2);
+    let frames = channels.first().map_or(0, |c| c.len());
+    let frames_per_block = RECORD_BLOCK_SAMPLES / channel_count;
+
+    let mut start = 0;
+    while start < frames {
+        let count = (frames - start).min(frames_per_block);
+        let mut block = RecordBlock {
+            stream,
+            take,
+            channels: channel_count as u16,
+            frames: count,
+            last: false,
+            samples: [0.0; RECORD_BLOCK_SAMPLES],
+        };
+        for i in 0..count {
+            for (c, channel) in channels.iter().take(channel_count).enumerate() {
+                block.samples[i * channel_count + c] = channel.get(start + i).copied().unwrap_or(0.0);
+            }
+        }
+
+        if producer.push(block).is_err() {
+            stats.dropped.fetch_add(count as u64, Ordering::Relaxed);
+        }
+        start += count;
+    }
+}
+
+/// Tell the writer a take has ended.
+///
+/// Returns `false` if the ring is full, so the caller can try again.
+fn push_end(producer: &mut Producer<RecordBlock>, stream: StreamId, take: u32, channels: u16) -> bool {
+    let block = RecordBlock {
+        stream,
+        take,
+        channels,
+        frames: 0,
+        last: true,
+        samples: [0.0; RECORD_BLOCK_SAMPLES],
+    };
+    producer.push(block).is_ok()
+}
+
+/// Results reported by the writer thread.
+#[derive(Debug, Clone)]
+pub enum RecorderEvent {
+    /// A take was written.
+    Saved {
+        /// The WAV file.
+        path: PathBuf,
+        /// Length of the take in frames.
+        frames: u64,
+    },
+    /// A file could not be created or written.
+    Failed(String),
+}
+
+/// Messages sent from the UI to the writer thread.
+enum WriterMessage {
+    /// File to write the given main recording take to.
+    Take { take: u32, path: PathBuf },
+    /// Sample rate of files created from now on.
+    SampleRate(u32),
+}
+
+/// UI side of the recorder: names takes and reports on the writer thread.
+///
+/// Dropping the recorder lets the writer finish what is on the ring buffer,
+/// then closes all open files.
+pub struct WavRecorder {
+    dir: PathBuf,
+    sample_rate: u32,
+    stats: Arc<RecorderStats>,
+    sender: Option<Sender<WriterMessage>>,
+    events: Receiver<RecorderEvent>,
+    writer: Option<thread::JoinHandle<()>>,
+    next_take: u32,
+}
+
+impl WavRecorder {
+    /// Start a writer thread saving into `dir`.
+    ///
+    /// Takes of Recorder modules are named after `session`. Returns the
+    /// recorder and the tap to hand to the `AudioProcessor`.
+    pub fn start(dir: impl Into<PathBuf>, session: &str, sample_rate: u32) -> io::Result<(Self, RecorderTap)> {
+        Self::with_capacity(dir, session, sample_rate, DEFAULT_RECORD_BUFFER_BLOCKS)
+    }
+
+    /// Like `start`, with a ring buffer of `blocks` blocks.
+    pub fn with_capacity(
+        dir: impl Into<PathBuf>,
+        session: &str,
+        sample_rate: u32,
+        blocks: usize,
+    ) -> io::Result<(Self, RecorderTap)> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+
+        let (producer, consumer) = RingBuffer::new(blocks);
+        let (sender, receiver) = mpsc::channel();
+        let (event_sender, events) = mpsc::channel();
+        let stats = Arc::new(RecorderStats::default());
+
+        let writer = Writer {
+            dir: dir.clone(),
+            session: session.to_string(),
+            sample_rate,
+            receiver,
+            paths: HashMap::new(),
+            files: HashMap::new(),
+            events: event_sender,
+        };
+        let handle = thread::Builder::new()
+            .name("recorder".to_string())
+            .spawn(move || writer.run(consumer))?;
+
+        let recorder = Self {
+            dir,
+            sample_rate,
+            stats: Arc::clone(&stats),
+            sender: Some(sender),
+            events,
+            writer: Some(handle),
+            next_take: 1,
+        };
+        Ok((recorder, RecorderTap::new(producer, stats)))
+    }
+
+    /// Directory recordings are saved to.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Sample rate recordings are written at.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Change the sample rate after the audio stream was reconfigured.
+    ///
+    /// Applies to takes started afterwards; stop recording first.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(WriterMessage::SampleRate(sample_rate));
+        }
+    }
+
+    /// Prepare a main recording take saved as `<name>.wav` (or `<name>-2.wav`
+    /// and so on if that exists).
+    ///
+    /// Returns the take number to send with `EngineCommand::StartRecording`.
+    pub fn begin_take(&mut self, name: &str) -> u32 {
+        let take = self.next_take;
+        self.next_take += 1;
+        self.stats.dropped.store(0, Ordering::Relaxed);
+
+        if let Some(sender) = &self.sender {
+            let path = unique_path(&self.dir, name);
+            let _ = sender.send(WriterMessage::Take { take, path });
+        }
+        take
+    }
+
+    /// Length of the current (or last) main recording.
+    pub fn elapsed(&self) -> Duration {
+        let frames = self.stats.frames.load(Ordering::Relaxed);
+        Duration::from_secs_f64(frames as f64 / f64::from(self.sample_rate.max(1)))
+    }
+
+    /// Frames lost since the last main recording started.
+    pub fn dropped_frames(&self) -> u64 {
+        self.stats.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Results reported by the writer thread since the last call.
+    pub fn poll_events(&self) -> Vec<RecorderEvent> {
+        self.events.try_iter().collect()
+    }
+}
+
+impl Drop for WavRecorder {
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// A file being written.
+struct OpenTake {
+    take: u32,
+    path: PathBuf,
+    /// None after a write error, so the rest of the take is skipped.
+    wav: Option<WavWriter<io::BufWriter<std::fs::File>>>,
+}
+
+/// Writer thread state.
+struct Writer {
+    dir: PathBuf,
+    session: String,
+    sample_rate: u32,
+    receiver: Receiver<WriterMessage>,
+    /// Files announced for main recording takes.
+    paths: HashMap<u32, PathBuf>,
+    files: HashMap<StreamId, OpenTake>,
+    events: Sender<RecorderEvent>,
+}
+
+impl Writer {
+    /// Writer thread loop; returns once the UI side is gone and the ring is empty.
+    fn run(mut self, mut consumer: Consumer<RecordBlock>) {
+        loop {
+            let closed = self.receive();
+
+            let mut idle = true;
+            while let Ok(block) = consumer.pop() {
+                idle = false;
+                self.handle(&block);
+            }
+
+            if closed {
+                let streams: Vec<StreamId> = self.files.keys().copied().collect();
+                for stream in streams {
+                    self.finish(stream);
+                }
+                return;
+            }
+            if idle {
+                thread::sleep(WRITER_POLL_INTERVAL);
+            }
+        }
+    }
+
+    /// Take pending messages from the UI. Returns whether the UI side is gone.
+    fn receive(&mut self) -> bool {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(WriterMessage::Take { take, path }) => {
+                    self.paths.insert(take, path);
+                }
+                Ok(WriterMessage::SampleRate(sample_rate)) => {
+                    self.sample_rate = sample_rate;
+                }
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => return true,
+            }
+        }
+    }
+
+    fn handle(&mut self, block: &RecordBlock) {
+        let is_open = self.files.get(&block.stream).map(|file| file.take == block.take);
+        if block.last && block.frames == 0 && is_open != Some(true) {
+            // The end of a take that has no file: all its blocks were
+            // dropped, or a later take already closed it
+            return;
+        }
+        // A different take means the end of the previous one was dropped
+        if is_open == Some(false) {
+            self.finish(block.stream);
+        }
+
+        if !self.files.contains_key(&block.stream) {
+            let path = match block.stream {
+                // The take's file is announced before its first block is pushed
+                StreamId::Main => self.receive_path(block.take)
+                    .unwrap_or_else(|| unique_path(&self.dir, &format!("{}-recording", self.session))),
+                StreamId::Node(node_id) => unique_path(
+                    &self.dir,
+                    &format!("{}-recorder{}-take{}", self.session, node_id, block.take),
+                ),
+            };
+            let wav = match WavWriter::create(&path, block.channels.max(1), self.sample_rate) {
+                Ok(wav) => Some(wav),
+                Err(e) => {
+                    self.fail(&path, e);
+                    None
+                }
+            };
+            self.files.insert(block.stream, OpenTake { take: block.take, path, wav });
+        }
+
+        if let Some(file) = self.files.get_mut(&block.stream) {
+            if let Some(wav) = &mut file.wav {
+                let len = (block.frames * usize::from(wav.channels())).min(RECORD_BLOCK_SAMPLES);
+                if !wav.has_room(len) {
+                    // Close the file while its header can still describe it
+                    // and skip the rest of the take
+                    let path = file.path.clone();
+                    self.stop_at_limit(block.stream);
+                    let _ = self.events.send(RecorderEvent::Failed(format!(
+                        "{}: recording stopped at the 4 GB WAV limit",
+                        path.display()
+                    )));
+                } else if let Err(e) = wav.write_samples(&block.samples[..len]) {
+                    file.wav = None;
+                    let path = file.path.clone();
+                    self.fail(&path, e);
+                }
+            }
+        }
+
+        if block.last {
+            self.finish(block.stream);
+        }
+    }
+
+    /// The file announced for a main recording take.
+    fn receive_path(&mut self, take: u32) -> Option<PathBuf> {
+        if !self.paths.contains_key(&take) {
+            self.receive();
+        }
+        self.paths.remove(&take)
+    }
+
+    /// Close the file of a stream and report it.
+    fn finish(&mut self, stream: StreamId) {
+        let Some(file) = self.files.remove(&stream) else {
+            return;
+        };
+        let Some(wav) = file.wav else {
+            return;
+        };
+
+        let frames = wav.frames();
+        match wav.finalize() {
+            Ok(_) => {
+                let _ = self.events.send(RecorderEvent::Saved { path: file.path, frames });
+            }
+            Err(e) => self.fail(&file.path, e),
+        }
+    }
+
+    /// Finalize a stream's file but keep the take open, so its remaining
+    /// blocks are dropped instead of starting a new file.
+    fn stop_at_limit(&mut self, stream: StreamId) {
+        let Some(file) = self.files.get_mut(&stream) else {
+            return;
+        };
+        let Some(wav) = file.wav.take() else {
+            return;
+        };
+
+        let path = file.path.clone();
+        let frames = wav.frames();
+        match wav.finalize() {
+            Ok(_) => {
+                let _ = self.events.send(RecorderEvent::Saved { path, frames });
+            }
+            Err(e) => self.fail(&path, e),
+        }
+    }
+
+    fn fail(&self, path: &Path, error: io::Error) {
+        let _ = self.events.send(RecorderEvent::Failed(format!("{}: {}", path.display(), error)));
+    }
+}
+
+/// `dir/<name>.wav`, or `dir/<name>-2.wav` and so on if that already exists.
+fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let mut path = dir.join(format!("{}.wav", name));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.wav", name, n));
+        n += 1;
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestDir;
+    use std::fs;
+
+
+    /// Data chunk size of a WAV file written by `WavWriter`.
+    fn data_size(path: &Path) -> u32 {
+        let bytes = fs::read(path).unwrap();
+        u32::from_le_bytes(bytes[52..56].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_main_recording() {
+        let dir = TestDir::new("recorder_main");
+        let (mut recorder, mut tap) = WavRecorder::start(dir.path(), "session", 48000).unwrap();
+
+        let take = recorder.begin_take("song");
+        tap.start(RecordSource::Master, take);
+        let left = [0.5; 300];
+        let right = [-0.5; 300];
+        tap.write_main(&[&left, &right]);
+        tap.write_main(&[&left, &right]);
+        tap.stop();
+        assert_eq!(recorder.elapsed(), Duration::from_secs_f64(600.0 / 48000.0));
+
+        drop(recorder);
+        let path = dir.join("song.wav");
+        assert_eq!(data_size(&path), 600 * 2 * 4);
+    }
+
+    #[test]
+    fn test_sample_rate_change() {
+        let dir = TestDir::new("recorder_rate");
+        let (mut recorder, mut tap) = WavRecorder::start(dir.path(), "session", 44100).unwrap();
+        recorder.set_sample_rate(96000);
+        assert_eq!(recorder.sample_rate(), 96000);
+
+        let take = recorder.begin_take("fast");
+        tap.start(RecordSource::Master, take);
+        tap.write_main(&[&[0.5; 64], &[0.5; 64]]);
+        tap.stop();
+
+        drop(recorder);
+        let bytes = fs::read(dir.join("fast.wav")).unwrap();
+        assert_eq!(u32::from_le_bytes(bytes[24..28].try_into().unwrap()), 96000);
+    }
+
+    #[test]
+    fn test_recorder_module_takes() {
+        let dir = TestDir::new("recorder_takes");
+        let (recorder, mut tap) = WavRecorder::start(dir.path(), "session", 44100).unwrap();
+        let block = [0.25; 64];
+
+        tap.write_nodes([(7, &block[..], &block[..], true)].into_iter());
+        tap.write_nodes([(7, &block[..], &block[..], false)].into_iter());
+        tap.write_nodes([(7, &block[..], &block[..], true)].into_iter());
+        // Removing the module ends its take
+        tap.write_nodes(std::iter::empty());
+
+        thread::sleep(Duration::from_millis(50));
+        let saved: Vec<_> = recorder.poll_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                RecorderEvent::Saved { path, frames } => Some((path, frames)),
+                RecorderEvent::Failed(_) => None,
+            })
+            .collect();
+        assert_eq!(saved.len(), 2);
+        assert!(saved.iter().all(|(_, frames)| *frames == 64));
+        assert!(dir.join("session-recorder7-take1.wav").exists());
+        assert!(dir.join("session-recorder7-take2.wav").exists());
+
+        drop(recorder);
+    }
+
+    #[test]
+    fn test_dropped_frames_are_counted() {
+        // A ring with room for one block and nobody draining it
+        let (producer, _consumer) = RingBuffer::new(1);
+        let stats = Arc::new(RecorderStats::default());
+        let mut tap = RecorderTap::new(producer, Arc::clone(&stats));
+
+        tap.start(RecordSource::Output { node_id: 1, output_index: 0 }, 1);
+        let mono = [0.1; RECORD_BLOCK_SAMPLES * 3];
+        tap.write_main(&[&mono]);
+
+        // Mono blocks hold twice as many frames; the first of three fits
+        assert_eq!(stats.frames.load(Ordering::Relaxed), mono.len() as u64);
+        assert_eq!(stats.dropped.load(Ordering::Relaxed), (RECORD_BLOCK_SAMPLES * 2) as u64);
+    }
+
+    #[test]
+    fn test_end_is_retried_when_ring_is_full() {
+        let (producer, mut consumer) = RingBuffer::new(1);
+        let mut tap = RecorderTap::new(producer, Arc::new(RecorderStats::default()));
+        let block = [0.25; 64];
+
+        tap.write_nodes([(7, &block[..], &block[..], true)].into_iter());
+        // The ring is full, so the end has to wait
+        tap.write_nodes([(7, &block[..], &block[..], false)].into_iter());
+        assert!(!consumer.pop().unwrap().last);
+
+        tap.write_nodes([(7, &block[..], &block[..], false)].into_iter());
+        let end = consumer.pop().unwrap();
+        assert!(end.last);
+        assert_eq!(end.take, 1);
+        tap.write_nodes(std::iter::empty());
+        assert!(consumer.pop().is_err());
+
+        // Same for the main recording
+        tap.start(RecordSource::Master, 1);
+        tap.write_main(&[&block, &block]);
+        tap.stop();
+        assert!(!consumer.pop().unwrap().last);
+        tap.write_nodes(std::iter::empty());
+        assert!(consumer.pop().unwrap().last);
+    }
+
+    #[test]
+    fn test_end_only_take_creates_no_file() {
+        let dir = TestDir::new("recorder_end_only");
+        let (recorder, mut tap) = WavRecorder::start(dir.path(), "session", 44100).unwrap();
+        // A take whose samples were all dropped: only the end arrives
+        push_end(&mut tap.producer, StreamId::Node(3), 1, 2);
+        tap.start(RecordSource::Master, 5);
+        tap.stop();
+
+        drop(recorder);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_unique_path() {
+        let dir = TestDir::new("recorder_unique");
+        fs::create_dir_all(dir.path()).unwrap();
+        assert_eq!(unique_path(dir.path(), "take"), dir.join("take.wav"));
+        fs::write(dir.join("take.wav"), b"").unwrap();
+        assert_eq!(unique_path(dir.path(), "take"), dir.join("take-2.wav"));
+    }
+}
@@ -0,0 +1,180 @@
+//! Output Routing
+//!
+//! Maps the signals leaving the patch onto the channels of the audio
+//! device. There are two kinds of sources: the stereo master (the sum of
+//! all Audio Output modules) and the channels of Multi Output modules.
+//! Each source can be sent to any number of device channels, and sources
+//! sent to the same channel are summed.
+
+/// Maximum number of device channels that can be routed to.
+pub const MAX_OUTPUT_CHANNELS: usize = 16;
+
+/// Number of channels of the Multi Output module.
+pub const MULTI_OUTPUT_CHANNELS: usize = 8;
+
+/// Number of routing sources: master left and right, then the multi channels.
+pub const ROUTING_SOURCES: usize = 2 + MULTI_OUTPUT_CHANNELS;
+
+/// Index of the master left source.
+pub const SOURCE_MASTER_LEFT: usize = 0;
+
+/// Index of the master right source.
+pub const SOURCE_MASTER_RIGHT: usize = 1;
+
+/// Index of the first Multi Output channel source.
+pub const SOURCE_MULTI: usize = 2;
+
+/// Which device channels each source is sent to.
+///
+/// Stored as one channel bitmask per source so it can be sent to the audio
+/// thread by value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputRouting {
+    routes: [u16; ROUTING_SOURCES],
+}
+
+impl Default for OutputRouting {
+    /// Master left and right on channels 1 and 2, Multi Output channel N on
+    /// device channel N.
+    fn default() -> Self {
+        let mut routing = Self::empty();
+        routing.set(SOURCE_MASTER_LEFT, 0, true);
+        routing.set(SOURCE_MASTER_RIGHT, 1, true);
+        for channel in 0..MULTI_OUTPUT_CHANNELS {
+            routing.set(SOURCE_MULTI + channel, channel, true);
+        }
+        routing
+    }
+}
+
+impl OutputRouting {
+    /// A routing that sends nothing anywhere.
+    pub fn empty() -> Self {
+        Self {
+            routes: [0; ROUTING_SOURCES],
+        }
+    }
+
+    /// The channel bitmask of each source, for saving the routing.
+    pub fn masks(&self) -> [u16; ROUTING_SOURCES] {
+        self.routes
+    }
+
+    /// Rebuild a routing saved with `masks`. Missing sources route nowhere
+    /// and extra ones are ignored.
+    pub fn from_masks(masks: &[u16]) -> Self {
+        let mut routing = Self::empty();
+        for (route, mask) in routing.routes.iter_mut().zip(masks) {
+            *route = *mask;
+        }
+        routing
+    }
+
+    /// Display name of a source.
+    pub fn source_name(source: usize) -> String {
+        match source {
+            SOURCE_MASTER_LEFT => "Master L".to_string(),
+            SOURCE_MASTER_RIGHT => "Master R".to_string(),
+            _ => format!("Multi {}", source - SOURCE_MULTI + 1),
+        }
+    }
+
+    /// Whether `source` is sent to device channel `channel` (0-based).
+    pub fn is_routed(&self, source: usize, channel: usize) -> bool {
+        channel < MAX_OUTPUT_CHANNELS
+            && self.routes.get(source).is_some_and(|mask| mask & (1 << channel) != 0)
+    }
+
+    /// Send `source` to device channel `channel` (0-based), or stop sending it.
+    pub fn set(&mut self, source: usize, channel: usize, routed: bool) {
+        if channel >= MAX_OUTPUT_CHANNELS {
+            return;
+        }
+        if let Some(mask) = self.routes.get_mut(source) {
+            if routed {
+                *mask |= 1 << channel;
+            } else {
+                *mask &= !(1 << channel);
+            }
+        }
+    }
+
+    /// Mix the sources into an interleaved device buffer.
+    ///
+    /// `sources` holds one buffer per source, in source order. The output is
+    /// overwritten; channels nothing is routed to are silent.
+    pub fn render(&self, sources: &[Vec<f32>], output: &mut [f32], channels: usize) {
+        output.fill(0.0);
+        if channels == 0 {
+            return;
+        }
+
+        for (mask, samples) in self.routes.iter().zip(sources) {
+            if *mask == 0 {
+                continue;
+            }
+            for channel in (0..channels.min(MAX_OUTPUT_CHANNELS)).filter(|c| mask & (1 << c) != 0) {
+                for (frame, sample) in output.chunks_mut(channels).zip(samples) {
+                    frame[channel] += sample;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_routing() {
+        let routing = OutputRouting::default();
+        assert!(routing.is_routed(SOURCE_MASTER_LEFT, 0));
+        assert!(routing.is_routed(SOURCE_MASTER_RIGHT, 1));
+        assert!(!routing.is_routed(SOURCE_MASTER_LEFT, 2));
+        assert!(routing.is_routed(SOURCE_MULTI + 3, 3));
+        assert!(!routing.is_routed(SOURCE_MULTI, MAX_OUTPUT_CHANNELS));
+        assert_eq!(OutputRouting::source_name(SOURCE_MULTI + 7), "Multi 8");
+    }
+
+    #[test]
+    fn test_render_sums_sources() {
+        let mut routing = OutputRouting::empty();
+        routing.set(SOURCE_MASTER_LEFT, 0, true);
+        routing.set(SOURCE_MASTER_RIGHT, 1, true);
+        routing.set(SOURCE_MULTI, 0, true);
+        routing.set(SOURCE_MULTI + 1, 2, true);
+        // Routed beyond the device's channels: ignored
+        routing.set(SOURCE_MULTI + 2, 5, true);
+
+        let mut sources = vec![vec![0.0; 2]; ROUTING_SOURCES];
+        sources[SOURCE_MASTER_LEFT] = vec![0.5, 0.5];
+        sources[SOURCE_MASTER_RIGHT] = vec![-0.5, -0.5];
+        sources[SOURCE_MULTI] = vec![0.25, 0.25];
+        sources[SOURCE_MULTI + 1] = vec![1.0, 1.0];
+        sources[SOURCE_MULTI + 2] = vec![1.0, 1.0];
+
+        let mut output = vec![9.0; 8];
+        routing.render(&sources, &mut output, 4);
+        assert_eq!(output, vec![0.75, -0.5, 1.0, 0.0, 0.75, -0.5, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_masks_round_trip() {
+        let mut routing = OutputRouting::default();
+        routing.set(SOURCE_MULTI + 4, 12, true);
+        assert_eq!(OutputRouting::from_masks(&routing.masks()), routing);
+
+        let partial = OutputRouting::from_masks(&[0b10]);
+        assert!(partial.is_routed(SOURCE_MASTER_LEFT, 1));
+        assert!(!partial.is_routed(SOURCE_MASTER_RIGHT, 1));
+    }
+
+    #[test]
+    fn test_unrouting_a_channel() {
+        let mut routing = OutputRouting::default();
+        routing.set(SOURCE_MASTER_LEFT, 0, false);
+        assert!(!routing.is_routed(SOURCE_MASTER_LEFT, 0));
+        assert!(routing.is_routed(SOURCE_MASTER_RIGHT, 1));
+    }
+}
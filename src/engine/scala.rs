@@ -0,0 +1,332 @@
+//! Scala scale and keyboard mapping files
+//!
+//! Reads scales in the Scala `.scl` format and keyboard mappings in the
+//! `.kbm` format, and combines them into a `Tuning` for the note modules.
+//!
+//! A scale lists the pitches of its degrees above the unison, in cents
+//! (values with a period) or as ratios; the last degree is the period the
+//! scale repeats at. A keyboard mapping says which key plays which degree,
+//! and which key sounds at which frequency. Without a mapping, middle C
+//! plays degree 0 at 261.63 Hz and every key plays the next degree.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::dsp::{Tuning, MIDDLE_C_HZ};
+
+/// Error type for reading Scala files.
+#[derive(Debug)]
+pub enum ScalaError {
+    /// Reading the file failed.
+    Io(io::Error),
+    /// The file ends before this field.
+    Missing(&'static str),
+    /// A line couldn't be read as the value expected there.
+    Invalid { line: usize, text: String },
+    /// The scale has no degrees.
+    EmptyScale,
+}
+
+impl fmt::Display for ScalaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScalaError::Io(e) => write!(f, "Scala file error: {}", e),
+            ScalaError::Missing(field) => write!(f, "Scala file is missing the {}", field),
+            ScalaError::Invalid { line, text } => write!(f, "Invalid value on line {}: \"{}\"", line, text),
+            ScalaError::EmptyScale => write!(f, "Scale has no notes"),
+        }
+    }
+}
+
+impl std::error::Error for ScalaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScalaError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ScalaError {
+    fn from(e: io::Error) -> Self {
+        ScalaError::Io(e)
+    }
+}
+
+/// The lines of a Scala file that aren't comments, with their line numbers.
+fn data_lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.starts_with('!'))
+        .map(|(index, line)| (index + 1, line.trim()))
+}
+
+/// Parse the first word of a line, or fail with the line.
+fn parse_field<T: std::str::FromStr>((line, text): (usize, &str)) -> Result<T, ScalaError> {
+    text.split_whitespace()
+        .next()
+        .and_then(|word| word.parse().ok())
+        .ok_or_else(|| ScalaError::Invalid { line, text: text.to_string() })
+}
+
+/// A scale read from a `.scl` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scale {
+    /// Description line of the file.
+    pub description: String,
+    /// Pitch of each degree above the unison in cents; the last is the period.
+    pub degrees: Vec<f64>,
+}
+
+impl Scale {
+    /// Parse the text of a `.scl` file.
+    pub fn parse(text: &str) -> Result<Self, ScalaError> {
+        let mut lines = data_lines(text);
+        let description = lines.next().ok_or(ScalaError::Missing("description"))?.1.to_string();
+        let count: usize = parse_field(lines.next().ok_or(ScalaError::Missing("note count"))?)?;
+
+        let degrees = (0..count)
+            .map(|_| parse_pitch(lines.next().ok_or(ScalaError::Missing("notes"))?))
+            .collect::<Result<Vec<_>, _>>()?;
+        if degrees.is_empty() {
+            return Err(ScalaError::EmptyScale);
+        }
+        Ok(Self { description, degrees })
+    }
+
+    /// Read a `.scl` file.
+    pub fn load(path: &Path) -> Result<Self, ScalaError> {
+        Self::parse(&String::from_utf8_lossy(&fs::read(path)?))
+    }
+
+    /// Pitch of a degree in cents, counting on through further periods.
+    pub fn cents(&self, degree: i32) -> f64 {
+        let size = self.degrees.len() as i32;
+        let period = self.degrees[self.degrees.len() - 1];
+        let step = degree.rem_euclid(size);
+        let base = if step == 0 { 0.0 } else { self.degrees[step as usize - 1] };
+        base + degree.div_euclid(size) as f64 * period
+    }
+}
+
+/// Parse a pitch line: cents if it has a period, otherwise a ratio.
+fn parse_pitch((line, text): (usize, &str)) -> Result<f64, ScalaError> {
+    let invalid = || ScalaError::Invalid { line, text: text.to_string() };
+    let word = text.split_whitespace().next().ok_or_else(invalid)?;
+
+    if word.contains('.') {
+        return word.parse().map_err(|_| invalid());
+    }
+    let (numerator, denominator) = word.split_once('/').unwrap_or((word, "1"));
+    let numerator: f64 = numerator.parse().map_err(|_| invalid())?;
+    let denominator: f64 = denominator.parse().map_err(|_| invalid())?;
+    if numerator <= 0.0 || denominator <= 0.0 {
+        return Err(invalid());
+    }
+    Ok(1200.0 * (numerator / denominator).log2())
+}
+
+/// A keyboard mapping read from a `.kbm` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardMapping {
+    /// Number of keys the mapping pattern repeats after (0 = every key plays
+    /// the next degree).
+    pub size: usize,
+    /// First note to tune; notes outside the range are left unmapped.
+    pub first_note: u8,
+    /// Last note to tune.
+    pub last_note: u8,
+    /// Note playing degree 0 of the scale.
+    pub middle_note: u8,
+    /// Note whose frequency is given.
+    pub reference_note: u8,
+    /// Frequency of the reference note in Hz.
+    pub reference_frequency: f64,
+    /// Degree that is the period of the pattern (0 = the scale's period).
+    pub octave_degree: usize,
+    /// Degree each key of the pattern plays, None for unmapped keys.
+    pub keys: Vec<Option<usize>>,
+}
+
+impl Default for KeyboardMapping {
+    fn default() -> Self {
+        Self {
+            size: 0,
+            first_note: 0,
+            last_note: 127,
+            middle_note: 60,
+            reference_note: 60,
+            reference_frequency: MIDDLE_C_HZ,
+            octave_degree: 0,
+            keys: Vec::new(),
+        }
+    }
+}
+
+impl KeyboardMapping {
+    /// Parse the text of a `.kbm` file.
+    pub fn parse(text: &str) -> Result<Self, ScalaError> {
+        let mut lines = data_lines(text).filter(|(_, line)| !line.is_empty());
+        let mut field = |name| lines.next().ok_or(ScalaError::Missing(name));
+
+        let size = parse_field(field("map size")?)?;
+        let first_note = parse_field(field("first note")?)?;
+        let last_note = parse_field(field("last note")?)?;
+        let middle_note = parse_field(field("middle note")?)?;
+        let reference_note = parse_field(field("reference note")?)?;
+        let reference_frequency = parse_field(field("reference frequency")?)?;
+        let octave_degree = parse_field(field("octave degree")?)?;
+
+        let mut keys = Vec::with_capacity(size);
+        for (line, text) in lines.take(size) {
+            if text.starts_with(['x', 'X']) {
+                keys.push(None);
+            } else {
+                keys.push(Some(parse_field((line, text))?));
+            }
+        }
+        // Keys missing at the end of the file are unmapped
+        keys.resize(size, None);
+
+        Ok(Self { size, first_note, last_note, middle_note, reference_note, reference_frequency, octave_degree, keys })
+    }
+
+    /// Read a `.kbm` file.
+    pub fn load(path: &Path) -> Result<Self, ScalaError> {
+        Self::parse(&String::from_utf8_lossy(&fs::read(path)?))
+    }
+
+    /// Pitch of a note in cents above degree 0, or None if it is unmapped.
+    fn cents(&self, scale: &Scale, note: u8) -> Option<f64> {
+        if note < self.first_note || note > self.last_note {
+            return None;
+        }
+
+        let offset = note as i32 - self.middle_note as i32;
+        if self.size == 0 {
+            return Some(scale.cents(offset));
+        }
+        let key = self.keys[offset.rem_euclid(self.size as i32) as usize]?;
+        let period = match self.octave_degree {
+            0 => scale.degrees[scale.degrees.len() - 1],
+            degree => scale.cents(degree as i32),
+        };
+        Some(scale.cents(key as i32) + offset.div_euclid(self.size as i32) as f64 * period)
+    }
+
+    /// The V/Oct tuning of `scale` played through this mapping.
+    ///
+    /// If the reference note itself is unmapped, the reference frequency is
+    /// given to degree 0 instead.
+    pub fn tuning(&self, scale: &Scale) -> Tuning {
+        let reference_cents = self.cents(scale, self.reference_note).unwrap_or(0.0);
+        let reference_voct = (self.reference_frequency / MIDDLE_C_HZ).log2();
+        let pitches = std::array::from_fn(|note| {
+            self.cents(scale, note as u8)
+                .map(|cents| (reference_voct + (cents - reference_cents) / 1200.0) as f32)
+        });
+        Tuning::from_pitches(&pitches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PYTHAGOREAN: &str = "! pyth.scl
+!
+Pythagorean pentatonic
+ 5
+!
+ 9/8
+ 81/64
+ 3/2
+ 27/16
+ 2/1   octave
+";
+
+    #[test]
+    fn test_parse_scale() {
+        let scale = Scale::parse(PYTHAGOREAN).unwrap();
+        assert_eq!(scale.description, "Pythagorean pentatonic");
+        assert_eq!(scale.degrees.len(), 5);
+        assert!((scale.degrees[0] - 203.91).abs() < 0.01);
+        assert!((scale.degrees[4] - 1200.0).abs() < 1e-9);
+
+        // Degrees continue through further periods in both directions
+        assert!((scale.cents(0) - 0.0).abs() < 1e-9);
+        assert!((scale.cents(6) - 1403.91).abs() < 0.01);
+        assert!((scale.cents(-1) - -294.13).abs() < 0.01);
+
+        // Cents, integers as ratios, and an empty description
+        let scale = Scale::parse("\n2\n100.0\n2\n").unwrap();
+        assert_eq!(scale.description, "");
+        assert_eq!(scale.degrees, vec![100.0, 1200.0]);
+
+        assert!(matches!(Scale::parse("Empty\n0\n"), Err(ScalaError::EmptyScale)));
+        assert!(matches!(Scale::parse("Short\n3\n100.0\n"), Err(ScalaError::Missing(_))));
+        assert!(matches!(Scale::parse("Bad\n1\nabc\n"), Err(ScalaError::Invalid { line: 3, .. })));
+    }
+
+    #[test]
+    fn test_scale_without_mapping() {
+        // 19-TET: every key is one nineteenth of an octave
+        let scale = Scale::parse("19-TET\n1\n63.157894\n").unwrap();
+        let tuning = KeyboardMapping::default().tuning(&scale);
+        assert!((tuning.voct(60.0) - 0.0).abs() < 1e-6);
+        assert!((tuning.voct(79.0) - 1.0).abs() < 1e-5);
+        assert!((tuning.voct(41.0) - -1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_keyboard_mapping() {
+        // The pentatonic scale on the white keys from C, A4 at 440 Hz, and
+        // the black keys unmapped
+        let kbm = "! white keys
+12
+0
+127
+60
+69
+440.0
+5
+0
+x
+1
+x
+2
+x
+x
+3
+x
+4
+x
+";
+        let mapping = KeyboardMapping::parse(kbm).unwrap();
+        assert_eq!(mapping.size, 12);
+        assert_eq!(mapping.keys.len(), 12);
+        assert_eq!(mapping.keys[2], Some(1));
+        assert_eq!(mapping.keys[11], None);
+
+        let scale = Scale::parse(PYTHAGOREAN).unwrap();
+        let tuning = mapping.tuning(&scale);
+        assert!((tuning.frequency(69) - 440.0).abs() < 1e-3);
+        // A4 is degree 4 (27/16), so C4 is 440 * 16/27
+        assert!((tuning.frequency(60) - 260.7407).abs() < 1e-3);
+        assert!((tuning.frequency(72) - 521.4815).abs() < 1e-3);
+        // D4 is 9/8 above C4, and C#4 plays C4
+        assert!((tuning.frequency(62) - 293.3333).abs() < 1e-3);
+        assert_eq!(tuning.voct(61.0), tuning.voct(60.0));
+    }
+
+    #[test]
+    fn test_parse_mapping_errors() {
+        assert!(matches!(KeyboardMapping::parse("12\n0\n127\n"), Err(ScalaError::Missing("middle note"))));
+        assert!(matches!(
+            KeyboardMapping::parse("0\n0\n127\n60\n60\nfast\n0\n"),
+            Err(ScalaError::Invalid { line: 6, .. })
+        ));
+    }
+}
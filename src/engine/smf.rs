@@ -0,0 +1,660 @@
+//! Standard MIDI Files
+//!
+//! Reads and writes Standard MIDI Files (SMF) of format 0 and 1. Channel
+//! messages become `MidiEvent`s and tempo changes are kept for the tempo
+//! map; system exclusive messages and other meta events are skipped when
+//! reading.
+//!
+//! `MidiFile::note_sequence` merges the notes of all tracks into one
+//! timeline, ready to be played back on the audio thread by the MIDI File
+//! Player module.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::midi_engine::MidiEvent;
+use crate::dsp::{NoteSequence, SequenceNote};
+
+/// Tempo of a file before its first tempo event: 120 BPM, in microseconds
+/// per quarter note.
+pub const DEFAULT_TEMPO_US: u32 = 500_000;
+
+/// Error type for reading MIDI files.
+#[derive(Debug)]
+pub enum SmfError {
+    /// Reading or writing the file failed.
+    Io(io::Error),
+    /// The data doesn't start with an SMF header.
+    NotMidiFile,
+    /// Format 2 (independent sequences) isn't supported.
+    UnsupportedFormat(u16),
+    /// SMPTE time division isn't supported, only ticks per quarter note.
+    SmpteTiming,
+    /// The data ends in the middle of a chunk or event.
+    Truncated,
+    /// A data byte appeared where a status byte was expected.
+    MissingStatus,
+}
+
+impl fmt::Display for SmfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SmfError::Io(e) => write!(f, "MIDI file error: {}", e),
+            SmfError::NotMidiFile => write!(f, "Not a Standard MIDI File"),
+            SmfError::UnsupportedFormat(format) => write!(f, "MIDI file format {} is not supported", format),
+            SmfError::SmpteTiming => write!(f, "MIDI files with SMPTE timing are not supported"),
+            SmfError::Truncated => write!(f, "MIDI file is truncated"),
+            SmfError::MissingStatus => write!(f, "MIDI file has a data byte without a status byte"),
+        }
+    }
+}
+
+impl std::error::Error for SmfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SmfError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SmfError {
+    fn from(e: io::Error) -> Self {
+        SmfError::Io(e)
+    }
+}
+
+/// What happens at a point in a track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackEventKind {
+    /// A channel message.
+    Midi(MidiEvent),
+    /// A tempo change, in microseconds per quarter note.
+    Tempo(u32),
+}
+
+/// An event of a track at an absolute time in ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrackEvent {
+    /// Ticks from the start of the track.
+    pub tick: u64,
+    /// The event.
+    pub kind: TrackEventKind,
+}
+
+impl TrackEvent {
+    /// A channel message at `tick`.
+    pub fn midi(tick: u64, event: MidiEvent) -> Self {
+        Self { tick, kind: TrackEventKind::Midi(event) }
+    }
+
+    /// A tempo change at `tick`.
+    pub fn tempo(tick: u64, us_per_quarter: u32) -> Self {
+        Self { tick, kind: TrackEventKind::Tempo(us_per_quarter) }
+    }
+}
+
+/// One track of a MIDI file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MidiTrack {
+    /// Track name meta event, if any.
+    pub name: Option<String>,
+    /// Events in tick order.
+    pub events: Vec<TrackEvent>,
+    /// Tick of the End of Track event; the track lasts at least until its
+    /// last event.
+    pub end_tick: u64,
+}
+
+impl MidiTrack {
+    /// An empty track with the given name.
+    pub fn named(name: impl Into<String>) -> Self {
+        Self { name: Some(name.into()), ..Self::default() }
+    }
+
+    /// Length of the track in ticks.
+    pub fn length_ticks(&self) -> u64 {
+        let last = self.events.last().map_or(0, |event| event.tick);
+        self.end_tick.max(last)
+    }
+}
+
+/// A Standard MIDI File.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MidiFile {
+    /// SMF format: 0 (a single track) or 1 (simultaneous tracks).
+    pub format: u16,
+    /// Resolution in ticks per quarter note.
+    pub ticks_per_quarter: u16,
+    /// The tracks. In format 1 files the first is usually the tempo track.
+    pub tracks: Vec<MidiTrack>,
+}
+
+impl MidiFile {
+    /// An empty file of the given format and resolution.
+    pub fn new(format: u16, ticks_per_quarter: u16) -> Self {
+        Self { format, ticks_per_quarter: ticks_per_quarter.max(1), tracks: Vec::new() }
+    }
+
+    /// Read a MIDI file from disk.
+    pub fn load(path: &Path) -> Result<Self, SmfError> {
+        Self::parse(&fs::read(path)?)
+    }
+
+    /// Write the file to disk.
+    pub fn save(&self, path: &Path) -> Result<(), SmfError> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Parse a MIDI file from its bytes.
+    ///
+    /// Chunks other than the header and tracks are skipped.
+    pub fn parse(data: &[u8]) -> Result<Self, SmfError> {
+        let mut reader = Reader::new(data);
+        if reader.bytes(4).ok() != Some(b"MThd".as_slice()) {
+            return Err(SmfError::NotMidiFile);
+        }
+        let header = reader.chunk_body()?;
+        let mut header = Reader::new(header);
+        let format = header.u16()?;
+        let _track_count = header.u16()?;
+        let division = header.u16()?;
+
+        if format > 1 {
+            return Err(SmfError::UnsupportedFormat(format));
+        }
+        if division & 0x8000 != 0 {
+            return Err(SmfError::SmpteTiming);
+        }
+
+        let mut file = Self::new(format, division);
+        while !reader.is_empty() {
+            let id = reader.bytes(4)?;
+            let body = reader.chunk_body()?;
+            if id == b"MTrk" {
+                file.tracks.push(parse_track(body)?);
+            }
+        }
+        Ok(file)
+    }
+
+    /// Encode the file as SMF bytes, using running status.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(b"MThd");
+        out.extend_from_slice(&6u32.to_be_bytes());
+        out.extend_from_slice(&self.format.to_be_bytes());
+        out.extend_from_slice(&(self.tracks.len() as u16).to_be_bytes());
+        out.extend_from_slice(&self.ticks_per_quarter.to_be_bytes());
+
+        for track in &self.tracks {
+            let body = write_track(track);
+            out.extend_from_slice(b"MTrk");
+            out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            out.extend_from_slice(&body);
+        }
+        out
+    }
+
+    /// Length of the longest track in ticks.
+    pub fn length_ticks(&self) -> u64 {
+        self.tracks.iter().map(MidiTrack::length_ticks).max().unwrap_or(0)
+    }
+
+    /// The tempo map built from the tempo events of all tracks.
+    pub fn tempo_map(&self) -> TempoMap {
+        let tempos = self.tracks.iter().flat_map(|track| {
+            track.events.iter().filter_map(|event| match event.kind {
+                TrackEventKind::Tempo(us_per_quarter) => Some((event.tick, us_per_quarter)),
+                TrackEventKind::Midi(_) => None,
+            })
+        });
+        TempoMap::new(self.ticks_per_quarter, tempos)
+    }
+
+    /// Merge the notes of all tracks into one timeline.
+    ///
+    /// Other channel messages are left out.
+    pub fn note_sequence(&self) -> NoteSequence {
+        let tempo_map = self.tempo_map();
+        let ticks_per_quarter = self.ticks_per_quarter.max(1) as f64;
+        let mut notes: Vec<SequenceNote> = self
+            .tracks
+            .iter()
+            .enumerate()
+            .flat_map(|(index, track)| track.events.iter().map(move |event| (index, event)))
+            .filter_map(|(index, event)| {
+                let (channel, note, velocity) = match event.kind {
+                    TrackEventKind::Midi(MidiEvent::NoteOn { channel, note, velocity }) => (channel, note, velocity),
+                    TrackEventKind::Midi(MidiEvent::NoteOff { channel, note, .. }) => (channel, note, 0),
+                    _ => return None,
+                };
+                Some(SequenceNote {
+                    seconds: tempo_map.seconds_at(event.tick),
+                    beats: event.tick as f64 / ticks_per_quarter,
+                    track: index as u16,
+                    channel,
+                    note,
+                    velocity,
+                })
+            })
+            .collect();
+        // Stable, so notes at the same time keep their order within a track
+        notes.sort_by(|a, b| a.beats.total_cmp(&b.beats));
+
+        let length_ticks = self.length_ticks();
+        NoteSequence {
+            notes,
+            length_seconds: tempo_map.seconds_at(length_ticks),
+            length_beats: length_ticks as f64 / ticks_per_quarter,
+            tracks: self.tracks.len(),
+        }
+    }
+}
+
+/// Converts tick positions to seconds, following the tempo changes of a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoMap {
+    /// Resolution in ticks per quarter note.
+    ticks_per_quarter: u16,
+    /// Tempo changes in tick order: (tick, seconds at that tick, microseconds
+    /// per quarter note from there). Always starts at tick 0.
+    changes: Vec<(u64, f64, u32)>,
+}
+
+impl TempoMap {
+    /// Build a tempo map from (tick, microseconds per quarter note) changes.
+    ///
+    /// The tempo before the first change is `DEFAULT_TEMPO_US`.
+    pub fn new(ticks_per_quarter: u16, tempos: impl IntoIterator<Item = (u64, u32)>) -> Self {
+        let mut tempos: Vec<(u64, u32)> = tempos.into_iter().filter(|&(_, tempo)| tempo > 0).collect();
+        tempos.sort_by_key(|&(tick, _)| tick);
+
+        let ticks_per_quarter = ticks_per_quarter.max(1);
+        let mut changes = vec![(0, 0.0, DEFAULT_TEMPO_US)];
+        for (tick, tempo) in tempos {
+            let (last_tick, last_seconds, last_tempo) = *changes.last().unwrap_or(&(0, 0.0, DEFAULT_TEMPO_US));
+            let seconds = last_seconds + ticks_to_seconds(tick - last_tick, last_tempo, ticks_per_quarter);
+            if tick == last_tick {
+                // A later change at the same tick replaces the earlier one
+                changes.pop();
+            }
+            changes.push((tick, seconds, tempo));
+        }
+        Self { ticks_per_quarter, changes }
+    }
+
+    /// Time of `tick` in seconds from the start.
+    pub fn seconds_at(&self, tick: u64) -> f64 {
+        let index = self.changes.partition_point(|&(change, _, _)| change <= tick).saturating_sub(1);
+        let (change_tick, seconds, tempo) = self.changes[index];
+        seconds + ticks_to_seconds(tick - change_tick, tempo, self.ticks_per_quarter)
+    }
+
+    /// Tempo at `tick` in beats per minute.
+    pub fn tempo_bpm_at(&self, tick: u64) -> f32 {
+        let index = self.changes.partition_point(|&(change, _, _)| change <= tick).saturating_sub(1);
+        (60_000_000.0 / self.changes[index].2 as f64) as f32
+    }
+}
+
+/// Duration of `ticks` at a tempo of `us_per_quarter`, in seconds.
+fn ticks_to_seconds(ticks: u64, us_per_quarter: u32, ticks_per_quarter: u16) -> f64 {
+    ticks as f64 * us_per_quarter as f64 / (ticks_per_quarter as f64 * 1_000_000.0)
+}
+
+/// Reads big-endian values and variable-length quantities from a byte slice.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn u8(&mut self) -> Result<u8, SmfError> {
+        let byte = *self.data.get(self.pos).ok_or(SmfError::Truncated)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn u16(&mut self) -> Result<u16, SmfError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], SmfError> {
+        let end = self.pos.checked_add(len).ok_or(SmfError::Truncated)?;
+        let bytes = self.data.get(self.pos..end).ok_or(SmfError::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// A chunk's 32-bit length followed by that many bytes.
+    fn chunk_body(&mut self) -> Result<&'a [u8], SmfError> {
+        let len = self.bytes(4)?;
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]);
+        self.bytes(len as usize)
+    }
+
+    /// A variable-length quantity: 7 bits per byte, most significant first,
+    /// high bit set on all but the last byte. At most 4 bytes.
+    fn vlq(&mut self) -> Result<u32, SmfError> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let byte = self.u8()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Ok(value)
+    }
+}
+
+/// Parse the body of an MTrk chunk.
+fn parse_track(data: &[u8]) -> Result<MidiTrack, SmfError> {
+    let mut reader = Reader::new(data);
+    let mut track = MidiTrack::default();
+    let mut tick = 0u64;
+    let mut running_status: Option<u8> = None;
+
+    while !reader.is_empty() {
+        tick += reader.vlq()? as u64;
+        let byte = reader.u8()?;
+
+        match byte {
+            0xFF => {
+                // Meta event: type, length, data
+                let kind = reader.u8()?;
+                let len = reader.vlq()? as usize;
+                let data = reader.bytes(len)?;
+                running_status = None;
+                match kind {
+                    0x03 if track.name.is_none() => {
+                        track.name = Some(String::from_utf8_lossy(data).into_owned());
+                    }
+                    0x51 if len == 3 => {
+                        let tempo = u32::from_be_bytes([0, data[0], data[1], data[2]]);
+                        track.events.push(TrackEvent::tempo(tick, tempo));
+                    }
+                    0x2F => {
+                        track.end_tick = tick;
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            0xF0 | 0xF7 => {
+                // System exclusive: length, data
+                let len = reader.vlq()? as usize;
+                reader.bytes(len)?;
+                running_status = None;
+            }
+            _ => {
+                // A data byte reuses the last status byte
+                let (status, first_data) = if byte < 0x80 {
+                    (running_status.ok_or(SmfError::MissingStatus)?, Some(byte))
+                } else {
+                    running_status = Some(byte);
+                    (byte, None)
+                };
+
+                let data_len = match status & 0xF0 {
+                    0xC0 | 0xD0 => 1,
+                    0xF0 => return Err(SmfError::MissingStatus),
+                    _ => 2,
+                };
+                let mut message = [status, 0, 0];
+                for (i, slot) in message[1..=data_len].iter_mut().enumerate() {
+                    *slot = match (i, first_data) {
+                        (0, Some(data)) => data,
+                        _ => reader.u8()?,
+                    };
+                }
+                if let Some(event) = MidiEvent::from_bytes(&message[..=data_len]) {
+                    track.events.push(TrackEvent::midi(tick, event));
+                }
+            }
+        }
+    }
+
+    track.end_tick = track.end_tick.max(tick);
+    Ok(track)
+}
+
+/// Encode a track as the body of an MTrk chunk.
+fn write_track(track: &MidiTrack) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut last_tick = 0u64;
+    let mut running_status: Option<u8> = None;
+
+    if let Some(name) = &track.name {
+        write_vlq(&mut out, 0);
+        out.extend_from_slice(&[0xFF, 0x03]);
+        write_vlq(&mut out, name.len() as u32);
+        out.extend_from_slice(name.as_bytes());
+    }
+
+    let mut events: Vec<&TrackEvent> = track.events.iter().collect();
+    events.sort_by_key(|event| event.tick);
+
+    for event in events {
+        let (bytes, len) = match event.kind {
+            TrackEventKind::Midi(midi) => midi.to_bytes(),
+            TrackEventKind::Tempo(tempo) => {
+                write_delta(&mut out, &mut last_tick, event.tick);
+                let tempo = tempo.min(0xFF_FFFF).to_be_bytes();
+                out.extend_from_slice(&[0xFF, 0x51, 0x03, tempo[1], tempo[2], tempo[3]]);
+                running_status = None;
+                continue;
+            }
+        };
+        // Clock, transport and song position have no place in a file, and
+        // Parameter Number values are stored as their Control Changes
+        if len == 0 || bytes[0] >= 0xF0 {
+            continue;
+        }
+
+        write_delta(&mut out, &mut last_tick, event.tick);
+        if running_status != Some(bytes[0]) {
+            out.push(bytes[0]);
+            running_status = Some(bytes[0]);
+        }
+        out.extend_from_slice(&bytes[1..len]);
+    }
+
+    write_delta(&mut out, &mut last_tick, track.length_ticks());
+    out.extend_from_slice(&[0xFF, 0x2F, 0x00]);
+    out
+}
+
+/// Write the delta time from `last_tick` to `tick` and move `last_tick` on.
+fn write_delta(out: &mut Vec<u8>, last_tick: &mut u64, tick: u64) {
+    let delta = tick.saturating_sub(*last_tick).min(0x0FFF_FFFF);
+    write_vlq(out, delta as u32);
+    *last_tick = (*last_tick).max(tick);
+}
+
+/// Write a variable-length quantity (at most 28 bits).
+fn write_vlq(out: &mut Vec<u8>, value: u32) {
+    let value = value & 0x0FFF_FFFF;
+    let mut shift = 21;
+    while shift > 0 && value >> shift == 0 {
+        shift -= 7;
+    }
+    while shift > 0 {
+        out.push(((value >> shift) & 0x7F) as u8 | 0x80);
+        shift -= 7;
+    }
+    out.push((value & 0x7F) as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note_on(channel: u8, note: u8) -> MidiEvent {
+        MidiEvent::NoteOn { channel, note, velocity: 100 }
+    }
+
+    fn note_off(channel: u8, note: u8) -> MidiEvent {
+        MidiEvent::NoteOff { channel, note, velocity: 0 }
+    }
+
+    /// A format 0 file with two notes, written by hand with running status.
+    fn format_0_bytes() -> Vec<u8> {
+        let track: &[u8] = &[
+            0x00, 0xFF, 0x03, 0x04, b'L', b'e', b'a', b'd', // name "Lead"
+            0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // 120 BPM
+            0x00, 0x90, 0x3C, 0x64, // note on C4
+            0x83, 0x60, 0x3C, 0x00, // 480 ticks later: running status, velocity 0 = off
+            0x00, 0x3E, 0x50, // running status note on D4
+            0x83, 0x60, 0x80, 0x3E, 0x40, // note off D4
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let mut bytes = b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x01\xE0MTrk".to_vec();
+        bytes.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(track);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_format_0_with_running_status() {
+        let file = MidiFile::parse(&format_0_bytes()).unwrap();
+        assert_eq!(file.format, 0);
+        assert_eq!(file.ticks_per_quarter, 480);
+        assert_eq!(file.tracks.len(), 1);
+
+        let track = &file.tracks[0];
+        assert_eq!(track.name.as_deref(), Some("Lead"));
+        assert_eq!(
+            track.events,
+            vec![
+                TrackEvent::tempo(0, 500_000),
+                TrackEvent::midi(0, note_on(0, 60)),
+                TrackEvent::midi(480, note_off(0, 60)),
+                TrackEvent::midi(480, MidiEvent::NoteOn { channel: 0, note: 62, velocity: 80 }),
+                TrackEvent::midi(960, MidiEvent::NoteOff { channel: 0, note: 62, velocity: 64 }),
+            ]
+        );
+        assert_eq!(track.end_tick, 960);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(MidiFile::parse(b"RIFF0000"), Err(SmfError::NotMidiFile)));
+
+        let mut bytes = format_0_bytes();
+        bytes[9] = 2;
+        assert!(matches!(MidiFile::parse(&bytes), Err(SmfError::UnsupportedFormat(2))));
+
+        let mut bytes = format_0_bytes();
+        bytes[12] = 0xE8;
+        assert!(matches!(MidiFile::parse(&bytes), Err(SmfError::SmpteTiming)));
+
+        let bytes = format_0_bytes();
+        assert!(matches!(MidiFile::parse(&bytes[..bytes.len() - 5]), Err(SmfError::Truncated)));
+    }
+
+    #[test]
+    fn test_write_and_read_format_1() {
+        let mut file = MidiFile::new(1, 960);
+        let mut tempo = MidiTrack::named("Tempo");
+        tempo.events.push(TrackEvent::tempo(0, 600_000));
+        tempo.events.push(TrackEvent::tempo(1920, 400_000));
+        let mut notes = MidiTrack::named("Keys");
+        notes.events.push(TrackEvent::midi(0, note_on(1, 60)));
+        notes.events.push(TrackEvent::midi(0, note_on(1, 64)));
+        notes.events.push(TrackEvent::midi(960, note_off(1, 60)));
+        notes.events.push(TrackEvent::midi(200_000, note_off(1, 64)));
+        notes.events.push(TrackEvent::midi(200_000, MidiEvent::PitchBend { channel: 1, value: -100 }));
+        notes.end_tick = 200_000;
+        file.tracks = vec![tempo, notes];
+
+        let bytes = file.to_bytes();
+        let mut read = MidiFile::parse(&bytes).unwrap();
+        // The tempo track ends at its last event
+        assert_eq!(read.tracks[0].end_tick, 1920);
+        read.tracks[0].end_tick = 0;
+        assert_eq!(read, file);
+    }
+
+    #[test]
+    fn test_writer_uses_running_status() {
+        let mut file = MidiFile::new(0, 480);
+        let mut track = MidiTrack::default();
+        track.events.push(TrackEvent::midi(0, note_on(0, 60)));
+        track.events.push(TrackEvent::midi(0, note_on(0, 64)));
+        file.tracks.push(track);
+
+        let bytes = file.to_bytes();
+        let body = &bytes[22..];
+        assert_eq!(body, &[0x00, 0x90, 60, 100, 0x00, 64, 100, 0x00, 0xFF, 0x2F, 0x00]);
+    }
+
+    #[test]
+    fn test_vlq() {
+        for value in [0, 0x40, 0x7F, 0x80, 0x2000, 0x3FFF, 0x4000, 0x1F_FFFF, 0x20_0000, 0x0FFF_FFFF] {
+            let mut bytes = Vec::new();
+            write_vlq(&mut bytes, value);
+            assert_eq!(Reader::new(&bytes).vlq().unwrap(), value);
+        }
+
+        let mut bytes = Vec::new();
+        write_vlq(&mut bytes, 0x80);
+        assert_eq!(bytes, vec![0x81, 0x00]);
+    }
+
+    #[test]
+    fn test_tempo_map() {
+        // 120 BPM for two beats, then 60 BPM
+        let map = TempoMap::new(480, [(960, 1_000_000), (0, 500_000)]);
+        assert!((map.seconds_at(480) - 0.5).abs() < 1e-9);
+        assert!((map.seconds_at(960) - 1.0).abs() < 1e-9);
+        assert!((map.seconds_at(1440) - 2.0).abs() < 1e-9);
+        assert_eq!(map.tempo_bpm_at(0), 120.0);
+        assert_eq!(map.tempo_bpm_at(1000), 60.0);
+
+        // No tempo events: 120 BPM
+        let map = TempoMap::new(96, []);
+        assert!((map.seconds_at(96) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_note_sequence_merges_tracks() {
+        let mut file = MidiFile::new(1, 480);
+        let mut tempo = MidiTrack::default();
+        tempo.events.push(TrackEvent::tempo(0, 1_000_000));
+        let mut bass = MidiTrack::default();
+        bass.events.push(TrackEvent::midi(0, note_on(0, 36)));
+        bass.events.push(TrackEvent::midi(0, MidiEvent::ControlChange { channel: 0, controller: 1, value: 64 }));
+        bass.events.push(TrackEvent::midi(960, note_off(0, 36)));
+        let mut lead = MidiTrack::default();
+        lead.events.push(TrackEvent::midi(480, note_on(1, 72)));
+        lead.events.push(TrackEvent::midi(480, note_off(1, 72)));
+        lead.end_tick = 1920;
+        file.tracks = vec![tempo, bass, lead];
+
+        let sequence = file.note_sequence();
+        assert_eq!(sequence.tracks, 3);
+        assert_eq!(sequence.length_beats, 4.0);
+        assert!((sequence.length_seconds - 4.0).abs() < 1e-9);
+
+        let order: Vec<(f64, u16, u8, u8)> =
+            sequence.notes.iter().map(|n| (n.beats, n.track, n.note, n.velocity)).collect();
+        assert_eq!(order, vec![(0.0, 1, 36, 100), (1.0, 2, 72, 100), (1.0, 2, 72, 0), (2.0, 1, 36, 0)]);
+        assert!((sequence.notes[1].seconds - 1.0).abs() < 1e-9);
+        assert!(sequence.notes[1].is_note_on());
+        assert!(!sequence.notes[2].is_note_on());
+    }
+}
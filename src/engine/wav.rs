@@ -0,0 +1,202 @@
+//! WAV File Writer
+//!
+//! Writes 32-bit float WAV files. The sizes in the header are written as
+//! zero and filled in by `finalize`, so a file can be streamed to disk
+//! without knowing its length up front.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// WAVE_FORMAT_IEEE_FLOAT.
+const FORMAT_IEEE_FLOAT: u16 = 3;
+
+/// Bytes per sample (32-bit float).
+const BYTES_PER_SAMPLE: u16 = 4;
+
+/// Size of the header written before the sample data.
+const HEADER_SIZE: u32 = 12 + 8 + 16 + 8 + 4 + 8;
+
+/// Largest data chunk that keeps the RIFF chunk size within 32 bits.
+pub const MAX_DATA_SIZE: u32 = u32::MAX - (HEADER_SIZE - 8);
+
+/// Offset of the RIFF chunk size.
+const RIFF_SIZE_OFFSET: u64 = 4;
+
+/// Offset of the sample frame count in the fact chunk.
+const FACT_FRAMES_OFFSET: u64 = 44;
+
+/// Offset of the data chunk size.
+const DATA_SIZE_OFFSET: u64 = 52;
+
+/// Streams interleaved float samples to a WAV file.
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    channels: u16,
+    frames: u64,
+}
+
+impl WavWriter<BufWriter<File>> {
+    /// Create a WAV file at `path`, replacing any existing file.
+    pub fn create(path: &Path, channels: u16, sample_rate: u32) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), channels, sample_rate)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// Write the header to `writer` and return a writer positioned at the sample data.
+    pub fn new(mut writer: W, channels: u16, sample_rate: u32) -> io::Result<Self> {
+        let block_align = channels * BYTES_PER_SAMPLE;
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&FORMAT_IEEE_FLOAT.to_le_bytes())?;
+        writer.write_all(&channels.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * u32::from(block_align)).to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&(BYTES_PER_SAMPLE * 8).to_le_bytes())?;
+
+        // Non-PCM formats carry a fact chunk with the frame count
+        writer.write_all(b"fact")?;
+        writer.write_all(&4u32.to_le_bytes())?;
+        writer.write_all(&0u32.to_le_bytes())?;
+
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self {
+            writer,
+            channels,
+            frames: 0,
+        })
+    }
+
+    /// Number of channels per frame.
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Number of complete frames written so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Size of the sample data written so far, in bytes.
+    fn data_size(&self) -> u64 {
+        self.frames * u64::from(self.channels * BYTES_PER_SAMPLE)
+    }
+
+    /// Whether `samples` more samples fit without exceeding the 4 GB WAV limit.
+    pub fn has_room(&self, samples: usize) -> bool {
+        (samples as u64)
+            .checked_mul(u64::from(BYTES_PER_SAMPLE))
+            .and_then(|size| size.checked_add(self.data_size()))
+            .is_some_and(|size| size <= u64::from(MAX_DATA_SIZE))
+    }
+
+    /// Append interleaved samples; the length should be a multiple of the channel count.
+    /// Nothing is written if the samples would exceed the 4 GB WAV limit.
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        if !self.has_room(samples.len()) {
+            return Err(io::Error::other("recording reached the 4 GB WAV limit"));
+        }
+        for sample in samples {
+            self.writer.write_all(&sample.to_le_bytes())?;
+        }
+        self.frames += (samples.len() / usize::from(self.channels)) as u64;
+        Ok(())
+    }
+
+    /// Fill in the header sizes, flush, and return the underlying writer.
+    pub fn finalize(mut self) -> io::Result<W> {
+        let too_large = || io::Error::other("recording exceeds the 4 GB WAV limit");
+        let data_size = u32::try_from(self.data_size()).map_err(|_| too_large())?;
+        let riff_size = (HEADER_SIZE - 8).checked_add(data_size).ok_or_else(too_large)?;
+        let frames = u32::try_from(self.frames).map_err(|_| too_large())?;
+
+        self.writer.seek(SeekFrom::Start(RIFF_SIZE_OFFSET))?;
+        self.writer.write_all(&riff_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(FACT_FRAMES_OFFSET))?;
+        self.writer.write_all(&frames.to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(DATA_SIZE_OFFSET))?;
+        self.writer.write_all(&data_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_wav_header_and_data() {
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), 2, 48000).unwrap();
+        wav.write_samples(&[0.5, -0.5, 0.25, -0.25]).unwrap();
+        wav.write_samples(&[1.0, -1.0]).unwrap();
+        assert_eq!(wav.frames(), 3);
+
+        let bytes = wav.finalize().unwrap().into_inner();
+        assert_eq!(bytes.len(), HEADER_SIZE as usize + 6 * 4);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32_at(&bytes, 4) as usize, bytes.len() - 8);
+        assert_eq!(&bytes[8..12], b"WAVE");
+        assert_eq!(u16_at(&bytes, 20), FORMAT_IEEE_FLOAT);
+        assert_eq!(u16_at(&bytes, 22), 2);
+        assert_eq!(u32_at(&bytes, 24), 48000);
+        assert_eq!(u32_at(&bytes, 28), 48000 * 8);
+        assert_eq!(u16_at(&bytes, 32), 8);
+        assert_eq!(u16_at(&bytes, 34), 32);
+        assert_eq!(&bytes[36..40], b"fact");
+        assert_eq!(u32_at(&bytes, 44), 3);
+        assert_eq!(&bytes[48..52], b"data");
+        assert_eq!(u32_at(&bytes, 52), 24);
+
+        let first = f32::from_le_bytes(bytes[56..60].try_into().unwrap());
+        assert!((first - 0.5).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_wav_empty_file() {
+        let wav = WavWriter::new(Cursor::new(Vec::new()), 1, 44100).unwrap();
+        let bytes = wav.finalize().unwrap().into_inner();
+        assert_eq!(bytes.len(), HEADER_SIZE as usize);
+        assert_eq!(u32_at(&bytes, 52), 0);
+    }
+
+    #[test]
+    fn test_wav_size_limit() {
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), 2, 48000).unwrap();
+        // Pretend the file is one frame short of the limit
+        let frame_size = u64::from(2 * BYTES_PER_SAMPLE);
+        wav.frames = u64::from(MAX_DATA_SIZE) / frame_size - 1;
+        assert!(wav.has_room(2));
+        assert!(!wav.has_room(4));
+        assert!(!wav.has_room(usize::MAX));
+
+        assert!(wav.write_samples(&[0.0; 4]).is_err());
+        assert_eq!(wav.writer.get_ref().len(), HEADER_SIZE as usize);
+        wav.write_samples(&[0.0; 2]).unwrap();
+        assert!(!wav.has_room(2));
+
+        let bytes = wav.finalize().unwrap().into_inner();
+        let data_size = u32_at(&bytes, 52);
+        assert!(data_size <= MAX_DATA_SIZE);
+        assert_eq!(u32_at(&bytes, 4), HEADER_SIZE - 8 + data_size);
+    }
+}
@@ -15,6 +15,7 @@ use egui_node_graph2::{NodeDataTrait, NodeResponse, UserResponseTrait};
 
 use crate::dsp::ModuleCategory;
 use crate::engine::midi_engine::MidiEvent;
+use crate::persistence::{ButtonMode, ControlKind, EncoderMode, MappingOptions, ResponseCurve, Takeover};
 use crate::widgets::{knob, led, waveform_display, generate_waveform_cycle, KnobConfig, LedConfig, ParamFormat, WaveformConfig, WaveformType, adsr_display, AdsrConfig, AdsrParams, spectrum_display, SpectrumConfig, SpectrumStyle, generate_filter_response, FilterResponseType, piano, PianoConfig, PianoData};
 use super::{SynthResponse, SynthValueType};
 
@@ -32,6 +33,9 @@ mod midi_colors {
     pub const OTHER: Color32 = Color32::from_rgb(150, 150, 150);
 }
 
+/// Modules that use the tuning for pitch, and can have their own tuning.
+const TUNED_MODULES: [&str; 5] = ["input.midi_note", "input.keyboard", "input.midi_file", "seq.step", "util.quantizer"];
+
 /// Convert a MIDI note number to a note name (e.g., 60 -> "C4").
 fn note_to_name(note: u8) -> String {
     const NOTES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
@@ -71,6 +75,18 @@ fn format_midi_event(event: &MidiEvent) -> (String, Color32) {
             format!("Program Ch{} #{}", channel + 1, program),
             midi_colors::OTHER,
         ),
+        MidiEvent::Clock => ("Clock".to_string(), midi_colors::OTHER),
+        MidiEvent::Start => ("Start".to_string(), midi_colors::OTHER),
+        MidiEvent::Continue => ("Continue".to_string(), midi_colors::OTHER),
+        MidiEvent::Stop => ("Stop".to_string(), midi_colors::OTHER),
+        MidiEvent::SongPosition { position } => (
+            format!("SongPos {}.{}", position / 16 + 1, position % 16 / 4 + 1),
+            midi_colors::OTHER,
+        ),
+        MidiEvent::ParameterNumber { channel, registered, number, value } => (
+            format!("{} Ch{} #{} val={}", if *registered { "RPN" } else { "NRPN" }, channel + 1, number, value),
+            midi_colors::CC,
+        ),
     }
 }
 
@@ -219,8 +235,16 @@ pub struct SynthNodeData {
 struct KnobMidiConfig {
     /// Whether this knob has a MIDI CC mapping.
     has_midi_mapping: bool,
-    /// The CC number if mapped.
-    cc_number: Option<u8>,
+    /// The mapped control if mapped, e.g. "CC 74".
+    label: Option<String>,
+    /// The MIDI input device the mapping listens to (None = any input).
+    device: Option<String>,
+    /// What the mapped controller sends.
+    control: ControlKind,
+    /// How the mapping responds to the controller.
+    options: MappingOptions,
+    /// What a learned note or button does to this parameter.
+    button: ButtonMode,
     /// Whether this knob is the current MIDI Learn target.
     is_learn_target: bool,
     /// Parameter min value (for MIDI Learn).
@@ -260,6 +284,11 @@ impl SynthNodeData {
         self
     }
 
+    /// Whether the module converts notes through a tuning.
+    pub fn is_tuned(&self) -> bool {
+        TUNED_MODULES.contains(&self.module_id)
+    }
+
     /// Get the header color for this node based on its category.
     pub fn header_color(&self) -> Color32 {
         self.category.color()
@@ -405,6 +434,56 @@ impl SynthNodeData {
         }
     }
 
+    /// Menu entries for how a MIDI mapping responds to its controller.
+    ///
+    /// Notes are always buttons and a CC can be either; `button` is the
+    /// mode that suits the parameter, and only parameters with choices
+    /// offer Cycle. Continuous controls offer the encoder, curve and
+    /// takeover settings instead.
+    fn mapping_options_menu(ui: &mut egui::Ui, options: &mut MappingOptions, control: ControlKind, button: ButtonMode) {
+        let is_note = matches!(control, ControlKind::Note(_));
+        if is_note || control == ControlKind::Cc {
+            let text = options.button.map_or("Continuous", ButtonMode::label);
+            ui.menu_button(format!("Button: {}", text), |ui| {
+                if !is_note {
+                    ui.radio_value(&mut options.button, None, "Continuous");
+                }
+                for mode in ButtonMode::ALL {
+                    if mode != ButtonMode::Cycle || button == ButtonMode::Cycle {
+                        ui.radio_value(&mut options.button, Some(mode), mode.label());
+                    }
+                }
+            });
+        }
+
+        if options.button.is_none() {
+            if control == ControlKind::Cc {
+                ui.menu_button(format!("Encoder: {}", options.encoder.label()), |ui| {
+                    for mode in EncoderMode::ALL {
+                        ui.radio_value(&mut options.encoder, mode, mode.label());
+                    }
+                });
+            }
+            ui.menu_button(format!("Curve: {}", options.curve.label()), |ui| {
+                for curve in ResponseCurve::ALL {
+                    ui.radio_value(&mut options.curve, curve, curve.label());
+                }
+            });
+            ui.add_enabled_ui(!options.encoder.is_relative(), |ui| {
+                ui.menu_button(format!("Takeover: {}", options.takeover.label()), |ui| {
+                    for mode in Takeover::ALL {
+                        ui.radio_value(&mut options.takeover, mode, mode.label());
+                    }
+                })
+                .response
+                .on_disabled_hover_text("Relative encoders always continue from the current value");
+            });
+        }
+        ui.checkbox(&mut options.inverted, "Invert");
+        ui.checkbox(&mut options.feedback, "Send Feedback")
+            .on_hover_text("Send the value back to the controller's faders and LEDs");
+    }
+
     /// Render an interactive knob widget for a parameter value.
     ///
     /// When the knob is changed, emits a ParameterChanged response.
@@ -618,20 +697,22 @@ impl NodeDataTrait for SynthNodeData {
     fn top_bar_ui(
         &self,
         ui: &mut egui::Ui,
-        _node_id: egui_node_graph2::NodeId,
+        node_id: egui_node_graph2::NodeId,
         _graph: &egui_node_graph2::Graph<Self, Self::DataType, Self::ValueType>,
-        _user_state: &mut Self::UserState,
+        user_state: &mut Self::UserState,
         zoom: f32,
     ) -> Vec<NodeResponse<Self::Response, Self>>
     where
         Self::Response: UserResponseTrait,
     {
+        let mut responses = Vec::new();
+
         // Allocate space for the category icon (drawn before the title)
         let icon_size = 14.0 * zoom;
         let icon_padding = 4.0 * zoom;
-        let (icon_rect, _response) = ui.allocate_exact_size(
+        let (icon_rect, icon_response) = ui.allocate_exact_size(
             egui::vec2(icon_size + icon_padding, icon_size),
-            egui::Sense::hover(),
+            egui::Sense::click(),
         );
 
         // Draw the category icon centered in the allocated space
@@ -641,7 +722,78 @@ impl NodeDataTrait for SynthNodeData {
         );
         self.draw_category_icon(ui.painter(), icon_center, icon_size, Color32::WHITE);
 
-        Vec::new()
+        // Right-click the icon for the module's presets, and its tuning
+        let presets = user_state.presets.get(self.module_id).cloned().unwrap_or_default();
+        let tuning = user_state
+            .get_engine_node_id(node_id)
+            .and_then(|engine_node_id| user_state.node_tunings.get(&engine_node_id))
+            .map(|tuning| tuning.label());
+        let hover_text = if self.is_tuned() { "Right-click for presets and tuning" } else { "Right-click for presets" };
+        let menu_response = icon_response
+            .on_hover_text(hover_text)
+            .context_menu(|ui| {
+                ui.label(RichText::new("Presets").strong());
+                ui.separator();
+
+                if presets.is_empty() {
+                    ui.label(RichText::new("No presets yet").weak().italics());
+                }
+                for preset in &presets {
+                    ui.horizontal(|ui| {
+                        if ui.button(&preset.name).clicked() {
+                            responses.push(NodeResponse::User(SynthResponse::LoadPreset {
+                                node_id,
+                                name: preset.name.clone(),
+                            }));
+                            ui.close_menu();
+                        }
+                        if preset.factory {
+                            ui.label(RichText::new("factory").weak().small());
+                        } else if ui.small_button("🗑").on_hover_text("Delete preset").clicked() {
+                            responses.push(NodeResponse::User(SynthResponse::DeletePreset {
+                                node_id,
+                                name: preset.name.clone(),
+                            }));
+                            ui.close_menu();
+                        }
+                    });
+                }
+
+                ui.separator();
+                if ui.button("💾 Save Preset...").clicked() {
+                    responses.push(NodeResponse::User(SynthResponse::SavePreset(node_id)));
+                    ui.close_menu();
+                }
+
+                if self.is_tuned() {
+                    ui.add_space(4.0);
+                    ui.label(RichText::new("Tuning").strong());
+                    ui.separator();
+                    ui.label(RichText::new(tuning.as_deref().unwrap_or("Patch tuning")).weak());
+                    if ui.button("🎼 Load Scale...").on_hover_text("Give this module its own Scala scale").clicked() {
+                        responses.push(NodeResponse::User(SynthResponse::LoadNodeScale(node_id)));
+                        ui.close_menu();
+                    }
+                    if ui.add_enabled(tuning.is_some(), egui::Button::new("⌨ Load Mapping..."))
+                        .on_hover_text("Choose which keys play which scale degrees")
+                        .on_disabled_hover_text("Load a scale first")
+                        .clicked()
+                    {
+                        responses.push(NodeResponse::User(SynthResponse::LoadNodeMapping(node_id)));
+                        ui.close_menu();
+                    }
+                    if ui.add_enabled(tuning.is_some(), egui::Button::new("Use Patch Tuning")).clicked() {
+                        responses.push(NodeResponse::User(SynthResponse::ClearNodeTuning(node_id)));
+                        ui.close_menu();
+                    }
+                }
+            });
+        // Set flag if context menu is open to prevent add-node menu
+        if menu_response.is_some() {
+            user_state.widget_context_menu_open = true;
+        }
+
+        responses
     }
 
     fn bottom_ui(
@@ -756,6 +908,8 @@ impl NodeDataTrait for SynthNodeData {
 
                         ui.horizontal(|ui| {
                             ui.label(RichText::new(&timestamp).small().weak().monospace());
+                            ui.label(RichText::new(format!("In {}", event.port)).small().weak().monospace())
+                                .on_hover_text("MIDI input port");
                             ui.label(RichText::new(&text).small().color(color).monospace());
                         });
                     }
@@ -817,6 +971,58 @@ impl NodeDataTrait for SynthNodeData {
             crate::widgets::oscilloscope_display(ui, channel1, channel2, &config);
         }
 
+        // Special rendering for MIDI File Player module
+        if self.module_id == "input.midi_file" {
+            // Add separator with zoom-scaled margins
+            ui.add_space(4.0 * zoom);
+            let category_color = self.category.color();
+            let separator_color = Color32::from_rgba_unmultiplied(
+                category_color.r(),
+                category_color.g(),
+                category_color.b(),
+                64,
+            );
+            let margin = 4.0 * zoom;
+            let rect = ui.available_rect_before_wrap();
+            ui.painter().hline(
+                (rect.left() + margin)..=(rect.right() - margin),
+                ui.cursor().top(),
+                egui::Stroke::new(1.0 * zoom, separator_color),
+            );
+            ui.add_space(4.0 * zoom);
+
+            // Show the loaded file and a button to choose another
+            let loaded = engine_node_id.and_then(|eid| user_state.midi_files.get(&eid));
+            ui.horizontal(|ui| {
+                if let Some(file) = loaded {
+                    let name = file
+                        .path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    let seconds = file.length_seconds.round() as u64;
+                    ui.label(RichText::new(&name).small().monospace())
+                        .on_hover_text(file.path.display().to_string());
+                    ui.label(
+                        RichText::new(format!(
+                            "{}:{:02}, {} track{}",
+                            seconds / 60,
+                            seconds % 60,
+                            file.tracks,
+                            if file.tracks == 1 { "" } else { "s" }
+                        ))
+                        .small()
+                        .weak(),
+                    );
+                } else {
+                    ui.label(RichText::new("No file").small().weak().italics());
+                }
+                if ui.small_button("Load…").on_hover_text("Choose a MIDI file").clicked() {
+                    responses.push(NodeResponse::User(SynthResponse::LoadMidiFile(node_id)));
+                }
+            });
+        }
+
         // Special rendering for Step Sequencer module
         if self.module_id == "seq.step" {
             // Add separator with zoom-scaled margins
@@ -1128,6 +1334,107 @@ impl NodeDataTrait for SynthNodeData {
             });
         }
 
+        // Special rendering for Quantizer module - scale mask
+        if self.module_id == "util.quantizer" {
+            use crate::modules::quantizer::{Quantizer, MASK_NAMES, ROOT_LABELS, SCALE_STEPS};
+
+            // Add separator with zoom-scaled margins
+            ui.add_space(4.0 * zoom);
+            let category_color = self.category.color();
+            let separator_color = Color32::from_rgba_unmultiplied(
+                category_color.r(),
+                category_color.g(),
+                category_color.b(),
+                64,
+            );
+            let margin = 4.0 * zoom;
+            let rect = ui.available_rect_before_wrap();
+            ui.painter().hline(
+                (rect.left() + margin)..=(rect.right() - margin),
+                ui.cursor().top(),
+                egui::Stroke::new(1.0 * zoom, separator_color),
+            );
+            ui.add_space(4.0 * zoom);
+
+            // Get scale, root and mask from the node's input parameters
+            let mut scale = 1usize;
+            let mut root = 0usize;
+            let mut custom_mask = [false; 12];
+            if let Some(node) = graph.nodes.get(node_id) {
+                for (name, input_id) in &node.inputs {
+                    let input = graph.get_input(*input_id);
+                    match (name.as_str(), &input.value) {
+                        ("Scale", SynthValueType::Select { value, .. }) => scale = *value,
+                        ("Root", SynthValueType::Select { value, .. }) => root = *value,
+                        (name, SynthValueType::Toggle { value, .. }) => {
+                            if let Some(degree) = MASK_NAMES.iter().position(|mask| *mask == name) {
+                                custom_mask[degree] = *value;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            if scale == Quantizer::SCALE_TUNING {
+                ui.label(RichText::new("Notes of the tuning").small().weak().italics());
+            } else {
+                // Notes of the selected scale, by degree above the root
+                let mask: [bool; 12] = match SCALE_STEPS.get(scale) {
+                    Some(steps) => std::array::from_fn(|degree| steps.contains(&(degree as u8))),
+                    None => custom_mask,
+                };
+
+                // Degree of the note being output (Out is output index 0)
+                let playing = engine_node_id
+                    .and_then(|eid| user_state.get_output_value(eid, 0))
+                    .map(|v| ((v * 12.0).round() as i32 - root as i32).rem_euclid(12) as usize);
+
+                ui.horizontal(|ui| {
+                    ui.spacing_mut().item_spacing.x = 2.0 * zoom;
+                    let cell_size = egui::vec2(16.0 * zoom, 20.0 * zoom);
+
+                    for degree in 0..12 {
+                        let (rect, response) = ui.allocate_exact_size(cell_size, egui::Sense::click());
+
+                        let color = match (mask[degree], playing == Some(degree)) {
+                            (true, true) => Color32::from_rgb(200, 255, 200),
+                            (true, false) => Color32::from_rgb(100, 200, 100),
+                            (false, _) => Color32::from_rgb(60, 60, 70),
+                        };
+                        ui.painter().rect_filled(rect, 2.0, color);
+                        ui.painter().text(
+                            rect.center(),
+                            egui::Align2::CENTER_CENTER,
+                            ROOT_LABELS[(root + degree) % 12],
+                            egui::FontId::proportional(7.0 * zoom),
+                            if mask[degree] { Color32::BLACK } else { Color32::from_gray(180) },
+                        );
+
+                        // Clicking a note edits the custom mask, starting from the selected scale
+                        if response.clicked() {
+                            for (other, name) in MASK_NAMES.iter().enumerate() {
+                                let on = if other == degree { !mask[other] } else { mask[other] };
+                                responses.push(NodeResponse::User(SynthResponse::ParameterChanged {
+                                    node_id,
+                                    param_name: name.to_string(),
+                                    value: if on { 1.0 } else { 0.0 },
+                                }));
+                            }
+                            if scale != Quantizer::SCALE_CUSTOM {
+                                responses.push(NodeResponse::User(SynthResponse::ParameterChanged {
+                                    node_id,
+                                    param_name: "Scale".to_string(),
+                                    value: Quantizer::SCALE_CUSTOM as f32,
+                                }));
+                            }
+                        }
+                        response.on_hover_text("Click to toggle this note in the custom scale");
+                    }
+                });
+            }
+        }
+
         // Special rendering for Oscillator module - waveform preview
         if self.module_id == "osc.sine" {
             // Add separator with zoom-scaled margins
@@ -1533,6 +1840,32 @@ impl NodeDataTrait for SynthNodeData {
             });
         }
 
+        // MIDI input device of the MIDI Note and MPE Voice modules, chosen by
+        // name so it survives inputs being added, removed or reordered
+        if let (Some(eid), "input.midi_note" | "input.mpe_voice") = (engine_node_id, self.module_id) {
+            let current = user_state.node_midi_inputs.get(&eid).cloned();
+            let mut selected = current.clone();
+            ui.horizontal(|ui| {
+                ui.label("In");
+                egui::ComboBox::from_id_salt(("midi_input", node_id))
+                    .width(100.0 * zoom)
+                    .selected_text(selected.as_deref().unwrap_or("Any"))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut selected, None, "Any");
+                        for name in &user_state.midi_input_names {
+                            ui.selectable_value(&mut selected, Some(name.clone()), name);
+                        }
+                        // A device that isn't connected now stays selected
+                        if let Some(name) = current.as_ref().filter(|name| !user_state.midi_input_names.contains(name)) {
+                            ui.selectable_value(&mut selected, Some(name.clone()), format!("{} (not connected)", name));
+                        }
+                    });
+            });
+            if selected != current {
+                responses.push(NodeResponse::User(SynthResponse::SetMidiInput { node_id, device: selected }));
+            }
+        }
+
         // Special rendering for MIDI Note module - piano keyboard display
         if self.module_id == "input.midi_note" {
             // Add separator with zoom-scaled margins
@@ -1664,10 +1997,21 @@ impl NodeDataTrait for SynthNodeData {
                                 SynthValueType::Select { options, .. } => (0.0, (options.len() - 1) as f32),
                             };
 
+                            // Buttons switch toggles, step through choices and hold anything else
+                            let button = match &input.value {
+                                SynthValueType::Toggle { .. } => ButtonMode::Toggle,
+                                SynthValueType::Select { .. } => ButtonMode::Cycle,
+                                _ => ButtonMode::Momentary,
+                            };
+
                             // Build MIDI config for the knob
                             let midi_config = KnobMidiConfig {
                                 has_midi_mapping: midi_mapping.is_some(),
-                                cc_number: midi_mapping.map(|m| m.cc_number),
+                                label: midi_mapping.map(|m| m.label.clone()),
+                                device: midi_mapping.and_then(|m| m.device.clone()),
+                                control: midi_mapping.map_or_else(ControlKind::default, |m| m.control),
+                                options: midi_mapping.map_or_else(MappingOptions::default, |m| m.options),
+                                button,
                                 is_learn_target,
                                 min_value,
                                 max_value,
@@ -1766,20 +2110,49 @@ impl NodeDataTrait for SynthNodeData {
 
                             // Create an interactive rect over the knob area for context menu
                             let knob_rect = knob_response.response.rect;
+
+                            // Small lock in the corner of knobs locked against randomization
+                            let is_locked = engine_node_id
+                                .map(|eid| user_state.is_param_locked(eid, current_param_index))
+                                .unwrap_or(false);
+                            if is_locked {
+                                ui.painter().text(
+                                    knob_rect.right_top(),
+                                    egui::Align2::RIGHT_TOP,
+                                    "🔒",
+                                    egui::FontId::proportional(8.0 * zoom),
+                                    Color32::from_rgb(220, 180, 80), // Amber for locked
+                                );
+                            }
+
                             let interact_response = ui.interact(
                                 knob_rect,
                                 egui::Id::new(("knob_context", node_id, current_param_index)),
                                 egui::Sense::click(),
                             );
 
-                            // Handle right-click context menu for MIDI Learn
+                            // Handle right-click context menu for MIDI Learn and locking
                             if let Some(engine_id) = engine_node_id {
                                 let menu_response = interact_response.context_menu(|ui| {
                                     if midi_config.has_midi_mapping {
-                                        let cc_text = midi_config.cc_number
-                                            .map(|cc| format!("CC #{}", cc))
-                                            .unwrap_or_else(|| "MIDI".to_string());
-                                        ui.label(RichText::new(cc_text).small().weak());
+                                        let control_text = match (&midi_config.label, &midi_config.device) {
+                                            (Some(label), None) => label.clone(),
+                                            (Some(label), Some(device)) => format!("{} · {}", label, device),
+                                            (None, _) => "MIDI".to_string(),
+                                        };
+                                        ui.label(RichText::new(control_text).small().weak());
+                                        ui.separator();
+
+                                        // How the mapping responds to the controller
+                                        let mut options = midi_config.options;
+                                        Self::mapping_options_menu(ui, &mut options, midi_config.control, midi_config.button);
+                                        if options != midi_config.options {
+                                            responses.push(NodeResponse::User(SynthResponse::MidiMappingOptions {
+                                                engine_node_id: engine_id,
+                                                param_index: current_param_index,
+                                                options,
+                                            }));
+                                        }
                                         ui.separator();
 
                                         if ui.button("Clear MIDI").clicked() {
@@ -1789,28 +2162,43 @@ impl NodeDataTrait for SynthNodeData {
                                             }));
                                             ui.close_menu();
                                         }
-                                        if ui.button("Re-learn MIDI CC").clicked() {
+                                        if ui.button("Re-learn MIDI").clicked() {
                                             responses.push(NodeResponse::User(SynthResponse::MidiLearnStart {
                                                 engine_node_id: engine_id,
                                                 param_index: current_param_index,
                                                 param_name: knob_param.param_name.clone(),
                                                 min_value: midi_config.min_value,
                                                 max_value: midi_config.max_value,
+                                                button: midi_config.button,
                                             }));
                                             ui.close_menu();
                                         }
                                     } else {
-                                        if ui.button("Learn MIDI CC").clicked() {
+                                        if ui.button("Learn MIDI").clicked() {
                                             responses.push(NodeResponse::User(SynthResponse::MidiLearnStart {
                                                 engine_node_id: engine_id,
                                                 param_index: current_param_index,
                                                 param_name: knob_param.param_name.clone(),
                                                 min_value: midi_config.min_value,
                                                 max_value: midi_config.max_value,
+                                                button: midi_config.button,
                                             }));
                                             ui.close_menu();
                                         }
                                     }
+
+                                    ui.separator();
+                                    let lock_text = if is_locked { "🔓 Unlock" } else { "🔒 Lock" };
+                                    if ui.button(lock_text)
+                                        .on_hover_text("Locked parameters are left alone by Randomize and Mutate")
+                                        .clicked()
+                                    {
+                                        responses.push(NodeResponse::User(SynthResponse::ToggleParamLock {
+                                            engine_node_id: engine_id,
+                                            param_index: current_param_index,
+                                        }));
+                                        ui.close_menu();
+                                    }
                                 });
                                 // Set flag if context menu is open to prevent add-node menu
                                 if menu_response.is_some() {
@@ -4,6 +4,8 @@
 
 use egui_node_graph2::UserResponseTrait;
 
+use crate::persistence::{ButtonMode, MappingOptions};
+
 /// Custom responses generated by node graph interactions.
 ///
 /// These events are collected during UI drawing and processed
@@ -31,12 +33,50 @@ pub enum SynthResponse {
         param_name: String,
         min_value: f32,
         max_value: f32,
+        button: ButtonMode,
     },
     /// Request to clear MIDI mapping for a parameter.
     MidiLearnClear {
         engine_node_id: u64,
         param_index: usize,
     },
+    /// Request to change how a parameter's MIDI mapping responds.
+    MidiMappingOptions {
+        engine_node_id: u64,
+        param_index: usize,
+        options: MappingOptions,
+    },
+    /// Request to lock or unlock a parameter against randomization.
+    ToggleParamLock {
+        engine_node_id: u64,
+        param_index: usize,
+    },
+    /// Request to load a module preset into a node.
+    LoadPreset {
+        node_id: egui_node_graph2::NodeId,
+        name: String,
+    },
+    /// Request to save a node's settings as a new preset.
+    SavePreset(egui_node_graph2::NodeId),
+    /// Request to delete a user preset of a node's module type.
+    DeletePreset {
+        node_id: egui_node_graph2::NodeId,
+        name: String,
+    },
+    /// Request to choose a MIDI file for a MIDI File Player node.
+    LoadMidiFile(egui_node_graph2::NodeId),
+    /// Request to choose a Scala scale for a note module's own tuning.
+    LoadNodeScale(egui_node_graph2::NodeId),
+    /// Request to choose a Scala keyboard mapping for a note module's own tuning.
+    LoadNodeMapping(egui_node_graph2::NodeId),
+    /// Request to return a note module to the patch tuning.
+    ClearNodeTuning(egui_node_graph2::NodeId),
+    /// Choose the MIDI input device a MIDI Note or MPE Voice node listens to.
+    SetMidiInput {
+        node_id: egui_node_graph2::NodeId,
+        /// Device name (None = any input).
+        device: Option<String>,
+    },
 }
 
 impl SynthResponse {
@@ -80,6 +120,7 @@ impl SynthResponse {
         param_name: impl Into<String>,
         min_value: f32,
         max_value: f32,
+        button: ButtonMode,
     ) -> Self {
         Self::MidiLearnStart {
             engine_node_id,
@@ -87,6 +128,7 @@ impl SynthResponse {
             param_name: param_name.into(),
             min_value,
             max_value,
+            button,
         }
     }
 
@@ -97,6 +139,14 @@ impl SynthResponse {
             param_index,
         }
     }
+
+    /// Create a parameter lock toggle response.
+    pub fn toggle_param_lock(engine_node_id: u64, param_index: usize) -> Self {
+        Self::ToggleParamLock {
+            engine_node_id,
+            param_index,
+        }
+    }
 }
 
 impl UserResponseTrait for SynthResponse {}
@@ -4,11 +4,13 @@
 
 use egui::{Color32, Pos2};
 use egui_node_graph2::{ConnectionSignalTrait, GraphEditorState, NodeId};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
 use std::time::Instant;
 
 use crate::engine::NodeId as EngineNodeId;
 use crate::engine::midi_engine::MidiEvent;
+use crate::persistence::{ControlKind, MappingOptions, MidiMapping, TuningData};
 use super::{SynthDataType, SynthNodeData, SynthValueType};
 use super::templates::SynthNodeTemplate;
 
@@ -23,6 +25,8 @@ const MAX_MIDI_EVENTS: usize = 10;
 pub struct DisplayMidiEvent {
     /// The MIDI event.
     pub event: MidiEvent,
+    /// Input port the event arrived on.
+    pub port: u8,
     /// Relative timestamp (seconds since first event).
     pub timestamp: f32,
 }
@@ -38,13 +42,51 @@ pub struct ScopeData {
     pub triggered: bool,
 }
 
-/// Info about a MIDI CC mapping for display in the UI.
+/// Info about a MIDI mapping for display in the UI.
 #[derive(Clone, Debug)]
 pub struct MidiMappingInfo {
-    /// CC number that is mapped.
-    pub cc_number: u8,
+    /// The mapped control, e.g. "CC 74" or "NRPN 389".
+    pub label: String,
     /// MIDI channel (0 = omni).
     pub channel: u8,
+    /// MIDI input device (None = any input).
+    pub device: Option<String>,
+    /// What the controller sends.
+    pub control: ControlKind,
+    /// How the mapping responds to the controller.
+    pub options: MappingOptions,
+}
+
+impl From<&MidiMapping> for MidiMappingInfo {
+    fn from(mapping: &MidiMapping) -> Self {
+        Self {
+            label: mapping.control_label(),
+            channel: mapping.channel,
+            device: mapping.device.clone(),
+            control: mapping.control,
+            options: mapping.options(),
+        }
+    }
+}
+
+/// A preset listed in a node's preset menu.
+#[derive(Clone, Debug)]
+pub struct PresetMenuEntry {
+    /// Preset name.
+    pub name: String,
+    /// Whether the preset ships with the synth (and can't be deleted).
+    pub factory: bool,
+}
+
+/// A MIDI file loaded into a MIDI File Player node.
+#[derive(Clone, Debug)]
+pub struct LoadedMidiFile {
+    /// Where the file was loaded from.
+    pub path: PathBuf,
+    /// Number of tracks in the file.
+    pub tracks: usize,
+    /// Length in seconds.
+    pub length_seconds: f64,
 }
 
 /// User state for the graph editor.
@@ -107,6 +149,12 @@ pub struct SynthGraphState {
     /// Tuple of (engine_node_id, param_index).
     pub midi_learn_target: Option<(EngineNodeId, usize)>,
 
+    /// Parameters locked against randomization, as (engine_node_id, param_index).
+    pub locked_params: HashSet<(EngineNodeId, usize)>,
+
+    /// Presets offered in each module type's preset menu, keyed by module ID.
+    pub presets: HashMap<String, Vec<PresetMenuEntry>>,
+
     /// Flag set when a widget context menu is shown this frame.
     /// Used to prevent the add-node menu from also appearing.
     pub widget_context_menu_open: bool,
@@ -115,6 +163,23 @@ pub struct SynthGraphState {
     /// Key: engine_node_id, Value: scope waveform data.
     pub scope_data: HashMap<EngineNodeId, ScopeData>,
 
+    /// MIDI files loaded into MIDI File Player nodes.
+    /// Key: engine_node_id.
+    pub midi_files: HashMap<EngineNodeId, LoadedMidiFile>,
+
+    /// Tunings of note modules that don't follow the patch tuning.
+    /// Key: engine_node_id.
+    pub node_tunings: HashMap<EngineNodeId, TuningData>,
+
+    /// MIDI input devices MIDI Note and MPE Voice nodes listen to; nodes
+    /// without one listen to every input.
+    /// Key: engine_node_id.
+    pub node_midi_inputs: HashMap<EngineNodeId, String>,
+
+    /// Names of the chosen MIDI inputs in port order, offered by the
+    /// input menus of the MIDI modules.
+    pub midi_input_names: Vec<String>,
+
     /// Current zoom level for scaling UI elements.
     /// Set by the graph editor before rendering.
     pub zoom: f32,
@@ -150,8 +215,14 @@ impl Default for SynthGraphState {
             midi_mappings: HashMap::new(),
             midi_learn_active: false,
             midi_learn_target: None,
+            locked_params: HashSet::new(),
+            presets: HashMap::new(),
             widget_context_menu_open: false,
             scope_data: HashMap::new(),
+            midi_files: HashMap::new(),
+            node_tunings: HashMap::new(),
+            node_midi_inputs: HashMap::new(),
+            midi_input_names: Vec::new(),
             zoom: 1.0,
             is_playing: false,
             keyboard_active_notes: Vec::new(),
@@ -166,6 +237,18 @@ impl SynthGraphState {
         Self::default()
     }
 
+    /// An empty state for another patch, keeping what isn't part of a patch
+    /// (the preset menus, MIDI inputs, zoom and transport).
+    pub fn for_new_patch(&self) -> Self {
+        Self {
+            presets: self.presets.clone(),
+            midi_input_names: self.midi_input_names.clone(),
+            zoom: self.zoom,
+            is_playing: self.is_playing,
+            ..Self::default()
+        }
+    }
+
     /// Allocate a new engine node ID and map it to a graph node.
     pub fn allocate_engine_node_id(&mut self, graph_node_id: NodeId) -> EngineNodeId {
         let id = self.next_engine_node_id;
@@ -200,8 +283,12 @@ impl SynthGraphState {
         self.midi_mappings.clear();
         self.midi_learn_active = false;
         self.midi_learn_target = None;
+        self.locked_params.clear();
         self.widget_context_menu_open = false;
         self.scope_data.clear();
+        self.midi_files.clear();
+        self.node_tunings.clear();
+        self.node_midi_inputs.clear();
         self.keyboard_active_notes.clear();
         self.midi_active_notes.clear();
     }
@@ -211,12 +298,9 @@ impl SynthGraphState {
         self.midi_mappings.get(&(engine_node_id, param_index))
     }
 
-    /// Set or update a MIDI mapping for a parameter.
-    pub fn set_midi_mapping(&mut self, engine_node_id: EngineNodeId, param_index: usize, cc_number: u8, channel: u8) {
-        self.midi_mappings.insert(
-            (engine_node_id, param_index),
-            MidiMappingInfo { cc_number, channel },
-        );
+    /// Set or update the MIDI mapping shown for a parameter.
+    pub fn set_midi_mapping(&mut self, mapping: &MidiMapping) {
+        self.midi_mappings.insert((mapping.node_id, mapping.param_index), MidiMappingInfo::from(mapping));
     }
 
     /// Remove a MIDI mapping for a parameter.
@@ -224,6 +308,11 @@ impl SynthGraphState {
         self.midi_mappings.remove(&(engine_node_id, param_index));
     }
 
+    /// Check if a parameter is locked against randomization.
+    pub fn is_param_locked(&self, engine_node_id: EngineNodeId, param_index: usize) -> bool {
+        self.locked_params.contains(&(engine_node_id, param_index))
+    }
+
     /// Check if a parameter is currently the MIDI Learn target.
     pub fn is_midi_learn_target(&self, engine_node_id: EngineNodeId, param_index: usize) -> bool {
         self.midi_learn_target == Some((engine_node_id, param_index))
@@ -288,7 +377,7 @@ impl SynthGraphState {
     ///
     /// Events are stored with a relative timestamp from the first event.
     /// Only the most recent MAX_MIDI_EVENTS are kept.
-    pub fn push_midi_event(&mut self, event: MidiEvent) {
+    pub fn push_midi_event(&mut self, event: MidiEvent, port: u8) {
         let now = Instant::now();
         let timestamp = if let Some(first_time) = self.midi_first_event_time {
             first_time.elapsed().as_secs_f32()
@@ -297,7 +386,7 @@ impl SynthGraphState {
             0.0
         };
 
-        self.midi_events.push_back(DisplayMidiEvent { event, timestamp });
+        self.midi_events.push_back(DisplayMidiEvent { event, port, timestamp });
 
         // Keep only the most recent events
         while self.midi_events.len() > MAX_MIDI_EVENTS {
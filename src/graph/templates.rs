@@ -16,8 +16,18 @@ use super::{SynthDataType, SynthGraphState, SynthNodeData, SynthValueType, KnobP
 pub enum SynthNodeTemplate {
     /// Sine oscillator - basic audio source.
     SineOscillator,
+    /// Audio input - channels of the audio input device.
+    AudioInput,
     /// Audio output - final destination in signal chain.
     AudioOutput,
+    /// Recorder - record the input to WAV files while gated.
+    Recorder,
+    /// Multi Output - eight separate outputs to device channels.
+    MultiOutput,
+    /// CV/Gate to MIDI - send notes to external gear.
+    CvToMidi,
+    /// CV to CC - send a control signal to external gear as MIDI CC.
+    CvToCc,
     /// LFO - low frequency oscillator for modulation.
     Lfo,
     /// State Variable Filter - multi-mode filter with LP, HP, BP outputs.
@@ -32,14 +42,22 @@ pub enum SynthNodeTemplate {
     Attenuverter,
     /// Mixer - 2-channel summing mixer.
     Mixer,
+    /// Scene Morph - morph position between two parameter scenes.
+    SceneMorph,
     /// Keyboard - virtual keyboard for playing notes from computer keyboard.
     Keyboard,
     /// MIDI Monitor - display incoming MIDI events.
     MidiMonitor,
     /// MIDI Note - convert MIDI note events to CV signals.
     MidiNote,
+    /// MPE Voice - one voice of an MPE controller with per-note expression.
+    MpeVoice,
+    /// MIDI File Player - play the notes of a MIDI file as CV signals.
+    MidiFilePlayer,
     /// Sample & Hold - sample input on trigger, hold until next trigger.
     SampleHold,
+    /// Quantizer - snap pitch CV to the notes of a scale.
+    Quantizer,
     /// Oscilloscope - real-time waveform visualization.
     Oscilloscope,
     /// Step Sequencer - 16-step sequencer with pitch, gate, and velocity.
@@ -65,6 +83,10 @@ impl SynthNodeTemplate {
         match self {
             SynthNodeTemplate::SineOscillator => "osc.sine",
             SynthNodeTemplate::AudioOutput => "output.audio",
+            SynthNodeTemplate::Recorder => "output.recorder",
+            SynthNodeTemplate::MultiOutput => "output.multi",
+            SynthNodeTemplate::CvToMidi => "output.cv_to_midi",
+            SynthNodeTemplate::CvToCc => "output.cv_to_cc",
             SynthNodeTemplate::Lfo => "mod.lfo",
             SynthNodeTemplate::SvfFilter => "filter.svf",
             SynthNodeTemplate::AdsrEnvelope => "mod.adsr",
@@ -72,10 +94,15 @@ impl SynthNodeTemplate {
             SynthNodeTemplate::Vca => "util.vca",
             SynthNodeTemplate::Attenuverter => "util.attenuverter",
             SynthNodeTemplate::Mixer => "util.mixer",
+            SynthNodeTemplate::SceneMorph => "util.scene_morph",
             SynthNodeTemplate::Keyboard => "input.keyboard",
+            SynthNodeTemplate::AudioInput => "input.audio",
             SynthNodeTemplate::MidiMonitor => "util.midi_monitor",
             SynthNodeTemplate::MidiNote => "input.midi_note",
+            SynthNodeTemplate::MpeVoice => "input.mpe_voice",
+            SynthNodeTemplate::MidiFilePlayer => "input.midi_file",
             SynthNodeTemplate::SampleHold => "util.sample_hold",
+            SynthNodeTemplate::Quantizer => "util.quantizer",
             SynthNodeTemplate::Oscilloscope => "util.oscilloscope",
             SynthNodeTemplate::StepSequencer => "seq.step",
             SynthNodeTemplate::StereoDelay => "fx.delay",
@@ -92,6 +119,10 @@ impl SynthNodeTemplate {
         match self {
             SynthNodeTemplate::SineOscillator => ModuleCategory::Source,
             SynthNodeTemplate::AudioOutput => ModuleCategory::Output,
+            SynthNodeTemplate::Recorder => ModuleCategory::Output,
+            SynthNodeTemplate::MultiOutput => ModuleCategory::Output,
+            SynthNodeTemplate::CvToMidi => ModuleCategory::Output,
+            SynthNodeTemplate::CvToCc => ModuleCategory::Output,
             SynthNodeTemplate::Lfo => ModuleCategory::Modulation,
             SynthNodeTemplate::SvfFilter => ModuleCategory::Filter,
             SynthNodeTemplate::AdsrEnvelope => ModuleCategory::Modulation,
@@ -99,10 +130,15 @@ impl SynthNodeTemplate {
             SynthNodeTemplate::Vca => ModuleCategory::Utility,
             SynthNodeTemplate::Attenuverter => ModuleCategory::Utility,
             SynthNodeTemplate::Mixer => ModuleCategory::Utility,
+            SynthNodeTemplate::SceneMorph => ModuleCategory::Utility,
             SynthNodeTemplate::Keyboard => ModuleCategory::Source,
+            SynthNodeTemplate::AudioInput => ModuleCategory::Source,
             SynthNodeTemplate::MidiMonitor => ModuleCategory::Utility,
             SynthNodeTemplate::MidiNote => ModuleCategory::Source,
+            SynthNodeTemplate::MpeVoice => ModuleCategory::Source,
+            SynthNodeTemplate::MidiFilePlayer => ModuleCategory::Source,
             SynthNodeTemplate::SampleHold => ModuleCategory::Utility,
+            SynthNodeTemplate::Quantizer => ModuleCategory::Utility,
             SynthNodeTemplate::Oscilloscope => ModuleCategory::Utility,
             SynthNodeTemplate::StepSequencer => ModuleCategory::Utility,
             SynthNodeTemplate::StereoDelay => ModuleCategory::Effect,
@@ -113,6 +149,65 @@ impl SynthNodeTemplate {
             SynthNodeTemplate::Compressor => ModuleCategory::Effect,
         }
     }
+
+    /// Find the template for a module ID.
+    pub fn from_module_id(module_id: &str) -> Option<Self> {
+        AllNodeTemplates
+            .all_kinds()
+            .into_iter()
+            .find(|template| template.module_id() == module_id)
+    }
+
+    /// Describe the ports and parameters this template creates in the graph.
+    ///
+    /// Builds the node in a scratch graph, so the result always matches what
+    /// the editor creates. Patches refer to ports by these graph names, which
+    /// don't always match the DSP module's port names.
+    pub fn layout(&self) -> NodeLayout {
+        let mut graph: Graph<SynthNodeData, SynthDataType, SynthValueType> = Graph::default();
+        let mut user_state = SynthGraphState::default();
+        let node_id = graph.add_node(
+            self.node_graph_label(&mut user_state),
+            self.user_data(&mut user_state),
+            |graph, node_id| self.build_node(graph, &mut user_state, node_id),
+        );
+
+        let mut layout = NodeLayout::default();
+        if let Some(node) = graph.nodes.get(node_id) {
+            for (name, input_id) in &node.inputs {
+                let input = graph.get_input(*input_id);
+                match input.kind {
+                    InputParamKind::ConnectionOnly => {
+                        layout.inputs.push(name.clone());
+                    }
+                    InputParamKind::ConstantOnly => {
+                        layout.parameters.push((name.clone(), input.value.clone()));
+                    }
+                    InputParamKind::ConnectionOrConstant => {
+                        layout.inputs.push(name.clone());
+                        layout.parameters.push((name.clone(), input.value.clone()));
+                    }
+                }
+            }
+            layout.outputs = node.outputs.iter().map(|(name, _)| name.clone()).collect();
+        }
+
+        layout
+    }
+}
+
+/// The graph-side ports and parameters of a node template.
+///
+/// Each list is in engine order: the n-th input is the module's n-th input
+/// port, and the n-th parameter is the module's n-th parameter.
+#[derive(Debug, Clone, Default)]
+pub struct NodeLayout {
+    /// Names of inputs that accept connections.
+    pub inputs: Vec<String>,
+    /// Names of outputs.
+    pub outputs: Vec<String>,
+    /// Parameter names and their default values.
+    pub parameters: Vec<(String, SynthValueType)>,
 }
 
 /// Iterator over all available node templates.
@@ -124,8 +219,11 @@ impl NodeTemplateIter for AllNodeTemplates {
     fn all_kinds(&self) -> Vec<Self::Item> {
         vec![
             SynthNodeTemplate::SineOscillator,
+            SynthNodeTemplate::AudioInput,
             SynthNodeTemplate::Keyboard,
             SynthNodeTemplate::MidiNote,
+            SynthNodeTemplate::MpeVoice,
+            SynthNodeTemplate::MidiFilePlayer,
             SynthNodeTemplate::SvfFilter,
             SynthNodeTemplate::AdsrEnvelope,
             SynthNodeTemplate::Lfo,
@@ -133,7 +231,9 @@ impl NodeTemplateIter for AllNodeTemplates {
             SynthNodeTemplate::Vca,
             SynthNodeTemplate::Attenuverter,
             SynthNodeTemplate::Mixer,
+            SynthNodeTemplate::SceneMorph,
             SynthNodeTemplate::SampleHold,
+            SynthNodeTemplate::Quantizer,
             SynthNodeTemplate::Oscilloscope,
             SynthNodeTemplate::StepSequencer,
             SynthNodeTemplate::StereoDelay,
@@ -143,6 +243,10 @@ impl NodeTemplateIter for AllNodeTemplates {
             SynthNodeTemplate::Chorus,
             SynthNodeTemplate::Compressor,
             SynthNodeTemplate::MidiMonitor,
+            SynthNodeTemplate::Recorder,
+            SynthNodeTemplate::MultiOutput,
+            SynthNodeTemplate::CvToMidi,
+            SynthNodeTemplate::CvToCc,
             SynthNodeTemplate::AudioOutput,
         ]
     }
@@ -194,6 +298,10 @@ impl NodeTemplateTrait for SynthNodeTemplate {
         match self {
             SynthNodeTemplate::SineOscillator => Cow::Borrowed("Oscillator"),
             SynthNodeTemplate::AudioOutput => Cow::Borrowed("Audio Output"),
+            SynthNodeTemplate::Recorder => Cow::Borrowed("Recorder"),
+            SynthNodeTemplate::MultiOutput => Cow::Borrowed("Multi Output"),
+            SynthNodeTemplate::CvToMidi => Cow::Borrowed("CV/Gate to MIDI"),
+            SynthNodeTemplate::CvToCc => Cow::Borrowed("CV to CC"),
             SynthNodeTemplate::Lfo => Cow::Borrowed("LFO"),
             SynthNodeTemplate::SvfFilter => Cow::Borrowed("SVF Filter"),
             SynthNodeTemplate::AdsrEnvelope => Cow::Borrowed("ADSR Envelope"),
@@ -201,10 +309,15 @@ impl NodeTemplateTrait for SynthNodeTemplate {
             SynthNodeTemplate::Vca => Cow::Borrowed("VCA"),
             SynthNodeTemplate::Attenuverter => Cow::Borrowed("Attenuverter"),
             SynthNodeTemplate::Mixer => Cow::Borrowed("Mixer"),
+            SynthNodeTemplate::SceneMorph => Cow::Borrowed("Scene Morph"),
             SynthNodeTemplate::Keyboard => Cow::Borrowed("Keyboard"),
+            SynthNodeTemplate::AudioInput => Cow::Borrowed("Audio Input"),
             SynthNodeTemplate::MidiMonitor => Cow::Borrowed("MIDI Monitor"),
             SynthNodeTemplate::MidiNote => Cow::Borrowed("MIDI Note"),
+            SynthNodeTemplate::MpeVoice => Cow::Borrowed("MPE Voice"),
+            SynthNodeTemplate::MidiFilePlayer => Cow::Borrowed("MIDI File Player"),
             SynthNodeTemplate::SampleHold => Cow::Borrowed("Sample & Hold"),
+            SynthNodeTemplate::Quantizer => Cow::Borrowed("Quantizer"),
             SynthNodeTemplate::Oscilloscope => Cow::Borrowed("Oscilloscope"),
             SynthNodeTemplate::StepSequencer => Cow::Borrowed("Step Sequencer"),
             SynthNodeTemplate::StereoDelay => Cow::Borrowed("Stereo Delay"),
@@ -224,6 +337,10 @@ impl NodeTemplateTrait for SynthNodeTemplate {
         match self {
             SynthNodeTemplate::SineOscillator => "Oscillator".to_string(),
             SynthNodeTemplate::AudioOutput => "Audio Output".to_string(),
+            SynthNodeTemplate::Recorder => "Recorder".to_string(),
+            SynthNodeTemplate::MultiOutput => "Multi Output".to_string(),
+            SynthNodeTemplate::CvToMidi => "CV/Gate to MIDI".to_string(),
+            SynthNodeTemplate::CvToCc => "CV to CC".to_string(),
             SynthNodeTemplate::Lfo => "LFO".to_string(),
             SynthNodeTemplate::SvfFilter => "SVF Filter".to_string(),
             SynthNodeTemplate::AdsrEnvelope => "ADSR Envelope".to_string(),
@@ -231,10 +348,15 @@ impl NodeTemplateTrait for SynthNodeTemplate {
             SynthNodeTemplate::Vca => "VCA".to_string(),
             SynthNodeTemplate::Attenuverter => "Attenuverter".to_string(),
             SynthNodeTemplate::Mixer => "Mixer".to_string(),
+            SynthNodeTemplate::SceneMorph => "Scene Morph".to_string(),
             SynthNodeTemplate::Keyboard => "Keyboard".to_string(),
+            SynthNodeTemplate::AudioInput => "Audio Input".to_string(),
             SynthNodeTemplate::MidiMonitor => "MIDI Monitor".to_string(),
             SynthNodeTemplate::MidiNote => "MIDI Note".to_string(),
+            SynthNodeTemplate::MpeVoice => "MPE Voice".to_string(),
+            SynthNodeTemplate::MidiFilePlayer => "MIDI File Player".to_string(),
             SynthNodeTemplate::SampleHold => "Sample & Hold".to_string(),
+            SynthNodeTemplate::Quantizer => "Quantizer".to_string(),
             SynthNodeTemplate::Oscilloscope => "Oscilloscope".to_string(),
             SynthNodeTemplate::StepSequencer => "Step Sequencer".to_string(),
             SynthNodeTemplate::StereoDelay => "Stereo Delay".to_string(),
@@ -269,6 +391,32 @@ impl NodeTemplateTrait for SynthNodeTemplate {
                 // Volume is knob-only
                 KnobParam::knob_only("Volume", "Vol"),
             ]),
+            SynthNodeTemplate::Recorder => SynthNodeData::new(
+                "output.recorder",
+                "Recorder",
+                ModuleCategory::Output,
+            ),
+            SynthNodeTemplate::MultiOutput => SynthNodeData::new(
+                "output.multi",
+                "Multi Output",
+                ModuleCategory::Output,
+            ).with_knob_params(vec![
+                // Level is knob-only
+                KnobParam::knob_only("Level", "Level"),
+            ]),
+            SynthNodeTemplate::CvToMidi => SynthNodeData::new(
+                "output.cv_to_midi",
+                "CV/Gate to MIDI",
+                ModuleCategory::Output,
+            ),
+            SynthNodeTemplate::CvToCc => SynthNodeData::new(
+                "output.cv_to_cc",
+                "CV to CC",
+                ModuleCategory::Output,
+            ).with_knob_params(vec![
+                // Controller number is knob-only
+                KnobParam::knob_only("CC", "CC"),
+            ]),
             SynthNodeTemplate::Lfo => SynthNodeData::new(
                 "mod.lfo",
                 "LFO",
@@ -338,6 +486,13 @@ impl NodeTemplateTrait for SynthNodeTemplate {
                 KnobParam::knob_only("Level 1", "Lv 1"),
                 KnobParam::knob_only("Level 2", "Lv 2"),
             ]),
+            SynthNodeTemplate::SceneMorph => SynthNodeData::new(
+                "util.scene_morph",
+                "Scene Morph",
+                ModuleCategory::Utility,
+            ).with_knob_params(vec![
+                KnobParam::knob_only("Position", "Pos"),
+            ]).with_monitored_outputs(vec![0]), // The app reads the output to drive the morph
             SynthNodeTemplate::Keyboard => SynthNodeData::new(
                 "input.keyboard",
                 "Keyboard",
@@ -348,6 +503,13 @@ impl NodeTemplateTrait for SynthNodeTemplate {
                 // Velocity: 0-1
                 KnobParam::knob_only("Velocity", "Vel"),
             ]).with_monitored_outputs(vec![0]), // Monitor Gate output for lit port
+            SynthNodeTemplate::AudioInput => SynthNodeData::new(
+                "input.audio",
+                "Audio Input",
+                ModuleCategory::Source,
+            ).with_knob_params(vec![
+                KnobParam::knob_only("Gain", "Gain"),
+            ]),
             SynthNodeTemplate::MidiMonitor => SynthNodeData::new(
                 "util.midi_monitor",
                 "MIDI Monitor",
@@ -362,6 +524,21 @@ impl NodeTemplateTrait for SynthNodeTemplate {
                 // Octave shift: -4 to +4
                 KnobParam::knob_only("Octave", "Oct"),
             ]).with_monitored_outputs(vec![1]), // Monitor Gate output for lit port
+            SynthNodeTemplate::MpeVoice => SynthNodeData::new(
+                "input.mpe_voice",
+                "MPE Voice",
+                ModuleCategory::Source,
+            ).with_knob_params(vec![
+                // Member channel count and pitch bend range of the zone
+                KnobParam::knob_only("Channels", "Chans"),
+                KnobParam::knob_only("Bend Range", "Bend"),
+            ]).with_monitored_outputs(vec![1]), // Monitor Gate output for lit port
+            SynthNodeTemplate::MidiFilePlayer => SynthNodeData::new(
+                "input.midi_file",
+                "MIDI File Player",
+                ModuleCategory::Source,
+            ).with_monitored_outputs(vec![1]), // Monitor Gate output for lit port
+            // Note: MidiFilePlayer has no knob_params - the loaded file is shown below the node
             SynthNodeTemplate::SampleHold => SynthNodeData::new(
                 "util.sample_hold",
                 "Sample & Hold",
@@ -370,6 +547,15 @@ impl NodeTemplateTrait for SynthNodeTemplate {
                 // Slew: glide time to new value (0-1s)
                 KnobParam::knob_only("Slew", "Slew"),
             ]),
+            SynthNodeTemplate::Quantizer => SynthNodeData::new(
+                "util.quantizer",
+                "Quantizer",
+                ModuleCategory::Utility,
+            ).with_knob_params(vec![
+                // Hysteresis: dead band around note boundaries (0-50 cents)
+                KnobParam::knob_only("Hysteresis", "Hyst"),
+            ]).with_monitored_outputs(vec![0, 1]), // Monitor Out for the playing note, Trig for lit port
+            // Note: the scale mask is drawn below the node
             SynthNodeTemplate::Oscilloscope => SynthNodeData::new(
                 "util.oscilloscope",
                 "Oscilloscope",
@@ -630,6 +816,134 @@ impl NodeTemplateTrait for SynthNodeTemplate {
                     true,
                 );
             }
+            SynthNodeTemplate::Recorder => {
+                // Audio input ports
+                for name in ["Left", "Right", "Mono"] {
+                    graph.add_input_param(
+                        node_id,
+                        name.to_string(),
+                        SynthDataType::new(SignalType::Audio),
+                        SynthValueType::scalar(0.0, ""),
+                        InputParamKind::ConnectionOnly,
+                        true,
+                    );
+                }
+
+                // Record gate input
+                graph.add_input_param(
+                    node_id,
+                    "Record".to_string(),
+                    SynthDataType::new(SignalType::Gate),
+                    SynthValueType::scalar(0.0, ""),
+                    InputParamKind::ConnectionOnly,
+                    true,
+                );
+
+                // Mode selector - shown inline
+                graph.add_input_param(
+                    node_id,
+                    "Mode".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::select(
+                        0,
+                        vec!["Gate".to_string(), "Toggle".to_string()],
+                        "Mode",
+                    ),
+                    InputParamKind::ConstantOnly,
+                    true, // Shown inline as dropdown
+                );
+            }
+            SynthNodeTemplate::MultiOutput => {
+                // One audio input per output channel
+                for channel in 1..=8 {
+                    graph.add_input_param(
+                        node_id,
+                        format!("Ch {}", channel),
+                        SynthDataType::new(SignalType::Audio),
+                        SynthValueType::scalar(0.0, ""),
+                        InputParamKind::ConnectionOnly,
+                        true,
+                    );
+                }
+
+                // Level: knob-only parameter
+                graph.add_input_param(
+                    node_id,
+                    "Level".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::scalar(1.0, ""),
+                    InputParamKind::ConstantOnly,
+                    false, // Hidden inline - shown in bottom knob row
+                );
+            }
+            SynthNodeTemplate::CvToMidi => {
+                // Note input ports
+                graph.add_input_param(
+                    node_id,
+                    "Pitch".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::scalar(0.0, ""),
+                    InputParamKind::ConnectionOnly,
+                    true,
+                );
+                graph.add_input_param(
+                    node_id,
+                    "Gate".to_string(),
+                    SynthDataType::new(SignalType::Gate),
+                    SynthValueType::scalar(0.0, ""),
+                    InputParamKind::ConnectionOnly,
+                    true,
+                );
+                graph.add_input_param(
+                    node_id,
+                    "Velocity".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::scalar(0.8, ""),
+                    InputParamKind::ConnectionOnly,
+                    true,
+                );
+
+                // MIDI channel the notes are sent on
+                graph.add_input_param(
+                    node_id,
+                    "Channel".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::select(0, (1..=16).map(|channel| channel.to_string()).collect(), "Ch"),
+                    InputParamKind::ConstantOnly,
+                    true, // Shown inline as dropdown
+                );
+            }
+            SynthNodeTemplate::CvToCc => {
+                // Control input port
+                graph.add_input_param(
+                    node_id,
+                    "CV".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::scalar(0.0, ""),
+                    InputParamKind::ConnectionOnly,
+                    true,
+                );
+
+                // CC: knob-only parameter (controller number 0-127)
+                graph.add_input_param(
+                    node_id,
+                    "CC".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::linear_range(1.0, 0.0, 127.0, "", ""),
+                    InputParamKind::ConstantOnly,
+                    false, // Hidden inline - shown in bottom knob row
+                );
+
+                // MIDI channel the messages are sent on
+                graph.add_input_param(
+                    node_id,
+                    "Channel".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::select(0, (1..=16).map(|channel| channel.to_string()).collect(), "Ch"),
+                    InputParamKind::ConstantOnly,
+                    true, // Shown inline as dropdown
+                );
+            }
             SynthNodeTemplate::Lfo => {
                 // Rate: exposed parameter (Rate CV input + knob at bottom)
                 graph.add_input_param(
@@ -878,6 +1192,26 @@ impl NodeTemplateTrait for SynthNodeTemplate {
                     true, // Shown inline as checkbox
                 );
 
+                // MIDI clock output toggle (shown inline)
+                graph.add_input_param(
+                    node_id,
+                    "MIDI Clock".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::toggle(false, "MIDI Clock"),
+                    InputParamKind::ConstantOnly,
+                    true, // Shown inline as checkbox
+                );
+
+                // Tempo source: internal or external MIDI clock (shown inline)
+                graph.add_input_param(
+                    node_id,
+                    "Source".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::select(0, vec!["Internal".to_string(), "MIDI".to_string()], "Source"),
+                    InputParamKind::ConstantOnly,
+                    true, // Shown inline as dropdown
+                );
+
                 // Gate output port
                 graph.add_output_param(
                     node_id,
@@ -971,6 +1305,34 @@ impl NodeTemplateTrait for SynthNodeTemplate {
                     SynthDataType::new(SignalType::Control),
                 );
             }
+            SynthNodeTemplate::SceneMorph => {
+                // CV input, added to the position knob
+                graph.add_input_param(
+                    node_id,
+                    "CV".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::scalar(0.0, ""),
+                    InputParamKind::ConnectionOnly,
+                    true,
+                );
+
+                // Position: knob-only parameter (0 = scene A, 1 = scene B)
+                graph.add_input_param(
+                    node_id,
+                    "Position".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::linear_range(0.0, 0.0, 1.0, "", ""),
+                    InputParamKind::ConstantOnly,
+                    false, // Hidden inline - shown in bottom knob row
+                );
+
+                // Morph position output
+                graph.add_output_param(
+                    node_id,
+                    "Out".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                );
+            }
             SynthNodeTemplate::Mixer => {
                 // Channel 1 audio input
                 graph.add_input_param(
@@ -1093,6 +1455,26 @@ impl NodeTemplateTrait for SynthNodeTemplate {
                     SynthDataType::new(SignalType::Control),
                 );
             }
+            SynthNodeTemplate::AudioInput => {
+                // Gain: knob-only parameter
+                graph.add_input_param(
+                    node_id,
+                    "Gain".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::linear_range(0.0, -24.0, 24.0, "dB", ""),
+                    InputParamKind::ConstantOnly,
+                    false,
+                );
+
+                // One output per input channel
+                for channel in 1..=8 {
+                    graph.add_output_param(
+                        node_id,
+                        format!("In {}", channel),
+                        SynthDataType::new(SignalType::Audio),
+                    );
+                }
+            }
             SynthNodeTemplate::MidiMonitor => {
                 // Channel filter (0 = all, 1-16 = specific channel)
                 graph.add_input_param(
@@ -1263,6 +1645,159 @@ impl NodeTemplateTrait for SynthNodeTemplate {
                     SynthDataType::new(SignalType::Control),
                 );
             }
+            SynthNodeTemplate::MpeVoice => {
+                // Voice state: controlled by MIDI events
+                // Hidden parameters - not shown inline
+                graph.add_input_param(
+                    node_id,
+                    "Pitch".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::linear_range(0.0, -10.0, 10.0, "V", ""),
+                    InputParamKind::ConstantOnly,
+                    false, // Hidden - controlled by MIDI events
+                );
+                graph.add_input_param(
+                    node_id,
+                    "Gate".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::toggle(false, ""),
+                    InputParamKind::ConstantOnly,
+                    false, // Hidden - controlled by MIDI events
+                );
+                for (name, default) in [("Velocity", 0.0), ("Pressure", 0.0), ("Slide", 0.5)] {
+                    graph.add_input_param(
+                        node_id,
+                        name.to_string(),
+                        SynthDataType::new(SignalType::Control),
+                        SynthValueType::linear_range(default, 0.0, 1.0, "", ""),
+                        InputParamKind::ConstantOnly,
+                        false, // Hidden - controlled by MIDI events
+                    );
+                }
+
+                // Zone: lower (master channel 1) or upper (master channel 16)
+                graph.add_input_param(
+                    node_id,
+                    "Zone".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::select(0, vec!["Lower".to_string(), "Upper".to_string()], "Zone"),
+                    InputParamKind::ConstantOnly,
+                    true, // Shown inline as dropdown
+                );
+
+                // Member channels: 1-15
+                graph.add_input_param(
+                    node_id,
+                    "Channels".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::linear_range(15.0, 1.0, 15.0, "", ""),
+                    InputParamKind::ConstantOnly,
+                    false, // Hidden inline - shown in bottom knob row
+                );
+
+                // Pitch bend range of the member channels: 1-96 semitones
+                graph.add_input_param(
+                    node_id,
+                    "Bend Range".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::linear_range(48.0, 1.0, 96.0, "st", ""),
+                    InputParamKind::ConstantOnly,
+                    false, // Hidden inline - shown in bottom knob row
+                );
+
+                // Which voice of the zone this module outputs
+                graph.add_input_param(
+                    node_id,
+                    "Voice".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::select(0, (1..=15).map(|voice| voice.to_string()).collect(), "Voice"),
+                    InputParamKind::ConstantOnly,
+                    true, // Shown inline as dropdown
+                );
+
+                // Output ports
+                for (name, signal_type) in [
+                    ("Pitch", SignalType::Control),
+                    ("Gate", SignalType::Gate),
+                    ("Velocity", SignalType::Control),
+                    ("Pressure", SignalType::Control),
+                    ("Slide", SignalType::Control),
+                ] {
+                    graph.add_output_param(node_id, name.to_string(), SynthDataType::new(signal_type));
+                }
+            }
+            SynthNodeTemplate::MidiFilePlayer => {
+                // Reset input port
+                graph.add_input_param(
+                    node_id,
+                    "Reset".to_string(),
+                    SynthDataType::new(SignalType::Gate),
+                    SynthValueType::scalar(0.0, ""),
+                    InputParamKind::ConnectionOnly,
+                    true,
+                );
+
+                // Track: which track of the file to play (0=All)
+                graph.add_input_param(
+                    node_id,
+                    "Track".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::select(
+                        0,
+                        std::iter::once("All".to_string())
+                            .chain((1..=16).map(|track| track.to_string()))
+                            .collect(),
+                        "Track",
+                    ),
+                    InputParamKind::ConstantOnly,
+                    true, // Shown inline as dropdown
+                );
+
+                // Channel: which MIDI channel to play (0=Omni)
+                graph.add_input_param(
+                    node_id,
+                    "Channel".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::select(
+                        0,
+                        std::iter::once("Omni".to_string())
+                            .chain((1..=16).map(|channel| channel.to_string()))
+                            .collect(),
+                        "Ch",
+                    ),
+                    InputParamKind::ConstantOnly,
+                    true, // Shown inline as dropdown
+                );
+
+                // Sync: the file's own tempo, or the engine transport
+                graph.add_input_param(
+                    node_id,
+                    "Sync".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::select(0, vec!["Free".to_string(), "Transport".to_string()], "Sync"),
+                    InputParamKind::ConstantOnly,
+                    true, // Shown inline as dropdown
+                );
+
+                // Loop toggle (shown inline)
+                graph.add_input_param(
+                    node_id,
+                    "Loop".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::toggle(true, "Loop"),
+                    InputParamKind::ConstantOnly,
+                    true, // Shown inline as checkbox
+                );
+
+                // Output ports
+                for (name, signal_type) in [
+                    ("Pitch", SignalType::Control),
+                    ("Gate", SignalType::Gate),
+                    ("Velocity", SignalType::Control),
+                ] {
+                    graph.add_output_param(node_id, name.to_string(), SynthDataType::new(signal_type));
+                }
+            }
             SynthNodeTemplate::SampleHold => {
                 // Signal input port
                 graph.add_input_param(
@@ -1301,6 +1836,92 @@ impl NodeTemplateTrait for SynthNodeTemplate {
                     SynthDataType::new(SignalType::Control),
                 );
             }
+            SynthNodeTemplate::Quantizer => {
+                use crate::modules::quantizer::{MASK_NAMES, ROOT_LABELS, SCALE_LABELS};
+
+                // Pitch input port
+                graph.add_input_param(
+                    node_id,
+                    "In".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::scalar(0.0, ""),
+                    InputParamKind::ConnectionOnly,
+                    true,
+                );
+
+                // Transpose input port
+                graph.add_input_param(
+                    node_id,
+                    "Transpose".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::scalar(0.0, ""),
+                    InputParamKind::ConnectionOnly,
+                    true,
+                );
+
+                // Scale: dropdown selector (shown inline)
+                graph.add_input_param(
+                    node_id,
+                    "Scale".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::select(
+                        1, // Major
+                        SCALE_LABELS.iter().map(|label| label.to_string()).collect(),
+                        "Scale",
+                    ),
+                    InputParamKind::ConstantOnly,
+                    true, // Shown inline as dropdown
+                );
+
+                // Root: dropdown selector (shown inline)
+                graph.add_input_param(
+                    node_id,
+                    "Root".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::select(
+                        0, // C
+                        ROOT_LABELS.iter().map(|label| label.to_string()).collect(),
+                        "Root",
+                    ),
+                    InputParamKind::ConstantOnly,
+                    true, // Shown inline as dropdown
+                );
+
+                // Hysteresis: knob-only parameter (0-50 cents)
+                graph.add_input_param(
+                    node_id,
+                    "Hysteresis".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                    SynthValueType::linear_range(10.0, 0.0, 50.0, "ct", ""),
+                    InputParamKind::ConstantOnly,
+                    false, // Hidden inline - shown in bottom knob row
+                );
+
+                // Custom scale mask (12 toggles, hidden)
+                // These are controlled via the custom quantizer UI
+                for (degree, name) in MASK_NAMES.iter().enumerate() {
+                    graph.add_input_param(
+                        node_id,
+                        name.to_string(),
+                        SynthDataType::new(SignalType::Control),
+                        SynthValueType::toggle(matches!(degree, 0 | 2 | 4 | 5 | 7 | 9 | 11), ""), // Major scale
+                        InputParamKind::ConstantOnly,
+                        false, // Hidden - controlled via custom UI
+                    );
+                }
+
+                // Output ports
+                graph.add_output_param(
+                    node_id,
+                    "Out".to_string(),
+                    SynthDataType::new(SignalType::Control),
+                );
+                graph.add_output_param(
+                    node_id,
+                    "Trig".to_string(),
+                    SynthDataType::new(SignalType::Gate),
+                );
+            }
             SynthNodeTemplate::Oscilloscope => {
                 // Input 1: Primary signal (audio or control)
                 graph.add_input_param(
@@ -2057,11 +2678,17 @@ mod tests {
     #[test]
     fn test_all_templates() {
         let templates = AllNodeTemplates.all_kinds();
-        assert_eq!(templates.len(), 21);
+        assert_eq!(templates.len(), 30);
         assert!(templates.contains(&SynthNodeTemplate::SineOscillator));
+        assert!(templates.contains(&SynthNodeTemplate::AudioInput));
         assert!(templates.contains(&SynthNodeTemplate::AudioOutput));
+        assert!(templates.contains(&SynthNodeTemplate::Recorder));
+        assert!(templates.contains(&SynthNodeTemplate::MultiOutput));
+        assert!(templates.contains(&SynthNodeTemplate::CvToMidi));
+        assert!(templates.contains(&SynthNodeTemplate::CvToCc));
         assert!(templates.contains(&SynthNodeTemplate::Lfo));
         assert!(templates.contains(&SynthNodeTemplate::Mixer));
+        assert!(templates.contains(&SynthNodeTemplate::SceneMorph));
         assert!(templates.contains(&SynthNodeTemplate::SvfFilter));
         assert!(templates.contains(&SynthNodeTemplate::AdsrEnvelope));
         assert!(templates.contains(&SynthNodeTemplate::Clock));
@@ -2071,7 +2698,10 @@ mod tests {
         assert!(templates.contains(&SynthNodeTemplate::Keyboard));
         assert!(templates.contains(&SynthNodeTemplate::MidiMonitor));
         assert!(templates.contains(&SynthNodeTemplate::MidiNote));
+        assert!(templates.contains(&SynthNodeTemplate::MpeVoice));
+        assert!(templates.contains(&SynthNodeTemplate::MidiFilePlayer));
         assert!(templates.contains(&SynthNodeTemplate::SampleHold));
+        assert!(templates.contains(&SynthNodeTemplate::Quantizer));
         assert!(templates.contains(&SynthNodeTemplate::Oscilloscope));
         assert!(templates.contains(&SynthNodeTemplate::StepSequencer));
         assert!(templates.contains(&SynthNodeTemplate::StereoDelay));
@@ -2085,6 +2715,10 @@ mod tests {
     fn test_module_id() {
         assert_eq!(SynthNodeTemplate::SineOscillator.module_id(), "osc.sine");
         assert_eq!(SynthNodeTemplate::AudioOutput.module_id(), "output.audio");
+        assert_eq!(SynthNodeTemplate::Recorder.module_id(), "output.recorder");
+        assert_eq!(SynthNodeTemplate::MultiOutput.module_id(), "output.multi");
+        assert_eq!(SynthNodeTemplate::CvToMidi.module_id(), "output.cv_to_midi");
+        assert_eq!(SynthNodeTemplate::CvToCc.module_id(), "output.cv_to_cc");
         assert_eq!(SynthNodeTemplate::Lfo.module_id(), "mod.lfo");
         assert_eq!(SynthNodeTemplate::SvfFilter.module_id(), "filter.svf");
         assert_eq!(SynthNodeTemplate::AdsrEnvelope.module_id(), "mod.adsr");
@@ -2092,9 +2726,13 @@ mod tests {
         assert_eq!(SynthNodeTemplate::Vca.module_id(), "util.vca");
         assert_eq!(SynthNodeTemplate::Attenuverter.module_id(), "util.attenuverter");
         assert_eq!(SynthNodeTemplate::Keyboard.module_id(), "input.keyboard");
+        assert_eq!(SynthNodeTemplate::AudioInput.module_id(), "input.audio");
         assert_eq!(SynthNodeTemplate::MidiMonitor.module_id(), "util.midi_monitor");
         assert_eq!(SynthNodeTemplate::MidiNote.module_id(), "input.midi_note");
+        assert_eq!(SynthNodeTemplate::MpeVoice.module_id(), "input.mpe_voice");
+        assert_eq!(SynthNodeTemplate::MidiFilePlayer.module_id(), "input.midi_file");
         assert_eq!(SynthNodeTemplate::SampleHold.module_id(), "util.sample_hold");
+        assert_eq!(SynthNodeTemplate::Quantizer.module_id(), "util.quantizer");
         assert_eq!(SynthNodeTemplate::Oscilloscope.module_id(), "util.oscilloscope");
         assert_eq!(SynthNodeTemplate::StepSequencer.module_id(), "seq.step");
         assert_eq!(SynthNodeTemplate::StereoDelay.module_id(), "fx.delay");
@@ -2103,12 +2741,17 @@ mod tests {
         assert_eq!(SynthNodeTemplate::Distortion.module_id(), "fx.distortion");
         assert_eq!(SynthNodeTemplate::Chorus.module_id(), "fx.chorus");
         assert_eq!(SynthNodeTemplate::Compressor.module_id(), "fx.compressor");
+        assert_eq!(SynthNodeTemplate::SceneMorph.module_id(), "util.scene_morph");
     }
 
     #[test]
     fn test_category() {
         assert_eq!(SynthNodeTemplate::SineOscillator.category(), ModuleCategory::Source);
         assert_eq!(SynthNodeTemplate::AudioOutput.category(), ModuleCategory::Output);
+        assert_eq!(SynthNodeTemplate::Recorder.category(), ModuleCategory::Output);
+        assert_eq!(SynthNodeTemplate::MultiOutput.category(), ModuleCategory::Output);
+        assert_eq!(SynthNodeTemplate::CvToMidi.category(), ModuleCategory::Output);
+        assert_eq!(SynthNodeTemplate::CvToCc.category(), ModuleCategory::Output);
         assert_eq!(SynthNodeTemplate::Lfo.category(), ModuleCategory::Modulation);
         assert_eq!(SynthNodeTemplate::SvfFilter.category(), ModuleCategory::Filter);
         assert_eq!(SynthNodeTemplate::AdsrEnvelope.category(), ModuleCategory::Modulation);
@@ -2116,9 +2759,13 @@ mod tests {
         assert_eq!(SynthNodeTemplate::Vca.category(), ModuleCategory::Utility);
         assert_eq!(SynthNodeTemplate::Attenuverter.category(), ModuleCategory::Utility);
         assert_eq!(SynthNodeTemplate::Keyboard.category(), ModuleCategory::Source);
+        assert_eq!(SynthNodeTemplate::AudioInput.category(), ModuleCategory::Source);
         assert_eq!(SynthNodeTemplate::MidiMonitor.category(), ModuleCategory::Utility);
         assert_eq!(SynthNodeTemplate::MidiNote.category(), ModuleCategory::Source);
+        assert_eq!(SynthNodeTemplate::MpeVoice.category(), ModuleCategory::Source);
+        assert_eq!(SynthNodeTemplate::MidiFilePlayer.category(), ModuleCategory::Source);
         assert_eq!(SynthNodeTemplate::SampleHold.category(), ModuleCategory::Utility);
+        assert_eq!(SynthNodeTemplate::Quantizer.category(), ModuleCategory::Utility);
         assert_eq!(SynthNodeTemplate::Oscilloscope.category(), ModuleCategory::Utility);
         assert_eq!(SynthNodeTemplate::StepSequencer.category(), ModuleCategory::Utility);
         assert_eq!(SynthNodeTemplate::StereoDelay.category(), ModuleCategory::Effect);
@@ -2127,6 +2774,7 @@ mod tests {
         assert_eq!(SynthNodeTemplate::Distortion.category(), ModuleCategory::Effect);
         assert_eq!(SynthNodeTemplate::Chorus.category(), ModuleCategory::Effect);
         assert_eq!(SynthNodeTemplate::Compressor.category(), ModuleCategory::Effect);
+        assert_eq!(SynthNodeTemplate::SceneMorph.category(), ModuleCategory::Utility);
     }
 
     #[test]
@@ -2180,6 +2828,10 @@ mod tests {
             SynthNodeTemplate::SampleHold.node_finder_label(&mut state),
             "Sample & Hold"
         );
+        assert_eq!(
+            SynthNodeTemplate::Quantizer.node_finder_label(&mut state),
+            "Quantizer"
+        );
         assert_eq!(
             SynthNodeTemplate::Oscilloscope.node_finder_label(&mut state),
             "Oscilloscope"
@@ -2209,4 +2861,23 @@ mod tests {
             "Chorus"
         );
     }
+
+    #[test]
+    fn test_from_module_id() {
+        for template in AllNodeTemplates.all_kinds() {
+            assert_eq!(SynthNodeTemplate::from_module_id(template.module_id()), Some(template));
+        }
+        assert_eq!(SynthNodeTemplate::from_module_id("osc.nonexistent"), None);
+    }
+
+    #[test]
+    fn test_layout() {
+        let layout = SynthNodeTemplate::SineOscillator.layout();
+        assert_eq!(layout.inputs, vec!["V/Oct", "FM", "Frequency", "PWM"]);
+        assert_eq!(layout.outputs, vec!["Out"]);
+
+        let names: Vec<&str> = layout.parameters.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["Frequency", "FM Depth", "Waveform", "Pulse Width"]);
+        assert!((layout.parameters[0].1.actual_value() - 440.0).abs() < 0.01);
+    }
 }
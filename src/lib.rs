@@ -9,3 +9,6 @@ pub mod graph;
 pub mod modules;
 pub mod persistence;
 pub mod widgets;
+
+#[cfg(test)]
+mod test_util;
@@ -1,26 +1,109 @@
 //! Modular Synth - A node-based modular audio synthesizer
 //!
 //! Entry point for the application.
+//!
+//! `modular_synth lint <patch>...` checks patch files for common mistakes
+//! instead of starting the synthesizer; it exits with status 1 if any patch
+//! has errors.
+//!
+//! `--virtual-midi[=NAME]` publishes a virtual MIDI input port for the
+//! session, so other applications and scripts can play the synth.
+
+use std::path::Path;
 
 use eframe::egui;
 use modular_synth::app::SynthApp;
+use modular_synth::dsp::ModuleRegistry;
+use modular_synth::engine::{create_module_registry, DEFAULT_VIRTUAL_PORT_NAME};
+use modular_synth::persistence::{
+    dsl, lint, load_from_file, paths, AppConfig, Patch, PatchError, DSL_EXTENSION,
+};
 
 fn main() -> eframe::Result<()> {
     // Parse command line arguments
     let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("lint") {
+        std::process::exit(lint_command(&args[2..]));
+    }
     let test_tone = args.iter().any(|arg| arg == "--test-tone");
+    let virtual_midi = args.iter().find_map(|arg| match arg.as_str() {
+        "--virtual-midi" => Some(DEFAULT_VIRTUAL_PORT_NAME.to_string()),
+        _ => arg.strip_prefix("--virtual-midi=").map(str::to_string),
+    });
+
+    // Reopen the window where it was last time
+    let config = AppConfig::load(&paths::config_file());
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_inner_size([1280.0, 720.0])
+        .with_min_inner_size([800.0, 600.0])
+        .with_title("Modular Synth");
+    if let Some(window) = config.window {
+        viewport = viewport
+            .with_inner_size([window.width.max(800.0), window.height.max(600.0)])
+            .with_maximized(window.maximized);
+        if let Some((x, y)) = window.position {
+            viewport = viewport.with_position([x, y]);
+        }
+    }
 
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([1280.0, 720.0])
-            .with_min_inner_size([800.0, 600.0])
-            .with_title("Modular Synth"),
+        viewport,
         ..Default::default()
     };
 
     eframe::run_native(
         "Modular Synth",
         options,
-        Box::new(move |_cc| Ok(Box::new(SynthApp::new(test_tone)))),
+        Box::new(move |cc| {
+            let mut app = SynthApp::new(test_tone, config);
+            app.wake_on_midi(cc.egui_ctx.clone());
+            if let Some(name) = &virtual_midi {
+                app.open_virtual_midi_port(name);
+            }
+            Ok(Box::new(app))
+        }),
     )
 }
+
+/// Lint patch files, printing one line per finding. Returns the exit status.
+fn lint_command(paths: &[String]) -> i32 {
+    if paths.is_empty() {
+        eprintln!("usage: modular_synth lint <patch.json | patch.{}>...", DSL_EXTENSION);
+        return 2;
+    }
+
+    let registry = create_module_registry();
+    let mut failed = false;
+
+    for path in paths {
+        let patch = match read_patch(Path::new(path), &registry) {
+            Ok(patch) => patch,
+            Err(e) => {
+                eprintln!("{}: error: {}", path, e);
+                failed = true;
+                continue;
+            }
+        };
+
+        let lints = lint::lint(&patch, &registry);
+        for finding in &lints {
+            println!("{}: {}", path, finding);
+        }
+        if lints.is_empty() {
+            println!("{}: ok", path);
+        }
+        failed |= lint::has_errors(&lints);
+    }
+
+    i32::from(failed)
+}
+
+/// Read a JSON patch, or compile a patch script.
+fn read_patch(path: &Path, registry: &ModuleRegistry) -> Result<Patch, PatchError> {
+    if path.extension().and_then(|ext| ext.to_str()) != Some(DSL_EXTENSION) {
+        return load_from_file(path);
+    }
+
+    let source = std::fs::read_to_string(path)?;
+    Ok(dsl::compile(&source, registry)?)
+}
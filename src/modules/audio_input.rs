@@ -0,0 +1,199 @@
+//! Audio input module.
+//!
+//! Brings the signal of the selected audio input device into the patch,
+//! so external instruments and vocals can be processed by the effects.
+
+use crate::dsp::{
+    context::ProcessContext,
+    module_trait::{DspModule, ModuleCategory, ModuleInfo},
+    parameter::{ParameterDefinition, ParameterDisplay},
+    port::PortDefinition,
+    signal::SignalBuffer,
+    smoothed_value::SmoothedValue,
+    SignalType,
+};
+
+/// A source that outputs the channels of the audio input device.
+///
+/// The device is chosen in the toolbar; the engine passes its signal to
+/// this module before each block. Channels the device doesn't have, and
+/// all channels while no input device is selected, output silence.
+///
+/// # Ports
+///
+/// **Outputs:**
+/// - **In 1** to **In 8** (Audio): One output per input channel.
+///
+/// # Parameters
+///
+/// - **Gain** (-24 to +24 dB): Input gain applied to every channel, default 0 dB.
+pub struct AudioInput {
+    /// Port definitions.
+    ports: Vec<PortDefinition>,
+    /// Parameter definitions.
+    parameters: Vec<ParameterDefinition>,
+    /// Input signal of the next block, one buffer per output.
+    input: Vec<Vec<f32>>,
+    /// Number of channels received from the device.
+    channels: usize,
+    /// Smoothed linear gain.
+    gain_smooth: SmoothedValue,
+}
+
+impl AudioInput {
+    /// Creates a new audio input module.
+    pub fn new() -> Self {
+        Self {
+            ports: vec![
+                PortDefinition::output("in1", "In 1", SignalType::Audio),
+                PortDefinition::output("in2", "In 2", SignalType::Audio),
+                PortDefinition::output("in3", "In 3", SignalType::Audio),
+                PortDefinition::output("in4", "In 4", SignalType::Audio),
+                PortDefinition::output("in5", "In 5", SignalType::Audio),
+                PortDefinition::output("in6", "In 6", SignalType::Audio),
+                PortDefinition::output("in7", "In 7", SignalType::Audio),
+                PortDefinition::output("in8", "In 8", SignalType::Audio),
+            ],
+            parameters: vec![ParameterDefinition::new(
+                "gain",
+                "Gain",
+                -24.0,
+                24.0,
+                0.0,
+                ParameterDisplay::linear("dB"),
+            ).not_randomized()],
+            input: vec![Vec::new(); Self::CHANNELS],
+            channels: 0,
+            gain_smooth: SmoothedValue::with_default_smoothing(1.0, 44100.0),
+        }
+    }
+
+    /// Number of channel outputs.
+    const CHANNELS: usize = 8;
+
+    /// Parameter index constants.
+    const PARAM_GAIN: usize = 0;
+
+    /// Number of channels the input device provided for the last block.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+}
+
+impl Default for AudioInput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DspModule for AudioInput {
+    fn info(&self) -> &ModuleInfo {
+        static INFO: ModuleInfo = ModuleInfo {
+            id: "input.audio",
+            name: "Audio Input",
+            category: ModuleCategory::Source,
+            description: "Channels of the audio input device",
+        };
+        &INFO
+    }
+
+    fn ports(&self) -> &[PortDefinition] {
+        &self.ports
+    }
+
+    fn parameters(&self) -> &[ParameterDefinition] {
+        &self.parameters
+    }
+
+    fn prepare(&mut self, sample_rate: f32, max_block_size: usize) {
+        for buffer in &mut self.input {
+            buffer.resize(max_block_size, 0.0);
+        }
+        self.gain_smooth.set_sample_rate(sample_rate);
+    }
+
+    fn uses_audio_input(&self) -> bool {
+        true
+    }
+
+    fn set_audio_input(&mut self, channels: &[&[f32]]) {
+        self.channels = channels.len().min(Self::CHANNELS);
+        for (index, buffer) in self.input.iter_mut().enumerate() {
+            buffer.clear();
+            if let Some(samples) = channels.get(index) {
+                buffer.extend_from_slice(samples);
+            }
+        }
+    }
+
+    fn process(
+        &mut self,
+        _inputs: &[&SignalBuffer],
+        outputs: &mut [SignalBuffer],
+        params: &[f32],
+        context: &ProcessContext,
+    ) {
+        let gain_db = params[Self::PARAM_GAIN];
+        self.gain_smooth.set_target(10.0_f32.powf(gain_db / 20.0));
+
+        for i in 0..context.block_size {
+            let gain = self.gain_smooth.next();
+            for (output, input) in outputs.iter_mut().zip(&self.input) {
+                if let Some(out) = output.samples.get_mut(i) {
+                    *out = input.get(i).copied().unwrap_or(0.0) * gain;
+                }
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for buffer in &mut self.input {
+            buffer.clear();
+        }
+        self.channels = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_input_info() {
+        let input = AudioInput::new();
+        assert_eq!(input.info().id, "input.audio");
+        assert_eq!(input.info().category, ModuleCategory::Source);
+        assert_eq!(input.ports().len(), AudioInput::CHANNELS);
+        assert!(input.ports().iter().all(|p| p.is_output()));
+        assert_eq!(input.parameters()[0].id, "gain");
+        assert!(input.uses_audio_input());
+    }
+
+    #[test]
+    fn test_audio_input_outputs_channels() {
+        let mut input = AudioInput::new();
+        input.prepare(44100.0, 4);
+        input.set_audio_input(&[&[0.5; 4], &[-0.25; 4]]);
+        assert_eq!(input.channels(), 2);
+
+        let mut outputs: Vec<SignalBuffer> = (0..AudioInput::CHANNELS).map(|_| SignalBuffer::audio(4)).collect();
+        let ctx = ProcessContext::new(44100.0, 4);
+        input.process(&[], &mut outputs, &[0.0], &ctx);
+
+        assert!((outputs[0].samples[3] - 0.5).abs() < 1e-6);
+        assert!((outputs[1].samples[3] + 0.25).abs() < 1e-6);
+        assert!(outputs[2].samples.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_audio_input_silent_without_device() {
+        let mut input = AudioInput::new();
+        input.prepare(44100.0, 4);
+        input.set_audio_input(&[]);
+
+        let mut outputs: Vec<SignalBuffer> = (0..AudioInput::CHANNELS).map(|_| SignalBuffer::audio(4)).collect();
+        let ctx = ProcessContext::new(44100.0, 4);
+        input.process(&[], &mut outputs, &[6.0], &ctx);
+        assert!(outputs.iter().all(|buf| buf.samples.iter().all(|&s| s == 0.0)));
+    }
+}
@@ -1,13 +1,14 @@
 //! Clock module.
 //!
 //! Generates periodic gate triggers for driving envelopes and creating rhythmic patterns.
+//! Can also send MIDI clock to external gear, or follow an external MIDI clock.
 
 use crate::dsp::{
     context::ProcessContext,
     module_trait::{DspModule, ModuleCategory, ModuleInfo},
     parameter::ParameterDefinition,
     port::PortDefinition,
-    signal::SignalBuffer,
+    signal::{MidiEvent, MidiMessage, SignalBuffer},
     ParameterDisplay, SignalType,
 };
 
@@ -39,6 +40,11 @@ impl ClockDivision {
         }
     }
 
+    /// Number of MIDI clock ticks (24 per quarter note) in one cycle of this division.
+    pub fn midi_ticks(&self) -> u32 {
+        (self.beat_multiplier() * Clock::MIDI_PPQN as f32) as u32
+    }
+
     /// Get the beat multiplier for this division.
     /// Quarter note = 1.0 beat, whole = 4.0, sixteenth = 0.25
     pub fn beat_multiplier(&self) -> f32 {
@@ -68,11 +74,27 @@ impl ClockDivision {
 /// - **Gate Length** (1-99%): Duration of the gate high as percentage of beat.
 /// - **Division** (0-4): Note division (whole, half, quarter, eighth, sixteenth).
 /// - **Run** (toggle): Whether the clock is running.
+/// - **MIDI Clock** (toggle): Send MIDI clock at 24 ticks per quarter note,
+///   with Start and Stop as the clock starts and stops. The ticks are locked
+///   to the gate, so a sync pulse realigns them too.
+/// - **Source** (Internal/MIDI): Where the tempo comes from. With MIDI the
+///   clock follows the tempo and transport of an external MIDI clock (see
+///   `TransportState`): it runs only while the external sequencer plays and
+///   starts from its song position. The Tempo knob is used until the first
+///   clock tick arrives.
 pub struct Clock {
     /// Current phase within the beat cycle (0.0 to 1.0).
     phase: f32,
     /// Previous sync state for edge detection.
     prev_sync: bool,
+    /// Whether MIDI clock was being sent in the last block.
+    midi_running: bool,
+    /// Whether the clock was following a playing external transport in the last block.
+    following: bool,
+    /// MIDI clock tick of the cycle that was last sent (None = none yet).
+    last_tick: Option<u32>,
+    /// MIDI messages generated by the last block, waiting to be sent.
+    midi_out: Vec<MidiEvent>,
     /// Sample rate from last prepare() call.
     sample_rate: f32,
     /// Port definitions.
@@ -87,6 +109,10 @@ impl Clock {
         Self {
             phase: 0.0,
             prev_sync: false,
+            midi_running: false,
+            following: false,
+            last_tick: None,
+            midi_out: Vec::with_capacity(Self::MAX_MIDI_OUT),
             sample_rate: 44100.0,
             ports: vec![
                 // Input port
@@ -122,6 +148,10 @@ impl Clock {
                 ),
                 // Run toggle
                 ParameterDefinition::toggle("run", "Run", true),
+                // MIDI clock output toggle
+                ParameterDefinition::toggle("midi_clock", "MIDI Clock", false).not_randomized(),
+                // Tempo source: own tempo or an external MIDI clock
+                ParameterDefinition::choice("source", "Source", &["Internal", "MIDI"], 0).not_randomized(),
             ],
         }
     }
@@ -135,9 +165,25 @@ impl Clock {
     const PARAM_GATE_LENGTH: usize = 1;
     const PARAM_DIVISION: usize = 2;
     const PARAM_RUN: usize = 3;
+    pub const PARAM_MIDI_CLOCK: usize = 4;
+    pub const PARAM_SOURCE: usize = 5;
 
     /// Sync threshold for detecting high/low states.
     const SYNC_THRESHOLD: f32 = 0.5;
+
+    /// MIDI clock ticks per quarter note.
+    pub const MIDI_PPQN: u32 = 24;
+
+    /// Most MIDI messages kept per block; more are dropped rather than
+    /// allocating on the audio thread.
+    const MAX_MIDI_OUT: usize = 256;
+
+    /// Queue a MIDI message, dropping it if the block already has too many.
+    fn send_midi(&mut self, sample_offset: usize, message: MidiMessage) {
+        if self.midi_out.len() < Self::MAX_MIDI_OUT {
+            self.midi_out.push(MidiEvent::new(sample_offset as u32, 0, message));
+        }
+    }
 }
 
 impl Default for Clock {
@@ -176,10 +222,28 @@ impl DspModule for Clock {
         params: &[f32],
         context: &ProcessContext,
     ) {
-        let tempo = params[Self::PARAM_TEMPO];
         let gate_length_percent = params[Self::PARAM_GATE_LENGTH] / 100.0;
         let division = ClockDivision::from_param(params[Self::PARAM_DIVISION]);
-        let is_running = params[Self::PARAM_RUN] > 0.5;
+        let midi_clock = params[Self::PARAM_MIDI_CLOCK] > 0.5;
+
+        // With the MIDI source, run at the external tempo while it plays
+        let external = params[Self::PARAM_SOURCE] > 0.5;
+        let transport = &context.transport;
+        let tempo = if external {
+            transport.tempo_bpm.unwrap_or(params[Self::PARAM_TEMPO])
+        } else {
+            params[Self::PARAM_TEMPO]
+        };
+        let is_running = params[Self::PARAM_RUN] > 0.5 && (!external || transport.playing);
+
+        // Pick up from the external song position when it starts playing
+        let following = external && transport.playing;
+        if following && !self.following {
+            let beats = transport.position_in_beats(context.sample_rate).unwrap_or(0.0);
+            self.phase = (beats / division.beat_multiplier() as f64).fract() as f32;
+            self.last_tick = None;
+        }
+        self.following = following;
 
         // Get sync input
         let sync_in = inputs.get(Self::PORT_SYNC);
@@ -194,6 +258,20 @@ impl DspModule for Clock {
         let beats_per_second = tempo / 60.0;
         let samples_per_cycle = self.sample_rate / beats_per_second * division.beat_multiplier();
         let phase_increment = 1.0 / samples_per_cycle;
+        let ticks_per_cycle = division.midi_ticks();
+
+        // Start and stop the external gear, starting from the downbeat
+        let midi_running = midi_clock && is_running;
+        if midi_running && !self.midi_running {
+            if !external {
+                self.phase = 0.0;
+                self.last_tick = None;
+            }
+            self.send_midi(0, MidiMessage::Start);
+        } else if !midi_running && self.midi_running {
+            self.send_midi(0, MidiMessage::Stop);
+        }
+        self.midi_running = midi_running;
 
         // Process each sample
         for i in 0..context.block_size {
@@ -218,6 +296,15 @@ impl DspModule for Clock {
             };
             output.samples[i] = gate_out;
 
+            // Send a MIDI clock tick each time the phase enters a new tick
+            if midi_running {
+                let tick = (self.phase * ticks_per_cycle as f32) as u32;
+                if self.last_tick != Some(tick) {
+                    self.last_tick = Some(tick);
+                    self.send_midi(i, MidiMessage::Clock);
+                }
+            }
+
             // Advance phase if running
             if is_running {
                 self.phase += phase_increment;
@@ -233,6 +320,12 @@ impl DspModule for Clock {
     fn reset(&mut self) {
         self.phase = 0.0;
         self.prev_sync = false;
+        self.following = false;
+        self.last_tick = None;
+    }
+
+    fn take_midi_output(&mut self, messages: &mut Vec<MidiEvent>) {
+        messages.append(&mut self.midi_out);
     }
 }
 
@@ -271,7 +364,7 @@ mod tests {
         let clock = Clock::new();
         let params = clock.parameters();
 
-        assert_eq!(params.len(), 4);
+        assert_eq!(params.len(), 6);
 
         // Tempo
         assert_eq!(params[0].id, "tempo");
@@ -292,6 +385,14 @@ mod tests {
         // Run
         assert_eq!(params[3].id, "run");
         assert_eq!(params[3].default, 1.0); // Running by default
+
+        // MIDI Clock
+        assert_eq!(params[4].id, "midi_clock");
+        assert_eq!(params[4].default, 0.0); // Off by default
+
+        // Source
+        assert_eq!(params[5].id, "source");
+        assert_eq!(params[5].default, 0.0); // Internal by default
     }
 
     #[test]
@@ -322,7 +423,7 @@ mod tests {
         let ctx = ProcessContext::new(44100.0, 256);
 
         // Run = false (0.0)
-        clock.process(&[], &mut outputs, &[120.0, 50.0, 2.0, 0.0], &ctx);
+        clock.process(&[], &mut outputs, &[120.0, 50.0, 2.0, 0.0, 0.0, 0.0], &ctx);
 
         // All outputs should be zero when stopped
         assert!(outputs[0].samples.iter().all(|&s| s == 0.0));
@@ -339,7 +440,7 @@ mod tests {
 
         // 120 BPM, 50% gate, quarter note, running
         // At 120 BPM: 2 beats per second, so 22050 samples per beat
-        clock.process(&[], &mut outputs, &[120.0, 50.0, 2.0, 1.0], &ctx);
+        clock.process(&[], &mut outputs, &[120.0, 50.0, 2.0, 1.0, 0.0, 0.0], &ctx);
 
         // Should have both high and low values
         let has_high = outputs[0].samples.iter().any(|&s| s == 1.0);
@@ -369,7 +470,7 @@ mod tests {
         // 60 BPM = 1 beat per second = 44100 samples per beat
         // Quarter note division, 50% gate length
         // So gate should be high for ~22050 samples, then low for ~22050
-        clock.process(&[], &mut outputs, &[60.0, 50.0, 2.0, 1.0], &ctx);
+        clock.process(&[], &mut outputs, &[60.0, 50.0, 2.0, 1.0, 0.0, 0.0], &ctx);
 
         // Count high samples in first beat
         let high_count = outputs[0].samples[..44100]
@@ -399,7 +500,7 @@ mod tests {
         let ctx = ProcessContext::new(sample_rate, 44100);
 
         // 60 BPM, 25% gate, quarter note
-        clock.process(&[], &mut outputs, &[60.0, 25.0, 2.0, 1.0], &ctx);
+        clock.process(&[], &mut outputs, &[60.0, 25.0, 2.0, 1.0, 0.0, 0.0], &ctx);
 
         let high_count = outputs[0].samples[..44100]
             .iter()
@@ -429,7 +530,7 @@ mod tests {
         // 60 BPM, eighth notes (0.5 beats)
         // At 60 BPM: 1 beat/sec, eighth = 0.5 beats = 0.5 sec = 22050 samples per cycle
         // In 2 seconds, should get 4 complete cycles
-        clock.process(&[], &mut outputs, &[60.0, 50.0, 3.0, 1.0], &ctx);
+        clock.process(&[], &mut outputs, &[60.0, 50.0, 3.0, 1.0, 0.0, 0.0], &ctx);
 
         // Count rising edges (transitions from 0 to 1)
         let mut rising_edges = 0;
@@ -459,7 +560,7 @@ mod tests {
         // Run clock to advance phase
         let mut outputs = vec![SignalBuffer::control(1000)];
         let ctx = ProcessContext::new(sample_rate, 1000);
-        clock.process(&[], &mut outputs, &[120.0, 50.0, 2.0, 1.0], &ctx);
+        clock.process(&[], &mut outputs, &[120.0, 50.0, 2.0, 1.0, 0.0, 0.0], &ctx);
 
         // Now send a sync pulse
         let mut sync = SignalBuffer::control(100);
@@ -467,7 +568,7 @@ mod tests {
 
         let mut outputs2 = vec![SignalBuffer::control(100)];
         let ctx2 = ProcessContext::new(sample_rate, 100);
-        clock.process(&[&sync], &mut outputs2, &[120.0, 50.0, 2.0, 1.0], &ctx2);
+        clock.process(&[&sync], &mut outputs2, &[120.0, 50.0, 2.0, 1.0, 0.0, 0.0], &ctx2);
 
         // After sync, the gate should be high (phase reset to 0, which is < gate_length)
         assert_eq!(
@@ -484,7 +585,7 @@ mod tests {
         // Advance the clock
         let mut outputs = vec![SignalBuffer::control(256)];
         let ctx = ProcessContext::new(44100.0, 256);
-        clock.process(&[], &mut outputs, &[120.0, 50.0, 2.0, 1.0], &ctx);
+        clock.process(&[], &mut outputs, &[120.0, 50.0, 2.0, 1.0, 0.0, 0.0], &ctx);
 
         // Reset
         clock.reset();
@@ -492,7 +593,7 @@ mod tests {
         // Phase should be back to 0, so first output should be high (0 < 0.5 gate length)
         let mut outputs2 = vec![SignalBuffer::control(1)];
         let ctx2 = ProcessContext::new(44100.0, 1);
-        clock.process(&[], &mut outputs2, &[120.0, 50.0, 2.0, 1.0], &ctx2);
+        clock.process(&[], &mut outputs2, &[120.0, 50.0, 2.0, 1.0, 0.0, 0.0], &ctx2);
 
         assert_eq!(
             outputs2[0].samples[0], 1.0,
@@ -528,7 +629,7 @@ mod tests {
         assert_eq!(module.info().id, "util.clock");
         assert_eq!(module.info().name, "Clock");
         assert_eq!(module.ports().len(), 2);
-        assert_eq!(module.parameters().len(), 4);
+        assert_eq!(module.parameters().len(), 6);
     }
 
     #[test]
@@ -541,7 +642,7 @@ mod tests {
         let ctx = ProcessContext::new(sample_rate, 44100);
 
         // 300 BPM = 5 beats per second, sixteenth notes = 20 triggers per second
-        clock.process(&[], &mut outputs, &[300.0, 50.0, 4.0, 1.0], &ctx);
+        clock.process(&[], &mut outputs, &[300.0, 50.0, 4.0, 1.0, 0.0, 0.0], &ctx);
 
         // Count rising edges
         let mut rising_edges = 0;
@@ -572,7 +673,7 @@ mod tests {
 
         // 30 BPM = 0.5 beats per second, whole notes = 1 trigger per 8 seconds
         // In 2 seconds, should see only partial first cycle
-        clock.process(&[], &mut outputs, &[30.0, 50.0, 0.0, 1.0], &ctx);
+        clock.process(&[], &mut outputs, &[30.0, 50.0, 0.0, 1.0, 0.0, 0.0], &ctx);
 
         // Count rising edges - should be just 1 (the initial start)
         let mut rising_edges = 0;
@@ -590,4 +691,83 @@ mod tests {
             rising_edges
         );
     }
+
+    #[test]
+    fn test_division_midi_ticks() {
+        assert_eq!(ClockDivision::Whole.midi_ticks(), 96);
+        assert_eq!(ClockDivision::Quarter.midi_ticks(), 24);
+        assert_eq!(ClockDivision::Sixteenth.midi_ticks(), 6);
+    }
+
+    #[test]
+    fn test_midi_clock_output() {
+        let mut clock = Clock::new();
+        let sample_rate = 48000.0;
+        clock.prepare(sample_rate, 48000);
+
+        let mut outputs = vec![SignalBuffer::control(48000)];
+        let ctx = ProcessContext::new(sample_rate, 48000);
+        let mut messages = Vec::new();
+
+        // Off by default: no MIDI at all
+        clock.process(&[], &mut outputs, &[120.0, 50.0, 2.0, 1.0, 0.0, 0.0], &ctx);
+        clock.take_midi_output(&mut messages);
+        assert!(messages.is_empty());
+
+        // 120 BPM for one second: Start, then 2 beats of 24 ticks
+        clock.process(&[], &mut outputs, &[120.0, 50.0, 4.0, 1.0, 1.0, 0.0], &ctx);
+        clock.take_midi_output(&mut messages);
+        assert_eq!(messages[0].message, MidiMessage::Start);
+        let ticks: Vec<u32> = messages[1..]
+            .iter()
+            .map(|event| {
+                assert_eq!(event.message, MidiMessage::Clock);
+                event.sample_offset
+            })
+            .collect();
+        assert_eq!(ticks.len(), 48);
+        // The first tick is on the downbeat, then one every 1000 samples
+        // (give or take the rounding of the phase)
+        assert_eq!(ticks[0], 0);
+        assert!((ticks[24] as i32 - 24000).abs() <= 5, "got {}", ticks[24]);
+
+        // Stopping the clock stops the external gear
+        messages.clear();
+        clock.process(&[], &mut outputs, &[120.0, 50.0, 2.0, 0.0, 1.0, 0.0], &ctx);
+        clock.take_midi_output(&mut messages);
+        assert_eq!(messages, vec![MidiEvent::new(0, 0, MidiMessage::Stop)]);
+    }
+
+    #[test]
+    fn test_midi_source_follows_transport() {
+        use crate::dsp::context::TransportState;
+
+        let mut clock = Clock::new();
+        let sample_rate = 48000.0;
+        clock.prepare(sample_rate, 12000);
+        let mut outputs = vec![SignalBuffer::control(12000)];
+        // Internal tempo 60 BPM, following an external clock at 240 BPM
+        let params = [60.0, 50.0, 2.0, 1.0, 0.0, 1.0];
+
+        // Stopped transport: no gates
+        let stopped = ProcessContext::with_transport(sample_rate, 12000, TransportState::new());
+        clock.process(&[], &mut outputs, &params, &stopped);
+        assert!(outputs[0].samples.iter().all(|&s| s == 0.0));
+
+        // Playing at 240 BPM: one beat every 12000 samples, gate high for the first half
+        let playing = ProcessContext::with_transport(sample_rate, 12000, TransportState::playing_at(240.0));
+        clock.process(&[], &mut outputs, &params, &playing);
+        assert_eq!(outputs[0].samples[0], 1.0);
+        assert_eq!(outputs[0].samples[5000], 1.0);
+        assert_eq!(outputs[0].samples[7000], 0.0);
+
+        // Stop, then continue half a beat into the song: starts with the gate low
+        clock.process(&[], &mut outputs, &params, &stopped);
+        let mut transport = TransportState::playing_at(240.0);
+        transport.sample_position = 6000;
+        let located = ProcessContext::with_transport(sample_rate, 12000, transport);
+        clock.process(&[], &mut outputs, &params, &located);
+        assert_eq!(outputs[0].samples[0], 0.0);
+        assert_eq!(outputs[0].samples[7000], 1.0);
+    }
 }
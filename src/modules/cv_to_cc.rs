@@ -0,0 +1,201 @@
+//! CV to CC module.
+//!
+//! Sends a control signal to external gear as a MIDI Control Change, so an
+//! LFO or envelope can move a knob on a hardware synth.
+
+use crate::dsp::{
+    context::ProcessContext,
+    module_trait::{DspModule, ModuleCategory, ModuleInfo},
+    parameter::ParameterDefinition,
+    port::PortDefinition,
+    signal::{MidiEvent, SignalBuffer},
+    ParameterDisplay, SignalType,
+};
+
+/// A module that sends a control signal as MIDI CC messages.
+///
+/// The input is read once per block and sent when its 7-bit value changes,
+/// which keeps the message rate well within what a MIDI cable can carry.
+///
+/// # Ports
+///
+/// **Inputs:**
+/// - **CV** (Control): Controller value, 0.0-1.0 (clamped).
+///
+/// # Parameters
+///
+/// - **CC** (0-127): Controller number.
+/// - **Channel** (1-16): MIDI channel the messages are sent on.
+pub struct CvToCc {
+    /// Last value sent, with its controller and channel (None = nothing sent yet).
+    last_sent: Option<(u8, u8, u8)>,
+    /// Messages generated by the last block, waiting to be sent.
+    pending: Vec<MidiEvent>,
+    /// Port definitions.
+    ports: Vec<PortDefinition>,
+    /// Parameter definitions.
+    parameters: Vec<ParameterDefinition>,
+}
+
+impl CvToCc {
+    /// Creates a new CV to CC module.
+    pub fn new() -> Self {
+        Self {
+            last_sent: None,
+            pending: Vec::with_capacity(1),
+            ports: vec![PortDefinition::input_with_default("cv", "CV", SignalType::Control, 0.0)],
+            parameters: vec![
+                // CC: controller number (1 = mod wheel)
+                ParameterDefinition::new(
+                    "cc",
+                    "CC",
+                    0.0,
+                    127.0,
+                    1.0,
+                    ParameterDisplay::Linear { unit: "" },
+                ).not_randomized(),
+                // Channel: MIDI channel the messages are sent on
+                ParameterDefinition::choice(
+                    "channel",
+                    "Channel",
+                    &["1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15", "16"],
+                    0,
+                ).not_randomized(),
+            ],
+        }
+    }
+
+    /// Port index constants.
+    const PORT_CV: usize = 0;
+
+    /// Parameter index constants.
+    pub const PARAM_CC: usize = 0;
+    pub const PARAM_CHANNEL: usize = 1;
+
+    /// Convert a 0.0-1.0 control value to a 7-bit CC value.
+    pub fn cv_to_value(cv: f32) -> u8 {
+        (cv.clamp(0.0, 1.0) * 127.0).round() as u8
+    }
+}
+
+impl Default for CvToCc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DspModule for CvToCc {
+    fn info(&self) -> &ModuleInfo {
+        static INFO: ModuleInfo = ModuleInfo {
+            id: "output.cv_to_cc",
+            name: "CV to CC",
+            category: ModuleCategory::Output,
+            description: "Sends a control signal as MIDI CC messages",
+        };
+        &INFO
+    }
+
+    fn ports(&self) -> &[PortDefinition] {
+        &self.ports
+    }
+
+    fn parameters(&self) -> &[ParameterDefinition] {
+        &self.parameters
+    }
+
+    fn prepare(&mut self, _sample_rate: f32, _max_block_size: usize) {}
+
+    fn process(
+        &mut self,
+        inputs: &[&SignalBuffer],
+        _outputs: &mut [SignalBuffer],
+        params: &[f32],
+        context: &ProcessContext,
+    ) {
+        let controller = params[Self::PARAM_CC].round().clamp(0.0, 127.0) as u8;
+        let channel = (params[Self::PARAM_CHANNEL] as u8).min(15);
+
+        let Some(last) = context.block_size.checked_sub(1) else {
+            return;
+        };
+        let cv = inputs
+            .get(Self::PORT_CV)
+            .map(|buf| buf.samples.get(last).copied().unwrap_or(0.0))
+            .unwrap_or(0.0);
+        let value = Self::cv_to_value(cv);
+
+        // Also resend when the controller or channel changes
+        if self.last_sent != Some((controller, channel, value)) {
+            self.pending.clear();
+            self.pending.push(MidiEvent::control_change(last as u32, channel, controller, value));
+            self.last_sent = Some((controller, channel, value));
+        }
+    }
+
+    fn reset(&mut self) {
+        self.last_sent = None;
+        self.pending.clear();
+    }
+
+    fn take_midi_output(&mut self, messages: &mut Vec<MidiEvent>) {
+        messages.append(&mut self.pending);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process_cv(module: &mut CvToCc, cv: f32, params: &[f32]) -> Vec<MidiEvent> {
+        let ctx = ProcessContext::new(44100.0, 64);
+        let mut cv_in = SignalBuffer::control(64);
+        cv_in.fill(cv);
+        module.process(&[&cv_in], &mut [], params, &ctx);
+
+        let mut messages = Vec::new();
+        module.take_midi_output(&mut messages);
+        messages
+    }
+
+    #[test]
+    fn test_cv_to_cc_info() {
+        let module = CvToCc::new();
+        assert_eq!(module.info().id, "output.cv_to_cc");
+        assert_eq!(module.info().category, ModuleCategory::Output);
+        assert_eq!(module.ports().len(), 1);
+        assert_eq!(module.parameters().len(), 2);
+        assert_eq!(module.parameters()[CvToCc::PARAM_CC].default, 1.0);
+    }
+
+    #[test]
+    fn test_cv_to_value() {
+        assert_eq!(CvToCc::cv_to_value(0.0), 0);
+        assert_eq!(CvToCc::cv_to_value(0.5), 64);
+        assert_eq!(CvToCc::cv_to_value(1.0), 127);
+        assert_eq!(CvToCc::cv_to_value(-1.0), 0);
+        assert_eq!(CvToCc::cv_to_value(3.0), 127);
+    }
+
+    #[test]
+    fn test_sends_only_changes() {
+        let mut module = CvToCc::new();
+        let messages = process_cv(&mut module, 0.5, &[74.0, 1.0]);
+        assert_eq!(messages, vec![MidiEvent::control_change(63, 1, 74, 64)]);
+
+        // Same 7-bit value: nothing to send
+        assert!(process_cv(&mut module, 0.501, &[74.0, 1.0]).is_empty());
+
+        assert_eq!(process_cv(&mut module, 1.0, &[74.0, 1.0]), vec![MidiEvent::control_change(63, 1, 74, 127)]);
+
+        // A new controller number gets the current value
+        assert_eq!(process_cv(&mut module, 1.0, &[75.0, 1.0]), vec![MidiEvent::control_change(63, 1, 75, 127)]);
+    }
+
+    #[test]
+    fn test_reset_resends_value() {
+        let mut module = CvToCc::new();
+        process_cv(&mut module, 0.25, &[1.0, 0.0]);
+        module.reset();
+        assert_eq!(process_cv(&mut module, 0.25, &[1.0, 0.0]).len(), 1);
+    }
+}
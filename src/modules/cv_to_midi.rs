@@ -0,0 +1,272 @@
+//! CV/Gate to MIDI module.
+//!
+//! Turns pitch, gate and velocity signals into MIDI notes for external gear,
+//! so a Step Sequencer or Keyboard can play a hardware synth.
+
+use crate::dsp::{
+    context::ProcessContext,
+    module_trait::{DspModule, ModuleCategory, ModuleInfo},
+    parameter::ParameterDefinition,
+    port::PortDefinition,
+    signal::{MidiEvent, SignalBuffer},
+    SignalType,
+};
+
+/// A module that sends a MIDI note for each gate.
+///
+/// A rising gate sends a Note On for the pitch at that moment, and the
+/// falling gate sends the matching Note Off. Changing the pitch while the
+/// gate is high doesn't send a new note.
+///
+/// # Ports
+///
+/// **Inputs:**
+/// - **Pitch** (Control): V/Oct pitch CV. 0.0 = C4 (MIDI 60), +1.0 = C5.
+/// - **Gate** (Gate): Note on while high.
+/// - **Velocity** (Control): Note velocity, 0.0-1.0 (default 0.8).
+///
+/// # Parameters
+///
+/// - **Channel** (1-16): MIDI channel the notes are sent on.
+pub struct CvToMidi {
+    /// Note currently sounding on the external gear, with its channel.
+    held_note: Option<(u8, u8)>,
+    /// Previous gate state for edge detection.
+    prev_gate: bool,
+    /// Messages generated by the last block, waiting to be sent.
+    pending: Vec<MidiEvent>,
+    /// Port definitions.
+    ports: Vec<PortDefinition>,
+    /// Parameter definitions.
+    parameters: Vec<ParameterDefinition>,
+}
+
+impl CvToMidi {
+    /// Creates a new CV/Gate to MIDI module.
+    pub fn new() -> Self {
+        Self {
+            held_note: None,
+            prev_gate: false,
+            pending: Vec::with_capacity(Self::MAX_PENDING),
+            ports: vec![
+                PortDefinition::input_with_default("pitch", "Pitch", SignalType::Control, 0.0),
+                PortDefinition::input_with_default("gate", "Gate", SignalType::Gate, 0.0),
+                PortDefinition::input_with_default("velocity", "Velocity", SignalType::Control, 0.8),
+            ],
+            parameters: vec![
+                // Channel: MIDI channel the notes are sent on
+                ParameterDefinition::choice(
+                    "channel",
+                    "Channel",
+                    &["1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15", "16"],
+                    0,
+                ).not_randomized(),
+            ],
+        }
+    }
+
+    /// Port index constants.
+    const PORT_PITCH: usize = 0;
+    const PORT_GATE: usize = 1;
+    const PORT_VELOCITY: usize = 2;
+
+    /// Parameter index constants.
+    pub const PARAM_CHANNEL: usize = 0;
+
+    /// Gate threshold for detecting high/low states.
+    const GATE_THRESHOLD: f32 = 0.5;
+
+    /// Most messages kept per block; more are dropped rather than allocating
+    /// on the audio thread.
+    const MAX_PENDING: usize = 64;
+
+    /// Convert a V/Oct pitch CV to the nearest MIDI note number.
+    ///
+    /// 0.0 = middle C (MIDI 60). Notes outside 0-127 are clamped.
+    pub fn voct_to_note(pitch: f32) -> u8 {
+        (60.0 + pitch * 12.0).round().clamp(0.0, 127.0) as u8
+    }
+
+    /// Convert a 0.0-1.0 velocity CV to a MIDI velocity of at least 1, since
+    /// velocity 0 would be read as a Note Off.
+    pub fn cv_to_velocity(velocity: f32) -> u8 {
+        (velocity.clamp(0.0, 1.0) * 127.0).round().max(1.0) as u8
+    }
+
+    /// Queue a message, dropping it if the block already has too many.
+    fn send(&mut self, event: MidiEvent) {
+        if self.pending.len() < Self::MAX_PENDING {
+            self.pending.push(event);
+        }
+    }
+
+    /// Queue the Note Off of the sounding note, if any.
+    fn release(&mut self, sample_offset: u32) {
+        if let Some((note, channel)) = self.held_note.take() {
+            self.send(MidiEvent::note_off(sample_offset, channel, note, 0));
+        }
+    }
+}
+
+impl Default for CvToMidi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DspModule for CvToMidi {
+    fn info(&self) -> &ModuleInfo {
+        static INFO: ModuleInfo = ModuleInfo {
+            id: "output.cv_to_midi",
+            name: "CV/Gate to MIDI",
+            category: ModuleCategory::Output,
+            description: "Sends MIDI notes from pitch, gate and velocity signals",
+        };
+        &INFO
+    }
+
+    fn ports(&self) -> &[PortDefinition] {
+        &self.ports
+    }
+
+    fn parameters(&self) -> &[ParameterDefinition] {
+        &self.parameters
+    }
+
+    fn prepare(&mut self, _sample_rate: f32, _max_block_size: usize) {}
+
+    fn process(
+        &mut self,
+        inputs: &[&SignalBuffer],
+        _outputs: &mut [SignalBuffer],
+        params: &[f32],
+        context: &ProcessContext,
+    ) {
+        let channel = (params[Self::PARAM_CHANNEL] as u8).min(15);
+
+        let pitch_in = inputs.get(Self::PORT_PITCH);
+        let gate_in = inputs.get(Self::PORT_GATE);
+        let velocity_in = inputs.get(Self::PORT_VELOCITY);
+
+        for i in 0..context.block_size {
+            let gate_value = gate_in
+                .map(|buf| buf.samples.get(i).copied().unwrap_or(0.0))
+                .unwrap_or(0.0);
+            let gate_high = gate_value >= Self::GATE_THRESHOLD;
+
+            if gate_high && !self.prev_gate {
+                let pitch = pitch_in
+                    .map(|buf| buf.samples.get(i).copied().unwrap_or(0.0))
+                    .unwrap_or(0.0);
+                let velocity = velocity_in
+                    .map(|buf| buf.samples.get(i).copied().unwrap_or(0.8))
+                    .unwrap_or(0.8);
+                let note = Self::voct_to_note(pitch);
+
+                self.release(i as u32);
+                self.send(MidiEvent::note_on(i as u32, channel, note, Self::cv_to_velocity(velocity)));
+                self.held_note = Some((note, channel));
+            } else if !gate_high && self.prev_gate {
+                self.release(i as u32);
+            }
+            self.prev_gate = gate_high;
+        }
+    }
+
+    fn reset(&mut self) {
+        // Don't leave a note hanging on the external gear
+        self.release(0);
+        self.prev_gate = false;
+    }
+
+    fn take_midi_output(&mut self, messages: &mut Vec<MidiEvent>) {
+        messages.append(&mut self.pending);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::MidiMessage;
+
+    fn process_gate(module: &mut CvToMidi, pitch: f32, gate: &[f32], channel: f32) -> Vec<MidiEvent> {
+        let ctx = ProcessContext::new(44100.0, gate.len());
+        let mut pitch_in = SignalBuffer::control(gate.len());
+        pitch_in.fill(pitch);
+        let mut gate_in = SignalBuffer::gate(gate.len());
+        gate_in.samples.copy_from_slice(gate);
+        let mut velocity_in = SignalBuffer::control(gate.len());
+        velocity_in.fill(1.0);
+        module.process(&[&pitch_in, &gate_in, &velocity_in], &mut [], &[channel], &ctx);
+
+        let mut messages = Vec::new();
+        module.take_midi_output(&mut messages);
+        messages
+    }
+
+    #[test]
+    fn test_cv_to_midi_info() {
+        let module = CvToMidi::new();
+        assert_eq!(module.info().id, "output.cv_to_midi");
+        assert_eq!(module.info().category, ModuleCategory::Output);
+        assert_eq!(module.ports().len(), 3);
+        assert!(module.ports().iter().all(|port| port.is_input()));
+        assert_eq!(module.parameters().len(), 1);
+    }
+
+    #[test]
+    fn test_voct_to_note() {
+        assert_eq!(CvToMidi::voct_to_note(0.0), 60);
+        assert_eq!(CvToMidi::voct_to_note(1.0), 72);
+        assert_eq!(CvToMidi::voct_to_note(-1.0), 48);
+        assert_eq!(CvToMidi::voct_to_note(0.75), 69);
+        // Slightly off pitches round to the nearest note
+        assert_eq!(CvToMidi::voct_to_note(1.0 / 12.0 + 0.01), 61);
+        assert_eq!(CvToMidi::voct_to_note(20.0), 127);
+        assert_eq!(CvToMidi::voct_to_note(-20.0), 0);
+    }
+
+    #[test]
+    fn test_cv_to_velocity() {
+        assert_eq!(CvToMidi::cv_to_velocity(1.0), 127);
+        assert_eq!(CvToMidi::cv_to_velocity(0.0), 1);
+        assert_eq!(CvToMidi::cv_to_velocity(2.0), 127);
+    }
+
+    #[test]
+    fn test_gate_sends_note_on_and_off() {
+        let mut module = CvToMidi::new();
+        let messages = process_gate(&mut module, 1.0, &[0.0, 1.0, 1.0, 0.0], 2.0);
+        assert_eq!(
+            messages,
+            vec![MidiEvent::note_on(1, 2, 72, 127), MidiEvent::note_off(3, 2, 72, 0)]
+        );
+
+        // Nothing more while the gate stays low
+        assert!(process_gate(&mut module, 1.0, &[0.0; 4], 2.0).is_empty());
+    }
+
+    #[test]
+    fn test_note_off_follows_held_note() {
+        let mut module = CvToMidi::new();
+        let messages = process_gate(&mut module, 0.0, &[1.0, 1.0], 0.0);
+        assert_eq!(messages, vec![MidiEvent::note_on(0, 0, 60, 127)]);
+
+        // The pitch and channel changed while held; the Note Off matches the Note On
+        let messages = process_gate(&mut module, 1.0, &[1.0, 0.0], 5.0);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].channel, 0);
+        assert_eq!(messages[0].message, MidiMessage::NoteOff { note: 60, velocity: 0 });
+    }
+
+    #[test]
+    fn test_reset_releases_held_note() {
+        let mut module = CvToMidi::new();
+        process_gate(&mut module, 0.0, &[1.0], 0.0);
+        module.reset();
+
+        let mut messages = Vec::new();
+        module.take_midi_output(&mut messages);
+        assert_eq!(messages, vec![MidiEvent::note_off(0, 0, 60, 0)]);
+    }
+}
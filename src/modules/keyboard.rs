@@ -3,13 +3,15 @@
 //! A virtual keyboard that converts computer keyboard input into gate, pitch CV,
 //! and velocity signals for playing the synthesizer.
 
+use std::sync::Arc;
+
 use crate::dsp::{
     context::ProcessContext,
     module_trait::{DspModule, ModuleCategory, ModuleInfo},
     parameter::ParameterDefinition,
     port::PortDefinition,
     signal::SignalBuffer,
-    ParameterDisplay, SignalType,
+    ParameterDisplay, SignalType, Tuning,
 };
 
 /// Key priority modes for handling multiple simultaneous keys.
@@ -44,7 +46,8 @@ impl KeyPriority {
 ///
 /// **Outputs:**
 /// - **Gate** (Gate): High (1.0) when a key is pressed, low (0.0) when released.
-/// - **Pitch** (Control): V/Oct pitch CV. 0.0 = C4 (middle C), +1.0 = C5, -1.0 = C3.
+/// - **Pitch** (Control): V/Oct pitch CV. 0.0 = C4 (middle C), +1.0 = C5, -1.0 = C3,
+///   in 12-TET; other tunings set the pitch of each note.
 /// - **Velocity** (Control): Note velocity (0.0-1.0).
 ///
 /// # Parameters
@@ -65,6 +68,8 @@ pub struct KeyboardInput {
     current_pitch: f32,
     /// Current output gate state.
     current_gate: f32,
+    /// Tuning to convert notes with.
+    tuning: Arc<Tuning>,
 }
 
 impl KeyboardInput {
@@ -88,9 +93,9 @@ impl KeyboardInput {
                     127.0,
                     60.0, // Default to middle C
                     ParameterDisplay::Linear { unit: "" },
-                ),
+                ).not_randomized(),
                 // Gate: 0 or 1, set by UI when keys pressed/released
-                ParameterDefinition::toggle("gate", "Gate", false),
+                ParameterDefinition::toggle("gate", "Gate", false).not_randomized(),
                 // Octave: shift the keyboard up/down by octaves
                 ParameterDefinition::new(
                     "octave",
@@ -99,19 +104,20 @@ impl KeyboardInput {
                     2.0,
                     0.0,
                     ParameterDisplay::Linear { unit: "" },
-                ),
+                ).not_randomized(),
                 // Velocity: fixed velocity for all notes
-                ParameterDefinition::normalized("velocity", "Velocity", 1.0),
+                ParameterDefinition::normalized("velocity", "Velocity", 1.0).not_randomized(),
                 // Priority: key priority mode
                 ParameterDefinition::choice(
                     "priority",
                     "Priority",
                     &["Last", "Lowest", "Highest"],
                     0,
-                ),
+                ).not_randomized(),
             ],
             current_pitch: 0.0,
             current_gate: 0.0,
+            tuning: Arc::new(Tuning::equal()),
         }
     }
 
@@ -128,14 +134,15 @@ impl KeyboardInput {
     #[allow(dead_code)]
     const PARAM_PRIORITY: usize = 4;
 
-    /// Convert MIDI note number to V/Oct pitch CV.
+    /// Convert MIDI note number to V/Oct pitch CV through the tuning.
     ///
+    /// In 12-TET:
     /// Middle C (MIDI 60) = 0.0
     /// C5 (MIDI 72) = +1.0
     /// C3 (MIDI 48) = -1.0
     #[inline]
-    fn midi_to_voct(midi_note: f32) -> f32 {
-        (midi_note - 60.0) / 12.0
+    fn midi_to_voct(&self, midi_note: f32) -> f32 {
+        self.tuning.voct(midi_note)
     }
 }
 
@@ -182,8 +189,7 @@ impl DspModule for KeyboardInput {
         let velocity = params[Self::PARAM_VELOCITY];
 
         // Calculate pitch with octave shift
-        let shifted_note = note + (octave * 12.0);
-        let target_pitch = Self::midi_to_voct(shifted_note);
+        let target_pitch = self.midi_to_voct(note) + octave;
 
         // Fill output buffers
         for i in 0..context.block_size {
@@ -208,6 +214,10 @@ impl DspModule for KeyboardInput {
         self.current_pitch = 0.0;
         self.current_gate = 0.0;
     }
+
+    fn set_tuning(&mut self, tuning: Arc<Tuning>) {
+        self.tuning = tuning;
+    }
 }
 
 /// Maps a computer keyboard key to a MIDI note number relative to C4.
@@ -321,17 +331,19 @@ mod tests {
 
     #[test]
     fn test_midi_to_voct() {
+        let module = KeyboardInput::new();
+
         // Middle C (60) = 0.0
-        assert!((KeyboardInput::midi_to_voct(60.0) - 0.0).abs() < f32::EPSILON);
+        assert!((module.midi_to_voct(60.0) - 0.0).abs() < f32::EPSILON);
 
         // C5 (72) = +1.0
-        assert!((KeyboardInput::midi_to_voct(72.0) - 1.0).abs() < f32::EPSILON);
+        assert!((module.midi_to_voct(72.0) - 1.0).abs() < f32::EPSILON);
 
         // C3 (48) = -1.0
-        assert!((KeyboardInput::midi_to_voct(48.0) - (-1.0)).abs() < f32::EPSILON);
+        assert!((module.midi_to_voct(48.0) - (-1.0)).abs() < f32::EPSILON);
 
         // A4 (69) = 0.75 (9 semitones above C4)
-        assert!((KeyboardInput::midi_to_voct(69.0) - 0.75).abs() < 0.001);
+        assert!((module.midi_to_voct(69.0) - 0.75).abs() < 0.001);
     }
 
     #[test]
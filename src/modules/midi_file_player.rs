@@ -0,0 +1,489 @@
+//! MIDI File Player module.
+//!
+//! Plays the notes of a Standard MIDI File as pitch, gate and velocity,
+//! either on its own or following the engine transport.
+
+use std::sync::Arc;
+
+use crate::dsp::{
+    context::ProcessContext,
+    module_trait::{DspModule, ModuleCategory, ModuleInfo},
+    parameter::ParameterDefinition,
+    port::PortDefinition,
+    signal::SignalBuffer,
+    NoteSequence, SequenceNote, SignalType, Tuning,
+};
+
+/// A monophonic player for the notes of a MIDI file.
+///
+/// The file is loaded from the node and handed to the module as a
+/// `NoteSequence`. The notes of the selected track and channel are played
+/// with last-note priority; a note that starts while another is sounding
+/// drops the gate for one sample so envelopes retrigger.
+///
+/// # Ports
+///
+/// **Inputs:**
+/// - **Reset** (Gate): Restarts the file on a rising edge (Free sync only).
+///
+/// **Outputs:**
+/// - **Pitch** (Control): V/Oct pitch of the current note through the tuning.
+///   0.0 = C4 (MIDI 60) in 12-TET.
+/// - **Gate** (Gate): High while a note is held.
+/// - **Velocity** (Control): Velocity of the current note (0.0-1.0).
+///
+/// # Parameters
+///
+/// - **Track** (All/1-16): Which track of the file to play.
+/// - **Channel** (Omni/1-16): Which MIDI channel to play.
+/// - **Sync** (Free/Transport): Free plays at the file's own tempo from the
+///   moment it is loaded; Transport plays while the engine transport is
+///   running, at its position and tempo.
+/// - **Loop** (On/Off): Start over at the end of the file.
+pub struct MidiFilePlayer {
+    /// Notes of the loaded file (None = no file).
+    sequence: Option<Arc<NoteSequence>>,
+    /// Index of the next note to play.
+    cursor: usize,
+    /// Play position within the file: seconds with Free sync, beats with
+    /// Transport sync.
+    position: f64,
+    /// Sync mode of the last block.
+    transport_sync: bool,
+    /// Transport sample position expected at the next block, to notice
+    /// jumps (None = the transport wasn't playing).
+    expected_sample: Option<u64>,
+    /// Track and channel filter of the last block.
+    filter: (usize, usize),
+    /// Held notes of the selected track and channel, most recent last.
+    held: Vec<u8>,
+    /// Pitch output (V/Oct).
+    pitch: f32,
+    /// Velocity output.
+    velocity: f32,
+    /// Gate of the last sample.
+    gate: bool,
+    /// Whether to hold the gate low for the current sample.
+    retrigger: bool,
+    /// Previous Reset input state for edge detection.
+    prev_reset: bool,
+    /// Port definitions.
+    ports: Vec<PortDefinition>,
+    /// Parameter definitions.
+    parameters: Vec<ParameterDefinition>,
+    /// Tuning to convert notes with.
+    tuning: Arc<Tuning>,
+}
+
+impl MidiFilePlayer {
+    /// Creates a new MIDI File Player module.
+    pub fn new() -> Self {
+        Self {
+            sequence: None,
+            cursor: 0,
+            position: 0.0,
+            transport_sync: false,
+            expected_sample: None,
+            filter: (0, 0),
+            held: Vec::with_capacity(Self::MAX_HELD_NOTES),
+            pitch: 0.0,
+            velocity: 0.0,
+            gate: false,
+            retrigger: false,
+            prev_reset: false,
+            ports: vec![
+                PortDefinition::input_with_default("reset", "Reset", SignalType::Gate, 0.0),
+                PortDefinition::output("pitch", "Pitch", SignalType::Control),
+                PortDefinition::output("gate", "Gate", SignalType::Gate),
+                PortDefinition::output("velocity", "Velocity", SignalType::Control),
+            ],
+            parameters: vec![
+                // Track: which track of the file to play (0=All)
+                ParameterDefinition::choice(
+                    "track",
+                    "Track",
+                    &["All", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15", "16"],
+                    0,
+                ).not_randomized(),
+                // Channel: which MIDI channel to play (0=Omni)
+                ParameterDefinition::choice(
+                    "channel",
+                    "Channel",
+                    &["Omni", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15", "16"],
+                    0,
+                ).not_randomized(),
+                // Sync: the file's own tempo, or the engine transport
+                ParameterDefinition::choice("sync", "Sync", &["Free", "Transport"], 0).not_randomized(),
+                ParameterDefinition::toggle("loop", "Loop", true).not_randomized(),
+            ],
+            tuning: Arc::new(Tuning::equal()),
+        }
+    }
+
+    /// Port index constants.
+    const PORT_RESET: usize = 0;
+    const PORT_PITCH: usize = 0;
+    const PORT_GATE: usize = 1;
+    const PORT_VELOCITY: usize = 2;
+
+    /// Parameter index constants.
+    pub const PARAM_TRACK: usize = 0;
+    pub const PARAM_CHANNEL: usize = 1;
+    pub const PARAM_SYNC: usize = 2;
+    pub const PARAM_LOOP: usize = 3;
+
+    /// Most notes held at once (every MIDI note).
+    const MAX_HELD_NOTES: usize = 128;
+
+    /// Tempo used with Transport sync while the transport has none.
+    const DEFAULT_TEMPO_BPM: f64 = 120.0;
+
+    /// Move the play position to `position`, releasing all notes.
+    fn seek(&mut self, sequence: &NoteSequence, position: f64, transport_sync: bool, looping: bool) {
+        let length = Self::length(sequence, transport_sync);
+        self.position = if looping && length > 0.0 { position.rem_euclid(length) } else { position.max(0.0) };
+        self.cursor = sequence
+            .notes
+            .partition_point(|note| Self::time(note, transport_sync) < self.position);
+        self.release_all();
+    }
+
+    /// Release all held notes.
+    fn release_all(&mut self) {
+        self.held.clear();
+        self.retrigger = self.gate;
+    }
+
+    /// Play a Note On or Note Off.
+    fn play(&mut self, note: &SequenceNote) {
+        if note.is_note_on() {
+            // Let envelopes hear the new note, even straight after a Note Off
+            if self.gate {
+                self.retrigger = true;
+            }
+            self.held.retain(|&held| held != note.note);
+            if self.held.len() < Self::MAX_HELD_NOTES {
+                self.held.push(note.note);
+            }
+            self.pitch = self.tuning.voct(note.note as f32);
+            self.velocity = note.velocity as f32 / 127.0;
+        } else {
+            let was_last = self.held.last() == Some(&note.note);
+            self.held.retain(|&held| held != note.note);
+            if was_last {
+                if let Some(&last) = self.held.last() {
+                    self.pitch = self.tuning.voct(last as f32);
+                }
+            }
+        }
+    }
+
+    /// Time of a note in the units of the sync mode.
+    fn time(note: &SequenceNote, transport_sync: bool) -> f64 {
+        if transport_sync {
+            note.beats
+        } else {
+            note.seconds
+        }
+    }
+
+    /// Length of the file in the units of the sync mode.
+    fn length(sequence: &NoteSequence, transport_sync: bool) -> f64 {
+        if transport_sync {
+            sequence.length_beats
+        } else {
+            sequence.length_seconds
+        }
+    }
+}
+
+impl Default for MidiFilePlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DspModule for MidiFilePlayer {
+    fn info(&self) -> &ModuleInfo {
+        static INFO: ModuleInfo = ModuleInfo {
+            id: "input.midi_file",
+            name: "MIDI File Player",
+            category: ModuleCategory::Source,
+            description: "Plays a MIDI file as pitch, gate and velocity",
+        };
+        &INFO
+    }
+
+    fn ports(&self) -> &[PortDefinition] {
+        &self.ports
+    }
+
+    fn parameters(&self) -> &[ParameterDefinition] {
+        &self.parameters
+    }
+
+    fn prepare(&mut self, _sample_rate: f32, _max_block_size: usize) {}
+
+    fn process(
+        &mut self,
+        inputs: &[&SignalBuffer],
+        outputs: &mut [SignalBuffer],
+        params: &[f32],
+        context: &ProcessContext,
+    ) {
+        let filter = (
+            params[Self::PARAM_TRACK].round() as usize,
+            params[Self::PARAM_CHANNEL].round() as usize,
+        );
+        let transport_sync = params[Self::PARAM_SYNC] > 0.5;
+        let looping = params[Self::PARAM_LOOP] > 0.5;
+
+        // Notes of another track or channel would never be released
+        if filter != self.filter {
+            self.filter = filter;
+            self.release_all();
+        }
+
+        let Some(sequence) = self.sequence.clone() else {
+            for i in 0..context.block_size {
+                outputs[Self::PORT_PITCH].samples[i] = self.pitch;
+                outputs[Self::PORT_GATE].samples[i] = 0.0;
+                outputs[Self::PORT_VELOCITY].samples[i] = self.velocity;
+            }
+            self.gate = false;
+            return;
+        };
+
+        let transport = &context.transport;
+        let running = !transport_sync || transport.playing;
+        let step = if transport_sync {
+            transport.tempo_bpm.map_or(Self::DEFAULT_TEMPO_BPM, |tempo| tempo as f64) / 60.0
+                / context.sample_rate as f64
+        } else {
+            1.0 / context.sample_rate as f64
+        };
+
+        if transport_sync != self.transport_sync {
+            self.transport_sync = transport_sync;
+            self.expected_sample = None;
+            if !transport_sync {
+                self.seek(&sequence, 0.0, false, looping);
+            }
+        }
+
+        if transport_sync {
+            if transport.playing {
+                // Pick up the song position when the transport starts or jumps
+                if self.expected_sample != Some(transport.sample_position) {
+                    let beats = transport.sample_position as f64 * step;
+                    self.seek(&sequence, beats, true, looping);
+                }
+                self.expected_sample = Some(transport.sample_position + context.block_size as u64);
+            } else if self.expected_sample.take().is_some() {
+                self.release_all();
+            }
+        }
+
+        let length = Self::length(&sequence, transport_sync);
+        let reset_in = inputs.get(Self::PORT_RESET);
+
+        for i in 0..context.block_size {
+            let reset = reset_in.and_then(|buf| buf.samples.get(i)).is_some_and(|&v| v > 0.5);
+            if reset && !self.prev_reset && !transport_sync {
+                self.seek(&sequence, 0.0, false, looping);
+            }
+            self.prev_reset = reset;
+
+            if running {
+                if looping && length > 0.0 && self.position >= length {
+                    self.position -= length;
+                    self.cursor = 0;
+                    self.release_all();
+                }
+
+                while let Some(note) = sequence.notes.get(self.cursor) {
+                    if Self::time(note, transport_sync) > self.position {
+                        break;
+                    }
+                    self.cursor += 1;
+                    let track_matches = filter.0 == 0 || note.track as usize == filter.0 - 1;
+                    let channel_matches = filter.1 == 0 || note.channel as usize == filter.1 - 1;
+                    if track_matches && channel_matches {
+                        self.play(note);
+                    }
+                }
+                self.position += step;
+            }
+
+            self.gate = !self.held.is_empty() && !self.retrigger;
+            self.retrigger = false;
+            outputs[Self::PORT_PITCH].samples[i] = self.pitch;
+            outputs[Self::PORT_GATE].samples[i] = if self.gate { 1.0 } else { 0.0 };
+            outputs[Self::PORT_VELOCITY].samples[i] = self.velocity;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.cursor = 0;
+        self.position = 0.0;
+        self.expected_sample = None;
+        self.held.clear();
+        self.gate = false;
+        self.retrigger = false;
+        self.prev_reset = false;
+    }
+
+    fn set_note_sequence(&mut self, sequence: Option<Arc<NoteSequence>>) {
+        self.sequence = sequence;
+        self.reset();
+    }
+
+    fn set_tuning(&mut self, tuning: Arc<Tuning>) {
+        self.tuning = tuning;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::TransportState;
+
+    const SAMPLE_RATE: f32 = 1000.0;
+
+    fn note(beats: f64, track: u16, note: u8, velocity: u8) -> SequenceNote {
+        // At 120 BPM
+        SequenceNote { seconds: beats / 2.0, beats, track, channel: track as u8, note, velocity }
+    }
+
+    /// One bar of 120 BPM: a C4 on track 0 and an overlapping E4 and G4 on track 1.
+    fn sequence() -> Arc<NoteSequence> {
+        Arc::new(NoteSequence {
+            notes: vec![
+                note(0.0, 0, 60, 127),
+                note(1.0, 1, 64, 100),
+                note(2.0, 0, 60, 0),
+                note(2.0, 1, 67, 50),
+                note(3.0, 1, 64, 0),
+                note(4.0, 1, 67, 0),
+            ],
+            length_seconds: 2.0,
+            length_beats: 4.0,
+            tracks: 2,
+        })
+    }
+
+    fn params(track: f32, sync: f32, looping: f32) -> [f32; 4] {
+        [track, 0.0, sync, looping]
+    }
+
+    fn run(player: &mut MidiFilePlayer, params: &[f32], context: &ProcessContext, reset: &[f32]) -> Vec<SignalBuffer> {
+        let reset = SignalBuffer { samples: reset.to_vec(), ..SignalBuffer::control(reset.len()) };
+        let mut outputs: Vec<SignalBuffer> =
+            (0..3).map(|_| SignalBuffer::control(context.block_size)).collect();
+        player.process(&[&reset], &mut outputs, params, context);
+        outputs
+    }
+
+    #[test]
+    fn test_midi_file_player_info() {
+        let player = MidiFilePlayer::new();
+        assert_eq!(player.info().id, "input.midi_file");
+        assert_eq!(player.ports().len(), 4);
+        assert_eq!(player.parameters().len(), 4);
+        assert_eq!(player.parameters()[MidiFilePlayer::PARAM_LOOP].default, 1.0);
+    }
+
+    #[test]
+    fn test_silent_without_file() {
+        let mut player = MidiFilePlayer::new();
+        let context = ProcessContext::new(SAMPLE_RATE, 100);
+        let outputs = run(&mut player, &params(0.0, 0.0, 1.0), &context, &[0.0; 100]);
+        assert!(outputs[1].samples.iter().all(|&gate| gate == 0.0));
+    }
+
+    #[test]
+    fn test_plays_all_tracks_with_last_note_priority() {
+        let mut player = MidiFilePlayer::new();
+        player.set_note_sequence(Some(sequence()));
+        // Four seconds in one block
+        let context = ProcessContext::new(SAMPLE_RATE, 4000);
+        let outputs = run(&mut player, &params(0.0, 0.0, 0.0), &context, &[0.0; 4000]);
+        let (pitch, gate, velocity) = (&outputs[0].samples, &outputs[1].samples, &outputs[2].samples);
+
+        assert_eq!((pitch[0], gate[0], velocity[0]), (0.0, 1.0, 1.0));
+        // E4 starts while C4 is held: the gate drops for one sample
+        assert_eq!(gate[495..505].iter().filter(|&&g| g == 0.0).count(), 1);
+        assert!((pitch[505] - 4.0 / 12.0).abs() < 1e-6);
+        assert!((velocity[505] - 100.0 / 127.0).abs() < 1e-6);
+        // G4 at 1 s, then E4 is released while G4 is the latest note
+        assert!((pitch[1005] - 7.0 / 12.0).abs() < 1e-6);
+        assert!((pitch[1600] - 7.0 / 12.0).abs() < 1e-6);
+        assert_eq!(gate[1600], 1.0);
+        // Everything released at 2 s, and no loop
+        assert_eq!(gate[1995], 1.0);
+        assert_eq!(gate[2005], 0.0);
+        assert_eq!(gate[3999], 0.0);
+    }
+
+    #[test]
+    fn test_track_filter_and_loop() {
+        let mut player = MidiFilePlayer::new();
+        player.set_note_sequence(Some(sequence()));
+        let context = ProcessContext::new(SAMPLE_RATE, 4000);
+
+        // Track 1 only: C4 from 0 to 1 s
+        let outputs = run(&mut player, &params(1.0, 0.0, 1.0), &context, &[0.0; 4000]);
+        let gate = &outputs[1].samples;
+        assert_eq!(gate[995], 1.0);
+        assert_eq!(gate[1005], 0.0);
+        assert_eq!(gate[1995], 0.0);
+        // The file is 2 s long and loops
+        assert_eq!(gate[2005], 1.0);
+    }
+
+    #[test]
+    fn test_reset_restarts_file() {
+        let mut player = MidiFilePlayer::new();
+        player.set_note_sequence(Some(sequence()));
+        let context = ProcessContext::new(SAMPLE_RATE, 1500);
+        run(&mut player, &params(1.0, 0.0, 0.0), &context, &[0.0; 1500]);
+
+        let mut reset = [0.0; 1500];
+        reset[10] = 1.0;
+        let outputs = run(&mut player, &params(1.0, 0.0, 0.0), &context, &reset);
+        assert_eq!(outputs[1].samples[9], 0.0);
+        assert_eq!(outputs[1].samples[10], 1.0);
+        assert_eq!(outputs[1].samples[1005], 1.0);
+        assert_eq!(outputs[1].samples[1015], 0.0);
+    }
+
+    #[test]
+    fn test_transport_sync() {
+        let mut player = MidiFilePlayer::new();
+        player.set_note_sequence(Some(sequence()));
+        let params = params(2.0, 1.0, 1.0);
+
+        // Stopped transport: silence
+        let stopped = ProcessContext::with_transport(SAMPLE_RATE, 1000, TransportState::new());
+        let outputs = run(&mut player, &params, &stopped, &[0.0; 1000]);
+        assert!(outputs[1].samples.iter().all(|&gate| gate == 0.0));
+
+        // Playing at 240 BPM from beat 1.5: E4 started before, G4 starts at beat 2
+        let mut transport = TransportState::playing_at(240.0);
+        transport.sample_position = 375;
+        let playing = ProcessContext::with_transport(SAMPLE_RATE, 500, transport);
+        let outputs = run(&mut player, &params, &playing, &[0.0; 500]);
+        assert_eq!(outputs[1].samples[0], 0.0);
+        assert_eq!(outputs[1].samples[120], 0.0);
+        assert_eq!(outputs[1].samples[130], 1.0);
+        assert!((outputs[0].samples[130] - 7.0 / 12.0).abs() < 1e-6);
+
+        // The transport jumps back to the start: track 2 is silent until beat 1
+        transport.sample_position = 0;
+        let located = ProcessContext::with_transport(SAMPLE_RATE, 500, transport);
+        let outputs = run(&mut player, &params, &located, &[0.0; 500]);
+        assert_eq!(outputs[1].samples[245], 0.0);
+        assert_eq!(outputs[1].samples[255], 1.0);
+        assert!((outputs[0].samples[255] - 4.0 / 12.0).abs() < 1e-6);
+    }
+}
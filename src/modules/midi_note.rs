@@ -3,13 +3,15 @@
 //! Converts MIDI note events into CV signals (V/Oct pitch, gate, velocity, aftertouch).
 //! This provides hardware MIDI input as an alternative to the Keyboard module.
 
+use std::sync::Arc;
+
 use crate::dsp::{
     context::ProcessContext,
     module_trait::{DspModule, ModuleCategory, ModuleInfo},
     parameter::ParameterDefinition,
     port::PortDefinition,
     signal::SignalBuffer,
-    ParameterDisplay, SignalType,
+    ParameterDisplay, SignalType, Tuning,
 };
 
 /// Voice priority modes for handling polyphonic input.
@@ -37,13 +39,14 @@ impl VoicePriority {
 
 /// A MIDI Note module that converts MIDI input to CV signals.
 ///
-/// Reads from the global MIDI input and outputs pitch CV, gate, velocity,
+/// Reads from the MIDI inputs and outputs pitch CV, gate, velocity,
 /// and aftertouch signals for driving oscillators and envelopes.
 ///
 /// # Ports
 ///
 /// **Outputs:**
-/// - **Pitch** (Control): V/Oct pitch CV. 0.0 = C4 (MIDI 60), +1.0 = C5, -1.0 = C3.
+/// - **Pitch** (Control): V/Oct pitch CV. 0.0 = C4 (MIDI 60), +1.0 = C5, -1.0 = C3,
+///   in 12-TET; other tunings set the pitch of each note.
 /// - **Gate** (Gate): High (1.0) when a note is held, low (0.0) when released.
 /// - **Velocity** (Control): Note velocity (0.0-1.0).
 /// - **Aftertouch** (Control): Channel pressure (0.0-1.0).
@@ -58,6 +61,9 @@ impl VoicePriority {
 /// - **Octave** (-4 to +4): Octave shift applied to MIDI input.
 /// - **Priority** (0-2): Voice priority mode (Last, Low, High).
 /// - **Retrigger** (0/1): Retrigger gate on legato notes.
+///
+/// The MIDI input device it listens to is chosen by name in the editor, not
+/// as a parameter, since input port numbers change as devices come and go.
 pub struct MidiNote {
     /// Sample rate from last prepare() call.
     sample_rate: f32,
@@ -73,6 +79,8 @@ pub struct MidiNote {
     current_velocity: f32,
     /// Current aftertouch value.
     current_aftertouch: f32,
+    /// Tuning to convert notes with.
+    tuning: Arc<Tuning>,
 }
 
 impl MidiNote {
@@ -97,9 +105,9 @@ impl MidiNote {
                     127.0,
                     60.0, // Default to middle C
                     ParameterDisplay::Linear { unit: "" },
-                ),
+                ).not_randomized(),
                 // Gate: 0 or 1, set by MIDI events
-                ParameterDefinition::toggle("gate", "Gate", false),
+                ParameterDefinition::toggle("gate", "Gate", false).not_randomized(),
                 // Velocity: 0-127, set by MIDI events
                 ParameterDefinition::new(
                     "velocity",
@@ -108,7 +116,7 @@ impl MidiNote {
                     127.0,
                     100.0,
                     ParameterDisplay::Linear { unit: "" },
-                ),
+                ).not_randomized(),
                 // Aftertouch: 0-127, set by MIDI events
                 ParameterDefinition::new(
                     "aftertouch",
@@ -117,14 +125,14 @@ impl MidiNote {
                     127.0,
                     0.0,
                     ParameterDisplay::Linear { unit: "" },
-                ),
+                ).not_randomized(),
                 // Channel: MIDI channel filter (0=Omni, 1-16=specific)
                 ParameterDefinition::choice(
                     "channel",
                     "Channel",
                     &["Omni", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15", "16"],
                     0,
-                ),
+                ).not_randomized(),
                 // Octave: shift the notes up/down by octaves
                 ParameterDefinition::new(
                     "octave",
@@ -133,21 +141,22 @@ impl MidiNote {
                     4.0,
                     0.0,
                     ParameterDisplay::Linear { unit: "" },
-                ),
+                ).not_randomized(),
                 // Priority: voice priority mode
                 ParameterDefinition::choice(
                     "priority",
                     "Priority",
                     &["Last", "Low", "High"],
                     0,
-                ),
+                ).not_randomized(),
                 // Retrigger: retrigger gate on legato notes
-                ParameterDefinition::toggle("retrigger", "Retrigger", false),
+                ParameterDefinition::toggle("retrigger", "Retrigger", false).not_randomized(),
             ],
             current_pitch: 0.0,
             current_gate: 0.0,
             current_velocity: 0.0,
             current_aftertouch: 0.0,
+            tuning: Arc::new(Tuning::equal()),
         }
     }
 
@@ -169,14 +178,15 @@ impl MidiNote {
     #[allow(dead_code)]
     pub const PARAM_RETRIGGER: usize = 7;
 
-    /// Convert MIDI note number to V/Oct pitch CV.
+    /// Convert MIDI note number to V/Oct pitch CV through the tuning.
     ///
+    /// In 12-TET:
     /// Middle C (MIDI 60) = 0.0
     /// C5 (MIDI 72) = +1.0
     /// C3 (MIDI 48) = -1.0
     #[inline]
-    pub fn midi_to_voct(midi_note: f32) -> f32 {
-        (midi_note - 60.0) / 12.0
+    pub fn midi_to_voct(&self, midi_note: f32) -> f32 {
+        self.tuning.voct(midi_note)
     }
 }
 
@@ -228,8 +238,7 @@ impl DspModule for MidiNote {
         let aftertouch = aftertouch_raw / 127.0;
 
         // Calculate pitch with octave shift
-        let shifted_note = note + (octave * 12.0);
-        let target_pitch = Self::midi_to_voct(shifted_note);
+        let target_pitch = self.midi_to_voct(note) + octave;
 
         // Fill output buffers
         for i in 0..context.block_size {
@@ -260,6 +269,10 @@ impl DspModule for MidiNote {
         self.current_velocity = 0.0;
         self.current_aftertouch = 0.0;
     }
+
+    fn set_tuning(&mut self, tuning: Arc<Tuning>) {
+        self.tuning = tuning;
+    }
 }
 
 #[cfg(test)]
@@ -318,17 +331,19 @@ mod tests {
 
     #[test]
     fn test_midi_to_voct() {
+        let module = MidiNote::new();
+
         // Middle C (60) = 0.0
-        assert!((MidiNote::midi_to_voct(60.0) - 0.0).abs() < f32::EPSILON);
+        assert!((module.midi_to_voct(60.0) - 0.0).abs() < f32::EPSILON);
 
         // C5 (72) = +1.0
-        assert!((MidiNote::midi_to_voct(72.0) - 1.0).abs() < f32::EPSILON);
+        assert!((module.midi_to_voct(72.0) - 1.0).abs() < f32::EPSILON);
 
         // C3 (48) = -1.0
-        assert!((MidiNote::midi_to_voct(48.0) - (-1.0)).abs() < f32::EPSILON);
+        assert!((module.midi_to_voct(48.0) - (-1.0)).abs() < f32::EPSILON);
 
         // A4 (69) = 0.75 (9 semitones above C4)
-        assert!((MidiNote::midi_to_voct(69.0) - 0.75).abs() < 0.001);
+        assert!((module.midi_to_voct(69.0) - 0.75).abs() < 0.001);
     }
 
     #[test]
@@ -392,6 +407,33 @@ mod tests {
         assert!((outputs[0].samples[0] - (-1.0)).abs() < f32::EPSILON);
     }
 
+    #[test]
+    fn test_midi_note_tuning() {
+        let mut module = MidiNote::new();
+        module.prepare(44100.0, 256);
+
+        // A quarter-tone tuning: every note is half a semitone
+        let mut pitches = [None; 128];
+        for (note, pitch) in pitches.iter_mut().enumerate() {
+            *pitch = Some((note as f32 - 60.0) / 24.0);
+        }
+        module.set_tuning(Arc::new(Tuning::from_pitches(&pitches)));
+
+        let mut outputs = vec![
+            SignalBuffer::control(256),
+            SignalBuffer::gate(256),
+            SignalBuffer::control(256),
+            SignalBuffer::control(256),
+        ];
+        let ctx = ProcessContext::new(44100.0, 256);
+
+        // Note 84 is an octave above middle C, and the octave shift adds octaves
+        module.process(&[], &mut outputs, &[84.0, 1.0, 100.0, 0.0, 0.0, 0.0, 0.0, 0.0], &ctx);
+        assert!((outputs[0].samples[0] - 1.0).abs() < 1e-6);
+        module.process(&[], &mut outputs, &[61.0, 1.0, 100.0, 0.0, 0.0, -1.0, 0.0, 0.0], &ctx);
+        assert!((outputs[0].samples[0] - (1.0 / 24.0 - 1.0)).abs() < 1e-6);
+    }
+
     #[test]
     fn test_midi_note_velocity_normalization() {
         let mut module = MidiNote::new();
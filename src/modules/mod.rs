@@ -1,12 +1,16 @@
 //! Modules module
 //!
 //! Built-in synthesizer modules.
-//! Includes oscillators, filters, envelopes, LFOs, utilities, and output modules.
+//! Includes audio and MIDI input, MIDI file playback, oscillators, filters, envelopes, LFOs, utilities,
+//! and audio and MIDI output modules.
 
 pub mod attenuverter;
+pub mod audio_input;
 pub mod chorus;
 pub mod clock;
 pub mod compressor;
+pub mod cv_to_cc;
+pub mod cv_to_midi;
 pub mod delay;
 pub mod distortion;
 pub mod envelope;
@@ -14,22 +18,31 @@ pub mod eq;
 pub mod filter;
 pub mod keyboard;
 pub mod lfo;
+pub mod midi_file_player;
 pub mod midi_monitor;
 pub mod mixer;
 pub mod midi_note;
+pub mod mpe_voice;
+pub mod multi_output;
 pub mod oscillator;
 pub mod oscilloscope;
 pub mod output;
+pub mod quantizer;
+pub mod recorder;
 pub mod reverb;
 pub mod sample_hold;
+pub mod scene_morph;
 pub mod sequencer;
 pub mod vca;
 
 // Re-export commonly used types
 pub use attenuverter::Attenuverter;
+pub use audio_input::AudioInput;
 pub use chorus::Chorus;
 pub use clock::Clock;
 pub use compressor::Compressor;
+pub use cv_to_cc::CvToCc;
+pub use cv_to_midi::CvToMidi;
 pub use delay::StereoDelay;
 pub use distortion::Distortion;
 pub use envelope::AdsrEnvelope;
@@ -37,13 +50,19 @@ pub use eq::ParametricEq;
 pub use filter::SvfFilter;
 pub use keyboard::KeyboardInput;
 pub use lfo::Lfo;
+pub use midi_file_player::MidiFilePlayer;
 pub use midi_monitor::MidiMonitor;
 pub use mixer::Mixer;
 pub use midi_note::MidiNote;
+pub use mpe_voice::MpeVoice;
+pub use multi_output::MultiOutput;
 pub use oscillator::SineOscillator;
 pub use oscilloscope::Oscilloscope;
 pub use output::AudioOutput;
+pub use quantizer::Quantizer;
+pub use recorder::Recorder;
 pub use reverb::Reverb;
 pub use sample_hold::SampleHold;
+pub use scene_morph::SceneMorph;
 pub use sequencer::StepSequencer;
 pub use vca::Vca;
@@ -0,0 +1,246 @@
+//! MPE Voice module.
+//!
+//! Outputs one voice of an MPE controller: pitch with per-note bend,
+//! pressure, slide, gate and velocity. Add one module per voice.
+
+use crate::dsp::{
+    context::ProcessContext,
+    module_trait::{DspModule, ModuleCategory, ModuleInfo},
+    parameter::ParameterDefinition,
+    port::PortDefinition,
+    signal::SignalBuffer,
+    ParameterDisplay, SignalType,
+};
+
+/// A module that outputs one voice of an MPE zone.
+///
+/// The notes of the zone are assigned to voices as they are played, and
+/// each MPE Voice module outputs the voice selected by its Voice parameter.
+/// Modules with the same zone settings and input share one set of voices.
+///
+/// # Ports
+///
+/// **Outputs:**
+/// - **Pitch** (Control): V/Oct pitch including per-note and zone pitch bend.
+///   0.0 = C4 (MIDI 60).
+/// - **Gate** (Gate): High while the voice's note is held.
+/// - **Velocity** (Control): Note On velocity (0.0-1.0).
+/// - **Pressure** (Control): Per-note pressure (0.0-1.0).
+/// - **Slide** (Control): Per-note slide, CC74 (0.0-1.0).
+///
+/// # Parameters
+///
+/// - **Pitch**, **Gate**, **Velocity**, **Pressure**, **Slide**: Current
+///   voice state (set by MIDI events).
+/// - **Zone** (Lower/Upper): Master channel 1 or 16.
+/// - **Channels** (1-15): Number of member channels in the zone.
+/// - **Bend Range** (1-96 semitones): Pitch bend range of the member channels.
+/// - **Voice** (1-15): Which voice this module outputs.
+///
+/// The MIDI input device it listens to is chosen by name in the editor, not
+/// as a parameter, since input port numbers change as devices come and go.
+pub struct MpeVoice {
+    /// Smoothed pitch output.
+    current_pitch: f32,
+    /// Smoothed pressure output.
+    current_pressure: f32,
+    /// Smoothed slide output.
+    current_slide: f32,
+    /// Gate of the last sample, to start new notes at their pitch.
+    prev_gate: bool,
+    /// Per-sample smoothing coefficient.
+    smoothing: f32,
+    /// Port definitions.
+    ports: Vec<PortDefinition>,
+    /// Parameter definitions.
+    parameters: Vec<ParameterDefinition>,
+}
+
+impl MpeVoice {
+    /// Creates a new MPE Voice module.
+    pub fn new() -> Self {
+        let mut module = Self {
+            current_pitch: 0.0,
+            current_pressure: 0.0,
+            current_slide: 0.5,
+            prev_gate: false,
+            smoothing: 1.0,
+            ports: vec![
+                PortDefinition::output("pitch", "Pitch", SignalType::Control),
+                PortDefinition::output("gate", "Gate", SignalType::Gate),
+                PortDefinition::output("velocity", "Velocity", SignalType::Control),
+                PortDefinition::output("pressure", "Pressure", SignalType::Control),
+                PortDefinition::output("slide", "Slide", SignalType::Control),
+            ],
+            parameters: vec![
+                // Voice state, set by MIDI events
+                ParameterDefinition::new("pitch", "Pitch", -10.0, 10.0, 0.0, ParameterDisplay::Linear { unit: "V" }).not_randomized(),
+                ParameterDefinition::toggle("gate", "Gate", false).not_randomized(),
+                ParameterDefinition::new("velocity", "Velocity", 0.0, 1.0, 0.0, ParameterDisplay::Linear { unit: "" }).not_randomized(),
+                ParameterDefinition::new("pressure", "Pressure", 0.0, 1.0, 0.0, ParameterDisplay::Linear { unit: "" }).not_randomized(),
+                ParameterDefinition::new("slide", "Slide", 0.0, 1.0, 0.5, ParameterDisplay::Linear { unit: "" }).not_randomized(),
+                // Zone: lower (master channel 1) or upper (master channel 16)
+                ParameterDefinition::choice("zone", "Zone", &["Lower", "Upper"], 0).not_randomized(),
+                // Channels: number of member channels in the zone
+                ParameterDefinition::new("channels", "Channels", 1.0, 15.0, 15.0, ParameterDisplay::Linear { unit: "" }).not_randomized(),
+                // Bend Range: pitch bend range of the member channels
+                ParameterDefinition::new("bend_range", "Bend Range", 1.0, 96.0, 48.0, ParameterDisplay::Linear { unit: "st" }).not_randomized(),
+                // Voice: which voice of the zone this module outputs
+                ParameterDefinition::choice(
+                    "voice",
+                    "Voice",
+                    &["1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15"],
+                    0,
+                ).not_randomized(),
+            ],
+        };
+        module.prepare(44100.0, 0);
+        module
+    }
+
+    /// Port index constants.
+    const PORT_PITCH: usize = 0;
+    const PORT_GATE: usize = 1;
+    const PORT_VELOCITY: usize = 2;
+    const PORT_PRESSURE: usize = 3;
+    const PORT_SLIDE: usize = 4;
+
+    /// Parameter index constants.
+    pub const PARAM_PITCH: usize = 0;
+    pub const PARAM_GATE: usize = 1;
+    pub const PARAM_VELOCITY: usize = 2;
+    pub const PARAM_PRESSURE: usize = 3;
+    pub const PARAM_SLIDE: usize = 4;
+    pub const PARAM_ZONE: usize = 5;
+    pub const PARAM_CHANNELS: usize = 6;
+    pub const PARAM_BEND_RANGE: usize = 7;
+    pub const PARAM_VOICE: usize = 8;
+
+    /// Time constant of the expression smoothing. The values arrive once
+    /// per UI frame; smoothing turns the steps into a continuous signal.
+    const SMOOTHING_TIME: f32 = 0.005;
+}
+
+impl Default for MpeVoice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DspModule for MpeVoice {
+    fn info(&self) -> &ModuleInfo {
+        static INFO: ModuleInfo = ModuleInfo {
+            id: "input.mpe_voice",
+            name: "MPE Voice",
+            category: ModuleCategory::Source,
+            description: "One voice of an MPE controller with per-note expression",
+        };
+        &INFO
+    }
+
+    fn ports(&self) -> &[PortDefinition] {
+        &self.ports
+    }
+
+    fn parameters(&self) -> &[ParameterDefinition] {
+        &self.parameters
+    }
+
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.smoothing = 1.0 - (-1.0 / (Self::SMOOTHING_TIME * sample_rate)).exp();
+    }
+
+    fn process(
+        &mut self,
+        _inputs: &[&SignalBuffer],
+        outputs: &mut [SignalBuffer],
+        params: &[f32],
+        context: &ProcessContext,
+    ) {
+        let pitch = params[Self::PARAM_PITCH];
+        let gate = params[Self::PARAM_GATE] > 0.5;
+        let velocity = params[Self::PARAM_VELOCITY];
+        let pressure = params[Self::PARAM_PRESSURE];
+        let slide = params[Self::PARAM_SLIDE];
+
+        // A new note starts at its own pitch instead of gliding from the last one
+        if gate && !self.prev_gate {
+            self.current_pitch = pitch;
+        }
+        self.prev_gate = gate;
+
+        for i in 0..context.block_size {
+            self.current_pitch += (pitch - self.current_pitch) * self.smoothing;
+            self.current_pressure += (pressure - self.current_pressure) * self.smoothing;
+            self.current_slide += (slide - self.current_slide) * self.smoothing;
+
+            outputs[Self::PORT_PITCH].samples[i] = self.current_pitch;
+            outputs[Self::PORT_GATE].samples[i] = if gate { 1.0 } else { 0.0 };
+            outputs[Self::PORT_VELOCITY].samples[i] = velocity;
+            outputs[Self::PORT_PRESSURE].samples[i] = self.current_pressure;
+            outputs[Self::PORT_SLIDE].samples[i] = self.current_slide;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.current_pitch = 0.0;
+        self.current_pressure = 0.0;
+        self.current_slide = 0.5;
+        self.prev_gate = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pitch: f32, gate: f32, pressure: f32) -> [f32; 10] {
+        [pitch, gate, 0.8, pressure, 0.5, 0.0, 15.0, 48.0, 0.0, 0.0]
+    }
+
+    fn process(module: &mut MpeVoice, params: &[f32]) -> Vec<SignalBuffer> {
+        let ctx = ProcessContext::new(48000.0, 2400);
+        let mut outputs: Vec<SignalBuffer> = (0..5).map(|_| SignalBuffer::control(2400)).collect();
+        module.process(&[], &mut outputs, params, &ctx);
+        outputs
+    }
+
+    #[test]
+    fn test_mpe_voice_info() {
+        let module = MpeVoice::new();
+        assert_eq!(module.info().id, "input.mpe_voice");
+        assert_eq!(module.info().category, ModuleCategory::Source);
+        assert_eq!(module.ports().len(), 5);
+        assert!(module.ports().iter().all(|port| port.is_output()));
+
+        let params = module.parameters();
+        assert_eq!(params.len(), 9);
+        assert_eq!(params[MpeVoice::PARAM_CHANNELS].default, 15.0);
+        assert_eq!(params[MpeVoice::PARAM_BEND_RANGE].default, 48.0);
+    }
+
+    #[test]
+    fn test_new_note_starts_at_its_pitch() {
+        let mut module = MpeVoice::new();
+        module.prepare(48000.0, 2400);
+
+        let outputs = process(&mut module, &params(1.0, 1.0, 0.0));
+        assert_eq!(outputs[0].samples[0], 1.0);
+        assert_eq!(outputs[1].samples[0], 1.0);
+        assert!((outputs[2].samples[0] - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_expression_is_smoothed() {
+        let mut module = MpeVoice::new();
+        module.prepare(48000.0, 2400);
+        process(&mut module, &params(0.0, 1.0, 0.0));
+
+        // A bend while the note is held glides over a few milliseconds
+        let outputs = process(&mut module, &params(0.5, 1.0, 1.0));
+        let pitch = &outputs[0].samples;
+        assert!(pitch[0] > 0.0 && pitch[0] < 0.1, "got {}", pitch[0]);
+        assert!((pitch[2399] - 0.5).abs() < 0.01, "got {}", pitch[2399]);
+        assert!((outputs[3].samples[2399] - 1.0).abs() < 0.01);
+    }
+}
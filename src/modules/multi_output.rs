@@ -0,0 +1,172 @@
+//! Multi output module.
+//!
+//! Sends up to eight separate signals to the audio device, so stems can
+//! go to different channels of an audio interface or a hardware mixer.
+
+use crate::dsp::{
+    context::ProcessContext,
+    module_trait::{DspModule, ModuleCategory, ModuleInfo},
+    parameter::ParameterDefinition,
+    port::PortDefinition,
+    signal::SignalBuffer,
+    smoothed_value::SmoothedValue,
+    SignalType,
+};
+
+/// An eight-channel output to the audio device.
+///
+/// Which device channel each input reaches is set in the output routing
+/// matrix; by default input N goes to device channel N. The signals of all
+/// Multi Output modules are summed per channel, and added to whatever the
+/// routing sends there from the stereo master.
+///
+/// # Ports
+///
+/// **Inputs:**
+/// - **Ch 1** to **Ch 8** (Audio): One signal per output channel.
+///
+/// # Parameters
+///
+/// - **Level** (0.0-1.0): Level of all channels, default 1.0. No limiter is
+///   applied, so the channels reach the device as they are.
+pub struct MultiOutput {
+    /// Port definitions.
+    ports: Vec<PortDefinition>,
+    /// Parameter definitions.
+    parameters: Vec<ParameterDefinition>,
+    /// Output of the last block, one buffer per channel.
+    output_buffer: Vec<Vec<f32>>,
+    /// Smoothed level parameter.
+    level_smooth: SmoothedValue,
+}
+
+impl MultiOutput {
+    /// Creates a new multi output module.
+    pub fn new() -> Self {
+        Self {
+            ports: vec![
+                PortDefinition::input_with_default("ch1", "Ch 1", SignalType::Audio, 0.0),
+                PortDefinition::input_with_default("ch2", "Ch 2", SignalType::Audio, 0.0),
+                PortDefinition::input_with_default("ch3", "Ch 3", SignalType::Audio, 0.0),
+                PortDefinition::input_with_default("ch4", "Ch 4", SignalType::Audio, 0.0),
+                PortDefinition::input_with_default("ch5", "Ch 5", SignalType::Audio, 0.0),
+                PortDefinition::input_with_default("ch6", "Ch 6", SignalType::Audio, 0.0),
+                PortDefinition::input_with_default("ch7", "Ch 7", SignalType::Audio, 0.0),
+                PortDefinition::input_with_default("ch8", "Ch 8", SignalType::Audio, 0.0),
+            ],
+            parameters: vec![ParameterDefinition::normalized("level", "Level", 1.0).not_randomized()],
+            output_buffer: vec![Vec::new(); Self::CHANNELS],
+            level_smooth: SmoothedValue::with_default_smoothing(1.0, 44100.0),
+        }
+    }
+
+    /// Number of channel inputs.
+    const CHANNELS: usize = 8;
+
+    /// Parameter index constants.
+    const PARAM_LEVEL: usize = 0;
+}
+
+impl Default for MultiOutput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DspModule for MultiOutput {
+    fn info(&self) -> &ModuleInfo {
+        static INFO: ModuleInfo = ModuleInfo {
+            id: "output.multi",
+            name: "Multi Output",
+            category: ModuleCategory::Output,
+            description: "Eight separate outputs to the audio device",
+        };
+        &INFO
+    }
+
+    fn ports(&self) -> &[PortDefinition] {
+        &self.ports
+    }
+
+    fn parameters(&self) -> &[ParameterDefinition] {
+        &self.parameters
+    }
+
+    fn prepare(&mut self, sample_rate: f32, max_block_size: usize) {
+        for buffer in &mut self.output_buffer {
+            buffer.resize(max_block_size, 0.0);
+        }
+        self.level_smooth.set_sample_rate(sample_rate);
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&SignalBuffer],
+        _outputs: &mut [SignalBuffer],
+        params: &[f32],
+        context: &ProcessContext,
+    ) {
+        self.level_smooth.set_target(params[Self::PARAM_LEVEL]);
+
+        for buffer in &mut self.output_buffer {
+            buffer.resize(context.block_size, 0.0);
+        }
+
+        for i in 0..context.block_size {
+            let level = self.level_smooth.next();
+            for (channel, buffer) in self.output_buffer.iter_mut().enumerate() {
+                let sample = inputs
+                    .get(channel)
+                    .and_then(|buf| buf.samples.get(i).copied())
+                    .unwrap_or(0.0);
+                buffer[i] = sample * level;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for buffer in &mut self.output_buffer {
+            buffer.fill(0.0);
+        }
+    }
+
+    fn get_multichannel_output(&self) -> Option<&[Vec<f32>]> {
+        Some(&self.output_buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_output_info() {
+        let output = MultiOutput::new();
+        assert_eq!(output.info().id, "output.multi");
+        assert_eq!(output.info().category, ModuleCategory::Output);
+        assert_eq!(output.ports().len(), MultiOutput::CHANNELS);
+        assert!(output.ports().iter().all(|p| p.is_input()));
+        assert!(output.get_audio_output().is_none());
+    }
+
+    #[test]
+    fn test_multi_output_channels() {
+        let mut output = MultiOutput::new();
+        output.prepare(44100.0, 4);
+
+        let mut ch1 = SignalBuffer::audio(4);
+        ch1.fill(0.5);
+        let silent = SignalBuffer::audio(4);
+        let mut ch3 = SignalBuffer::audio(4);
+        ch3.fill(-0.25);
+        let ctx = ProcessContext::new(44100.0, 4);
+        output.process(&[&ch1, &silent, &ch3], &mut [], &[1.0], &ctx);
+
+        let channels = output.get_multichannel_output().unwrap();
+        assert_eq!(channels.len(), MultiOutput::CHANNELS);
+        assert!((channels[0][3] - 0.5).abs() < 1e-6);
+        assert!(channels[1].iter().all(|&s| s == 0.0));
+        assert!((channels[2][3] + 0.25).abs() < 1e-6);
+        assert!(channels[7].iter().all(|&s| s == 0.0));
+    }
+}
@@ -153,7 +153,7 @@ impl Oscilloscope {
                     3.0,
                     0.0, // Default: Auto
                     ParameterDisplay::discrete(&["Auto", "Normal", "Single", "Free"]),
-                ),
+                ).not_randomized(),
                 // Trigger Level: -1 to +1
                 ParameterDefinition::new(
                     "trigger_level",
@@ -162,7 +162,7 @@ impl Oscilloscope {
                     1.0,
                     0.0, // Default: 0.0 (center)
                     ParameterDisplay::linear(""),
-                ),
+                ).not_randomized(),
             ],
         }
     }
@@ -66,8 +66,8 @@ impl AudioOutput {
                 PortDefinition::input_with_default("mono", "Mono", SignalType::Audio, 0.0),
             ],
             parameters: vec![
-                ParameterDefinition::normalized("volume", "Volume", 0.8),
-                ParameterDefinition::toggle("limiter", "Limiter", true),
+                ParameterDefinition::normalized("volume", "Volume", 0.8).not_randomized(),
+                ParameterDefinition::toggle("limiter", "Limiter", true).not_randomized(),
             ],
             output_buffer: [Vec::new(), Vec::new()],
             peak_left: 0.0,
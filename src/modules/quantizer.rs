@@ -0,0 +1,663 @@
+//! Quantizer module.
+//!
+//! Snaps V/Oct pitch CV to the notes of a scale, so that random or LFO-driven
+//! melodies stay in key. Scales are chosen from a list of presets, a custom
+//! 12-note mask, or the active tuning.
+
+use std::sync::Arc;
+
+use crate::dsp::{
+    context::ProcessContext,
+    module_trait::{DspModule, ModuleCategory, ModuleInfo},
+    parameter::ParameterDefinition,
+    port::PortDefinition,
+    signal::SignalBuffer,
+    ParameterDisplay, SignalType, Tuning,
+};
+
+/// Labels of the Scale parameter.
+pub static SCALE_LABELS: [&str; 14] = [
+    "Chromatic", "Major", "Minor", "Dorian", "Phrygian", "Lydian", "Mixolydian",
+    "Locrian", "Harm. Minor", "Maj. Pent.", "Min. Pent.", "Blues", "Custom", "Tuning",
+];
+
+/// Labels of the Root parameter.
+pub static ROOT_LABELS: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Semitones above the root of each preset scale (in `SCALE_LABELS` order).
+pub static SCALE_STEPS: [&[u8]; 12] = [
+    &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+    &[0, 2, 4, 5, 7, 9, 11],
+    &[0, 2, 3, 5, 7, 8, 10],
+    &[0, 2, 3, 5, 7, 9, 10],
+    &[0, 1, 3, 5, 7, 8, 10],
+    &[0, 2, 4, 6, 7, 9, 11],
+    &[0, 2, 4, 5, 7, 9, 10],
+    &[0, 1, 3, 5, 6, 8, 10],
+    &[0, 2, 3, 5, 7, 8, 11],
+    &[0, 2, 4, 7, 9],
+    &[0, 3, 5, 7, 10],
+    &[0, 3, 5, 6, 7, 10],
+];
+
+// Static parameter IDs and names for the custom mask (must be 'static for ParameterDefinition)
+static MASK_IDS: [&str; 12] = [
+    "mask_0", "mask_1", "mask_2", "mask_3", "mask_4", "mask_5",
+    "mask_6", "mask_7", "mask_8", "mask_9", "mask_10", "mask_11",
+];
+
+/// Names of the custom mask parameters, by scale degree above the root.
+pub static MASK_NAMES: [&str; 12] = [
+    "Mask 1", "Mask b2", "Mask 2", "Mask b3", "Mask 3", "Mask 4",
+    "Mask b5", "Mask 5", "Mask b6", "Mask 6", "Mask b7", "Mask 7",
+];
+
+/// Number of MIDI notes in a tuning.
+const NOTES: usize = 128;
+
+/// A note the quantizer can output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Note {
+    /// Semitones from middle C in a 12-note scale.
+    Semitone(i32),
+    /// Index into the distinct pitches of the tuning.
+    Tuned(usize),
+}
+
+/// A pitch quantizer.
+///
+/// Snaps the input to the nearest note of the selected scale. Ties go to
+/// the lower note. The Trigger output fires whenever the output note
+/// changes, so the quantizer can clock envelopes from a continuous CV.
+///
+/// # Use Cases
+///
+/// - Noise → S&H → Quantizer → Oscillator V/Oct = random melody in key
+/// - Slow LFO → Quantizer = arpeggiated scale runs
+/// - Sequencer → Quantizer (transposed by a second sequencer) = chord changes
+///
+/// # Ports
+///
+/// **Inputs:**
+/// - **In** (Control): V/Oct pitch to quantize.
+/// - **Transpose** (Control): V/Oct transposition, rounded to whole semitones.
+///
+/// **Outputs:**
+/// - **Out** (Control): Quantized V/Oct pitch.
+/// - **Trig** (Gate): Short pulse when the output note changes.
+///
+/// # Parameters
+///
+/// - **Scale**: Preset scale, Custom (the mask), or Tuning (the notes of the
+///   active tuning, ignoring Root and the mask).
+/// - **Root**: Root note of the scale.
+/// - **Hysteresis** (0-50 cents): How far past the midpoint between two notes
+///   the input must move before the output changes. Stops the output
+///   chattering when the input sits near a boundary.
+/// - **Mask 1 - Mask 7**: The notes of the Custom scale, by degree above
+///   the root. An empty mask plays only the root.
+///
+/// Transposing moves the scale with the output, so a transposed major scale
+/// is the major scale of the new key. In Tuning mode, each semitone of
+/// transposition moves one note of the tuning.
+pub struct Quantizer {
+    /// Note currently being output, if any.
+    current: Option<Note>,
+    /// Transposition of the current output in semitones.
+    current_transpose: i32,
+    /// Remaining samples of the trigger pulse.
+    trigger_timer: usize,
+    /// Trigger pulse duration in samples at the current sample rate.
+    trigger_pulse_samples: usize,
+    /// Distinct pitches of the tuning in ascending order.
+    tuned_pitches: [f32; NOTES],
+    /// Number of valid entries in `tuned_pitches`.
+    tuned_count: usize,
+    /// Port definitions.
+    ports: Vec<PortDefinition>,
+    /// Parameter definitions.
+    parameters: Vec<ParameterDefinition>,
+}
+
+impl Quantizer {
+    /// Creates a new Quantizer module.
+    pub fn new() -> Self {
+        let mut parameters = vec![
+            ParameterDefinition::choice("scale", "Scale", &SCALE_LABELS, 1),
+            ParameterDefinition::choice("root", "Root", &ROOT_LABELS, 0),
+            ParameterDefinition::new(
+                "hysteresis",
+                "Hysteresis",
+                0.0,
+                50.0,
+                10.0,
+                ParameterDisplay::linear("ct"),
+            ),
+        ];
+        // The custom mask defaults to the major scale
+        for degree in 0..12 {
+            let in_major = SCALE_STEPS[1].contains(&(degree as u8));
+            parameters.push(ParameterDefinition::toggle(MASK_IDS[degree], MASK_NAMES[degree], in_major));
+        }
+
+        let mut quantizer = Self {
+            current: None,
+            current_transpose: 0,
+            trigger_timer: 0,
+            trigger_pulse_samples: Self::trigger_pulse_samples(44100.0),
+            tuned_pitches: [0.0; NOTES],
+            tuned_count: 0,
+            ports: vec![
+                // Input ports
+                PortDefinition::input_with_default("in", "In", SignalType::Control, 0.0),
+                PortDefinition::input_with_default("transpose", "Transpose", SignalType::Control, 0.0),
+                // Output ports
+                PortDefinition::output("out", "Out", SignalType::Control),
+                PortDefinition::output("trigger", "Trig", SignalType::Gate),
+            ],
+            parameters,
+        };
+        quantizer.set_tuning(Arc::new(Tuning::equal()));
+        quantizer
+    }
+
+    /// Port index constants.
+    const PORT_IN: usize = 0;
+    const PORT_TRANSPOSE: usize = 1;
+    const PORT_OUT: usize = 0;
+    const PORT_TRIGGER: usize = 1;
+
+    /// Parameter index constants.
+    const PARAM_SCALE: usize = 0;
+    const PARAM_ROOT: usize = 1;
+    const PARAM_HYSTERESIS: usize = 2;
+    const PARAM_MASK: usize = 3;
+
+    /// Scale index of the custom mask.
+    pub const SCALE_CUSTOM: usize = 12;
+    /// Scale index of the active tuning.
+    pub const SCALE_TUNING: usize = 13;
+
+    /// Trigger pulse duration in seconds.
+    const TRIGGER_PULSE_SECONDS: f32 = 0.001;
+
+    /// Range of the pitch and transpose inputs in volts. Keeps the
+    /// semitone arithmetic far from `i32` overflow.
+    const MAX_VOLTS: f32 = 10.0;
+
+    /// Trigger pulse duration in samples at a sample rate.
+    fn trigger_pulse_samples(sample_rate: f32) -> usize {
+        ((sample_rate * Self::TRIGGER_PULSE_SECONDS).round() as usize).max(1)
+    }
+
+    /// The selected scale as a 12-bit mask of degrees above the root.
+    fn scale_mask(scale: usize, params: &[f32]) -> u16 {
+        let mask = match SCALE_STEPS.get(scale) {
+            Some(steps) => steps.iter().fold(0, |mask, step| mask | 1 << step),
+            None => (0..12)
+                .filter(|degree| params.get(Self::PARAM_MASK + degree).is_some_and(|v| *v > 0.5))
+                .fold(0, |mask, degree| mask | 1 << degree),
+        };
+        // An empty mask plays only the root
+        if mask == 0 { 1 } else { mask }
+    }
+
+    /// V/Oct pitch of a note, before transposing.
+    fn pitch(&self, note: Note) -> f32 {
+        match note {
+            Note::Semitone(semitone) => semitone as f32 / 12.0,
+            Note::Tuned(index) => self.tuned_pitches[index],
+        }
+    }
+
+    /// Whether a note belongs to the scale.
+    fn in_scale(&self, note: Note, tuned: bool, mask: u16, root: i32) -> bool {
+        match note {
+            Note::Semitone(semitone) => !tuned && mask & 1 << (semitone - root).rem_euclid(12) != 0,
+            Note::Tuned(index) => tuned && index < self.tuned_count,
+        }
+    }
+
+    /// Nearest note of the scale to a pitch, the lower one on a tie.
+    fn nearest(&self, pitch: f32, tuned: bool, mask: u16, root: i32) -> Note {
+        if tuned {
+            let pitches = &self.tuned_pitches[..self.tuned_count];
+            let above = pitches.partition_point(|p| *p < pitch).min(pitches.len() - 1);
+            if above > 0 && pitch - pitches[above - 1] <= (pitches[above] - pitch).abs() {
+                return Note::Tuned(above - 1);
+            }
+            return Note::Tuned(above);
+        }
+
+        // Every pitch class is within six semitones, so the search always
+        // finds a note of a non-empty mask
+        let semitones = pitch * 12.0;
+        let center = semitones.round() as i32;
+        let mut best = Note::Semitone(center);
+        let mut best_distance = f32::INFINITY;
+        for semitone in center - 6..=center + 6 {
+            let note = Note::Semitone(semitone);
+            let distance = (semitones - semitone as f32).abs();
+            if self.in_scale(note, false, mask, root) && distance < best_distance {
+                best = note;
+                best_distance = distance;
+            }
+        }
+        best
+    }
+
+    /// V/Oct output of a note transposed by a number of semitones.
+    fn output(&self, note: Note, transpose: i32) -> f32 {
+        match note {
+            Note::Semitone(semitone) => (semitone + transpose) as f32 / 12.0,
+            Note::Tuned(index) => {
+                let last = self.tuned_count as i32 - 1;
+                self.tuned_pitches[(index as i32 + transpose).clamp(0, last) as usize]
+            }
+        }
+    }
+}
+
+impl Default for Quantizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DspModule for Quantizer {
+    fn info(&self) -> &ModuleInfo {
+        static INFO: ModuleInfo = ModuleInfo {
+            id: "util.quantizer",
+            name: "Quantizer",
+            category: ModuleCategory::Utility,
+            description: "Snap pitch CV to the notes of a scale",
+        };
+        &INFO
+    }
+
+    fn ports(&self) -> &[PortDefinition] {
+        &self.ports
+    }
+
+    fn parameters(&self) -> &[ParameterDefinition] {
+        &self.parameters
+    }
+
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.trigger_pulse_samples = Self::trigger_pulse_samples(sample_rate);
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&SignalBuffer],
+        outputs: &mut [SignalBuffer],
+        params: &[f32],
+        context: &ProcessContext,
+    ) {
+        let scale = params[Self::PARAM_SCALE].round() as usize;
+        let root = params[Self::PARAM_ROOT].round() as i32;
+        let hysteresis = params[Self::PARAM_HYSTERESIS] / 1200.0;
+        let tuned = scale == Self::SCALE_TUNING;
+        let mask = Self::scale_mask(scale, params);
+
+        let signal_in = inputs.get(Self::PORT_IN);
+        let transpose_in = inputs.get(Self::PORT_TRANSPOSE);
+
+        for i in 0..context.block_size {
+            let input_value = signal_in
+                .map(|buf| buf.samples.get(i).copied().unwrap_or(0.0))
+                .map_or(0.0, |v| v.clamp(-Self::MAX_VOLTS, Self::MAX_VOLTS));
+            let transpose = transpose_in
+                .map(|buf| buf.samples.get(i).copied().unwrap_or(0.0))
+                .map(|cv| (cv.clamp(-Self::MAX_VOLTS, Self::MAX_VOLTS) * 12.0).round() as i32)
+                .unwrap_or(0);
+
+            // Quantize the untransposed pitch against the untransposed scale
+            let pitch = if tuned { input_value } else { input_value - transpose as f32 / 12.0 };
+            let candidate = self.nearest(pitch, tuned, mask, root);
+
+            // Keep the current note until the input is clearly nearer another
+            let note = match self.current {
+                Some(current) if self.in_scale(current, tuned, mask, root) => {
+                    let current_distance = (pitch - self.pitch(current)).abs();
+                    let candidate_distance = (pitch - self.pitch(candidate)).abs();
+                    if current_distance > candidate_distance + 2.0 * hysteresis {
+                        candidate
+                    } else {
+                        current
+                    }
+                }
+                _ => candidate,
+            };
+
+            // The first note isn't a change
+            let changed = self.current.is_some_and(|current| current != note)
+                || (self.current.is_some() && transpose != self.current_transpose);
+            if changed {
+                self.trigger_timer = self.trigger_pulse_samples;
+            }
+            self.current = Some(note);
+            self.current_transpose = transpose;
+
+            outputs[Self::PORT_OUT].samples[i] = self.output(note, transpose);
+            outputs[Self::PORT_TRIGGER].samples[i] = if self.trigger_timer > 0 { 1.0 } else { 0.0 };
+
+            if self.trigger_timer > 0 {
+                self.trigger_timer -= 1;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.current = None;
+        self.current_transpose = 0;
+        self.trigger_timer = 0;
+    }
+
+    fn set_tuning(&mut self, tuning: Arc<Tuning>) {
+        // Sort and deduplicate in place, without allocating on the audio thread
+        let mut pitches = *tuning.pitches();
+        pitches.sort_unstable_by(f32::total_cmp);
+        let mut count = 0;
+        for pitch in pitches {
+            if count == 0 || pitch > self.tuned_pitches[count - 1] {
+                self.tuned_pitches[count] = pitch;
+                count += 1;
+            }
+        }
+        self.tuned_count = count;
+
+        // Tuned note indices may no longer match
+        if matches!(self.current, Some(Note::Tuned(_))) {
+            self.current = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Default parameters: major scale, root C, no hysteresis, major mask.
+    fn params(scale: usize, root: usize, hysteresis: f32) -> Vec<f32> {
+        let quantizer = Quantizer::new();
+        let mut params: Vec<f32> = quantizer.parameters().iter().map(|p| p.default).collect();
+        params[Quantizer::PARAM_SCALE] = scale as f32;
+        params[Quantizer::PARAM_ROOT] = root as f32;
+        params[Quantizer::PARAM_HYSTERESIS] = hysteresis;
+        params
+    }
+
+    /// Quantize a single value and return (output, trigger).
+    fn quantize(quantizer: &mut Quantizer, input: f32, transpose: f32, params: &[f32]) -> (f32, f32) {
+        let mut signal_in = SignalBuffer::control(1);
+        signal_in.fill(input);
+        let mut transpose_in = SignalBuffer::control(1);
+        transpose_in.fill(transpose);
+        let mut outputs = vec![SignalBuffer::control(1), SignalBuffer::control(1)];
+        let ctx = ProcessContext::new(44100.0, 1);
+
+        quantizer.process(&[&signal_in, &transpose_in], &mut outputs, params, &ctx);
+        (outputs[0].samples[0], outputs[1].samples[0])
+    }
+
+    fn semitones(voct: f32) -> f32 {
+        (voct * 12.0 * 1000.0).round() / 1000.0
+    }
+
+    #[test]
+    fn test_quantizer_info() {
+        let quantizer = Quantizer::new();
+        assert_eq!(quantizer.info().id, "util.quantizer");
+        assert_eq!(quantizer.info().name, "Quantizer");
+        assert_eq!(quantizer.info().category, ModuleCategory::Utility);
+    }
+
+    #[test]
+    fn test_quantizer_ports() {
+        let quantizer = Quantizer::new();
+        let ports = quantizer.ports();
+
+        // 2 inputs + 2 outputs = 4 ports
+        assert_eq!(ports.len(), 4);
+        assert!(ports[0].is_input());
+        assert_eq!(ports[0].id, "in");
+        assert!(ports[1].is_input());
+        assert_eq!(ports[1].id, "transpose");
+        assert!(ports[2].is_output());
+        assert_eq!(ports[2].id, "out");
+        assert_eq!(ports[2].signal_type, SignalType::Control);
+        assert!(ports[3].is_output());
+        assert_eq!(ports[3].id, "trigger");
+        assert_eq!(ports[3].signal_type, SignalType::Gate);
+    }
+
+    #[test]
+    fn test_quantizer_parameters() {
+        let quantizer = Quantizer::new();
+        let params = quantizer.parameters();
+
+        // Scale, Root, Hysteresis and the 12-note mask
+        assert_eq!(params.len(), 15);
+        assert_eq!(params[0].id, "scale");
+        assert!((params[0].max - 13.0).abs() < f32::EPSILON);
+        assert_eq!(params[1].id, "root");
+        assert!((params[1].max - 11.0).abs() < f32::EPSILON);
+        assert_eq!(params[2].id, "hysteresis");
+        assert_eq!(params[3].id, "mask_0");
+        assert_eq!(params[14].name, "Mask 7");
+
+        // The mask defaults to the major scale
+        let mask: Vec<bool> = params[3..].iter().map(|p| p.default > 0.5).collect();
+        assert_eq!(mask, vec![true, false, true, false, true, true, false, true, false, true, false, true]);
+    }
+
+    #[test]
+    fn test_quantizer_scales() {
+        let mut quantizer = Quantizer::new();
+
+        // C major: C# (1) rounds down to C, D# (3) down to D, F# (6) down to F
+        let major = params(1, 0, 0.0);
+        for (input, expected) in [(0.0, 0.0), (1.0, 0.0), (3.0, 2.0), (6.0, 5.0), (11.4, 11.0), (-1.0, -1.0), (12.6, 12.0)] {
+            quantizer.reset();
+            let (out, _) = quantize(&mut quantizer, input / 12.0, 0.0, &major);
+            assert_eq!(semitones(out), expected, "C major from {}", input);
+        }
+
+        // A minor pentatonic: A C D E G
+        let pentatonic = params(10, 9, 0.0);
+        for (input, expected) in [(1.0, 0.0), (5.0, 4.0), (6.0, 7.0), (8.0, 7.0), (9.4, 9.0), (10.6, 12.0)] {
+            quantizer.reset();
+            let (out, _) = quantize(&mut quantizer, input / 12.0, 0.0, &pentatonic);
+            assert_eq!(semitones(out), expected, "A minor pentatonic from {}", input);
+        }
+
+        // Chromatic rounds to the nearest semitone
+        quantizer.reset();
+        let (out, _) = quantize(&mut quantizer, 0.3 / 12.0, 0.0, &params(0, 0, 0.0));
+        assert_eq!(semitones(out), 0.0);
+    }
+
+    #[test]
+    fn test_quantizer_custom_mask() {
+        let mut quantizer = Quantizer::new();
+
+        // Root and fifth above D
+        let mut custom = params(Quantizer::SCALE_CUSTOM, 2, 0.0);
+        for degree in 0..12 {
+            custom[Quantizer::PARAM_MASK + degree] = if degree == 0 || degree == 7 { 1.0 } else { 0.0 };
+        }
+        let (out, _) = quantize(&mut quantizer, 7.0 / 12.0, 0.0, &custom);
+        assert_eq!(semitones(out), 9.0);
+
+        // An empty mask plays only the root
+        for degree in 0..12 {
+            custom[Quantizer::PARAM_MASK + degree] = 0.0;
+        }
+        quantizer.reset();
+        let (out, _) = quantize(&mut quantizer, 7.0 / 12.0, 0.0, &custom);
+        assert_eq!(semitones(out), 2.0);
+    }
+
+    #[test]
+    fn test_quantizer_transpose() {
+        let mut quantizer = Quantizer::new();
+        let major = params(1, 0, 0.0);
+
+        // Transposing by a whole tone plays D major: F# instead of F
+        let (out, _) = quantize(&mut quantizer, 6.0 / 12.0, 2.0 / 12.0, &major);
+        assert_eq!(semitones(out), 6.0);
+
+        // Transpose CV is rounded to semitones
+        quantizer.reset();
+        let (out, _) = quantize(&mut quantizer, 1.0 / 12.0, 0.95 / 12.0, &major);
+        assert_eq!(semitones(out), 1.0);
+    }
+
+    #[test]
+    fn test_quantizer_trigger_on_note_change() {
+        let mut quantizer = Quantizer::new();
+        let major = params(1, 0, 0.0);
+
+        // The first note doesn't trigger
+        let (_, trigger) = quantize(&mut quantizer, 0.0, 0.0, &major);
+        assert_eq!(trigger, 0.0);
+
+        // The same note doesn't trigger
+        let (_, trigger) = quantize(&mut quantizer, 0.02, 0.0, &major);
+        assert_eq!(trigger, 0.0);
+
+        // A new note triggers a pulse
+        let (_, trigger) = quantize(&mut quantizer, 2.0 / 12.0, 0.0, &major);
+        assert_eq!(trigger, 1.0);
+        for _ in 1..quantizer.trigger_pulse_samples {
+            quantize(&mut quantizer, 2.0 / 12.0, 0.0, &major);
+        }
+        let (_, trigger) = quantize(&mut quantizer, 2.0 / 12.0, 0.0, &major);
+        assert_eq!(trigger, 0.0);
+
+        // So does a transposition
+        let (_, trigger) = quantize(&mut quantizer, 2.0 / 12.0, 1.0, &major);
+        assert_eq!(trigger, 1.0);
+    }
+
+    #[test]
+    fn test_quantizer_hysteresis() {
+        let mut quantizer = Quantizer::new();
+        let chromatic = params(0, 0, 20.0);
+
+        quantize(&mut quantizer, 0.0, 0.0, &chromatic);
+
+        // Just past the midpoint stays on C
+        let (out, trigger) = quantize(&mut quantizer, 0.6 / 12.0, 0.0, &chromatic);
+        assert_eq!(semitones(out), 0.0);
+        assert_eq!(trigger, 0.0);
+
+        // More than 20 cents past the midpoint moves to C#
+        let (out, trigger) = quantize(&mut quantizer, 0.75 / 12.0, 0.0, &chromatic);
+        assert_eq!(semitones(out), 1.0);
+        assert_eq!(trigger, 1.0);
+
+        // And coming back needs the same margin
+        let (out, _) = quantize(&mut quantizer, 0.4 / 12.0, 0.0, &chromatic);
+        assert_eq!(semitones(out), 1.0);
+        let (out, _) = quantize(&mut quantizer, 0.25 / 12.0, 0.0, &chromatic);
+        assert_eq!(semitones(out), 0.0);
+
+        // A note that leaves the scale changes at once
+        quantize(&mut quantizer, 0.9 / 12.0, 0.0, &chromatic);
+        let (out, _) = quantize(&mut quantizer, 0.9 / 12.0, 0.0, &params(1, 0, 20.0));
+        assert_eq!(semitones(out), 0.0);
+    }
+
+    #[test]
+    fn test_quantizer_tuning() {
+        let mut quantizer = Quantizer::new();
+        let tuned = params(Quantizer::SCALE_TUNING, 0, 0.0);
+
+        // Two notes: C and a quarter octave above
+        let mut pitches = [None; NOTES];
+        pitches[60] = Some(0.0);
+        pitches[62] = Some(0.25);
+        quantizer.set_tuning(Arc::new(Tuning::from_pitches(&pitches)));
+
+        let (out, _) = quantize(&mut quantizer, 0.1, 0.0, &tuned);
+        assert_eq!(out, 0.0);
+        let (out, _) = quantize(&mut quantizer, 0.2, 0.0, &tuned);
+        assert_eq!(out, 0.25);
+        let (out, _) = quantize(&mut quantizer, 3.0, 0.0, &tuned);
+        assert_eq!(out, 0.25);
+
+        // Transposing moves by notes of the tuning, clamped to its range
+        let (out, _) = quantize(&mut quantizer, 0.0, -1.0 / 12.0, &tuned);
+        assert_eq!(out, 0.0);
+        let (out, _) = quantize(&mut quantizer, 0.0, 1.0 / 12.0, &tuned);
+        assert_eq!(out, 0.25);
+
+        // The default tuning is 12-TET
+        let mut quantizer = Quantizer::new();
+        let (out, _) = quantize(&mut quantizer, 0.4 / 12.0, 0.0, &tuned);
+        assert!(out.abs() < 1e-6);
+        let (out, _) = quantize(&mut quantizer, 7.0 / 12.0, 0.0, &tuned);
+        assert!((out - 7.0 / 12.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_quantizer_trigger_follows_sample_rate() {
+        let mut quantizer = Quantizer::new();
+        quantizer.prepare(96000.0, 64);
+        assert_eq!(quantizer.trigger_pulse_samples, 96);
+        quantizer.prepare(44100.0, 64);
+        assert_eq!(quantizer.trigger_pulse_samples, 44);
+    }
+
+    #[test]
+    fn test_quantizer_extreme_inputs() {
+        let mut quantizer = Quantizer::new();
+        let major = params(1, 0, 0.0);
+
+        // Out-of-range voltages are clamped instead of overflowing
+        let (out, _) = quantize(&mut quantizer, f32::MAX, f32::MAX, &major);
+        assert_eq!(semitones(out), 120.0);
+        let (out, _) = quantize(&mut quantizer, f32::MIN, f32::MIN, &major);
+        assert_eq!(semitones(out), -120.0);
+    }
+
+    #[test]
+    fn test_quantizer_no_inputs() {
+        let mut quantizer = Quantizer::new();
+        let mut outputs = vec![SignalBuffer::control(64), SignalBuffer::control(64)];
+        let ctx = ProcessContext::new(44100.0, 64);
+
+        quantizer.process(&[], &mut outputs, &params(1, 0, 10.0), &ctx);
+        assert!(outputs[0].samples.iter().all(|v| *v == 0.0));
+        assert!(outputs[1].samples.iter().all(|v| *v == 0.0));
+    }
+
+    #[test]
+    fn test_quantizer_reset() {
+        let mut quantizer = Quantizer::new();
+        let major = params(1, 0, 0.0);
+        quantize(&mut quantizer, 0.0, 0.0, &major);
+        quantize(&mut quantizer, 4.0 / 12.0, 0.0, &major);
+        assert!(quantizer.trigger_timer > 0);
+
+        quantizer.reset();
+        assert_eq!(quantizer.current, None);
+        assert_eq!(quantizer.trigger_timer, 0);
+    }
+
+    #[test]
+    fn test_quantizer_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Quantizer>();
+    }
+
+    #[test]
+    fn test_quantizer_default() {
+        let quantizer = Quantizer::default();
+        assert_eq!(quantizer.info().id, "util.quantizer");
+    }
+}
@@ -0,0 +1,208 @@
+//! Recorder module.
+//!
+//! A patchable recording point: whatever reaches its inputs while its
+//! Record input is high is written to a WAV file, one file per take.
+//! Several recorders in a patch bounce several stems at once.
+
+use crate::dsp::{
+    context::ProcessContext,
+    module_trait::{DspModule, ModuleCategory, ModuleInfo},
+    parameter::ParameterDefinition,
+    port::PortDefinition,
+    signal::SignalBuffer,
+    SignalType,
+};
+
+/// A stereo recorder controlled by a gate.
+///
+/// The module only collects its input; the audio engine streams it to the
+/// recording writer thread, which saves each take to its own file in the
+/// recordings directory. Takes start and stop on audio block boundaries.
+///
+/// # Ports
+///
+/// **Inputs:**
+/// - **Left** (Audio): Left channel.
+/// - **Right** (Audio): Right channel.
+/// - **Mono** (Audio): Added to both channels.
+/// - **Record** (Gate): Starts and stops takes, depending on Mode.
+///
+/// # Parameters
+///
+/// - **Mode** (Gate/Toggle): Gate records while Record is high; Toggle
+///   starts a take on one rising edge and stops it on the next.
+pub struct Recorder {
+    /// Port definitions.
+    ports: Vec<PortDefinition>,
+    /// Parameter definitions.
+    parameters: Vec<ParameterDefinition>,
+    /// Collected stereo input of the last block. Index 0 = Left, 1 = Right.
+    buffer: [Vec<f32>; 2],
+    /// Previous Record input state for edge detection.
+    prev_gate: bool,
+    /// Whether a take is in progress.
+    recording: bool,
+}
+
+impl Recorder {
+    /// Creates a new Recorder module.
+    pub fn new() -> Self {
+        Self {
+            ports: vec![
+                PortDefinition::input_with_default("left", "Left", SignalType::Audio, 0.0),
+                PortDefinition::input_with_default("right", "Right", SignalType::Audio, 0.0),
+                PortDefinition::input_with_default("mono", "Mono", SignalType::Audio, 0.0),
+                PortDefinition::input_with_default("record", "Record", SignalType::Gate, 0.0),
+            ],
+            parameters: vec![ParameterDefinition::choice("mode", "Mode", &["Gate", "Toggle"], 0).not_randomized()],
+            buffer: [Vec::new(), Vec::new()],
+            prev_gate: false,
+            recording: false,
+        }
+    }
+
+    /// Port index constants.
+    const PORT_LEFT: usize = 0;
+    const PORT_RIGHT: usize = 1;
+    const PORT_MONO: usize = 2;
+    const PORT_RECORD: usize = 3;
+
+    /// Parameter index constants.
+    const PARAM_MODE: usize = 0;
+
+    /// Whether a take is in progress.
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DspModule for Recorder {
+    fn info(&self) -> &ModuleInfo {
+        static INFO: ModuleInfo = ModuleInfo {
+            id: "output.recorder",
+            name: "Recorder",
+            category: ModuleCategory::Output,
+            description: "Records its input to WAV files while gated",
+        };
+        &INFO
+    }
+
+    fn ports(&self) -> &[PortDefinition] {
+        &self.ports
+    }
+
+    fn parameters(&self) -> &[ParameterDefinition] {
+        &self.parameters
+    }
+
+    fn prepare(&mut self, _sample_rate: f32, max_block_size: usize) {
+        self.buffer[0].resize(max_block_size, 0.0);
+        self.buffer[1].resize(max_block_size, 0.0);
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&SignalBuffer],
+        _outputs: &mut [SignalBuffer],
+        params: &[f32],
+        context: &ProcessContext,
+    ) {
+        let toggle = params[Self::PARAM_MODE] >= 0.5;
+        let sample = |port: usize, i: usize| {
+            inputs
+                .get(port)
+                .and_then(|buf| buf.samples.get(i).copied())
+                .unwrap_or(0.0)
+        };
+
+        self.buffer[0].resize(context.block_size, 0.0);
+        self.buffer[1].resize(context.block_size, 0.0);
+
+        for i in 0..context.block_size {
+            let mono = sample(Self::PORT_MONO, i);
+            self.buffer[0][i] = sample(Self::PORT_LEFT, i) + mono;
+            self.buffer[1][i] = sample(Self::PORT_RIGHT, i) + mono;
+
+            let gate = sample(Self::PORT_RECORD, i) > 0.5;
+            if toggle {
+                if gate && !self.prev_gate {
+                    self.recording = !self.recording;
+                }
+            } else {
+                self.recording = gate;
+            }
+            self.prev_gate = gate;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.prev_gate = false;
+        self.recording = false;
+    }
+
+    fn get_recording(&self) -> Option<(&[f32], &[f32], bool)> {
+        Some((&self.buffer[0], &self.buffer[1], self.recording))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(recorder: &mut Recorder, left: f32, mono: f32, gate: f32, mode: f32) {
+        let mut l = SignalBuffer::audio(64);
+        l.fill(left);
+        let r = SignalBuffer::audio(64);
+        let mut m = SignalBuffer::audio(64);
+        m.fill(mono);
+        let mut g = SignalBuffer::gate(64);
+        g.fill(gate);
+        let ctx = ProcessContext::new(44100.0, 64);
+        recorder.process(&[&l, &r, &m, &g], &mut [], &[mode], &ctx);
+    }
+
+    #[test]
+    fn test_recorder_info() {
+        let recorder = Recorder::new();
+        assert_eq!(recorder.info().id, "output.recorder");
+        assert_eq!(recorder.info().category, ModuleCategory::Output);
+        assert_eq!(recorder.ports().len(), 4);
+        assert!(recorder.ports().iter().all(|p| p.is_input()));
+        assert_eq!(recorder.parameters()[0].id, "mode");
+    }
+
+    #[test]
+    fn test_recorder_gate_mode() {
+        let mut recorder = Recorder::new();
+        recorder.prepare(44100.0, 64);
+
+        run(&mut recorder, 0.5, 0.25, 1.0, 0.0);
+        let (left, right, recording) = recorder.get_recording().unwrap();
+        assert!(recording);
+        assert_eq!(left.len(), 64);
+        assert!((left[10] - 0.75).abs() < f32::EPSILON);
+        assert!((right[10] - 0.25).abs() < f32::EPSILON);
+
+        run(&mut recorder, 0.5, 0.0, 0.0, 0.0);
+        assert!(!recorder.is_recording());
+    }
+
+    #[test]
+    fn test_recorder_toggle_mode() {
+        let mut recorder = Recorder::new();
+        recorder.prepare(44100.0, 64);
+
+        run(&mut recorder, 0.0, 0.0, 1.0, 1.0);
+        assert!(recorder.is_recording());
+        run(&mut recorder, 0.0, 0.0, 0.0, 1.0);
+        assert!(recorder.is_recording());
+        run(&mut recorder, 0.0, 0.0, 1.0, 1.0);
+        assert!(!recorder.is_recording());
+    }
+}
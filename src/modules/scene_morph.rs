@@ -0,0 +1,175 @@
+//! Scene Morph utility module.
+//!
+//! Provides the morph position between two parameter scenes, so a scene
+//! morph can be played from a knob or driven by any control signal.
+
+use crate::dsp::{
+    context::ProcessContext,
+    module_trait::{DspModule, ModuleCategory, ModuleInfo},
+    parameter::ParameterDefinition,
+    port::PortDefinition,
+    signal::SignalBuffer,
+    smoothed_value::SmoothedValue,
+    SignalType,
+};
+
+/// A morph position source for parameter scenes.
+///
+/// The module itself only computes the position; the application reads its
+/// output and morphs the patch between the two scenes chosen in the Scenes
+/// window. Its own parameter is never stored in or changed by a scene.
+///
+/// # Ports
+///
+/// **Inputs:**
+/// - **CV** (Control): Added to the Position knob.
+///
+/// **Outputs:**
+/// - **Out** (Control): Morph position, 0 (scene A) to 1 (scene B).
+///
+/// # Parameters
+///
+/// - **Position** (0 to 1): Morph position when no CV is connected.
+pub struct SceneMorph {
+    /// Port definitions.
+    ports: Vec<PortDefinition>,
+    /// Parameter definitions.
+    parameters: Vec<ParameterDefinition>,
+    /// Smoothed position parameter.
+    position_smooth: SmoothedValue,
+}
+
+impl SceneMorph {
+    /// Creates a new Scene Morph module.
+    pub fn new() -> Self {
+        Self {
+            ports: vec![
+                PortDefinition::input_with_default("cv", "CV", SignalType::Control, 0.0),
+                PortDefinition::output("out", "Out", SignalType::Control),
+            ],
+            parameters: vec![ParameterDefinition::normalized("position", "Position", 0.0).not_randomized()],
+            position_smooth: SmoothedValue::with_default_smoothing(0.0, 44100.0),
+        }
+    }
+
+    /// Port index constants.
+    const PORT_CV: usize = 0;
+    const PORT_OUT: usize = 0;
+
+    /// Parameter index constants.
+    const PARAM_POSITION: usize = 0;
+}
+
+impl Default for SceneMorph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DspModule for SceneMorph {
+    fn info(&self) -> &ModuleInfo {
+        static INFO: ModuleInfo = ModuleInfo {
+            id: "util.scene_morph",
+            name: "Scene Morph",
+            category: ModuleCategory::Utility,
+            description: "Morph position between two parameter scenes",
+        };
+        &INFO
+    }
+
+    fn ports(&self) -> &[PortDefinition] {
+        &self.ports
+    }
+
+    fn parameters(&self) -> &[ParameterDefinition] {
+        &self.parameters
+    }
+
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.position_smooth.set_sample_rate(sample_rate);
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&SignalBuffer],
+        outputs: &mut [SignalBuffer],
+        params: &[f32],
+        context: &ProcessContext,
+    ) {
+        self.position_smooth.set_target(params[Self::PARAM_POSITION]);
+
+        let cv = inputs.get(Self::PORT_CV);
+        let output = &mut outputs[Self::PORT_OUT];
+
+        for i in 0..context.block_size {
+            let position = self.position_smooth.next();
+            let cv_value = cv
+                .map(|buf| buf.samples.get(i).copied().unwrap_or(0.0))
+                .unwrap_or(0.0);
+
+            output.samples[i] = (position + cv_value).clamp(0.0, 1.0);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.position_smooth.reset(self.position_smooth.target());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(position: f32, cv: f32) -> f32 {
+        let mut morph = SceneMorph::new();
+        morph.prepare(44100.0, 256);
+
+        let mut input = SignalBuffer::control(256);
+        input.fill(cv);
+        let mut outputs = vec![SignalBuffer::control(256)];
+        let ctx = ProcessContext::new(44100.0, 256);
+
+        // Process multiple times to let parameter smoothing settle
+        for _ in 0..20 {
+            morph.process(&[&input], &mut outputs, &[position], &ctx);
+        }
+        outputs[0].samples[255]
+    }
+
+    #[test]
+    fn test_scene_morph_info() {
+        let morph = SceneMorph::new();
+        assert_eq!(morph.info().id, "util.scene_morph");
+        assert_eq!(morph.info().name, "Scene Morph");
+        assert_eq!(morph.info().category, ModuleCategory::Utility);
+    }
+
+    #[test]
+    fn test_scene_morph_ports_and_parameters() {
+        let morph = SceneMorph::new();
+        let ports = morph.ports();
+
+        assert_eq!(ports.len(), 2);
+        assert!(ports[0].is_input());
+        assert_eq!(ports[0].id, "cv");
+        assert!(ports[1].is_output());
+        assert_eq!(ports[1].signal_type, SignalType::Control);
+
+        let params = morph.parameters();
+        assert_eq!(params.len(), 1);
+        assert_eq!(params[0].id, "position");
+        assert!((params[0].default - 0.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_scene_morph_position() {
+        assert!((run(0.25, 0.0) - 0.25).abs() < 0.01);
+        assert!((run(0.25, 0.5) - 0.75).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_scene_morph_clamps() {
+        assert!((run(0.5, 1.0) - 1.0).abs() < f32::EPSILON);
+        assert!(run(0.0, -1.0).abs() < f32::EPSILON);
+    }
+}
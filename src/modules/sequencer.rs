@@ -3,13 +3,15 @@
 //! A 16-step sequencer with per-step pitch, gate, and velocity.
 //! Advances on clock input, outputs CV/Gate signals for driving oscillators and envelopes.
 
+use std::sync::Arc;
+
 use crate::dsp::{
     context::ProcessContext,
     module_trait::{DspModule, ModuleCategory, ModuleInfo},
     parameter::ParameterDefinition,
     port::PortDefinition,
     signal::SignalBuffer,
-    ParameterDisplay, SignalType,
+    ParameterDisplay, SignalType, Tuning,
 };
 
 /// Maximum number of steps in the sequencer.
@@ -84,12 +86,6 @@ impl SequenceDirection {
     }
 }
 
-/// Convert a MIDI note number (0-127) to V/Oct control signal.
-/// C4 (note 60) = 0V, each semitone = 1/12 V
-fn note_to_voct(note: u8) -> f32 {
-    (note as f32 - 60.0) / 12.0
-}
-
 /// Convert a note number to a note name for display.
 pub fn note_to_name(note: u8) -> String {
     const NOTES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
@@ -110,7 +106,7 @@ pub fn note_to_name(note: u8) -> String {
 /// - **Run** (Gate): Enables/disables sequencer advancement.
 ///
 /// **Outputs:**
-/// - **Pitch** (Control): V/Oct pitch CV from current step.
+/// - **Pitch** (Control): V/Oct pitch CV from current step, converted through the tuning.
 /// - **Gate** (Gate): Gate output for current step.
 /// - **Velocity** (Control): Velocity (0-1) from current step.
 /// - **Step** (Control): Current step as 0-1 value (for visualization).
@@ -145,6 +141,8 @@ pub struct StepSequencer {
     ports: Vec<PortDefinition>,
     /// Parameter definitions.
     parameters: Vec<ParameterDefinition>,
+    /// Tuning to convert step notes with.
+    tuning: Arc<Tuning>,
 }
 
 impl StepSequencer {
@@ -230,9 +228,16 @@ impl StepSequencer {
             sample_rate: 44100.0,
             ports,
             parameters,
+            tuning: Arc::new(Tuning::equal()),
         }
     }
 
+    /// Convert a MIDI note number (0-127) to V/Oct control signal.
+    /// In 12-TET, C4 (note 60) = 0V, each semitone = 1/12 V
+    fn note_to_voct(&self, note: u8) -> f32 {
+        self.tuning.voct(note as f32)
+    }
+
     /// Port index constants.
     const PORT_CLOCK: usize = 0;
     const PORT_RESET: usize = 1;
@@ -438,7 +443,7 @@ impl DspModule for StepSequencer {
             let step_velocity = params[Self::step_velocity_param(self.current_step)] / 127.0;
 
             // Generate outputs (access directly by index to avoid multiple mutable borrows)
-            outputs[Self::PORT_PITCH].samples[i] = note_to_voct(step_pitch);
+            outputs[Self::PORT_PITCH].samples[i] = self.note_to_voct(step_pitch);
 
             // Gate output: high if timer > 0 and step gate is enabled
             let gate_active = self.gate_timer > 0 && step_gate_enabled;
@@ -470,6 +475,10 @@ impl DspModule for StepSequencer {
         self.gate_timer = 0;
         self.eoc_timer = 0;
     }
+
+    fn set_tuning(&mut self, tuning: Arc<Tuning>) {
+        self.tuning = tuning;
+    }
 }
 
 #[cfg(test)]
@@ -543,12 +552,21 @@ mod tests {
 
     #[test]
     fn test_note_to_voct() {
+        let mut seq = StepSequencer::new();
         // C4 (60) = 0V
-        assert!((note_to_voct(60) - 0.0).abs() < 0.001);
+        assert!((seq.note_to_voct(60) - 0.0).abs() < 0.001);
         // C5 (72) = +1V
-        assert!((note_to_voct(72) - 1.0).abs() < 0.001);
+        assert!((seq.note_to_voct(72) - 1.0).abs() < 0.001);
         // C3 (48) = -1V
-        assert!((note_to_voct(48) - -1.0).abs() < 0.001);
+        assert!((seq.note_to_voct(48) - -1.0).abs() < 0.001);
+
+        // Through a tuning: only middle C and the D above it are tuned
+        let mut pitches = [None; 128];
+        pitches[60] = Some(0.0);
+        pitches[62] = Some(0.2);
+        seq.set_tuning(Arc::new(Tuning::from_pitches(&pitches)));
+        assert!((seq.note_to_voct(61) - 0.0).abs() < 0.001);
+        assert!((seq.note_to_voct(64) - 0.2).abs() < 0.001);
     }
 
     #[test]
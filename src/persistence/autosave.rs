@@ -0,0 +1,363 @@
+//! Autosave and crash recovery.
+//!
+//! While the app runs, a lock file marks the session as active and the
+//! current patch is periodically written to a recovery directory by a
+//! background thread, so the UI never waits on disk I/O. A clean shutdown
+//! removes both files. If the lock file is still present on the next start
+//! and the process that wrote it is gone, the previous session ended
+//! uncleanly and its recovered patch can be offered to the user. If that
+//! process is still running, another instance owns the session and the new
+//! one runs without autosave.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::patch::{Patch, PatchError};
+
+/// How often the current patch is autosaved while it has unsaved changes.
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// File whose presence marks a running (or crashed) session. Holds the
+/// process ID of its session.
+const LOCK_FILE: &str = "session.lock";
+
+/// File holding the most recent autosave.
+const RECOVERY_FILE: &str = "recovery.json";
+
+/// Temporary file used to make recovery writes atomic.
+const RECOVERY_TMP_FILE: &str = "recovery.json.tmp";
+
+/// Contents of the recovery file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryData {
+    /// Path the patch was last saved to or loaded from (None if never saved).
+    pub source_path: Option<PathBuf>,
+    /// The autosaved patch.
+    pub patch: Patch,
+}
+
+/// Tracks whether the patch has changed since it was last saved or autosaved.
+///
+/// Edits bump a revision counter; saving records the revision that was
+/// written. This is independent of any undo history.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DirtyTracker {
+    revision: u64,
+    saved_revision: u64,
+    autosaved_revision: u64,
+}
+
+impl DirtyTracker {
+    /// Create a tracker for an unmodified patch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an edit to the patch.
+    pub fn mark_dirty(&mut self) {
+        self.revision += 1;
+    }
+
+    /// Record that the patch was saved (or loaded) in its current state.
+    pub fn mark_saved(&mut self) {
+        self.saved_revision = self.revision;
+        self.autosaved_revision = self.revision;
+    }
+
+    /// Record that the current state was handed to the autosaver.
+    pub fn mark_autosaved(&mut self) {
+        self.autosaved_revision = self.revision;
+    }
+
+    /// Whether the patch has changes that were not saved by the user.
+    pub fn is_dirty(&self) -> bool {
+        self.revision != self.saved_revision
+    }
+
+    /// Whether the patch has changes that were not yet autosaved.
+    pub fn needs_autosave(&self) -> bool {
+        self.revision != self.autosaved_revision
+    }
+}
+
+/// Messages sent to the autosave writer thread.
+enum WriterMessage {
+    /// Write this data to the recovery file.
+    Write(Box<RecoveryData>),
+    /// Delete the recovery file (e.g. after a manual save).
+    Clear,
+}
+
+/// An active autosave session.
+///
+/// Dropping the session marks a clean shutdown: pending writes are flushed,
+/// then the lock and recovery files are removed, as long as the lock is
+/// still this session's. If the thread is panicking, the files are kept so
+/// the next start can offer recovery.
+pub struct AutosaveSession {
+    /// Recovery directory for this session.
+    dir: PathBuf,
+    /// Channel to the writer thread.
+    sender: Option<Sender<WriterMessage>>,
+    /// Background writer thread handle.
+    writer: Option<thread::JoinHandle<()>>,
+}
+
+impl AutosaveSession {
+    /// Start a session in the given recovery directory.
+    ///
+    /// Returns the session and, if the previous session did not shut down
+    /// cleanly, the patch it last autosaved. Fails if another running
+    /// instance holds the lock.
+    pub fn start(dir: impl Into<PathBuf>) -> Result<(Self, Option<RecoveryData>), PatchError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let lock_path = dir.join(LOCK_FILE);
+        let recovered = match read_lock(&dir) {
+            Some(pid) if pid != std::process::id() && process_alive(pid) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("another instance (process {}) is running", pid),
+                )
+                .into());
+            }
+            Some(_) => read_recovery(&dir),
+            // An unreadable lock is left over from a crash all the same
+            None if lock_path.exists() => read_recovery(&dir),
+            None => None,
+        };
+
+        // Anything left over from a clean exit is stale
+        if recovered.is_none() {
+            let _ = fs::remove_file(dir.join(RECOVERY_FILE));
+        }
+
+        fs::write(&lock_path, std::process::id().to_string())?;
+
+        let (sender, receiver) = mpsc::channel();
+        let writer_dir = dir.clone();
+        let writer = thread::Builder::new()
+            .name("autosave".to_string())
+            .spawn(move || run_writer(&writer_dir, receiver))?;
+
+        let session = Self {
+            dir,
+            sender: Some(sender),
+            writer: Some(writer),
+        };
+
+        Ok((session, recovered))
+    }
+
+    /// Queue a patch to be written to the recovery file.
+    ///
+    /// Returns immediately; serialization and disk I/O happen on the writer thread.
+    pub fn submit(&self, data: RecoveryData) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(WriterMessage::Write(Box::new(data)));
+        }
+    }
+
+    /// Queue removal of the recovery file.
+    pub fn clear(&self) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(WriterMessage::Clear);
+        }
+    }
+
+    /// The recovery directory used by this session.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for AutosaveSession {
+    fn drop(&mut self) {
+        // Closing the channel lets the writer finish pending work and exit
+        self.sender.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+
+        // Leave the files alone if another instance has taken over the lock
+        if !thread::panicking() && read_lock(&self.dir) == Some(std::process::id()) {
+            let _ = fs::remove_file(self.dir.join(RECOVERY_FILE));
+            let _ = fs::remove_file(self.dir.join(LOCK_FILE));
+        }
+    }
+}
+
+/// Writer thread loop.
+fn run_writer(dir: &Path, receiver: Receiver<WriterMessage>) {
+    while let Ok(mut message) = receiver.recv() {
+        // Coalesce a backlog so only the newest state hits the disk
+        while let Ok(next) = receiver.try_recv() {
+            message = next;
+        }
+
+        let result = match message {
+            WriterMessage::Write(data) => write_recovery(dir, &data),
+            WriterMessage::Clear => match fs::remove_file(dir.join(RECOVERY_FILE)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            },
+        };
+
+        if let Err(e) = result {
+            eprintln!("Autosave failed: {}", e);
+        }
+    }
+}
+
+/// Write recovery data atomically (write to a temp file, then rename).
+fn write_recovery(dir: &Path, data: &RecoveryData) -> Result<(), PatchError> {
+    let json = serde_json::to_string_pretty(data)?;
+    let tmp_path = dir.join(RECOVERY_TMP_FILE);
+    fs::write(&tmp_path, json)?;
+    fs::rename(&tmp_path, dir.join(RECOVERY_FILE))?;
+    Ok(())
+}
+
+/// Read the recovery file, ignoring missing, corrupt or incompatible data.
+fn read_recovery(dir: &Path) -> Option<RecoveryData> {
+    let json = fs::read_to_string(dir.join(RECOVERY_FILE)).ok()?;
+    let data: RecoveryData = serde_json::from_str(&json).ok()?;
+    data.patch.is_compatible().then_some(data)
+}
+
+/// Process ID recorded in the lock file, if there is a readable one.
+fn read_lock(dir: &Path) -> Option<u32> {
+    fs::read_to_string(dir.join(LOCK_FILE)).ok()?.trim().parse().ok()
+}
+
+/// Whether a process with this ID is running.
+///
+/// A lock left by a crashed session whose ID has since been reused looks
+/// alive; autosave then stays off until that process exits.
+#[cfg(target_os = "linux")]
+fn process_alive(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+/// Whether a process with this ID is running.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_alive(pid: u32) -> bool {
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Whether a process with this ID is running.
+#[cfg(windows)]
+fn process_alive(pid: u32) -> bool {
+    std::process::Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH", "/FO", "CSV"])
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains(&format!("\"{}\"", pid)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestDir;
+
+
+    #[test]
+    fn test_dirty_tracker() {
+        let mut tracker = DirtyTracker::new();
+        assert!(!tracker.is_dirty());
+        assert!(!tracker.needs_autosave());
+
+        tracker.mark_dirty();
+        assert!(tracker.is_dirty());
+        assert!(tracker.needs_autosave());
+
+        tracker.mark_autosaved();
+        assert!(tracker.is_dirty());
+        assert!(!tracker.needs_autosave());
+
+        tracker.mark_dirty();
+        tracker.mark_saved();
+        assert!(!tracker.is_dirty());
+        assert!(!tracker.needs_autosave());
+    }
+
+    #[test]
+    fn test_clean_shutdown_leaves_nothing_to_recover() {
+        let dir = TestDir::new("autosave_clean");
+
+        let (session, recovered) = AutosaveSession::start(dir.path()).unwrap();
+        assert!(recovered.is_none());
+        assert!(dir.join(LOCK_FILE).exists());
+
+        session.submit(RecoveryData {
+            source_path: None,
+            patch: Patch::new("Work in progress"),
+        });
+        drop(session);
+
+        assert!(!dir.join(LOCK_FILE).exists());
+        assert!(!dir.join(RECOVERY_FILE).exists());
+
+        let (_session, recovered) = AutosaveSession::start(dir.path()).unwrap();
+        assert!(recovered.is_none());
+    }
+
+    #[test]
+    fn test_unclean_shutdown_offers_recovery() {
+        let dir = TestDir::new("autosave_unclean");
+        fs::create_dir_all(dir.path()).unwrap();
+
+        // Simulate a crashed session: lock file and autosave left behind
+        fs::write(dir.join(LOCK_FILE), u32::MAX.to_string()).unwrap();
+        let data = RecoveryData {
+            source_path: Some(PathBuf::from("bass.json")),
+            patch: Patch::new("Crashed"),
+        };
+        write_recovery(dir.path(), &data).unwrap();
+
+        let (_session, recovered) = AutosaveSession::start(dir.path()).unwrap();
+        let recovered = recovered.expect("should recover patch");
+        assert_eq!(recovered.patch.name, "Crashed");
+        assert_eq!(recovered.source_path, Some(PathBuf::from("bass.json")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_running_instance_keeps_its_lock() {
+        let dir = TestDir::new("autosave_running");
+        fs::create_dir_all(dir.path()).unwrap();
+
+        // The parent process stands in for another running instance
+        let other = std::os::unix::process::parent_id().to_string();
+        fs::write(dir.join(LOCK_FILE), &other).unwrap();
+        write_recovery(dir.path(), &RecoveryData {
+            source_path: None,
+            patch: Patch::new("Other instance"),
+        })
+        .unwrap();
+
+        assert!(AutosaveSession::start(dir.path()).is_err());
+        assert_eq!(fs::read_to_string(dir.join(LOCK_FILE)).unwrap(), other);
+        assert!(dir.join(RECOVERY_FILE).exists());
+    }
+
+    #[test]
+    fn test_drop_leaves_a_lock_taken_over() {
+        let dir = TestDir::new("autosave_taken_over");
+
+        let (session, _) = AutosaveSession::start(dir.path()).unwrap();
+        fs::write(dir.join(LOCK_FILE), "1").unwrap();
+        drop(session);
+
+        assert_eq!(fs::read_to_string(dir.join(LOCK_FILE)).unwrap(), "1");
+    }
+}
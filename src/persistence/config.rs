@@ -0,0 +1,158 @@
+//! Application configuration.
+//!
+//! Remembers the audio and MIDI setup, the open setlist, the interface
+//! scale and the window geometry between sessions. Devices are stored by
+//! name, since their indices change as devices come and go; a device that
+//! has disappeared is simply not reselected.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::patch::PatchError;
+
+/// Smallest and largest interface scale.
+pub const UI_SCALE_RANGE: (f32, f32) = (0.5, 3.0);
+
+/// Persistent application configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    /// Name of the audio output device (None = the system default).
+    pub audio_output: Option<String>,
+    /// Name of the audio input device (None = no input).
+    pub audio_input: Option<String>,
+    /// Sample rate in Hz (None = the device default).
+    pub sample_rate: Option<u32>,
+    /// Buffer size in frames (None = the device default).
+    pub buffer_size: Option<u32>,
+    /// Device channels of each output routing source, as channel bitmasks
+    /// (None = the default routing).
+    pub output_routing: Option<Vec<u16>>,
+    /// Names of the connected MIDI input devices.
+    pub midi_inputs: Vec<String>,
+    /// Name of the published virtual MIDI input port (None = no virtual port).
+    pub virtual_midi_input: Option<String>,
+    /// Names of the connected MIDI output devices.
+    pub midi_outputs: Vec<String>,
+    /// Name of the published virtual MIDI output port (None = no virtual port).
+    pub virtual_midi_output: Option<String>,
+    /// Name of the MIDI output controller feedback is sent to (None = no feedback).
+    pub midi_feedback_output: Option<String>,
+    /// Setlist file opened for program changes (None = no setlist).
+    pub setlist: Option<PathBuf>,
+    /// Interface scale (1.0 = normal size).
+    pub ui_scale: f32,
+    /// Main window geometry (None = the default size).
+    pub window: Option<WindowState>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            audio_output: None,
+            audio_input: None,
+            sample_rate: None,
+            buffer_size: None,
+            output_routing: None,
+            midi_inputs: Vec::new(),
+            virtual_midi_input: None,
+            midi_outputs: Vec::new(),
+            virtual_midi_output: None,
+            midi_feedback_output: None,
+            setlist: None,
+            ui_scale: 1.0,
+            window: None,
+        }
+    }
+}
+
+/// Position and size of the main window, in points.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowState {
+    /// Inner width.
+    pub width: f32,
+    /// Inner height.
+    pub height: f32,
+    /// Outer position of the top-left corner, if the platform reports it.
+    pub position: Option<(f32, f32)>,
+    /// Whether the window was maximized.
+    pub maximized: bool,
+}
+
+impl AppConfig {
+    /// Load the configuration from `path`.
+    ///
+    /// A missing or unreadable file gives the default configuration, so a
+    /// broken config never stops the synth from starting.
+    pub fn load(path: &Path) -> Self {
+        let mut config: Self = fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        config.ui_scale = config.ui_scale.clamp(UI_SCALE_RANGE.0, UI_SCALE_RANGE.1);
+        config
+    }
+
+    /// Save the configuration to `path`, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), PatchError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestDir;
+
+    #[test]
+    fn test_config_round_trip() {
+        let dir = TestDir::new("config_round_trip");
+        let path = dir.join("config.json");
+        let config = AppConfig {
+            audio_output: Some("Interface".to_string()),
+            audio_input: Some("Interface".to_string()),
+            sample_rate: Some(96000),
+            buffer_size: Some(128),
+            output_routing: Some(vec![1, 2, 4]),
+            midi_inputs: vec!["Keys".to_string(), "Pads".to_string()],
+            virtual_midi_input: Some("Modular Synth In".to_string()),
+            midi_outputs: vec!["Hardware Synth".to_string()],
+            virtual_midi_output: Some("Modular Synth Out".to_string()),
+            midi_feedback_output: Some("Fader Box".to_string()),
+            setlist: Some(PathBuf::from("/setlists/gig.json")),
+            ui_scale: 1.25,
+            window: Some(WindowState {
+                width: 1600.0,
+                height: 900.0,
+                position: Some((40.0, 30.0)),
+                maximized: false,
+            }),
+        };
+        config.save(&path).unwrap();
+        assert_eq!(AppConfig::load(&path), config);
+    }
+
+    #[test]
+    fn test_missing_or_broken_config() {
+        let dir = TestDir::new("config_broken");
+        let path = dir.join("config.json");
+        assert_eq!(AppConfig::load(&path), AppConfig::default());
+
+        fs::create_dir_all(dir.path()).unwrap();
+        fs::write(&path, "{ not json").unwrap();
+        assert_eq!(AppConfig::load(&path), AppConfig::default());
+
+        // Unknown fields are ignored and missing ones take their defaults
+        fs::write(&path, r#"{"sample_rate": 48000, "ui_scale": 10.0, "future": true}"#).unwrap();
+        let config = AppConfig::load(&path);
+        assert_eq!(config.sample_rate, Some(48000));
+        assert_eq!(config.ui_scale, UI_SCALE_RANGE.1);
+        assert!(config.midi_inputs.is_empty());
+    }
+}
@@ -0,0 +1,317 @@
+//! Structural diff between two patches.
+//!
+//! Used by hot-reload to apply only what changed in a patch file to the
+//! running engine, so unchanged modules keep their internal state (phase,
+//! envelopes, delay lines) and audio never restarts.
+//!
+//! Nodes are matched by their patch ID. A node whose module type changed is
+//! treated as removed and re-added, along with every connection touching it.
+
+use std::collections::{HashMap, HashSet};
+
+use super::patch::{ConnectionData, MidiMapping, NodeData, ParameterValue, Patch, TuningData};
+use super::randomize::ParameterLock;
+use super::scene::Scene;
+
+/// A single changed parameter on a node present in both patches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterChange {
+    /// Patch ID of the node.
+    pub node_id: u64,
+    /// Parameter index within the node.
+    pub param_index: usize,
+    /// New parameter value.
+    pub value: ParameterValue,
+}
+
+/// The changes needed to turn one patch into another.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PatchDiff {
+    /// Patch IDs of nodes to remove.
+    pub removed_nodes: Vec<u64>,
+    /// Nodes to add.
+    pub added_nodes: Vec<NodeData>,
+    /// Parameter values that changed on kept nodes.
+    pub changed_parameters: Vec<ParameterChange>,
+    /// Kept nodes whose position changed, with their new position.
+    pub moved_nodes: Vec<(u64, (f32, f32))>,
+    /// Kept nodes whose own tuning changed, with their new tuning.
+    pub retuned_nodes: Vec<(u64, Option<TuningData>)>,
+    /// Kept nodes whose MIDI input device changed, with their new device.
+    pub rerouted_nodes: Vec<(u64, Option<String>)>,
+    /// Connections to remove.
+    pub removed_connections: Vec<ConnectionData>,
+    /// Connections to add.
+    pub added_connections: Vec<ConnectionData>,
+    /// New MIDI mappings, if they differ from the old patch.
+    pub midi_mappings: Option<Vec<MidiMapping>>,
+    /// New scenes, if they differ from the old patch.
+    pub scenes: Option<Vec<Scene>>,
+    /// New parameter locks, if they differ from the old patch.
+    pub locks: Option<Vec<ParameterLock>>,
+    /// New patch tuning, if it differs from the old patch.
+    pub tuning: Option<Option<TuningData>>,
+}
+
+impl PatchDiff {
+    /// Compute the diff that turns `old` into `new`.
+    pub fn between(old: &Patch, new: &Patch) -> Self {
+        let mut diff = Self::default();
+
+        let old_nodes: HashMap<u64, &NodeData> = old.nodes.iter().map(|n| (n.id, n)).collect();
+        let new_nodes: HashMap<u64, &NodeData> = new.nodes.iter().map(|n| (n.id, n)).collect();
+
+        // Nodes that are recreated; their connections must be recreated too
+        let mut replaced: HashSet<u64> = HashSet::new();
+
+        for old_node in &old.nodes {
+            match new_nodes.get(&old_node.id) {
+                None => diff.removed_nodes.push(old_node.id),
+                Some(new_node) if new_node.module_id != old_node.module_id => {
+                    diff.removed_nodes.push(old_node.id);
+                    replaced.insert(old_node.id);
+                }
+                Some(_) => {}
+            }
+        }
+
+        for new_node in &new.nodes {
+            match old_nodes.get(&new_node.id) {
+                Some(old_node) if old_node.module_id == new_node.module_id => {
+                    for (param_index, value) in new_node.parameters.iter().enumerate() {
+                        if old_node.parameters.get(param_index) != Some(value) {
+                            diff.changed_parameters.push(ParameterChange {
+                                node_id: new_node.id,
+                                param_index,
+                                value: value.clone(),
+                            });
+                        }
+                    }
+                    if old_node.position != new_node.position {
+                        diff.moved_nodes.push((new_node.id, new_node.position));
+                    }
+                    if old_node.tuning != new_node.tuning {
+                        diff.retuned_nodes.push((new_node.id, new_node.tuning.clone()));
+                    }
+                    if old_node.midi_input != new_node.midi_input {
+                        diff.rerouted_nodes.push((new_node.id, new_node.midi_input.clone()));
+                    }
+                }
+                _ => diff.added_nodes.push(new_node.clone()),
+            }
+        }
+
+        let touches_replaced =
+            |conn: &ConnectionData| replaced.contains(&conn.from_node) || replaced.contains(&conn.to_node);
+
+        // Connections to removed nodes disappear with the node; only list the rest
+        diff.removed_connections = old.connections
+            .iter()
+            .filter(|conn| new_nodes.contains_key(&conn.from_node) && new_nodes.contains_key(&conn.to_node))
+            .filter(|conn| !touches_replaced(conn) && !new.connections.contains(conn))
+            .cloned()
+            .collect();
+
+        diff.added_connections = new.connections
+            .iter()
+            .filter(|conn| touches_replaced(conn) || !old.connections.contains(conn))
+            .cloned()
+            .collect();
+
+        if old.midi_mappings != new.midi_mappings {
+            diff.midi_mappings = Some(new.midi_mappings.clone());
+        }
+
+        if old.scenes != new.scenes {
+            diff.scenes = Some(new.scenes.clone());
+        }
+
+        if old.locks != new.locks {
+            diff.locks = Some(new.locks.clone());
+        }
+
+        if old.tuning != new.tuning {
+            diff.tuning = Some(new.tuning.clone());
+        }
+
+        diff
+    }
+
+    /// Whether the two patches were identical.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::patch::{load_from_file, save_to_file};
+
+    fn base_patch() -> Patch {
+        let mut patch = Patch::new("Base");
+        let mut osc = NodeData::new(1, "osc.sine", (0.0, 0.0));
+        osc.parameters = vec![ParameterValue::Frequency(440.0), ParameterValue::Select(0)];
+        patch.nodes.push(osc);
+        patch.nodes.push(NodeData::new(2, "filter.svf", (200.0, 0.0)));
+        patch.nodes.push(NodeData::new(3, "output.audio", (400.0, 0.0)));
+        patch.connections.push(ConnectionData::new(1, "Out", 2, "In"));
+        patch.connections.push(ConnectionData::new(2, "LowPass", 3, "Left"));
+        patch
+    }
+
+    #[test]
+    fn test_identical_patches() {
+        let patch = base_patch();
+        assert!(PatchDiff::between(&patch, &patch).is_empty());
+    }
+
+    #[test]
+    fn test_parameter_and_position_changes() {
+        let old = base_patch();
+        let mut new = base_patch();
+        new.nodes[0].parameters[0] = ParameterValue::Frequency(220.0);
+        new.nodes[2].position = (500.0, 50.0);
+
+        let diff = PatchDiff::between(&old, &new);
+        assert_eq!(diff.changed_parameters, vec![ParameterChange {
+            node_id: 1,
+            param_index: 0,
+            value: ParameterValue::Frequency(220.0),
+        }]);
+        assert_eq!(diff.moved_nodes, vec![(3, (500.0, 50.0))]);
+        assert!(diff.added_nodes.is_empty());
+        assert!(diff.removed_nodes.is_empty());
+        assert!(diff.added_connections.is_empty());
+        assert!(diff.removed_connections.is_empty());
+    }
+
+    #[test]
+    fn test_added_and_removed_nodes() {
+        let old = base_patch();
+        let mut new = base_patch();
+        // Bypass the filter with a VCA
+        new.nodes.retain(|n| n.id != 2);
+        new.nodes.push(NodeData::new(4, "util.vca", (200.0, 0.0)));
+        new.connections = vec![
+            ConnectionData::new(1, "Out", 4, "In"),
+            ConnectionData::new(4, "Out", 3, "Left"),
+        ];
+
+        let diff = PatchDiff::between(&old, &new);
+        assert_eq!(diff.removed_nodes, vec![2]);
+        assert_eq!(diff.added_nodes.len(), 1);
+        assert_eq!(diff.added_nodes[0].id, 4);
+        // Connections to the removed filter go away with it
+        assert!(diff.removed_connections.is_empty());
+        assert_eq!(diff.added_connections.len(), 2);
+    }
+
+    #[test]
+    fn test_rewired_connection() {
+        let old = base_patch();
+        let mut new = base_patch();
+        new.connections[1] = ConnectionData::new(2, "HighPass", 3, "Left");
+
+        let diff = PatchDiff::between(&old, &new);
+        assert_eq!(diff.removed_connections, vec![ConnectionData::new(2, "LowPass", 3, "Left")]);
+        assert_eq!(diff.added_connections, vec![ConnectionData::new(2, "HighPass", 3, "Left")]);
+    }
+
+    #[test]
+    fn test_changed_module_type_recreates_node() {
+        let old = base_patch();
+        let mut new = base_patch();
+        new.nodes[1].module_id = "fx.distortion".to_string();
+        new.connections[1] = ConnectionData::new(2, "Out", 3, "Left");
+
+        let diff = PatchDiff::between(&old, &new);
+        assert_eq!(diff.removed_nodes, vec![2]);
+        assert_eq!(diff.added_nodes[0].module_id, "fx.distortion");
+        // Unchanged connection into the replaced node must be re-made
+        assert!(diff.added_connections.contains(&ConnectionData::new(1, "Out", 2, "In")));
+        assert!(diff.added_connections.contains(&ConnectionData::new(2, "Out", 3, "Left")));
+        assert!(diff.removed_connections.is_empty());
+    }
+
+    #[test]
+    fn test_midi_mapping_changes() {
+        let old = base_patch();
+        let mut new = base_patch();
+        new.midi_mappings.push(MidiMapping::new(74, 0, 2, 0, "Cutoff", 20.0, 20000.0));
+
+        let diff = PatchDiff::between(&old, &new);
+        assert_eq!(diff.midi_mappings.map(|m| m.len()), Some(1));
+    }
+
+    #[test]
+    fn test_scene_and_lock_changes() {
+        let old = base_patch();
+        let mut new = base_patch();
+        new.scenes.push(Scene::capture("Verse", &new, &[1]));
+        new.locks.push(ParameterLock::new(1, 0));
+
+        let diff = PatchDiff::between(&old, &new);
+        assert_eq!(diff.scenes.map(|s| s.len()), Some(1));
+        assert_eq!(diff.locks, Some(vec![ParameterLock::new(1, 0)]));
+        assert!(diff.changed_parameters.is_empty());
+    }
+
+    #[test]
+    fn test_save_and_reload_is_unchanged() {
+        // The running graph: engine IDs that differ from the file it was loaded
+        // from, plus a node added in the editor
+        let mut current = base_patch();
+        current.renumber(&[(1, 11), (2, 12), (3, 13)].into_iter().collect());
+        current.nodes.push(NodeData::new(14, "util.vca", (300.0, 100.0)));
+        current.midi_mappings.push(MidiMapping::new(74, 0, 12, 0, "Cutoff", 20.0, 20000.0));
+        current.scenes.push(Scene::capture("Verse", &current, &[11]));
+        current.locks.push(ParameterLock::new(11, 0));
+
+        let path = std::env::temp_dir().join(format!("diff_save_test_{}.json", std::process::id()));
+        save_to_file(&current, &path).unwrap();
+        let reloaded = load_from_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        // The file keeps the engine IDs, so after saving they are the file IDs
+        let file_ids: HashMap<u64, u64> = current.nodes.iter().map(|n| (n.id, n.id)).collect();
+        let mut snapshot = current.clone();
+        snapshot.renumber(&file_ids);
+        assert!(PatchDiff::between(&snapshot, &reloaded).is_empty());
+
+        // The IDs of the file loaded before would remove and re-add every node
+        let mut stale = current.clone();
+        stale.renumber(&[(11, 1), (12, 2), (13, 3), (14, u64::MAX)].into_iter().collect());
+        assert_eq!(PatchDiff::between(&stale, &reloaded).added_nodes.len(), 4);
+    }
+
+    #[test]
+    fn test_tuning_changes() {
+        let old = base_patch();
+        let mut new = base_patch();
+        let tuning = TuningData::new("19-TET", "19-TET\n1\n63.157894\n");
+        new.tuning = Some(tuning.clone());
+        new.nodes[0].tuning = Some(tuning.clone());
+
+        let diff = PatchDiff::between(&old, &new);
+        assert_eq!(diff.tuning, Some(Some(tuning.clone())));
+        assert_eq!(diff.retuned_nodes, vec![(1, Some(tuning))]);
+
+        // Back to 12-TET
+        let diff = PatchDiff::between(&new, &old);
+        assert_eq!(diff.tuning, Some(None));
+        assert_eq!(diff.retuned_nodes, vec![(1, None)]);
+    }
+
+    #[test]
+    fn test_midi_input_changes() {
+        let old = base_patch();
+        let mut new = base_patch();
+        new.nodes[0].midi_input = Some("Keys".to_string());
+
+        let diff = PatchDiff::between(&old, &new);
+        assert_eq!(diff.rerouted_nodes, vec![(1, Some("Keys".to_string()))]);
+        assert!(diff.added_nodes.is_empty());
+        assert_eq!(PatchDiff::between(&new, &old).rerouted_nodes, vec![(1, None)]);
+    }
+}
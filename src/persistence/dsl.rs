@@ -0,0 +1,1023 @@
+//! Text patch language.
+//!
+//! A compact, hand-writable alternative to patch JSON:
+//!
+//! ```text
+//! # A simple voice
+//! osc1 = osc.sine(frequency=220, waveform=Saw)
+//! vca1 = util.vca(level=0.8)
+//! out = output.audio()
+//!
+//! osc1.out -> vca1.in
+//! vca1.out -> out.left; vca1.out -> out.right
+//! ```
+//!
+//! Statements are separated by newlines or `;`, and `#` starts a comment.
+//! Nodes are declared as `name = module.id(param=value, ...)` using the
+//! module's parameter IDs. Values are numbers, `true`/`false`, or the label of
+//! a discrete choice (quoted if it isn't a plain word, e.g. `division="1/16"`).
+//! Connections are `node.port -> node.port` using port IDs.
+//!
+//! Everything is validated against the [`ModuleRegistry`], and node positions
+//! are laid out automatically from left to right along the signal flow. Node
+//! IDs are derived from the node names, so editing one line of a watched
+//! script leaves the other nodes alone.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::dsp::{ModuleRegistry, ParameterDefinition, ParameterDisplay, PortDefinition};
+use crate::graph::{NodeLayout, SynthNodeTemplate, SynthValueType};
+use super::patch::{ConnectionData, NodeData, ParameterValue, Patch, PatchError};
+
+/// File extension used for patch scripts.
+pub const DSL_EXTENSION: &str = "synth";
+
+/// Top-left position of the auto-layout.
+const LAYOUT_ORIGIN: (f32, f32) = (50.0, 50.0);
+
+/// Horizontal distance between auto-layout columns.
+const COLUMN_SPACING: f32 = 250.0;
+
+/// Vertical distance between nodes in an auto-layout column.
+const ROW_SPACING: f32 = 200.0;
+
+/// An error in a patch script, with the position it was found at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DslError {
+    /// Line number (1-based).
+    pub line: usize,
+    /// Column number (1-based, in characters).
+    pub column: usize,
+    /// Description of the problem.
+    pub message: String,
+}
+
+impl DslError {
+    fn new(pos: Pos, message: impl Into<String>) -> Self {
+        Self {
+            line: pos.line,
+            column: pos.column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for DslError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for DslError {}
+
+/// Node ID of a declared node, derived from its name.
+///
+/// Hot-reload matches nodes by ID, so IDs must not depend on declaration
+/// order: inserting a line would renumber, and so recreate, every node after
+/// it. FNV-1a keeps the IDs the same across builds, and they fit in 53 bits
+/// so tools that read JSON numbers as doubles keep them exact.
+pub fn node_id(name: &str) -> u64 {
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    hash & ((1 << 53) - 1)
+}
+
+/// Compile a patch script into a [`Patch`].
+///
+/// Node IDs come from [`node_id`]. The returned patch is named "Untitled";
+/// callers usually rename it after the file it came from.
+pub fn compile(source: &str, registry: &ModuleRegistry) -> Result<Patch, DslError> {
+    let tokens = tokenize(source)?;
+    let statements = Parser::new(tokens).parse()?;
+
+    let mut patch = Patch::default();
+    let mut nodes: HashMap<String, CompiledNode> = HashMap::new();
+    let mut names: HashMap<u64, &str> = HashMap::new();
+    let mut connections: Vec<&ConnectionDecl> = Vec::new();
+
+    for statement in &statements {
+        match statement {
+            Statement::Node(decl) => {
+                if nodes.contains_key(&decl.name) {
+                    return Err(DslError::new(
+                        decl.name_pos,
+                        format!("node '{}' is already declared", decl.name),
+                    ));
+                }
+
+                let id = node_id(&decl.name);
+                if let Some(other) = names.insert(id, &decl.name) {
+                    return Err(DslError::new(
+                        decl.name_pos,
+                        format!("node '{}' has the same ID as '{}'; rename one of them", decl.name, other),
+                    ));
+                }
+
+                let mut node = compile_node(decl, id, registry)?;
+                node.index = patch.nodes.len();
+                patch.nodes.push(node.data.clone());
+                nodes.insert(decl.name.clone(), node);
+            }
+            Statement::Connection(decl) => connections.push(decl),
+        }
+    }
+
+    // Connections are resolved after all nodes so they may refer to later declarations
+    let mut connected_inputs: HashMap<(u64, usize), Pos> = HashMap::new();
+    let mut edges: Vec<(usize, usize)> = Vec::new();
+
+    for decl in connections {
+        let from = lookup_node(&nodes, &decl.from)?;
+        let to = lookup_node(&nodes, &decl.to)?;
+
+        let (from_index, from_port) = find_port(&from.outputs, &decl.from.port)
+            .ok_or_else(|| port_error(from, &decl.from, true))?;
+        let (to_index, to_port) = find_port(&to.inputs, &decl.to.port)
+            .ok_or_else(|| port_error(to, &decl.to, false))?;
+
+        if !from_port.signal_type.can_connect_to(to_port.signal_type) {
+            return Err(DslError::new(
+                decl.arrow_pos,
+                format!(
+                    "cannot connect {} output '{}' to {} input '{}'",
+                    from_port.signal_type.name(),
+                    from_port.id,
+                    to_port.signal_type.name(),
+                    to_port.id
+                ),
+            ));
+        }
+
+        if let Some(previous) = connected_inputs.insert((to.data.id, to_index), decl.to.node_pos) {
+            return Err(DslError::new(
+                decl.to.port_pos,
+                format!(
+                    "input '{}.{}' is already connected (line {})",
+                    decl.to.node, to_port.id, previous.line
+                ),
+            ));
+        }
+
+        let from_name = from.layout.outputs.get(from_index).ok_or_else(|| {
+            DslError::new(decl.from.port_pos, format!("output '{}' is not available in the editor", from_port.id))
+        })?;
+        let to_name = to.layout.inputs.get(to_index).ok_or_else(|| {
+            DslError::new(decl.to.port_pos, format!("input '{}' is not available in the editor", to_port.id))
+        })?;
+
+        patch.connections.push(ConnectionData::new(from.data.id, from_name.clone(), to.data.id, to_name.clone()));
+        edges.push((from.index, to.index));
+    }
+
+    let positions = auto_layout(patch.nodes.len(), &edges);
+    for (node, position) in patch.nodes.iter_mut().zip(positions) {
+        node.position = position;
+    }
+
+    Ok(patch)
+}
+
+/// Convert a patch into script source.
+///
+/// Node names are derived from module IDs (`osc.sine` becomes `sine1`), and
+/// parameters are only written where they differ from the module default.
+/// Positions, MIDI mappings, scenes and locks are not part of the language and are dropped.
+pub fn export(patch: &Patch, registry: &ModuleRegistry) -> Result<String, PatchError> {
+    let mut out = format!("# {}\n", patch.name);
+    let mut names: HashMap<u64, String> = HashMap::new();
+    let mut modules: HashMap<u64, ModuleDescription> = HashMap::new();
+
+    for (node, name) in patch.nodes.iter().zip(export_names(patch)) {
+        let description = describe_module(&node.module_id, registry)
+            .ok_or_else(|| PatchError::UnknownModule(node.module_id.clone()))?;
+
+        let mut args: Vec<String> = Vec::new();
+        for (index, value) in node.parameters.iter().enumerate() {
+            let (Some(definition), Some((_, default))) =
+                (description.parameters.get(index), description.layout.parameters.get(index))
+            else {
+                continue;
+            };
+
+            let value = value.as_f32();
+            let default = default.actual_value();
+            if (value - default).abs() <= f32::EPSILON * default.abs().max(1.0) {
+                continue;
+            }
+            args.push(format!("{}={}", definition.id, format_value(definition, value)));
+        }
+
+        out.push_str(&format!("{} = {}({})\n", name, node.module_id, args.join(", ")));
+        names.insert(node.id, name);
+        modules.insert(node.id, description);
+    }
+
+    if !patch.connections.is_empty() {
+        out.push('\n');
+    }
+
+    for conn in &patch.connections {
+        let resolved = (|| {
+            let from_module = modules.get(&conn.from_node)?;
+            let to_module = modules.get(&conn.to_node)?;
+            let from_index = from_module.layout.outputs.iter().position(|name| *name == conn.from_port)?;
+            let to_index = to_module.layout.inputs.iter().position(|name| *name == conn.to_port)?;
+            Some(format!(
+                "{}.{} -> {}.{}\n",
+                names.get(&conn.from_node)?,
+                from_module.outputs.get(from_index)?.id,
+                names.get(&conn.to_node)?,
+                to_module.inputs.get(to_index)?.id
+            ))
+        })();
+
+        match resolved {
+            Some(line) => out.push_str(&line),
+            None => out.push_str(&format!(
+                "# unresolved connection: node {} '{}' -> node {} '{}'\n",
+                conn.from_node, conn.from_port, conn.to_node, conn.to_port
+            )),
+        }
+    }
+
+    Ok(out)
+}
+
+/// The IDs the nodes of `patch` get when its [`export`] is compiled again,
+/// keyed by patch node ID.
+pub fn script_node_ids(patch: &Patch) -> HashMap<u64, u64> {
+    patch.nodes
+        .iter()
+        .zip(export_names(patch))
+        .map(|(node, name)| (node.id, node_id(&name)))
+        .collect()
+}
+
+/// Names of the nodes of `patch` in an export, in node order.
+fn export_names(patch: &Patch) -> Vec<String> {
+    let mut counters: HashMap<String, usize> = HashMap::new();
+    patch.nodes
+        .iter()
+        .map(|node| {
+            let base = node_name_base(&node.module_id);
+            let counter = counters.entry(base.clone()).or_insert(0);
+            *counter += 1;
+            format!("{}{}", base, counter)
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Compilation
+// ---------------------------------------------------------------------------
+
+/// DSP and graph descriptions of a module, as needed by the compiler.
+struct ModuleDescription {
+    inputs: Vec<PortDefinition>,
+    outputs: Vec<PortDefinition>,
+    parameters: Vec<ParameterDefinition>,
+    layout: NodeLayout,
+}
+
+/// A declared node with everything needed to resolve its connections.
+struct CompiledNode {
+    data: NodeData,
+    /// Position in declaration order.
+    index: usize,
+    module_id: String,
+    inputs: Vec<PortDefinition>,
+    outputs: Vec<PortDefinition>,
+    layout: NodeLayout,
+}
+
+fn describe_module(module_id: &str, registry: &ModuleRegistry) -> Option<ModuleDescription> {
+    let module = registry.create(module_id)?;
+    let template = SynthNodeTemplate::from_module_id(module_id)?;
+
+    Some(ModuleDescription {
+        inputs: module.ports().iter().filter(|p| p.is_input()).cloned().collect(),
+        outputs: module.ports().iter().filter(|p| p.is_output()).cloned().collect(),
+        parameters: module.parameters().to_vec(),
+        layout: template.layout(),
+    })
+}
+
+fn compile_node(decl: &NodeDecl, id: u64, registry: &ModuleRegistry) -> Result<CompiledNode, DslError> {
+    let description = describe_module(&decl.module, registry).ok_or_else(|| {
+        DslError::new(decl.module_pos, format!("unknown module '{}'", decl.module))
+    })?;
+
+    let mut data = NodeData::new(id, decl.module.clone(), (0.0, 0.0));
+    data.parameters = description.layout.parameters
+        .iter()
+        .map(|(_, default)| parameter_value(default, default.actual_value()))
+        .collect();
+
+    let mut assigned: HashMap<usize, Pos> = HashMap::new();
+    for arg in &decl.args {
+        let index = description.parameters
+            .iter()
+            .position(|p| p.id == arg.name)
+            .or_else(|| description.parameters.iter().position(|p| p.name.eq_ignore_ascii_case(&arg.name)))
+            .ok_or_else(|| {
+                let known: Vec<&str> = description.parameters.iter().map(|p| p.id).collect();
+                DslError::new(
+                    arg.name_pos,
+                    format!(
+                        "unknown parameter '{}' for {}; expected one of: {}",
+                        arg.name,
+                        decl.module,
+                        known.join(", ")
+                    ),
+                )
+            })?;
+
+        if let Some(previous) = assigned.insert(index, arg.name_pos) {
+            return Err(DslError::new(
+                arg.name_pos,
+                format!("parameter '{}' is already set (column {})", arg.name, previous.column),
+            ));
+        }
+
+        let definition = &description.parameters[index];
+        let value = resolve_value(definition, &arg.value)
+            .map_err(|message| DslError::new(arg.value_pos, message))?;
+
+        let slot = data.parameters.get_mut(index).ok_or_else(|| {
+            DslError::new(arg.name_pos, format!("parameter '{}' is not available in the editor", definition.id))
+        })?;
+        let kind = &description.layout.parameters[index].1;
+        *slot = parameter_value(kind, value);
+    }
+
+    Ok(CompiledNode {
+        data,
+        index: 0,
+        module_id: decl.module.clone(),
+        inputs: description.inputs,
+        outputs: description.outputs,
+        layout: description.layout,
+    })
+}
+
+/// Turn a script value into the parameter's engine value, validating it.
+fn resolve_value(definition: &ParameterDefinition, value: &Value) -> Result<f32, String> {
+    match (definition.display, value) {
+        (ParameterDisplay::Toggle { off_label, on_label }, Value::Word(word)) => {
+            if word.eq_ignore_ascii_case("true") || word.eq_ignore_ascii_case(on_label) {
+                Ok(1.0)
+            } else if word.eq_ignore_ascii_case("false") || word.eq_ignore_ascii_case(off_label) {
+                Ok(0.0)
+            } else {
+                Err(format!("expected true or false for '{}', found '{}'", definition.id, word))
+            }
+        }
+        (ParameterDisplay::Discrete { labels }, Value::Word(word)) => labels
+            .iter()
+            .position(|label| label.eq_ignore_ascii_case(word))
+            .map(|index| index as f32)
+            .ok_or_else(|| {
+                format!(
+                    "unknown choice '{}' for '{}'; expected one of: {}",
+                    word,
+                    definition.id,
+                    labels.join(", ")
+                )
+            }),
+        (_, Value::Word(word)) => {
+            Err(format!("expected a number for '{}', found '{}'", definition.id, word))
+        }
+        (_, Value::Number(number)) => {
+            if *number < definition.min || *number > definition.max {
+                Err(format!(
+                    "value {} for '{}' is out of range ({} to {})",
+                    number, definition.id, definition.min, definition.max
+                ))
+            } else {
+                Ok(*number)
+            }
+        }
+    }
+}
+
+/// Store a value with the same type the editor uses for this parameter.
+fn parameter_value(kind: &SynthValueType, value: f32) -> ParameterValue {
+    match kind {
+        SynthValueType::Scalar { .. } => ParameterValue::Scalar(value),
+        SynthValueType::Frequency { .. } => ParameterValue::Frequency(value),
+        SynthValueType::LinearHz { .. } => ParameterValue::LinearHz(value),
+        SynthValueType::Time { .. } => ParameterValue::Time(value),
+        SynthValueType::LinearRange { .. } => ParameterValue::LinearRange(value),
+        SynthValueType::Toggle { .. } => ParameterValue::Toggle(value >= 0.5),
+        SynthValueType::Select { .. } => ParameterValue::Select(value.round().max(0.0) as usize),
+    }
+}
+
+fn lookup_node<'a>(
+    nodes: &'a HashMap<String, CompiledNode>,
+    port: &PortRef,
+) -> Result<&'a CompiledNode, DslError> {
+    nodes
+        .get(&port.node)
+        .ok_or_else(|| DslError::new(port.node_pos, format!("unknown node '{}'", port.node)))
+}
+
+/// Find a port by ID, or by display name ignoring case.
+fn find_port<'a>(ports: &'a [PortDefinition], name: &str) -> Option<(usize, &'a PortDefinition)> {
+    ports
+        .iter()
+        .position(|p| p.id == name)
+        .or_else(|| ports.iter().position(|p| p.name.eq_ignore_ascii_case(name)))
+        .map(|index| (index, &ports[index]))
+}
+
+fn port_error(node: &CompiledNode, port: &PortRef, want_output: bool) -> DslError {
+    let (wanted, other, candidates, others) = if want_output {
+        ("output", "input", &node.outputs, &node.inputs)
+    } else {
+        ("input", "output", &node.inputs, &node.outputs)
+    };
+
+    let message = if find_port(others, &port.port).is_some() {
+        format!("'{}' is an {} of '{}', not an {}", port.port, other, port.node, wanted)
+    } else {
+        let known: Vec<&str> = candidates.iter().map(|p| p.id).collect();
+        format!(
+            "unknown {} '{}' on '{}' ({}); expected one of: {}",
+            wanted,
+            port.port,
+            port.node,
+            node.module_id,
+            if known.is_empty() { "(none)".to_string() } else { known.join(", ") }
+        )
+    };
+
+    DslError::new(port.port_pos, message)
+}
+
+/// Place nodes in columns by their distance from the signal sources.
+///
+/// Each node goes one column to the right of its furthest upstream node, so
+/// signal flows left to right. Feedback loops are cut off after a bounded
+/// number of passes.
+fn auto_layout(count: usize, edges: &[(usize, usize)]) -> Vec<(f32, f32)> {
+    let mut columns = vec![0usize; count];
+
+    for _ in 0..count {
+        let mut changed = false;
+        for &(from, to) in edges {
+            let column = columns[from] + 1;
+            if column > columns[to] && column < count {
+                columns[to] = column;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut rows: HashMap<usize, usize> = HashMap::new();
+    columns
+        .iter()
+        .map(|&column| {
+            let row = rows.entry(column).or_insert(0);
+            let position = (
+                LAYOUT_ORIGIN.0 + column as f32 * COLUMN_SPACING,
+                LAYOUT_ORIGIN.1 + *row as f32 * ROW_SPACING,
+            );
+            *row += 1;
+            position
+        })
+        .collect()
+}
+
+/// Base name for exported nodes: the last segment of the module ID.
+fn node_name_base(module_id: &str) -> String {
+    let base: String = module_id
+        .rsplit('.')
+        .next()
+        .unwrap_or(module_id)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    if base.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        base
+    } else {
+        format!("node_{}", base)
+    }
+}
+
+/// Format a parameter value the way the compiler reads it back.
+fn format_value(definition: &ParameterDefinition, value: f32) -> String {
+    match definition.display {
+        ParameterDisplay::Toggle { .. } => (value >= 0.5).to_string(),
+        ParameterDisplay::Discrete { labels } => {
+            match labels.get(value.round().max(0.0) as usize) {
+                Some(label) if is_word(label) => label.to_string(),
+                Some(label) => format!("\"{}\"", label),
+                None => value.to_string(),
+            }
+        }
+        _ => value.to_string(),
+    }
+}
+
+fn is_word(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// ---------------------------------------------------------------------------
+// Lexer
+// ---------------------------------------------------------------------------
+
+/// A position in the source text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Pos {
+    line: usize,
+    column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Word(String),
+    Number(f32),
+    Str(String),
+    Equals,
+    Dot,
+    Comma,
+    LParen,
+    RParen,
+    Arrow,
+    Separator,
+    Eof,
+}
+
+impl TokenKind {
+    fn describe(&self) -> String {
+        match self {
+            Self::Word(word) => format!("'{}'", word),
+            Self::Number(number) => format!("number {}", number),
+            Self::Str(text) => format!("string \"{}\"", text),
+            Self::Equals => "'='".to_string(),
+            Self::Dot => "'.'".to_string(),
+            Self::Comma => "','".to_string(),
+            Self::LParen => "'('".to_string(),
+            Self::RParen => "')'".to_string(),
+            Self::Arrow => "'->'".to_string(),
+            Self::Separator => "end of statement".to_string(),
+            Self::Eof => "end of input".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    pos: Pos,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, DslError> {
+    let mut tokens = Vec::new();
+
+    for (line_index, line) in source.lines().enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            let pos = Pos { line: line_index + 1, column: i + 1 };
+
+            let kind = match c {
+                '#' => break,
+                c if c.is_whitespace() => {
+                    i += 1;
+                    continue;
+                }
+                '=' => TokenKind::Equals,
+                '.' if !chars.get(i + 1).is_some_and(|n| n.is_ascii_digit()) => TokenKind::Dot,
+                ',' => TokenKind::Comma,
+                '(' => TokenKind::LParen,
+                ')' => TokenKind::RParen,
+                ';' => TokenKind::Separator,
+                '-' if chars.get(i + 1) == Some(&'>') => {
+                    i += 1;
+                    TokenKind::Arrow
+                }
+                '"' => {
+                    let start = i + 1;
+                    let end = chars[start..]
+                        .iter()
+                        .position(|&c| c == '"')
+                        .map(|offset| start + offset)
+                        .ok_or_else(|| DslError::new(pos, "unterminated string"))?;
+                    i = end;
+                    TokenKind::Str(chars[start..end].iter().collect())
+                }
+                c if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                    let start = i;
+                    let mut end = i + 1;
+                    while end < chars.len() {
+                        let n = chars[end];
+                        let exponent_sign = (n == '-' || n == '+') && matches!(chars[end - 1], 'e' | 'E');
+                        if n.is_ascii_digit() || n == '.' || n == 'e' || n == 'E' || exponent_sign {
+                            end += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    let text: String = chars[start..end].iter().collect();
+                    let number = text
+                        .parse::<f32>()
+                        .map_err(|_| DslError::new(pos, format!("invalid number '{}'", text)))?;
+                    i = end - 1;
+                    TokenKind::Number(number)
+                }
+                c if c.is_ascii_alphabetic() || c == '_' => {
+                    let start = i;
+                    let mut end = i + 1;
+                    while end < chars.len() && (chars[end].is_ascii_alphanumeric() || chars[end] == '_') {
+                        end += 1;
+                    }
+                    i = end - 1;
+                    TokenKind::Word(chars[start..end].iter().collect())
+                }
+                other => return Err(DslError::new(pos, format!("unexpected character '{}'", other))),
+            };
+
+            tokens.push(Token { kind, pos });
+            i += 1;
+        }
+
+        tokens.push(Token {
+            kind: TokenKind::Separator,
+            pos: Pos { line: line_index + 1, column: chars.len() + 1 },
+        });
+    }
+
+    let eof_pos = tokens.last().map_or(Pos { line: 1, column: 1 }, |t| t.pos);
+    tokens.push(Token { kind: TokenKind::Eof, pos: eof_pos });
+
+    Ok(tokens)
+}
+
+// ---------------------------------------------------------------------------
+// Parser
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+enum Value {
+    Number(f32),
+    Word(String),
+}
+
+#[derive(Debug)]
+struct Arg {
+    name: String,
+    name_pos: Pos,
+    value: Value,
+    value_pos: Pos,
+}
+
+#[derive(Debug)]
+struct NodeDecl {
+    name: String,
+    name_pos: Pos,
+    module: String,
+    module_pos: Pos,
+    args: Vec<Arg>,
+}
+
+#[derive(Debug)]
+struct PortRef {
+    node: String,
+    node_pos: Pos,
+    port: String,
+    port_pos: Pos,
+}
+
+#[derive(Debug)]
+struct ConnectionDecl {
+    from: PortRef,
+    arrow_pos: Pos,
+    to: PortRef,
+}
+
+#[derive(Debug)]
+enum Statement {
+    Node(NodeDecl),
+    Connection(ConnectionDecl),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self { tokens, position: 0 }
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position.min(self.tokens.len() - 1)]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.peek().clone();
+        if self.position < self.tokens.len() - 1 {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn unexpected(token: &Token, expected: &str) -> DslError {
+        DslError::new(token.pos, format!("expected {}, found {}", expected, token.kind.describe()))
+    }
+
+    fn expect(&mut self, kind: TokenKind, expected: &str) -> Result<Token, DslError> {
+        let token = self.next();
+        if token.kind == kind {
+            Ok(token)
+        } else {
+            Err(Self::unexpected(&token, expected))
+        }
+    }
+
+    fn word(&mut self, expected: &str) -> Result<(String, Pos), DslError> {
+        let token = self.next();
+        match token.kind {
+            TokenKind::Word(word) => Ok((word, token.pos)),
+            _ => Err(Self::unexpected(&token, expected)),
+        }
+    }
+
+    fn parse(mut self) -> Result<Vec<Statement>, DslError> {
+        let mut statements = Vec::new();
+
+        loop {
+            match self.peek().kind {
+                TokenKind::Eof => break,
+                TokenKind::Separator => {
+                    self.next();
+                }
+                _ => {
+                    statements.push(self.statement()?);
+                    let token = self.next();
+                    if !matches!(token.kind, TokenKind::Separator | TokenKind::Eof) {
+                        return Err(Self::unexpected(&token, "end of statement"));
+                    }
+                }
+            }
+        }
+
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> Result<Statement, DslError> {
+        let (name, name_pos) = self.word("a node name")?;
+
+        let token = self.next();
+        match token.kind {
+            TokenKind::Equals => self.node_decl(name, name_pos).map(Statement::Node),
+            TokenKind::Dot => {
+                let (port, port_pos) = self.word("a port name")?;
+                let from = PortRef { node: name, node_pos: name_pos, port, port_pos };
+                let arrow = self.expect(TokenKind::Arrow, "'->'")?;
+                let (node, node_pos) = self.word("a node name")?;
+                self.expect(TokenKind::Dot, "'.'")?;
+                let (port, port_pos) = self.word("a port name")?;
+                Ok(Statement::Connection(ConnectionDecl {
+                    from,
+                    arrow_pos: arrow.pos,
+                    to: PortRef { node, node_pos, port, port_pos },
+                }))
+            }
+            _ => Err(Self::unexpected(&token, "'=' or '.'")),
+        }
+    }
+
+    fn node_decl(&mut self, name: String, name_pos: Pos) -> Result<NodeDecl, DslError> {
+        // Module IDs are dotted words, e.g. osc.sine
+        let (mut module, module_pos) = self.word("a module ID")?;
+        while self.peek().kind == TokenKind::Dot {
+            self.next();
+            let (segment, _) = self.word("a module ID")?;
+            module.push('.');
+            module.push_str(&segment);
+        }
+
+        self.expect(TokenKind::LParen, "'('")?;
+        let mut args = Vec::new();
+
+        if self.peek().kind == TokenKind::RParen {
+            self.next();
+        } else {
+            loop {
+                let (arg_name, arg_pos) = self.word("a parameter name")?;
+                self.expect(TokenKind::Equals, "'='")?;
+                let token = self.next();
+                let value = match token.kind {
+                    TokenKind::Number(number) => Value::Number(number),
+                    TokenKind::Word(word) | TokenKind::Str(word) => Value::Word(word),
+                    _ => return Err(Self::unexpected(&token, "a value")),
+                };
+                args.push(Arg { name: arg_name, name_pos: arg_pos, value, value_pos: token.pos });
+
+                let token = self.next();
+                match token.kind {
+                    TokenKind::Comma => continue,
+                    TokenKind::RParen => break,
+                    _ => return Err(Self::unexpected(&token, "',' or ')'")),
+                }
+            }
+        }
+
+        Ok(NodeDecl { name, name_pos, module, module_pos, args })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::create_module_registry;
+    use crate::persistence::PatchDiff;
+
+    const VOICE: &str = "\
+# A simple voice
+osc1 = osc.sine(frequency=220, waveform=Saw)
+vca1 = util.vca(level=0.8)
+out = output.audio(limiter=false)
+
+osc1.out -> vca1.in
+vca1.out -> out.left; vca1.out -> out.right
+";
+
+    #[test]
+    fn test_compile_voice() {
+        let patch = compile(VOICE, &create_module_registry()).unwrap();
+
+        assert_eq!(patch.nodes.len(), 3);
+        assert_eq!(patch.nodes[0].module_id, "osc.sine");
+        assert_eq!(patch.nodes[0].parameters[0], ParameterValue::Frequency(220.0));
+        assert_eq!(patch.nodes[0].parameters[2], ParameterValue::Select(1));
+        assert_eq!(patch.nodes[2].parameters[1], ParameterValue::Toggle(false));
+
+        assert_eq!(patch.connections.len(), 3);
+        let (osc, vca, out) = (node_id("osc1"), node_id("vca1"), node_id("out"));
+        assert_eq!(patch.nodes[0].id, osc);
+        assert_eq!(patch.connections[0], ConnectionData::new(osc, "Out", vca, "In"));
+        assert_eq!(patch.connections[1], ConnectionData::new(vca, "Out", out, "Left"));
+    }
+
+    #[test]
+    fn test_node_ids_follow_names() {
+        let registry = create_module_registry();
+        let patch = compile(VOICE, &registry).unwrap();
+
+        // Inserting a node keeps the IDs of the nodes declared after it
+        let edited = VOICE.replace("vca1 = util.vca", "lfo1 = mod.lfo()\nvca1 = util.vca");
+        let edited = compile(&edited, &registry).unwrap();
+        assert_eq!(edited.nodes.len(), 4);
+        for node in &patch.nodes {
+            assert!(edited.nodes.iter().any(|n| n.id == node.id && n.module_id == node.module_id));
+        }
+        assert!(PatchDiff::between(&patch, &edited).removed_nodes.is_empty());
+
+        assert_ne!(node_id("osc1"), node_id("osc2"));
+        assert!(node_id("osc1") < 1 << 53);
+    }
+
+    #[test]
+    fn test_unset_parameters_use_defaults() {
+        let patch = compile("osc1 = osc.sine()", &create_module_registry()).unwrap();
+        assert_eq!(patch.nodes[0].parameters.len(), 4);
+        assert_eq!(patch.nodes[0].parameters[0], ParameterValue::Frequency(440.0));
+    }
+
+    #[test]
+    fn test_auto_layout_follows_signal_flow() {
+        let patch = compile(VOICE, &create_module_registry()).unwrap();
+        let x: Vec<f32> = patch.nodes.iter().map(|n| n.position.0).collect();
+        assert!(x[0] < x[1]);
+        assert!(x[1] < x[2]);
+    }
+
+    #[test]
+    fn test_graph_port_names() {
+        // The delay's "time_cv" port is called "Time" in the editor
+        let source = "lfo1 = mod.lfo()\ndelay1 = fx.delay()\nlfo1.out -> delay1.time_cv";
+        let patch = compile(source, &create_module_registry()).unwrap();
+        assert_eq!(patch.connections[0].to_port, "Time");
+    }
+
+    #[test]
+    fn test_forward_references() {
+        let source = "a.out -> b.in\na = osc.sine()\nb = util.vca()";
+        assert!(compile(source, &create_module_registry()).is_ok());
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let registry = create_module_registry();
+
+        let err = compile("osc1 = osc.sine()\nvca1 = util.vcaa()", &registry).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 8));
+        assert!(err.message.contains("unknown module"));
+
+        let err = compile("osc1 = osc.sine(frequency=5)", &registry).unwrap_err();
+        assert_eq!((err.line, err.column), (1, 27));
+        assert!(err.message.contains("out of range"));
+
+        let err = compile("osc1 = osc.sine(pitch=5)", &registry).unwrap_err();
+        assert_eq!((err.line, err.column), (1, 17));
+        assert!(err.message.contains("unknown parameter"));
+
+        let err = compile("osc1 = osc.sine(waveform=Noise)", &registry).unwrap_err();
+        assert!(err.message.contains("Sine, Saw"));
+
+        let err = compile("osc1 = osc.sine(\n", &registry).unwrap_err();
+        assert_eq!(err.line, 1);
+        assert!(err.message.contains("expected a parameter name"));
+    }
+
+    #[test]
+    fn test_connection_errors() {
+        let registry = create_module_registry();
+        let nodes = "osc1 = osc.sine()\nvca1 = util.vca()\nenv1 = mod.adsr()\n";
+
+        let err = compile(&format!("{}osc1.out -> vca2.in", nodes), &registry).unwrap_err();
+        assert_eq!((err.line, err.column), (4, 13));
+        assert!(err.message.contains("unknown node 'vca2'"));
+
+        let err = compile(&format!("{}vca1.in -> osc1.fm", nodes), &registry).unwrap_err();
+        assert!(err.message.contains("is an input"));
+
+        let err = compile(&format!("{}osc1.out -> env1.gate", nodes), &registry).unwrap_err();
+        assert!(err.message.contains("cannot connect"));
+
+        let err = compile(&format!("{}osc1.out -> vca1.in\nosc1.out -> vca1.in", nodes), &registry)
+            .unwrap_err();
+        assert!(err.message.contains("already connected"));
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let registry = create_module_registry();
+        let patch = compile(VOICE, &registry).unwrap();
+
+        let source = export(&patch, &registry).unwrap();
+        assert!(source.contains("sine1 = osc.sine(frequency=220, waveform=Saw)"));
+        assert!(source.contains("sine1.out -> vca1.in"));
+
+        let reloaded = compile(&source, &registry).unwrap();
+        assert_eq!(reloaded.nodes.len(), patch.nodes.len());
+        for (a, b) in reloaded.nodes.iter().zip(&patch.nodes) {
+            assert_eq!(a.module_id, b.module_id);
+            assert_eq!(a.parameters, b.parameters);
+        }
+        // Exported nodes are renamed, so compare connections by position
+        fn ends(p: &Patch) -> Vec<(Option<usize>, &str, Option<usize>, &str)> {
+            let index = |id: u64| p.nodes.iter().position(|n| n.id == id);
+            p.connections
+                .iter()
+                .map(|c| (index(c.from_node), c.from_port.as_str(), index(c.to_node), c.to_port.as_str()))
+                .collect()
+        }
+        assert_eq!(ends(&reloaded), ends(&patch));
+    }
+
+    #[test]
+    fn test_script_node_ids() {
+        let registry = create_module_registry();
+        let mut patch = compile(VOICE, &registry).unwrap();
+        patch.renumber(&patch.nodes.iter().map(|n| (n.id, n.id + 100)).collect());
+
+        // Saving as a script and reading it back gives the IDs predicted for it
+        let mut saved = patch.clone();
+        saved.renumber(&script_node_ids(&patch));
+        let reloaded = compile(&export(&patch, &registry).unwrap(), &registry).unwrap();
+
+        let diff = PatchDiff::between(&saved, &reloaded);
+        assert!(diff.added_nodes.is_empty());
+        assert!(diff.removed_nodes.is_empty());
+        assert!(diff.changed_parameters.is_empty());
+        assert!(diff.added_connections.is_empty());
+        assert!(diff.removed_connections.is_empty());
+    }
+
+    #[test]
+    fn test_export_unknown_module() {
+        let mut patch = Patch::new("Broken");
+        patch.nodes.push(NodeData::new(1, "osc.nonexistent", (0.0, 0.0)));
+        assert!(matches!(
+            export(&patch, &create_module_registry()),
+            Err(PatchError::UnknownModule(_))
+        ));
+    }
+}
@@ -0,0 +1,380 @@
+//! Patch library index.
+//!
+//! Indexes the `.json` patches in a directory tree so they can be browsed
+//! and searched by name, metadata and the modules they use. Favorites and
+//! the chosen library directory are kept in a small settings file.
+//!
+//! Reading every patch of a large library takes a while, so indexing runs on
+//! a background thread; the previous index stays available until it's done.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+
+use serde::{Deserialize, Serialize};
+
+use super::metadata::PatchMetadata;
+use super::patch::{load_from_file, PatchError};
+
+/// Persistent library settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct LibrarySettings {
+    /// Library directory (None = the default library directory).
+    root: Option<PathBuf>,
+    /// Favorite patch files.
+    favorites: BTreeSet<PathBuf>,
+}
+
+/// A patch file found in the library.
+#[derive(Debug, Clone)]
+pub struct LibraryEntry {
+    /// Path of the patch file.
+    pub path: PathBuf,
+    /// Patch name.
+    pub name: String,
+    /// Patch metadata.
+    pub metadata: PatchMetadata,
+    /// Distinct module IDs used by the patch, sorted.
+    pub modules: Vec<String>,
+    /// Number of nodes in the patch.
+    pub node_count: usize,
+}
+
+impl LibraryEntry {
+    /// Whether the entry matches a single search term.
+    ///
+    /// `tag:name` matches a tag exactly, `author:name` matches within the
+    /// author; any other term matches within the name, file name, author,
+    /// description, tags or module IDs. Matching ignores case.
+    fn matches_term(&self, term: &str) -> bool {
+        let term = term.to_lowercase();
+        let contains = |text: &str| text.to_lowercase().contains(&term);
+
+        if let Some(tag) = term.strip_prefix("tag:") {
+            return self.metadata.has_tag(tag);
+        }
+        if let Some(author) = term.strip_prefix("author:") {
+            return self.metadata.author.to_lowercase().contains(author);
+        }
+
+        contains(&self.name)
+            || self.path.file_name().and_then(|n| n.to_str()).is_some_and(contains)
+            || contains(&self.metadata.author)
+            || contains(&self.metadata.description)
+            || self.metadata.tags.iter().any(|tag| contains(tag))
+            || self.modules.iter().any(|module| contains(module))
+    }
+}
+
+/// An indexed directory of patches.
+pub struct PatchLibrary {
+    /// File the settings are persisted to.
+    settings_path: PathBuf,
+    /// Root directory used when the settings don't name one.
+    default_root: PathBuf,
+    settings: LibrarySettings,
+    entries: Vec<LibraryEntry>,
+    /// Files that looked like patches but could not be read.
+    skipped: Vec<PathBuf>,
+    /// Indexing in progress on a background thread.
+    scan: Option<JoinHandle<Scan>>,
+}
+
+/// Result of indexing a directory.
+struct Scan {
+    entries: Vec<LibraryEntry>,
+    skipped: Vec<PathBuf>,
+}
+
+impl PatchLibrary {
+    /// Open the library described by the settings file and start indexing it.
+    ///
+    /// `default_root` is used until another directory is chosen, and is
+    /// created if it doesn't exist. Missing or corrupt settings start empty.
+    pub fn open(settings_path: impl Into<PathBuf>, default_root: impl Into<PathBuf>) -> Self {
+        let settings_path = settings_path.into();
+        let settings = fs::read_to_string(&settings_path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        let mut library = Self {
+            settings_path,
+            default_root: default_root.into(),
+            settings,
+            entries: Vec::new(),
+            skipped: Vec::new(),
+            scan: None,
+        };
+
+        if library.settings.root.is_none() {
+            let _ = fs::create_dir_all(&library.default_root);
+        }
+        library.rescan();
+        library
+    }
+
+    /// The indexed directory.
+    pub fn root(&self) -> &Path {
+        self.settings.root.as_deref().unwrap_or(&self.default_root)
+    }
+
+    /// Index a different directory and remember it.
+    pub fn set_root(&mut self, root: impl Into<PathBuf>) -> Result<(), PatchError> {
+        self.settings.root = Some(root.into());
+        self.rescan();
+        self.save_settings()
+    }
+
+    /// Start re-reading every patch under the root directory in the
+    /// background, replacing any scan still running.
+    ///
+    /// Call [`update`](Self::update) to pick up the result.
+    pub fn rescan(&mut self) {
+        let root = self.root().to_path_buf();
+        self.scan = Some(std::thread::spawn(move || scan(&root)));
+    }
+
+    /// Whether indexing is still running.
+    pub fn is_scanning(&self) -> bool {
+        self.scan.is_some()
+    }
+
+    /// Take the new index if the background scan has finished.
+    pub fn update(&mut self) {
+        if self.scan.as_ref().is_some_and(|scan| scan.is_finished()) {
+            self.finish_scan();
+        }
+    }
+
+    /// Wait for a running scan and take its index.
+    pub fn finish_scan(&mut self) {
+        let Some(scan) = self.scan.take() else {
+            return;
+        };
+        // A scan that panicked keeps the previous index
+        if let Ok(scan) = scan.join() {
+            self.entries = scan.entries;
+            self.skipped = scan.skipped;
+        }
+    }
+
+    /// Whether a path lies inside the library directory.
+    pub fn contains(&self, path: &Path) -> bool {
+        path.starts_with(self.root())
+    }
+
+    /// All indexed patches, sorted by name.
+    pub fn entries(&self) -> &[LibraryEntry] {
+        &self.entries
+    }
+
+    /// Files that could not be read as patches during the last scan.
+    pub fn skipped(&self) -> &[PathBuf] {
+        &self.skipped
+    }
+
+    /// The patch after `current` in name order, wrapping around; the first
+    /// patch if `current` isn't in the library.
+    pub fn next_after(&self, current: Option<&Path>) -> Option<&LibraryEntry> {
+        let index = current.and_then(|path| self.entries.iter().position(|entry| entry.path == path));
+        let next = index.map_or(0, |index| (index + 1) % self.entries.len());
+        self.entries.get(next)
+    }
+
+    /// Find patches matching every whitespace-separated term of `query`.
+    ///
+    /// Favorites are listed first; an empty query matches everything.
+    pub fn search(&self, query: &str, favorites_only: bool) -> Vec<&LibraryEntry> {
+        let terms: Vec<&str> = query.split_whitespace().collect();
+
+        let mut results: Vec<&LibraryEntry> = self.entries
+            .iter()
+            .filter(|entry| !favorites_only || self.is_favorite(&entry.path))
+            .filter(|entry| terms.iter().all(|term| entry.matches_term(term)))
+            .collect();
+
+        // Stable sort keeps the name order within each group
+        results.sort_by_key(|entry| !self.is_favorite(&entry.path));
+        results
+    }
+
+    /// All tags in the library with the number of patches using each, sorted by tag.
+    pub fn tags(&self) -> Vec<(String, usize)> {
+        let mut tags: Vec<(String, usize)> = Vec::new();
+        for tag in self.entries.iter().flat_map(|entry| &entry.metadata.tags) {
+            match tags.iter_mut().find(|(t, _)| t == tag) {
+                Some((_, count)) => *count += 1,
+                None => tags.push((tag.clone(), 1)),
+            }
+        }
+        tags.sort();
+        tags
+    }
+
+    /// Whether a patch file is marked as a favorite.
+    pub fn is_favorite(&self, path: &Path) -> bool {
+        self.settings.favorites.contains(path)
+    }
+
+    /// Mark or unmark a patch file as a favorite, and persist the change.
+    pub fn toggle_favorite(&mut self, path: &Path) -> Result<(), PatchError> {
+        if !self.settings.favorites.remove(path) {
+            self.settings.favorites.insert(path.to_path_buf());
+        }
+        self.save_settings()
+    }
+
+    fn save_settings(&self) -> Result<(), PatchError> {
+        if let Some(parent) = self.settings_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.settings_path, serde_json::to_string_pretty(&self.settings)?)?;
+        Ok(())
+    }
+}
+
+/// Read every patch under `root`, sorted by name.
+fn scan(root: &Path) -> Scan {
+    let mut files = Vec::new();
+    collect_patch_files(root, &mut files);
+
+    let mut entries = Vec::new();
+    let mut skipped = Vec::new();
+    for path in files {
+        match load_from_file(&path) {
+            Ok(patch) => {
+                let modules: BTreeSet<String> =
+                    patch.nodes.iter().map(|node| node.module_id.clone()).collect();
+                entries.push(LibraryEntry {
+                    name: patch.name,
+                    metadata: patch.metadata,
+                    modules: modules.into_iter().collect(),
+                    node_count: patch.nodes.len(),
+                    path,
+                });
+            }
+            Err(_) => skipped.push(path),
+        }
+    }
+
+    entries.sort_by_key(|entry| entry.name.to_lowercase());
+    Scan { entries, skipped }
+}
+
+/// Recursively collect `.json` files under `dir`, skipping hidden entries.
+fn collect_patch_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if entry.file_name().to_str().is_some_and(|name| name.starts_with('.')) {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_patch_files(&path, files);
+        } else if path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+            files.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::patch::{save_to_file, NodeData, Patch};
+    use crate::test_util::TestDir;
+
+    fn write_patch(path: &Path, name: &str, tags: &str, module_id: &str) {
+        let mut patch = Patch::new(name);
+        patch.metadata.author = "Wendy".to_string();
+        patch.metadata.set_tags(tags);
+        patch.nodes.push(NodeData::new(1, module_id, (0.0, 0.0)));
+        save_to_file(&patch, path).unwrap();
+    }
+
+    fn sample_library(name: &str) -> (TestDir, PatchLibrary) {
+        let dir = TestDir::new(&format!("library_{}", name));
+        let patches = dir.join("patches");
+        fs::create_dir_all(patches.join("bass")).unwrap();
+        write_patch(&patches.join("pad.json"), "Glass Pad", "pad, ambient", "fx.reverb");
+        write_patch(&patches.join("bass").join("acid.json"), "Acid Bass", "bass, acid", "filter.svf");
+        write_patch(&patches.join("bass").join("sub.json"), "Sub Bass", "bass", "osc.sine");
+        fs::write(patches.join("notes.json"), "not a patch").unwrap();
+
+        let mut library = PatchLibrary::open(dir.join("library.json"), &patches);
+        library.finish_scan();
+        (dir, library)
+    }
+
+    #[test]
+    fn test_scan() {
+        let (_dir, library) = sample_library("scan");
+        assert!(!library.is_scanning());
+        let names: Vec<&str> = library.entries().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["Acid Bass", "Glass Pad", "Sub Bass"]);
+        assert_eq!(library.skipped().len(), 1);
+        assert_eq!(library.tags()[0], ("acid".to_string(), 1));
+        assert!(library.tags().contains(&("bass".to_string(), 2)));
+    }
+
+    #[test]
+    fn test_rescan_keeps_index_until_done() {
+        let (dir, mut library) = sample_library("rescan");
+        write_patch(&dir.join("patches").join("lead.json"), "Lead", "", "osc.saw");
+
+        library.rescan();
+        assert!(library.is_scanning());
+        assert_eq!(library.entries().len(), 3);
+
+        library.finish_scan();
+        assert!(!library.is_scanning());
+        assert_eq!(library.entries().len(), 4);
+    }
+
+    #[test]
+    fn test_next_after() {
+        let (dir, library) = sample_library("next");
+        let patches = dir.join("patches");
+        let next = |path: Option<PathBuf>| library.next_after(path.as_deref()).map(|e| e.name.clone());
+
+        assert_eq!(next(None).as_deref(), Some("Acid Bass"));
+        assert_eq!(next(Some(patches.join("pad.json"))).as_deref(), Some("Sub Bass"));
+        assert_eq!(next(Some(patches.join("bass").join("sub.json"))).as_deref(), Some("Acid Bass"));
+        assert_eq!(next(Some(dir.join("elsewhere.json"))).as_deref(), Some("Acid Bass"));
+    }
+
+    #[test]
+    fn test_search() {
+        let (_dir, library) = sample_library("search");
+        let names = |query: &str| -> Vec<String> {
+            library.search(query, false).iter().map(|e| e.name.clone()).collect()
+        };
+
+        assert_eq!(names("").len(), 3);
+        assert_eq!(names("BASS"), vec!["Acid Bass", "Sub Bass"]);
+        assert_eq!(names("tag:acid"), vec!["Acid Bass"]);
+        assert_eq!(names("bass tag:acid"), vec!["Acid Bass"]);
+        assert_eq!(names("reverb"), vec!["Glass Pad"]);
+        assert_eq!(names("author:wendy").len(), 3);
+        assert!(names("tag:bas").is_empty());
+    }
+
+    #[test]
+    fn test_favorites_persist() {
+        let (dir, mut library) = sample_library("favorites");
+        let sub = dir.join("patches").join("bass").join("sub.json");
+
+        library.toggle_favorite(&sub).unwrap();
+        assert_eq!(library.search("", false)[0].name, "Sub Bass");
+        assert_eq!(library.search("", true).len(), 1);
+
+        let reopened = PatchLibrary::open(dir.join("library.json"), dir.join("patches"));
+        assert!(reopened.is_favorite(&sub));
+    }
+}
@@ -0,0 +1,537 @@
+//! Static checks for patches.
+//!
+//! Flags common patching mistakes without running the patch: a missing
+//! output, modules whose signal never reaches an output, processors with
+//! nothing to process, questionable or impossible connections, clashing MIDI
+//! mappings and parameter values outside their range. Used by the `lint`
+//! command line and the warnings list in the status bar.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::dsp::{DspModule, ModuleRegistry, SignalType};
+use crate::graph::{NodeLayout, SynthNodeTemplate};
+use super::patch::{MidiMapping, Patch};
+
+/// Module IDs of the modules that send the patch out of the synth: audio to
+/// the device, or MIDI to external gear.
+const OUTPUT_MODULES: &[&str] = &["output.audio", "output.multi", "output.cv_to_midi", "output.cv_to_cc"];
+
+/// Modules that are an end point of a patch even without reaching the audio
+/// output: the outputs themselves, recorders, displays, and the Scene Morph
+/// control whose output is read by the application.
+const SINK_MODULES: &[&str] = &[
+    "output.audio",
+    "output.multi",
+    "output.recorder",
+    "output.cv_to_midi",
+    "output.cv_to_cc",
+    "util.midi_monitor",
+    "util.oscilloscope",
+    "util.scene_morph",
+];
+
+/// How serious a finding is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Probably a mistake, but the patch still runs.
+    Warning,
+    /// The patch cannot work as written.
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// One finding of the linter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    /// How serious the finding is.
+    pub severity: Severity,
+    /// The node the finding is about, if any.
+    pub node_id: Option<u64>,
+    /// Human-readable description.
+    pub message: String,
+}
+
+impl Lint {
+    fn warning(node_id: Option<u64>, message: String) -> Self {
+        Self { severity: Severity::Warning, node_id, message }
+    }
+
+    fn error(node_id: Option<u64>, message: String) -> Self {
+        Self { severity: Severity::Error, node_id, message }
+    }
+}
+
+impl std::fmt::Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Whether any finding is an error.
+pub fn has_errors(lints: &[Lint]) -> bool {
+    lints.iter().any(|lint| lint.severity == Severity::Error)
+}
+
+/// A patch node together with its module, once resolved.
+struct LintNode {
+    label: String,
+    module_id: String,
+    module: Box<dyn DspModule>,
+    layout: NodeLayout,
+}
+
+impl LintNode {
+    /// Signal type of the input shown in the editor under `name`.
+    fn input_type(&self, name: &str) -> Option<SignalType> {
+        let index = self.layout.inputs.iter().position(|input| input == name)?;
+        self.module.ports().iter().filter(|p| p.is_input()).nth(index).map(|p| p.signal_type)
+    }
+
+    /// Signal type of the output shown in the editor under `name`.
+    fn output_type(&self, name: &str) -> Option<SignalType> {
+        let index = self.layout.outputs.iter().position(|output| output == name)?;
+        self.module.ports().iter().filter(|p| p.is_output()).nth(index).map(|p| p.signal_type)
+    }
+}
+
+/// Check a patch for common mistakes.
+///
+/// Findings are sorted with errors first. An empty patch has none.
+pub fn lint(patch: &Patch, registry: &ModuleRegistry) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    if patch.nodes.is_empty() {
+        return lints;
+    }
+
+    let nodes = resolve_nodes(patch, registry, &mut lints);
+    check_parameters(patch, &nodes, &mut lints);
+    check_connections(patch, &nodes, &mut lints);
+    check_unconnected_inputs(patch, &nodes, &mut lints);
+    check_reachability(patch, &nodes, &mut lints);
+    check_midi_mappings(patch, &nodes, &mut lints);
+
+    // Stable sort keeps patch order within each severity
+    lints.sort_by_key(|lint| std::cmp::Reverse(lint.severity));
+    lints
+}
+
+/// Look up the module of every node, reporting unknown modules and duplicate IDs.
+fn resolve_nodes(patch: &Patch, registry: &ModuleRegistry, lints: &mut Vec<Lint>) -> HashMap<u64, LintNode> {
+    let mut nodes = HashMap::new();
+
+    for node in &patch.nodes {
+        let resolved = registry
+            .create(&node.module_id)
+            .zip(SynthNodeTemplate::from_module_id(&node.module_id));
+        let Some((module, template)) = resolved else {
+            lints.push(Lint::error(
+                Some(node.id),
+                format!("node {} uses unknown module '{}'", node.id, node.module_id),
+            ));
+            continue;
+        };
+
+        let lint_node = LintNode {
+            label: format!("{} (node {})", module.info().name, node.id),
+            module_id: node.module_id.clone(),
+            module,
+            layout: template.layout(),
+        };
+        if nodes.insert(node.id, lint_node).is_some() {
+            lints.push(Lint::error(Some(node.id), format!("node ID {} is used more than once", node.id)));
+        }
+    }
+
+    if !has_output(patch) {
+        lints.push(Lint::error(
+            None,
+            "patch has no output module (audio or MIDI), so it sends nothing out".to_string(),
+        ));
+    }
+
+    nodes
+}
+
+/// Report parameter values outside their definition's range.
+fn check_parameters(patch: &Patch, nodes: &HashMap<u64, LintNode>, lints: &mut Vec<Lint>) {
+    for node in &patch.nodes {
+        let Some(lint_node) = nodes.get(&node.id) else {
+            continue;
+        };
+
+        for (value, definition) in node.parameters.iter().zip(lint_node.module.parameters()) {
+            let value = value.as_f32();
+            // Written this way so NaN is reported too
+            if !(definition.min..=definition.max).contains(&value) {
+                lints.push(Lint::warning(
+                    Some(node.id),
+                    format!(
+                        "{} of {} is {}, outside its range {} to {}",
+                        definition.name, lint_node.label, value, definition.min, definition.max
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+/// Report connections to missing nodes or ports, and between signal types that don't mix.
+fn check_connections(patch: &Patch, nodes: &HashMap<u64, LintNode>, lints: &mut Vec<Lint>) {
+    for conn in &patch.connections {
+        let (Some(from), Some(to)) = (nodes.get(&conn.from_node), nodes.get(&conn.to_node)) else {
+            // Unknown modules were reported already; only report IDs that aren't in the patch
+            let missing = [conn.from_node, conn.to_node]
+                .into_iter()
+                .find(|id| !patch.nodes.iter().any(|node| node.id == *id));
+            if let Some(id) = missing {
+                lints.push(Lint::error(None, format!("a connection refers to missing node {}", id)));
+            }
+            continue;
+        };
+
+        let Some(from_type) = from.output_type(&conn.from_port) else {
+            lints.push(Lint::error(
+                Some(conn.from_node),
+                format!("{} has no output '{}'", from.label, conn.from_port),
+            ));
+            continue;
+        };
+        let Some(to_type) = to.input_type(&conn.to_port) else {
+            lints.push(Lint::error(
+                Some(conn.to_node),
+                format!("{} has no input '{}'", to.label, conn.to_port),
+            ));
+            continue;
+        };
+
+        let description = format!("{} '{}' -> {} '{}'", from.label, conn.from_port, to.label, conn.to_port);
+        if from_type.can_connect_to(to_type) {
+            continue;
+        }
+        // A gate input fed at audio rate would flip on every zero crossing,
+        // also when the audio passes through Control ports on the way
+        let audio_into_gate = to_type == SignalType::Gate
+            && carries_audio(patch, nodes, conn.from_node, &conn.from_port, &mut HashSet::new());
+        let message = if audio_into_gate {
+            format!("{}: an audio-rate signal into a gate input retriggers on every cycle", description)
+        } else {
+            format!("{}: {} cannot connect to {}", description, from_type.name(), to_type.name())
+        };
+        lints.push(Lint::error(Some(conn.to_node), message));
+    }
+}
+
+/// Whether the output `port` of `node_id` is audio, or a Control output of a
+/// module with audio on one of its inputs.
+fn carries_audio(
+    patch: &Patch,
+    nodes: &HashMap<u64, LintNode>,
+    node_id: u64,
+    port: &str,
+    visited: &mut HashSet<u64>,
+) -> bool {
+    let Some(node) = nodes.get(&node_id) else {
+        return false;
+    };
+    match node.output_type(port) {
+        Some(SignalType::Audio) => true,
+        Some(SignalType::Control) if visited.insert(node_id) => patch.connections
+            .iter()
+            .filter(|conn| conn.to_node == node_id)
+            .any(|conn| carries_audio(patch, nodes, conn.from_node, &conn.from_port, visited)),
+        _ => false,
+    }
+}
+
+/// Report processors with nothing on their audio inputs and envelopes without a gate.
+fn check_unconnected_inputs(patch: &Patch, nodes: &HashMap<u64, LintNode>, lints: &mut Vec<Lint>) {
+    let connected: HashSet<(u64, &str)> = patch.connections
+        .iter()
+        .map(|conn| (conn.to_node, conn.to_port.as_str()))
+        .collect();
+
+    for node in &patch.nodes {
+        let Some(lint_node) = nodes.get(&node.id) else {
+            continue;
+        };
+
+        // Only inputs shown in the editor can be connected
+        let inputs: Vec<(&str, SignalType, &str)> = lint_node.module.ports()
+            .iter()
+            .filter(|p| p.is_input())
+            .zip(&lint_node.layout.inputs)
+            .map(|(port, name)| (port.id, port.signal_type, name.as_str()))
+            .collect();
+        let is_connected = |name: &str| connected.contains(&(node.id, name));
+
+        let audio_inputs: Vec<&str> = inputs
+            .iter()
+            .filter(|(_, signal_type, _)| *signal_type == SignalType::Audio)
+            .map(|(_, _, name)| *name)
+            .collect();
+        if !audio_inputs.is_empty() && !audio_inputs.iter().any(|name| is_connected(name)) {
+            lints.push(Lint::warning(
+                Some(node.id),
+                format!("{} has nothing connected to its audio input", lint_node.label),
+            ));
+        }
+
+        for (id, _, name) in &inputs {
+            if *id == "gate" && !is_connected(name) {
+                lints.push(Lint::warning(
+                    Some(node.id),
+                    format!("{} has nothing connected to '{}', so it never opens", lint_node.label, name),
+                ));
+            }
+        }
+    }
+}
+
+/// Whether the patch has a module that sends audio to the device or MIDI to external gear.
+fn has_output(patch: &Patch) -> bool {
+    patch.nodes.iter().any(|node| OUTPUT_MODULES.contains(&node.module_id.as_str()))
+}
+
+/// Report modules whose signal never reaches the output or a display.
+fn check_reachability(patch: &Patch, nodes: &HashMap<u64, LintNode>, lints: &mut Vec<Lint>) {
+    // Without an output everything is unreachable; that is reported once already
+    if !has_output(patch) {
+        return;
+    }
+
+    // Walk backwards from every sink
+    let mut reaches: HashSet<u64> = nodes
+        .iter()
+        .filter(|(_, node)| SINK_MODULES.contains(&node.module_id.as_str()))
+        .map(|(id, _)| *id)
+        .collect();
+    let mut pending: Vec<u64> = reaches.iter().copied().collect();
+    while let Some(id) = pending.pop() {
+        for conn in patch.connections.iter().filter(|conn| conn.to_node == id) {
+            if reaches.insert(conn.from_node) {
+                pending.push(conn.from_node);
+            }
+        }
+    }
+
+    for node in &patch.nodes {
+        if let Some(lint_node) = nodes.get(&node.id).filter(|_| !reaches.contains(&node.id)) {
+            lints.push(Lint::warning(
+                Some(node.id),
+                format!("{} does not reach the output", lint_node.label),
+            ));
+        }
+    }
+}
+
+/// Report MIDI mappings to missing nodes and controls mapped more than once.
+fn check_midi_mappings(patch: &Patch, nodes: &HashMap<u64, LintNode>, lints: &mut Vec<Lint>) {
+    // What a mapping controls: an app action, or a parameter of a node
+    let target = |mapping: &MidiMapping| match mapping.action {
+        Some(action) => action.label().to_string(),
+        None => {
+            let label = nodes.get(&mapping.node_id).map_or("a missing node", |node| node.label.as_str());
+            format!("{} of {}", mapping.param_name, label)
+        }
+    };
+
+    for (index, mapping) in patch.midi_mappings.iter().enumerate() {
+        if mapping.action.is_none() && !nodes.contains_key(&mapping.node_id) {
+            lints.push(Lint::warning(
+                None,
+                format!("{} is mapped to missing node {}", mapping.control_label(), mapping.node_id),
+            ));
+            continue;
+        }
+
+        // Report each clash once, at its second mapping; channel 0 and no device listen to everything
+        let clash = patch.midi_mappings[..index].iter().find(|other| {
+            other.shares_control(mapping)
+                && (other.channel == mapping.channel || other.channel == 0 || mapping.channel == 0)
+                && (other.device == mapping.device || other.device.is_none() || mapping.device.is_none())
+        });
+        if let Some(other) = clash {
+            lints.push(Lint::warning(
+                mapping.action.is_none().then_some(mapping.node_id),
+                format!("{} controls both {} and {}", mapping.control_label(), target(other), target(mapping)),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::create_module_registry;
+    use crate::persistence::mapping::{ControlKind, MidiAction};
+    use crate::persistence::patch::{ConnectionData, MidiMapping, NodeData, ParameterValue};
+
+    fn node(id: u64, module_id: &str) -> NodeData {
+        let module = create_module_registry().create(module_id).unwrap();
+        let mut node = NodeData::new(id, module_id, (0.0, 0.0));
+        node.parameters = module.parameters()
+            .iter()
+            .map(|definition| ParameterValue::Scalar(definition.default))
+            .collect();
+        node
+    }
+
+    /// Oscillator -> VCA -> output, with an envelope on the VCA.
+    fn clean_patch() -> Patch {
+        let mut patch = Patch::new("Lint");
+        patch.nodes = vec![
+            node(1, "osc.sine"),
+            node(2, "util.vca"),
+            node(3, "output.audio"),
+            node(4, "mod.adsr"),
+            node(5, "util.clock"),
+        ];
+        patch.connections = vec![
+            ConnectionData::new(1, "Out", 2, "In"),
+            ConnectionData::new(2, "Out", 3, "Mono"),
+            ConnectionData::new(4, "Out", 2, "CV"),
+            ConnectionData::new(5, "Gate", 4, "Gate"),
+        ];
+        patch
+    }
+
+    fn messages(lints: &[Lint]) -> Vec<String> {
+        lints.iter().map(|lint| lint.to_string()).collect()
+    }
+
+    #[test]
+    fn test_clean_patch() {
+        let registry = create_module_registry();
+        let lints = lint(&clean_patch(), &registry);
+        assert!(lints.is_empty(), "{:?}", messages(&lints));
+        assert!(lint(&Patch::new("Empty"), &registry).is_empty());
+    }
+
+    #[test]
+    fn test_missing_output_and_unreachable_nodes() {
+        let registry = create_module_registry();
+
+        let mut patch = clean_patch();
+        patch.nodes.retain(|node| node.id != 3);
+        patch.connections.retain(|conn| conn.to_node != 3);
+        let lints = lint(&patch, &registry);
+        assert!(has_errors(&lints));
+        assert_eq!(lints.len(), 1, "{:?}", messages(&lints));
+
+        let mut patch = clean_patch();
+        patch.nodes.push(node(6, "mod.lfo"));
+        let lints = lint(&patch, &registry);
+        assert!(!has_errors(&lints));
+        assert_eq!(lints.len(), 1);
+        assert_eq!(lints[0].node_id, Some(6));
+        assert!(lints[0].message.contains("does not reach the output"));
+
+        // A Multi Output is an output too
+        let mut patch = clean_patch();
+        patch.nodes.retain(|node| node.id != 3);
+        patch.connections.retain(|conn| conn.to_node != 3);
+        patch.nodes.push(node(3, "output.multi"));
+        patch.connections.push(ConnectionData::new(2, "Out", 3, "Ch 1"));
+        let lints = lint(&patch, &registry);
+        assert!(lints.is_empty(), "{:?}", messages(&lints));
+
+        // So is a module sending MIDI to external gear
+        let mut patch = Patch::new("Hardware");
+        patch.nodes = vec![node(1, "util.clock"), node(2, "output.cv_to_midi")];
+        patch.connections = vec![ConnectionData::new(1, "Gate", 2, "Gate")];
+        let lints = lint(&patch, &registry);
+        assert!(lints.is_empty(), "{:?}", messages(&lints));
+    }
+
+    #[test]
+    fn test_unconnected_inputs() {
+        let registry = create_module_registry();
+        let mut patch = clean_patch();
+        patch.connections.retain(|conn| conn.to_port != "In" && conn.to_port != "Gate");
+        let lints = lint(&patch, &registry);
+
+        let found: Vec<Option<u64>> = lints.iter().map(|lint| lint.node_id).collect();
+        assert!(found.contains(&Some(2)), "VCA without input: {:?}", messages(&lints));
+        assert!(found.contains(&Some(4)), "envelope without gate: {:?}", messages(&lints));
+    }
+
+    #[test]
+    fn test_connection_types() {
+        let registry = create_module_registry();
+        let mut patch = clean_patch();
+        patch.connections.retain(|conn| conn.from_node != 5);
+        patch.connections.push(ConnectionData::new(1, "Out", 4, "Gate"));
+        patch.connections.push(ConnectionData::new(1, "Out", 3, "Nope"));
+        let lints = lint(&patch, &registry);
+
+        assert!(has_errors(&lints));
+        assert!(lints.iter().any(|lint| lint.message.contains("has no input 'Nope'")));
+        assert!(lints.iter().any(|lint| lint.severity == Severity::Error && lint.message.contains("gate input")));
+    }
+
+    #[test]
+    fn test_audio_through_control_into_gate() {
+        let registry = create_module_registry();
+        let mut patch = clean_patch();
+        patch.connections.retain(|conn| conn.from_node != 5);
+        patch.nodes.push(node(6, "util.attenuverter"));
+        patch.connections.push(ConnectionData::new(1, "Out", 6, "In"));
+        patch.connections.push(ConnectionData::new(6, "Out", 4, "Gate"));
+        let lints = lint(&patch, &registry);
+
+        let gate = lints.iter().find(|lint| lint.node_id == Some(4)).expect("no finding for the envelope");
+        assert_eq!(gate.severity, Severity::Error);
+        assert!(gate.message.contains("audio-rate"), "{}", gate);
+
+        // Control without audio behind it is still refused, with the plain message
+        patch.connections.retain(|conn| conn.from_node != 1 || conn.to_node != 6);
+        let lints = lint(&patch, &registry);
+        let gate = lints.iter().find(|lint| lint.node_id == Some(4)).expect("no finding for the envelope");
+        assert!(gate.message.contains("Control cannot connect to Gate"), "{}", gate);
+    }
+
+    #[test]
+    fn test_parameters_and_midi_mappings() {
+        let registry = create_module_registry();
+        let mut patch = clean_patch();
+        patch.nodes[0].parameters[0] = ParameterValue::Frequency(50000.0);
+        patch.midi_mappings = vec![
+            MidiMapping::new(74, 0, 1, 0, "Frequency", 20.0, 2000.0),
+            MidiMapping::new(74, 3, 2, 0, "Gain", 0.0, 1.0),
+            MidiMapping::new(75, 1, 2, 0, "Gain", 0.0, 1.0),
+            MidiMapping::new(75, 2, 1, 0, "Frequency", 20.0, 2000.0),
+            // Same CC and channel from different input devices: no clash
+            MidiMapping::new(76, 1, 1, 0, "Frequency", 20.0, 2000.0).with_device(Some("Keys".to_string())),
+            MidiMapping::new(76, 1, 2, 0, "Gain", 0.0, 1.0).with_device(Some("Knobs".to_string())),
+        ];
+        let lints = lint(&patch, &registry);
+
+        assert!(!has_errors(&lints));
+        assert_eq!(lints.len(), 2, "{:?}", messages(&lints));
+        assert!(lints[0].message.contains("outside its range"));
+        assert!(lints[1].message.starts_with("CC 74 controls both"));
+    }
+
+    #[test]
+    fn test_action_mappings() {
+        let registry = create_module_registry();
+        let mut patch = clean_patch();
+        patch.midi_mappings = vec![
+            MidiMapping::for_action(MidiAction::Panic, ControlKind::Note(36), 0),
+            MidiMapping::new(0, 0, 1, 0, "Frequency", 20.0, 2000.0).with_control(ControlKind::Note(36)),
+        ];
+        let lints = lint(&patch, &registry);
+
+        // Actions have no node, but still clash with other mappings of their control
+        assert_eq!(lints.len(), 1, "{:?}", messages(&lints));
+        assert!(lints[0].message.starts_with("Note 36 controls both Panic and Frequency"));
+    }
+}
@@ -0,0 +1,724 @@
+//! Controller mapping responses.
+//!
+//! How the messages of a mapped MIDI controller become parameter values:
+//! 7-bit and 14-bit Control Changes, NRPN and RPN, relative encoders,
+//! response curves and takeover modes, notes and buttons, plus the
+//! detection MIDI Learn uses to tell them apart and the rate limiting of
+//! the values sent back to the controller.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::MidiEvent;
+
+/// What a mapped controller sends.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ControlKind {
+    /// A 7-bit Control Change.
+    #[default]
+    Cc,
+    /// A 14-bit Control Change: the mapped CC (0-31) carries the MSB and
+    /// the CC 32 above it the LSB.
+    Cc14,
+    /// A Non-Registered Parameter Number (0-16383).
+    Nrpn(u16),
+    /// A Registered Parameter Number (0-16383).
+    Rpn(u16),
+    /// A note (0-127), used as a button.
+    Note(u8),
+}
+
+/// How a controller encodes its value.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum EncoderMode {
+    /// The value is the controller's position.
+    #[default]
+    Absolute,
+    /// Relative: 1-63 turn up, 127-65 turn down (127 = -1).
+    TwosComplement,
+    /// Relative: 65 and up turn up, 63 and down turn down (64 = no change).
+    BinaryOffset,
+    /// Relative: 1-63 turn up, 65-127 turn down (65 = -1).
+    SignMagnitude,
+}
+
+impl EncoderMode {
+    /// All encoder modes, in menu order.
+    pub const ALL: [EncoderMode; 4] = [
+        EncoderMode::Absolute,
+        EncoderMode::TwosComplement,
+        EncoderMode::BinaryOffset,
+        EncoderMode::SignMagnitude,
+    ];
+
+    /// Name for display.
+    pub fn label(self) -> &'static str {
+        match self {
+            EncoderMode::Absolute => "Absolute",
+            EncoderMode::TwosComplement => "Relative (2's complement)",
+            EncoderMode::BinaryOffset => "Relative (binary offset)",
+            EncoderMode::SignMagnitude => "Relative (sign-magnitude)",
+        }
+    }
+
+    /// Whether the controller sends changes rather than positions.
+    pub fn is_relative(self) -> bool {
+        self != EncoderMode::Absolute
+    }
+
+    /// The change a relative CC value stands for, in steps.
+    pub fn delta(self, value: u8) -> i32 {
+        let value = i32::from(value & 0x7F);
+        match self {
+            EncoderMode::Absolute => 0,
+            EncoderMode::TwosComplement => if value < 64 { value } else { value - 128 },
+            EncoderMode::BinaryOffset => value - 64,
+            EncoderMode::SignMagnitude => if value < 64 { value } else { 64 - value },
+        }
+    }
+}
+
+/// How the controller's travel is spread over the parameter range.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ResponseCurve {
+    /// Even steps over the whole range.
+    #[default]
+    Linear,
+    /// Fast at the start, fine control at the top of the range.
+    Logarithmic,
+    /// Fine control at the start, fast at the top of the range.
+    Exponential,
+}
+
+impl ResponseCurve {
+    /// All curves, in menu order.
+    pub const ALL: [ResponseCurve; 3] = [ResponseCurve::Linear, ResponseCurve::Logarithmic, ResponseCurve::Exponential];
+
+    /// Steepness of the logarithmic and exponential curves.
+    const SHAPE: f32 = 4.0;
+
+    /// Name for display.
+    pub fn label(self) -> &'static str {
+        match self {
+            ResponseCurve::Linear => "Linear",
+            ResponseCurve::Logarithmic => "Logarithmic",
+            ResponseCurve::Exponential => "Exponential",
+        }
+    }
+
+    /// The share of the parameter range (0.0-1.0) for a controller position (0.0-1.0).
+    pub fn apply(self, position: f32) -> f32 {
+        let position = position.clamp(0.0, 1.0);
+        match self {
+            ResponseCurve::Linear => position,
+            ResponseCurve::Logarithmic => (1.0 + position * Self::SHAPE.exp_m1()).ln() / Self::SHAPE,
+            ResponseCurve::Exponential => (position * Self::SHAPE).exp_m1() / Self::SHAPE.exp_m1(),
+        }
+    }
+
+    /// The controller position for a share of the parameter range; the inverse of `apply`.
+    pub fn invert(self, amount: f32) -> f32 {
+        let amount = amount.clamp(0.0, 1.0);
+        match self {
+            ResponseCurve::Linear => amount,
+            ResponseCurve::Logarithmic => (amount * Self::SHAPE).exp_m1() / Self::SHAPE.exp_m1(),
+            ResponseCurve::Exponential => (1.0 + amount * Self::SHAPE.exp_m1()).ln() / Self::SHAPE,
+        }
+    }
+}
+
+/// What happens when a controller's position doesn't match the parameter,
+/// for example after loading a patch or recalling a scene.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Takeover {
+    /// The parameter jumps to the controller.
+    #[default]
+    Jump,
+    /// The parameter waits until the controller passes its value.
+    Pickup,
+    /// The parameter moves with the controller, scaled so that both reach
+    /// the end of their range together.
+    Scaling,
+}
+
+impl Takeover {
+    /// All takeover modes, in menu order.
+    pub const ALL: [Takeover; 3] = [Takeover::Jump, Takeover::Pickup, Takeover::Scaling];
+
+    /// Positions this close count as matching.
+    const TOLERANCE: f32 = 0.02;
+
+    /// Parameter changes smaller than this are rounding, not a move.
+    const MOVED: f32 = 1e-3;
+
+    /// Name for display.
+    pub fn label(self) -> &'static str {
+        match self {
+            Takeover::Jump => "Jump",
+            Takeover::Pickup => "Pickup",
+            Takeover::Scaling => "Scaling",
+        }
+    }
+}
+
+/// What a button does to its parameter.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ButtonMode {
+    /// Maximum while held, minimum when released.
+    #[default]
+    Momentary,
+    /// Each press switches between minimum and maximum.
+    Toggle,
+    /// Each press steps to the next value, wrapping back to the first.
+    Cycle,
+}
+
+impl ButtonMode {
+    /// All button modes, in menu order.
+    pub const ALL: [ButtonMode; 3] = [ButtonMode::Momentary, ButtonMode::Toggle, ButtonMode::Cycle];
+
+    /// Name for display.
+    pub fn label(self) -> &'static str {
+        match self {
+            ButtonMode::Momentary => "Momentary",
+            ButtonMode::Toggle => "Toggle",
+            ButtonMode::Cycle => "Cycle",
+        }
+    }
+
+    /// The parameter value for a press or release, or None to leave it.
+    ///
+    /// `off` and `on` are the values a button switches between; Cycle steps
+    /// by one from `off` towards `on`, as for discrete parameters.
+    pub fn value(self, pressed: bool, current: f32, off: f32, on: f32) -> Option<f32> {
+        match self {
+            ButtonMode::Momentary => Some(if pressed { on } else { off }),
+            ButtonMode::Toggle if pressed => {
+                Some(if (current - off).abs() <= (current - on).abs() { on } else { off })
+            }
+            ButtonMode::Cycle if pressed => {
+                let step = if on >= off { 1.0 } else { -1.0 };
+                let next = current.round() + step;
+                let wrapped = if on >= off { next > on } else { next < on };
+                Some(if wrapped { off } else { next })
+            }
+            ButtonMode::Toggle | ButtonMode::Cycle => None,
+        }
+    }
+}
+
+/// How a mapping responds to its controller; the settings of a mapped
+/// knob's menu.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MappingOptions {
+    /// Absolute control or relative encoder.
+    pub encoder: EncoderMode,
+    /// Response curve.
+    pub curve: ResponseCurve,
+    /// Whether the controller's direction is reversed.
+    pub inverted: bool,
+    /// Takeover mode.
+    pub takeover: Takeover,
+    /// Button mode (None = a continuous control).
+    pub button: Option<ButtonMode>,
+    /// Whether the parameter's value is sent back to the controller.
+    pub feedback: bool,
+}
+
+/// An app action a button can trigger instead of setting a parameter.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum MidiAction {
+    /// Start or stop audio processing.
+    PlayStop,
+    /// Recall the scene after the last one recalled.
+    NextScene,
+    /// Release every note, in the patch and on MIDI outputs.
+    Panic,
+    /// Open the next patch in the library.
+    NextPatch,
+}
+
+impl MidiAction {
+    /// All actions, in menu order.
+    pub const ALL: [MidiAction; 4] = [MidiAction::PlayStop, MidiAction::NextScene, MidiAction::Panic, MidiAction::NextPatch];
+
+    /// Name for display.
+    pub fn label(self) -> &'static str {
+        match self {
+            MidiAction::PlayStop => "Play / Stop",
+            MidiAction::NextScene => "Next Scene",
+            MidiAction::Panic => "Panic",
+            MidiAction::NextPatch => "Next Patch",
+        }
+    }
+}
+
+/// A controller message, as seen by mappings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlMessage {
+    /// A Control Change.
+    Cc {
+        /// Controller number (0-127).
+        controller: u8,
+        /// Value (0-127).
+        value: u8,
+    },
+    /// A complete NRPN or RPN value.
+    ParameterNumber {
+        /// Whether this is an RPN rather than an NRPN.
+        registered: bool,
+        /// Parameter number (0-16383).
+        number: u16,
+        /// 14-bit value (0-16383).
+        value: u16,
+    },
+    /// A Note On, or a Note Off as velocity 0.
+    Note {
+        /// Note number (0-127).
+        note: u8,
+        /// Velocity (0 = released).
+        velocity: u8,
+    },
+}
+
+/// Runtime state of one mapping: the halves of a 14-bit CC and where the
+/// controller and parameter were last. Not saved with the patch.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ControllerState {
+    /// Last MSB of a 14-bit CC.
+    pub(super) msb: u8,
+    /// Last LSB of a 14-bit CC.
+    pub(super) lsb: u8,
+    /// Last controller position (0.0-1.0).
+    last_position: Option<f32>,
+    /// Parameter position the mapping last set (0.0-1.0).
+    last_output: Option<f32>,
+    /// Whether the controller has picked up the parameter.
+    picked_up: bool,
+}
+
+impl ControllerState {
+    /// The parameter position for a new controller position, or None to
+    /// leave the parameter alone.
+    ///
+    /// `current` is the parameter's position now. A parameter that was
+    /// moved by anything else since the last message must be picked up
+    /// again.
+    pub fn take_over(&mut self, takeover: Takeover, position: f32, current: f32) -> Option<f32> {
+        let last_position = self.last_position.replace(position);
+        if self.last_output.is_some_and(|output| (output - current).abs() > Takeover::MOVED) {
+            self.picked_up = false;
+        }
+        if (position - current).abs() <= Takeover::TOLERANCE {
+            self.picked_up = true;
+        }
+
+        let output = match takeover {
+            Takeover::Jump => Some(position),
+            Takeover::Pickup => {
+                // Passing the parameter's value between two messages also picks it up
+                let crossed = last_position.is_some_and(|last| (last - current) * (position - current) <= 0.0);
+                self.picked_up |= crossed;
+                self.picked_up.then_some(position)
+            }
+            Takeover::Scaling if self.picked_up => Some(position),
+            Takeover::Scaling => {
+                let last = last_position?;
+                let output = if position > last && last < 1.0 {
+                    current + (position - last) * (1.0 - current) / (1.0 - last)
+                } else if position < last && last > 0.0 {
+                    current - (last - position) * current / last
+                } else {
+                    current
+                };
+                Some(output.clamp(0.0, 1.0))
+            }
+        };
+        self.last_output = output.or(self.last_output);
+        output
+    }
+}
+
+/// What MIDI Learn found out about a controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LearnedControl {
+    /// What the controller sends.
+    pub control: ControlKind,
+    /// CC number (the MSB for a 14-bit CC, 0 for NRPN, RPN and notes).
+    pub cc_number: u8,
+    /// How the controller encodes its value.
+    pub encoder: EncoderMode,
+    /// Whether the control is a button: a note, or a CC that only sends 0 and 127.
+    pub button: bool,
+    /// MIDI input port the controller is on.
+    pub port: u8,
+}
+
+/// Works out what a controller sends during MIDI Learn.
+///
+/// Collects the controller messages received after learning starts and,
+/// once enough have arrived or a short time has passed, tells a 7-bit CC
+/// from a 14-bit pair, an NRPN/RPN or a note, an absolute control from a
+/// relative encoder, and a knob from a button.
+#[derive(Debug, Clone, Default)]
+pub struct MappingLearner {
+    /// Port and message of everything received, in order.
+    messages: Vec<(u8, ControlMessage)>,
+    /// When the first message arrived.
+    first: Option<Instant>,
+}
+
+impl MappingLearner {
+    /// Messages after which to decide.
+    pub const MESSAGES: usize = 8;
+    /// Time after the first message after which to decide.
+    pub const WINDOW: Duration = Duration::from_millis(400);
+
+    /// Creates a learner with nothing received.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget everything received.
+    pub fn reset(&mut self) {
+        self.messages.clear();
+        self.first = None;
+    }
+
+    /// Record a message received at `now` on `port`.
+    pub fn handle(&mut self, message: ControlMessage, port: u8, now: Instant) {
+        self.first.get_or_insert(now);
+        self.messages.push((port, message));
+    }
+
+    /// The learned control, once enough has been received; resets the learner.
+    pub fn finish(&mut self, now: Instant) -> Option<LearnedControl> {
+        let first = self.first?;
+        if self.messages.len() < Self::MESSAGES && now.saturating_duration_since(first) < Self::WINDOW {
+            return None;
+        }
+        let learned = self.detect();
+        self.reset();
+        learned
+    }
+
+    /// Work out the control from the messages received.
+    fn detect(&self) -> Option<LearnedControl> {
+        // NRPN and RPN arrive with their Control Changes; the assembled value wins
+        let parameter_number = self.messages.iter().find_map(|&(port, message)| match message {
+            ControlMessage::ParameterNumber { registered, number, .. } => Some((port, registered, number)),
+            _ => None,
+        });
+        if let Some((port, registered, number)) = parameter_number {
+            let control = if registered { ControlKind::Rpn(number) } else { ControlKind::Nrpn(number) };
+            return Some(LearnedControl { control, cc_number: 0, encoder: EncoderMode::Absolute, button: false, port });
+        }
+
+        let note = self.messages.iter().find_map(|&(port, message)| match message {
+            ControlMessage::Note { note, .. } => Some((port, note)),
+            _ => None,
+        });
+        if let Some((port, note)) = note {
+            let control = ControlKind::Note(note);
+            return Some(LearnedControl { control, cc_number: 0, encoder: EncoderMode::Absolute, button: true, port });
+        }
+
+        let ccs: Vec<(u8, u8, u8)> = self.messages
+            .iter()
+            .filter_map(|&(port, message)| match message {
+                ControlMessage::Cc { controller, value } => Some((port, controller, value)),
+                _ => None,
+            })
+            .collect();
+        let &(port, first, _) = ccs.first()?;
+        let sent = |controller: u8| ccs.iter().any(|&(p, c, _)| p == port && c == controller);
+
+        // A 14-bit pair sends its MSB on CC 0-31 and its LSB 32 above
+        let msb = if (32..64).contains(&first) && sent(first - 32) { first - 32 } else { first };
+        if msb < 32 && sent(msb + 32) {
+            let encoder = EncoderMode::Absolute;
+            return Some(LearnedControl { control: ControlKind::Cc14, cc_number: msb, encoder, button: false, port });
+        }
+
+        // Buttons send their extremes and nothing between
+        let values: Vec<u8> = ccs.iter().filter(|&&(p, c, _)| p == port && c == msb).map(|&(_, _, v)| v).collect();
+        let button = values.iter().all(|&value| value == 0 || value == 127);
+        let encoder = if button { EncoderMode::Absolute } else { detect_encoder(&values) };
+        Some(LearnedControl { control: ControlKind::Cc, cc_number: msb, encoder, button, port })
+    }
+}
+
+/// Tell a relative encoder from an absolute control by its values.
+///
+/// Absolute controls only send when their position changes, in small
+/// steps. Repeated values or big jumps mean an encoder sending changes,
+/// and the values it uses tell its encoding apart. An encoder only turned
+/// up can't be told from the others; turning it both ways while learning
+/// removes the doubt.
+fn detect_encoder(values: &[u8]) -> EncoderMode {
+    let relative = values.windows(2).any(|pair| pair[0] == pair[1] || pair[0].abs_diff(pair[1]) > 32);
+    if !relative {
+        return EncoderMode::Absolute;
+    }
+
+    let all = |ranges: &[std::ops::RangeInclusive<u8>]| {
+        values.iter().all(|value| ranges.iter().any(|range| range.contains(value)))
+    };
+    if all(&[57..=71]) {
+        EncoderMode::BinaryOffset
+    } else if all(&[1..=31, 65..=95]) && values.iter().any(|value| (65..=95).contains(value)) {
+        EncoderMode::SignMagnitude
+    } else if all(&[1..=31, 97..=127]) {
+        EncoderMode::TwosComplement
+    } else {
+        EncoderMode::Absolute
+    }
+}
+
+/// Rate-limits controller feedback: the values of mapped parameters sent
+/// back to motorized faders and LED rings.
+///
+/// Each mapped parameter sends at most once per interval; the latest value
+/// is held back and sent when the interval is up, so the controller always
+/// ends up on the final value. Values the controller already shows, such as
+/// the one it just sent, are not sent back.
+#[derive(Debug, Clone)]
+pub struct FeedbackThrottle {
+    /// Shortest time between two sends for one parameter.
+    interval: Duration,
+    /// What the controller shows for each parameter, and when it was last sent.
+    shown: HashMap<(u64, usize), (Vec<MidiEvent>, Option<Instant>)>,
+    /// Messages waiting for their parameter's interval to pass.
+    pending: HashMap<(u64, usize), Vec<MidiEvent>>,
+}
+
+impl FeedbackThrottle {
+    /// Default shortest time between two sends for one parameter.
+    pub const INTERVAL: Duration = Duration::from_millis(30);
+
+    /// Creates a throttle sending each parameter at most once per `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, shown: HashMap::new(), pending: HashMap::new() }
+    }
+
+    /// Note that the controller shows `messages` for a parameter already,
+    /// e.g. because it just sent them.
+    pub fn received(&mut self, key: (u64, usize), messages: Vec<MidiEvent>) {
+        self.pending.remove(&key);
+        let sent = self.shown.get(&key).and_then(|(_, sent)| *sent);
+        self.shown.insert(key, (messages, sent));
+    }
+
+    /// Queue the messages for a parameter's current value, unless the
+    /// controller shows them already.
+    pub fn update(&mut self, key: (u64, usize), messages: Vec<MidiEvent>) {
+        if self.shown.get(&key).is_some_and(|(shown, _)| *shown == messages) {
+            self.pending.remove(&key);
+        } else {
+            self.pending.insert(key, messages);
+        }
+    }
+
+    /// Take the queued messages whose interval has passed.
+    pub fn take_due(&mut self, now: Instant) -> Vec<MidiEvent> {
+        let due: Vec<(u64, usize)> = self
+            .pending
+            .keys()
+            .filter(|key| {
+                let sent = self.shown.get(key).and_then(|(_, sent)| *sent);
+                sent.is_none_or(|sent| now.duration_since(sent) >= self.interval)
+            })
+            .copied()
+            .collect();
+
+        let mut events = Vec::new();
+        for key in due {
+            if let Some(messages) = self.pending.remove(&key) {
+                events.extend_from_slice(&messages);
+                self.shown.insert(key, (messages, Some(now)));
+            }
+        }
+        events
+    }
+
+    /// Forget what the controller shows, so every value is sent again.
+    pub fn resend_all(&mut self) {
+        self.shown.clear();
+    }
+}
+
+impl Default for FeedbackThrottle {
+    fn default() -> Self {
+        Self::new(Self::INTERVAL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cc(controller: u8, value: u8) -> ControlMessage {
+        ControlMessage::Cc { controller, value }
+    }
+
+    fn learn(messages: &[ControlMessage]) -> LearnedControl {
+        let start = Instant::now();
+        let mut learner = MappingLearner::new();
+        for &message in messages {
+            learner.handle(message, 1, start);
+        }
+        learner.finish(start + MappingLearner::WINDOW).unwrap()
+    }
+
+    #[test]
+    fn test_encoder_deltas() {
+        assert_eq!(EncoderMode::TwosComplement.delta(1), 1);
+        assert_eq!(EncoderMode::TwosComplement.delta(127), -1);
+        assert_eq!(EncoderMode::BinaryOffset.delta(65), 1);
+        assert_eq!(EncoderMode::BinaryOffset.delta(60), -4);
+        assert_eq!(EncoderMode::SignMagnitude.delta(3), 3);
+        assert_eq!(EncoderMode::SignMagnitude.delta(65), -1);
+        assert_eq!(EncoderMode::Absolute.delta(100), 0);
+    }
+
+    #[test]
+    fn test_curves_invert() {
+        for curve in ResponseCurve::ALL {
+            assert_eq!(curve.apply(0.0), 0.0);
+            assert!((curve.apply(1.0) - 1.0).abs() < 1e-6);
+            for position in [0.1, 0.5, 0.9] {
+                assert!((curve.invert(curve.apply(position)) - position).abs() < 1e-5, "{:?}", curve);
+            }
+        }
+        assert!(ResponseCurve::Exponential.apply(0.5) < 0.5);
+        assert!(ResponseCurve::Logarithmic.apply(0.5) > 0.5);
+    }
+
+    #[test]
+    fn test_pickup_waits_for_the_controller() {
+        let mut state = ControllerState::default();
+        assert_eq!(state.take_over(Takeover::Pickup, 0.2, 0.5), None);
+        assert_eq!(state.take_over(Takeover::Pickup, 0.4, 0.5), None);
+        // Passing the parameter's value picks it up
+        assert_eq!(state.take_over(Takeover::Pickup, 0.6, 0.5), Some(0.6));
+        assert_eq!(state.take_over(Takeover::Pickup, 0.7, 0.6), Some(0.7));
+
+        // Moved elsewhere (a scene recall, say): wait again
+        assert_eq!(state.take_over(Takeover::Pickup, 0.8, 0.1), None);
+    }
+
+    #[test]
+    fn test_scaling_meets_at_the_end() {
+        let mut state = ControllerState::default();
+        assert_eq!(state.take_over(Takeover::Scaling, 0.5, 0.8), None);
+        // Half the controller's remaining travel moves the parameter half of its own
+        let output = state.take_over(Takeover::Scaling, 0.75, 0.8).unwrap();
+        assert!((output - 0.9).abs() < 1e-6);
+        let output = state.take_over(Takeover::Scaling, 1.0, output).unwrap();
+        assert!((output - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_learn_detects_controls() {
+        let absolute = learn(&[cc(74, 10), cc(74, 11), cc(74, 13), cc(74, 14)]);
+        assert_eq!((absolute.control, absolute.cc_number, absolute.encoder), (ControlKind::Cc, 74, EncoderMode::Absolute));
+        assert_eq!(absolute.port, 1);
+
+        let fine = learn(&[cc(1, 20), cc(33, 5), cc(1, 20), cc(33, 90)]);
+        assert_eq!((fine.control, fine.cc_number), (ControlKind::Cc14, 1));
+
+        let nrpn = learn(&[
+            cc(99, 3),
+            cc(98, 5),
+            cc(6, 64),
+            ControlMessage::ParameterNumber { registered: false, number: 389, value: 8192 },
+        ]);
+        assert_eq!(nrpn.control, ControlKind::Nrpn(389));
+    }
+
+    #[test]
+    fn test_learn_detects_encoders() {
+        let encoder = |values: &[u8]| {
+            let messages: Vec<_> = values.iter().map(|&value| cc(20, value)).collect();
+            learn(&messages).encoder
+        };
+        assert_eq!(encoder(&[1, 1, 2, 127, 127]), EncoderMode::TwosComplement);
+        assert_eq!(encoder(&[65, 65, 63, 62]), EncoderMode::BinaryOffset);
+        assert_eq!(encoder(&[1, 1, 65, 66]), EncoderMode::SignMagnitude);
+        assert_eq!(encoder(&[60, 61, 62, 63, 64, 65]), EncoderMode::Absolute);
+    }
+
+    #[test]
+    fn test_learn_detects_buttons() {
+        let pad = learn(&[
+            ControlMessage::Note { note: 36, velocity: 100 },
+            ControlMessage::Note { note: 36, velocity: 0 },
+        ]);
+        assert_eq!((pad.control, pad.button), (ControlKind::Note(36), true));
+
+        let button = learn(&[cc(20, 127), cc(20, 0)]);
+        assert_eq!((button.control, button.encoder, button.button), (ControlKind::Cc, EncoderMode::Absolute, true));
+        assert!(!learn(&[cc(20, 126), cc(20, 127)]).button);
+    }
+
+    #[test]
+    fn test_button_modes() {
+        assert_eq!(ButtonMode::Momentary.value(true, 0.0, 0.0, 1.0), Some(1.0));
+        assert_eq!(ButtonMode::Momentary.value(false, 1.0, 0.0, 1.0), Some(0.0));
+
+        assert_eq!(ButtonMode::Toggle.value(true, 0.0, 0.0, 1.0), Some(1.0));
+        assert_eq!(ButtonMode::Toggle.value(true, 1.0, 0.0, 1.0), Some(0.0));
+        assert_eq!(ButtonMode::Toggle.value(false, 1.0, 0.0, 1.0), None);
+
+        // Cycling through four choices wraps around, or runs backwards between swapped ends
+        assert_eq!(ButtonMode::Cycle.value(true, 2.0, 0.0, 3.0), Some(3.0));
+        assert_eq!(ButtonMode::Cycle.value(true, 3.0, 0.0, 3.0), Some(0.0));
+        assert_eq!(ButtonMode::Cycle.value(true, 0.0, 3.0, 0.0), Some(3.0));
+        assert_eq!(ButtonMode::Cycle.value(false, 0.0, 0.0, 3.0), None);
+    }
+
+    #[test]
+    fn test_learner_waits() {
+        let start = Instant::now();
+        let mut learner = MappingLearner::new();
+        assert_eq!(learner.finish(start), None);
+        learner.handle(cc(74, 1), 0, start);
+        assert_eq!(learner.finish(start + Duration::from_millis(100)), None);
+        assert!(learner.finish(start + MappingLearner::WINDOW).is_some());
+        // Finishing starts over
+        assert_eq!(learner.finish(start + MappingLearner::WINDOW), None);
+    }
+
+    #[test]
+    fn test_feedback_throttle() {
+        let start = Instant::now();
+        let later = |ms| start + Duration::from_millis(ms);
+        let value = |value| vec![MidiEvent::control_change(0, 0, 74, value)];
+        let mut throttle = FeedbackThrottle::new(Duration::from_millis(30));
+
+        // The first change goes out at once, the next waits for the interval
+        throttle.update((1, 0), value(10));
+        assert_eq!(throttle.take_due(start), value(10));
+        throttle.update((1, 0), value(11));
+        throttle.update((1, 0), value(12));
+        assert!(throttle.take_due(later(10)).is_empty());
+        assert_eq!(throttle.take_due(later(30)), value(12));
+
+        // Unchanged values and values the controller sent aren't sent back
+        throttle.update((1, 0), value(12));
+        assert!(throttle.take_due(later(100)).is_empty());
+        throttle.received((1, 0), value(90));
+        throttle.update((1, 0), value(90));
+        assert!(throttle.take_due(later(100)).is_empty());
+
+        // Other parameters have their own interval
+        throttle.update((2, 0), value(5));
+        assert_eq!(throttle.take_due(later(100)), value(5));
+
+        throttle.resend_all();
+        throttle.update((1, 0), value(90));
+        assert_eq!(throttle.take_due(later(101)), value(90));
+    }
+}
@@ -0,0 +1,199 @@
+//! Descriptive patch metadata.
+//!
+//! Everything here is optional and only used to organize patches in the
+//! library browser; none of it affects how a patch sounds.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::patch::Patch;
+
+/// Optional information about a patch.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PatchMetadata {
+    /// Who made the patch.
+    pub author: String,
+    /// Free-form description or notes.
+    pub description: String,
+    /// Lowercase tags, e.g. "bass", "ambient".
+    pub tags: Vec<String>,
+    /// When the patch was first saved (seconds since the Unix epoch).
+    pub created: Option<u64>,
+    /// When the patch was last saved (seconds since the Unix epoch).
+    pub modified: Option<u64>,
+    /// Intended tempo in BPM.
+    pub tempo: Option<f32>,
+    /// Miniature of the node graph, shown in the library browser.
+    pub thumbnail: Option<PatchThumbnail>,
+}
+
+impl PatchMetadata {
+    /// Record a save at `now`, setting the creation time on the first save.
+    pub fn touch(&mut self, now: u64) {
+        self.created.get_or_insert(now);
+        self.modified = Some(now);
+    }
+
+    /// Replace the tags from a comma-separated list.
+    ///
+    /// Tags are trimmed and lowercased; empty and duplicate tags are dropped.
+    pub fn set_tags(&mut self, list: &str) {
+        self.tags.clear();
+        for tag in list.split(',') {
+            let tag = tag.trim().to_lowercase();
+            if !tag.is_empty() && !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+    }
+
+    /// Tags as a comma-separated list, the inverse of [`set_tags`](Self::set_tags).
+    pub fn tags_string(&self) -> String {
+        self.tags.join(", ")
+    }
+
+    /// Whether the patch has the given tag (case-insensitive).
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+}
+
+/// A miniature of a patch's node graph.
+///
+/// Node positions are normalized to 0.0-1.0 over the patch's bounding box,
+/// so the thumbnail can be drawn at any size without loading the patch.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PatchThumbnail {
+    /// Normalized node positions.
+    pub nodes: Vec<(f32, f32)>,
+    /// Connections as indices into `nodes` (from, to).
+    pub connections: Vec<(usize, usize)>,
+}
+
+impl PatchThumbnail {
+    /// Build a thumbnail from a patch's nodes and connections.
+    pub fn from_patch(patch: &Patch) -> Self {
+        let (min_x, min_y) = patch.origin();
+        let (max_x, max_y) = patch.nodes.iter().fold((min_x, min_y), |(x, y), node| {
+            (x.max(node.position.0), y.max(node.position.1))
+        });
+
+        // A single row or column of nodes is centered on that axis
+        let normalize = |value: f32, min: f32, max: f32| {
+            if max > min {
+                (value - min) / (max - min)
+            } else {
+                0.5
+            }
+        };
+
+        let nodes = patch.nodes
+            .iter()
+            .map(|node| {
+                (
+                    normalize(node.position.0, min_x, max_x),
+                    normalize(node.position.1, min_y, max_y),
+                )
+            })
+            .collect();
+
+        let index_of = |id: u64| patch.nodes.iter().position(|node| node.id == id);
+        let connections = patch.connections
+            .iter()
+            .filter_map(|conn| Some((index_of(conn.from_node)?, index_of(conn.to_node)?)))
+            .collect();
+
+        Self { nodes, connections }
+    }
+}
+
+/// Current time in seconds since the Unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Format a Unix timestamp as a UTC date and time, e.g. "2024-03-09 14:05".
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let minutes_of_day = secs % 86_400 / 60;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        minutes_of_day / 60,
+        minutes_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::patch::{ConnectionData, NodeData};
+
+    #[test]
+    fn test_touch() {
+        let mut metadata = PatchMetadata::default();
+        metadata.touch(100);
+        assert_eq!(metadata.created, Some(100));
+        assert_eq!(metadata.modified, Some(100));
+
+        metadata.touch(200);
+        assert_eq!(metadata.created, Some(100));
+        assert_eq!(metadata.modified, Some(200));
+    }
+
+    #[test]
+    fn test_set_tags() {
+        let mut metadata = PatchMetadata::default();
+        metadata.set_tags(" Bass, acid,, bass ,Mono");
+        assert_eq!(metadata.tags, vec!["bass", "acid", "mono"]);
+        assert_eq!(metadata.tags_string(), "bass, acid, mono");
+        assert!(metadata.has_tag("ACID"));
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00");
+        assert_eq!(format_timestamp(1_709_993_100), "2024-03-09 14:05");
+    }
+
+    #[test]
+    fn test_thumbnail() {
+        let mut patch = Patch::new("Thumb");
+        patch.nodes.push(NodeData::new(7, "osc.sine", (100.0, 50.0)));
+        patch.nodes.push(NodeData::new(9, "output.audio", (300.0, 50.0)));
+        patch.connections.push(ConnectionData::new(7, "Out", 9, "Left"));
+        patch.connections.push(ConnectionData::new(7, "Out", 42, "Left"));
+
+        let thumbnail = PatchThumbnail::from_patch(&patch);
+        assert_eq!(thumbnail.nodes, vec![(0.0, 0.5), (1.0, 0.5)]);
+        // Connections to missing nodes are skipped
+        assert_eq!(thumbnail.connections, vec![(0, 1)]);
+    }
+
+    #[test]
+    fn test_patches_without_metadata_still_load() {
+        let json = r#"{"name":"Old","version":2,"nodes":[],"connections":[]}"#;
+        let patch: Patch = serde_json::from_str(json).unwrap();
+        assert_eq!(patch.metadata, PatchMetadata::default());
+    }
+}
@@ -1,10 +1,42 @@
 //! Persistence module
 //!
-//! Patch save/load functionality using serde and JSON.
+//! Patch save/load functionality using serde and JSON, plus autosave,
+//! crash recovery, patch diffing for hot-reload, a text patch language,
+//! the patch library, module presets, parameter scenes, randomization,
+//! linting, MIDI controller mappings, setlists and the application
+//! configuration.
 
+pub mod autosave;
+pub mod config;
+pub mod diff;
+pub mod dsl;
+pub mod library;
+pub mod lint;
+pub mod mapping;
+pub mod metadata;
 pub mod patch;
+pub mod paths;
+pub mod preset;
+pub mod randomize;
+pub mod scene;
+pub mod setlist;
 
+pub use autosave::{AutosaveSession, DirtyTracker, RecoveryData, AUTOSAVE_INTERVAL};
+pub use config::{AppConfig, WindowState};
+pub use diff::{ParameterChange, PatchDiff};
+pub use dsl::{DslError, DSL_EXTENSION};
+pub use library::{LibraryEntry, PatchLibrary};
+pub use lint::{Lint, Severity};
+pub use mapping::{
+    ButtonMode, ControlKind, ControlMessage, ControllerState, EncoderMode, FeedbackThrottle, LearnedControl,
+    MappingLearner, MappingOptions, MidiAction, ResponseCurve, Takeover,
+};
+pub use metadata::{format_timestamp, unix_now, PatchMetadata, PatchThumbnail};
 pub use patch::{
-    ConnectionData, MidiMapping, NodeData, ParameterValue, Patch, PatchError,
+    ConnectionData, MidiMapping, NodeData, ParameterValue, Patch, PatchError, TuningData,
     load_from_file, save_to_file, PATCH_VERSION,
 };
+pub use preset::{ModulePreset, PresetStore};
+pub use randomize::{ParameterLock, Variation};
+pub use scene::{Scene, SceneValue};
+pub use setlist::{program_label, PatchPreloader, ProgramSelect, Setlist, SetlistEntry};
@@ -4,21 +4,46 @@
 //! to JSON files. A patch captures the complete state of the node graph including
 //! all nodes, their positions, parameter values, and connections.
 
-use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::dsp::MidiEvent;
+use super::dsl::DslError;
+use super::mapping::{
+    ButtonMode, ControlKind, ControlMessage, ControllerState, EncoderMode, MappingOptions, MidiAction, ResponseCurve,
+    Takeover,
+};
+use super::metadata::PatchMetadata;
+use super::randomize::ParameterLock;
+use super::scene::Scene;
 
 /// Current patch format version.
 /// Increment this when making breaking changes to the format.
 pub const PATCH_VERSION: u32 = 2;
 
-/// A MIDI CC to parameter mapping.
+/// A MIDI controller to parameter mapping.
+///
+/// Maps a hardware MIDI controller to a synthesizer parameter. The
+/// controller can be a 7-bit or 14-bit CC or an NRPN/RPN, absolute or a
+/// relative encoder, with a response curve and a takeover mode. Notes and
+/// CC buttons can set a parameter as a button, or trigger an app action
+/// instead.
 ///
-/// Maps a hardware MIDI CC controller to a synthesizer parameter.
+/// Loading a mapping whose numbers are out of range (see `is_valid`) fails.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(remote = "Self")]
 pub struct MidiMapping {
-    /// CC number (0-127).
+    /// CC number (0-127; 0-31 for a 14-bit CC).
     pub cc_number: u8,
     /// MIDI channel (0 = omni/any channel, 1-16 = specific channel).
     pub channel: u8,
+    /// Name of the MIDI input device to listen to (None = any input).
+    /// Inputs are matched by name, since their port numbers change as
+    /// devices are connected and removed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
     /// Target engine node ID.
     pub node_id: u64,
     /// Target parameter index within the node.
@@ -29,6 +54,30 @@ pub struct MidiMapping {
     pub min_value: f32,
     /// Maximum value of the mapped range.
     pub max_value: f32,
+    /// What the controller sends (optional for backwards compatibility).
+    #[serde(default)]
+    pub control: ControlKind,
+    /// Absolute control or relative encoder (optional).
+    #[serde(default)]
+    pub encoder: EncoderMode,
+    /// Response curve (optional).
+    #[serde(default)]
+    pub curve: ResponseCurve,
+    /// Whether the controller's direction is reversed (optional).
+    #[serde(default)]
+    pub inverted: bool,
+    /// Takeover mode (optional).
+    #[serde(default)]
+    pub takeover: Takeover,
+    /// Button mode (None = a continuous control; notes are always buttons).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub button: Option<ButtonMode>,
+    /// App action to trigger instead of setting the parameter (optional).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<MidiAction>,
+    /// Whether the parameter's value is sent back to the controller (optional).
+    #[serde(default)]
+    pub feedback: bool,
 }
 
 impl MidiMapping {
@@ -45,23 +94,319 @@ impl MidiMapping {
         Self {
             cc_number,
             channel,
+            device: None,
             node_id,
             param_index,
             param_name: param_name.into(),
             min_value,
             max_value,
+            control: ControlKind::Cc,
+            encoder: EncoderMode::Absolute,
+            curve: ResponseCurve::Linear,
+            inverted: false,
+            takeover: Takeover::Jump,
+            button: None,
+            action: None,
+            feedback: false,
+        }
+    }
+
+    /// Create a mapping that triggers an app action. It has no target
+    /// parameter.
+    pub fn for_action(action: MidiAction, control: ControlKind, cc_number: u8) -> Self {
+        Self {
+            control,
+            action: Some(action),
+            ..Self::new(cc_number, 0, 0, 0, action.label(), 0.0, 1.0)
+        }
+    }
+
+    /// Only respond to one MIDI input device (None = any input).
+    pub fn with_device(mut self, device: Option<String>) -> Self {
+        self.device = device;
+        self
+    }
+
+    /// Set what the controller sends.
+    pub fn with_control(mut self, control: ControlKind) -> Self {
+        self.control = control;
+        self
+    }
+
+    /// Set how the controller encodes its value.
+    pub fn with_encoder(mut self, encoder: EncoderMode) -> Self {
+        self.encoder = encoder;
+        self
+    }
+
+    /// Use the control as a button.
+    pub fn with_button(mut self, button: ButtonMode) -> Self {
+        self.button = Some(button);
+        self
+    }
+
+    /// How the mapping responds to its controller.
+    pub fn options(&self) -> MappingOptions {
+        MappingOptions {
+            encoder: self.encoder,
+            curve: self.curve,
+            inverted: self.inverted,
+            takeover: self.takeover,
+            button: self.button_mode(),
+            feedback: self.feedback,
+        }
+    }
+
+    /// Change how the mapping responds to its controller.
+    pub fn set_options(&mut self, options: MappingOptions) {
+        self.encoder = options.encoder;
+        self.curve = options.curve;
+        self.inverted = options.inverted;
+        self.takeover = options.takeover;
+        self.button = options.button;
+        self.feedback = options.feedback;
+    }
+
+    /// How the control acts as a button, or None for a continuous control.
+    pub fn button_mode(&self) -> Option<ButtonMode> {
+        match self.control {
+            ControlKind::Note(_) => Some(self.button.unwrap_or_default()),
+            ControlKind::Cc => self.button,
+            _ => None,
+        }
+    }
+
+    /// Whether the mapping's numbers are in range: a CC of 0-127 (0-31 for
+    /// the MSB of a 14-bit CC), a parameter number of 0-16383 or a note of
+    /// 0-127.
+    pub fn is_valid(&self) -> bool {
+        match self.control {
+            ControlKind::Cc => self.cc_number < 128,
+            ControlKind::Cc14 => self.cc_number < 32,
+            ControlKind::Nrpn(number) | ControlKind::Rpn(number) => number < 16384,
+            ControlKind::Note(note) => note < 128,
         }
     }
 
-    /// Check if this mapping matches a given CC event.
-    pub fn matches(&self, cc_number: u8, channel: u8) -> bool {
-        self.cc_number == cc_number && (self.channel == 0 || self.channel == channel + 1)
+    /// The CC carrying the LSB of a 14-bit mapping (32 above its MSB).
+    fn lsb_number(&self) -> Option<u8> {
+        match self.control {
+            ControlKind::Cc14 => self.cc_number.checked_add(32).filter(|lsb| *lsb < 128),
+            _ => None,
+        }
+    }
+
+    /// Whether a message presses (true) or releases (false) this mapping's
+    /// note or CC button; None if it's for another control.
+    ///
+    /// `channel` is the 0-based channel of the message and `device` the
+    /// input it arrived on. CC buttons count as pressed from 64 up.
+    pub fn pressed(&self, message: ControlMessage, channel: u8, device: Option<&str>) -> Option<bool> {
+        if !self.accepts(channel, device) {
+            return None;
+        }
+        match (self.control, message) {
+            (ControlKind::Note(mapped), ControlMessage::Note { note, velocity }) if note == mapped => Some(velocity > 0),
+            (ControlKind::Cc, ControlMessage::Cc { controller, value }) if controller == self.cc_number => Some(value >= 64),
+            _ => None,
+        }
+    }
+
+    /// Check if this mapping listens to a given CC event.
+    ///
+    /// `channel` is the 0-based channel of the event and `device` the input
+    /// it arrived on. A 14-bit mapping listens to both of its CCs.
+    pub fn matches(&self, cc_number: u8, channel: u8, device: Option<&str>) -> bool {
+        let cc_matches = match self.control {
+            ControlKind::Cc => self.cc_number == cc_number,
+            ControlKind::Cc14 => self.cc_number == cc_number || self.lsb_number() == Some(cc_number),
+            ControlKind::Nrpn(_) | ControlKind::Rpn(_) | ControlKind::Note(_) => false,
+        };
+        cc_matches && self.accepts(channel, device)
+    }
+
+    /// Whether this mapping sets a given parameter (action mappings set none).
+    pub fn targets(&self, node_id: u64, param_index: usize) -> bool {
+        self.action.is_none() && self.node_id == node_id && self.param_index == param_index
+    }
+
+    /// Check if this mapping listens to a channel (0-based) and input device.
+    pub fn accepts(&self, channel: u8, device: Option<&str>) -> bool {
+        (self.channel == 0 || self.channel == channel + 1)
+            && (self.device.is_none() || self.device.as_deref() == device)
+    }
+
+    /// Whether two mappings listen to the same control, ignoring channel and
+    /// port. A 14-bit CC also clashes with a 7-bit CC on its LSB.
+    pub fn shares_control(&self, other: &MidiMapping) -> bool {
+        match (self.control, other.control) {
+            (ControlKind::Cc14, ControlKind::Cc) => {
+                self.cc_number == other.cc_number || self.lsb_number() == Some(other.cc_number)
+            }
+            (ControlKind::Cc, ControlKind::Cc14) => other.shares_control(self),
+            (ControlKind::Cc | ControlKind::Cc14, ControlKind::Cc | ControlKind::Cc14) => self.cc_number == other.cc_number,
+            (ControlKind::Nrpn(a), ControlKind::Nrpn(b)) | (ControlKind::Rpn(a), ControlKind::Rpn(b)) => a == b,
+            (ControlKind::Note(a), ControlKind::Note(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Name of the control for display, e.g. "CC 74", "CC 1+33" or "NRPN 389".
+    pub fn control_label(&self) -> String {
+        match self.control {
+            ControlKind::Cc => format!("CC {}", self.cc_number),
+            ControlKind::Cc14 => match self.lsb_number() {
+                Some(lsb) => format!("CC {}+{}", self.cc_number, lsb),
+                None => format!("CC {}", self.cc_number),
+            },
+            ControlKind::Nrpn(number) => format!("NRPN {}", number),
+            ControlKind::Rpn(number) => format!("RPN {}", number),
+            ControlKind::Note(note) => format!("Note {}", note),
+        }
     }
 
     /// Convert a CC value (0-127) to the mapped parameter range.
     pub fn cc_to_value(&self, cc_value: u8) -> f32 {
-        let normalized = cc_value as f32 / 127.0;
-        self.min_value + normalized * (self.max_value - self.min_value)
+        self.position_to_value(cc_value as f32 / 127.0)
+    }
+
+    /// Convert a controller position (0.0-1.0) to the mapped parameter
+    /// range, through the response curve.
+    pub fn position_to_value(&self, position: f32) -> f32 {
+        let position = if self.inverted { 1.0 - position } else { position };
+        self.min_value + self.curve.apply(position) * (self.max_value - self.min_value)
+    }
+
+    /// The controller position (0.0-1.0) that gives a parameter value.
+    pub fn value_to_position(&self, value: f32) -> f32 {
+        let range = self.max_value - self.min_value;
+        let amount = if range == 0.0 { 0.0 } else { (value - self.min_value) / range };
+        let position = self.curve.invert(amount);
+        if self.inverted { 1.0 - position } else { position }
+    }
+
+    /// The messages that show a parameter value on the controller: the
+    /// controller's own messages, as if it had been moved to the value.
+    ///
+    /// Buttons light up (note or CC at 127) when the value is nearer the
+    /// "on" end. Omni mappings reply on channel 1. Action mappings have no
+    /// value to show.
+    pub fn feedback_messages(&self, value: f32) -> Vec<MidiEvent> {
+        if self.action.is_some() {
+            return Vec::new();
+        }
+
+        let channel = self.channel.saturating_sub(1);
+        let position = self.value_to_position(value).clamp(0.0, 1.0);
+        let cc = |controller: u8, value: u16| MidiEvent::control_change(0, channel, controller, (value & 0x7F) as u8);
+
+        if self.button_mode().is_some() {
+            let level = if position >= 0.5 { 127 } else { 0 };
+            return match self.control {
+                ControlKind::Note(note) => vec![MidiEvent::note_on(0, channel, note, level)],
+                _ => vec![cc(self.cc_number, level as u16)],
+            };
+        }
+
+        let fine = (position * 16383.0).round() as u16;
+        match self.control {
+            ControlKind::Cc => vec![cc(self.cc_number, (position * 127.0).round() as u16)],
+            ControlKind::Cc14 => match self.lsb_number() {
+                Some(lsb) => vec![cc(self.cc_number, fine >> 7), cc(lsb, fine)],
+                None => Vec::new(),
+            },
+            ControlKind::Nrpn(number) | ControlKind::Rpn(number) => {
+                let (select_msb, select_lsb) = if matches!(self.control, ControlKind::Rpn(_)) { (101, 100) } else { (99, 98) };
+                vec![
+                    cc(select_msb, number >> 7),
+                    cc(select_lsb, number),
+                    cc(6, fine >> 7),
+                    cc(38, fine),
+                ]
+            }
+            ControlKind::Note(_) => Vec::new(),
+        }
+    }
+
+    /// The new parameter value for a controller message, or None if the
+    /// message isn't for this mapping or the takeover mode holds the
+    /// parameter.
+    ///
+    /// `channel` is the 0-based channel of the message, `device` the input
+    /// it arrived on and `current` the parameter's value now.
+    pub fn handle(
+        &self,
+        message: ControlMessage,
+        channel: u8,
+        device: Option<&str>,
+        current: f32,
+        state: &mut ControllerState,
+    ) -> Option<f32> {
+        if self.action.is_some() || !self.accepts(channel, device) {
+            return None;
+        }
+
+        if let Some(button) = self.button_mode() {
+            let (off, on) = if self.inverted {
+                (self.max_value, self.min_value)
+            } else {
+                (self.min_value, self.max_value)
+            };
+            return button.value(self.pressed(message, channel, device)?, current, off, on);
+        }
+
+        let position = match (self.control, message) {
+            (ControlKind::Cc, ControlMessage::Cc { controller, value }) if controller == self.cc_number => {
+                if self.encoder.is_relative() {
+                    // Each step of an encoder moves one 7-bit step, from wherever the parameter is
+                    let steps = self.encoder.delta(value) as f32;
+                    let position = self.value_to_position(current) + steps / 127.0;
+                    return Some(self.position_to_value(position.clamp(0.0, 1.0)));
+                }
+                value as f32 / 127.0
+            }
+            (ControlKind::Cc14, ControlMessage::Cc { controller, value }) if controller == self.cc_number => {
+                // A new MSB clears the LSB
+                state.msb = value;
+                state.lsb = 0;
+                ((u16::from(state.msb) << 7) | u16::from(state.lsb)) as f32 / 16383.0
+            }
+            (ControlKind::Cc14, ControlMessage::Cc { controller, value }) if self.lsb_number() == Some(controller) => {
+                state.lsb = value;
+                ((u16::from(state.msb) << 7) | u16::from(state.lsb)) as f32 / 16383.0
+            }
+            (ControlKind::Nrpn(mapped), ControlMessage::ParameterNumber { registered: false, number, value })
+            | (ControlKind::Rpn(mapped), ControlMessage::ParameterNumber { registered: true, number, value })
+                if number == mapped =>
+            {
+                value as f32 / 16383.0
+            }
+            _ => return None,
+        };
+
+        let current = self.value_to_position(current);
+        state
+            .take_over(self.takeover, position, current)
+            .map(|position| self.position_to_value(position))
+    }
+}
+
+impl Serialize for MidiMapping {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MidiMapping::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MidiMapping {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mapping = MidiMapping::deserialize(deserializer)?;
+        if !mapping.is_valid() {
+            return Err(serde::de::Error::custom(format!(
+                "MIDI mapping of {} has an out-of-range control number",
+                mapping.param_name
+            )));
+        }
+        Ok(mapping)
     }
 }
 
@@ -82,6 +427,18 @@ pub struct Patch {
     /// MIDI CC mappings (optional for backwards compatibility).
     #[serde(default)]
     pub midi_mappings: Vec<MidiMapping>,
+    /// Author, tags and other library information (optional).
+    #[serde(default)]
+    pub metadata: PatchMetadata,
+    /// Parameter scenes (optional).
+    #[serde(default)]
+    pub scenes: Vec<Scene>,
+    /// Parameters excluded from randomization (optional).
+    #[serde(default)]
+    pub locks: Vec<ParameterLock>,
+    /// Tuning of the note modules (optional, None = 12-TET).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tuning: Option<TuningData>,
 }
 
 impl Patch {
@@ -93,6 +450,10 @@ impl Patch {
             nodes: Vec::new(),
             connections: Vec::new(),
             midi_mappings: Vec::new(),
+            metadata: PatchMetadata::default(),
+            scenes: Vec::new(),
+            locks: Vec::new(),
+            tuning: None,
         }
     }
 
@@ -100,6 +461,65 @@ impl Patch {
     pub fn is_compatible(&self) -> bool {
         self.version <= PATCH_VERSION
     }
+
+    /// Extract a patch fragment containing only the given nodes.
+    ///
+    /// Connections are kept only when both ends are inside the selection.
+    /// MIDI mappings are not carried over, since pasting them would bind the
+    /// same controller to several parameters, and neither are scenes or locks.
+    pub fn extract(&self, node_ids: &[u64]) -> Patch {
+        let mut fragment = Patch::new(self.name.clone());
+
+        fragment.nodes = self.nodes
+            .iter()
+            .filter(|node| node_ids.contains(&node.id))
+            .cloned()
+            .collect();
+
+        fragment.connections = self.connections
+            .iter()
+            .filter(|conn| node_ids.contains(&conn.from_node) && node_ids.contains(&conn.to_node))
+            .cloned()
+            .collect();
+
+        fragment
+    }
+
+    /// Change node IDs through `ids`, everywhere the patch refers to a node.
+    ///
+    /// IDs missing from `ids` are kept.
+    pub fn renumber(&mut self, ids: &HashMap<u64, u64>) {
+        let map = |id: u64| ids.get(&id).copied().unwrap_or(id);
+
+        for node in &mut self.nodes {
+            node.id = map(node.id);
+        }
+        for conn in &mut self.connections {
+            conn.from_node = map(conn.from_node);
+            conn.to_node = map(conn.to_node);
+        }
+        for mapping in self.midi_mappings.iter_mut().filter(|m| m.action.is_none()) {
+            mapping.node_id = map(mapping.node_id);
+        }
+        self.scenes = self.scenes.iter().map(|scene| scene.retarget(|id| Some(map(id)))).collect();
+        for lock in &mut self.locks {
+            lock.node_id = map(lock.node_id);
+        }
+        self.locks.sort_by_key(|lock| (lock.node_id, lock.param_index));
+    }
+
+    /// Top-left corner of the bounding box of all node positions.
+    ///
+    /// Returns (0.0, 0.0) for an empty patch.
+    pub fn origin(&self) -> (f32, f32) {
+        if self.nodes.is_empty() {
+            return (0.0, 0.0);
+        }
+
+        self.nodes.iter().fold((f32::MAX, f32::MAX), |(x, y), node| {
+            (x.min(node.position.0), y.min(node.position.1))
+        })
+    }
 }
 
 impl Default for Patch {
@@ -109,7 +529,7 @@ impl Default for Patch {
 }
 
 /// Serialized data for a single node in the patch.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct NodeData {
     /// Unique identifier for this node within the patch.
     /// Used for referencing in connections.
@@ -122,6 +542,16 @@ pub struct NodeData {
     /// Parameter values in order they appear in the node.
     /// These are the actual values (Hz for frequency, seconds for time, etc.).
     pub parameters: Vec<ParameterValue>,
+    /// File used by the node, such as the MIDI file of a MIDI File Player
+    /// (optional for backwards compatibility).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    /// Tuning of the node, overriding the patch tuning (optional).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tuning: Option<TuningData>,
+    /// Name of the MIDI input device the node listens to (None = any input).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub midi_input: Option<String>,
 }
 
 impl NodeData {
@@ -132,12 +562,47 @@ impl NodeData {
             module_id: module_id.into(),
             position,
             parameters: Vec::new(),
+            file: None,
+            tuning: None,
+            midi_input: None,
+        }
+    }
+}
+
+/// A Scala tuning, kept as the text of its files so the patch doesn't
+/// depend on them.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TuningData {
+    /// Name of the scale, usually its file name.
+    pub name: String,
+    /// Contents of the `.scl` scale file.
+    pub scale: String,
+    /// Contents of the `.kbm` keyboard mapping file (None = the default
+    /// mapping, degree 0 on middle C).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mapping: Option<String>,
+    /// Name of the keyboard mapping, usually its file name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mapping_name: Option<String>,
+}
+
+impl TuningData {
+    /// A tuning of a scale with the default keyboard mapping.
+    pub fn new(name: impl Into<String>, scale: impl Into<String>) -> Self {
+        Self { name: name.into(), scale: scale.into(), mapping: None, mapping_name: None }
+    }
+
+    /// Display name: the scale name, with the mapping name if any.
+    pub fn label(&self) -> String {
+        match &self.mapping_name {
+            Some(mapping) => format!("{} · {}", self.name, mapping),
+            None => self.name.clone(),
         }
     }
 }
 
 /// A parameter value that preserves type information for proper restoration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type", content = "value")]
 pub enum ParameterValue {
     /// Scalar value (0.0-1.0 range).
@@ -169,10 +634,23 @@ impl ParameterValue {
             Self::Select(v) => *v as f32,
         }
     }
+
+    /// A value of the same kind holding `value` (rounded for toggles and selections).
+    pub fn with_f32(&self, value: f32) -> Self {
+        match self {
+            Self::Scalar(_) => Self::Scalar(value),
+            Self::Frequency(_) => Self::Frequency(value),
+            Self::LinearHz(_) => Self::LinearHz(value),
+            Self::Time(_) => Self::Time(value),
+            Self::LinearRange(_) => Self::LinearRange(value),
+            Self::Toggle(_) => Self::Toggle(value >= 0.5),
+            Self::Select(_) => Self::Select(value.round().max(0.0) as usize),
+        }
+    }
 }
 
 /// Serialized data for a connection between two nodes.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ConnectionData {
     /// Source node ID.
     pub from_node: u64,
@@ -212,6 +690,8 @@ pub enum PatchError {
     IncompatibleVersion { found: u32, expected: u32 },
     /// Unknown module type in patch.
     UnknownModule(String),
+    /// Error in a patch script.
+    ScriptError(DslError),
 }
 
 impl std::fmt::Display for PatchError {
@@ -223,6 +703,7 @@ impl std::fmt::Display for PatchError {
                 write!(f, "Incompatible patch version: found {}, expected <= {}", found, expected)
             }
             Self::UnknownModule(id) => write!(f, "Unknown module type: {}", id),
+            Self::ScriptError(e) => write!(f, "Script error: {}", e),
         }
     }
 }
@@ -232,6 +713,7 @@ impl std::error::Error for PatchError {
         match self {
             Self::IoError(e) => Some(e),
             Self::SerializationError(e) => Some(e),
+            Self::ScriptError(e) => Some(e),
             _ => None,
         }
     }
@@ -249,6 +731,12 @@ impl From<serde_json::Error> for PatchError {
     }
 }
 
+impl From<DslError> for PatchError {
+    fn from(err: DslError) -> Self {
+        Self::ScriptError(err)
+    }
+}
+
 /// Save a patch to a JSON file.
 pub fn save_to_file(patch: &Patch, path: &std::path::Path) -> Result<(), PatchError> {
     let json = serde_json::to_string_pretty(patch)?;
@@ -296,6 +784,9 @@ mod tests {
                 ParameterValue::Frequency(440.0),
                 ParameterValue::Scalar(0.5),
             ],
+            file: None,
+            tuning: None,
+            midi_input: None,
         });
         patch.connections.push(ConnectionData::new(1, "Out", 2, "In"));
 
@@ -307,6 +798,38 @@ mod tests {
         assert_eq!(loaded.connections.len(), 1);
     }
 
+    #[test]
+    fn test_node_file_is_optional() {
+        let node = NodeData::new(1, "osc.sine", (0.0, 0.0));
+        let json = serde_json::to_string(&node).unwrap();
+        assert!(!json.contains("file"));
+
+        let mut player = NodeData::new(2, "input.midi_file", (0.0, 0.0));
+        player.file = Some(PathBuf::from("songs/bass.mid"));
+        let loaded: NodeData = serde_json::from_str(&serde_json::to_string(&player).unwrap()).unwrap();
+        assert_eq!(loaded.file, player.file);
+    }
+
+    #[test]
+    fn test_tuning_round_trip() {
+        let mut patch = Patch::new("Tuned");
+        let json = serde_json::to_string(&patch).unwrap();
+        assert!(!json.contains("tuning"));
+
+        let mut tuning = TuningData::new("pyth", "Pythagorean\n1\n3/2\n");
+        patch.tuning = Some(tuning.clone());
+        tuning.mapping = Some("0\n0\n127\n60\n69\n440.0\n0\n".to_string());
+        tuning.mapping_name = Some("a440".to_string());
+        let mut node = NodeData::new(1, "input.midi_note", (0.0, 0.0));
+        node.tuning = Some(tuning.clone());
+        patch.nodes.push(node);
+
+        let loaded: Patch = serde_json::from_str(&serde_json::to_string(&patch).unwrap()).unwrap();
+        assert_eq!(loaded.tuning, patch.tuning);
+        assert_eq!(loaded.nodes[0].tuning, Some(tuning));
+        assert_eq!(loaded.nodes[0].tuning.as_ref().unwrap().label(), "pyth · a440");
+    }
+
     #[test]
     fn test_version_compatibility() {
         let patch = Patch::new("Test");
@@ -318,6 +841,10 @@ mod tests {
             nodes: vec![],
             connections: vec![],
             midi_mappings: vec![],
+            metadata: PatchMetadata::default(),
+            scenes: vec![],
+            locks: vec![],
+            tuning: None,
         };
         assert!(!future_patch.is_compatible());
     }
@@ -330,4 +857,170 @@ mod tests {
         assert!((ParameterValue::Toggle(false).as_f32()).abs() < f32::EPSILON);
         assert!((ParameterValue::Select(2).as_f32() - 2.0).abs() < f32::EPSILON);
     }
+
+    #[test]
+    fn test_parameter_value_with_f32() {
+        assert_eq!(ParameterValue::Time(0.1).with_f32(0.5), ParameterValue::Time(0.5));
+        assert_eq!(ParameterValue::Toggle(false).with_f32(0.7), ParameterValue::Toggle(true));
+        assert_eq!(ParameterValue::Select(0).with_f32(2.4), ParameterValue::Select(2));
+    }
+
+    #[test]
+    fn test_midi_mapping_matches_channel_and_device() {
+        let omni = MidiMapping::new(74, 0, 1, 0, "Cutoff", 0.0, 1.0);
+        assert!(omni.matches(74, 0, Some("Keys")));
+        assert!(omni.matches(74, 15, None));
+        assert!(!omni.matches(75, 0, Some("Keys")));
+
+        let filtered = MidiMapping::new(74, 2, 1, 0, "Cutoff", 0.0, 1.0).with_device(Some("Knobs".to_string()));
+        assert!(filtered.matches(74, 1, Some("Knobs")));
+        assert!(!filtered.matches(74, 0, Some("Knobs")));
+        assert!(!filtered.matches(74, 1, Some("Keys")));
+        assert!(!filtered.matches(74, 1, None));
+
+        // Mappings saved before devices existed respond to any input
+        let json = r#"{"cc_number":1,"channel":0,"node_id":3,"param_index":0,
+            "param_name":"Level","min_value":0.0,"max_value":1.0}"#;
+        let loaded: MidiMapping = serde_json::from_str(json).unwrap();
+        assert_eq!(loaded.device, None);
+        assert_eq!(loaded.control, ControlKind::Cc);
+        assert_eq!(loaded.takeover, Takeover::Jump);
+    }
+
+    #[test]
+    fn test_midi_mapping_controls() {
+        let mut state = ControllerState::default();
+        let cc = |controller, value| ControlMessage::Cc { controller, value };
+
+        // 14-bit: the MSB clears the LSB, the LSB fills in the fine steps
+        let fine = MidiMapping::new(1, 0, 1, 0, "Level", 0.0, 16383.0).with_control(ControlKind::Cc14);
+        assert!(fine.matches(33, 0, None));
+        assert_eq!(fine.handle(cc(1, 64), 0, None, 0.0, &mut state), Some(8192.0));
+        assert_eq!(fine.handle(cc(33, 5), 0, None, 8192.0, &mut state), Some(8197.0));
+        assert_eq!(fine.handle(cc(2, 5), 0, None, 8197.0, &mut state), None);
+
+        // A 14-bit MSB must leave room for its LSB
+        let json = r#"{"cc_number":100,"channel":0,"node_id":3,"param_index":0,
+            "param_name":"Level","min_value":0.0,"max_value":1.0,"control":"Cc14"}"#;
+        assert!(serde_json::from_str::<MidiMapping>(json).is_err());
+        let overflow = MidiMapping::new(230, 0, 1, 0, "Level", 0.0, 1.0).with_control(ControlKind::Cc14);
+        assert!(!overflow.is_valid());
+        assert!(!overflow.matches(6, 0, None));
+        assert_eq!(overflow.control_label(), "CC 230");
+        assert!(overflow.feedback_messages(1.0).is_empty());
+
+        // The LSB of a pair clashes with a 7-bit CC on the same number
+        let lsb = MidiMapping::new(33, 0, 2, 0, "Level", 0.0, 1.0);
+        assert!(fine.shares_control(&lsb));
+        assert!(lsb.shares_control(&fine));
+        assert!(!fine.shares_control(&MidiMapping::new(34, 0, 2, 0, "Level", 0.0, 1.0)));
+
+        let nrpn = MidiMapping::new(0, 0, 1, 0, "Level", 0.0, 1.0).with_control(ControlKind::Nrpn(389));
+        let message = ControlMessage::ParameterNumber { registered: false, number: 389, value: 16383 };
+        assert_eq!(nrpn.handle(message, 0, None, 0.0, &mut ControllerState::default()), Some(1.0));
+        let rpn = ControlMessage::ParameterNumber { registered: true, number: 389, value: 16383 };
+        assert_eq!(nrpn.handle(rpn, 0, None, 0.0, &mut ControllerState::default()), None);
+        assert_eq!(nrpn.control_label(), "NRPN 389");
+
+        // Relative encoders step from wherever the parameter is
+        let encoder = MidiMapping::new(20, 0, 1, 0, "Level", 0.0, 127.0).with_encoder(EncoderMode::TwosComplement);
+        let value = encoder.handle(cc(20, 126), 0, None, 64.0, &mut state).unwrap();
+        assert!((value - 62.0).abs() < 1e-4);
+
+        // Inverted curves run from the top of the range
+        let mut inverted = MidiMapping::new(74, 0, 1, 0, "Level", 0.0, 1.0);
+        inverted.inverted = true;
+        assert_eq!(inverted.cc_to_value(127), 0.0);
+        assert!((inverted.value_to_position(0.25) - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_midi_mapping_buttons() {
+        let mut state = ControllerState::default();
+        let note = |velocity| ControlMessage::Note { note: 36, velocity };
+
+        // Notes are momentary unless told otherwise
+        let pad = MidiMapping::new(0, 0, 1, 0, "Gate", 0.0, 1.0).with_control(ControlKind::Note(36));
+        assert_eq!(pad.handle(note(100), 0, None, 0.0, &mut state), Some(1.0));
+        assert_eq!(pad.handle(note(0), 0, None, 1.0, &mut state), Some(0.0));
+        assert_eq!(pad.handle(ControlMessage::Note { note: 37, velocity: 100 }, 0, None, 0.0, &mut state), None);
+        assert_eq!(pad.control_label(), "Note 36");
+        let mut toggle = pad.clone();
+        toggle.set_options(MappingOptions { button: Some(ButtonMode::Toggle), ..pad.options() });
+        assert_eq!(pad.options().button, Some(ButtonMode::Momentary));
+        assert_eq!(toggle.handle(note(0), 0, None, 1.0, &mut state), None);
+
+        // A CC button cycling through the four choices of a discrete parameter
+        let cycle = MidiMapping::new(20, 0, 1, 0, "Wave", 0.0, 3.0).with_button(ButtonMode::Cycle);
+        let cc = |value| ControlMessage::Cc { controller: 20, value };
+        assert_eq!(cycle.handle(cc(127), 0, None, 3.0, &mut state), Some(0.0));
+        assert_eq!(cycle.handle(cc(0), 0, None, 0.0, &mut state), None);
+
+        // Action mappings leave parameters alone but still see presses
+        let panic = MidiMapping::for_action(MidiAction::Panic, ControlKind::Note(36), 0);
+        assert_eq!(panic.handle(note(100), 0, None, 0.0, &mut state), None);
+        assert_eq!(panic.pressed(note(100), 0, None), Some(true));
+
+        let json = serde_json::to_string(&panic).unwrap();
+        assert_eq!(serde_json::from_str::<MidiMapping>(&json).unwrap(), panic);
+        assert!(!serde_json::to_string(&pad).unwrap().contains("action"));
+    }
+
+    #[test]
+    fn test_midi_mapping_feedback() {
+        let cc = |controller, value| MidiEvent::control_change(0, 2, controller, value);
+
+        // A 7-bit CC on channel 3 replies where the controller would be
+        let mut cutoff = MidiMapping::new(74, 3, 1, 0, "Cutoff", 0.0, 100.0);
+        assert_eq!(cutoff.feedback_messages(50.0), vec![cc(74, 64)]);
+        cutoff.set_options(MappingOptions { inverted: true, feedback: true, ..cutoff.options() });
+        assert!(cutoff.feedback);
+        assert_eq!(cutoff.feedback_messages(100.0), vec![cc(74, 0)]);
+        assert_eq!(cutoff.feedback_messages(250.0), vec![cc(74, 0)]);
+
+        // 14-bit pairs and NRPNs carry the fine value
+        let fine = MidiMapping::new(1, 3, 1, 0, "Cutoff", 0.0, 1.0).with_control(ControlKind::Cc14);
+        assert_eq!(fine.feedback_messages(1.0), vec![cc(1, 127), cc(33, 127)]);
+        let nrpn = MidiMapping::new(0, 3, 1, 0, "Cutoff", 0.0, 1.0).with_control(ControlKind::Nrpn(389));
+        assert_eq!(nrpn.feedback_messages(0.0), vec![cc(99, 3), cc(98, 5), cc(6, 0), cc(38, 0)]);
+
+        // Buttons light up when on; omni mappings reply on channel 1
+        let pad = MidiMapping::new(0, 0, 1, 0, "Gate", 0.0, 1.0).with_control(ControlKind::Note(36));
+        assert_eq!(pad.feedback_messages(1.0), vec![MidiEvent::note_on(0, 0, 36, 127)]);
+        assert_eq!(pad.feedback_messages(0.0), vec![MidiEvent::note_on(0, 0, 36, 0)]);
+        assert!(MidiMapping::for_action(MidiAction::Panic, ControlKind::Cc, 20).feedback_messages(1.0).is_empty());
+
+        // Older patches have feedback off
+        let json = r#"{"cc_number":1,"channel":0,"node_id":1,"param_index":0,"param_name":"A","min_value":0.0,"max_value":1.0}"#;
+        assert!(!serde_json::from_str::<MidiMapping>(json).unwrap().feedback);
+    }
+
+    #[test]
+    fn test_extract_keeps_internal_connections() {
+        let mut patch = Patch::new("Test");
+        patch.nodes.push(NodeData::new(1, "osc.sine", (100.0, 50.0)));
+        patch.nodes.push(NodeData::new(2, "util.vca", (300.0, 80.0)));
+        patch.nodes.push(NodeData::new(3, "output.audio", (500.0, 80.0)));
+        patch.connections.push(ConnectionData::new(1, "Out", 2, "In"));
+        patch.connections.push(ConnectionData::new(2, "Out", 3, "Left"));
+        patch.midi_mappings.push(MidiMapping::new(74, 0, 1, 0, "Frequency", 20.0, 20000.0));
+
+        let fragment = patch.extract(&[1, 2]);
+
+        assert_eq!(fragment.nodes.len(), 2);
+        assert_eq!(fragment.connections.len(), 1);
+        assert_eq!(fragment.connections[0].from_node, 1);
+        assert_eq!(fragment.connections[0].to_node, 2);
+        assert!(fragment.midi_mappings.is_empty());
+    }
+
+    #[test]
+    fn test_origin() {
+        assert_eq!(Patch::new("Empty").origin(), (0.0, 0.0));
+
+        let mut patch = Patch::new("Test");
+        patch.nodes.push(NodeData::new(1, "osc.sine", (100.0, 50.0)));
+        patch.nodes.push(NodeData::new(2, "util.vca", (30.0, 80.0)));
+        assert_eq!(patch.origin(), (30.0, 50.0));
+    }
 }
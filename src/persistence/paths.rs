@@ -0,0 +1,96 @@
+//! Per-user application directories.
+//!
+//! Resolves where the synth keeps files that are not part of a patch,
+//! such as autosave/recovery data, the patch library, module presets,
+//! setlists, recordings and the application configuration, following each platform's
+//! conventions.
+
+use std::path::PathBuf;
+
+/// Directory name used under the platform data directory.
+const APP_DIR_NAME: &str = "modular_synth";
+
+/// Root directory for per-user application data.
+///
+/// - Linux: `$XDG_DATA_HOME/modular_synth` or `~/.local/share/modular_synth`
+/// - macOS: `~/Library/Application Support/modular_synth`
+/// - Windows: `%APPDATA%\modular_synth`
+///
+/// Falls back to the system temp directory if no home directory is known.
+pub fn data_dir() -> PathBuf {
+    platform_data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(APP_DIR_NAME)
+}
+
+/// Directory where autosaved patches are kept for crash recovery.
+pub fn recovery_dir() -> PathBuf {
+    data_dir().join("recovery")
+}
+
+/// Default directory indexed by the patch library.
+pub fn library_dir() -> PathBuf {
+    data_dir().join("patches")
+}
+
+/// Directory holding user module presets, one subdirectory per module type.
+pub fn presets_dir() -> PathBuf {
+    data_dir().join("presets")
+}
+
+/// Default directory for setlist files.
+pub fn setlists_dir() -> PathBuf {
+    data_dir().join("setlists")
+}
+
+/// Directory recordings are saved to.
+pub fn recordings_dir() -> PathBuf {
+    data_dir().join("recordings")
+}
+
+/// File holding patch library settings (library directory, favorites).
+pub fn library_settings_file() -> PathBuf {
+    data_dir().join("library.json")
+}
+
+/// File holding the application configuration (devices, window state).
+pub fn config_file() -> PathBuf {
+    data_dir().join("config.json")
+}
+
+#[cfg(target_os = "windows")]
+fn platform_data_dir() -> Option<PathBuf> {
+    std::env::var_os("APPDATA").map(PathBuf::from)
+}
+
+#[cfg(target_os = "macos")]
+fn platform_data_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join("Library").join("Application Support"))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn platform_data_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directories_are_namespaced() {
+        assert!(data_dir().ends_with(APP_DIR_NAME));
+        assert!(recovery_dir().starts_with(data_dir()));
+        assert!(library_dir().starts_with(data_dir()));
+        assert!(presets_dir().starts_with(data_dir()));
+        assert!(setlists_dir().starts_with(data_dir()));
+        assert!(recordings_dir().starts_with(data_dir()));
+        assert!(config_file().starts_with(data_dir()));
+    }
+}
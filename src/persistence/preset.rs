@@ -0,0 +1,312 @@
+//! Module presets.
+//!
+//! A preset stores the settings of a single node under a name, for one
+//! module type. Values are keyed by parameter ID rather than position, so
+//! presets keep working when a module gains or reorders parameters. Only
+//! parameters are stored, not a module's internal state. User presets are
+//! JSON files in one folder per module; a few factory presets
+//! ship with the synth.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::ParameterDefinition;
+use super::patch::{ParameterValue, PatchError};
+
+/// Named settings for one module type.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModulePreset {
+    /// Display name, unique per module type.
+    pub name: String,
+    /// Module type the preset belongs to (e.g. "fx.reverb").
+    pub module_id: String,
+    /// Parameter values keyed by parameter ID.
+    pub values: BTreeMap<String, f32>,
+    /// Whether the preset ships with the synth (and can't be deleted).
+    #[serde(skip)]
+    pub factory: bool,
+}
+
+impl ModulePreset {
+    /// Capture a node's parameter values.
+    pub fn capture(
+        name: impl Into<String>,
+        module_id: impl Into<String>,
+        parameters: &[ParameterValue],
+        definitions: &[ParameterDefinition],
+    ) -> Self {
+        let values = definitions
+            .iter()
+            .zip(parameters)
+            .map(|(definition, value)| (definition.id.to_string(), value.as_f32()))
+            .collect();
+
+        Self { name: name.into(), module_id: module_id.into(), values, factory: false }
+    }
+
+    /// The parameter values this preset sets on a node, as (index, value) pairs.
+    ///
+    /// Values are matched to parameters by ID and clamped to their current
+    /// range. Parameters the preset doesn't know keep their value, and
+    /// stored values for parameters that no longer exist are ignored.
+    pub fn apply(&self, current: &[ParameterValue], definitions: &[ParameterDefinition]) -> Vec<(usize, ParameterValue)> {
+        definitions
+            .iter()
+            .zip(current)
+            .enumerate()
+            .filter_map(|(index, (definition, value))| {
+                let stored = self.values.get(definition.id)?;
+                Some((index, value.with_f32(definition.clamp(*stored))))
+            })
+            .collect()
+    }
+
+    fn factory(name: &str, module_id: &str, values: &[(&str, f32)]) -> Self {
+        Self {
+            name: name.to_string(),
+            module_id: module_id.to_string(),
+            values: values.iter().map(|(id, value)| (id.to_string(), *value)).collect(),
+            factory: true,
+        }
+    }
+}
+
+/// Factory and user presets for every module type.
+pub struct PresetStore {
+    /// Directory holding one subdirectory of user presets per module type.
+    dir: PathBuf,
+    factory: Vec<ModulePreset>,
+    user: Vec<ModulePreset>,
+}
+
+impl PresetStore {
+    /// Open the store and read the user presets under `dir`.
+    ///
+    /// Files that can't be read are skipped.
+    pub fn open(dir: impl Into<PathBuf>) -> Self {
+        let mut store = Self { dir: dir.into(), factory: factory_presets(), user: Vec::new() };
+        store.rescan();
+        store
+    }
+
+    /// Re-read the user presets from disk.
+    pub fn rescan(&mut self) {
+        self.user.clear();
+        let Ok(module_dirs) = fs::read_dir(&self.dir) else {
+            return;
+        };
+
+        for module_dir in module_dirs.flatten().filter(|entry| entry.path().is_dir()) {
+            let Ok(files) = fs::read_dir(module_dir.path()) else {
+                continue;
+            };
+            for file in files.flatten() {
+                let path = file.path();
+                if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                    continue;
+                }
+                let preset = fs::read_to_string(&path)
+                    .ok()
+                    .and_then(|json| serde_json::from_str::<ModulePreset>(&json).ok());
+                if let Some(preset) = preset {
+                    self.user.push(preset);
+                }
+            }
+        }
+
+        self.user.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    }
+
+    /// Presets for a module type: factory presets first, then the user's, by name.
+    ///
+    /// Factory presets hidden by a user preset of the same name are left out.
+    pub fn presets(&self, module_id: &str) -> Vec<&ModulePreset> {
+        let user: Vec<&ModulePreset> = self.user.iter().filter(|preset| preset.module_id == module_id).collect();
+        self.factory
+            .iter()
+            .filter(|preset| preset.module_id == module_id)
+            .filter(|preset| !user.iter().any(|mine| mine.name == preset.name))
+            .chain(user.iter().copied())
+            .collect()
+    }
+
+    /// A preset by name; a user preset hides a factory preset of the same name.
+    pub fn find(&self, module_id: &str, name: &str) -> Option<&ModulePreset> {
+        let matches = |preset: &&ModulePreset| preset.module_id == module_id && preset.name == name;
+        self.user.iter().find(matches).or_else(|| self.factory.iter().find(matches))
+    }
+
+    /// Save a user preset, replacing one of the same name.
+    pub fn save(&mut self, mut preset: ModulePreset) -> Result<(), PatchError> {
+        preset.factory = false;
+        let path = self.preset_path(&preset.module_id, &preset.name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(&preset)?)?;
+
+        self.user.retain(|p| !(p.module_id == preset.module_id && p.name == preset.name));
+        self.user.push(preset);
+        self.user.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        Ok(())
+    }
+
+    /// Delete a user preset. Factory presets can't be deleted.
+    pub fn delete(&mut self, module_id: &str, name: &str) -> Result<(), PatchError> {
+        let path = self.preset_path(module_id, name);
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        self.user.retain(|p| !(p.module_id == module_id && p.name == name));
+        Ok(())
+    }
+
+    fn preset_path(&self, module_id: &str, name: &str) -> PathBuf {
+        self.dir.join(file_stem(module_id)).join(format!("{}.json", file_stem(name)))
+    }
+}
+
+/// A file name built from a display name, keeping only portable characters.
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.') { c } else { '_' })
+        .collect();
+    let stem = stem.trim_matches('.');
+    if stem.is_empty() { "preset".to_string() } else { stem.to_string() }
+}
+
+/// Presets shipped with the synth.
+pub fn factory_presets() -> Vec<ModulePreset> {
+    let mut presets = vec![
+        ModulePreset::factory("Small Room", "fx.reverb", &[
+            ("size", 0.3), ("decay", 0.6), ("damping", 0.6), ("predelay", 5.0), ("mix", 0.2), ("width", 0.8),
+        ]),
+        ModulePreset::factory("Large Hall", "fx.reverb", &[
+            ("size", 0.85), ("decay", 4.5), ("damping", 0.4), ("predelay", 25.0), ("mix", 0.35), ("width", 1.0),
+        ]),
+        ModulePreset::factory("Ambient Wash", "fx.reverb", &[
+            ("size", 1.0), ("decay", 15.0), ("damping", 0.3), ("predelay", 40.0), ("mix", 0.6), ("width", 1.0),
+        ]),
+        ModulePreset::factory("Gentle Glue", "fx.compressor", &[
+            ("threshold", -18.0), ("ratio", 2.0), ("attack", 30.0), ("release", 200.0),
+            ("knee", 6.0), ("makeup", 2.0), ("mix", 1.0),
+        ]),
+        ModulePreset::factory("Parallel Smash", "fx.compressor", &[
+            ("threshold", -30.0), ("ratio", 8.0), ("attack", 1.0), ("release", 80.0),
+            ("knee", 2.0), ("makeup", 8.0), ("mix", 0.5),
+        ]),
+        ModulePreset::factory("Limiter", "fx.compressor", &[
+            ("threshold", -6.0), ("ratio", 20.0), ("attack", 0.5), ("release", 50.0),
+            ("knee", 0.0), ("makeup", 0.0), ("mix", 1.0),
+        ]),
+    ];
+
+    presets.push(sequence_preset("Minor Arp", 8, &[57, 60, 64, 69, 72, 69, 64, 60], &[true; 8]));
+    presets.push(sequence_preset("Octave Pulse", 8, &[36, 48, 36, 48, 36, 48, 43, 55], &[true; 8]));
+    presets.push(sequence_preset(
+        "Offbeat Stabs",
+        16,
+        &[60; 16],
+        &[false, false, true, false, false, false, true, false, false, false, true, false, false, false, true, true],
+    ));
+    presets
+}
+
+/// A step sequencer preset playing `pitches` (MIDI notes) with the given gates.
+fn sequence_preset(name: &str, steps: usize, pitches: &[u8], gates: &[bool]) -> ModulePreset {
+    let mut preset = ModulePreset::factory(name, "seq.step", &[
+        ("steps", steps as f32), ("direction", 0.0), ("gate_length", 50.0),
+    ]);
+    for (i, (pitch, gate)) in pitches.iter().zip(gates).enumerate() {
+        preset.values.insert(format!("step_{}_pitch", i + 1), *pitch as f32);
+        preset.values.insert(format!("step_{}_gate", i + 1), if *gate { 1.0 } else { 0.0 });
+    }
+    preset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::create_module_registry;
+    use crate::test_util::TestDir;
+
+
+    #[test]
+    fn test_capture_and_apply_by_id() {
+        let definitions = vec![
+            ParameterDefinition::normalized("mix", "Mix", 0.5),
+            ParameterDefinition::normalized("size", "Size", 0.5),
+        ];
+        let values = vec![ParameterValue::Scalar(0.2), ParameterValue::Scalar(0.9)];
+        let preset = ModulePreset::capture("Mine", "fx.test", &values, &definitions);
+        assert_eq!(preset.values.get("size"), Some(&0.9));
+
+        // A newer module version reorders the parameters and adds one
+        let reordered = vec![
+            ParameterDefinition::normalized("size", "Size", 0.5),
+            ParameterDefinition::normalized("width", "Width", 1.0),
+            ParameterDefinition::normalized("mix", "Mix", 0.5),
+        ];
+        let current = vec![ParameterValue::Scalar(0.5); 3];
+        let applied = preset.apply(&current, &reordered);
+        assert_eq!(applied, vec![(0, ParameterValue::Scalar(0.9)), (2, ParameterValue::Scalar(0.2))]);
+    }
+
+    #[test]
+    fn test_apply_clamps_and_keeps_kind() {
+        let definitions = vec![ParameterDefinition::toggle("on", "On", false)];
+        let mut preset = ModulePreset::capture("Loud", "fx.test", &[], &[]);
+        preset.values.insert("on".to_string(), 7.0);
+
+        let applied = preset.apply(&[ParameterValue::Toggle(false)], &definitions);
+        assert_eq!(applied, vec![(0, ParameterValue::Toggle(true))]);
+    }
+
+    #[test]
+    fn test_factory_presets_match_modules() {
+        let registry = create_module_registry();
+        for preset in factory_presets() {
+            let module = registry.create(&preset.module_id).expect("factory preset for unknown module");
+            for (id, value) in &preset.values {
+                let definition = module.parameters()
+                    .iter()
+                    .find(|p| p.id == id)
+                    .unwrap_or_else(|| panic!("{}: unknown parameter {}", preset.name, id));
+                assert!((definition.min..=definition.max).contains(value), "{}: {} out of range", preset.name, id);
+            }
+        }
+    }
+
+    #[test]
+    fn test_save_find_and_delete() {
+        let dir = TestDir::new("presets_store");
+        let mut store = PresetStore::open(dir.path());
+        let factory_count = store.presets("fx.reverb").len();
+        assert!(factory_count > 0);
+
+        let mut preset = store.find("fx.reverb", "Large Hall").unwrap().clone();
+        assert!(preset.factory);
+        preset.values.insert("mix".to_string(), 0.9);
+        store.save(preset).unwrap();
+        store.save(ModulePreset::capture("My/Plate", "fx.reverb", &[], &[])).unwrap();
+
+        // Saved presets survive a reopen, and shadow factory presets of the same name
+        let mut store = PresetStore::open(dir.path());
+        let presets = store.presets("fx.reverb");
+        assert_eq!(presets.len(), factory_count + 1);
+        assert_eq!(presets.iter().filter(|preset| preset.name == "Large Hall").count(), 1);
+        let hall = store.find("fx.reverb", "Large Hall").unwrap();
+        assert!(!hall.factory);
+        assert_eq!(hall.values.get("mix"), Some(&0.9));
+        assert!(dir.join("fx.reverb").join("My_Plate.json").exists());
+
+        store.delete("fx.reverb", "Large Hall").unwrap();
+        assert!(store.find("fx.reverb", "Large Hall").unwrap().factory);
+        assert_eq!(store.presets("fx.reverb").len(), factory_count + 1);
+    }
+}
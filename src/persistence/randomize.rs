@@ -0,0 +1,286 @@
+//! Parameter randomization and mutation.
+//!
+//! Produces new parameter values for some or all nodes of a patch, either
+//! fully random or as a small variation of the current values. Ranges and
+//! scaling come from each module's [`ParameterDefinition`]s, so frequencies
+//! vary evenly in pitch and choices only take valid values. Parameters
+//! marked as not randomized (settings of live inputs and external gear,
+//! output levels, displays) keep their value. Results depend only on the
+//! seed, so a variation can be reproduced.
+
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::{ModuleRegistry, ParameterDefinition};
+use super::patch::{ParameterValue, Patch};
+use super::scene::SceneValue;
+
+/// A parameter excluded from randomization.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct ParameterLock {
+    /// Node ID (a patch ID on disk, an engine ID while the patch is loaded).
+    pub node_id: u64,
+    /// Parameter index within the node.
+    pub param_index: usize,
+}
+
+impl ParameterLock {
+    /// Create a lock for a parameter.
+    pub fn new(node_id: u64, param_index: usize) -> Self {
+        Self { node_id, param_index }
+    }
+}
+
+/// How to vary parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variation {
+    /// Pick every value anywhere in its range.
+    Randomize,
+    /// Move every value by up to `amount` (0.0-1.0) of its range. Discrete
+    /// values change with probability `amount`.
+    Mutate { amount: f32 },
+}
+
+/// Small seeded random number generator (xorshift64*).
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Create a generator; equal seeds give equal sequences.
+    pub fn new(seed: u64) -> Self {
+        // Scramble the seed so nearby seeds diverge; the state must not be zero
+        let state = (seed ^ 0x9E37_79B9_7F4A_7C15).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        Self { state: state.max(1) }
+    }
+
+    /// Next raw 64-bit value.
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform value in 0.0..1.0.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// New values for the unlocked parameters of the given nodes.
+///
+/// Only values that actually change are returned. Parameters whose
+/// definition is marked `not_randomized()` keep their value, and nodes of
+/// unknown modules are skipped.
+pub fn vary(
+    patch: &Patch,
+    node_ids: &[u64],
+    registry: &ModuleRegistry,
+    variation: Variation,
+    locks: &[ParameterLock],
+    seed: u64,
+) -> Vec<SceneValue> {
+    let mut rng = Rng::new(seed);
+    let mut values = Vec::new();
+
+    for node in patch.nodes.iter().filter(|node| node_ids.contains(&node.id)) {
+        let Some(module) = registry.create(&node.module_id) else {
+            continue;
+        };
+
+        for (param_index, (current, definition)) in node.parameters.iter().zip(module.parameters()).enumerate() {
+            if !definition.randomize {
+                continue;
+            }
+            // Draw even for locked parameters, so locking one doesn't reshuffle the rest
+            let value = vary_value(current, definition, variation, &mut rng);
+            if locks.contains(&ParameterLock::new(node.id, param_index)) || value == *current {
+                continue;
+            }
+            values.push(SceneValue { node_id: node.id, param_index, value });
+        }
+    }
+
+    values
+}
+
+/// Vary one value within its parameter's range.
+fn vary_value(
+    current: &ParameterValue,
+    definition: &ParameterDefinition,
+    variation: Variation,
+    rng: &mut Rng,
+) -> ParameterValue {
+    let (a, b) = (rng.next_f32(), rng.next_f32());
+
+    if definition.display.is_discrete() {
+        let steps = (definition.max - definition.min).round().max(0.0) as usize + 1;
+        let random_step = definition.min + ((a * steps as f32) as usize).min(steps - 1) as f32;
+        return match variation {
+            Variation::Mutate { amount } if b >= amount => current.clone(),
+            _ => current.with_f32(random_step),
+        };
+    }
+
+    let position = match variation {
+        Variation::Randomize => a,
+        Variation::Mutate { amount } => {
+            let offset = (a * 2.0 - 1.0) * amount;
+            if offset == 0.0 {
+                // Avoid rounding noise from the scaling round trip
+                return current.clone();
+            }
+            (to_position(current.as_f32(), definition) + offset).clamp(0.0, 1.0)
+        }
+    };
+    current.with_f32(from_position(position, definition))
+}
+
+/// Whether a parameter is scaled logarithmically.
+fn is_logarithmic(definition: &ParameterDefinition) -> bool {
+    definition.display.is_logarithmic() && definition.min > 0.0
+}
+
+/// Position of a value within its range (0.0-1.0), respecting the display scaling.
+fn to_position(value: f32, definition: &ParameterDefinition) -> f32 {
+    let value = definition.clamp(value);
+    if is_logarithmic(definition) {
+        (value / definition.min).ln() / (definition.max / definition.min).ln()
+    } else {
+        definition.normalize(value)
+    }
+}
+
+/// Value at a position within its range, the inverse of [`to_position`].
+fn from_position(position: f32, definition: &ParameterDefinition) -> f32 {
+    if is_logarithmic(definition) {
+        definition.min * (definition.max / definition.min).powf(position)
+    } else {
+        definition.denormalize(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::create_module_registry;
+    use crate::persistence::patch::NodeData;
+
+    fn sample_patch() -> Patch {
+        let mut patch = Patch::new("Random");
+        let mut osc = NodeData::new(1, "osc.sine", (0.0, 0.0));
+        osc.parameters = vec![
+            ParameterValue::Frequency(440.0),
+            ParameterValue::LinearRange(0.0),
+            ParameterValue::Select(0),
+            ParameterValue::LinearRange(0.5),
+        ];
+        let mut out = NodeData::new(2, "output.audio", (200.0, 0.0));
+        out.parameters = vec![ParameterValue::LinearRange(0.5)];
+        patch.nodes.push(osc);
+        patch.nodes.push(out);
+        patch
+    }
+
+    #[test]
+    fn test_rng_is_reproducible() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let mut c = Rng::new(43);
+        let first: Vec<u64> = (0..4).map(|_| a.next_u64()).collect();
+        assert_eq!(first, (0..4).map(|_| b.next_u64()).collect::<Vec<_>>());
+        assert_ne!(first, (0..4).map(|_| c.next_u64()).collect::<Vec<_>>());
+        assert!((0..1000).all(|_| (0.0..1.0).contains(&a.next_f32())));
+    }
+
+    #[test]
+    fn test_randomize_respects_ranges() {
+        let registry = create_module_registry();
+        let patch = sample_patch();
+
+        for seed in 0..50 {
+            let values = vary(&patch, &[1, 2], &registry, Variation::Randomize, &[], seed);
+            assert!(values.iter().all(|v| v.node_id == 1), "output level must stay fixed");
+            for value in &values {
+                match (value.param_index, &value.value) {
+                    (0, ParameterValue::Frequency(hz)) => assert!((20.0..=20000.0).contains(hz)),
+                    (2, ParameterValue::Select(index)) => assert!(*index <= 3),
+                    (1, ParameterValue::LinearRange(v)) => assert!((0.0..=1000.0).contains(v)),
+                    (3, ParameterValue::LinearRange(v)) => assert!((0.1..=0.9).contains(v)),
+                    other => panic!("unexpected value {:?}", other),
+                }
+            }
+        }
+
+        let a = vary(&patch, &[1], &registry, Variation::Randomize, &[], 7);
+        assert_eq!(a, vary(&patch, &[1], &registry, Variation::Randomize, &[], 7));
+    }
+
+    #[test]
+    fn test_randomize_frequency_is_logarithmic() {
+        let registry = create_module_registry();
+        let patch = sample_patch();
+
+        // On a log scale, about half the values land below the geometric middle (632 Hz)
+        let below = (0..200)
+            .filter_map(|seed| vary(&patch, &[1], &registry, Variation::Randomize, &[], seed).first().cloned())
+            .filter(|v| v.param_index == 0 && v.value.as_f32() < 632.0)
+            .count();
+        assert!((70..130).contains(&below), "{} of 200 below the middle", below);
+    }
+
+    #[test]
+    fn test_mutate_stays_close() {
+        let registry = create_module_registry();
+        let patch = sample_patch();
+
+        for seed in 0..50 {
+            let values = vary(&patch, &[1], &registry, Variation::Mutate { amount: 0.1 }, &[], seed);
+            let frequency = values.iter().find(|v| v.param_index == 0).map_or(440.0, |v| v.value.as_f32());
+            // 10% of the 20 Hz - 20 kHz log range is about one octave
+            assert!((220.0..=880.0).contains(&frequency), "{} Hz", frequency);
+        }
+
+        let unchanged = vary(&patch, &[1], &registry, Variation::Mutate { amount: 0.0 }, &[], 3);
+        assert!(unchanged.is_empty());
+    }
+
+    #[test]
+    fn test_clock_midi_settings_stay_fixed() {
+        let registry = create_module_registry();
+        let module = registry.create("util.clock").unwrap();
+        let mut patch = Patch::new("Clock");
+        let mut clock = NodeData::new(1, "util.clock", (0.0, 0.0));
+        clock.parameters = module.parameters().iter().map(|p| ParameterValue::Scalar(p.default)).collect();
+        patch.nodes.push(clock);
+
+        let fixed: Vec<usize> = module.parameters()
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.id == "midi_clock" || p.id == "source")
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(fixed.len(), 2);
+        for seed in 0..50 {
+            let values = vary(&patch, &[1], &registry, Variation::Randomize, &[], seed);
+            assert!(!values.is_empty());
+            assert!(values.iter().all(|v| !fixed.contains(&v.param_index)));
+        }
+    }
+
+    #[test]
+    fn test_locks() {
+        let registry = create_module_registry();
+        let patch = sample_patch();
+        let free = vary(&patch, &[1], &registry, Variation::Randomize, &[], 11);
+        let locked = vary(&patch, &[1], &registry, Variation::Randomize, &[ParameterLock::new(1, 0)], 11);
+
+        assert!(locked.iter().all(|v| v.param_index != 0));
+        // Locking one parameter leaves the others' values unchanged
+        let others: Vec<&SceneValue> = free.iter().filter(|v| v.param_index != 0).collect();
+        assert_eq!(others, locked.iter().collect::<Vec<_>>());
+    }
+}
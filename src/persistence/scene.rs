@@ -0,0 +1,206 @@
+//! Parameter scenes.
+//!
+//! A scene is a named snapshot of parameter values, taken from the whole
+//! patch or from a few of its nodes. Scenes can be recalled instantly or
+//! morphed between: continuous parameters are interpolated, while discrete
+//! ones (choices and toggles) switch over at the midpoint.
+
+use serde::{Deserialize, Serialize};
+
+use super::patch::{ParameterValue, Patch};
+
+/// One stored parameter value.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SceneValue {
+    /// Node ID (a patch ID on disk, an engine ID while the patch is loaded).
+    pub node_id: u64,
+    /// Parameter index within the node.
+    pub param_index: usize,
+    /// Stored value.
+    pub value: ParameterValue,
+}
+
+/// A named snapshot of parameter values.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Scene {
+    /// Display name.
+    pub name: String,
+    /// Stored values, in patch node order.
+    pub values: Vec<SceneValue>,
+}
+
+impl Scene {
+    /// Snapshot the parameters of the given nodes of a patch.
+    pub fn capture(name: impl Into<String>, patch: &Patch, node_ids: &[u64]) -> Self {
+        let values = patch.nodes
+            .iter()
+            .filter(|node| node_ids.contains(&node.id))
+            .flat_map(|node| {
+                node.parameters.iter().enumerate().map(|(param_index, value)| SceneValue {
+                    node_id: node.id,
+                    param_index,
+                    value: value.clone(),
+                })
+            })
+            .collect();
+
+        Self { name: name.into(), values }
+    }
+
+    /// The stored value of a parameter, if the scene has one.
+    pub fn get(&self, node_id: u64, param_index: usize) -> Option<&ParameterValue> {
+        self.values
+            .iter()
+            .find(|v| v.node_id == node_id && v.param_index == param_index)
+            .map(|v| &v.value)
+    }
+
+    /// Number of distinct nodes the scene stores values for.
+    pub fn node_count(&self) -> usize {
+        let mut nodes: Vec<u64> = self.values.iter().map(|v| v.node_id).collect();
+        nodes.sort_unstable();
+        nodes.dedup();
+        nodes.len()
+    }
+
+    /// Translate node IDs through `map`, dropping values whose node it doesn't know.
+    pub fn retarget(&self, map: impl Fn(u64) -> Option<u64>) -> Self {
+        let values = self.values
+            .iter()
+            .filter_map(|v| Some(SceneValue { node_id: map(v.node_id)?, ..v.clone() }))
+            .collect();
+
+        Self { name: self.name.clone(), values }
+    }
+}
+
+/// Interpolate between two parameter values at position `t` (0.0 = `a`, 1.0 = `b`).
+///
+/// Frequencies are interpolated logarithmically so a morph sweeps evenly in
+/// pitch. Discrete values, toggles, selections and mismatched value types
+/// switch from `a` to `b` at the midpoint.
+pub fn interpolate(a: &ParameterValue, b: &ParameterValue, t: f32, discrete: bool) -> ParameterValue {
+    let t = t.clamp(0.0, 1.0);
+    let lerp = |x: f32, y: f32| x + (y - x) * t;
+    let switch = || if t < 0.5 { a.clone() } else { b.clone() };
+
+    if discrete {
+        return switch();
+    }
+
+    match (a, b) {
+        (ParameterValue::Frequency(x), ParameterValue::Frequency(y)) if *x > 0.0 && *y > 0.0 => {
+            ParameterValue::Frequency(x * (y / x).powf(t))
+        }
+        (ParameterValue::Frequency(x), ParameterValue::Frequency(y)) => ParameterValue::Frequency(lerp(*x, *y)),
+        (ParameterValue::Scalar(x), ParameterValue::Scalar(y)) => ParameterValue::Scalar(lerp(*x, *y)),
+        (ParameterValue::LinearHz(x), ParameterValue::LinearHz(y)) => ParameterValue::LinearHz(lerp(*x, *y)),
+        (ParameterValue::Time(x), ParameterValue::Time(y)) => ParameterValue::Time(lerp(*x, *y)),
+        (ParameterValue::LinearRange(x), ParameterValue::LinearRange(y)) => {
+            ParameterValue::LinearRange(lerp(*x, *y))
+        }
+        _ => switch(),
+    }
+}
+
+/// The parameter values at position `t` of a morph from scene `a` to scene `b`.
+///
+/// Parameters stored in both scenes are interpolated; `is_discrete(node_id,
+/// param_index)` marks the ones that must switch at the midpoint instead.
+/// Parameters stored in only one scene keep that scene's value.
+pub fn morph(a: &Scene, b: &Scene, t: f32, is_discrete: impl Fn(u64, usize) -> bool) -> Vec<SceneValue> {
+    let mut values: Vec<SceneValue> = a.values
+        .iter()
+        .map(|from| {
+            let value = match b.get(from.node_id, from.param_index) {
+                Some(to) => interpolate(&from.value, to, t, is_discrete(from.node_id, from.param_index)),
+                None => from.value.clone(),
+            };
+            SceneValue { value, ..from.clone() }
+        })
+        .collect();
+
+    values.extend(
+        b.values
+            .iter()
+            .filter(|to| a.get(to.node_id, to.param_index).is_none())
+            .cloned(),
+    );
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::patch::NodeData;
+
+    fn scene(name: &str, cutoff: f32, mode: usize) -> Scene {
+        let mut patch = Patch::new("Scenes");
+        let mut filter = NodeData::new(4, "filter.svf", (0.0, 0.0));
+        filter.parameters = vec![ParameterValue::Frequency(cutoff), ParameterValue::Select(mode)];
+        let mut vca = NodeData::new(5, "util.vca", (200.0, 0.0));
+        vca.parameters = vec![ParameterValue::Scalar(0.5)];
+        patch.nodes.push(filter);
+        patch.nodes.push(vca);
+        Scene::capture(name, &patch, &[4])
+    }
+
+    #[test]
+    fn test_capture_selected_nodes() {
+        let scene = scene("A", 1000.0, 0);
+        assert_eq!(scene.values.len(), 2);
+        assert_eq!(scene.node_count(), 1);
+        assert_eq!(scene.get(4, 0), Some(&ParameterValue::Frequency(1000.0)));
+        assert_eq!(scene.get(5, 0), None);
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let a = ParameterValue::Scalar(0.0);
+        let b = ParameterValue::Scalar(1.0);
+        assert_eq!(interpolate(&a, &b, 0.25, false), ParameterValue::Scalar(0.25));
+        assert_eq!(interpolate(&a, &b, 2.0, false), ParameterValue::Scalar(1.0));
+
+        // Frequencies meet in the geometric middle
+        let mid = interpolate(&ParameterValue::Frequency(100.0), &ParameterValue::Frequency(400.0), 0.5, false);
+        assert!((mid.as_f32() - 200.0).abs() < 0.01);
+
+        // Discrete values switch at the midpoint
+        let on = ParameterValue::Toggle(true);
+        let off = ParameterValue::Toggle(false);
+        assert_eq!(interpolate(&off, &on, 0.49, false), off);
+        assert_eq!(interpolate(&off, &on, 0.5, false), on);
+        assert_eq!(interpolate(&a, &b, 0.3, true), a);
+    }
+
+    #[test]
+    fn test_morph() {
+        let a = scene("A", 100.0, 0);
+        let mut b = scene("B", 400.0, 2);
+        b.values.push(SceneValue { node_id: 7, param_index: 0, value: ParameterValue::Scalar(0.9) });
+
+        let values = morph(&a, &b, 0.75, |_, _| false);
+        assert_eq!(values.len(), 3);
+        assert!((values[0].value.as_f32() - 282.84).abs() < 0.1);
+        assert_eq!(values[1].value, ParameterValue::Select(2));
+        assert_eq!(values[2].value, ParameterValue::Scalar(0.9));
+
+        // A discrete flag overrides the value type
+        let values = morph(&a, &b, 0.25, |_, index| index == 0);
+        assert_eq!(values[0].value, ParameterValue::Frequency(100.0));
+    }
+
+    #[test]
+    fn test_retarget() {
+        let scene = scene("A", 100.0, 0).retarget(|id| (id == 4).then_some(40));
+        assert_eq!(scene.get(40, 1), Some(&ParameterValue::Select(0)));
+        assert!(scene.retarget(|_| None).values.is_empty());
+    }
+
+    #[test]
+    fn test_patches_without_scenes_still_load() {
+        let json = r#"{"name":"Old","version":2,"nodes":[],"connections":[]}"#;
+        let patch: Patch = serde_json::from_str(json).unwrap();
+        assert!(patch.scenes.is_empty());
+    }
+}
@@ -0,0 +1,291 @@
+//! Setlists for live use.
+//!
+//! A setlist is an ordered list of patches, or scenes within a patch, that
+//! MIDI program changes step through. Entry N answers program N of bank 0,
+//! entry 128 + N program N of bank 1, and so on, with the bank chosen by
+//! Bank Select (CC 0 and CC 32) before the program change. The next entry's
+//! patch is read in the background so switching to it doesn't wait on disk.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use super::patch::{Patch, PatchError};
+
+/// Number of programs in a bank.
+pub const PROGRAMS_PER_BANK: usize = 128;
+
+/// Controller number of Bank Select MSB (LSB is this plus 32).
+const BANK_SELECT: u8 = 0;
+
+/// One step of a setlist.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SetlistEntry {
+    /// Patch file to load.
+    pub path: PathBuf,
+    /// Scene to recall once the patch is loaded (None = the patch as saved).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scene: Option<usize>,
+}
+
+impl SetlistEntry {
+    /// An entry loading a patch, optionally recalling one of its scenes.
+    pub fn new(path: impl Into<PathBuf>, scene: Option<usize>) -> Self {
+        Self { path: path.into(), scene }
+    }
+
+    /// Display name: the patch file name, with the scene number if any.
+    pub fn label(&self) -> String {
+        let name = self.path.file_stem().and_then(|s| s.to_str()).unwrap_or("?");
+        match self.scene {
+            Some(scene) => format!("{} · scene {}", name, scene),
+            None => name.to_string(),
+        }
+    }
+}
+
+/// An ordered list of patches and scenes addressed by program number.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Setlist {
+    /// Setlist name.
+    pub name: String,
+    /// Entries, in program order.
+    pub entries: Vec<SetlistEntry>,
+}
+
+impl Setlist {
+    /// Load a setlist file.
+    pub fn load(path: &Path) -> Result<Self, PatchError> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Save the setlist to `path`, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), PatchError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Move an entry one place up (`up`) or down, keeping it in range.
+    ///
+    /// Returns the entry's new index.
+    pub fn move_entry(&mut self, index: usize, up: bool) -> usize {
+        let target = if up { index.saturating_sub(1) } else { index + 1 };
+        if index < self.entries.len() && target < self.entries.len() {
+            self.entries.swap(index, target);
+            target
+        } else {
+            index
+        }
+    }
+
+    /// Index of the entry after `index`, wrapping around.
+    pub fn next_index(&self, index: Option<usize>) -> Option<usize> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        Some(index.map_or(0, |index| (index + 1) % self.entries.len()))
+    }
+}
+
+/// Bank and program of a setlist index, as "bank:program".
+pub fn program_label(index: usize) -> String {
+    format!("{}:{:03}", index / PROGRAMS_PER_BANK, index % PROGRAMS_PER_BANK)
+}
+
+/// Tracks Bank Select per MIDI channel to number incoming program changes.
+#[derive(Debug, Clone, Default)]
+pub struct ProgramSelect {
+    /// Bank Select MSB and LSB per channel.
+    banks: [(u8, u8); 16],
+}
+
+impl ProgramSelect {
+    /// Create a tracker with every channel on bank 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note a control change, keeping it if it is a Bank Select.
+    pub fn control_change(&mut self, channel: u8, controller: u8, value: u8) {
+        let Some(bank) = self.banks.get_mut(channel as usize) else {
+            return;
+        };
+        if controller == BANK_SELECT {
+            bank.0 = value & 0x7F;
+        } else if controller == BANK_SELECT + 32 {
+            bank.1 = value & 0x7F;
+        }
+    }
+
+    /// The bank currently selected on a channel (MSB * 128 + LSB).
+    pub fn bank(&self, channel: u8) -> usize {
+        self.banks
+            .get(channel as usize)
+            .map_or(0, |&(msb, lsb)| ((msb as usize) << 7) | lsb as usize)
+    }
+
+    /// Setlist index addressed by a program change on a channel.
+    pub fn program_change(&self, channel: u8, program: u8) -> usize {
+        self.bank(channel) * PROGRAMS_PER_BANK + (program & 0x7F) as usize
+    }
+}
+
+/// A patch file being read on a background thread.
+struct Preload {
+    path: PathBuf,
+    /// Modification time of the file when reading started.
+    modified: Option<SystemTime>,
+    handle: JoinHandle<Result<Patch, PatchError>>,
+}
+
+/// Reads the patch a setlist will need next ahead of time.
+///
+/// Once read, the app builds the patch in the engine's scratch graph, so
+/// the switch itself is only a graph swap.
+#[derive(Default)]
+pub struct PatchPreloader {
+    preload: Option<Preload>,
+}
+
+impl PatchPreloader {
+    /// Create a preloader with nothing loaded.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start reading `path` with `read`, replacing any earlier preload.
+    ///
+    /// Does nothing if `path` is already being preloaded.
+    pub fn preload(&mut self, path: &Path, read: fn(&Path) -> Result<Patch, PatchError>) {
+        if self.preload.as_ref().is_some_and(|preload| preload.path == path) {
+            return;
+        }
+
+        let owned = path.to_path_buf();
+        self.preload = Some(Preload {
+            path: path.to_path_buf(),
+            modified: modified(path),
+            handle: std::thread::spawn(move || read(&owned)),
+        });
+    }
+
+    /// Take the preloaded patch for `path`, waiting for it if still reading.
+    ///
+    /// Returns None if a different file was preloaded, or the file changed
+    /// since it was read; the caller then reads it itself.
+    pub fn take(&mut self, path: &Path) -> Option<Result<Patch, PatchError>> {
+        if self.preload.as_ref()?.path != path {
+            return None;
+        }
+
+        let preload = self.preload.take()?;
+        let result = preload.handle.join().ok()?;
+        (modified(path) == preload.modified).then_some(result)
+    }
+
+    /// Path of the preload, once its file has been read.
+    pub fn finished(&self) -> Option<&Path> {
+        let preload = self.preload.as_ref()?;
+        preload.handle.is_finished().then_some(preload.path.as_path())
+    }
+
+    /// Whether a file is still being read.
+    pub fn is_reading(&self) -> bool {
+        self.preload.as_ref().is_some_and(|preload| !preload.handle.is_finished())
+    }
+
+    /// Drop any preload.
+    pub fn clear(&mut self) {
+        self.preload = None;
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::patch::{load_from_file, save_to_file};
+    use crate::test_util::TestDir;
+
+    #[test]
+    fn test_setlist_round_trip() {
+        let dir = TestDir::new("setlist_round_trip");
+        fs::create_dir_all(dir.path()).unwrap();
+        let path = dir.join("gig.json");
+        let setlist = Setlist {
+            name: "Gig".to_string(),
+            entries: vec![
+                SetlistEntry::new("/patches/intro.json", None),
+                SetlistEntry::new("/patches/song.json", Some(2)),
+            ],
+        };
+        setlist.save(&path).unwrap();
+        assert_eq!(Setlist::load(&path).unwrap(), setlist);
+        assert_eq!(setlist.entries[1].label(), "song · scene 2");
+    }
+
+    #[test]
+    fn test_move_and_next() {
+        let mut setlist = Setlist {
+            name: String::new(),
+            entries: (0..3).map(|i| SetlistEntry::new(format!("{}.json", i), None)).collect(),
+        };
+        assert_eq!(setlist.move_entry(0, false), 1);
+        assert_eq!(setlist.entries[1].path, PathBuf::from("0.json"));
+        assert_eq!(setlist.move_entry(0, true), 0);
+        assert_eq!(setlist.move_entry(2, false), 2);
+
+        assert_eq!(setlist.next_index(None), Some(0));
+        assert_eq!(setlist.next_index(Some(2)), Some(0));
+        assert_eq!(Setlist::default().next_index(Some(0)), None);
+    }
+
+    #[test]
+    fn test_program_select() {
+        let mut select = ProgramSelect::new();
+        assert_eq!(select.program_change(0, 5), 5);
+
+        // Bank 1:2 on channel 3 only
+        select.control_change(3, 0, 1);
+        select.control_change(3, 32, 2);
+        select.control_change(3, 7, 100);
+        assert_eq!(select.bank(3), 130);
+        assert_eq!(select.program_change(3, 5), 130 * 128 + 5);
+        assert_eq!(select.program_change(0, 5), 5);
+
+        select.control_change(3, 0, 0);
+        select.control_change(3, 32, 1);
+        assert_eq!(select.program_change(3, 0), 128);
+        assert_eq!(program_label(128 + 7), "1:007");
+    }
+
+    #[test]
+    fn test_preloader() {
+        let dir = TestDir::new("setlist_preload");
+        fs::create_dir_all(dir.path()).unwrap();
+        let path = dir.join("next.json");
+        save_to_file(&Patch::new("Next"), &path).unwrap();
+
+        let mut preloader = PatchPreloader::new();
+        assert!(preloader.finished().is_none());
+        preloader.preload(&path, load_from_file);
+        while preloader.is_reading() {
+            std::thread::yield_now();
+        }
+        assert_eq!(preloader.finished(), Some(path.as_path()));
+        assert!(preloader.take(&dir.join("other.json")).is_none());
+        assert_eq!(preloader.take(&path).unwrap().unwrap().name, "Next");
+        // Taken once only
+        assert!(preloader.take(&path).is_none());
+    }
+}
@@ -0,0 +1,39 @@
+//! Test helpers shared across modules.
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// A directory under the system temp directory for one test.
+///
+/// Anything left over from an earlier run is removed first, and the
+/// directory is removed again when the value is dropped, even if the test
+/// panics. The directory itself is not created.
+pub struct TestDir(PathBuf);
+
+impl TestDir {
+    /// `name` should be unique across the crate's tests.
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("modular_synth_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
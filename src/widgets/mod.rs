@@ -2,7 +2,7 @@
 //!
 //! Custom UI controls for the synthesizer interface.
 //! Includes knobs, faders, waveform displays, spectrum displays, LED indicators, CPU meters,
-//! and specialized displays for envelopes and other module types.
+//! VU meters, patch thumbnails, and specialized displays for envelopes and other module types.
 
 pub mod knob;
 pub mod fader;
@@ -13,9 +13,8 @@ pub mod cpu_meter;
 pub mod oscilloscope_display;
 pub mod adsr_display;
 pub mod piano;
-
-// Future submodules:
-// pub mod vu_meter;
+pub mod patch_thumbnail;
+pub mod vu_meter;
 
 // Re-export commonly used items
 pub use knob::{knob, mini_knob, KnobConfig, ParamFormat};
@@ -33,3 +32,5 @@ pub use cpu_meter::{cpu_meter, CpuMeterConfig, cpu_load_color};
 pub use oscilloscope_display::{oscilloscope_display, OscilloscopeConfig, TriggerMode};
 pub use adsr_display::{adsr_display, AdsrConfig, AdsrParams, generate_adsr_curve, get_adsr_segment_boundaries};
 pub use piano::{piano, PianoConfig, PianoData};
+pub use patch_thumbnail::patch_thumbnail;
+pub use vu_meter::{vu_meter, VuMeterConfig};
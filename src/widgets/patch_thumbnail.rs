@@ -0,0 +1,49 @@
+//! Patch thumbnail widget.
+//!
+//! Draws a miniature of a patch's node graph from its stored thumbnail,
+//! so patches can be previewed in the library without loading them.
+
+use eframe::egui::{self, Color32, Pos2, Response, Sense, Stroke, Ui, Vec2};
+
+use crate::app::theme;
+use crate::persistence::PatchThumbnail;
+
+/// Size of a node box in the thumbnail, in pixels.
+const NODE_SIZE: Vec2 = Vec2::new(8.0, 6.0);
+
+/// Draws a patch thumbnail of the given size.
+///
+/// An empty box is drawn for patches without a thumbnail.
+pub fn patch_thumbnail(ui: &mut Ui, thumbnail: Option<&PatchThumbnail>, size: Vec2) -> Response {
+    let (rect, response) = ui.allocate_exact_size(size, Sense::hover());
+
+    if !ui.is_rect_visible(rect) {
+        return response;
+    }
+
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 3.0, theme::background::MAIN);
+
+    let Some(thumbnail) = thumbnail else {
+        return response;
+    };
+
+    // Keep node boxes fully inside the frame
+    let inner = rect.shrink2(NODE_SIZE * 0.5 + Vec2::splat(3.0));
+    let to_screen = |(x, y): (f32, f32)| Pos2::new(inner.left() + x * inner.width(), inner.top() + y * inner.height());
+
+    let cable = Stroke::new(1.0, theme::text::DISABLED);
+    for &(from, to) in &thumbnail.connections {
+        if let (Some(&from), Some(&to)) = (thumbnail.nodes.get(from), thumbnail.nodes.get(to)) {
+            painter.line_segment([to_screen(from), to_screen(to)], cable);
+        }
+    }
+
+    for &position in &thumbnail.nodes {
+        let node_rect = egui::Rect::from_center_size(to_screen(position), NODE_SIZE);
+        painter.rect_filled(node_rect, 1.0, theme::accent::PRIMARY);
+        painter.rect_stroke(node_rect, 1.0, Stroke::new(1.0, Color32::from_black_alpha(120)));
+    }
+
+    response
+}
@@ -0,0 +1,143 @@
+//! VU meter widget for displaying signal levels.
+//!
+//! Draws one thin horizontal bar per channel, stacked vertically, on a
+//! decibel scale. Used to meter the audio input in the toolbar.
+
+use eframe::egui::{self, Color32, Rect, Response, Sense, Ui, Vec2};
+
+use crate::app::theme;
+
+/// Configuration for the VU meter widget.
+#[derive(Clone, Debug)]
+pub struct VuMeterConfig {
+    /// Width of the bars in pixels.
+    pub width: f32,
+    /// Total height of all bars in pixels.
+    pub height: f32,
+    /// Level shown as an empty bar, in dB.
+    pub floor_db: f32,
+    /// Level above which the bar turns amber, in dB.
+    pub warning_db: f32,
+    /// Level above which the bar turns red, in dB.
+    pub clip_db: f32,
+}
+
+impl Default for VuMeterConfig {
+    fn default() -> Self {
+        Self {
+            width: 60.0,
+            height: 12.0,
+            floor_db: -60.0,
+            warning_db: -12.0,
+            clip_db: -0.5,
+        }
+    }
+}
+
+impl VuMeterConfig {
+    /// Creates a compact VU meter for toolbars.
+    pub fn compact() -> Self {
+        Self {
+            width: 40.0,
+            height: 10.0,
+            ..Default::default()
+        }
+    }
+}
+
+/// Converts a linear peak level to decibels (silence maps to negative infinity).
+pub fn level_to_db(level: f32) -> f32 {
+    20.0 * level.abs().log10()
+}
+
+/// Returns the bar fill (0.0-1.0) for a linear peak level.
+pub fn level_fill(level: f32, config: &VuMeterConfig) -> f32 {
+    ((level_to_db(level) - config.floor_db) / -config.floor_db).clamp(0.0, 1.0)
+}
+
+/// Returns the color for a linear peak level.
+pub fn level_color(level: f32, config: &VuMeterConfig) -> Color32 {
+    let db = level_to_db(level);
+    if db >= config.clip_db {
+        theme::accent::ERROR
+    } else if db >= config.warning_db {
+        theme::accent::WARNING
+    } else {
+        theme::accent::SUCCESS
+    }
+}
+
+/// Draws a VU meter widget.
+///
+/// # Arguments
+/// * `ui` - The egui UI to draw into
+/// * `levels` - Peak level per channel (linear, 1.0 = full scale)
+/// * `config` - Configuration for the meter appearance
+///
+/// # Returns
+/// The response from the meter widget
+pub fn vu_meter(ui: &mut Ui, levels: &[f32], config: &VuMeterConfig) -> Response {
+    let (rect, response) = ui.allocate_exact_size(
+        Vec2::new(config.width, config.height),
+        Sense::hover(),
+    );
+
+    if ui.is_rect_visible(rect) && !levels.is_empty() {
+        let painter = ui.painter();
+        painter.rect_filled(rect, 2.0, theme::background::WIDGET);
+
+        let bar_height = rect.height() / levels.len() as f32;
+        for (channel, &level) in levels.iter().enumerate() {
+            let fill = level_fill(level, config);
+            if fill > 0.0 {
+                let bar = Rect::from_min_size(
+                    egui::pos2(rect.min.x, rect.min.y + channel as f32 * bar_height),
+                    Vec2::new(fill * rect.width(), (bar_height - 1.0).max(1.0)),
+                );
+                painter.rect_filled(bar, 1.0, level_color(level, config));
+            }
+        }
+
+        painter.rect_stroke(
+            rect,
+            2.0,
+            egui::Stroke::new(1.0, theme::background::WIDGET_HOVERED),
+        );
+    }
+
+    let readout: Vec<String> = levels
+        .iter()
+        .enumerate()
+        .map(|(channel, &level)| {
+            let db = level_to_db(level);
+            if db > config.floor_db {
+                format!("{}: {:.1} dB", channel + 1, db)
+            } else {
+                format!("{}: -inf", channel + 1)
+            }
+        })
+        .collect();
+    response.on_hover_text(readout.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_fill() {
+        let config = VuMeterConfig::default();
+        assert_eq!(level_fill(0.0, &config), 0.0);
+        assert_eq!(level_fill(1.0, &config), 1.0);
+        assert!((level_fill(0.001, &config) - 0.5).abs() < 1e-4);
+        assert_eq!(level_fill(2.0, &config), 1.0);
+    }
+
+    #[test]
+    fn test_level_color() {
+        let config = VuMeterConfig::default();
+        assert_eq!(level_color(0.1, &config), theme::accent::SUCCESS);
+        assert_eq!(level_color(0.5, &config), theme::accent::WARNING);
+        assert_eq!(level_color(1.0, &config), theme::accent::ERROR);
+    }
+}